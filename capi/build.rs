@@ -0,0 +1,27 @@
+// generates the c header from the extern functions in src/lib.rs into OUT_DIR, where the
+// tests check that the committed include/feuernes.h still matches it. after changing the
+// api, refresh the committed copy with
+//
+//     FEUERNES_UPDATE_HEADER=1 cargo build
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=FEUERNES_UPDATE_HEADER");
+
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&dir)
+        .with_config(config)
+        .generate()
+        .expect("generating feuernes.h");
+    bindings.write_to_file(out.join("feuernes.h"));
+    if env::var_os("FEUERNES_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(dir.join("include/feuernes.h"));
+    }
+}
@@ -0,0 +1,98 @@
+/*
+    feuernes, generated from src/lib.rs by cbindgen, do not edit
+
+    functions returning int give 0 on success and -1 on failure, feuernes_last_error says
+    why. a handle is not safe to use from two threads at once. frames are rgba, row by row,
+    sound is mono float at FEUERNES_SAMPLE_RATE
+*/
+
+#ifndef FEUERNES_H
+#define FEUERNES_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Of the picture feuernes_framebuffer gives, in pixels.
+#define FEUERNES_WIDTH 256
+
+#define FEUERNES_HEIGHT 240
+
+// Of the samples feuernes_audio_pull gives.
+#define FEUERNES_SAMPLE_RATE 44100
+
+// For feuernes_set_button.
+#define FEUERNES_BUTTON_A 1
+
+#define FEUERNES_BUTTON_B 2
+
+#define FEUERNES_BUTTON_SELECT 4
+
+#define FEUERNES_BUTTON_START 8
+
+#define FEUERNES_BUTTON_UP 16
+
+#define FEUERNES_BUTTON_DOWN 32
+
+#define FEUERNES_BUTTON_LEFT 64
+
+#define FEUERNES_BUTTON_RIGHT 128
+
+// One console, made by feuernes_create.
+typedef struct FeuerNes FeuerNes;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// A handle without a rom, free it with feuernes_destroy. Null if it could not be made.
+struct FeuerNes *feuernes_create(void);
+
+// Frees a handle from feuernes_create, null is ignored.
+void feuernes_destroy(struct FeuerNes *nes);
+
+// Power-cycles the console with a copy of the iNES image at `rom`. NES 2.0 headers are
+// read for their iNES fields only. On failure the console keeps the rom it had.
+//
+// # Safety
+// `rom` is null or points to `len` readable bytes.
+int feuernes_load_rom(struct FeuerNes *nes, const uint8_t *rom, size_t len);
+
+// The reset button.
+int feuernes_reset(struct FeuerNes *nes);
+
+// Runs the console until it finishes the next picture.
+int feuernes_run_frame(struct FeuerNes *nes);
+
+// The last frame as FEUERNES_WIDTH * FEUERNES_HEIGHT rgba pixels, row by row. It stays
+// valid until the next call taking the handle. Null before a rom is loaded.
+//
+// # Safety
+// `len` is null or points to a writable size_t, which gets the length in bytes.
+const uint8_t *feuernes_framebuffer(struct FeuerNes *nes, size_t *len);
+
+// Presses or releases one of the FEUERNES_BUTTON_ flags on port 1 or 2, from the next
+// frame on.
+int feuernes_set_button(struct FeuerNes *nes, int port, uint8_t button, bool pressed);
+
+// A byte of the cpu's address space without side effects, 0 before a rom is loaded.
+uint8_t feuernes_peek(const struct FeuerNes *nes, uint16_t addr);
+
+// Moves up to `len` samples of the frames run so far into `out` and returns how many.
+// They are mono, at FEUERNES_SAMPLE_RATE.
+//
+// # Safety
+// `out` is null or points to `len` writable floats.
+size_t feuernes_audio_pull(struct FeuerNes *nes, float *out, size_t len);
+
+// Why the last failing call failed, empty when none has. It stays valid until the next
+// call taking the handle.
+const char *feuernes_last_error(const struct FeuerNes *nes);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FEUERNES_H */
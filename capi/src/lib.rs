@@ -0,0 +1,375 @@
+// a c api over the headless core. a handle owns one console, the host drives it a frame
+// at a time and copies the picture and sound out after each one:
+//
+//     FeuerNes *nes = feuernes_create();
+//     if (feuernes_load_rom(nes, rom, rom_len) != 0) {
+//         puts(feuernes_last_error(nes));
+//     }
+//     feuernes_set_button(nes, 1, FEUERNES_BUTTON_START, true);
+//     feuernes_run_frame(nes);
+//     size_t len;
+//     const uint8_t *rgba = feuernes_framebuffer(nes, &len);
+//     size_t samples = feuernes_audio_pull(nes, buffer, 1024);
+//     feuernes_destroy(nes);
+//
+// functions returning int give 0 on success and -1 on failure, feuernes_last_error says
+// why. a handle is not safe to use from two threads at once
+
+use std::any::Any;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::Arc;
+
+use feuernes_core::audio::ring_buffer::AudioRingBuffer;
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::joypad::{JoypadButton, Port};
+use feuernes_core::mem::Memory;
+use feuernes_core::nes::Nes;
+use feuernes_core::sync::AtomicRefCell;
+
+/// Of the picture feuernes_framebuffer gives, in pixels.
+pub const FEUERNES_WIDTH: usize = 256;
+pub const FEUERNES_HEIGHT: usize = 240;
+/// Of the samples feuernes_audio_pull gives.
+pub const FEUERNES_SAMPLE_RATE: u32 = 44100;
+
+/// For feuernes_set_button.
+pub const FEUERNES_BUTTON_A: u8 = 0b0000_0001;
+pub const FEUERNES_BUTTON_B: u8 = 0b0000_0010;
+pub const FEUERNES_BUTTON_SELECT: u8 = 0b0000_0100;
+pub const FEUERNES_BUTTON_START: u8 = 0b0000_1000;
+pub const FEUERNES_BUTTON_UP: u8 = 0b0001_0000;
+pub const FEUERNES_BUTTON_DOWN: u8 = 0b0010_0000;
+pub const FEUERNES_BUTTON_LEFT: u8 = 0b0100_0000;
+pub const FEUERNES_BUTTON_RIGHT: u8 = 0b1000_0000;
+
+/// One console, made by feuernes_create.
+pub struct FeuerNes {
+    // None until a rom is loaded
+    nes: Option<Nes>,
+    // what each port holds, set_button changes one button at a time
+    buttons: [JoypadButton; 2],
+    // filled by the console's audio callback, emptied by feuernes_audio_pull. a second of
+    // sound, older samples are dropped when the host does not pull
+    audio: Arc<AtomicRefCell<AudioRingBuffer>>,
+    error: CString,
+}
+
+impl FeuerNes {
+    fn new() -> Self {
+        FeuerNes {
+            nes: None,
+            buttons: [JoypadButton::empty(); 2],
+            audio: Arc::new(AtomicRefCell::new(AudioRingBuffer::new(
+                FEUERNES_SAMPLE_RATE as usize,
+            ))),
+            error: CString::default(),
+        }
+    }
+
+    fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        let cartridge = Cartridge::new(rom)?;
+        match self.nes.as_mut() {
+            // keeps the audio callback
+            Some(nes) => nes.load_cartridge(cartridge),
+            None => {
+                let mut nes = Nes::new(cartridge);
+                let audio = self.audio.clone();
+                nes.set_audio_callback(move |samples| audio.borrow_mut_spin().push(samples));
+                self.nes = Some(nes);
+            }
+        }
+        self.buttons = [JoypadButton::empty(); 2];
+        self.nes.as_mut().unwrap().reset();
+        Ok(())
+    }
+
+    fn nes(&mut self) -> Result<&mut Nes, String> {
+        self.nes.as_mut().ok_or_else(|| "no rom loaded".to_string())
+    }
+
+    fn set_button(&mut self, port: Port, button: JoypadButton, pressed: bool) {
+        let buttons = &mut self.buttons[port as usize];
+        buttons.set(button, pressed);
+        let buttons = *buttons;
+        if let Some(nes) = self.nes.as_mut() {
+            let frame = nes.stats().frames;
+            nes.queue_input(frame, port, buttons);
+        }
+    }
+
+    // up to out.len() samples, fewer when that many have not been made yet
+    fn audio_pull(&mut self, out: &mut [f32]) -> usize {
+        let mut audio = self.audio.borrow_mut_spin();
+        let count = audio.len().min(out.len());
+        audio.pop_into(&mut out[..count]);
+        count
+    }
+
+    // 0 or -1, keeping the message for feuernes_last_error
+    fn status(&mut self, result: Result<(), String>) -> c_int {
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                // a message with a nul in it is cut there
+                let end = e.find('\0').unwrap_or(e.len());
+                self.error = CString::new(&e[..end]).unwrap();
+                -1
+            }
+        }
+    }
+}
+
+// ports are numbered like on the console
+fn parse_port(port: c_int) -> Result<Port, String> {
+    match port {
+        1 => Ok(Port::One),
+        2 => Ok(Port::Two),
+        _ => Err(format!("no controller port {}", port)),
+    }
+}
+
+// nothing may unwind into c, a panic gives `on_panic` instead
+fn guard<T, F: FnOnce() -> T>(on_panic: T, body: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(on_panic)
+}
+
+// the body of the functions returning int. a panic is reported like any other failure,
+// and the console is dropped since it may have been left halfway through a step
+fn call<F>(nes: Option<&mut FeuerNes>, body: F) -> c_int
+where
+    F: FnOnce(&mut FeuerNes) -> Result<(), String>,
+{
+    let nes = match nes {
+        Some(nes) => nes,
+        None => return -1,
+    };
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(|| body(&mut *nes))).unwrap_or_else(|panic| {
+            nes.nes = None;
+            Err(format!("the emulator panicked: {}", panic_message(&*panic)))
+        });
+    nes.status(result)
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map_or("unknown", String::as_str),
+    }
+}
+
+/// A handle without a rom, free it with feuernes_destroy. Null if it could not be made.
+#[no_mangle]
+pub extern "C" fn feuernes_create() -> Option<Box<FeuerNes>> {
+    guard(None, || Some(Box::new(FeuerNes::new())))
+}
+
+/// Frees a handle from feuernes_create, null is ignored.
+#[no_mangle]
+pub extern "C" fn feuernes_destroy(nes: Option<Box<FeuerNes>>) {
+    guard((), || drop(nes))
+}
+
+/// Power-cycles the console with a copy of the iNES image at `rom`. NES 2.0 headers are
+/// read for their iNES fields only. On failure the console keeps the rom it had.
+///
+/// # Safety
+/// `rom` is null or points to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn feuernes_load_rom(
+    nes: Option<&mut FeuerNes>,
+    rom: *const u8,
+    len: usize,
+) -> c_int {
+    call(nes, |nes| {
+        if rom.is_null() {
+            return Err("no rom".to_string());
+        }
+        nes.load_rom(slice::from_raw_parts(rom, len))
+    })
+}
+
+/// The reset button.
+#[no_mangle]
+pub extern "C" fn feuernes_reset(nes: Option<&mut FeuerNes>) -> c_int {
+    call(nes, |nes| nes.nes().map(|nes| nes.reset()))
+}
+
+/// Runs the console until it finishes the next picture.
+#[no_mangle]
+pub extern "C" fn feuernes_run_frame(nes: Option<&mut FeuerNes>) -> c_int {
+    call(nes, |nes| nes.nes().map(|nes| nes.run_frame()))
+}
+
+/// The last frame as FEUERNES_WIDTH * FEUERNES_HEIGHT rgba pixels, row by row. It stays
+/// valid until the next call taking the handle. Null before a rom is loaded.
+///
+/// # Safety
+/// `len` is null or points to a writable size_t, which gets the length in bytes.
+#[no_mangle]
+pub unsafe extern "C" fn feuernes_framebuffer(
+    nes: Option<&mut FeuerNes>,
+    len: *mut usize,
+) -> *const u8 {
+    let data = guard(&[][..], || match nes.and_then(|nes| nes.nes.as_ref()) {
+        Some(nes) => &nes.frame().data[..],
+        None => &[][..],
+    });
+    if !len.is_null() {
+        *len = data.len();
+    }
+    if data.is_empty() {
+        std::ptr::null()
+    } else {
+        data.as_ptr()
+    }
+}
+
+/// Presses or releases one of the FEUERNES_BUTTON_ flags on port 1 or 2, from the next
+/// frame on.
+#[no_mangle]
+pub extern "C" fn feuernes_set_button(
+    nes: Option<&mut FeuerNes>,
+    port: c_int,
+    button: u8,
+    pressed: bool,
+) -> c_int {
+    call(nes, |nes| {
+        let port = parse_port(port)?;
+        nes.set_button(port, JoypadButton::from_bits_truncate(button), pressed);
+        Ok(())
+    })
+}
+
+/// A byte of the cpu's address space without side effects, 0 before a rom is loaded.
+#[no_mangle]
+pub extern "C" fn feuernes_peek(nes: Option<&FeuerNes>, addr: u16) -> u8 {
+    guard(0, || {
+        nes.and_then(|nes| nes.nes.as_ref())
+            .map_or(0, |nes| nes.cpu.mem_peek(addr))
+    })
+}
+
+/// Moves up to `len` samples of the frames run so far into `out` and returns how many.
+/// They are mono, at FEUERNES_SAMPLE_RATE.
+///
+/// # Safety
+/// `out` is null or points to `len` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn feuernes_audio_pull(
+    nes: Option<&mut FeuerNes>,
+    out: *mut f32,
+    len: usize,
+) -> usize {
+    guard(0, || match nes {
+        Some(nes) if !out.is_null() => nes.audio_pull(slice::from_raw_parts_mut(out, len)),
+        _ => 0,
+    })
+}
+
+/// Why the last failing call failed, empty when none has. It stays valid until the next
+/// call taking the handle.
+#[no_mangle]
+pub extern "C" fn feuernes_last_error(nes: Option<&FeuerNes>) -> *const c_char {
+    match nes {
+        Some(nes) => nes.error.as_ptr(),
+        None => b"no handle\0".as_ptr() as *const c_char,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use feuernes_core::apu::SAMPLE_RATE;
+    use feuernes_core::render::frame::{SCREEN_HEIGHT, SCREEN_WIDTH};
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_c_api() {
+        assert_eq!(
+            (FEUERNES_WIDTH, FEUERNES_HEIGHT, FEUERNES_SAMPLE_RATE),
+            (SCREEN_WIDTH, SCREEN_HEIGHT, SAMPLE_RATE)
+        );
+        assert_eq!(FEUERNES_BUTTON_A, JoypadButton::BUTTON_A.bits());
+        assert_eq!(FEUERNES_BUTTON_RIGHT, JoypadButton::RIGHT.bits());
+
+        let mut nes = feuernes_create().unwrap();
+        assert_eq!(feuernes_run_frame(Some(&mut nes)), -1);
+        let error = unsafe { CStr::from_ptr(feuernes_last_error(Some(&nes))) };
+        assert_eq!(error.to_str(), Ok("no rom loaded"));
+        let mut len = 1;
+        let frame = unsafe { feuernes_framebuffer(Some(&mut nes), &mut len) };
+        assert!(frame.is_null());
+        assert_eq!(len, 0);
+
+        let garbage = [0u8; 16];
+        let result = unsafe { feuernes_load_rom(Some(&mut nes), garbage.as_ptr(), garbage.len()) };
+        assert_eq!(result, -1);
+
+        let rom = include_bytes!("../../core/res/test.nes");
+        let result = unsafe { feuernes_load_rom(Some(&mut nes), rom.as_ptr(), rom.len()) };
+        assert_eq!(result, 0);
+        assert_eq!(
+            feuernes_set_button(Some(&mut nes), 3, FEUERNES_BUTTON_A, true),
+            -1
+        );
+        assert_eq!(
+            feuernes_set_button(Some(&mut nes), 1, FEUERNES_BUTTON_A, true),
+            0
+        );
+        assert_eq!(
+            feuernes_set_button(Some(&mut nes), 1, FEUERNES_BUTTON_UP, true),
+            0
+        );
+        assert_eq!(
+            feuernes_set_button(Some(&mut nes), 1, FEUERNES_BUTTON_A, false),
+            0
+        );
+        for _ in 0..30 {
+            assert_eq!(feuernes_run_frame(Some(&mut nes)), 0);
+        }
+        let joypad = nes.nes.as_mut().unwrap().cpu.bus.joypad(Port::One);
+        assert_eq!(joypad.get_buttons(), JoypadButton::UP);
+
+        let frame = unsafe { feuernes_framebuffer(Some(&mut nes), &mut len) };
+        assert_eq!(len, FEUERNES_WIDTH * FEUERNES_HEIGHT * 4);
+        let frame = unsafe { slice::from_raw_parts(frame, len) };
+        assert!(frame.iter().any(|byte| *byte != 0));
+
+        // 30 frames of sound, then nothing until the next frame
+        let mut samples = vec![0.0; FEUERNES_SAMPLE_RATE as usize];
+        let pulled = unsafe { feuernes_audio_pull(Some(&mut nes), samples.as_mut_ptr(), 1000) };
+        assert_eq!(pulled, 1000);
+        let pulled =
+            unsafe { feuernes_audio_pull(Some(&mut nes), samples.as_mut_ptr(), samples.len()) };
+        assert!(pulled > 20000 && pulled < 23000, "{} samples", pulled);
+        let pulled = unsafe { feuernes_audio_pull(Some(&mut nes), samples.as_mut_ptr(), 1) };
+        assert_eq!(pulled, 0);
+
+        // a panic in the core comes back as an error and takes the console with it
+        let result = call(Some(&mut nes), |_| panic!("a broken rom"));
+        assert_eq!(result, -1);
+        let error = unsafe { CStr::from_ptr(feuernes_last_error(Some(&nes))) };
+        assert_eq!(error.to_str(), Ok("the emulator panicked: a broken rom"));
+        assert_eq!(feuernes_run_frame(Some(&mut nes)), -1);
+        let result = unsafe { feuernes_load_rom(Some(&mut nes), rom.as_ptr(), rom.len()) };
+        assert_eq!(result, 0);
+
+        feuernes_destroy(Some(nes));
+        feuernes_destroy(None);
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(
+            include_str!("../include/feuernes.h"),
+            include_str!(concat!(env!("OUT_DIR"), "/feuernes.h")),
+            "include/feuernes.h is out of date, see build.rs"
+        );
+    }
+}
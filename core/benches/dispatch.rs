@@ -0,0 +1,55 @@
+// opcode dispatch, see cpu/dispatch.rs. each run measures the dispatch the features pick,
+// criterion compares a run against the one before it under the same names:
+//
+//     cargo bench -p feuernes-core --bench dispatch
+//     cargo bench -p feuernes-core --bench dispatch --features dispatch-table
+//     cargo bench -p feuernes-core --bench dispatch --features dispatch-match
+//
+// or with --save-baseline <name> and --baseline <name> to keep all three around
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::nes::Nes;
+
+// the official opcodes part of nestest, in its automated mode from $C000
+const NESTEST_INSTRUCTIONS: usize = 5000;
+const GAME_FRAMES: usize = 10;
+
+fn nestest(c: &mut Criterion) {
+    let raw = include_bytes!("../res/test.nes").to_vec();
+    c.bench_function("nestest", |b| {
+        b.iter_with_setup(
+            || {
+                let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+                nes.reset();
+                nes.cpu.pc = 0xC000;
+                nes
+            },
+            |mut nes| {
+                for _ in 0..NESTEST_INSTRUCTIONS {
+                    nes.step();
+                }
+                nes
+            },
+        )
+    });
+}
+
+// a game's main loop, mostly the cpu waiting for the next frame with the ppu and apu
+// running alongside
+fn game_loop(c: &mut Criterion) {
+    let raw = include_bytes!("../res/snake.nes").to_vec();
+    let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+    nes.reset();
+    c.bench_function("game loop", |b| {
+        b.iter(|| {
+            for _ in 0..GAME_FRAMES {
+                nes.run_frame();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, nestest, game_loop);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+// runs every .nes file of a directory for a while and writes a screenshot of each next to
+// a report, for a quick look at what a rom set does with the emulator:
+//
+//     cargo run --release -p feuernes-core --example survey -- <roms> <out> [frames]
+//
+// <out> gets <rom>.png for every rom that ran, report.csv and report.json, and compat.json
+// with the same results in the form of res/compat.json to merge the interesting ones in
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use feuernes_core::compat::{self, CompatEntry};
+use feuernes_core::render::png;
+use feuernes_core::survey::{self, DEFAULT_SURVEY_FRAMES};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("usage: survey <roms> <out> [frames]");
+        process::exit(2);
+    }
+    let frames = match args.get(2) {
+        Some(frames) => frames.parse().unwrap_or_else(|_| {
+            eprintln!("bad frame count {:?}", frames);
+            process::exit(2);
+        }),
+        None => DEFAULT_SURVEY_FRAMES,
+    };
+    if let Err(error) = run(Path::new(&args[0]), Path::new(&args[1]), frames) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn run(roms: &Path, out: &Path, frames: u32) -> std::io::Result<()> {
+    let mut paths: Vec<_> = fs::read_dir(roms)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            extension.map_or(false, |extension| extension.eq_ignore_ascii_case("nes"))
+        })
+        .collect();
+    paths.sort();
+    fs::create_dir_all(out)?;
+
+    let mut entries = Vec::new();
+    for path in paths.iter() {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let entry = survey::survey_rom(&name, &fs::read(path)?, frames);
+        println!(
+            "{}: {} {}",
+            name,
+            entry.status.name(),
+            entry.status.detail()
+        );
+        if let Some(screenshot) = &entry.screenshot {
+            let file_name = format!("{}.png", path.file_stem().unwrap().to_string_lossy());
+            fs::write(out.join(file_name), png::encode(screenshot))?;
+        }
+        entries.push(entry);
+    }
+    fs::write(out.join("report.csv"), survey::report_csv(&entries))?;
+    fs::write(
+        out.join("report.json"),
+        survey::report_json(&entries).to_string(),
+    )?;
+    let compat: Vec<_> = entries
+        .iter()
+        .filter_map(CompatEntry::from_survey)
+        .collect();
+    fs::write(out.join("compat.json"), compat::to_json(&compat))?;
+    Ok(())
+}
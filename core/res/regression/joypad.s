@@ -0,0 +1,79 @@
+; input regression rom, NROM-128 with an empty chr bank, builds joypad.nes:
+;
+;   ca65 joypad.s && ld65 -t nes joypad.o -o joypad.nes
+;
+; every nmi reads controller 1, moves sprite 0 with the d-pad and folds the buttons into a
+; running checksum, so a movie that presses anything ends in a different state than one
+; that does not.
+;
+;   $00  buttons of this frame, A B Select Start Up Down Left Right from bit 7 down
+;   $10  sprite x
+;   $11  sprite y
+;   $12  checksum of all buttons seen
+
+.segment "HEADER"
+    .byte "NES", $1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+
+.segment "CODE"
+reset:
+    sei
+    cld
+    ldx #$FF
+    txs
+    lda #$80            ; nmi on vblank
+    sta $2000
+loop:
+    jmp loop
+
+nmi:
+    lda #$01            ; strobe both controllers
+    sta $4016
+    lda #$00
+    sta $4016
+    ldx #$08
+read:
+    lda $4016
+    lsr a
+    rol $00
+    dex
+    bne read
+
+    lda $00
+    lsr a               ; right
+    bcc no_right
+    inc $10
+no_right:
+    lsr a               ; left
+    bcc no_left
+    dec $10
+no_left:
+    lsr a               ; down
+    bcc no_down
+    inc $11
+no_down:
+    lsr a               ; up
+    bcc no_up
+    dec $11
+no_up:
+
+    lda $12
+    asl a
+    eor $00
+    sta $12
+
+    lda #$00            ; sprite 0: y, tile, attributes, x
+    sta $2003
+    lda $11
+    sta $2004
+    lda #$00
+    sta $2004
+    sta $2004
+    lda $10
+    sta $2004
+    rti
+
+.segment "VECTORS"
+    .word nmi, reset, reset
+
+.segment "CHARS"
+    .res $2000
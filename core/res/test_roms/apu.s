@@ -0,0 +1,80 @@
+; apu test rom, NROM-128 with an empty chr bank and prg ram, builds apu.nes:
+;
+;   ca65 apu.s && ld65 -t nes apu.o -o apu.nes
+;
+; the pulse 1 length counter as $4015 reports it. public domain.
+;
+;   2  $4015 did not report the length counter loaded through $4003
+;   3  disabling the channel in $4015 did not clear its length counter
+;   4  $4003 loaded the length counter of a disabled channel
+
+.segment "HEADER"
+    .byte "NES", $1A, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0   ; 8k prg ram for the results
+
+.segment "CODE"
+reset:
+    sei
+    cld
+    ldx #$FF
+    txs
+    lda #$40            ; no frame counter irq
+    sta $4017
+    jsr begin
+
+    lda #$01
+    sta $4015
+    lda #$08            ; length 254
+    sta $4003
+    lda $4015
+    and #$01
+    bne loaded
+    lda #<load
+    sta $00
+    lda #>load
+    sta $01
+    lda #$02
+    jmp fail
+loaded:
+
+    lda #$00
+    sta $4015
+    lda $4015
+    and #$01
+    beq cleared
+    lda #<clear
+    sta $00
+    lda #>clear
+    sta $01
+    lda #$03
+    jmp fail
+cleared:
+
+    lda #$08
+    sta $4003
+    lda $4015
+    and #$01
+    beq ignored
+    lda #<disabled
+    sta $00
+    lda #>disabled
+    sta $01
+    lda #$04
+    jmp fail
+ignored:
+
+    jmp pass
+
+.include "report.inc"
+
+load:
+    .byte "length counter did not load", 0
+clear:
+    .byte "disabling did not clear the length counter", 0
+disabled:
+    .byte "disabled channel loaded its length counter", 0
+
+.segment "VECTORS"
+    .word ignore, reset, ignore
+
+.segment "CHARS"
+    .res $2000
@@ -0,0 +1,99 @@
+; cpu test rom, NROM-128 with an empty chr bank and prg ram, builds cpu.nes:
+;
+;   ca65 cpu.s && ld65 -t nes cpu.o -o cpu.nes
+;
+; arithmetic and addressing quirks of the 2A03. public domain, like the other test roms
+; in this folder.
+;
+;   2  ADC used decimal mode, the 2A03 has none
+;   3  ADC did not set overflow on $7F + 1
+;   4  SBC did not borrow on 0 - 1
+;   5  JMP ($02FF) did not take its high byte from $0200
+
+.segment "HEADER"
+    .byte "NES", $1A, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0   ; 8k prg ram for the results
+
+.segment "CODE"
+reset:
+    sei
+    cld
+    ldx #$FF
+    txs
+    jsr begin
+
+    sed
+    clc
+    lda #$09
+    adc #$01
+    cld
+    cmp #$0A
+    beq adc_binary
+    lda #<decimal
+    sta $00
+    lda #>decimal
+    sta $01
+    lda #$02
+    jmp fail
+adc_binary:
+
+    clc
+    lda #$7F
+    adc #$01
+    bvs adc_overflow
+    lda #<overflow
+    sta $00
+    lda #>overflow
+    sta $01
+    lda #$03
+    jmp fail
+adc_overflow:
+
+    sec
+    lda #$00
+    sbc #$01
+    bcs sbc_failed
+    cmp #$FF
+    beq sbc_borrow
+sbc_failed:
+    lda #<borrow
+    sta $00
+    lda #>borrow
+    sta $01
+    lda #$04
+    jmp fail
+sbc_borrow:
+
+    lda #<jmp_wrapped
+    sta $02FF
+    lda #>jmp_wrapped
+    sta $0200
+    lda #>jmp_crossed
+    sta $0300
+    jmp ($02FF)
+jmp_crossed:
+    lda #<page_wrap
+    sta $00
+    lda #>page_wrap
+    sta $01
+    lda #$05
+    jmp fail
+jmp_wrapped:
+
+    jmp pass
+
+.include "report.inc"
+
+decimal:
+    .byte "ADC used decimal mode", 0
+overflow:
+    .byte "ADC did not set overflow", 0
+borrow:
+    .byte "SBC did not borrow", 0
+page_wrap:
+    .byte "JMP indirect crossed the page", 0
+
+.segment "VECTORS"
+    .word ignore, reset, ignore
+
+.segment "CHARS"
+    .res $2000
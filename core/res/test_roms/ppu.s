@@ -0,0 +1,109 @@
+; ppu test rom, NROM-128 with an empty chr bank and prg ram, builds ppu.nes:
+;
+;   ca65 ppu.s && ld65 -t nes ppu.o -o ppu.nes
+;
+; the status flag and the $2007 read buffer, with rendering off. public domain.
+;
+;   2  reading $2002 did not clear the vblank flag
+;   3  the first $2007 read was not the stale buffer
+;   4  the second $2007 read was not the data
+;   5  a palette read through $2007 was buffered
+
+.segment "HEADER"
+    .byte "NES", $1A, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0   ; 8k prg ram for the results
+
+.segment "CODE"
+reset:
+    sei
+    cld
+    ldx #$FF
+    txs
+    jsr begin
+
+    ; the read that sees the flag clears it
+vblank:
+    bit $2002
+    bpl vblank
+    bit $2002
+    bpl status_cleared
+    lda #<status
+    sta $00
+    lda #>status
+    sta $01
+    lda #$02
+    jmp fail
+status_cleared:
+
+    lda #$20
+    sta $2006
+    lda #$00
+    sta $2006
+    lda #$AB
+    sta $2007
+    lda #$CD
+    sta $2007
+    lda #$20
+    sta $2006
+    lda #$00
+    sta $2006
+    lda $2007
+    cmp #$AB
+    bne buffered
+    lda #<stale
+    sta $00
+    lda #>stale
+    sta $01
+    lda #$03
+    jmp fail
+buffered:
+    lda $2007
+    cmp #$AB
+    beq read_data
+    lda #<data
+    sta $00
+    lda #>data
+    sta $01
+    lda #$04
+    jmp fail
+read_data:
+
+    lda #$3F
+    sta $2006
+    lda #$01
+    sta $2006
+    lda #$15
+    sta $2007
+    lda #$3F
+    sta $2006
+    lda #$01
+    sta $2006
+    lda $2007
+    and #$3F
+    cmp #$15
+    beq palette_direct
+    lda #<palette
+    sta $00
+    lda #>palette
+    sta $01
+    lda #$05
+    jmp fail
+palette_direct:
+
+    jmp pass
+
+.include "report.inc"
+
+status:
+    .byte "$2002 read left vblank set", 0
+stale:
+    .byte "$2007 read was not buffered", 0
+data:
+    .byte "$2007 buffer held the wrong byte", 0
+palette:
+    .byte "palette read was buffered", 0
+
+.segment "VECTORS"
+    .word ignore, reset, ignore
+
+.segment "CHARS"
+    .res $2000
@@ -0,0 +1,386 @@
+// memory condition sets checked once per frame, in the spirit of retroachievements. an
+// achievement unlocks on the first frame all of its conditions hold, which pushes
+// Event::AchievementUnlocked. definitions are json:
+//
+//     {"achievements": [{
+//         "id": 1,
+//         "title": "Speedster",
+//         "description": "Reach x 200 while the timer counts down",
+//         "conditions": [
+//             {"address": "0x0010", "compare": ">=", "value": 200},
+//             {"address": "0x07f8", "size": 16, "compare": "<", "delta": true},
+//             {"address": "0x0012", "compare": "!=", "value": 0, "hits": 30},
+//             {"address": "0x000e", "compare": "==", "value": 6, "reset": true}
+//         ]
+//     }]}
+//
+// a condition compares the byte (or little endian word with "size": 16) at a cpu address
+// with either a constant "value" or, with "delta", what the same address held the frame
+// before. "hits" makes a condition count only after it held on that many frames, not
+// necessarily in a row, and "reset" conditions clear those counts instead of being
+// required. addresses are numbers or "0x" hex strings.
+
+use crate::events::Event;
+use crate::json::{self, Value};
+use crate::mem::Memory;
+use crate::nes::Nes;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    Byte,
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Value(u16),
+    // the same address one frame earlier
+    Delta,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub address: u16,
+    pub size: Size,
+    pub compare: Compare,
+    pub operand: Operand,
+    // 0 when the condition only has to hold on the frame itself
+    pub hits: u32,
+    pub reset: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Achievement {
+    pub id: u32,
+    pub title: String,
+    pub description: String,
+    pub conditions: Vec<Condition>,
+}
+
+// what evaluating an achievement remembers between frames
+struct Progress {
+    hits: Vec<u32>,
+    previous: Vec<Option<u16>>,
+    unlocked: bool,
+}
+
+pub struct Achievements {
+    achievements: Vec<Achievement>,
+    progress: Vec<Progress>,
+}
+
+impl Compare {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "==" => Ok(Compare::Equal),
+            "!=" => Ok(Compare::NotEqual),
+            "<" => Ok(Compare::Less),
+            "<=" => Ok(Compare::LessEqual),
+            ">" => Ok(Compare::Greater),
+            ">=" => Ok(Compare::GreaterEqual),
+            _ => Err(format!("unknown comparison {:?}", text)),
+        }
+    }
+
+    fn holds(&self, left: u16, right: u16) -> bool {
+        match self {
+            Compare::Equal => left == right,
+            Compare::NotEqual => left != right,
+            Compare::Less => left < right,
+            Compare::LessEqual => left <= right,
+            Compare::Greater => left > right,
+            Compare::GreaterEqual => left >= right,
+        }
+    }
+}
+
+fn parse_number(value: &Value, what: &str) -> Result<u64, String> {
+    let parsed = match value {
+        Value::String(text) => text
+            .strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok()),
+        _ => value.as_u64(),
+    };
+    parsed.ok_or_else(|| format!("{} is not a number", what))
+}
+
+fn parse_u16(value: &Value, what: &str) -> Result<u16, String> {
+    let number = parse_number(value, what)?;
+    if number > 0xFFFF {
+        return Err(format!("{} {:#x} does not fit in 16 bits", what, number));
+    }
+    Ok(number as u16)
+}
+
+impl Condition {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let address = value.get("address").ok_or("missing address")?;
+        let address = parse_u16(address, "address")?;
+        let size = match value.get("size").map(|size| parse_number(size, "size")) {
+            None | Some(Ok(8)) => Size::Byte,
+            Some(Ok(16)) => Size::Word,
+            Some(Ok(size)) => return Err(format!("size {} is neither 8 nor 16", size)),
+            Some(Err(e)) => return Err(e),
+        };
+        let compare = value
+            .get("compare")
+            .and_then(Value::as_str)
+            .ok_or("missing compare")?;
+        let compare = Compare::parse(compare)?;
+
+        let delta = value.get("delta").and_then(Value::as_bool).unwrap_or(false);
+        let operand = match (value.get("value"), delta) {
+            (Some(constant), false) => Operand::Value(parse_u16(constant, "value")?),
+            (None, true) => Operand::Delta,
+            _ => return Err(String::from("needs exactly one of value and delta")),
+        };
+        let hits = match value.get("hits") {
+            Some(hits) => parse_number(hits, "hits")? as u32,
+            None => 0,
+        };
+        let reset = value.get("reset").and_then(Value::as_bool).unwrap_or(false);
+
+        Ok(Condition {
+            address: address,
+            size: size,
+            compare: compare,
+            operand: operand,
+            hits: hits,
+            reset: reset,
+        })
+    }
+
+    fn read(&self, nes: &Nes) -> u16 {
+        match self.size {
+            Size::Byte => nes.cpu.bus.mem_peek(self.address) as u16,
+            Size::Word => nes.cpu.bus.mem_peek_u16(self.address),
+        }
+    }
+}
+
+impl Achievement {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let id = value.get("id").ok_or("missing id")?;
+        let id = parse_number(id, "id")? as u32;
+        let title = value.get("title").and_then(Value::as_str).unwrap_or("");
+        let description = value
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        let conditions = value
+            .get("conditions")
+            .and_then(Value::as_array)
+            .ok_or_else(|| format!("achievement {}: missing conditions", id))?
+            .iter()
+            .map(Condition::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("achievement {}: {}", id, e))?;
+        if conditions.iter().all(|condition| condition.reset) {
+            return Err(format!("achievement {}: nothing to unlock it", id));
+        }
+
+        Ok(Achievement {
+            id: id,
+            title: title.to_string(),
+            description: description.to_string(),
+            conditions: conditions,
+        })
+    }
+}
+
+impl Progress {
+    fn new(achievement: &Achievement) -> Self {
+        Progress {
+            hits: vec![0; achievement.conditions.len()],
+            previous: vec![None; achievement.conditions.len()],
+            unlocked: false,
+        }
+    }
+
+    // true on the frame the achievement unlocks
+    fn evaluate(&mut self, achievement: &Achievement, nes: &Nes) -> bool {
+        let mut reset = false;
+        let mut holds = Vec::with_capacity(achievement.conditions.len());
+        for (i, condition) in achievement.conditions.iter().enumerate() {
+            let current = condition.read(nes);
+            let previous = self.previous[i].replace(current);
+            let right = match condition.operand {
+                Operand::Value(value) => Some(value),
+                Operand::Delta => previous,
+            };
+            let held = right.is_some_and(|right| condition.compare.holds(current, right));
+            reset |= condition.reset && held;
+            holds.push(held);
+        }
+
+        if reset {
+            self.hits.iter_mut().for_each(|hits| *hits = 0);
+            return false;
+        }
+        let mut all = true;
+        for (i, condition) in achievement.conditions.iter().enumerate() {
+            if condition.reset {
+                continue;
+            }
+            if condition.hits == 0 {
+                all &= holds[i];
+                continue;
+            }
+            if holds[i] && self.hits[i] < condition.hits {
+                self.hits[i] += 1;
+            }
+            all &= self.hits[i] >= condition.hits;
+        }
+        all
+    }
+}
+
+impl Achievements {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let root = json::parse(text)?;
+        let achievements = root
+            .get("achievements")
+            .and_then(Value::as_array)
+            .ok_or("missing achievements")?
+            .iter()
+            .map(Achievement::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Achievements::new(achievements))
+    }
+
+    pub fn new(achievements: Vec<Achievement>) -> Self {
+        Achievements {
+            progress: achievements.iter().map(Progress::new).collect(),
+            achievements: achievements,
+        }
+    }
+
+    // call once after every frame. returns the ids that unlocked on it
+    pub fn evaluate(&mut self, nes: &mut Nes) -> Vec<u32> {
+        let mut unlocked = Vec::new();
+        for (achievement, progress) in self.achievements.iter().zip(self.progress.iter_mut()) {
+            if progress.unlocked || !progress.evaluate(achievement, nes) {
+                continue;
+            }
+            progress.unlocked = true;
+            unlocked.push(achievement.id);
+            nes.push_event(Event::AchievementUnlocked {
+                id: achievement.id,
+                title: achievement.title.clone(),
+            });
+        }
+        unlocked
+    }
+
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    pub fn is_unlocked(&self, id: u32) -> bool {
+        self.achievements
+            .iter()
+            .zip(self.progress.iter())
+            .any(|(achievement, progress)| achievement.id == id && progress.unlocked)
+    }
+
+    // starts every achievement over, e.g. after loading another game
+    pub fn reset(&mut self) {
+        self.progress = self.achievements.iter().map(Progress::new).collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::joypad::JoypadButton;
+    use crate::nes::test::create_nes;
+
+    const DEFINITIONS: &str = r#"{"achievements": [
+        {"id": 1, "title": "Right", "conditions": [
+            {"address": "0x10", "compare": ">=", "value": 5}
+        ]},
+        {"id": 2, "title": "Moving down", "conditions": [
+            {"address": 17, "compare": ">", "delta": true, "hits": 3},
+            {"address": "0x0010", "compare": "<", "delta": true, "reset": true}
+        ]}
+    ]}"#;
+
+    fn run_frame(
+        nes: &mut Nes,
+        achievements: &mut Achievements,
+        buttons: JoypadButton,
+    ) -> Vec<u32> {
+        nes.cpu.bus.joypad1().set_buttons(buttons);
+        nes.run_frame();
+        achievements.evaluate(nes)
+    }
+
+    #[test]
+    fn test_achievements() {
+        let mut nes = create_nes();
+        let mut achievements = Achievements::parse(DEFINITIONS).unwrap();
+        // the sprite starts at 0, 0
+        for _ in 0..3 {
+            assert!(run_frame(&mut nes, &mut achievements, JoypadButton::empty()).is_empty());
+        }
+
+        let mut unlocked = Vec::new();
+        for _ in 0..6 {
+            unlocked.extend(run_frame(&mut nes, &mut achievements, JoypadButton::RIGHT));
+        }
+        assert_eq!(unlocked, vec![1]);
+
+        // moving left resets the hits for moving down
+        run_frame(&mut nes, &mut achievements, JoypadButton::DOWN);
+        run_frame(&mut nes, &mut achievements, JoypadButton::DOWN);
+        run_frame(&mut nes, &mut achievements, JoypadButton::LEFT);
+        run_frame(&mut nes, &mut achievements, JoypadButton::DOWN);
+        assert!(!achievements.is_unlocked(2));
+        run_frame(&mut nes, &mut achievements, JoypadButton::DOWN);
+        assert_eq!(
+            run_frame(&mut nes, &mut achievements, JoypadButton::DOWN),
+            vec![2]
+        );
+
+        assert!(achievements.is_unlocked(1));
+        let events = nes.take_events();
+        assert!(events.contains(&Event::AchievementUnlocked {
+            id: 2,
+            title: String::from("Moving down")
+        }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Achievements::parse(r#"{"achievements": [{"id": 1}]}"#).is_err());
+        let condition = |text: &str| {
+            Achievements::parse(&format!(
+                r#"{{"achievements": [{{"id": 1, "conditions": [{}]}}]}}"#,
+                text
+            ))
+        };
+        assert!(condition(r#"{"address": 1, "compare": "==", "value": 1}"#).is_ok());
+        assert!(condition(r#"{"address": "0x10000", "compare": "==", "value": 1}"#).is_err());
+        assert!(condition(r#"{"address": 1, "compare": "=", "value": 1}"#).is_err());
+        assert!(
+            condition(r#"{"address": 1, "compare": "==", "value": 1, "delta": true}"#).is_err()
+        );
+        assert!(condition(r#"{"address": 1, "size": 32, "compare": "==", "value": 1}"#).is_err());
+        assert!(
+            condition(r#"{"address": 1, "compare": "==", "value": 1, "reset": true}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+// where a mirrored address really goes, for the cpu bus and the ppu bus. everything here
+// is a pure function of the address (and the board's mirroring), the buses only look up
+// the index they get back
+
+use crate::cartridge::MirroringType;
+
+/*
+https://wiki.nesdev.com/w/index.php/CPU_memory_map
+    $0000-$07FF	$0800	2KB internal ram
+    $0800-$1FFF	$1800	Mirrors of $0000-$07FF
+    $2000-$2007	$0008	PPU registers
+    $2008-$3FFF	$1FF8	Mirrors of $2000-$2007, every 8 bytes
+*/
+const CPU_RAM_SIZE: u16 = 0x800;
+
+// $0000-$1FFF to an index into the 2KB of cpu ram
+pub fn cpu_ram_mirror(addr: u16) -> usize {
+    (addr % CPU_RAM_SIZE) as usize
+}
+
+// $2000-$3FFF to the register it mirrors, $2000-$2007
+pub fn ppu_reg_mirror(addr: u16) -> u16 {
+    0x2000 | (addr & 0x0007)
+}
+
+/*
+https://wiki.nesdev.com/w/index.php/Mirroring#Nametable_Mirroring
+    the four nametables at $2000, $2400, $2800 and $2C00 share the console's 2KB, two
+    each. $3000-$3EFF mirrors $2000-$2EFF
+
+                  $2000 $2400 $2800 $2C00
+    horizontal      A     A     B     B
+    vertical        A     B     A     B
+    single lower    A     A     A     A
+    single upper    B     B     B     B
+    four screen     A     B     C     D    (C and D are ram on the cartridge)
+*/
+// $2000-$3EFF to an index into nametable ram, 2KB or 4KB for four screen boards
+pub fn nametable_mirror(mirroring: MirroringType, addr: u16) -> usize {
+    let addr = (addr & 0x0FFF) as usize;
+    let nametable = addr / 0x400;
+    let offset = addr % 0x400;
+    let page = match mirroring {
+        MirroringType::Horizontal => nametable / 2,
+        MirroringType::Vertical => nametable % 2,
+        MirroringType::SingleScreenLower => 0,
+        MirroringType::SingleScreenUpper => 1,
+        MirroringType::FourScreen => nametable,
+    };
+    page * 0x400 + offset
+}
+
+/*
+https://wiki.nesdev.com/w/index.php/PPU_palettes#Memory_Map
+    $3F00-$3F1F is the palette, $3F20-$3FFF mirrors it. $3F10, $3F14, $3F18 and $3F1C are
+    the sprite palettes' transparent entries and mirror $3F00, $3F04, $3F08 and $3F0C
+*/
+// $3F00-$3FFF to an index into the 32 bytes of palette ram
+pub fn palette_mirror(addr: u16) -> usize {
+    let index = (addr & 0x1F) as usize;
+    if index & 0x13 == 0x10 {
+        index - 0x10
+    } else {
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_address_decode() {
+        assert_eq!(cpu_ram_mirror(0x0123), 0x123);
+        assert_eq!(cpu_ram_mirror(0x0923), 0x123);
+        assert_eq!(cpu_ram_mirror(0x1FFF), 0x7FF);
+
+        assert_eq!(ppu_reg_mirror(0x2002), 0x2002);
+        assert_eq!(ppu_reg_mirror(0x2008), 0x2000);
+        assert_eq!(ppu_reg_mirror(0x3FFF), 0x2007);
+
+        let pages = |mirroring| {
+            [0x2005, 0x2405, 0x2805, 0x2C05, 0x3405]
+                .iter()
+                .map(|addr| nametable_mirror(mirroring, *addr))
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(
+            pages(MirroringType::Horizontal),
+            vec![0x005, 0x005, 0x405, 0x405, 0x005]
+        );
+        assert_eq!(
+            pages(MirroringType::Vertical),
+            vec![0x005, 0x405, 0x005, 0x405, 0x405]
+        );
+        assert_eq!(
+            pages(MirroringType::SingleScreenLower),
+            vec![0x005, 0x005, 0x005, 0x005, 0x005]
+        );
+        assert_eq!(
+            pages(MirroringType::SingleScreenUpper),
+            vec![0x405, 0x405, 0x405, 0x405, 0x405]
+        );
+        assert_eq!(
+            pages(MirroringType::FourScreen),
+            vec![0x005, 0x405, 0x805, 0xC05, 0x405]
+        );
+        assert_eq!(nametable_mirror(MirroringType::Vertical, 0x3EFF), 0x6FF);
+
+        assert_eq!(palette_mirror(0x3F01), 0x01);
+        assert_eq!(palette_mirror(0x3F10), 0x00);
+        assert_eq!(palette_mirror(0x3F1C), 0x0C);
+        assert_eq!(palette_mirror(0x3F11), 0x11);
+        assert_eq!(palette_mirror(0x3FE4), 0x04);
+    }
+}
@@ -0,0 +1,95 @@
+/*
+https://wiki.nesdev.com/w/index.php/APU_Length_Counter
+    The length counter provides automatic duration control for the pulse, triangle and noise
+    channels. It is loaded from a lookup table by writing the high timer register of a channel,
+    and counts down on every half frame clock unless halted. When it reaches zero the channel
+    is silenced.
+*/
+
+use alloc::vec::Vec;
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, //
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+#[derive(Clone, Default)]
+pub struct LengthCounter {
+    enabled: bool,
+    halt: bool,
+    counter: u8,
+}
+
+impl LengthCounter {
+    pub fn new() -> Self {
+        LengthCounter::default()
+    }
+
+    // clearing the enable bit in $4015 immediately silences the channel
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn set_halt(&mut self, halt: bool) {
+        self.halt = halt;
+    }
+
+    // the load index is bits 3-7 of the channel's last register, ignored while disabled
+    pub fn load(&mut self, data: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(data >> 3) as usize];
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[self.enabled as u8, self.halt as u8, self.counter]);
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.enabled = state[0] != 0;
+        self.halt = state[1] != 0;
+        self.counter = state[2];
+        &state[3..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_and_clock() {
+        let mut length = LengthCounter::new();
+        length.load(0b0001_1000);
+        assert!(!length.is_active());
+
+        length.set_enabled(true);
+        length.load(0b0001_1000);
+        for _ in 0..2 {
+            assert!(length.is_active());
+            length.clock();
+        }
+        assert!(!length.is_active());
+
+        length.load(0);
+        length.set_halt(true);
+        length.clock();
+        assert!(length.is_active());
+        length.set_enabled(false);
+        assert!(!length.is_active());
+    }
+}
@@ -0,0 +1,472 @@
+/*
+https://wiki.nesdev.com/w/index.php/APU_registers
+    Registers	Channel	Units
+    $4000-$4003	Pulse 1	Timer, length counter, envelope, sweep
+    $4004-$4007	Pulse 2	Timer, length counter, envelope, sweep
+    $4008-$400B	Triangle	Timer, length counter, linear counter
+    $400C-$400F	Noise	Timer, length counter, envelope, linear feedback shift register
+    $4010-$4013	DMC	Timer, memory reader, sample buffer, output unit
+    $4015	All	Channel enable and length counter status
+    $4017	All	Frame counter
+*/
+
+pub mod length_counter;
+pub mod sunsoft5b;
+
+use self::length_counter::LengthCounter;
+
+use alloc::vec::Vec;
+
+pub const APU_REG_PULSE1_BEGIN: u16 = 0x4000;
+pub const APU_REG_DMC_END: u16 = 0x4013;
+pub const APU_REG_STATUS: u16 = 0x4015;
+pub const APU_REG_FRAME_COUNTER: u16 = 0x4017;
+
+// $4018-$401F is normally disabled APU and I/O functionality (CPU test mode)
+pub const APU_TEST_BEGIN: u16 = 0x4018;
+pub const APU_TEST_END: u16 = 0x401F;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const CPU_CLOCK_RATE: f64 = 1_789_773.0;
+
+// https://wiki.nesdev.com/w/index.php/APU_Frame_Counter
+// the 4-step sequence raises its interrupt on the last step, 29829 cpu cycles in
+const FRAME_COUNTER_4_STEP_CYCLES: usize = 29830;
+const FRAME_COUNTER_5_STEP_CYCLES: usize = 37282;
+// length counters are clocked on the half frame steps of each sequence
+const FRAME_COUNTER_4_STEP_HALF_FRAMES: [usize; 2] = [14913, 29829];
+const FRAME_COUNTER_5_STEP_HALF_FRAMES: [usize; 2] = [14913, 37281];
+
+// https://wiki.nesdev.com/w/index.php/APU_DMC
+// ntsc periods in cpu cycles, each sample byte takes 8 of them
+const DMC_RATE_TABLE: [usize; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+// the channels with a length counter, in $4015 bit order
+const CHANNEL_PULSE1: usize = 0;
+const CHANNEL_PULSE2: usize = 1;
+const CHANNEL_TRIANGLE: usize = 2;
+const CHANNEL_NOISE: usize = 3;
+
+/*
+https://wiki.nesdev.com/w/index.php/APU#Status_.28.244015.29
+    Status ($4015) <> read/write
+
+    7  bit  0
+    ---- ----
+    IF-D NT21
+    |||| ||||
+    |||| |||+- Pulse 1 length counter > 0
+    |||| ||+-- Pulse 2 length counter > 0
+    |||| |+--- Triangle length counter > 0
+    |||| +---- Noise length counter > 0
+    |||+------ DMC active
+    ||+------- Open bus
+    |+-------- Frame interrupt
+    +--------- DMC interrupt
+*/
+bitflags::bitflags! {
+    pub struct APUSTATUS : u8 {
+        const PULSE1    = 0b0000_0001;
+        const PULSE2    = 0b0000_0010;
+        const TRIANGLE  = 0b0000_0100;
+        const NOISE     = 0b0000_1000;
+        const DMC       = 0b0001_0000;
+        const OPEN_BUS  = 0b0010_0000;
+        const FRAME_IRQ = 0b0100_0000;
+        const DMC_IRQ   = 0b1000_0000;
+    }
+}
+
+#[derive(Clone)]
+pub struct APU {
+    // last values written to $4000-$4013
+    pub registers: [u8; 0x14],
+    pub channel_enable: APUSTATUS,
+    length_counters: [LengthCounter; 4],
+    // sample bytes the dmc still has to play, the memory reader itself is not emulated
+    dmc_bytes_remaining: u16,
+    dmc_cycles: usize,
+
+    frame_irq_flag: bool,
+    dmc_irq_flag: bool,
+    frame_irq_inhibit: bool,
+    five_step_mode: bool,
+    frame_cycles: usize,
+
+    sample_cycles: f64,
+    samples: Vec<f32>,
+    // cartridge expansion audio, see Mapper::audio_output
+    expansion_output: f32,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            registers: [0; 0x14],
+            channel_enable: APUSTATUS::empty(),
+            length_counters: [
+                LengthCounter::new(),
+                LengthCounter::new(),
+                LengthCounter::new(),
+                LengthCounter::new(),
+            ],
+            dmc_bytes_remaining: 0,
+            dmc_cycles: 0,
+
+            frame_irq_flag: false,
+            dmc_irq_flag: false,
+            frame_irq_inhibit: false,
+            five_step_mode: false,
+            frame_cycles: 0,
+
+            sample_cycles: 0.0,
+            samples: Vec::new(),
+            expansion_output: 0.0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        self.registers[(addr - APU_REG_PULSE1_BEGIN) as usize] = data;
+
+        match addr {
+            0x4000 => self.length_counters[CHANNEL_PULSE1].set_halt(data & 0b0010_0000 != 0),
+            0x4004 => self.length_counters[CHANNEL_PULSE2].set_halt(data & 0b0010_0000 != 0),
+            0x4008 => self.length_counters[CHANNEL_TRIANGLE].set_halt(data & 0b1000_0000 != 0),
+            0x400C => self.length_counters[CHANNEL_NOISE].set_halt(data & 0b0010_0000 != 0),
+            0x4003 => self.length_counters[CHANNEL_PULSE1].load(data),
+            0x4007 => self.length_counters[CHANNEL_PULSE2].load(data),
+            0x400B => self.length_counters[CHANNEL_TRIANGLE].load(data),
+            0x400F => self.length_counters[CHANNEL_NOISE].load(data),
+            // $4010 bit 7 is the DMC irq enable, clearing it acknowledges the interrupt
+            0x4010 if data & 0b1000_0000 == 0 => self.dmc_irq_flag = false,
+            _ => {}
+        }
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        // reading $4015 acknowledges the frame interrupt, but not the dmc one
+        self.frame_irq_flag = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        let mut status = APUSTATUS::empty();
+        status.set(
+            APUSTATUS::PULSE1,
+            self.length_counters[CHANNEL_PULSE1].is_active(),
+        );
+        status.set(
+            APUSTATUS::PULSE2,
+            self.length_counters[CHANNEL_PULSE2].is_active(),
+        );
+        status.set(
+            APUSTATUS::TRIANGLE,
+            self.length_counters[CHANNEL_TRIANGLE].is_active(),
+        );
+        status.set(
+            APUSTATUS::NOISE,
+            self.length_counters[CHANNEL_NOISE].is_active(),
+        );
+        status.set(APUSTATUS::DMC, self.dmc_bytes_remaining > 0);
+        status.set(APUSTATUS::FRAME_IRQ, self.frame_irq_flag);
+        status.set(APUSTATUS::DMC_IRQ, self.dmc_irq_flag);
+        status.bits
+    }
+
+    pub fn write_status(&mut self, data: u8) {
+        self.channel_enable = APUSTATUS::from_bits_truncate(data & 0b0001_1111);
+        let channels = [
+            APUSTATUS::PULSE1,
+            APUSTATUS::PULSE2,
+            APUSTATUS::TRIANGLE,
+            APUSTATUS::NOISE,
+        ];
+        for (length_counter, channel) in self.length_counters.iter_mut().zip(channels.iter()) {
+            length_counter.set_enabled(self.channel_enable.contains(*channel));
+        }
+
+        // enabling the dmc only restarts the sample once the previous one has finished
+        if !self.channel_enable.contains(APUSTATUS::DMC) {
+            self.dmc_bytes_remaining = 0;
+        } else if self.dmc_bytes_remaining == 0 {
+            self.restart_dmc();
+        }
+        // writing $4015 always clears the dmc interrupt
+        self.dmc_irq_flag = false;
+    }
+
+    pub fn write_frame_counter(&mut self, data: u8) {
+        self.five_step_mode = data & 0b1000_0000 != 0;
+        self.frame_irq_inhibit = data & 0b0100_0000 != 0;
+        if self.frame_irq_inhibit {
+            self.frame_irq_flag = false;
+        }
+        self.frame_cycles = 0;
+        // selecting the 5-step sequence clocks the length counters right away
+        if self.five_step_mode {
+            self.clock_half_frame();
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/CPU_power_up_state#After_reset
+        $4015 is written with 0, silencing every channel and clearing the dmc interrupt.
+        $4017 is written again with its last value, restarting the frame counter, and the
+        frame interrupt is cleared. $4000-$4013 keep their values
+    */
+    pub fn reset(&mut self) {
+        self.write_status(0);
+        let frame_counter = (self.five_step_mode as u8) << 7 | (self.frame_irq_inhibit as u8) << 6;
+        self.write_frame_counter(frame_counter);
+        self.frame_irq_flag = false;
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        let previous_cycles = self.frame_cycles;
+        self.frame_cycles += cycles as usize;
+        self.tick_dmc(cycles as usize);
+
+        let cycles_per_sample = CPU_CLOCK_RATE / SAMPLE_RATE as f64;
+        self.sample_cycles += cycles as f64;
+        while self.sample_cycles >= cycles_per_sample {
+            self.sample_cycles -= cycles_per_sample;
+            let sample = self.mix();
+            self.samples.push(sample);
+        }
+
+        let (sequence_cycles, half_frames) = if self.five_step_mode {
+            (
+                FRAME_COUNTER_5_STEP_CYCLES,
+                FRAME_COUNTER_5_STEP_HALF_FRAMES,
+            )
+        } else {
+            (
+                FRAME_COUNTER_4_STEP_CYCLES,
+                FRAME_COUNTER_4_STEP_HALF_FRAMES,
+            )
+        };
+
+        for half_frame in half_frames.iter() {
+            if previous_cycles < *half_frame && self.frame_cycles >= *half_frame {
+                self.clock_half_frame();
+            }
+        }
+
+        if self.frame_cycles >= sequence_cycles {
+            self.frame_cycles -= sequence_cycles;
+            // the 5-step sequence never raises the frame interrupt
+            if !self.five_step_mode && !self.frame_irq_inhibit {
+                self.frame_irq_flag = true;
+            }
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        for length_counter in self.length_counters.iter_mut() {
+            length_counter.clock();
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/APU_DMC
+        $4010	IL--.RRRR	Flags and Rate (write)
+        $4013	LLLL.LLLL	Sample length (write), %LLLL.LLLL0001 = (L * 16) + 1 bytes
+    */
+    fn restart_dmc(&mut self) {
+        self.dmc_bytes_remaining = self.registers[0x13] as u16 * 16 + 1;
+        self.dmc_cycles = 0;
+    }
+
+    fn tick_dmc(&mut self, cycles: usize) {
+        if self.dmc_bytes_remaining == 0 {
+            return;
+        }
+
+        let flags = self.registers[0x10];
+        let byte_cycles = DMC_RATE_TABLE[(flags & 0b1111) as usize] * 8;
+        self.dmc_cycles += cycles;
+        while self.dmc_cycles >= byte_cycles && self.dmc_bytes_remaining > 0 {
+            self.dmc_cycles -= byte_cycles;
+            self.dmc_bytes_remaining -= 1;
+            if self.dmc_bytes_remaining == 0 {
+                if flags & 0b0100_0000 != 0 {
+                    self.restart_dmc();
+                } else if flags & 0b1000_0000 != 0 {
+                    self.dmc_irq_flag = true;
+                }
+            }
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        core::mem::take(&mut self.samples)
+    }
+
+    // registers, length counters, dmc and frame counter, then the sample clock
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = self.registers.to_vec();
+        state.push(self.channel_enable.bits());
+        for length_counter in self.length_counters.iter() {
+            length_counter.save_state(&mut state);
+        }
+        state.extend_from_slice(&self.dmc_bytes_remaining.to_le_bytes());
+        state.extend_from_slice(&(self.dmc_cycles as u64).to_le_bytes());
+        state.extend_from_slice(&[
+            self.frame_irq_flag as u8,
+            self.dmc_irq_flag as u8,
+            self.frame_irq_inhibit as u8,
+            self.five_step_mode as u8,
+        ]);
+        state.extend_from_slice(&(self.frame_cycles as u64).to_le_bytes());
+        state.extend_from_slice(&self.sample_cycles.to_bits().to_le_bytes());
+        state.extend_from_slice(&self.expansion_output.to_bits().to_le_bytes());
+        state
+    }
+
+    // samples not taken yet belong to the console that made them and are dropped
+    pub fn load_state(&mut self, state: &[u8]) {
+        let u64_at = |at: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&state[at..at + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        self.registers.copy_from_slice(&state[..0x14]);
+        self.channel_enable = APUSTATUS::from_bits_truncate(state[0x14]);
+        let mut rest = &state[0x15..];
+        for length_counter in self.length_counters.iter_mut() {
+            rest = length_counter.load_state(rest);
+        }
+        let at = state.len() - rest.len();
+        self.dmc_bytes_remaining = u16::from_le_bytes([state[at], state[at + 1]]);
+        self.dmc_cycles = u64_at(at + 2) as usize;
+        self.frame_irq_flag = state[at + 10] != 0;
+        self.dmc_irq_flag = state[at + 11] != 0;
+        self.frame_irq_inhibit = state[at + 12] != 0;
+        self.five_step_mode = state[at + 13] != 0;
+        self.frame_cycles = u64_at(at + 14) as usize;
+        self.sample_cycles = f64::from_bits(u64_at(at + 22));
+        let output = [
+            state[at + 30],
+            state[at + 31],
+            state[at + 32],
+            state[at + 33],
+        ];
+        self.expansion_output = f32::from_bits(u32::from_le_bytes(output));
+        self.samples.clear();
+    }
+
+    // the cartridge drives the expansion audio pin with whatever its chip outputs now
+    pub fn set_expansion_output(&mut self, output: f32) {
+        self.expansion_output = output;
+    }
+
+    fn mix(&self) -> f32 {
+        // the apu channels are not synthesized yet, only expansion audio is heard. the
+        // registers, length counters and irqs around them are emulated, which is what the
+        // test roms check, but no test can tell a broken channel from a working one yet
+        self.expansion_output
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_irq_flag || self.dmc_irq_flag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_irq_cleared_by_status_read() {
+        let mut apu = APU::new();
+        for _ in 0..(FRAME_COUNTER_4_STEP_CYCLES / 2 + 1) {
+            apu.tick(2);
+        }
+
+        assert_eq!(apu.read_status() & 0b0100_0000, 0b0100_0000);
+        assert_eq!(apu.read_status() & 0b0100_0000, 0);
+    }
+
+    #[test]
+    fn test_frame_irq_inhibit() {
+        let mut apu = APU::new();
+        apu.write_frame_counter(0b0100_0000);
+        for _ in 0..(FRAME_COUNTER_4_STEP_CYCLES / 2 + 1) {
+            apu.tick(2);
+        }
+
+        assert!(!apu.irq_pending());
+        assert_eq!(apu.read_status() & 0b0100_0000, 0);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut apu = APU::new();
+        apu.write_status(0b0000_1111);
+        apu.write_register(0x4003, 0b0000_1000);
+        apu.write_register(0x400F, 0b0000_1000);
+        for _ in 0..(FRAME_COUNTER_4_STEP_CYCLES / 2 + 1) {
+            apu.tick(2);
+        }
+        assert_eq!(apu.peek_status(), 0b0100_1001);
+
+        apu.reset();
+        assert_eq!(apu.peek_status(), 0);
+        assert!(!apu.irq_pending());
+        assert_eq!(apu.registers[0x03], 0b0000_1000);
+        // the frame counter starts over in the mode it was in
+        for _ in 0..(FRAME_COUNTER_4_STEP_CYCLES / 2 - 1) {
+            apu.tick(2);
+        }
+        assert!(!apu.irq_pending());
+        apu.tick(4);
+        assert!(apu.irq_pending());
+    }
+
+    #[test]
+    fn test_length_counter_status() {
+        let mut apu = APU::new();
+        // loading while the channel is disabled has no effect
+        apu.write_register(0x4003, 0b0000_1000);
+        assert_eq!(apu.peek_status() & 0b0001_1111, 0);
+
+        apu.write_status(0b0000_0101);
+        apu.write_register(0x4003, 0b0001_1000);
+        apu.write_register(0x400B, 0b0000_1000);
+        apu.write_register(0x4007, 0b0000_1000);
+        assert_eq!(apu.peek_status() & 0b0001_1111, 0b0000_0101);
+
+        // a length of 254 outlasts one sequence, a length of 2 is gone after two half frames
+        for _ in 0..(FRAME_COUNTER_4_STEP_CYCLES / 2 + 1) {
+            apu.tick(2);
+        }
+        assert_eq!(apu.peek_status() & 0b0001_1111, 0b0000_0100);
+
+        apu.write_status(0);
+        assert_eq!(apu.peek_status() & 0b0001_1111, 0);
+    }
+
+    #[test]
+    fn test_dmc_status_and_irq() {
+        let mut apu = APU::new();
+        apu.write_register(0x4010, 0b1000_1111);
+        apu.write_register(0x4013, 0);
+        apu.write_status(0b0001_0000);
+        assert_eq!(apu.peek_status() & 0b0001_0000, 0b0001_0000);
+
+        for _ in 0..(DMC_RATE_TABLE[0xF] * 8 / 2) {
+            apu.tick(2);
+        }
+        assert_eq!(apu.peek_status() & 0b1001_0000, 0b1000_0000);
+        assert!(apu.irq_pending());
+
+        // reading the status leaves the dmc interrupt alone, writing it acknowledges it
+        apu.read_status();
+        assert!(apu.irq_pending());
+        apu.write_status(0);
+        assert!(!apu.irq_pending());
+    }
+}
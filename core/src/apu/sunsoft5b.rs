@@ -0,0 +1,287 @@
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/Sunsoft_5B_audio
+
+    a YM2149F (an AY-3-8910 clone) on the cartridge, written through the mapper:
+    $C000-$DFFF selects one of its 16 registers and $E000-$FFFF writes it
+
+    $00-$05  tone period of channels A, B and C, 12 bits, low byte first
+    $06      noise period, 5 bits
+    $07      ..CB Acba  noise (CBA) and tone (cba) disable per channel
+    $08-$0A  ...E VVVV  volume of channels A, B and C, E uses the envelope instead
+    $0B-$0C  envelope period, 16 bits, low byte first
+    $0D      .... CAaH  envelope shape: continue, attack, alternate, hold
+
+    the chip runs at half the cpu clock with an internal divider of 16: tones flip every
+    `period` steps of 16 cpu cycles, noise and the envelope step every 2 * `period`.
+    the envelope has 32 levels 1.5dB apart, the volume registers map onto every other one
+*/
+const TICK_CYCLES: u8 = 16;
+
+const REG_NOISE_PERIOD: usize = 0x06;
+const REG_MIXER: usize = 0x07;
+const REG_VOLUME: usize = 0x08;
+const REG_ENVELOPE_PERIOD: usize = 0x0B;
+const REG_ENVELOPE_SHAPE: usize = 0x0D;
+
+const ENVELOPE_CONTINUE: u8 = 0b1000;
+const ENVELOPE_ATTACK: u8 = 0b0100;
+const ENVELOPE_ALTERNATE: u8 = 0b0010;
+const ENVELOPE_HOLD: u8 = 0b0001;
+
+const LEVELS: u8 = 32;
+const VOLUME_TABLE: [f32; LEVELS as usize] = [
+    0.0000, 0.0056, 0.0067, 0.0079, 0.0094, 0.0112, 0.0133, 0.0158, 0.0188, 0.0224, 0.0266, 0.0316,
+    0.0376, 0.0447, 0.0531, 0.0631, 0.0750, 0.0891, 0.1059, 0.1259, 0.1496, 0.1778, 0.2113, 0.2512,
+    0.2985, 0.3548, 0.4217, 0.5012, 0.5957, 0.7079, 0.8414, 1.0000,
+];
+
+// the three channels at full volume, scaled down to leave the apu room in the mix
+const OUTPUT_SCALE: f32 = 0.5 / 3.0;
+
+#[derive(Clone)]
+pub struct Sunsoft5b {
+    selected: usize,
+    registers: [u8; 16],
+    divider: u8,
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_counter: u16,
+    // 17 bit lfsr
+    noise: u32,
+    envelope_counter: u32,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+}
+
+impl Sunsoft5b {
+    pub fn new() -> Self {
+        Sunsoft5b {
+            selected: 0,
+            registers: [0; 16],
+            divider: 0,
+            tone_counters: [0; 3],
+            tone_outputs: [false; 3],
+            noise_counter: 0,
+            noise: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: false,
+        }
+    }
+
+    pub fn select(&mut self, data: u8) {
+        self.selected = (data & 0x0F) as usize;
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.registers[self.selected] = data;
+        if self.selected == REG_ENVELOPE_SHAPE {
+            self.envelope_step = 0;
+            self.envelope_counter = 0;
+            self.envelope_attack = data & ENVELOPE_ATTACK != 0;
+            self.envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let period = (self.registers[channel * 2 + 1] as u16 & 0x0F) << 8
+            | self.registers[channel * 2] as u16;
+        period.max(1)
+    }
+
+    fn noise_period(&self) -> u16 {
+        (self.registers[REG_NOISE_PERIOD] as u16 & 0x1F).max(1) * 2
+    }
+
+    fn envelope_period(&self) -> u32 {
+        let period = (self.registers[REG_ENVELOPE_PERIOD + 1] as u32) << 8
+            | self.registers[REG_ENVELOPE_PERIOD] as u32;
+        period.max(1) * 2
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.divider += 1;
+            if self.divider == TICK_CYCLES {
+                self.divider = 0;
+                self.step();
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        for channel in 0..3 {
+            self.tone_counters[channel] += 1;
+            if self.tone_counters[channel] >= self.tone_period(channel) {
+                self.tone_counters[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+
+        self.noise_counter += 1;
+        if self.noise_counter >= self.noise_period() {
+            self.noise_counter = 0;
+            let feedback = (self.noise ^ (self.noise >> 3)) & 1;
+            self.noise = self.noise >> 1 | feedback << 16;
+        }
+
+        self.envelope_counter += 1;
+        if self.envelope_counter >= self.envelope_period() {
+            self.envelope_counter = 0;
+            self.step_envelope();
+        }
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        self.envelope_step += 1;
+        if self.envelope_step < LEVELS {
+            return;
+        }
+
+        let shape = self.registers[REG_ENVELOPE_SHAPE];
+        if shape & ENVELOPE_CONTINUE == 0 {
+            // one ramp, then silence
+            self.envelope_holding = true;
+            self.envelope_attack = false;
+            self.envelope_step = LEVELS - 1;
+        } else if shape & ENVELOPE_HOLD != 0 {
+            self.envelope_holding = true;
+            if shape & ENVELOPE_ALTERNATE != 0 {
+                self.envelope_attack = !self.envelope_attack;
+            }
+            self.envelope_step = LEVELS - 1;
+        } else {
+            self.envelope_step = 0;
+            if shape & ENVELOPE_ALTERNATE != 0 {
+                self.envelope_attack = !self.envelope_attack;
+            }
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_attack {
+            self.envelope_step
+        } else {
+            LEVELS - 1 - self.envelope_step
+        }
+    }
+
+    // 0.0 to 1.0
+    pub fn output(&self) -> f32 {
+        let mixer = self.registers[REG_MIXER];
+        let mut output = 0.0;
+        for channel in 0..3 {
+            let tone = self.tone_outputs[channel] || mixer & (1 << channel) != 0;
+            let noise = self.noise & 1 != 0 || mixer & (0b1000 << channel) != 0;
+            if !(tone && noise) {
+                continue;
+            }
+            let volume = self.registers[REG_VOLUME + channel];
+            let level = if volume & 0b1_0000 != 0 {
+                self.envelope_level()
+            } else if volume & 0x0F == 0 {
+                0
+            } else {
+                (volume & 0x0F) * 2 + 1
+            };
+            output += VOLUME_TABLE[level as usize];
+        }
+        output * OUTPUT_SCALE
+    }
+
+    // registers, then the counters, for Mapper::save_state
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.push(self.selected as u8);
+        state.extend_from_slice(&self.registers);
+        state.push(self.divider);
+        for channel in 0..3 {
+            state.extend_from_slice(&self.tone_counters[channel].to_le_bytes());
+            state.push(self.tone_outputs[channel] as u8);
+        }
+        state.extend_from_slice(&self.noise_counter.to_le_bytes());
+        state.extend_from_slice(&self.noise.to_le_bytes());
+        state.extend_from_slice(&self.envelope_counter.to_le_bytes());
+        state.push(self.envelope_step);
+        state.push(self.envelope_attack as u8);
+        state.push(self.envelope_holding as u8);
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        let u16_at = |at: usize| u16::from_le_bytes([state[at], state[at + 1]]);
+        let u32_at = |at: usize| {
+            u32::from_le_bytes([state[at], state[at + 1], state[at + 2], state[at + 3]])
+        };
+        self.selected = state[0] as usize;
+        self.registers.copy_from_slice(&state[1..17]);
+        self.divider = state[17];
+        for channel in 0..3 {
+            self.tone_counters[channel] = u16_at(18 + channel * 3);
+            self.tone_outputs[channel] = state[20 + channel * 3] != 0;
+        }
+        self.noise_counter = u16_at(27);
+        self.noise = u32_at(29);
+        self.envelope_counter = u32_at(33);
+        self.envelope_step = state[37];
+        self.envelope_attack = state[38] != 0;
+        self.envelope_holding = state[39] != 0;
+        &state[40..]
+    }
+}
+
+impl Default for Sunsoft5b {
+    fn default() -> Self {
+        Sunsoft5b::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(chip: &mut Sunsoft5b, register: u8, data: u8) {
+        chip.select(register);
+        chip.write(data);
+    }
+
+    #[test]
+    fn test_tone_and_envelope() {
+        let mut chip = Sunsoft5b::new();
+        // channel A only, tone period 2, full volume
+        write(&mut chip, 0x07, 0b0011_1110);
+        write(&mut chip, 0x00, 2);
+        write(&mut chip, 0x08, 0x0F);
+        assert_eq!(chip.output(), 0.0);
+        chip.tick(TICK_CYCLES * 2);
+        assert_eq!(chip.output(), OUTPUT_SCALE);
+        chip.tick(TICK_CYCLES * 2);
+        assert_eq!(chip.output(), 0.0);
+
+        // a single decay: starts at full volume and ends silent
+        write(&mut chip, 0x07, 0b0011_1111);
+        write(&mut chip, 0x08, 0b1_0000);
+        write(&mut chip, 0x0B, 1);
+        write(&mut chip, 0x0D, 0b0000);
+        assert_eq!(chip.output(), OUTPUT_SCALE);
+        for _ in 0..LEVELS * 2 {
+            chip.tick(TICK_CYCLES);
+        }
+        assert_eq!(chip.output(), 0.0);
+        chip.tick(TICK_CYCLES * 8);
+        assert_eq!(chip.output(), 0.0);
+
+        let mut state = Vec::new();
+        chip.save_state(&mut state);
+        let mut loaded = Sunsoft5b::new();
+        assert!(loaded.load_state(&state).is_empty());
+        assert_eq!(loaded.registers, chip.registers);
+        assert!(loaded.envelope_holding);
+    }
+}
@@ -0,0 +1,275 @@
+// a one instruction per line 6502 assembler for trying out small changes from the debugger,
+// NOPping out a check or changing a constant. no labels or expressions, just the official
+// opcodes, `.byte` and ';' comments in ca65 syntax:
+//
+//     LDA #$10      immediate, also #16 and #%00010000
+//     STA $20,X     zero page when the address has at most two hex digits or is below 256
+//     LDA ($20),Y   and ($20,X), JMP ($1234)
+//     ASL A         or just ASL
+//     BNE $8010     branches take the target, the offset is worked out from the origin
+
+use crate::bus::PrgPatch;
+use crate::cpu::AddressMode;
+use crate::opcode::{Opcode, OPCODES};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// a byte Nes::patch_code replaced, kept for undo_code_patch
+pub(crate) enum Overwritten {
+    Ram(u8),
+    // the debugger's earlier patch there, if any
+    Prg(Option<PrgPatch>),
+}
+
+// the forms an operand was written in, before the opcode decides between zero page and
+// absolute
+enum Operand {
+    Implied,
+    Immediate(u8),
+    // the address and whether it was written like a zero page one
+    Address(u16, bool),
+    AddressX(u16, bool),
+    AddressY(u16, bool),
+    Indirect(u16),
+    IndirectX(u8),
+    IndirectY(u8),
+}
+
+// the machine code of `source` placed at `origin`
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, String> {
+    let mut code = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let pc = origin.wrapping_add(code.len() as u16);
+        assemble_line(line, pc, &mut code)
+            .map_err(|reason| format!("{} on line {}", reason, number + 1))?;
+    }
+    Ok(code)
+}
+
+fn assemble_line(line: &str, pc: u16, code: &mut Vec<u8>) -> Result<(), String> {
+    let (mnemonic, operand) = match line.find(char::is_whitespace) {
+        Some(at) => (&line[..at], line[at..].trim()),
+        None => (line, ""),
+    };
+    let mnemonic = mnemonic.to_ascii_uppercase();
+
+    if mnemonic == ".BYTE" {
+        for value in operand.split(',') {
+            code.push(parse_byte(value.trim())?);
+        }
+        return Ok(());
+    }
+    if !OPCODES.iter().any(|opcode| opcode.name == mnemonic) {
+        return Err(format!("unknown instruction {:?}", mnemonic));
+    }
+
+    let not_allowed = || format!("{} does not take {:?}", mnemonic, operand);
+    let find = |mode: AddressMode, bytes: u8| {
+        OPCODES
+            .iter()
+            .find(|opcode| opcode.name == mnemonic && opcode.mode == mode && opcode.bytes == bytes)
+    };
+    // zero page when it was written that way and the instruction has the mode, otherwise
+    // the absolute form
+    let pick = |zero_page: AddressMode, absolute: AddressMode, addr: u16, short: bool| {
+        let opcode = match find(zero_page, 2) {
+            Some(opcode) if short && addr <= 0xFF => opcode,
+            _ => find(absolute, 3).ok_or_else(not_allowed)?,
+        };
+        Ok::<(&Opcode, u16), String>((opcode, addr))
+    };
+
+    // branches are the two byte instructions without an addressing mode
+    if let Some(opcode) = find(AddressMode::NoneAddressing, 2) {
+        let target = match parse_operand(operand)? {
+            Operand::Address(target, _) => target,
+            _ => return Err(not_allowed()),
+        };
+        let offset = target.wrapping_sub(pc.wrapping_add(2)) as i16;
+        if offset < i8::MIN as i16 || offset > i8::MAX as i16 {
+            return Err(format!("${:04X} is out of reach of the branch", target));
+        }
+        code.extend_from_slice(&[opcode.op, offset as u8]);
+        return Ok(());
+    }
+
+    let (opcode, value) = match parse_operand(operand)? {
+        Operand::Implied => (
+            find(AddressMode::NoneAddressing, 1).ok_or_else(not_allowed)?,
+            0,
+        ),
+        Operand::Immediate(value) => (
+            find(AddressMode::Immediate, 2).ok_or_else(not_allowed)?,
+            value as u16,
+        ),
+        Operand::Address(addr, short) => {
+            pick(AddressMode::ZeroPage, AddressMode::Absolute, addr, short)?
+        }
+        Operand::AddressX(addr, short) => {
+            pick(AddressMode::ZeroPageX, AddressMode::AbsoluteX, addr, short)?
+        }
+        Operand::AddressY(addr, short) => {
+            pick(AddressMode::ZeroPageY, AddressMode::AbsoluteY, addr, short)?
+        }
+        Operand::Indirect(addr) => (
+            find(AddressMode::NoneAddressing, 3).ok_or_else(not_allowed)?,
+            addr,
+        ),
+        Operand::IndirectX(addr) => (
+            find(AddressMode::IndirectX, 2).ok_or_else(not_allowed)?,
+            addr as u16,
+        ),
+        Operand::IndirectY(addr) => (
+            find(AddressMode::IndirectY, 2).ok_or_else(not_allowed)?,
+            addr as u16,
+        ),
+    };
+    code.push(opcode.op);
+    match opcode.bytes {
+        2 => code.push(value as u8),
+        3 => code.extend_from_slice(&value.to_le_bytes()),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_operand(operand: &str) -> Result<Operand, String> {
+    let upper = operand.to_ascii_uppercase().replace(' ', "");
+    if upper.is_empty() || upper == "A" {
+        return Ok(Operand::Implied);
+    }
+    if let Some(value) = upper.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_byte(value)?));
+    }
+    if let Some(inner) = upper.strip_prefix('(') {
+        if let Some(addr) = inner.strip_suffix(",X)") {
+            return Ok(Operand::IndirectX(parse_byte(addr)?));
+        }
+        if let Some(addr) = inner.strip_suffix("),Y") {
+            return Ok(Operand::IndirectY(parse_byte(addr)?));
+        }
+        if let Some(addr) = inner.strip_suffix(')') {
+            return Ok(Operand::Indirect(parse_number(addr)?.0));
+        }
+        return Err(format!("bad operand {:?}", operand));
+    }
+    if let Some(addr) = upper.strip_suffix(",X") {
+        let (addr, short) = parse_number(addr)?;
+        return Ok(Operand::AddressX(addr, short));
+    }
+    if let Some(addr) = upper.strip_suffix(",Y") {
+        let (addr, short) = parse_number(addr)?;
+        return Ok(Operand::AddressY(addr, short));
+    }
+    let (addr, short) = parse_number(&upper)?;
+    Ok(Operand::Address(addr, short))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    match parse_number(text)? {
+        (value, _) if value <= 0xFF => Ok(value as u8),
+        _ => Err(format!("{:?} does not fit in a byte", text)),
+    }
+}
+
+// the value and whether it could be a zero page address as written, $0010 is absolute
+fn parse_number(text: &str) -> Result<(u16, bool), String> {
+    let parsed = if let Some(hex) = text.strip_prefix('$') {
+        u16::from_str_radix(hex, 16).map(|value| (value, hex.len() <= 2))
+    } else if let Some(binary) = text.strip_prefix('%') {
+        u16::from_str_radix(binary, 2).map(|value| (value, value <= 0xFF))
+    } else {
+        text.parse::<u16>().map(|value| (value, value <= 0xFF))
+    };
+    parsed.map_err(|_| format!("bad number {:?}", text))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cartridge::Cartridge;
+    use crate::mem::Memory;
+    use crate::nes::Nes;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            lda #$10        ; immediate
+            sta $20,x
+            sta $0020
+            lda ($20),y
+            asl a
+            ror
+            jmp ($0300)
+            bne $C000
+            .byte 1, %10, $ff
+        ";
+        let code = assemble(source, 0xC000).unwrap();
+        assert_eq!(
+            code,
+            vec![
+                0xA9, 0x10, 0x95, 0x20, 0x8D, 0x20, 0x00, 0xB1, 0x20, 0x0A, 0x6A, 0x6C, 0x00, 0x03,
+                0xD0, 0xF0, 0x01, 0x02, 0xFF,
+            ]
+        );
+
+        // LDX is one of the two with a zero page,Y form
+        assert_eq!(assemble("ldx $10,y", 0).unwrap(), vec![0xB6, 0x10]);
+        assert_eq!(assemble("jsr $8000", 0).unwrap(), vec![0x20, 0x00, 0x80]);
+        assert_eq!(
+            assemble("nop\nfoo", 0),
+            Err(String::from("unknown instruction \"FOO\" on line 2"))
+        );
+        assert_eq!(
+            assemble("bne $9000", 0x8000),
+            Err(String::from(
+                "$9000 is out of reach of the branch on line 1"
+            ))
+        );
+        assert_eq!(
+            assemble("sta #1", 0),
+            Err(String::from("STA does not take \"#1\" on line 1"))
+        );
+    }
+
+    #[test]
+    fn test_patch_code() {
+        // INC $10, JMP $8000
+        let mut prg = vec![0xEA; 0x4000];
+        prg[..5].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0x80]);
+        prg[0x3FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        let raw = crate::cartridge::test::create_rom(0, 1, prg);
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+
+        assert_eq!(nes.patch_code(0x8000, "nop\nnop"), Ok(2));
+        assert_eq!(nes.patch_code(0x0300, "lda #1"), Ok(2));
+        assert_eq!(nes.cpu.mem_peek(0x0300), 0xA9);
+        assert_eq!(nes.cpu.bus.peek_prg_rom(0x8000), 0xE6);
+        for _ in 0..30 {
+            nes.step();
+        }
+        assert_eq!(nes.cpu.mem_peek(0x10), 0);
+
+        assert!(nes.undo_code_patch());
+        assert_eq!(nes.cpu.mem_peek(0x0300), 0);
+        assert!(nes.undo_code_patch());
+        assert!(!nes.undo_code_patch());
+        for _ in 0..30 {
+            nes.step();
+        }
+        assert_ne!(nes.cpu.mem_peek(0x10), 0);
+
+        assert_eq!(
+            nes.patch_code(0x1FFF, "nop\nnop"),
+            Err(String::from("$2000 is not ram or prg"))
+        );
+        assert_eq!(nes.code_patches(), 0);
+    }
+}
@@ -0,0 +1,98 @@
+// https://github.com/libretro/docs/blob/master/archive/ratecontrol.pdf
+// the emulator produces samples at its own pace (one frame per animation frame), the audio
+// device consumes them at a fixed rate. nudging the resampling ratio by a small amount
+// depending on how full the ring buffer is keeps it hovering around half full without
+// audible pitch changes.
+
+use alloc::vec::Vec;
+
+// at most 0.5% faster or slower
+pub const DEFAULT_MAX_DELTA: f64 = 0.005;
+
+pub struct DynamicRateControl {
+    input_rate: f64,
+    output_rate: f64,
+    max_delta: f64,
+
+    // position between `last_sample` and the first sample of the next input
+    position: f64,
+    last_sample: f32,
+}
+
+impl DynamicRateControl {
+    pub fn new(input_rate: f64, output_rate: f64) -> Self {
+        DynamicRateControl {
+            input_rate: input_rate,
+            output_rate: output_rate,
+            max_delta: DEFAULT_MAX_DELTA,
+
+            position: 0.0,
+            last_sample: 0.0,
+        }
+    }
+
+    // output samples per input sample for the given ring buffer fill level (0.0..=1.0)
+    pub fn ratio(&self, fill_level: f32) -> f64 {
+        let fill_level = (fill_level as f64).clamp(0.0, 1.0);
+        self.output_rate / self.input_rate * (1.0 + self.max_delta * (1.0 - 2.0 * fill_level))
+    }
+
+    // linear interpolation resampling of `input`, appended to `out`
+    pub fn process(&mut self, input: &[f32], fill_level: f32, out: &mut Vec<f32>) {
+        if input.is_empty() {
+            return;
+        }
+
+        let step = 1.0 / self.ratio(fill_level);
+        let sample_at = |index: usize| {
+            if index == 0 {
+                self.last_sample
+            } else {
+                input[index - 1]
+            }
+        };
+
+        // index 0 is the last sample of the previous call, so input chunks join seamlessly
+        let mut position = self.position;
+        while position + 1.0 < (input.len() + 1) as f64 {
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let a = sample_at(index);
+            let b = sample_at(index + 1);
+            out.push(a + (b - a) * fraction);
+            position += step;
+        }
+
+        self.position = position - input.len() as f64;
+        self.last_sample = input[input.len() - 1];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ratio_follows_fill_level() {
+        let rate = DynamicRateControl::new(44100.0, 48000.0);
+        let nominal = 48000.0 / 44100.0;
+        assert!((rate.ratio(0.5) - nominal).abs() < 1e-12);
+        assert!(rate.ratio(0.0) > nominal);
+        assert!(rate.ratio(1.0) < nominal);
+        assert!((rate.ratio(0.0) / nominal - 1.0 - DEFAULT_MAX_DELTA).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_process_keeps_rate() {
+        let mut rate = DynamicRateControl::new(44100.0, 48000.0);
+        let input: Vec<f32> = (0..735).map(|i| (i as f32 / 100.0).sin()).collect();
+        let mut out = Vec::new();
+        for _ in 0..60 {
+            rate.process(&input, 0.5, &mut out);
+        }
+
+        // one second in, one second out
+        assert!((out.len() as i64 - 60 * 735 * 48000 / 44100).abs() <= 1);
+        assert!(out.iter().all(|sample| sample.abs() <= 1.0));
+    }
+}
@@ -0,0 +1,121 @@
+// fixed size sample queue between the emulator (producer, once per frame) and the audio
+// device (consumer, whenever it needs a block)
+
+use alloc::vec::Vec;
+
+pub struct AudioRingBuffer {
+    buffer: Vec<f32>,
+    read: usize,
+    len: usize,
+    // repeated on underrun instead of dropping to zero, which would click
+    last_sample: f32,
+    underruns: u64,
+    overruns: u64,
+}
+
+impl AudioRingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        AudioRingBuffer {
+            buffer: vec![0.0; capacity.max(1)],
+            read: 0,
+            len: 0,
+            last_sample: 0.0,
+            underruns: 0,
+            overruns: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // 0.0 = empty, 1.0 = full
+    pub fn fill_level(&self) -> f32 {
+        self.len as f32 / self.capacity() as f32
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        let capacity = self.capacity();
+        if self.len + samples.len() > capacity {
+            // the device fell behind, drop the oldest samples to keep latency bounded
+            let excess = (self.len + samples.len() - capacity).min(self.len);
+            self.read = (self.read + excess) % capacity;
+            self.len -= excess;
+            self.overruns += 1;
+        }
+
+        let skip = samples.len().saturating_sub(capacity);
+        for sample in samples[skip..].iter() {
+            let write = (self.read + self.len) % capacity;
+            self.buffer[write] = *sample;
+            self.len += 1;
+        }
+    }
+
+    pub fn pop_into(&mut self, out: &mut [f32]) {
+        let available = self.len.min(out.len());
+        for sample in out[..available].iter_mut() {
+            *sample = self.buffer[self.read];
+            self.read = (self.read + 1) % self.capacity();
+        }
+        self.len -= available;
+        if available > 0 {
+            self.last_sample = out[available - 1];
+        }
+
+        if available < out.len() {
+            for sample in out[available..].iter_mut() {
+                *sample = self.last_sample;
+            }
+            self.underruns += 1;
+        }
+    }
+
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_underrun() {
+        let mut ring = AudioRingBuffer::new(8);
+        ring.push(&[0.1, 0.2, 0.3]);
+
+        let mut out = [0.0; 5];
+        ring.pop_into(&mut out);
+        assert_eq!(out, [0.1, 0.2, 0.3, 0.3, 0.3]);
+        assert_eq!(ring.underruns(), 1);
+        assert_eq!(ring.len(), 0);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn test_overrun_drops_oldest() {
+        let mut ring = AudioRingBuffer::new(4);
+        ring.push(&[1.0, 2.0, 3.0]);
+        ring.push(&[4.0, 5.0, 6.0]);
+        assert_eq!(ring.overruns(), 1);
+        assert_eq!(ring.fill_level(), 1.0);
+
+        let mut out = [0.0; 4];
+        ring.pop_into(&mut out);
+        assert_eq!(out, [3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(ring.underruns(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+// the audio device side, implemented by each platform's frontend (web audio in the
+// browser, cpal natively). the device pulls blocks from the sink's ring buffer whenever it
+// needs them, connect() has the console push its samples into that ring at the device's
+// rate, so the apu never learns what it is playing on
+
+use super::rate_control::DynamicRateControl;
+use super::ring_buffer::AudioRingBuffer;
+use crate::apu::SAMPLE_RATE;
+use crate::nes::Nes;
+use crate::sync::AtomicRefCell;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub trait AudioSink {
+    // of the device, the console's samples are resampled to it
+    fn sample_rate(&self) -> f64;
+
+    fn ring(&self) -> Arc<AtomicRefCell<AudioRingBuffer>>;
+
+    // playback can start before resume() on some platforms, browsers wait for a user gesture
+    fn resume(&self);
+
+    // stops the device callbacks until resume(), the samples in the ring stay
+    fn suspend(&self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioConfig {
+    // samples per device callback. smaller blocks react sooner but wake the device more
+    // often, platforms that cannot do the exact size pick one close to it
+    pub buffer_size: u32,
+    // length of the ring buffer. the rate control keeps it about half full, so about half
+    // of this is added to the device's own latency
+    pub latency_ms: f32,
+}
+
+impl Default for AudioConfig {
+    // about 21ms blocks at 48kHz and 50ms of buffered sound
+    fn default() -> Self {
+        AudioConfig {
+            buffer_size: 1024,
+            latency_ms: 100.0,
+        }
+    }
+}
+
+impl AudioConfig {
+    // never less than two device blocks, the half the rate control aims for has to cover a
+    // whole callback or every one of them underruns
+    pub fn ring_capacity(&self, sample_rate: f64) -> usize {
+        let capacity = (sample_rate * self.latency_ms as f64 / 1000.0) as usize;
+        capacity.max(self.buffer_size as usize * 2)
+    }
+}
+
+// replaces the console's audio callback with one feeding `sink`
+pub fn connect(nes: &mut Nes, sink: &dyn AudioSink) {
+    let ring = sink.ring();
+    let mut rate_control = DynamicRateControl::new(SAMPLE_RATE as f64, sink.sample_rate());
+    let mut resampled = Vec::new();
+    nes.set_audio_callback(move |samples| {
+        // the device may be popping on its own thread right now
+        let mut ring = ring.borrow_mut_spin();
+        resampled.clear();
+        rate_control.process(samples, ring.fill_level(), &mut resampled);
+        ring.push(&resampled);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    struct TestSink {
+        ring: Arc<AtomicRefCell<AudioRingBuffer>>,
+    }
+
+    impl AudioSink for TestSink {
+        fn sample_rate(&self) -> f64 {
+            48000.0
+        }
+
+        fn ring(&self) -> Arc<AtomicRefCell<AudioRingBuffer>> {
+            self.ring.clone()
+        }
+
+        fn resume(&self) {}
+
+        fn suspend(&self) {}
+    }
+
+    #[test]
+    fn test_connect() {
+        let config = AudioConfig::default();
+        assert_eq!(config.ring_capacity(48000.0), 4800);
+        let small = AudioConfig {
+            buffer_size: 512,
+            latency_ms: 5.0,
+        };
+        assert_eq!(small.ring_capacity(48000.0), 1024);
+
+        let sink = TestSink {
+            ring: Arc::new(AtomicRefCell::new(AudioRingBuffer::new(
+                config.ring_capacity(48000.0),
+            ))),
+        };
+        let raw = include_bytes!("../../res/snake.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        connect(&mut nes, &sink);
+        nes.reset();
+        nes.run_frame();
+        // one frame at 48kHz, give or take the rate control's nudge
+        let len = sink.ring.borrow().len();
+        assert!(len > 700 && len < 900, "{} samples", len);
+    }
+}
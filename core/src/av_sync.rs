@@ -0,0 +1,103 @@
+// timing for recordings. live play hands the audio callback whatever the apu made during
+// a frame, a sample more or less each time, and frontends stretch that to the wall clock.
+// a video file plays at one fixed frame rate instead, so with AvTiming::Exact every frame
+// carries exactly the samples that fall into it at the nominal rate, and audio and video
+// stay in sync however fast or slow the export runs
+
+use crate::apu::SAMPLE_RATE;
+
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/Cycle_reference_chart
+
+    NTSC: 1789773 Hz cpu clock, 29780.5 cpu cycles per frame with rendering on, which is
+    60.0988 frames per second. frames with rendering off are half a cycle longer, a
+    sample every minute or so that Exact leaves out
+*/
+// frames per second as a fraction, 1789773 / 29780.5
+pub const NTSC_FRAME_RATE: (u64, u64) = (3_579_546, 59_561);
+
+// at most this many samples wait for the next frame, more than that is dropped
+const MAX_PENDING_SAMPLES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AvTiming {
+    // the samples the apu made during the frame
+    Live,
+    // the frame's share of SAMPLE_RATE at NTSC_FRAME_RATE
+    Exact,
+}
+
+pub(crate) struct AvSync {
+    frames: u64,
+    samples: u64,
+    // made by the apu but not handed out yet
+    pending: Vec<f32>,
+}
+
+impl AvSync {
+    pub fn new() -> Self {
+        AvSync {
+            frames: 0,
+            samples: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    // the samples of the next frame, from the frame count rather than a running sum so
+    // rounding never adds up
+    pub fn next_frame_samples(&self) -> usize {
+        let (frames, seconds) = NTSC_FRAME_RATE;
+        let total = (self.frames + 1) * SAMPLE_RATE as u64 * seconds / frames;
+        (total - self.samples) as usize
+    }
+
+    // `made` is what the apu made during the frame. a shortfall repeats the last sample
+    pub fn frame(&mut self, made: &[f32]) -> Vec<f32> {
+        let count = self.next_frame_samples();
+        self.pending.extend_from_slice(made);
+        let last = self.pending.last().copied().unwrap_or(0.0);
+        if self.pending.len() < count {
+            self.pending.resize(count, last);
+        }
+        let samples: Vec<f32> = self.pending.drain(..count).collect();
+        if self.pending.len() > MAX_PENDING_SAMPLES {
+            let surplus = self.pending.len() - MAX_PENDING_SAMPLES;
+            self.pending.drain(..surplus);
+        }
+        self.frames += 1;
+        self.samples += count as u64;
+        samples
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::nes::Nes;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_exact_timing() {
+        let raw = include_bytes!("../res/snake.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        let counts = Arc::new(AtomicRefCell::new(Vec::new()));
+        let seen = counts.clone();
+        nes.set_audio_callback(move |samples| seen.borrow_mut().push(samples.len()));
+        nes.set_av_timing(AvTiming::Exact);
+        nes.reset();
+        for _ in 0..600 {
+            nes.run_frame();
+        }
+
+        // ten seconds of 60.0988 frames, 733 or 734 samples each
+        let counts = counts.borrow();
+        let total: usize = counts.iter().sum();
+        assert_eq!(counts.len(), 600);
+        assert_eq!(total, 600 * 44100 * 59_561 / 3_579_546);
+        assert!(counts.iter().all(|count| *count == 733 || *count == 734));
+    }
+}
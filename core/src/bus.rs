@@ -0,0 +1,892 @@
+use crate::address_decode::{cpu_ram_mirror, ppu_reg_mirror};
+use crate::apu::*;
+use crate::cartridge;
+use crate::config::{self, Accuracy, Console, PowerOnRng};
+use crate::expansion::ExpansionDevice;
+use crate::joypad::*;
+use crate::json::Value;
+use crate::mapper::{self, PrgRamWindow, SharedMapper};
+use crate::mem;
+use crate::ppu::registers::BitwiseRegister;
+use crate::ppu::*;
+use crate::savestate::{self, ChunkId};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+const RAM_BEGIN: u16 = 0x0000;
+const RAM_END: u16 = 0x1FFF;
+
+const PPU_REG_MIRROR_BEGIN: u16 = 0x2008; // 0x2000-0x2007 is ppu registers, mirror to it
+const PPU_REG_MIRROR_END: u16 = 0x3FFF;
+
+// dma from these pages would read the ppu registers, the copy sees open bus instead
+const OAM_DMA_PPU_PAGE_BEGIN: u8 = 0x20;
+const OAM_DMA_PPU_PAGE_END: u8 = 0x3F;
+
+// https://wiki.nesdev.com/w/index.php/PPU_power_up_state
+// until about one frame after power-on the ppu ignores writes to $2000, $2001, $2005 and
+// $2006. games wait for two vblanks before touching it, code that does not breaks on
+// hardware but not on emulators that skip this
+pub const PPU_WARM_UP_CYCLES: usize = 29658;
+
+// https://wiki.nesdev.com/w/index.php/Cycle_reference_chart
+// 341 dots a scanline at 3 dots per cpu cycle
+const CPU_CYCLES_PER_SCANLINE: usize = 341 / 3;
+
+// https://wiki.nesdev.com/w/index.php/CPU_memory_map
+// the rest of the io page is left to the cartridge
+const CARTRIDGE_EXPANSION_BEGIN: u16 = 0x4020;
+const CARTRIDGE_EXPANSION_END: u16 = 0x5FFF;
+
+const PRG_RAM_BEGIN: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
+
+const PRG_BEGIN: u16 = 0x8000;
+const PRG_END: u16 = 0xFFFF;
+
+pub struct Bus {
+    vram: [u8; 0x800],
+    mapper: SharedMapper,
+    // the ines number of the board, savestates are tagged with it
+    mapper_number: u8,
+    prg_ram: Vec<u8>,
+    // cartridge: cartridge::Cartridge,
+    ppu: PPU,
+    apu: APU,
+    joypad1: Joypad,
+    joypad2: Joypad,
+    cycles: usize,
+    // last value seen on the cpu data bus, returned by reads nothing responds to
+    open_bus: u8,
+    accuracy: Accuracy,
+    console: Console,
+    // see config::UNSTABLE_MAGIC
+    unstable_magic: u8,
+    // the famicom's controller 2 microphone picks up something loud enough
+    microphone: bool,
+    // whatever is plugged into the expansion port, see the expansion module
+    expansion: Option<Box<dyn ExpansionDevice>>,
+    // the game raised the controller strobe, see take_strobe
+    strobe: bool,
+    // extra scanlines of cpu time at the start of vblank, see set_overclock_scanlines
+    overclock_scanlines: u16,
+    // cpu cycles left to run while the ppu and apu wait
+    overclock_cycles: usize,
+    // debugger patches of prg rom by cpu address, the patched byte replaces the original
+    // only while the bank with the original is mapped there
+    prg_overlay: BTreeMap<u16, PrgPatch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrgPatch {
+    pub original: u8,
+    pub data: u8,
+}
+
+// everything on the bus a snapshot has to bring back, see Nes::snapshot
+#[derive(Clone)]
+pub struct BusState {
+    vram: [u8; 0x800],
+    prg_ram: Vec<u8>,
+    ppu: PPU,
+    apu: APU,
+    joypad1: Joypad,
+    joypad2: Joypad,
+    cycles: usize,
+    open_bus: u8,
+    strobe: bool,
+    overclock_cycles: usize,
+    mapper: Vec<u8>,
+}
+
+impl Bus {
+    pub fn new(cartridge: cartridge::Cartridge) -> Self {
+        let prg_ram_size = cartridge.prg_ram_size;
+        let mapper_number = cartridge.mapper;
+        let mapper = mapper::new_mapper(cartridge);
+        Bus {
+            vram: [0; 0x800],
+            mapper: mapper.clone(),
+            mapper_number: mapper_number,
+            prg_ram: vec![0; prg_ram_size],
+            // cartridge: cartridge,
+            ppu: PPU::new(mapper),
+            apu: APU::new(),
+            joypad1: Joypad::new(),
+            joypad2: Joypad::new(),
+            cycles: 0,
+            open_bus: 0,
+            accuracy: Accuracy::Balanced,
+            console: Console::Nes,
+            unstable_magic: config::UNSTABLE_MAGIC,
+            microphone: false,
+            expansion: None,
+            strobe: false,
+            overclock_scanlines: 0,
+            overclock_cycles: 0,
+            prg_overlay: BTreeMap::new(),
+        }
+    }
+
+    pub fn read_prg_rom(&self, addr: u16) -> u8 {
+        let data = self.mapper.borrow().read_prg(addr);
+        if self.prg_overlay.is_empty() {
+            return data;
+        }
+        match self.prg_overlay.get(&addr) {
+            Some(patch) if patch.original == data => patch.data,
+            _ => data,
+        }
+    }
+
+    // what the cartridge has at `addr`, without the overlay
+    pub fn peek_prg_rom(&self, addr: u16) -> u8 {
+        self.mapper.borrow().read_prg(addr)
+    }
+
+    pub fn prg_patch(&self, addr: u16) -> Option<PrgPatch> {
+        self.prg_overlay.get(&addr).copied()
+    }
+
+    // None takes the patch at `addr` out again
+    pub fn set_prg_patch(&mut self, addr: u16, patch: Option<PrgPatch>) {
+        match patch {
+            Some(patch) => self.prg_overlay.insert(addr, patch),
+            None => self.prg_overlay.remove(&addr),
+        };
+    }
+
+    pub fn read_prg_ram(&self, addr: u16) -> u8 {
+        if self.prg_ram.is_empty() {
+            // no ram on this board, nothing drives the data bus
+            return 0;
+        }
+        // boards with less than 8KB (Family BASIC has 2KB/4KB) mirror it over the window
+        self.prg_ram[(addr - PRG_RAM_BEGIN) as usize % self.prg_ram.len()]
+    }
+
+    pub fn write_prg_ram(&mut self, addr: u16, data: u8) {
+        if self.prg_ram.is_empty() {
+            return;
+        }
+        let len = self.prg_ram.len();
+        self.prg_ram[(addr - PRG_RAM_BEGIN) as usize % len] = data;
+    }
+
+    // $6000-$7FFF as the board maps it
+    fn read_prg_ram_window(&self, addr: u16) -> u8 {
+        match self.mapper.borrow().prg_ram_window(addr) {
+            PrgRamWindow::Ram => self.read_prg_ram(addr),
+            PrgRamWindow::Rom(data) => data,
+            PrgRamWindow::Register { data, mask } => data & mask | self.open_bus & !mask,
+            PrgRamWindow::Disabled => self.open_bus,
+        }
+    }
+
+    fn read_cartridge_expansion(&self, addr: u16) -> u8 {
+        self.mapper
+            .borrow()
+            .read_expansion_area(addr)
+            .unwrap_or(self.open_bus)
+    }
+
+    fn write_prg_ram_window(&mut self, addr: u16, data: u8) {
+        let window = self.mapper.borrow().prg_ram_window(addr);
+        match window {
+            PrgRamWindow::Ram => self.write_prg_ram(addr, data),
+            _ => self.mapper.borrow_mut().write_prg_ram_window(addr, data),
+        }
+    }
+
+    // cpu ram or prg ram, without the side effects of a cpu write
+    pub fn write_ram(&mut self, addr: u16, data: u8) {
+        match addr {
+            RAM_BEGIN..=RAM_END => self.vram[cpu_ram_mirror(addr)] = data,
+            PRG_RAM_BEGIN..=PRG_RAM_END => self.write_prg_ram(addr, data),
+            _ => {}
+        }
+    }
+
+    pub fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    pub fn load_prg_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    // what survives power-off: the board's eeprom if it has one, otherwise prg ram
+    pub fn save_data(&self) -> Vec<u8> {
+        match self.mapper.borrow().save_data() {
+            Some(data) => data,
+            None => self.prg_ram.clone(),
+        }
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if self.mapper.borrow().save_data().is_some() {
+            self.mapper.borrow_mut().load_save_data(data);
+        } else {
+            self.load_prg_ram(data);
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.mapper.borrow().games()
+    }
+
+    pub fn mapper_debug_state(&self) -> Value {
+        self.mapper.borrow().debug_state()
+    }
+
+    pub fn select_game(&mut self, game: usize) {
+        self.mapper.borrow_mut().select_game(game);
+    }
+
+    pub fn save_state(&self) -> BusState {
+        BusState {
+            vram: self.vram,
+            prg_ram: self.prg_ram.clone(),
+            ppu: self.ppu.clone(),
+            apu: self.apu.clone(),
+            joypad1: self.joypad1.clone(),
+            joypad2: self.joypad2.clone(),
+            cycles: self.cycles,
+            open_bus: self.open_bus,
+            strobe: self.strobe,
+            overclock_cycles: self.overclock_cycles,
+            mapper: self.mapper.borrow().save_state(),
+        }
+    }
+
+    // the state has to come from a bus with the same cartridge
+    pub fn load_state(&mut self, state: &BusState) {
+        self.vram = state.vram;
+        self.prg_ram.copy_from_slice(&state.prg_ram);
+        self.ppu = state.ppu.clone();
+        self.ppu.bus.set_mapper(self.mapper.clone());
+        self.apu = state.apu.clone();
+        self.joypad1 = state.joypad1.clone();
+        self.joypad2 = state.joypad2.clone();
+        // the console may have changed since
+        self.joypad2.set_missing(self.console.controller2_missing());
+        self.cycles = state.cycles;
+        self.open_bus = state.open_bus;
+        self.strobe = state.strobe;
+        self.overclock_cycles = state.overclock_cycles;
+        self.mapper.borrow_mut().load_state(&state.mapper);
+    }
+
+    // the bus's chunks of a savestate, see savestate
+    pub fn state_chunks(&self) -> Vec<(ChunkId, Vec<u8>)> {
+        let mut bus = self.vram.to_vec();
+        bus.extend_from_slice(&(self.cycles as u64).to_le_bytes());
+        bus.extend_from_slice(&[self.open_bus, self.strobe as u8]);
+        bus.extend_from_slice(&(self.overclock_cycles as u64).to_le_bytes());
+        bus.extend_from_slice(&self.prg_ram);
+        let mut ppu = Vec::new();
+        self.ppu.save_state(&mut ppu);
+        vec![
+            (savestate::CHUNK_BUS, bus),
+            (savestate::CHUNK_PPU, ppu),
+            (savestate::CHUNK_APU, self.apu.save_state()),
+            (savestate::CHUNK_PAD1, self.joypad1.save_state()),
+            (savestate::CHUNK_PAD2, self.joypad2.save_state()),
+            (
+                savestate::mapper_chunk_id(self.mapper_number),
+                self.mapper.borrow().save_state(),
+            ),
+        ]
+    }
+
+    // chunks that savestate::check found to match state_chunks
+    pub fn load_state_chunks(&mut self, chunks: &[(ChunkId, &[u8])]) {
+        let u64_at = |state: &[u8], at: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&state[at..at + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let bus = chunks[0].1;
+        self.vram.copy_from_slice(&bus[..0x800]);
+        self.cycles = u64_at(bus, 0x800) as usize;
+        self.open_bus = bus[0x808];
+        self.strobe = bus[0x809] != 0;
+        self.overclock_cycles = u64_at(bus, 0x80A) as usize;
+        self.prg_ram.copy_from_slice(&bus[0x812..]);
+        // the board first, the ppu's timeline starts with its chr banks
+        self.mapper.borrow_mut().load_state(chunks[5].1);
+        self.ppu.load_state(chunks[1].1);
+        self.apu.load_state(chunks[2].1);
+        self.joypad1.load_state(chunks[3].1);
+        self.joypad2.load_state(chunks[4].1);
+    }
+
+    pub fn randomize_memory(&mut self, rng: &mut PowerOnRng) {
+        rng.fill(&mut self.vram);
+        self.ppu.randomize_memory(rng);
+    }
+
+    pub fn fill_memory_pattern(&mut self) {
+        config::fill_pattern(&mut self.vram);
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
+        if self.overclock_cycles > 0 {
+            self.overclock_cycles = self.overclock_cycles.saturating_sub(cycles as usize);
+            return;
+        }
+
+        let scanline = self.ppu.scanline();
+        self.ppu.tick(cycles as u16 * 3);
+        let expansion_audio = {
+            let mut mapper = self.mapper.borrow_mut();
+            mapper.tick(cycles);
+            mapper.audio_output()
+        };
+        if self.console.expansion_audio() {
+            self.apu.set_expansion_output(expansion_audio);
+        }
+        self.apu.tick(cycles);
+        if scanline != self.ppu.scanline() && self.ppu.on_vblank_scanline() {
+            self.overclock_cycles = self.overclock_scanlines as usize * CPU_CYCLES_PER_SCANLINE;
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/Cycle_reference_chart#Vertical_blanking
+        games that run out of cpu time in a frame slow down or skip a frame. giving the
+        cpu extra scanlines' worth of cycles right after vblank starts, with the ppu
+        and apu halted, lets the nmi handler finish earlier without moving anything the
+        game can see on screen. 0 (the default) keeps the hardware timing
+    */
+    pub fn set_overclock_scanlines(&mut self, scanlines: u16) {
+        self.overclock_scanlines = scanlines;
+    }
+
+    pub fn overclock_scanlines(&self) -> u16 {
+        self.overclock_scanlines
+    }
+
+    // https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDMA
+    fn oam_dma(&mut self, page: u8) {
+        let mut data = [0u8; 256];
+        for (i, byte) in data.iter_mut().enumerate() {
+            // everything else is an ordinary read, io registers and all their side effects
+            *byte = match page {
+                OAM_DMA_PPU_PAGE_BEGIN..=OAM_DMA_PPU_PAGE_END => self.open_bus,
+                _ => mem::Memory::mem_read(self, (page as u16) << 8 | i as u16),
+            };
+        }
+        self.ppu.write_oam_dma(&data);
+
+        // 513 cycles, plus one more to align when started on an odd cycle
+        let dma_cycles = 513 + self.cycles % 2;
+        for _ in 0..dma_cycles {
+            self.tick(1);
+        }
+    }
+
+    pub fn accuracy(&self) -> Accuracy {
+        self.accuracy
+    }
+
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.accuracy = accuracy;
+        self.ppu.set_oam_glitches(accuracy.oam_glitches());
+        if !accuracy.open_bus() {
+            self.open_bus = 0;
+        }
+    }
+
+    pub fn console(&self) -> Console {
+        self.console
+    }
+
+    pub fn set_console(&mut self, console: Console) {
+        self.console = console;
+        self.joypad2.set_missing(console.controller2_missing());
+        if !console.expansion_audio() {
+            self.apu.set_expansion_output(0.0);
+        }
+    }
+
+    pub fn unstable_magic(&self) -> u8 {
+        self.unstable_magic
+    }
+
+    // what ANE and LXA see of the chip's internal bus, to match a test rom or a console
+    pub fn set_unstable_magic(&mut self, magic: u8) {
+        self.unstable_magic = magic;
+    }
+
+    // held until released, only a famicom has the microphone
+    pub fn set_microphone(&mut self, loud: bool) {
+        self.microphone = loud;
+    }
+
+    fn microphone_bit(&self) -> u8 {
+        if self.microphone && self.console.microphone() {
+            0b0000_0100
+        } else {
+            0
+        }
+    }
+
+    // returns the device that was plugged in before
+    pub fn plug_expansion(
+        &mut self,
+        device: Option<Box<dyn ExpansionDevice>>,
+    ) -> Option<Box<dyn ExpansionDevice>> {
+        core::mem::replace(&mut self.expansion, device)
+    }
+
+    pub fn expansion_name(&self) -> Option<&'static str> {
+        self.expansion.as_ref().map(|device| device.name())
+    }
+
+    fn read_expansion(&mut self, port: Port) -> u8 {
+        match &mut self.expansion {
+            Some(device) => device.read(port),
+            None => 0,
+        }
+    }
+
+    fn peek_expansion(&self, port: Port) -> u8 {
+        match &self.expansion {
+            Some(device) => device.peek(port),
+            None => 0,
+        }
+    }
+
+    fn ppu_warming_up(&self) -> bool {
+        self.accuracy.ppu_warm_up() && self.cycles < PPU_WARM_UP_CYCLES
+    }
+
+    // the reset button. the ppu and apu reset their registers and keep running, memory,
+    // the cartridge and the controllers are left alone
+    pub fn reset(&mut self) {
+        self.ppu.reset();
+        self.apu.reset();
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    pub fn ppu(&self) -> &PPU {
+        &self.ppu
+    }
+
+    pub(crate) fn ppu_mut(&mut self) -> &mut PPU {
+        &mut self.ppu
+    }
+
+    pub fn apu(&mut self) -> &mut APU {
+        &mut self.apu
+    }
+
+    pub fn take_frame_complete(&mut self) -> bool {
+        self.ppu.take_frame_complete()
+    }
+
+    pub fn joypad1(&mut self) -> &mut Joypad {
+        &mut self.joypad1
+    }
+
+    pub fn joypad2(&mut self) -> &mut Joypad {
+        &mut self.joypad2
+    }
+
+    pub fn joypad(&mut self, port: Port) -> &mut Joypad {
+        match port {
+            Port::One => &mut self.joypad1,
+            Port::Two => &mut self.joypad2,
+        }
+    }
+
+    // true once after the game starts reading the controllers
+    pub fn take_strobe(&mut self) -> bool {
+        let strobe = self.strobe;
+        self.strobe = false;
+        strobe
+    }
+
+    pub fn should_nmi(&mut self) -> bool {
+        self.ppu.should_nmi()
+    }
+
+    // the apu and the cartridge share the /IRQ line, it stays low until acknowledged
+    pub fn irq_pending(&self) -> bool {
+        self.apu.irq_pending() || self.mapper.borrow().irq_pending()
+    }
+}
+
+impl mem::Memory for Bus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            RAM_BEGIN..=RAM_END => self.vram[cpu_ram_mirror(addr)],
+            PPU_REG_CTRL | PPU_REG_MASK | PPU_REG_OAMADDR | PPU_REG_SCROLL | PPU_REG_ADDR
+            | PPU_REG_OAMDMA => {
+                // write only, a bad program or a stray pointer must not bring the emulator down
+                self.open_bus
+            }
+            PPU_REG_STATUS => {
+                // the low 5 bits are stale bus contents
+                self.ppu.read_status() | (self.open_bus & 0b0001_1111)
+            }
+            PPU_REG_OAMDATA => self.ppu.read_oam_data(),
+            PPU_REG_DATA => self.ppu.read(),
+            PPU_REG_MIRROR_BEGIN..=PPU_REG_MIRROR_END => self.mem_read(ppu_reg_mirror(addr)),
+            APU_REG_PULSE1_BEGIN..=APU_REG_DMC_END => {
+                // write only apu registers
+                self.open_bus
+            }
+            // only the low bits are driven by the controller port
+            JOYPAD_1 => {
+                self.joypad1.read()
+                    | self.microphone_bit()
+                    | self.read_expansion(Port::One)
+                    | (self.open_bus & 0b1110_0000)
+            }
+            JOYPAD_2 => {
+                self.joypad2.read() | self.read_expansion(Port::Two) | (self.open_bus & 0b1110_0000)
+            }
+            APU_REG_STATUS => {
+                // bit 5 is not driven by the apu. the apu sits inside the cpu, so the value
+                // never reaches the external data bus and open bus keeps what it had
+                return self.apu.read_status() | (self.open_bus & 0b0010_0000);
+            }
+            APU_TEST_BEGIN..=APU_TEST_END => {
+                // cpu test mode is disabled on retail consoles
+                self.open_bus
+            }
+            CARTRIDGE_EXPANSION_BEGIN..=CARTRIDGE_EXPANSION_END => {
+                self.read_cartridge_expansion(addr)
+            }
+            PRG_RAM_BEGIN..=PRG_RAM_END => self.read_prg_ram_window(addr),
+            PRG_BEGIN..=PRG_END => {
+                // reading prg rom
+                self.read_prg_rom(addr)
+            }
+        };
+        if self.accuracy.open_bus() {
+            self.open_bus = data;
+        }
+        data
+    }
+    fn mem_peek(&self, addr: u16) -> u8 {
+        match addr {
+            RAM_BEGIN..=RAM_END => self.vram[cpu_ram_mirror(addr)],
+            PPU_REG_STATUS => self.ppu.peek_status() | (self.open_bus & 0b0001_1111),
+            PPU_REG_OAMDATA => self.ppu.read_oam_data(),
+            PPU_REG_DATA => self.ppu.peek(),
+            PPU_REG_MIRROR_BEGIN..=PPU_REG_MIRROR_END => self.mem_peek(ppu_reg_mirror(addr)),
+            JOYPAD_1 => {
+                self.joypad1.peek()
+                    | self.microphone_bit()
+                    | self.peek_expansion(Port::One)
+                    | (self.open_bus & 0b1110_0000)
+            }
+            JOYPAD_2 => {
+                self.joypad2.peek() | self.peek_expansion(Port::Two) | (self.open_bus & 0b1110_0000)
+            }
+            APU_REG_STATUS => self.apu.peek_status() | (self.open_bus & 0b0010_0000),
+            CARTRIDGE_EXPANSION_BEGIN..=CARTRIDGE_EXPANSION_END => {
+                self.read_cartridge_expansion(addr)
+            }
+            PRG_RAM_BEGIN..=PRG_RAM_END => self.read_prg_ram_window(addr),
+            PRG_BEGIN..=PRG_END => self.read_prg_rom(addr),
+            // write only registers and unmapped space
+            _ => self.open_bus,
+        }
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        if self.accuracy.open_bus() {
+            self.open_bus = data;
+        }
+
+        match addr {
+            RAM_BEGIN..=RAM_END => {
+                self.vram[cpu_ram_mirror(addr)] = data;
+            }
+            PPU_REG_CTRL | PPU_REG_MASK | PPU_REG_SCROLL | PPU_REG_ADDR
+                if self.ppu_warming_up() =>
+            {
+                // ignored, see PPU_WARM_UP_CYCLES
+            }
+            PPU_REG_CTRL => {
+                self.ppu.write_ctrl(data);
+            }
+            PPU_REG_MASK => {
+                self.ppu.mask_register.update_bits(data);
+            }
+            PPU_REG_STATUS => {
+                // read only
+            }
+            PPU_REG_OAMADDR => {
+                self.ppu.oam_address_register.write_oam_address(data);
+            }
+            PPU_REG_OAMDATA => {
+                self.ppu.write_oam_data(data);
+            }
+            PPU_REG_SCROLL => {
+                self.ppu.write_scroll(data);
+            }
+            PPU_REG_ADDR => {
+                self.ppu.write_address(data);
+            }
+            PPU_REG_DATA => {
+                self.ppu.write(data);
+            }
+            PPU_REG_MIRROR_BEGIN..=PPU_REG_MIRROR_END => {
+                self.mem_write(ppu_reg_mirror(addr), data);
+            }
+            APU_REG_PULSE1_BEGIN..=APU_REG_DMC_END => {
+                self.apu.write_register(addr, data);
+            }
+            PPU_REG_OAMDMA => {
+                self.oam_dma(data);
+            }
+            APU_REG_STATUS => {
+                self.apu.write_status(data);
+            }
+            JOYPAD_1 => {
+                // the strobe line is shared by both controller ports
+                if data & 0b0000_0001 != 0 {
+                    self.strobe = true;
+                }
+                self.joypad1.write(data);
+                self.joypad2.write(data);
+                if let Some(device) = &mut self.expansion {
+                    device.write(data & 0b0000_0111);
+                }
+            }
+            APU_REG_FRAME_COUNTER => {
+                self.apu.write_frame_counter(data);
+            }
+            APU_TEST_BEGIN..=APU_TEST_END => {
+                // cpu test mode is disabled on retail consoles
+            }
+            PRG_RAM_BEGIN..=PRG_RAM_END => {
+                self.write_prg_ram_window(addr, data);
+            }
+            PRG_BEGIN..=PRG_END => {
+                // mapper registers live in the rom area
+                self.mapper.borrow_mut().write_prg(addr, data);
+            }
+            _ => {
+                log!("ignore writing memory to: {:#02X}", addr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::create_rom;
+    use crate::mem::Memory;
+
+    #[test]
+    fn test_prg_ram_probe() {
+        // games probe for ram by writing a pattern and reading it back
+        let raw = create_rom(0b0000_0010, 0, vec![0; 0x8000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.mem_write(0x6000, 0x55);
+        bus.mem_write(0x7FFF, 0xAA);
+
+        assert_eq!(bus.mem_read(0x6000), 0x55);
+        assert_eq!(bus.mem_read(0x7FFF), 0xAA);
+    }
+
+    #[test]
+    fn test_apu_status_read() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        for _ in 0..15000 {
+            bus.tick(2);
+        }
+
+        assert_eq!(bus.mem_read(APU_REG_STATUS) & 0b0100_0000, 0b0100_0000);
+        assert_eq!(bus.mem_read(APU_REG_STATUS) & 0b0100_0000, 0);
+    }
+
+    #[test]
+    fn test_apu_test_registers_open_bus() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.mem_write(0x0000, 0x42);
+        bus.mem_read(0x0000);
+
+        assert_eq!(bus.mem_read(0x4018), 0x42);
+        assert_eq!(bus.mem_read(0x401F), 0x42);
+    }
+
+    #[test]
+    fn test_apu_status_read_keeps_open_bus() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        for _ in 0..15000 {
+            bus.tick(2);
+        }
+        bus.mem_write(0x0000, 0x02);
+        bus.mem_read(0x0000);
+
+        assert_eq!(bus.mem_read(APU_REG_STATUS), 0b0100_0000);
+        assert_eq!(bus.mem_read(0x4018), 0x02);
+    }
+
+    #[test]
+    fn test_wrong_direction_ppu_access() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.mem_write(PPU_REG_STATUS, 0x42);
+
+        // write only registers read back whatever was last on the bus
+        assert_eq!(bus.mem_read(PPU_REG_CTRL), 0x42);
+        assert_eq!(bus.mem_read(PPU_REG_ADDR), 0x42);
+    }
+
+    #[test]
+    fn test_oam_dma_wraps_at_oam_address() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        for i in 0..=0xFF {
+            bus.mem_write(0x0200 + i, i as u8);
+        }
+
+        bus.mem_write(PPU_REG_OAMADDR, 0x10);
+        bus.mem_write(PPU_REG_OAMDMA, 0x02);
+
+        assert_eq!(bus.ppu().oam[0x10], 0x00);
+        assert_eq!(bus.ppu().oam[0xFF], 0xEF);
+        assert_eq!(bus.ppu().oam[0x00], 0xF0);
+        assert_eq!(bus.ppu().oam[0x0F], 0xFF);
+        // 256 writes bring OAMADDR back to where it started
+        assert_eq!(bus.mem_read(PPU_REG_OAMDATA), 0x00);
+    }
+
+    #[test]
+    fn test_oam_dma_from_io_page() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        for _ in 0..15000 {
+            bus.tick(2);
+        }
+        bus.joypad1().set_buttons(JoypadButton::BUTTON_A);
+        bus.mem_write(JOYPAD_1, 1);
+        bus.mem_write(JOYPAD_1, 0);
+        bus.mem_write(PPU_REG_OAMDMA, 0x40);
+
+        let oam = bus.ppu().oam;
+        // write only registers give the page number written to $4014, left on the bus
+        assert_eq!(oam[0x00], 0x40);
+        assert_eq!(oam[0x14], 0x40);
+        // the copy reads $4015 and $4016 like the cpu would, acknowledging the frame irq
+        // and clocking the controller
+        assert_eq!(oam[0x15] & 0b0100_0000, 0b0100_0000);
+        assert_eq!(bus.mem_read(APU_REG_STATUS) & 0b0100_0000, 0);
+        assert_eq!(oam[0x16] & 1, 1);
+        assert_eq!(bus.mem_read(JOYPAD_1) & 1, 0);
+        // no board here answers in the cartridge's part of the page
+        assert_eq!(oam[0xFF], oam[0x1F]);
+    }
+
+    #[test]
+    fn test_peek_has_no_side_effects() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        for _ in 0..15000 {
+            bus.tick(2);
+        }
+        bus.joypad1().set_buttons(JoypadButton::BUTTON_A);
+        bus.mem_write(JOYPAD_1, 1);
+        bus.mem_write(JOYPAD_1, 0);
+
+        assert_eq!(bus.mem_peek(APU_REG_STATUS) & 0b0100_0000, 0b0100_0000);
+        assert_eq!(bus.mem_peek(JOYPAD_1) & 1, 1);
+        assert_eq!(bus.mem_read(APU_REG_STATUS) & 0b0100_0000, 0b0100_0000);
+        assert_eq!(bus.mem_read(JOYPAD_1) & 1, 1);
+        assert_eq!(bus.mem_peek(JOYPAD_1) & 1, 0);
+    }
+
+    #[test]
+    fn test_fast_accuracy_has_no_open_bus() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.set_accuracy(Accuracy::Fast);
+        bus.mem_write(0x0000, 0x42);
+        bus.mem_read(0x0000);
+
+        assert_eq!(bus.mem_read(0x4018), 0);
+        assert_eq!(bus.mem_read(PPU_REG_CTRL), 0);
+    }
+
+    #[test]
+    fn test_ppu_warm_up() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.set_accuracy(Accuracy::Cycle);
+        bus.mem_write(PPU_REG_ADDR, 0x21);
+        bus.mem_write(PPU_REG_ADDR, 0x00);
+        assert_eq!(bus.ppu().address_register.get_address(), 0x0000);
+        // oam is not part of it
+        bus.mem_write(PPU_REG_OAMDATA, 0x42);
+        assert_eq!(bus.ppu().oam[0], 0x42);
+
+        while bus.cycles() < PPU_WARM_UP_CYCLES {
+            bus.tick(2);
+        }
+        bus.mem_write(PPU_REG_ADDR, 0x21);
+        bus.mem_write(PPU_REG_ADDR, 0x00);
+        assert_eq!(bus.ppu().address_register.get_address(), 0x2100);
+    }
+
+    #[test]
+    fn test_ppu_register_mirror() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        // $200B is OAMADDR, $3FFC OAMDATA
+        bus.mem_write(0x200B, 0x40);
+        assert_eq!(bus.ppu().oam_address_register.get_oam_address(), 0x40);
+        bus.mem_write(0x3FFC, 0x99);
+        assert_eq!(bus.ppu().oam[0x40], 0x99);
+    }
+
+    #[test]
+    fn test_prg_ram_absent() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.mem_write(0x6000, 0x55);
+
+        assert_eq!(bus.mem_read(0x6000), 0);
+    }
+
+    #[test]
+    fn test_console() {
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(cartridge::Cartridge::new(&raw).unwrap());
+        bus.joypad2()
+            .set_buttons(JoypadButton::START | JoypadButton::UP);
+        bus.set_microphone(true);
+        // the microphone bit of $4016 and the 8 buttons of $4017
+        let read_pads = |bus: &mut Bus| {
+            bus.mem_write(JOYPAD_1, 1);
+            bus.mem_write(JOYPAD_1, 0);
+            let pad2: Vec<u8> = (0..8).map(|_| bus.mem_read(JOYPAD_2)).collect();
+            (bus.mem_read(JOYPAD_1) & 0b100, pad2)
+        };
+
+        assert_eq!(read_pads(&mut bus), (0, vec![0, 0, 0, 1, 1, 0, 0, 0]));
+        // start is gone from controller 2, its microphone is there instead
+        bus.set_console(Console::Famicom);
+        assert_eq!(read_pads(&mut bus), (0b100, vec![0, 0, 0, 0, 1, 0, 0, 0]));
+        bus.set_console(Console::AvFamicom);
+        assert_eq!(read_pads(&mut bus), (0, vec![0, 0, 0, 1, 1, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::crc32;
+use crate::gamedb::{self, GameInfo};
+use crate::patch::{self, PatchFormat};
+use crate::sha1::Sha1;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const NES_MAGIC_NUMBER: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+
+const PRG_ROM_PAGE_SIZE: usize = 16384;
+const CHR_ROM_PAGE_SIZE: usize = 8192;
+const PRG_RAM_PAGE_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MirroringType {
+    Vertical,
+    Horizontal,
+    FourScreen,
+    // set by mappers like AxROM, MMC1 that map all four nametables to one page
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+// https://wiki.nesdev.com/w/index.php/INES#Flags_7
+// arcade boards sold alongside the console, flagged in the low bits of control byte 2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    PlayChoice10,
+}
+
+pub struct Cartridge {
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub mapper: u8,
+    pub mirroring_type: MirroringType,
+    pub prg_ram_size: usize,
+    pub has_battery_backed_ram: bool,
+    pub console_type: ConsoleType,
+    // of PRG+CHR, which is how rom databases identify a dump
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+    // canonical name, known when the game is in the database
+    pub title: Option<String>,
+    // the file as loaded, which is what patches are made against
+    pub raw: Vec<u8>,
+}
+
+impl Cartridge {
+    pub fn new(raw: &[u8]) -> Result<Self, String> {
+        if raw.len() < 16 || &raw[0..4] != NES_MAGIC_NUMBER {
+            return Err(String::from("not valid nes cartridge!"));
+        }
+
+        let num_of_prg_banks = raw[4] as usize;
+        let num_of_chr_banks = raw[5] as usize;
+        let ctrl_byte_one = raw[6];
+        let ctrl_byte_two = raw[7];
+
+        let size_of_prg_ram_in_8k = raw[8];
+        let reserved = raw[9];
+
+        let has_battery_backed_ram = ctrl_byte_one & 0b0000_0010 != 0;
+        let has_trainer = ctrl_byte_one & 0b0000_0100 != 0;
+        let has_four_scrren_vram_layout = ctrl_byte_one & 0b0000_1000 != 0;
+
+        let console_type = match ctrl_byte_two & 0b0000_0011 {
+            0b00 => ConsoleType::Nes,
+            0b01 => ConsoleType::VsSystem,
+            0b10 => ConsoleType::PlayChoice10,
+            _ => return Err(String::from("not valid iNES 1.0 cartridge!")),
+        };
+
+        // vs system games expect coin slots, dip switches and one of several scrambled
+        // palettes, none of which is emulated
+        if console_type == ConsoleType::VsSystem {
+            return Err(String::from(
+                "VS System dumps are not supported, look for the NES release of the game!",
+            ));
+        }
+
+        if ctrl_byte_two & 0b0000_1100 == 2 {
+            return Err(String::from("not support iNES 2.0 cartridge!"));
+        }
+
+        let is_vertical_mirroring = ctrl_byte_one & 0b0000_0001 != 0;
+        let mirroring_type = match (has_four_scrren_vram_layout, is_vertical_mirroring) {
+            (true, _) => MirroringType::FourScreen,
+            (false, false) => MirroringType::Horizontal,
+            (false, true) => MirroringType::Vertical,
+        };
+
+        let mapper = (ctrl_byte_two & 0b1111_0000) | (ctrl_byte_one >> 4);
+
+        // https://wiki.nesdev.com/w/index.php/INES#Flags_8
+        // a zero here is ambiguous: most NROM boards have no PRG RAM at all, but boards
+        // with a battery (Family BASIC) or a real mapper usually have 8KB at $6000-$7FFF.
+        let prg_ram_size = match (size_of_prg_ram_in_8k, has_battery_backed_ram, mapper) {
+            (0, false, 0) => 0,
+            (0, _, _) => PRG_RAM_PAGE_SIZE,
+            (n, _, _) => n as usize * PRG_RAM_PAGE_SIZE,
+        };
+
+        let size_of_prg_rom = num_of_prg_banks * PRG_ROM_PAGE_SIZE;
+        let size_of_chr_rom = num_of_chr_banks * CHR_ROM_PAGE_SIZE;
+
+        let entry_point_of_prg_rom = 16 + if has_trainer { 512 } else { 0 };
+        let entry_point_of_chr_rom = entry_point_of_prg_rom + size_of_prg_rom;
+
+        if size_of_prg_rom == 0 {
+            return Err(String::from("cartridge has no PRG ROM!"));
+        }
+
+        if raw.len() < entry_point_of_chr_rom + size_of_chr_rom {
+            return Err(String::from("cartridge is truncated!"));
+        }
+
+        // playchoice-10 dumps append the 8KB INST-ROM with the hint screens and the
+        // PROM after CHR. the game itself is a plain NES game, so those are just skipped
+        if console_type == ConsoleType::PlayChoice10 {
+            log!("PlayChoice-10 dump, running it as a NES game without the INST-ROM");
+        }
+
+        let prg = raw[entry_point_of_prg_rom..(entry_point_of_prg_rom + size_of_prg_rom)].to_vec();
+        let chr = raw[entry_point_of_chr_rom..(entry_point_of_chr_rom + size_of_chr_rom)].to_vec();
+
+        let crc32 = crc32::update(crc32::crc32(&prg), &chr);
+        let mut sha1 = Sha1::new();
+        sha1.update(&prg);
+        sha1.update(&chr);
+
+        let mut cartridge = Cartridge {
+            prg: prg,
+            chr: chr,
+            mapper: mapper,
+            mirroring_type: mirroring_type,
+            prg_ram_size: prg_ram_size,
+            has_battery_backed_ram: has_battery_backed_ram,
+            console_type: console_type,
+            crc32: crc32,
+            sha1: sha1.finish(),
+            title: None,
+            raw: raw.to_vec(),
+        };
+
+        // the database knows better than headers written by old dumping tools
+        if let Some(game) = gamedb::lookup(cartridge.crc32, &cartridge.sha1) {
+            let fixes = cartridge.apply_game_info(&game);
+            if !fixes.is_empty() {
+                log!(
+                    "{}: corrected bad iNES header, {}",
+                    game.title,
+                    fixes.join(", ")
+                );
+            }
+        }
+
+        return Ok(cartridge);
+    }
+
+    // becomes the cartridge `patch` turns this one into. header changes in the patch
+    // apply too, and the checksums are the patched rom's. on error nothing changes
+    pub fn apply_patch(&mut self, format: PatchFormat, patch: &[u8]) -> Result<(), String> {
+        let patched = patch::apply(format, &self.raw, patch)?;
+        *self = Cartridge::new(&patched)?;
+        Ok(())
+    }
+
+    // overrides the header fields with the database ones, returns what had to change
+    pub fn apply_game_info(&mut self, game: &GameInfo) -> Vec<String> {
+        let mut fixes = Vec::new();
+        if self.mapper != game.mapper {
+            fixes.push(format!("mapper {} -> {}", self.mapper, game.mapper));
+            self.mapper = game.mapper;
+        }
+        if self.mirroring_type != game.mirroring {
+            fixes.push(format!(
+                "mirroring {:?} -> {:?}",
+                self.mirroring_type, game.mirroring
+            ));
+            self.mirroring_type = game.mirroring;
+        }
+        if self.prg_ram_size != game.prg_ram_size {
+            fixes.push(format!(
+                "PRG RAM {}KB -> {}KB",
+                self.prg_ram_size / 1024,
+                game.prg_ram_size / 1024
+            ));
+            self.prg_ram_size = game.prg_ram_size;
+        }
+        self.title = Some(game.title.clone());
+        fixes
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    pub fn create_rom(ctrl_byte_one: u8, size_of_prg_ram_in_8k: u8, prg: Vec<u8>) -> Vec<u8> {
+        let num_of_prg_banks = (prg.len() + PRG_ROM_PAGE_SIZE - 1) / PRG_ROM_PAGE_SIZE;
+        let size_of_rom = 16 + num_of_prg_banks * PRG_ROM_PAGE_SIZE + CHR_ROM_PAGE_SIZE;
+
+        let mut raw = NES_MAGIC_NUMBER.to_vec();
+        raw.extend(&[num_of_prg_banks as u8, 1, ctrl_byte_one, 0]);
+        raw.extend(&[size_of_prg_ram_in_8k, 0, 0, 0, 0, 0, 0, 0]);
+        raw.extend(prg.iter());
+        raw.resize(size_of_rom, 0);
+        raw
+    }
+
+    #[test]
+    fn test_prg_ram_size() {
+        let nrom = Cartridge::new(&create_rom(0b0000_0000, 0, vec![0; 0x4000])).unwrap();
+        assert_eq!(nrom.prg_ram_size, 0);
+
+        // Family BASIC style board: battery-backed RAM without a size in the header
+        let family_basic = Cartridge::new(&create_rom(0b0000_0010, 0, vec![0; 0x8000])).unwrap();
+        assert_eq!(family_basic.prg_ram_size, 0x2000);
+        assert!(family_basic.has_battery_backed_ram);
+
+        let sized = Cartridge::new(&create_rom(0b0000_0000, 2, vec![0; 0x4000])).unwrap();
+        assert_eq!(sized.prg_ram_size, 0x4000);
+    }
+
+    #[test]
+    #[cfg(feature = "gamedb")]
+    fn test_gamedb_lookup() {
+        let mut raw = include_bytes!("../res/test.nes").to_vec();
+        let nestest = Cartridge::new(&raw).unwrap();
+        assert_eq!(nestest.crc32, 0x0628_A9F6);
+        assert_eq!(nestest.title.as_deref(), Some("nestest"));
+
+        // a header claiming vertical mirroring and mapper 7 is corrected by the database
+        raw[6] |= 0b0111_0001;
+        let fixed = Cartridge::new(&raw).unwrap();
+        assert_eq!(fixed.mirroring_type, MirroringType::Horizontal);
+        assert_eq!(fixed.mapper, 0);
+        assert_eq!(fixed.prg_ram_size, 0);
+
+        let unknown = Cartridge::new(&create_rom(0b0000_0000, 0, vec![0; 0x4000])).unwrap();
+        assert_eq!(unknown.title, None);
+    }
+
+    #[test]
+    fn test_console_type() {
+        let mut raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        assert_eq!(Cartridge::new(&raw).unwrap().console_type, ConsoleType::Nes);
+
+        raw[7] = 0b0000_0010;
+        raw.extend(vec![0; 0x2000 + 32]);
+        assert_eq!(
+            Cartridge::new(&raw).unwrap().console_type,
+            ConsoleType::PlayChoice10
+        );
+
+        raw[7] = 0b0000_0001;
+        assert!(Cartridge::new(&raw)
+            .err()
+            .unwrap()
+            .starts_with("VS System dumps are not supported"));
+
+        raw[7] = 0b0000_0011;
+        assert!(Cartridge::new(&raw).is_err());
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut cartridge = Cartridge::new(&create_rom(0b0000_0000, 0, vec![0; 0x4000])).unwrap();
+        let crc32 = cartridge.crc32;
+        // the header counts, so prg starts at 16. the second record sets vertical mirroring
+        let mut ips = b"PATCH".to_vec();
+        ips.extend_from_slice(&[0, 0, 16, 0, 2, 0xEA, 0xEA]);
+        ips.extend_from_slice(&[0, 0, 6, 0, 1, 0b0000_0001]);
+        ips.extend_from_slice(b"EOF");
+        cartridge.apply_patch(PatchFormat::Ips, &ips).unwrap();
+        assert_eq!(&cartridge.prg[0..3], &[0xEA, 0xEA, 0]);
+        assert_eq!(cartridge.mirroring_type, MirroringType::Vertical);
+        assert_ne!(cartridge.crc32, crc32);
+
+        // a patch that breaks the header leaves the cartridge as it was
+        let mut breaking = b"PATCH".to_vec();
+        breaking.extend_from_slice(&[0, 0, 0, 0, 1, b'X']);
+        breaking.extend_from_slice(b"EOF");
+        assert!(cartridge.apply_patch(PatchFormat::Ips, &breaking).is_err());
+        assert_eq!(cartridge.prg[0], 0xEA);
+    }
+
+    #[test]
+    fn test_apply_game_info() {
+        let mut cartridge = Cartridge::new(&create_rom(0b0000_0001, 0, vec![0; 0x4000])).unwrap();
+        let game = GameInfo {
+            crc32: cartridge.crc32,
+            sha1: cartridge.sha1,
+            mapper: 7,
+            mirroring: MirroringType::Vertical,
+            prg_ram_size: 0x2000,
+            title: String::from("Some Game"),
+        };
+
+        assert_eq!(
+            cartridge.apply_game_info(&game),
+            vec![
+                String::from("mapper 0 -> 7"),
+                String::from("PRG RAM 0KB -> 8KB"),
+            ]
+        );
+        assert_eq!(cartridge.mapper, 7);
+        assert_eq!(cartridge.prg_ram_size, 0x2000);
+        assert_eq!(cartridge.title.as_deref(), Some("Some Game"));
+        assert!(cartridge.apply_game_info(&game).is_empty());
+    }
+}
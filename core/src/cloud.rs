@@ -0,0 +1,208 @@
+// keeps battery saves and savestates in step with a copy somewhere else, so progress
+// follows the player between devices. the core does not talk to the network itself, the
+// frontend implements SyncBackend over whatever the player points it at (a webdav folder,
+// a small http api) and calls sync when a game is loaded and after it saves:
+//
+//     let key = SaveKey::sram(&rom);
+//     let local = SaveBlob { data: nes.save_sram(), modified: sram_saved_at };
+//     if let SyncOutcome::Downloaded(blob) = sync(&mut backend, &key, Some(&local))? {
+//         nes.load_sram(&blob.data);
+//     }
+//
+// saves are kept by the sha1 of the rom, a renamed or re-dumped file with the same
+// contents finds them again. conflicts go to the newer copy, timestamps are milliseconds
+// since the unix epoch from the frontend's clock
+
+use crate::sha1;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SaveKind {
+    Sram,
+    // a savestate slot, see Nes::save_state
+    State(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SaveKey {
+    pub rom_sha1: [u8; 20],
+    pub kind: SaveKind,
+}
+
+impl SaveKey {
+    pub fn sram(rom: &[u8]) -> Self {
+        SaveKey {
+            rom_sha1: sha1::sha1(rom),
+            kind: SaveKind::Sram,
+        }
+    }
+
+    pub fn state(rom: &[u8], slot: u8) -> Self {
+        SaveKey {
+            rom_sha1: sha1::sha1(rom),
+            kind: SaveKind::State(slot),
+        }
+    }
+
+    // "<sha1>/sram" or "<sha1>/state<slot>", a folder per game for path based backends
+    pub fn path(&self) -> String {
+        match self.kind {
+            SaveKind::Sram => format!("{}/sram", sha1::to_hex(&self.rom_sha1)),
+            SaveKind::State(slot) => format!("{}/state{}", sha1::to_hex(&self.rom_sha1), slot),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveBlob {
+    pub data: Vec<u8>,
+    // when the save was made, not when it was uploaded
+    pub modified: u64,
+}
+
+// a place saves are kept. errors are the backend's own words, for the player to read
+pub trait SyncBackend {
+    // when the stored copy was made, None when there is none. checked before every
+    // transfer so nothing is downloaded that would not be used
+    fn modified(&mut self, key: &SaveKey) -> Result<Option<u64>, String>;
+
+    fn download(&mut self, key: &SaveKey) -> Result<Option<SaveBlob>, String>;
+
+    // replaces the stored copy, keeping blob.modified as its time
+    fn upload(&mut self, key: &SaveKey, blob: &SaveBlob) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncOutcome {
+    // the local copy was newer or the backend had none
+    Uploaded,
+    // the backend's copy is newer, the frontend loads it and keeps it as its own
+    Downloaded(SaveBlob),
+    // both are the same age or neither exists
+    UpToDate,
+}
+
+// makes the local copy and the backend's agree on whichever is newer. `local` is None when
+// this device has never saved the game
+pub fn sync(
+    backend: &mut dyn SyncBackend,
+    key: &SaveKey,
+    local: Option<&SaveBlob>,
+) -> Result<SyncOutcome, String> {
+    let remote = backend.modified(key)?;
+    let upload = match (local, remote) {
+        (None, None) => return Ok(SyncOutcome::UpToDate),
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (Some(local), Some(remote)) if local.modified == remote => {
+            return Ok(SyncOutcome::UpToDate)
+        }
+        (Some(local), Some(remote)) => local.modified > remote,
+    };
+
+    if upload {
+        backend.upload(key, local.unwrap())?;
+        return Ok(SyncOutcome::Uploaded);
+    }
+    match backend.download(key)? {
+        Some(blob) => Ok(SyncOutcome::Downloaded(blob)),
+        None => Err(format!("{} went away while syncing", key.path())),
+    }
+}
+
+// keeps everything in memory, for tests and as the local end of a frontend's own cache
+#[derive(Default)]
+pub struct MemoryBackend {
+    saves: BTreeMap<SaveKey, SaveBlob>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.saves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.saves.is_empty()
+    }
+}
+
+impl SyncBackend for MemoryBackend {
+    fn modified(&mut self, key: &SaveKey) -> Result<Option<u64>, String> {
+        Ok(self.saves.get(key).map(|blob| blob.modified))
+    }
+
+    fn download(&mut self, key: &SaveKey) -> Result<Option<SaveBlob>, String> {
+        Ok(self.saves.get(key).cloned())
+    }
+
+    fn upload(&mut self, key: &SaveKey, blob: &SaveBlob) -> Result<(), String> {
+        self.saves.insert(*key, blob.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn blob(data: &[u8], modified: u64) -> SaveBlob {
+        SaveBlob {
+            data: data.to_vec(),
+            modified: modified,
+        }
+    }
+
+    #[test]
+    fn test_sync() {
+        let rom = include_bytes!("../res/test.nes");
+        let sram = SaveKey::sram(rom);
+        let state = SaveKey::state(rom, 2);
+        assert_eq!(
+            sram.path(),
+            format!("{}/sram", sha1::to_hex(&sha1::sha1(rom)))
+        );
+        assert!(state.path().ends_with("/state2"));
+
+        let mut backend = MemoryBackend::new();
+        assert_eq!(sync(&mut backend, &sram, None), Ok(SyncOutcome::UpToDate));
+        // the first device to save uploads
+        let first = blob(&[1, 2, 3], 1000);
+        assert_eq!(
+            sync(&mut backend, &sram, Some(&first)),
+            Ok(SyncOutcome::Uploaded)
+        );
+        // a second device without a save gets it
+        assert_eq!(
+            sync(&mut backend, &sram, None),
+            Ok(SyncOutcome::Downloaded(first.clone()))
+        );
+        assert_eq!(
+            sync(&mut backend, &sram, Some(&first)),
+            Ok(SyncOutcome::UpToDate)
+        );
+
+        // the newer copy wins either way
+        let newer = blob(&[4, 5, 6], 2000);
+        assert_eq!(
+            sync(&mut backend, &sram, Some(&newer)),
+            Ok(SyncOutcome::Uploaded)
+        );
+        assert_eq!(
+            sync(&mut backend, &sram, Some(&first)),
+            Ok(SyncOutcome::Downloaded(newer))
+        );
+
+        // slots and saves of other games stay apart
+        assert_eq!(sync(&mut backend, &state, None), Ok(SyncOutcome::UpToDate));
+        let other = SaveKey::sram(&rom[1..]);
+        assert_eq!(sync(&mut backend, &other, None), Ok(SyncOutcome::UpToDate));
+        assert_eq!(backend.len(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+// the compatibility database: how well each known game runs, by the crc32 of its prg and
+// chr like the gamedb. res/compat.json is not written by hand, the regression tests
+// generate it from a survey of the regression roms and their movies (run them with
+// FEUERNES_UPDATE_GOLDEN=1), and the survey example writes one for any rom set to merge in
+
+use crate::json::{self, Value};
+use crate::survey::{Status, SurveyEntry};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compatibility {
+    // plays, or at least gets past its title screen
+    Works,
+    // runs but something is off, a blank screen after the survey for example
+    Issues,
+    // does not get anywhere
+    Broken,
+}
+
+impl Compatibility {
+    // the name in the json file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compatibility::Works => "works",
+            Compatibility::Issues => "issues",
+            Compatibility::Broken => "broken",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Compatibility::Works => "Works",
+            Compatibility::Issues => "Issues",
+            Compatibility::Broken => "Broken",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "works" => Ok(Compatibility::Works),
+            "issues" => Ok(Compatibility::Issues),
+            "broken" => Ok(Compatibility::Broken),
+            _ => Err(format!("unknown compatibility {:?}", name)),
+        }
+    }
+
+    pub fn from_survey(status: &Status) -> Self {
+        match status {
+            Status::Booted => Compatibility::Works,
+            Status::Blank => Compatibility::Issues,
+            Status::IllegalOpcode { .. } | Status::BadRom(_) | Status::Panic(_) => {
+                Compatibility::Broken
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompatEntry {
+    pub crc32: u32,
+    pub title: String,
+    pub status: Compatibility,
+    // what the status is based on
+    pub note: String,
+}
+
+impl CompatEntry {
+    // None for a rom that did not load, there is no checksum to file it under
+    pub fn from_survey(entry: &SurveyEntry) -> Option<Self> {
+        let detail = entry.status.detail();
+        let note = if detail.is_empty() {
+            format!("survey: {}", entry.status.name())
+        } else {
+            format!("survey: {} {}", entry.status.name(), detail)
+        };
+        Some(CompatEntry {
+            crc32: entry.crc32?,
+            title: entry.name.clone(),
+            status: Compatibility::from_survey(&entry.status),
+            note: note,
+        })
+    }
+
+    // a movie that plays back in sync shows more than any survey can
+    pub fn movie_in_sync(&mut self, movie: &str) {
+        self.status = Compatibility::Works;
+        self.note = format!("movie: {} in sync", movie);
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object(vec![
+            ("crc32", Value::from(format!("{:08X}", self.crc32))),
+            ("title", Value::from(self.title.clone())),
+            ("status", Value::from(self.status.name())),
+            ("note", Value::from(self.note.clone())),
+        ])
+    }
+
+    fn parse(value: &Value) -> Result<Self, String> {
+        let crc32 = value
+            .get("crc32")
+            .and_then(Value::as_str)
+            .ok_or("missing crc32")?;
+        let crc32 = u32::from_str_radix(crc32, 16).map_err(|_| format!("bad crc32 {:?}", crc32))?;
+        let status = value
+            .get("status")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{:08X}: missing status", crc32))?;
+        let text = |key| value.get(key).and_then(Value::as_str).unwrap_or("");
+        Ok(CompatEntry {
+            crc32: crc32,
+            title: String::from(text("title")),
+            status: Compatibility::parse(status)?,
+            note: String::from(text("note")),
+        })
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<CompatEntry>, String> {
+    json::parse(text)?
+        .as_array()
+        .ok_or("not an array of games")?
+        .iter()
+        .map(CompatEntry::parse)
+        .collect()
+}
+
+// one game per line so a regenerated file diffs well
+pub fn to_json(entries: &[CompatEntry]) -> String {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("  {}", entry.to_json()))
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+#[cfg(feature = "compat")]
+static DATABASE: &str = include_str!("../res/compat.json");
+
+// parsed again on every lookup like the gamedb, it is only asked when a list of roms is shown
+#[cfg(feature = "compat")]
+pub fn lookup(crc32: u32) -> Option<CompatEntry> {
+    parse(DATABASE)
+        .ok()?
+        .into_iter()
+        .find(|entry| entry.crc32 == crc32)
+}
+
+#[cfg(not(feature = "compat"))]
+pub fn lookup(_crc32: u32) -> Option<CompatEntry> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::survey;
+
+    #[test]
+    fn test_compat() {
+        let nestest = include_bytes!("../res/test.nes");
+        let mut entry =
+            CompatEntry::from_survey(&survey::survey_rom("nestest.nes", nestest, 30)).unwrap();
+        assert_eq!(entry.crc32, 0x0628_A9F6);
+        assert_eq!(entry.status, Compatibility::Works);
+        assert_eq!(entry.note, "survey: boot ok");
+        assert!(CompatEntry::from_survey(&survey::survey_rom("empty.nes", &[], 30)).is_none());
+
+        let snake = include_bytes!("../res/snake.nes");
+        let mut snake =
+            CompatEntry::from_survey(&survey::survey_rom("snake.nes", snake, 30)).unwrap();
+        assert_eq!(snake.status, Compatibility::Issues);
+        snake.movie_in_sync("snake.fm2");
+        assert_eq!(snake.status, Compatibility::Works);
+
+        entry.status = Compatibility::Broken;
+        let entries = vec![entry, snake];
+        let text = to_json(&entries);
+        assert!(text.starts_with("[\n  {\"crc32\":\"0628A9F6\",\"title\":\"nestest.nes\""));
+        assert_eq!(parse(&text), Ok(entries));
+        assert!(parse("[{\"crc32\":\"0628A9F6\",\"status\":\"fine\"}]").is_err());
+        assert!(parse("{}").is_err());
+    }
+
+    #[cfg(feature = "compat")]
+    #[test]
+    fn test_lookup() {
+        assert!(parse(DATABASE).is_ok());
+        let nestest = lookup(0x0628_A9F6).unwrap();
+        assert_eq!(nestest.status, Compatibility::Works);
+        assert_eq!(lookup(0), None);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::joypad::JoypadButton;
+
+// what ram, vram, palette ram and oam contain when the console is switched on.
+// real hardware powers on with unpredictable contents, randomizing them (seeded, so runs
+// stay reproducible) catches homebrew that forgets to initialize memory.
+// many consoles come up with ram in alternating runs of four $00 and four $FF bytes,
+// which is what Pattern reproduces for the cpu ram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerOnRam {
+    Zero,
+    Random { seed: u64 },
+    Pattern,
+}
+
+// what a, x and y hold after power-on. they are undefined on hardware, while the status
+// ($34) and sp ($FD) always come out of the reset sequence the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerOnCpu {
+    Zero,
+    Random { seed: u64 },
+}
+
+// how closely the bus and the cpu follow the hardware, traded against speed. each preset
+// bundles the individual toggles below so a frontend only needs one switch.
+//   Fast      reads of write-only and unmapped addresses return 0
+//   Balanced  open bus, reads nothing responds to return the last value on the data bus
+//   Cycle     plus the dummy write of read-modify-write instructions, the dummy read of
+//             indexed stores and read-modify-writes, the ppu warm-up and the OAMADDR and
+//             OAMDATA glitches while rendering
+// there is no dot-accurate ppu yet, the ppu runs the same way in every preset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accuracy {
+    Fast,
+    Balanced,
+    Cycle,
+}
+
+impl Accuracy {
+    pub fn open_bus(&self) -> bool {
+        *self != Accuracy::Fast
+    }
+
+    pub fn dummy_writes(&self) -> bool {
+        *self == Accuracy::Cycle
+    }
+
+    pub fn dummy_reads(&self) -> bool {
+        *self == Accuracy::Cycle
+    }
+
+    pub fn ppu_warm_up(&self) -> bool {
+        *self == Accuracy::Cycle
+    }
+
+    pub fn oam_glitches(&self) -> bool {
+        *self == Accuracy::Cycle
+    }
+}
+
+// what ANE and LXA OR into a before they AND it (see cpu::instructions::unofficial). it
+// comes from the chip's internal bus and differs between cpus, with temperature and even
+// between runs; $00, $EE and $FF are all seen on real consoles. $EE is the usual pick of
+// test roms and other emulators, a fixed value keeps runs reproducible
+pub const UNSTABLE_MAGIC: u8 = 0xEE;
+
+/*
+https://wiki.nesdev.com/w/index.php/Famicom
+https://wiki.nesdev.com/w/index.php/Standard_controller#Input_($4016_read)
+
+    the console model around the same cpu and ppu, which a handful of games notice:
+      Nes        NES-001, detachable controllers. the cartridge's audio pins go to the
+                 expansion port underneath, so expansion audio is not heard
+      Famicom    HVC-001, hardwired controllers. controller 2 has a microphone instead
+                 of select and start, read in bit 2 of $4016. cartridge audio is mixed in
+      AvFamicom  HVC-101, detachable nes style controllers without a microphone,
+                 cartridge audio is mixed in
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Console {
+    Nes,
+    Famicom,
+    AvFamicom,
+}
+
+impl Console {
+    // the buttons controller 2 does not have, they always read as released
+    pub fn controller2_missing(&self) -> JoypadButton {
+        match self {
+            Console::Famicom => JoypadButton::SELECT | JoypadButton::START,
+            Console::Nes | Console::AvFamicom => JoypadButton::empty(),
+        }
+    }
+
+    pub fn microphone(&self) -> bool {
+        *self == Console::Famicom
+    }
+
+    pub fn expansion_audio(&self) -> bool {
+        *self != Console::Nes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub power_on_ram: PowerOnRam,
+    pub power_on_cpu: PowerOnCpu,
+    pub accuracy: Accuracy,
+    // see Bus::set_overclock_scanlines
+    pub overclock_scanlines: u16,
+    pub console: Console,
+    // see UNSTABLE_MAGIC
+    pub unstable_magic: u8,
+}
+
+impl Config {
+    // hardware-like power-on state instead of the all-zeros default
+    pub fn realistic(seed: u64) -> Self {
+        Config {
+            power_on_ram: PowerOnRam::Pattern,
+            power_on_cpu: PowerOnCpu::Random { seed: seed },
+            accuracy: Accuracy::Balanced,
+            overclock_scanlines: 0,
+            console: Console::Nes,
+            unstable_magic: UNSTABLE_MAGIC,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            power_on_ram: PowerOnRam::Zero,
+            power_on_cpu: PowerOnCpu::Zero,
+            accuracy: Accuracy::Balanced,
+            overclock_scanlines: 0,
+            console: Console::Nes,
+            unstable_magic: UNSTABLE_MAGIC,
+        }
+    }
+}
+
+pub fn fill_pattern(data: &mut [u8]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = if i & 0b100 == 0 { 0x00 } else { 0xFF };
+    }
+}
+
+// xorshift64*, good enough for filling memory and needs no dependency
+pub struct PowerOnRng {
+    state: u64,
+}
+
+impl PowerOnRng {
+    pub fn new(seed: u64) -> Self {
+        PowerOnRng {
+            // the state must never be zero
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    pub fn fill(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.next_u8();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seeded_fill() {
+        let (mut a, mut b) = ([0u8; 64], [0u8; 64]);
+        PowerOnRng::new(42).fill(&mut a);
+        PowerOnRng::new(42).fill(&mut b);
+        assert_eq!(a[..], b[..]);
+        assert!(a.iter().any(|byte| *byte != a[0]));
+
+        PowerOnRng::new(43).fill(&mut b);
+        assert_ne!(a[..], b[..]);
+    }
+
+    #[test]
+    fn test_fill_pattern() {
+        let mut data = [0x12u8; 10];
+        fill_pattern(&mut data);
+        assert_eq!(data, [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+    }
+}
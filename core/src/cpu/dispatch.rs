@@ -0,0 +1,633 @@
+// how interprect gets from the opcode byte to the code of the instruction. three ways are
+// kept to measure them against each other (benches/dispatch.rs), a feature picks the one
+// that is built:
+//
+//     (none)              a match on the mnemonic, looked up in a table built from OPCODES
+//     dispatch-match      a match on the opcode byte, one arm per group of opcodes
+//     dispatch-table      a table of 256 function pointers indexed by the opcode byte
+//
+// with both features on the match is built. the mnemonic came out a few percent ahead on
+// nestest and even with the others in a game's main loop, where the ppu takes most of the
+// time. the other two stay so the comparison can be repeated after changes to the
+// instructions or with a new compiler
+
+use super::instructions::bitwise::*;
+use super::instructions::branch::*;
+use super::instructions::compare::*;
+use super::instructions::jump::*;
+use super::instructions::memory::*;
+use super::instructions::stack::*;
+use super::instructions::status::*;
+use super::instructions::transfer::*;
+use super::instructions::unofficial::*;
+use super::{AddressMode, CPU};
+
+#[cfg(not(feature = "dispatch-match"))]
+use crate::opcode::{Opcode, OPCODES};
+
+#[cfg(not(any(feature = "dispatch-match", feature = "dispatch-table")))]
+#[inline(always)]
+pub fn execute(cpu: &mut CPU, op: u8, mode: &AddressMode) {
+    match MNEMONICS[op as usize] {
+        Mnemonic::Adc => adc(cpu, mode),
+        Mnemonic::Ane => ane(cpu, mode),
+        Mnemonic::And => and(cpu, mode),
+        Mnemonic::Asl => asl(cpu, mode),
+        Mnemonic::AslAcc => asl_acc(cpu),
+        Mnemonic::Bcc => bcc(cpu),
+        Mnemonic::Bcs => bcs(cpu),
+        Mnemonic::Beq => beq(cpu),
+        Mnemonic::Bit => bit(cpu, mode),
+        Mnemonic::Bmi => bmi(cpu),
+        Mnemonic::Bne => bne(cpu),
+        Mnemonic::Bpl => bpl(cpu),
+        Mnemonic::Brk => cpu.reset(),
+        Mnemonic::Bvc => bvc(cpu),
+        Mnemonic::Bvs => bvs(cpu),
+        Mnemonic::Clc => clc(cpu),
+        Mnemonic::Cld => cld(cpu),
+        Mnemonic::Cli => cli(cpu),
+        Mnemonic::Clv => clv(cpu),
+        Mnemonic::Cmp => cmp(cpu, mode),
+        Mnemonic::Cpx => cpx(cpu, mode),
+        Mnemonic::Cpy => cpy(cpu, mode),
+        Mnemonic::Dec => dec(cpu, mode),
+        Mnemonic::Dex => dex(cpu),
+        Mnemonic::Dey => dey(cpu),
+        Mnemonic::Eor => eor(cpu, mode),
+        Mnemonic::Inc => inc(cpu, mode),
+        Mnemonic::Inx => inx(cpu),
+        Mnemonic::Iny => iny(cpu),
+        Mnemonic::JmpAbsolute => jmp_absolute(cpu),
+        Mnemonic::JmpIndirect => jmp_indirect(cpu),
+        Mnemonic::Jsr => jsr(cpu),
+        Mnemonic::Las => las(cpu, mode),
+        Mnemonic::Lda => lda(cpu, mode),
+        Mnemonic::Ldx => ldx(cpu, mode),
+        Mnemonic::Ldy => ldy(cpu, mode),
+        Mnemonic::Lsr => lsr(cpu, mode),
+        Mnemonic::LsrAcc => lsr_acc(cpu),
+        Mnemonic::Lxa => lxa(cpu, mode),
+        Mnemonic::Nop => {}
+        Mnemonic::Ora => ora(cpu, mode),
+        Mnemonic::Pha => pha(cpu),
+        Mnemonic::Php => php(cpu),
+        Mnemonic::Pla => pla(cpu),
+        Mnemonic::Plp => plp(cpu),
+        Mnemonic::Rol => rol(cpu, mode),
+        Mnemonic::RolAcc => rol_acc(cpu),
+        Mnemonic::Ror => ror(cpu, mode),
+        Mnemonic::RorAcc => ror_acc(cpu),
+        Mnemonic::Rti => rti(cpu),
+        Mnemonic::Rts => rts(cpu),
+        Mnemonic::Sbc => sbc(cpu, mode),
+        Mnemonic::Sec => sec(cpu),
+        Mnemonic::Sed => sed(cpu),
+        Mnemonic::Sei => sei(cpu),
+        Mnemonic::Sha => sha(cpu, mode),
+        Mnemonic::Shx => shx(cpu, mode),
+        Mnemonic::Shy => shy(cpu, mode),
+        Mnemonic::Sta => sta(cpu, mode),
+        Mnemonic::Stx => stx(cpu, mode),
+        Mnemonic::Sty => sty(cpu, mode),
+        Mnemonic::Tas => tas(cpu, mode),
+        Mnemonic::Tax => tax(cpu),
+        Mnemonic::Tay => tay(cpu),
+        Mnemonic::Tsx => tsx(cpu),
+        Mnemonic::Txa => txa(cpu),
+        Mnemonic::Txs => txs(cpu),
+        Mnemonic::Tya => tya(cpu),
+        Mnemonic::None => {}
+    }
+}
+
+// the shift and rotate instructions on the accumulator and the two JMPs have their own,
+// their opcodes share the mnemonic but not the code
+#[cfg(not(feature = "dispatch-match"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mnemonic {
+    Adc,
+    And,
+    Ane,
+    Asl,
+    AslAcc,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    JmpAbsolute,
+    JmpIndirect,
+    Jsr,
+    Las,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    LsrAcc,
+    Lxa,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    RolAcc,
+    Ror,
+    RorAcc,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sha,
+    Shx,
+    Shy,
+    Sta,
+    Stx,
+    Sty,
+    Tas,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+    // the opcodes that run as a NOP
+    None,
+}
+
+#[cfg(not(feature = "dispatch-match"))]
+static MNEMONICS: [Mnemonic; 256] = mnemonics(OPCODES);
+
+#[cfg(not(feature = "dispatch-match"))]
+const fn mnemonics(codes: &'static [Opcode]) -> [Mnemonic; 256] {
+    let mut mnemonics = [Mnemonic::None; 256];
+    let mut i = 0;
+    while i < codes.len() {
+        mnemonics[codes[i].op as usize] = mnemonic(&codes[i]);
+        i += 1;
+    }
+    mnemonics
+}
+
+#[cfg(not(feature = "dispatch-match"))]
+const fn mnemonic(code: &Opcode) -> Mnemonic {
+    // the one byte forms work on the accumulator
+    let accumulator = code.bytes == 1;
+    match code.name.as_bytes() {
+        b"ADC" => Mnemonic::Adc,
+        b"AND" => Mnemonic::And,
+        b"ANE" => Mnemonic::Ane,
+        b"ASL" if accumulator => Mnemonic::AslAcc,
+        b"ASL" => Mnemonic::Asl,
+        b"BCC" => Mnemonic::Bcc,
+        b"BCS" => Mnemonic::Bcs,
+        b"BEQ" => Mnemonic::Beq,
+        b"BIT" => Mnemonic::Bit,
+        b"BMI" => Mnemonic::Bmi,
+        b"BNE" => Mnemonic::Bne,
+        b"BPL" => Mnemonic::Bpl,
+        b"BRK" => Mnemonic::Brk,
+        b"BVC" => Mnemonic::Bvc,
+        b"BVS" => Mnemonic::Bvs,
+        b"CLC" => Mnemonic::Clc,
+        b"CLD" => Mnemonic::Cld,
+        b"CLI" => Mnemonic::Cli,
+        b"CLV" => Mnemonic::Clv,
+        b"CMP" => Mnemonic::Cmp,
+        b"CPX" => Mnemonic::Cpx,
+        b"CPY" => Mnemonic::Cpy,
+        b"DEC" => Mnemonic::Dec,
+        b"DEX" => Mnemonic::Dex,
+        b"DEY" => Mnemonic::Dey,
+        b"EOR" => Mnemonic::Eor,
+        b"INC" => Mnemonic::Inc,
+        b"INX" => Mnemonic::Inx,
+        b"INY" => Mnemonic::Iny,
+        b"JMP" if code.op == 0x4C => Mnemonic::JmpAbsolute,
+        b"JMP" => Mnemonic::JmpIndirect,
+        b"JSR" => Mnemonic::Jsr,
+        b"LAS" => Mnemonic::Las,
+        b"LDA" => Mnemonic::Lda,
+        b"LDX" => Mnemonic::Ldx,
+        b"LDY" => Mnemonic::Ldy,
+        b"LSR" if accumulator => Mnemonic::LsrAcc,
+        b"LSR" => Mnemonic::Lsr,
+        b"LXA" => Mnemonic::Lxa,
+        b"NOP" => Mnemonic::Nop,
+        b"ORA" => Mnemonic::Ora,
+        b"PHA" => Mnemonic::Pha,
+        b"PHP" => Mnemonic::Php,
+        b"PLA" => Mnemonic::Pla,
+        b"PLP" => Mnemonic::Plp,
+        b"ROL" if accumulator => Mnemonic::RolAcc,
+        b"ROL" => Mnemonic::Rol,
+        b"ROR" if accumulator => Mnemonic::RorAcc,
+        b"ROR" => Mnemonic::Ror,
+        b"RTI" => Mnemonic::Rti,
+        b"RTS" => Mnemonic::Rts,
+        b"SBC" => Mnemonic::Sbc,
+        b"SEC" => Mnemonic::Sec,
+        b"SED" => Mnemonic::Sed,
+        b"SEI" => Mnemonic::Sei,
+        b"SHA" => Mnemonic::Sha,
+        b"SHX" => Mnemonic::Shx,
+        b"SHY" => Mnemonic::Shy,
+        b"STA" => Mnemonic::Sta,
+        b"STX" => Mnemonic::Stx,
+        b"STY" => Mnemonic::Sty,
+        b"TAS" => Mnemonic::Tas,
+        b"TAX" => Mnemonic::Tax,
+        b"TAY" => Mnemonic::Tay,
+        b"TSX" => Mnemonic::Tsx,
+        b"TXA" => Mnemonic::Txa,
+        b"TXS" => Mnemonic::Txs,
+        b"TYA" => Mnemonic::Tya,
+        _ => Mnemonic::None,
+    }
+}
+
+#[cfg(feature = "dispatch-match")]
+#[inline(always)]
+pub fn execute(cpu: &mut CPU, op: u8, mode: &AddressMode) {
+    match op {
+        0x00 => {
+            cpu.reset();
+        }
+        // NOP
+        0xEA => {}
+        // TRANSFER
+        0xAA => {
+            tax(cpu);
+        }
+        0xA8 => {
+            tay(cpu);
+        }
+        0x8A => {
+            txa(cpu);
+        }
+        0x98 => {
+            tya(cpu);
+        }
+        0xBA => {
+            tsx(cpu);
+        }
+        0x9A => {
+            txs(cpu);
+        }
+        // LDA
+        0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => {
+            lda(cpu, mode);
+        }
+        // LDX
+        0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => {
+            ldx(cpu, mode);
+        }
+        // LDY
+        0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => {
+            ldy(cpu, mode);
+        }
+        // STA
+        0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => {
+            sta(cpu, mode);
+        }
+        // STX
+        0x86 | 0x96 | 0x8E => {
+            stx(cpu, mode);
+        }
+        // STY
+        0x84 | 0x94 | 0x8C => {
+            sty(cpu, mode);
+        }
+        // ADC
+        0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => {
+            adc(cpu, mode);
+        }
+        // AND
+        0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => {
+            and(cpu, mode);
+        }
+        // EOR
+        0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => {
+            eor(cpu, mode);
+        }
+        // ORA
+        0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => {
+            ora(cpu, mode);
+        }
+        // ASL
+        0x0A => {
+            asl_acc(cpu);
+        }
+        0x06 | 0x16 | 0x0E | 0x1E => {
+            asl(cpu, mode);
+        }
+        // LSR
+        0x4A => {
+            lsr_acc(cpu);
+        }
+        0x46 | 0x56 | 0x4E | 0x5E => {
+            lsr(cpu, mode);
+        }
+        // ROL
+        0x2A => {
+            rol_acc(cpu);
+        }
+        0x26 | 0x36 | 0x2E | 0x3E => {
+            rol(cpu, mode);
+        }
+        // ROR
+        0x6A => {
+            ror_acc(cpu);
+        }
+        0x66 | 0x76 | 0x6E | 0x7E => {
+            ror(cpu, mode);
+        }
+        // BRANCH
+        0x90 => {
+            bcc(cpu);
+        }
+        0xB0 => {
+            bcs(cpu);
+        }
+        0xF0 => {
+            beq(cpu);
+        }
+        0x30 => {
+            bmi(cpu);
+        }
+        0xD0 => {
+            bne(cpu);
+        }
+        0x10 => {
+            bpl(cpu);
+        }
+        0x50 => {
+            bvc(cpu);
+        }
+        0x70 => {
+            bvs(cpu);
+        }
+        // SBC
+        0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => {
+            sbc(cpu, mode);
+        }
+        // BIT
+        0x24 | 0x2C => {
+            bit(cpu, mode);
+        }
+        // CLEAR
+        0x18 => {
+            clc(cpu);
+        }
+        0xD8 => {
+            cld(cpu);
+        }
+        0x58 => {
+            cli(cpu);
+        }
+        0xB8 => {
+            clv(cpu);
+        }
+        // COMPARE
+        0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => {
+            cmp(cpu, mode);
+        }
+        0xE0 | 0xE4 | 0xEC => {
+            cpx(cpu, mode);
+        }
+        0xC0 | 0xC4 | 0xCC => {
+            cpy(cpu, mode);
+        }
+        // DEC
+        0xC6 | 0xD6 | 0xCE | 0xDE => {
+            dec(cpu, mode);
+        }
+        // DEX
+        0xCA => {
+            dex(cpu);
+        }
+        // DEY
+        0x88 => {
+            dey(cpu);
+        }
+        // INC
+        0xE6 | 0xF6 | 0xEE | 0xFE => {
+            inc(cpu, mode);
+        }
+        // INX
+        0xE8 => {
+            inx(cpu);
+        }
+        // INY
+        0xC8 => {
+            iny(cpu);
+        }
+        // PHP
+        0x08 => {
+            php(cpu);
+        }
+        // PHA
+        0x48 => {
+            pha(cpu);
+        }
+        // PLP
+        0x28 => {
+            plp(cpu);
+        }
+        // PLA
+        0x68 => {
+            pla(cpu);
+        }
+        // JSR
+        0x20 => {
+            jsr(cpu);
+        }
+        // RTS
+        0x60 => {
+            rts(cpu);
+        }
+        // RTI
+        0x40 => {
+            rti(cpu);
+        }
+        // SET
+        0x38 => {
+            sec(cpu);
+        }
+        0xF8 => {
+            sed(cpu);
+        }
+        0x78 => {
+            sei(cpu);
+        }
+        // JMP
+        0x4C => {
+            jmp_absolute(cpu);
+        }
+        0x6C => {
+            jmp_indirect(cpu);
+        }
+        // UNOFFICIAL
+        0x8B => {
+            ane(cpu, mode);
+        }
+        0xAB => {
+            lxa(cpu, mode);
+        }
+        0xBB => {
+            las(cpu, mode);
+        }
+        0x9B => {
+            tas(cpu, mode);
+        }
+        0x9F | 0x93 => {
+            sha(cpu, mode);
+        }
+        0x9E => {
+            shx(cpu, mode);
+        }
+        0x9C => {
+            shy(cpu, mode);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(all(feature = "dispatch-table", not(feature = "dispatch-match")))]
+type Handler = fn(&mut CPU, &AddressMode);
+
+#[cfg(all(feature = "dispatch-table", not(feature = "dispatch-match")))]
+static HANDLERS: [Handler; 256] = handlers();
+
+#[cfg(all(feature = "dispatch-table", not(feature = "dispatch-match")))]
+#[inline(always)]
+pub fn execute(cpu: &mut CPU, op: u8, mode: &AddressMode) {
+    HANDLERS[op as usize](cpu, mode)
+}
+
+#[cfg(all(feature = "dispatch-table", not(feature = "dispatch-match")))]
+const fn handlers() -> [Handler; 256] {
+    let mut handlers = [handler(Mnemonic::None); 256];
+    let mut op = 0;
+    while op < 256 {
+        handlers[op] = handler(MNEMONICS[op]);
+        op += 1;
+    }
+    handlers
+}
+
+#[cfg(all(feature = "dispatch-table", not(feature = "dispatch-match")))]
+const fn handler(mnemonic: Mnemonic) -> Handler {
+    match mnemonic {
+        Mnemonic::Adc => adc,
+        Mnemonic::And => and,
+        Mnemonic::Ane => ane,
+        Mnemonic::Asl => asl,
+        Mnemonic::AslAcc => |cpu, _| asl_acc(cpu),
+        Mnemonic::Bcc => |cpu, _| bcc(cpu),
+        Mnemonic::Bcs => |cpu, _| bcs(cpu),
+        Mnemonic::Beq => |cpu, _| beq(cpu),
+        Mnemonic::Bit => bit,
+        Mnemonic::Bmi => |cpu, _| bmi(cpu),
+        Mnemonic::Bne => |cpu, _| bne(cpu),
+        Mnemonic::Bpl => |cpu, _| bpl(cpu),
+        Mnemonic::Brk => |cpu, _| cpu.reset(),
+        Mnemonic::Bvc => |cpu, _| bvc(cpu),
+        Mnemonic::Bvs => |cpu, _| bvs(cpu),
+        Mnemonic::Clc => |cpu, _| clc(cpu),
+        Mnemonic::Cld => |cpu, _| cld(cpu),
+        Mnemonic::Cli => |cpu, _| cli(cpu),
+        Mnemonic::Clv => |cpu, _| clv(cpu),
+        Mnemonic::Cmp => cmp,
+        Mnemonic::Cpx => cpx,
+        Mnemonic::Cpy => cpy,
+        Mnemonic::Dec => dec,
+        Mnemonic::Dex => |cpu, _| dex(cpu),
+        Mnemonic::Dey => |cpu, _| dey(cpu),
+        Mnemonic::Eor => eor,
+        Mnemonic::Inc => inc,
+        Mnemonic::Inx => |cpu, _| inx(cpu),
+        Mnemonic::Iny => |cpu, _| iny(cpu),
+        Mnemonic::JmpAbsolute => |cpu, _| jmp_absolute(cpu),
+        Mnemonic::JmpIndirect => |cpu, _| jmp_indirect(cpu),
+        Mnemonic::Jsr => |cpu, _| jsr(cpu),
+        Mnemonic::Las => las,
+        Mnemonic::Lda => lda,
+        Mnemonic::Ldx => ldx,
+        Mnemonic::Ldy => ldy,
+        Mnemonic::Lsr => lsr,
+        Mnemonic::LsrAcc => |cpu, _| lsr_acc(cpu),
+        Mnemonic::Lxa => lxa,
+        Mnemonic::Nop => |_, _| {},
+        Mnemonic::Ora => ora,
+        Mnemonic::Pha => |cpu, _| pha(cpu),
+        Mnemonic::Php => |cpu, _| php(cpu),
+        Mnemonic::Pla => |cpu, _| pla(cpu),
+        Mnemonic::Plp => |cpu, _| plp(cpu),
+        Mnemonic::Rol => rol,
+        Mnemonic::RolAcc => |cpu, _| rol_acc(cpu),
+        Mnemonic::Ror => ror,
+        Mnemonic::RorAcc => |cpu, _| ror_acc(cpu),
+        Mnemonic::Rti => |cpu, _| rti(cpu),
+        Mnemonic::Rts => |cpu, _| rts(cpu),
+        Mnemonic::Sbc => sbc,
+        Mnemonic::Sec => |cpu, _| sec(cpu),
+        Mnemonic::Sed => |cpu, _| sed(cpu),
+        Mnemonic::Sei => |cpu, _| sei(cpu),
+        Mnemonic::Sha => sha,
+        Mnemonic::Shx => shx,
+        Mnemonic::Shy => shy,
+        Mnemonic::Sta => sta,
+        Mnemonic::Stx => stx,
+        Mnemonic::Sty => sty,
+        Mnemonic::Tas => tas,
+        Mnemonic::Tax => |cpu, _| tax(cpu),
+        Mnemonic::Tay => |cpu, _| tay(cpu),
+        Mnemonic::Tsx => |cpu, _| tsx(cpu),
+        Mnemonic::Txa => |cpu, _| txa(cpu),
+        Mnemonic::Txs => |cpu, _| txs(cpu),
+        Mnemonic::Tya => |cpu, _| tya(cpu),
+        Mnemonic::None => |_, _| {},
+    }
+}
+
+#[cfg(all(test, not(feature = "dispatch-match")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mnemonics() {
+        for code in OPCODES.iter() {
+            assert_ne!(MNEMONICS[code.op as usize], Mnemonic::None, "{}", code.name);
+        }
+        assert_eq!(MNEMONICS[0x0A], Mnemonic::AslAcc);
+        assert_eq!(MNEMONICS[0x0E], Mnemonic::Asl);
+        assert_eq!(MNEMONICS[0x4C], Mnemonic::JmpAbsolute);
+        assert_eq!(MNEMONICS[0x6C], Mnemonic::JmpIndirect);
+        // unofficial
+        assert_eq!(MNEMONICS[0x02], Mnemonic::None);
+    }
+}
@@ -0,0 +1,157 @@
+// subscribers for tools (profiler, code/data logger, scripting) that want to follow the
+// interpreter without their own plumbing in the instruction loop
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    NMI,
+    IRQ,
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionEvent {
+    // address of the opcode
+    pub pc: u16,
+    pub opcode: u8,
+    pub cycles: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptEvent {
+    pub interrupt: Interrupt,
+    // where execution was interrupted and where the handler starts
+    pub return_pc: u16,
+    pub handler_pc: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StackEvent {
+    // address of the instruction that pushed or pulled
+    pub pc: u16,
+    // stack pointer before it wrapped around
+    pub sp: u8,
+}
+
+// a subscriber to events of type E
+type Hook<E> = Box<dyn FnMut(&E) + Send>;
+
+#[derive(Default)]
+pub struct Hooks {
+    instruction_executed: Vec<Hook<InstructionEvent>>,
+    interrupt: Vec<Hook<InterruptEvent>>,
+    stack_overflow: Vec<Hook<StackEvent>>,
+    stack_underflow: Vec<Hook<StackEvent>>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Hooks::default()
+    }
+
+    pub fn on_instruction_executed<F>(&mut self, hook: F)
+    where
+        F: FnMut(&InstructionEvent) + Send + 'static,
+    {
+        self.instruction_executed.push(Box::new(hook));
+    }
+
+    pub fn on_interrupt<F>(&mut self, hook: F)
+    where
+        F: FnMut(&InterruptEvent) + Send + 'static,
+    {
+        self.interrupt.push(Box::new(hook));
+    }
+
+    pub fn on_stack_overflow<F>(&mut self, hook: F)
+    where
+        F: FnMut(&StackEvent) + Send + 'static,
+    {
+        self.stack_overflow.push(Box::new(hook));
+    }
+
+    pub fn on_stack_underflow<F>(&mut self, hook: F)
+    where
+        F: FnMut(&StackEvent) + Send + 'static,
+    {
+        self.stack_underflow.push(Box::new(hook));
+    }
+
+    pub fn clear(&mut self) {
+        self.instruction_executed.clear();
+        self.interrupt.clear();
+        self.stack_overflow.clear();
+        self.stack_underflow.clear();
+    }
+
+    pub(super) fn instruction_executed(&mut self, event: InstructionEvent) {
+        for hook in self.instruction_executed.iter_mut() {
+            hook(&event);
+        }
+    }
+
+    pub(super) fn interrupt(&mut self, event: InterruptEvent) {
+        for hook in self.interrupt.iter_mut() {
+            hook(&event);
+        }
+    }
+
+    pub(super) fn stack_overflow(&mut self, event: StackEvent) {
+        for hook in self.stack_overflow.iter_mut() {
+            hook(&event);
+        }
+    }
+
+    pub(super) fn stack_underflow(&mut self, event: StackEvent) {
+        for hook in self.stack_underflow.iter_mut() {
+            hook(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::create_cpu;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_instruction_and_stack_hooks() {
+        // LDX #$00; TXS; PHA; PLA
+        let mut cpu = create_cpu(&[0xA2, 0x00, 0x9A, 0x48, 0x68]);
+
+        let executed = Arc::new(AtomicRefCell::new(Vec::new()));
+        let overflows = Arc::new(AtomicRefCell::new(Vec::new()));
+        let underflows = Arc::new(AtomicRefCell::new(Vec::new()));
+        let (e, o, u) = (executed.clone(), overflows.clone(), underflows.clone());
+        cpu.hooks
+            .on_instruction_executed(move |event| e.borrow_mut().push(event.pc));
+        cpu.hooks
+            .on_stack_overflow(move |event| o.borrow_mut().push(event.pc));
+        cpu.hooks
+            .on_stack_underflow(move |event| u.borrow_mut().push(event.pc));
+
+        for _ in 0..4 {
+            cpu.interprect();
+        }
+
+        assert_eq!(*executed.borrow(), vec![0x8000, 0x8002, 0x8003, 0x8004]);
+        assert_eq!(*overflows.borrow(), vec![0x8003]);
+        assert_eq!(*underflows.borrow(), vec![0x8004]);
+    }
+
+    #[test]
+    fn test_interrupt_hook() {
+        let mut cpu = create_cpu(&[]);
+        let interrupts = Arc::new(AtomicRefCell::new(Vec::new()));
+        let i = interrupts.clone();
+        cpu.hooks
+            .on_interrupt(move |event| i.borrow_mut().push(event.interrupt));
+
+        cpu.reset();
+        assert_eq!(*interrupts.borrow(), vec![Interrupt::Reset]);
+    }
+}
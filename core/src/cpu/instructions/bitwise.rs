@@ -45,14 +45,14 @@ pub fn rol_acc(cpu: &mut CPU) {
 }
 
 pub fn rol(cpu: &mut CPU, mode: &AddressMode) {
-    let addr = cpu.get_operand_address(mode);
+    let addr = cpu.get_store_address(mode);
     let value = cpu.mem_read(addr);
     let res = (value << 1) | (0x01 & cpu.status.bits());
 
     update_carry_flag(cpu, value >> 7 == 1);
     update_zero_flag(cpu, res);
     update_neg_flag(cpu, res);
-    cpu.mem_write(addr, res);
+    write_modified(cpu, addr, value, res);
 }
 
 pub fn ror_acc(cpu: &mut CPU) {
@@ -66,7 +66,7 @@ pub fn ror_acc(cpu: &mut CPU) {
 }
 
 pub fn ror(cpu: &mut CPU, mode: &AddressMode) {
-    let addr = cpu.get_operand_address(mode);
+    let addr = cpu.get_store_address(mode);
     let value = cpu.mem_read(addr);
     let res = (value >> 1) | (cpu.status.bits() << 7);
 
@@ -74,7 +74,7 @@ pub fn ror(cpu: &mut CPU, mode: &AddressMode) {
     update_zero_flag(cpu, res);
     update_neg_flag(cpu, res);
 
-    cpu.acc = res;
+    write_modified(cpu, addr, value, res);
 }
 
 pub fn lsr_acc(cpu: &mut CPU) {
@@ -87,14 +87,14 @@ pub fn lsr_acc(cpu: &mut CPU) {
 }
 
 pub fn lsr(cpu: &mut CPU, mode: &AddressMode) {
-    let addr = cpu.get_operand_address(mode);
+    let addr = cpu.get_store_address(mode);
     let value = cpu.mem_read(addr);
     let res = value >> 1;
 
     update_carry_flag(cpu, value & 0x01 == 1);
     update_zero_flag(cpu, res);
     update_neg_flag(cpu, res);
-    cpu.mem_write(addr, res);
+    write_modified(cpu, addr, value, res);
 }
 
 pub fn asl_acc(cpu: &mut CPU) {
@@ -109,30 +109,30 @@ pub fn asl_acc(cpu: &mut CPU) {
 }
 
 pub fn asl(cpu: &mut CPU, mode: &AddressMode) {
-    let addr = cpu.get_operand_address(mode);
-    let mut value = cpu.mem_read(addr);
+    let addr = cpu.get_store_address(mode);
+    let value = cpu.mem_read(addr);
 
     update_carry_flag(cpu, value >> 7 == 1);
 
-    value <<= 1;
-    update_neg_flag(cpu, value);
-    update_zero_flag(cpu, value);
+    let res = value << 1;
+    update_neg_flag(cpu, res);
+    update_zero_flag(cpu, res);
 
-    cpu.mem_write(addr, value);
+    write_modified(cpu, addr, value, res);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cpu::test::create_cpu;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 
     /* test for ADC */
     #[test]
     fn test_adc() {
         let program = vec![0x69, 0x10, 0x69, 0x20, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.acc, 0x30);
@@ -142,7 +142,7 @@ mod test {
     fn test_adc_overflow() {
         let program = vec![0x69, 0xD0, 0x69, 0x90, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert!(cpu.status.contains(CPUStatus::OVERFLOW));
@@ -153,7 +153,7 @@ mod test {
     fn test_sbc() {
         let program = vec![0x69, 0x10, 0xE9, 0x01, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.acc, 0x0E);
@@ -164,7 +164,7 @@ mod test {
     fn test_and() {
         let program = vec![0x69, 0x0F, 0x29, 0x11, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.acc, 0x01);
@@ -175,7 +175,7 @@ mod test {
     fn test_eor() {
         let program = vec![0x69, 0x09, 0x49, 0x06, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.acc, 0x0F);
@@ -186,7 +186,7 @@ mod test {
     fn test_asl() {
         let program = vec![0x06, 0xFF, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.mem_write(0x00FF, 0x10);
         cpu.run();
 
@@ -197,7 +197,7 @@ mod test {
     fn test_asl_acc() {
         let program = vec![0x69, 0x10, 0x0A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.acc, 0x20);
@@ -208,7 +208,7 @@ mod test {
     fn test_lsr() {
         let program = vec![0x4A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.acc = 0x09;
         cpu.interprect();
@@ -222,7 +222,7 @@ mod test {
     fn test_rol() {
         let program = vec![0x2A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.acc = 0x40;
         cpu.status.insert(CPUStatus::CARRY);
@@ -232,12 +232,27 @@ mod test {
         assert!(!cpu.status.contains(CPUStatus::CARRY));
     }
 
+    #[test]
+    fn test_rol_absolute_x() {
+        let program = vec![0x3E, 0x00, 0x02, 0x00];
+
+        let mut cpu = create_cpu(&program);
+        cpu.reset();
+        cpu.rx = 0x10;
+        cpu.mem_write(0x0210, 0x81);
+        cpu.interprect();
+
+        assert_eq!(cpu.mem_read(0x0210), 0x02);
+        assert!(cpu.status.contains(CPUStatus::CARRY));
+        assert_eq!(cpu.pc, 0x8003);
+    }
+
     /* test for ROR */
     #[test]
     fn test_ror() {
         let program = vec![0x6A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.acc = 0x08;
         cpu.status.insert(CPUStatus::CARRY);
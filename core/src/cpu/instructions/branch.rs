@@ -37,18 +37,18 @@ pub fn bvs(cpu: &mut CPU) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cpu::test::create_cpu;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 
     /* test for BRANCH */
     #[test]
     fn test_bcc() {
         let program = vec![0x90, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.remove(CPUStatus::CARRY);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -57,10 +57,10 @@ mod test {
     fn test_bcs() {
         let program = vec![0xB0, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.insert(CPUStatus::CARRY);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x21); // because the CARRY bit has been set
     }
@@ -69,10 +69,10 @@ mod test {
     fn test_beq() {
         let program = vec![0xF0, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.insert(CPUStatus::ZERO);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -81,10 +81,10 @@ mod test {
     fn test_bmi() {
         let program = vec![0x30, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.insert(CPUStatus::NEGATIVE);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -93,10 +93,10 @@ mod test {
     fn test_bne() {
         let program = vec![0xD0, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.remove(CPUStatus::ZERO);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -105,10 +105,10 @@ mod test {
     fn test_bpl() {
         let program = vec![0x10, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.remove(CPUStatus::NEGATIVE);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -117,10 +117,10 @@ mod test {
     fn test_bvc() {
         let program = vec![0x50, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.remove(CPUStatus::OVERFLOW);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
@@ -129,10 +129,10 @@ mod test {
     fn test_bvs() {
         let program = vec![0x70, 0x03, 0x69, 0x10, 0x00, 0x69, 0x20];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.status.insert(CPUStatus::OVERFLOW);
-        cpu.interprect();
+        cpu.run_until_brk();
 
         assert_eq!(cpu.acc, 0x20);
     }
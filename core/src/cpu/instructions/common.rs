@@ -1,4 +1,5 @@
 use super::super::CPUStatus;
+use super::super::StackFault;
 use super::super::CPU;
 use crate::mem::Memory;
 
@@ -42,11 +43,17 @@ pub fn update_carry_flag(cpu: &mut CPU, flag: bool) {
 
 /* stack */
 pub fn stack_push(cpu: &mut CPU, value: u8) {
+    if cpu.sp == 0x00 {
+        cpu.stack_fault(StackFault::Overflow);
+    }
     cpu.mem_write(cpu.sp as u16 + STACK_BOTTOM_LOC, value);
     cpu.sp = cpu.sp.wrapping_sub(1);
 }
 
 pub fn stack_pop(cpu: &mut CPU) -> u8 {
+    if cpu.sp == 0xFF {
+        cpu.stack_fault(StackFault::Underflow);
+    }
     cpu.sp = cpu.sp.wrapping_add(1);
     cpu.mem_read(cpu.sp as u16 + STACK_BOTTOM_LOC)
 }
@@ -63,6 +70,16 @@ pub fn stack_pop_u16(cpu: &mut CPU) -> u16 {
     hi << 8 | lo
 }
 
+/* read-modify-write */
+// the 6502 writes the unmodified value back while it computes the new one. $2007 and
+// mapper registers see both writes
+pub fn write_modified(cpu: &mut CPU, addr: u16, value: u8, res: u8) {
+    if cpu.bus.accuracy().dummy_writes() {
+        cpu.mem_write(addr, value);
+    }
+    cpu.mem_write(addr, res);
+}
+
 /* compare */
 pub fn compare(cpu: &mut CPU, v1: u8, v2: u8) {
     update_carry_flag(cpu, v1 >= v2);
@@ -73,10 +90,10 @@ pub fn compare(cpu: &mut CPU, v1: u8, v2: u8) {
 
 /* branch */
 pub fn branch(cpu: &mut CPU, flag: bool) {
+    let offset = cpu.mem_read(cpu.pc) as i8; // offset can be negative
+    cpu.pc = cpu.pc.wrapping_add(1);
     if flag {
-        let offset = cpu.mem_read(cpu.pc) as i8; // offset can be negative
-        let dst = cpu.pc.wrapping_add(1).wrapping_add(offset as u16);
-        cpu.pc = dst;
+        cpu.pc = cpu.pc.wrapping_add(offset as u16);
     }
 }
 
@@ -97,6 +114,8 @@ pub fn add_to_acc(cpu: &mut CPU, data: u8) {
     let res = sum as u8;
     // (M ^ result) & (N ^ result) & 0x80 != 0
     update_overflow_flag(cpu, (data ^ res) & (cpu.acc ^ res) & 0x80 != 0);
+    update_zero_flag(cpu, res);
+    update_neg_flag(cpu, res);
 
     cpu.acc = res;
 }
@@ -105,5 +124,4 @@ pub fn add_to_acc(cpu: &mut CPU, data: u8) {
 mod test {
     use super::*;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 }
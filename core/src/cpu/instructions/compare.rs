@@ -25,15 +25,15 @@ pub fn cpy(cpu: &mut CPU, mode: &AddressMode) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cpu::test::create_cpu;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 
     /* test for COMPARE */
     #[test]
     fn test_cmp1() {
         let program = vec![0x69, 0x10, 0xC9, 0x0F, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert!(cpu.status.contains(CPUStatus::CARRY));
@@ -43,7 +43,7 @@ mod test {
     fn test_cmp2() {
         let program = vec![0x69, 0x10, 0xC9, 0x10, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert!(cpu.status.contains(CPUStatus::CARRY));
@@ -0,0 +1,62 @@
+use super::super::CPUStatus;
+use super::super::CPU;
+use super::common::*;
+
+use crate::mem::Memory;
+
+// the high byte of the target is fetched after the return address is pushed, so a stack
+// sitting on top of the operand changes where the call goes
+pub fn jsr(cpu: &mut CPU) {
+    let lo = cpu.mem_read(cpu.pc) as u16;
+    stack_push_u16(cpu, cpu.pc.wrapping_add(1)); // PC + 2 - 1
+    let hi = cpu.mem_read(cpu.pc.wrapping_add(1)) as u16;
+    cpu.pc = hi << 8 | lo;
+}
+
+pub fn rts(cpu: &mut CPU) {
+    cpu.pc = stack_pop_u16(cpu).wrapping_add(1);
+}
+
+pub fn rti(cpu: &mut CPU) {
+    cpu.status.bits = stack_pop(cpu);
+    cpu.status.remove(CPUStatus::BREAK);
+    cpu.pc = stack_pop_u16(cpu);
+}
+
+pub fn brk(cpu: &mut CPU) {
+    let mut status = cpu.status.clone();
+    status.insert(CPUStatus::BREAK);
+    status.insert(CPUStatus::RESERVED);
+
+    stack_push_u16(cpu, cpu.pc);
+    stack_push(cpu, status.bits);
+
+    cpu.status.insert(CPUStatus::INTERRUPT_DISABLE);
+    cpu.pc = cpu.mem_read_u16(RESET_INTERRUPT_MEM_LOC);
+}
+
+pub fn jmp_absolute(cpu: &mut CPU) {
+    cpu.pc = cpu.mem_read_u16(cpu.pc);
+}
+
+// JMP is the only 6502 instruction to support indirection. the instruction contains a
+// 16 bit address which identifies the location of the least significant byte of another
+// 16 bit memory address which is the real target of the instruction. the pointer does not
+// cross pages, JMP ($10FF) reads its high byte from $1000
+// http://www.obelisk.me.uk/6502/addressing.html#IND
+pub fn jmp_indirect(cpu: &mut CPU) {
+    let addr = cpu.mem_read_u16(cpu.pc);
+    cpu.pc = if addr & 0x00FF == 0x00FF {
+        let lo = cpu.mem_read(addr);
+        let hi = cpu.mem_read(addr & 0xFF00);
+        (hi as u16) << 8 | (lo as u16)
+    } else {
+        cpu.mem_read_u16(addr)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::CPUStatus;
+}
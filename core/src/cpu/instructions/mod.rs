@@ -8,3 +8,4 @@ pub mod memory;
 pub mod stack;
 pub mod status;
 pub mod transfer;
+pub mod unofficial;
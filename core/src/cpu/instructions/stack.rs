@@ -0,0 +1,61 @@
+use super::super::CPUStatus;
+use super::super::CPU;
+use super::common::*;
+
+pub fn php(cpu: &mut CPU) {
+    let mut s = cpu.status.clone();
+    // http://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+    s.insert(CPUStatus::BREAK);
+    s.insert(CPUStatus::RESERVED);
+    stack_push(cpu, s.bits());
+}
+
+pub fn plp(cpu: &mut CPU) {
+    let s = stack_pop(cpu);
+    cpu.status.bits = s;
+    cpu.status.remove(CPUStatus::BREAK);
+}
+
+pub fn pha(cpu: &mut CPU) {
+    stack_push(cpu, cpu.acc);
+}
+
+pub fn pla(cpu: &mut CPU) {
+    cpu.acc = stack_pop(cpu);
+    update_neg_flag(cpu, cpu.acc);
+    update_zero_flag(cpu, cpu.acc);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::create_cpu;
+    use crate::cpu::CPUStatus;
+    use crate::cpu::StackFault;
+
+    #[test]
+    fn test_stack_break() {
+        // LDX #$00; TXS; PHA; PHA; PLA; PLA
+        let mut cpu = create_cpu(&[0xA2, 0x00, 0x9A, 0x48, 0x48, 0x68, 0x68]);
+        cpu.break_on_stack_fault = true;
+
+        cpu.interprect();
+        cpu.interprect();
+        assert_eq!(cpu.take_stack_break(), None);
+
+        cpu.interprect();
+        assert_eq!(cpu.sp, 0xFF);
+        assert_eq!(cpu.take_stack_break(), Some((StackFault::Overflow, 0x8003)));
+        assert_eq!(cpu.take_stack_break(), None);
+
+        // the pushes left sp at $FE, so the second pull wraps back to $00
+        cpu.interprect();
+        cpu.interprect();
+        assert_eq!(cpu.take_stack_break(), None);
+        cpu.interprect();
+        assert_eq!(
+            cpu.take_stack_break(),
+            Some((StackFault::Underflow, 0x8006))
+        );
+    }
+}
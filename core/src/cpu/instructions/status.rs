@@ -38,7 +38,7 @@ pub fn bit(cpu: &mut CPU, mode: &AddressMode) {
     let value = cpu.mem_read(addr);
 
     update_neg_flag(cpu, value);
-    update_overflow_flag(cpu, value & 0b0100_0000 == 1);
+    update_overflow_flag(cpu, value & 0b0100_0000 != 0);
     update_zero_flag(cpu, cpu.acc & value);
 }
 
@@ -46,5 +46,4 @@ pub fn bit(cpu: &mut CPU, mode: &AddressMode) {
 mod test {
     use super::*;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 }
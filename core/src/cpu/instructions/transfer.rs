@@ -92,24 +92,23 @@ pub fn tsx(cpu: &mut CPU) {
     update_zero_flag(cpu, cpu.rx);
 }
 
+// the only transfer that leaves the flags alone
 pub fn txs(cpu: &mut CPU) {
     cpu.sp = cpu.rx;
-    update_neg_flag(cpu, cpu.sp);
-    update_zero_flag(cpu, cpu.sp);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cpu::test::create_cpu;
     use crate::cpu::CPUStatus;
-    use crate::cpu::With;
 
     /* test for TRANSFER */
     #[test]
     fn test_tax() {
         let program = vec![0x69, 0x10, 0xAA, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.rx, 0x10);
@@ -119,7 +118,7 @@ mod test {
     fn test_tay() {
         let program = vec![0x69, 0x10, 0xA8, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.run();
 
         assert_eq!(cpu.ry, 0x10);
@@ -129,7 +128,7 @@ mod test {
     fn test_txa() {
         let program = vec![0x8A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.rx = 0x10;
         cpu.interprect();
@@ -141,7 +140,7 @@ mod test {
     fn test_tya() {
         let program = vec![0x98, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.ry = 0x10;
         cpu.interprect();
@@ -153,7 +152,7 @@ mod test {
     fn test_tsx() {
         let program = vec![0xBA, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.sp = 0x10;
         cpu.interprect();
@@ -165,7 +164,7 @@ mod test {
     fn test_txs() {
         let program = vec![0x9A, 0x00];
 
-        let mut cpu = CPU::with(program.to_vec());
+        let mut cpu = create_cpu(&program);
         cpu.reset();
         cpu.rx = 0x10;
         cpu.interprect();
@@ -0,0 +1,121 @@
+use super::super::AddressMode;
+use super::super::CPU;
+use super::common::*;
+
+use crate::mem::Memory;
+
+/*
+https://wiki.nesdev.com/w/index.php/Programming_with_unofficial_opcodes
+https://wiki.nesdev.com/w/index.php/CPU_unofficial_opcodes
+
+    the unstable ones, what they do depends on analog effects inside the chip:
+      ANE  A = (A | magic) & X & #i
+      LXA  A = X = (A | magic) & #i
+      LAS  A = X = S = M & S
+      TAS  S = A & X, then stores S & (H + 1)
+      SHA  stores A & X & (H + 1)
+      SHX  stores X & (H + 1)
+      SHY  stores Y & (H + 1)
+
+    magic is whatever the chip puts on the internal bus, see Bus::set_unstable_magic.
+    H is the high byte of the address before the index is added. when the index crosses a
+    page the stored value also replaces the high byte of the address, so the write lands
+    in page (value) instead of page H + 1
+*/
+pub fn ane(cpu: &mut CPU, mode: &AddressMode) {
+    let addr = cpu.get_operand_address(mode);
+    let value = cpu.mem_read(addr);
+
+    cpu.acc = (cpu.acc | cpu.bus.unstable_magic()) & cpu.rx & value;
+    update_zero_flag(cpu, cpu.acc);
+    update_neg_flag(cpu, cpu.acc);
+}
+
+pub fn lxa(cpu: &mut CPU, mode: &AddressMode) {
+    let addr = cpu.get_operand_address(mode);
+    let value = cpu.mem_read(addr);
+
+    cpu.acc = (cpu.acc | cpu.bus.unstable_magic()) & value;
+    cpu.rx = cpu.acc;
+    update_zero_flag(cpu, cpu.acc);
+    update_neg_flag(cpu, cpu.acc);
+}
+
+pub fn las(cpu: &mut CPU, mode: &AddressMode) {
+    let addr = cpu.get_operand_address(mode);
+    let value = cpu.mem_read(addr) & cpu.sp;
+
+    cpu.acc = value;
+    cpu.rx = value;
+    cpu.sp = value;
+    update_zero_flag(cpu, value);
+    update_neg_flag(cpu, value);
+}
+
+pub fn tas(cpu: &mut CPU, mode: &AddressMode) {
+    cpu.sp = cpu.acc & cpu.rx;
+    store_and_high(cpu, mode, cpu.sp);
+}
+
+pub fn sha(cpu: &mut CPU, mode: &AddressMode) {
+    store_and_high(cpu, mode, cpu.acc & cpu.rx);
+}
+
+pub fn shx(cpu: &mut CPU, mode: &AddressMode) {
+    store_and_high(cpu, mode, cpu.rx);
+}
+
+pub fn shy(cpu: &mut CPU, mode: &AddressMode) {
+    store_and_high(cpu, mode, cpu.ry);
+}
+
+// only called with the indexed modes, which all have an unfixed address
+fn store_and_high(cpu: &mut CPU, mode: &AddressMode, value: u8) {
+    let addr = cpu.get_store_address(mode);
+    let unfixed = cpu.unfixed_address(mode, addr).unwrap_or(addr);
+    let value = value & ((unfixed >> 8) as u8).wrapping_add(1);
+    let addr = if unfixed != addr {
+        (value as u16) << 8 | addr & 0x00FF
+    } else {
+        addr
+    };
+    cpu.mem_write(addr, value);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::create_cpu;
+    use crate::cpu::CPUStatus;
+
+    #[test]
+    fn test_unstable() {
+        // LDA #$FF, LDX #$0F, ANE #$3C
+        let mut cpu = create_cpu(&[0xA9, 0xFF, 0xA2, 0x0F, 0x8B, 0x3C]);
+        cpu.run();
+        assert_eq!(cpu.acc, 0x0C);
+        // LDA #$00, LXA #$F0: the magic decides what is left of a
+        let mut cpu = create_cpu(&[0xA9, 0x00, 0xAB, 0xF0]);
+        cpu.run();
+        assert_eq!((cpu.acc, cpu.rx), (0xE0, 0xE0));
+        let mut cpu = create_cpu(&[0xA9, 0x00, 0xAB, 0xF0]);
+        cpu.bus.set_unstable_magic(0x00);
+        cpu.run();
+        assert_eq!((cpu.acc, cpu.rx), (0x00, 0x00));
+        assert!(cpu.status.contains(CPUStatus::ZERO));
+
+        // LDX #$FF, LDY #$05, SHX $0300,Y stores $FF & $04
+        let mut cpu = create_cpu(&[0xA2, 0xFF, 0xA0, 0x05, 0x9E, 0x00, 0x03]);
+        cpu.run();
+        assert_eq!(cpu.mem_read(0x0305), 0x04);
+        // LDY #$03, LDX #$10, SHY $02F8,X crosses into page $03 & $03 instead of $03
+        let mut cpu = create_cpu(&[0xA0, 0x03, 0xA2, 0x10, 0x9C, 0xF8, 0x02]);
+        cpu.run();
+        assert_eq!(cpu.mem_read(0x0308), 0x03);
+        // LDY #$01 ... SHY $02F8,X stores $01 & $03 in page $01
+        let mut cpu = create_cpu(&[0xA0, 0x01, 0xA2, 0x10, 0x9C, 0xF8, 0x02]);
+        cpu.run();
+        assert_eq!(cpu.mem_read(0x0108), 0x01);
+        assert_eq!(cpu.mem_read(0x0308), 0x00);
+    }
+}
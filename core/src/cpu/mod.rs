@@ -0,0 +1,607 @@
+mod dispatch;
+pub mod hooks;
+mod instructions;
+#[cfg(test)]
+mod reference;
+
+use instructions::common::*;
+use instructions::*;
+
+use self::hooks::*;
+
+use crate::bus::{Bus, BusState};
+use crate::cartridge::Cartridge;
+use crate::error::EmuError;
+use crate::mem::Memory;
+use crate::opcode;
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const NMI_HANDLER_ADDR: u16 = 0xFFFA;
+const IRQ_HANDLER_ADDR: u16 = 0xFFFE;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AddressMode {
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    IndirectX,
+    IndirectY,
+    NoneAddressing,
+}
+
+/*
+http://wiki.nesdev.com/w/index.php/Status_flags
+
+    7  bit  0
+    ---- ----
+    NV_B DIZC
+    || | ||||
+    || | |||+- Carry
+    || | ||+-- Zero
+    || | |+--- Interrupt Disable
+    || | +---- Decimal
+    || +------ Break
+    ||
+    |+-------- Overflow
+    +--------- Negative
+*/
+
+bitflags::bitflags! {
+    pub struct CPUStatus: u8 {
+        const NEGATIVE          = 0b1000_0000;
+        const OVERFLOW          = 0b0100_0000;
+        const RESERVED          = 0b0010_0000;
+        const BREAK             = 0b0001_0000;
+        const DECIMAL           = 0b0000_1000;
+        const INTERRUPT_DISABLE = 0b0000_0100;
+        const ZERO              = 0b0000_0010;
+        const CARRY             = 0b0000_0001;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackFault {
+    Overflow,
+    Underflow,
+}
+
+pub struct CPU {
+    pub pc: u16,
+    pub sp: u8,
+    pub acc: u8,
+    pub rx: u8,
+    pub ry: u8,
+    pub status: CPUStatus,
+    pub bus: Bus,
+    pub hooks: Hooks,
+    // stop the frontend when the stack pointer wraps around, see take_stack_break
+    pub break_on_stack_fault: bool,
+
+    // address of the opcode being executed
+    instruction_pc: u16,
+    stack_break: Option<(StackFault, u16)>,
+    // opcode and pc of the last instruction that was skipped, see take_illegal_opcode
+    illegal_opcode: Option<(u8, u16)>,
+    history: Vec<opcode::Opcode>,
+    codes: BTreeSet<String>,
+}
+
+impl Memory for CPU {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.bus.mem_read(addr)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.bus.mem_peek(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.bus.mem_write(addr, data);
+    }
+
+    fn mem_read_u16(&mut self, addr: u16) -> u16 {
+        self.bus.mem_read_u16(addr)
+    }
+
+    fn mem_write_u16(&mut self, addr: u16, data: u16) {
+        self.bus.mem_write_u16(addr, data);
+    }
+}
+
+/*
+https://wiki.nesdev.com/w/index.php/CPU_addressing_modes
+    d,x  d,y    (d + x) % 256, the index is added to the zero page address without a carry,
+                so $FF,X with X = 1 is $00 and never leaves page zero
+    (d,x)       the pointer is read from (d + x) % 256 and (d + x + 1) % 256, a pointer at
+                $FF takes its high byte from $00
+    (d),y       the pointer is read from d and (d + 1) % 256, then y is added with a carry,
+                the result can be anywhere
+    a,x  a,y    a + x and a + y with a carry into the high byte, wrapping at $FFFF
+
+    `addr` is where the operand is, the result is the address the instruction works on
+*/
+fn resolve_address<F>(mode: &AddressMode, addr: u16, rx: u8, ry: u8, mut read: F) -> u16
+where
+    F: FnMut(u16) -> u8,
+{
+    match mode {
+        AddressMode::ZeroPage => read(addr) as u16,
+        AddressMode::ZeroPageX => {
+            let pos = read(addr);
+            pos.wrapping_add(rx) as u16
+        }
+        AddressMode::ZeroPageY => {
+            let pos = read(addr);
+            pos.wrapping_add(ry) as u16
+        }
+        AddressMode::Absolute | AddressMode::AbsoluteX | AddressMode::AbsoluteY => {
+            // little-endian
+            let lo = read(addr) as u16;
+            let hi = read(addr.wrapping_add(1)) as u16;
+            let pos = hi << 8 | lo;
+            match mode {
+                AddressMode::AbsoluteX => pos.wrapping_add(rx as u16),
+                AddressMode::AbsoluteY => pos.wrapping_add(ry as u16),
+                _ => pos,
+            }
+        }
+        AddressMode::IndirectX => {
+            let ptr = read(addr).wrapping_add(rx);
+            let lo = read(ptr as u16);
+            let hi = read(ptr.wrapping_add(1) as u16);
+            (hi as u16) << 8 | (lo as u16)
+        }
+        AddressMode::IndirectY => {
+            let base = read(addr);
+            let lo = read(base as u16);
+            let hi = read(base.wrapping_add(1) as u16);
+            let deref_base = (hi as u16) << 8 | (lo as u16);
+            deref_base.wrapping_add(ry as u16)
+        }
+        _ => {
+            panic!("not support for {:?}", mode)
+        }
+    }
+}
+
+// BRK, JSR, RTI, JMP, RTS, JMP (indirect) and the xxy10000 branches
+fn sets_pc(op: u8) -> bool {
+    match op {
+        0x00 | 0x20 | 0x40 | 0x4C | 0x60 | 0x6C => true,
+        _ => op & 0x1F == 0x10,
+    }
+}
+
+#[derive(Clone)]
+pub struct CpuState {
+    pc: u16,
+    sp: u8,
+    acc: u8,
+    rx: u8,
+    ry: u8,
+    status: CPUStatus,
+    bus: BusState,
+}
+
+#[deprecated(note = "use CPU::from_ines_bytes or CPU::from_cartridge")]
+pub trait With<T> {
+    fn with(value: T) -> Self;
+}
+
+#[allow(deprecated)]
+impl With<Vec<u8>> for CPU {
+    fn with(value: Vec<u8>) -> Self {
+        CPU::from_ines_bytes(&value).unwrap()
+    }
+}
+
+impl CPU {
+    pub fn new(bus: Bus) -> Self {
+        CPU {
+            pc: 0,
+            sp: STACK_RESET_LOC,
+            acc: 0,
+            rx: 0,
+            ry: 0,
+            status: CPUStatus::from_bits_truncate(0b0011_0100),
+            bus: bus,
+            hooks: Hooks::new(),
+            break_on_stack_fault: false,
+
+            instruction_pc: 0,
+            stack_break: None,
+            illegal_opcode: None,
+            history: Vec::new(),
+            codes: BTreeSet::new(),
+        }
+    }
+
+    pub fn from_ines_bytes(raw: &[u8]) -> Result<Self, EmuError> {
+        let cartridge = Cartridge::new(raw).map_err(EmuError::InvalidRom)?;
+        Ok(CPU::from_cartridge(cartridge))
+    }
+
+    pub fn from_cartridge(cartridge: Cartridge) -> Self {
+        CPU::new(Bus::new(cartridge))
+    }
+
+    pub fn reset(&mut self) {
+        self.acc = 0;
+        self.rx = 0;
+        self.ry = 0;
+        self.status = CPUStatus::from_bits_truncate(0b0011_0100);
+
+        let return_pc = self.pc;
+        self.pc = self.mem_read_u16(RESET_INTERRUPT_MEM_LOC);
+        self.sp = STACK_RESET_LOC;
+
+        self.hooks.interrupt(InterruptEvent {
+            interrupt: Interrupt::Reset,
+            return_pc: return_pc,
+            handler_pc: self.pc,
+        });
+    }
+
+    pub fn save_state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            sp: self.sp,
+            acc: self.acc,
+            rx: self.rx,
+            ry: self.ry,
+            status: self.status,
+            bus: self.bus.save_state(),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &CpuState) {
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.acc = state.acc;
+        self.rx = state.rx;
+        self.ry = state.ry;
+        self.status = state.status;
+        self.bus.load_state(&state.bus);
+    }
+
+    // runaway recursion or unbalanced pulls wrap sp around page $01 and silently
+    // overwrite the other end of the stack
+    pub(crate) fn stack_fault(&mut self, fault: StackFault) {
+        let event = StackEvent {
+            pc: self.instruction_pc,
+            sp: self.sp,
+        };
+        log!(
+            "warning: stack {:?} at pc {:#06X} (sp {:#04X})",
+            fault,
+            event.pc,
+            event.sp
+        );
+
+        match fault {
+            StackFault::Overflow => self.hooks.stack_overflow(event),
+            StackFault::Underflow => self.hooks.stack_underflow(event),
+        }
+        if self.break_on_stack_fault {
+            self.stack_break = Some((fault, event.pc));
+        }
+    }
+
+    // the fault and offending pc when break_on_stack_fault asked to stop
+    pub fn take_stack_break(&mut self) -> Option<(StackFault, u16)> {
+        self.stack_break.take()
+    }
+
+    pub fn take_illegal_opcode(&mut self) -> Option<(u8, u16)> {
+        self.illegal_opcode.take()
+    }
+
+    pub fn get_absolute_address(&mut self, mode: &AddressMode, addr: u16) -> u16 {
+        let (rx, ry) = (self.rx, self.ry);
+        resolve_address(mode, addr, rx, ry, |addr| self.mem_read(addr))
+    }
+
+    // same as get_absolute_address, but the pointer reads do not touch any state
+    pub fn peek_absolute_address(&self, mode: &AddressMode, addr: u16) -> u16 {
+        resolve_address(mode, addr, self.rx, self.ry, |addr| self.mem_peek(addr))
+    }
+
+    pub fn get_operand_address(&mut self, mode: &AddressMode) -> u16 {
+        match mode {
+            AddressMode::Immediate => self.pc,
+            _ => self.get_absolute_address(mode, self.pc),
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/CPU_addressing_modes
+    https://www.nesdev.org/6502_cpu.txt
+
+        a,x  a,y  (d),y  the index is added to the low byte first, the carry into the high
+                         byte takes another cycle. stores and read-modify-writes always
+                         spend that cycle reading from the unfixed address, high byte of
+                         the base and low byte of the sum, as they cannot take back a write
+                         to the wrong page. $20FF,X with X = 8 reads $2007 before it
+                         writes $2107
+    */
+    pub fn get_store_address(&mut self, mode: &AddressMode) -> u16 {
+        let addr = self.get_operand_address(mode);
+        if self.bus.accuracy().dummy_reads() {
+            if let Some(unfixed) = self.unfixed_address(mode, addr) {
+                self.mem_read(unfixed);
+            }
+        }
+        addr
+    }
+
+    // the address before the carry into the high byte, None for modes without that cycle
+    pub fn unfixed_address(&self, mode: &AddressMode, addr: u16) -> Option<u16> {
+        let index = match mode {
+            AddressMode::AbsoluteX => self.rx,
+            AddressMode::AbsoluteY | AddressMode::IndirectY => self.ry,
+            _ => return None,
+        };
+        let base = addr.wrapping_sub(index as u16);
+        Some(base & 0xFF00 | addr & 0x00FF)
+    }
+
+    // runs from the reset vector until the program reaches a BRK
+    pub fn run(&mut self) {
+        self.reset();
+        self.run_until_brk();
+    }
+
+    pub fn run_until_brk(&mut self) {
+        while self.mem_peek(self.pc) != 0x00 {
+            self.interprect();
+        }
+    }
+
+    pub fn interprect(&mut self) {
+        self.interprect_with_callback(|_| {});
+    }
+
+    fn interreupt_nmi(&mut self) {
+        let mut cur_status = self.status.clone();
+
+        // https://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+        cur_status.insert(CPUStatus::BREAK);
+        cur_status.remove(CPUStatus::RESERVED);
+
+        // store pc, status
+        stack_push_u16(self, self.pc);
+        stack_push(self, cur_status.bits);
+
+        self.status.insert(CPUStatus::INTERRUPT_DISABLE);
+        let return_pc = self.pc;
+        self.pc = self.mem_read_u16(NMI_HANDLER_ADDR);
+
+        self.bus.tick(2);
+        self.hooks.interrupt(InterruptEvent {
+            interrupt: Interrupt::NMI,
+            return_pc: return_pc,
+            handler_pc: self.pc,
+        });
+    }
+
+    // https://wiki.nesdev.com/w/index.php/IRQ
+    // level triggered: it fires again after RTI until the apu or the cartridge is acknowledged
+    fn interrupt_irq(&mut self) {
+        let mut cur_status = self.status.clone();
+        cur_status.remove(CPUStatus::BREAK);
+        cur_status.insert(CPUStatus::RESERVED);
+
+        stack_push_u16(self, self.pc);
+        stack_push(self, cur_status.bits);
+
+        self.status.insert(CPUStatus::INTERRUPT_DISABLE);
+        let return_pc = self.pc;
+        self.pc = self.mem_read_u16(IRQ_HANDLER_ADDR);
+
+        self.bus.tick(7);
+        self.hooks.interrupt(InterruptEvent {
+            interrupt: Interrupt::IRQ,
+            return_pc: return_pc,
+            handler_pc: self.pc,
+        });
+    }
+
+    pub fn interprect_with_callback<T>(&mut self, mut callback: T)
+    where
+        T: FnMut(&mut CPU) -> (),
+    {
+        if self.bus.should_nmi() {
+            self.interreupt_nmi();
+        } else if self.bus.irq_pending() && !self.status.contains(CPUStatus::INTERRUPT_DISABLE) {
+            self.interrupt_irq();
+        }
+        callback(self);
+
+        self.instruction_pc = self.pc;
+        let op = self.mem_read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+
+        let code = match opcode::OPCODES_MAP[op as usize] {
+            Some(code) => code,
+            None => {
+                self.illegal_opcode = Some((op, self.instruction_pc));
+                &opcode::UNSUPPORTED
+            }
+        };
+        // self.history.push(**code);
+        // self.codes.insert(String::from(code.name));
+
+        dispatch::execute(self, op, &code.mode);
+
+        // jumps, returns and branches move pc themselves, even when the target happens to
+        // be the byte after the opcode
+        if !sets_pc(op) {
+            self.pc = self.pc.wrapping_add((code.bytes - 1) as u16);
+        }
+
+        self.bus.tick(code.cycles);
+        self.hooks.instruction_executed(InstructionEvent {
+            pc: self.instruction_pc,
+            opcode: op,
+            cycles: code.cycles,
+        });
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::cartridge::test::create_rom;
+    use crate::config::Accuracy;
+
+    // nrom cartridge with the program at $8000, which is also the reset vector.
+    // the rest of the rom is BRK so run() stops right after the program
+    pub fn create_cpu(program: &[u8]) -> CPU {
+        let mut prg = vec![0x00; 0x4000];
+        prg[..program.len()].copy_from_slice(program);
+        prg[0x3FFC] = 0x00;
+        prg[0x3FFD] = 0x80;
+
+        let mut cpu = CPU::from_ines_bytes(&create_rom(0b0000_0000, 0, prg)).unwrap();
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_from_ines_bytes() {
+        let program = [0xA9, 0x42, 0x00];
+        let mut cpu = create_cpu(&program);
+        cpu.run();
+        assert_eq!(cpu.acc, 0x42);
+
+        assert_eq!(
+            CPU::from_ines_bytes(&program).err(),
+            Some(EmuError::InvalidRom(String::from(
+                "not valid nes cartridge!"
+            )))
+        );
+        let mut truncated = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        truncated.truncate(0x2000);
+        assert!(CPU::from_ines_bytes(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_zero_page_wrap() {
+        let mut cpu = create_cpu(&[]);
+        cpu.mem_write(0x0300, 0xFF);
+        cpu.rx = 0x01;
+        cpu.ry = 0x02;
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::ZeroPageX, 0x0300),
+            0x0000
+        );
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::ZeroPageY, 0x0300),
+            0x0001
+        );
+        // the same operand absolute carries into the next page
+        cpu.mem_write(0x0301, 0x00);
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::AbsoluteX, 0x0300),
+            0x0100
+        );
+
+        // ($FF,X) with X = 0 reads its high byte from $00, not $0100
+        cpu.rx = 0x00;
+        cpu.mem_write(0x00FF, 0x34);
+        cpu.mem_write(0x0000, 0x02);
+        cpu.mem_write(0x0100, 0x07);
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::IndirectX, 0x0300),
+            0x0234
+        );
+        // ($FE,X) with X = 1 is the same pointer
+        cpu.mem_write(0x0300, 0xFE);
+        cpu.rx = 0x01;
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::IndirectX, 0x0300),
+            0x0234
+        );
+        // ($FF),Y too, then adds Y
+        cpu.mem_write(0x0300, 0xFF);
+        assert_eq!(
+            cpu.get_absolute_address(&AddressMode::IndirectY, 0x0300),
+            0x0236
+        );
+    }
+
+    #[test]
+    fn test_dummy_write() {
+        // point $2006 at $2000, then INC $2007
+        let program = [
+            0xA9, 0x20, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20, 0xEE, 0x07, 0x20, 0x00,
+        ];
+        let mut cpu = create_cpu(&program);
+        cpu.run();
+        assert_eq!(cpu.bus.ppu().address_register.get_address(), 0x2002);
+
+        // the write of the unmodified value advances the address once more
+        let mut cpu = create_cpu(&program);
+        cpu.bus.set_accuracy(Accuracy::Cycle);
+        // past the ppu warm-up
+        while cpu.bus.cycles() < 30000 {
+            cpu.bus.tick(2);
+        }
+        cpu.run();
+        assert_eq!(cpu.bus.ppu().address_register.get_address(), 0x2003);
+    }
+
+    #[test]
+    fn test_dummy_read() {
+        // point $2006 at $2000, then STA $20FF,X with X = 8
+        let program = [
+            0xA9, 0x20, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20, 0xA2, 0x08, 0x9D, 0xFF,
+            0x20, 0x00,
+        ];
+        let mut cpu = create_cpu(&program);
+        cpu.rx = 0x08;
+        assert_eq!(
+            cpu.unfixed_address(&AddressMode::AbsoluteX, 0x2107),
+            Some(0x2007)
+        );
+        assert_eq!(cpu.unfixed_address(&AddressMode::Absolute, 0x2107), None);
+        cpu.run();
+        assert_eq!(cpu.bus.ppu().address_register.get_address(), 0x2001);
+
+        // the read of $2007 before the high byte is fixed advances the address first
+        let mut cpu = create_cpu(&program);
+        cpu.bus.set_accuracy(Accuracy::Cycle);
+        while cpu.bus.cycles() < 30000 {
+            cpu.bus.tick(2);
+        }
+        cpu.run();
+        assert_eq!(cpu.bus.ppu().address_register.get_address(), 0x2002);
+    }
+
+    #[test]
+    fn test_irq() {
+        // CLI, then spin while the apu frame counter runs; the handler at $8010 spins too
+        let mut prg = vec![0x00; 0x4000];
+        prg[..4].copy_from_slice(&[0x58, 0x4C, 0x01, 0x80]);
+        prg[0x10..0x13].copy_from_slice(&[0x4C, 0x10, 0x80]);
+        prg[0x3FFC..].copy_from_slice(&[0x00, 0x80, 0x10, 0x80]);
+        let mut cpu = CPU::from_ines_bytes(&create_rom(0b0000_0000, 0, prg)).unwrap();
+        cpu.reset();
+
+        while cpu.pc != 0x8010 && cpu.bus.cycles() < 40000 {
+            cpu.interprect();
+        }
+        assert_eq!(cpu.pc, 0x8010);
+        assert!(cpu.status.contains(CPUStatus::INTERRUPT_DISABLE));
+        // pushed with B clear, unlike BRK
+        let pushed = cpu.mem_read(0x0100 + cpu.sp.wrapping_add(1) as u16);
+        assert_eq!(pushed & 0b0011_0000, 0b0010_0000);
+    }
+}
@@ -0,0 +1,583 @@
+// a deliberately simple 6502 to check the real CPU against, one instruction at a time.
+// it shares nothing with the interpreter: opcodes are decoded from their aaabbbcc bit
+// fields instead of the opcode table, memory is a flat 64KB array with rom from $8000
+// and there is no decimal mode, like on the 2A03.
+// https://llx.com/Neil/a2/opcodes.html
+use alloc::vec;
+use alloc::vec::Vec;
+
+const CARRY: u8 = 0b0000_0001;
+const ZERO: u8 = 0b0000_0010;
+const INTERRUPT_DISABLE: u8 = 0b0000_0100;
+const DECIMAL: u8 = 0b0000_1000;
+const BREAK: u8 = 0b0001_0000;
+const RESERVED: u8 = 0b0010_0000;
+const OVERFLOW: u8 = 0b0100_0000;
+const NEGATIVE: u8 = 0b1000_0000;
+
+const ROM_BEGIN: u16 = 0x8000;
+const IRQ_VECTOR: u16 = 0xFFFE;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    IndirectX,
+    IndirectY,
+}
+
+pub struct Reference {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub p: u8,
+    pub pc: u16,
+    pub mem: Vec<u8>,
+    // every address the last instruction read or wrote
+    pub touched: Vec<u16>,
+}
+
+impl Reference {
+    pub fn new() -> Self {
+        Reference {
+            a: 0,
+            x: 0,
+            y: 0,
+            sp: 0xFD,
+            p: RESERVED | INTERRUPT_DISABLE,
+            pc: 0,
+            mem: vec![0; 0x10000],
+            touched: Vec::new(),
+        }
+    }
+
+    fn read(&mut self, addr: u16) -> u8 {
+        self.touched.push(addr);
+        self.mem[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.touched.push(addr);
+        if addr < ROM_BEGIN {
+            self.mem[addr as usize] = data;
+        }
+    }
+
+    fn fetch(&mut self) -> u8 {
+        let data = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        data
+    }
+
+    fn fetch_u16(&mut self) -> u16 {
+        let lo = self.fetch() as u16;
+        let hi = self.fetch() as u16;
+        hi << 8 | lo
+    }
+
+    fn push(&mut self, data: u8) {
+        self.write(0x0100 | self.sp as u16, data);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pull(&mut self) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        self.read(0x0100 | self.sp as u16)
+    }
+
+    fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    fn set_zn(&mut self, value: u8) {
+        self.set_flag(ZERO, value == 0);
+        self.set_flag(NEGATIVE, value & 0x80 != 0);
+    }
+
+    fn address(&mut self, mode: Mode) -> u16 {
+        match mode {
+            Mode::Immediate => {
+                let addr = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                addr
+            }
+            Mode::ZeroPage => self.fetch() as u16,
+            Mode::ZeroPageX => self.fetch().wrapping_add(self.x) as u16,
+            Mode::ZeroPageY => self.fetch().wrapping_add(self.y) as u16,
+            Mode::Absolute => self.fetch_u16(),
+            Mode::AbsoluteX => self.fetch_u16().wrapping_add(self.x as u16),
+            Mode::AbsoluteY => self.fetch_u16().wrapping_add(self.y as u16),
+            Mode::IndirectX => {
+                let pointer = self.fetch().wrapping_add(self.x);
+                let lo = self.read(pointer as u16) as u16;
+                let hi = self.read(pointer.wrapping_add(1) as u16) as u16;
+                hi << 8 | lo
+            }
+            Mode::IndirectY => {
+                let pointer = self.fetch();
+                let lo = self.read(pointer as u16) as u16;
+                let hi = self.read(pointer.wrapping_add(1) as u16) as u16;
+                (hi << 8 | lo).wrapping_add(self.y as u16)
+            }
+            Mode::Accumulator => unreachable!(),
+        }
+    }
+
+    fn add(&mut self, value: u8) {
+        let sum = self.a as u16 + value as u16 + (self.p & CARRY) as u16;
+        let result = sum as u8;
+        self.set_flag(CARRY, sum > 0xFF);
+        self.set_flag(OVERFLOW, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.a = result;
+        self.set_zn(result);
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CARRY, register >= value);
+        self.set_zn(register.wrapping_sub(value));
+    }
+
+    // ASL ROL LSR ROR by aaa
+    fn shift(&mut self, aaa: u8, value: u8) -> u8 {
+        let carry = self.p & CARRY;
+        let (result, carry_out) = match aaa {
+            0 => (value << 1, value & 0x80),
+            1 => (value << 1 | carry, value & 0x80),
+            2 => (value >> 1, value & 0x01),
+            _ => (value >> 1 | carry << 7, value & 0x01),
+        };
+        self.set_flag(CARRY, carry_out != 0);
+        self.set_zn(result);
+        result
+    }
+
+    fn branch(&mut self, taken: bool) {
+        let offset = self.fetch() as i8;
+        if taken {
+            self.pc = self.pc.wrapping_add(offset as u16);
+        }
+    }
+
+    // runs one instruction, false for the unofficial opcodes the model does not know
+    pub fn step(&mut self) -> bool {
+        self.touched.clear();
+        let op = self.fetch();
+        match op {
+            0x00 => {
+                self.pc = self.pc.wrapping_add(1);
+                self.push((self.pc >> 8) as u8);
+                self.push(self.pc as u8);
+                self.push(self.p | BREAK | RESERVED);
+                self.p |= INTERRUPT_DISABLE;
+                let lo = self.read(IRQ_VECTOR) as u16;
+                let hi = self.read(IRQ_VECTOR + 1) as u16;
+                self.pc = hi << 8 | lo;
+            }
+            0x20 => {
+                // pushes between reading the low and the high byte of the target
+                let lo = self.fetch() as u16;
+                self.push((self.pc >> 8) as u8);
+                self.push(self.pc as u8);
+                let hi = self.fetch() as u16;
+                self.pc = hi << 8 | lo;
+            }
+            0x40 => {
+                self.p = self.pull() & !BREAK | RESERVED;
+                let lo = self.pull() as u16;
+                let hi = self.pull() as u16;
+                self.pc = hi << 8 | lo;
+            }
+            0x60 => {
+                let lo = self.pull() as u16;
+                let hi = self.pull() as u16;
+                self.pc = (hi << 8 | lo).wrapping_add(1);
+            }
+            0x4C => self.pc = self.fetch_u16(),
+            0x6C => {
+                // the pointer's high byte is read from the same page
+                let pointer = self.fetch_u16();
+                let lo = self.read(pointer) as u16;
+                let hi = self.read(pointer & 0xFF00 | (pointer as u8).wrapping_add(1) as u16);
+                self.pc = (hi as u16) << 8 | lo;
+            }
+            0x08 => self.push(self.p | BREAK | RESERVED),
+            0x28 => self.p = self.pull() & !BREAK | RESERVED,
+            0x48 => self.push(self.a),
+            0x68 => {
+                self.a = self.pull();
+                self.set_zn(self.a);
+            }
+            0x18 => self.set_flag(CARRY, false),
+            0x38 => self.set_flag(CARRY, true),
+            0x58 => self.set_flag(INTERRUPT_DISABLE, false),
+            0x78 => self.set_flag(INTERRUPT_DISABLE, true),
+            0xB8 => self.set_flag(OVERFLOW, false),
+            0xD8 => self.set_flag(DECIMAL, false),
+            0xF8 => self.set_flag(DECIMAL, true),
+            0xAA | 0x8A | 0xA8 | 0x98 | 0xBA | 0xE8 | 0xCA | 0xC8 | 0x88 => {
+                let value = match op {
+                    0xAA => self.a,
+                    0x8A => self.x,
+                    0xA8 => self.a,
+                    0x98 => self.y,
+                    0xBA => self.sp,
+                    0xE8 => self.x.wrapping_add(1),
+                    0xCA => self.x.wrapping_sub(1),
+                    0xC8 => self.y.wrapping_add(1),
+                    _ => self.y.wrapping_sub(1),
+                };
+                match op {
+                    0xAA | 0xBA | 0xE8 | 0xCA => self.x = value,
+                    0x8A | 0x98 => self.a = value,
+                    _ => self.y = value,
+                }
+                self.set_zn(value);
+            }
+            0x9A => self.sp = self.x,
+            0xEA => {}
+            // xxy10000: xx picks the flag, y the value it is compared with
+            _ if op & 0x1F == 0x10 => {
+                let flag = [NEGATIVE, OVERFLOW, CARRY, ZERO][(op >> 6) as usize];
+                let set = self.p & flag != 0;
+                self.branch(set == (op & 0x20 != 0));
+            }
+            _ => return self.step_group(op),
+        }
+        true
+    }
+
+    fn step_group(&mut self, op: u8) -> bool {
+        let (aaa, bbb, cc) = (op >> 5, (op >> 2) & 0b111, op & 0b11);
+        match cc {
+            0b01 => {
+                let mode = [
+                    Mode::IndirectX,
+                    Mode::ZeroPage,
+                    Mode::Immediate,
+                    Mode::Absolute,
+                    Mode::IndirectY,
+                    Mode::ZeroPageX,
+                    Mode::AbsoluteY,
+                    Mode::AbsoluteX,
+                ][bbb as usize];
+                if aaa == 4 && mode == Mode::Immediate {
+                    return false;
+                }
+                let addr = self.address(mode);
+                if aaa == 4 {
+                    self.write(addr, self.a);
+                    return true;
+                }
+                let value = self.read(addr);
+                match aaa {
+                    0 => self.a |= value,
+                    1 => self.a &= value,
+                    2 => self.a ^= value,
+                    3 => self.add(value),
+                    5 => self.a = value,
+                    6 => {
+                        self.compare(self.a, value);
+                        return true;
+                    }
+                    _ => self.add(!value),
+                }
+                self.set_zn(self.a);
+            }
+            0b10 => {
+                // STX and LDX index with y instead of x
+                let uses_y = aaa == 4 || aaa == 5;
+                let mode = match bbb {
+                    0 if aaa == 5 => Mode::Immediate,
+                    1 => Mode::ZeroPage,
+                    2 if aaa < 4 => Mode::Accumulator,
+                    3 => Mode::Absolute,
+                    5 if uses_y => Mode::ZeroPageY,
+                    5 => Mode::ZeroPageX,
+                    7 if aaa == 5 => Mode::AbsoluteY,
+                    7 if aaa != 4 => Mode::AbsoluteX,
+                    _ => return false,
+                };
+                if mode == Mode::Accumulator {
+                    self.a = self.shift(aaa, self.a);
+                    return true;
+                }
+                let addr = self.address(mode);
+                match aaa {
+                    4 => self.write(addr, self.x),
+                    5 => {
+                        self.x = self.read(addr);
+                        self.set_zn(self.x);
+                    }
+                    _ => {
+                        let value = self.read(addr);
+                        let result = match aaa {
+                            6 => value.wrapping_sub(1),
+                            7 => value.wrapping_add(1),
+                            _ => self.shift(aaa, value),
+                        };
+                        self.set_zn(result);
+                        self.write(addr, result);
+                    }
+                }
+            }
+            0b00 => {
+                let mode = match bbb {
+                    0 if aaa >= 5 => Mode::Immediate,
+                    1 if aaa == 1 || aaa >= 4 => Mode::ZeroPage,
+                    3 if aaa == 1 || aaa >= 4 => Mode::Absolute,
+                    5 if aaa == 4 || aaa == 5 => Mode::ZeroPageX,
+                    7 if aaa == 5 => Mode::AbsoluteX,
+                    _ => return false,
+                };
+                let addr = self.address(mode);
+                match aaa {
+                    1 => {
+                        let value = self.read(addr);
+                        self.set_flag(ZERO, self.a & value == 0);
+                        self.set_flag(OVERFLOW, value & 0x40 != 0);
+                        self.set_flag(NEGATIVE, value & 0x80 != 0);
+                    }
+                    4 => self.write(addr, self.y),
+                    5 => {
+                        self.y = self.read(addr);
+                        self.set_zn(self.y);
+                    }
+                    6 => {
+                        let value = self.read(addr);
+                        self.compare(self.y, value);
+                    }
+                    _ => {
+                        let value = self.read(addr);
+                        self.compare(self.x, value);
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::create_cpu;
+    use crate::cpu::{AddressMode, CPUStatus, CPU};
+    use crate::mem::Memory;
+    use crate::opcode::OPCODES;
+    use core::cell::RefCell;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+
+    const RAM_SIZE: u16 = 0x0800;
+    const CASES: u32 = 10_000;
+    // B and bit 5 only exist on the stack, not in the register
+    const FLAGS: u8 = !(BREAK | RESERVED);
+
+    // the cpu and what its rom looks like to the reference
+    fn cpu_and_rom() -> (CPU, Vec<u8>) {
+        let cpu = create_cpu(&[]);
+        let rom = (ROM_BEGIN..=0xFFFF)
+            .map(|addr| cpu.mem_peek(addr))
+            .collect();
+        (cpu, rom)
+    }
+
+    // random ram with one instruction at a random place in it, and random registers
+    #[derive(Debug, Clone)]
+    struct Case {
+        ram: Vec<u8>,
+        pc: u16,
+        op: u8,
+        // a, x, y, sp and p
+        registers: [u8; 5],
+    }
+
+    impl Case {
+        fn reference(&self, rom: &[u8]) -> Reference {
+            let [a, x, y, sp, p] = self.registers;
+            let mut reference = Reference::new();
+            reference.mem[..RAM_SIZE as usize].copy_from_slice(&self.ram);
+            reference.mem[ROM_BEGIN as usize..].copy_from_slice(rom);
+            reference.pc = self.pc;
+            reference.mem[self.pc as usize] = self.op;
+            reference.a = a;
+            reference.x = x;
+            reference.y = y;
+            reference.sp = sp;
+            reference.p = p & FLAGS | RESERVED;
+            reference
+        }
+    }
+
+    // BRK ends programs in this interpreter instead of calling the irq handler, so it is
+    // left out
+    fn random_case() -> impl Strategy<Value = Case> {
+        (
+            prop::collection::vec(any::<u8>(), RAM_SIZE as usize),
+            0..RAM_SIZE - 3,
+            1..=0xFFu8,
+            any::<[u8; 5]>(),
+        )
+            .prop_map(|(ram, pc, op, registers)| Case {
+                ram: ram,
+                pc: pc,
+                op: op,
+                registers: registers,
+            })
+    }
+
+    // runs the instruction at the reference's pc on both and compares registers and ram.
+    // cases that reach outside ram and rom are skipped, the reference has no mirrors or
+    // io registers. returns the opcode when the case ran
+    fn check_instruction(cpu: &mut CPU, reference: &mut Reference) -> Option<u8> {
+        for addr in 0..RAM_SIZE {
+            cpu.mem_write(addr, reference.mem[addr as usize]);
+        }
+        cpu.acc = reference.a;
+        cpu.rx = reference.x;
+        cpu.ry = reference.y;
+        cpu.sp = reference.sp;
+        cpu.pc = reference.pc;
+        cpu.status = CPUStatus::from_bits_truncate(reference.p);
+
+        let op = reference.mem[reference.pc as usize];
+        if !reference.step() {
+            return None;
+        }
+        if reference
+            .touched
+            .iter()
+            .any(|addr| *addr >= RAM_SIZE && *addr < ROM_BEGIN)
+        {
+            return None;
+        }
+        cpu.interprect();
+
+        let registers = |a, x, y, sp, pc, p: u8| (a, x, y, sp, pc, p & FLAGS);
+        assert_eq!(
+            registers(cpu.acc, cpu.rx, cpu.ry, cpu.sp, cpu.pc, cpu.status.bits()),
+            registers(
+                reference.a,
+                reference.x,
+                reference.y,
+                reference.sp,
+                reference.pc,
+                reference.p
+            ),
+            "opcode {:#04X}: (a, x, y, sp, pc, p) differ",
+            op
+        );
+        for addr in 0..RAM_SIZE {
+            assert_eq!(
+                cpu.mem_peek(addr),
+                reference.mem[addr as usize],
+                "opcode {:#04X}: ram at {:#06X} differs",
+                op,
+                addr
+            );
+        }
+        Some(op)
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        // one cpu for all cases, making a new one each time is most of the run time
+        let (cpu, rom) = cpu_and_rom();
+        let cpu = RefCell::new(cpu);
+        // failing cases are saved next to this file like proptest! does
+        let config = ProptestConfig {
+            cases: CASES,
+            source_file: Some(file!()),
+            ..ProptestConfig::default()
+        };
+        let mut runner = TestRunner::new(config);
+        let result = runner.run(&random_case(), |case| {
+            check_instruction(&mut cpu.borrow_mut(), &mut case.reference(&rom));
+            Ok(())
+        });
+        if let Err(failure) = result {
+            panic!("{}", failure);
+        }
+    }
+
+    // every official opcode except BRK once, on zeroed ram so every operand and pointer
+    // stays inside it. the random cases above may miss some
+    #[test]
+    fn test_every_opcode_matches_reference() {
+        let (mut cpu, rom) = cpu_and_rom();
+        let mut ran = 0;
+        for op in 1..=0xFF {
+            let mut reference = Reference::new();
+            reference.mem[ROM_BEGIN as usize..].copy_from_slice(&rom);
+            reference.pc = 0x0200;
+            reference.mem[0x0200] = op;
+            reference.sp = 0xFD;
+            reference.p = RESERVED;
+            if check_instruction(&mut cpu, &mut reference).is_some() {
+                ran += 1;
+            }
+        }
+        assert_eq!(ran, 150);
+    }
+
+    // every d,x / d,y / (d,x) opcode with every index, on operands next to the end of page
+    // zero. the zero page only holds $02-$07, so every pointer lands in ram and a pointer
+    // or operand taken from page one instead of wrapping shows up as a difference
+    #[test]
+    fn test_zero_page_wrap_matches_reference() {
+        let (mut cpu, rom) = cpu_and_rom();
+        // any bytes do outside of page zero
+        let mut ram: Vec<u8> = (0..RAM_SIZE as usize)
+            .map(|addr| (addr * 97 + 13) as u8)
+            .collect();
+        for byte in ram[..0x100].iter_mut() {
+            *byte = *byte % 6 + 2;
+        }
+
+        let wrapping = OPCODES.iter().filter(|opcode| {
+            matches!(
+                opcode.mode,
+                AddressMode::ZeroPageX | AddressMode::ZeroPageY | AddressMode::IndirectX
+            )
+        });
+        let mut checked = 0;
+        for opcode in wrapping {
+            for operand in [0x00, 0x01, 0x80, 0xFE, 0xFF].iter() {
+                for index in 0..=0xFF {
+                    let mut reference = Reference::new();
+                    reference.mem[ROM_BEGIN as usize..].copy_from_slice(&rom);
+                    reference.mem[..RAM_SIZE as usize].copy_from_slice(&ram);
+                    reference.pc = 0x0100;
+                    reference.mem[0x0100] = opcode.op;
+                    reference.mem[0x0101] = *operand;
+                    reference.x = index;
+                    reference.y = index;
+                    assert_eq!(
+                        check_instruction(&mut cpu, &mut reference),
+                        Some(opcode.op),
+                        "{} {:?} ${:02X} with {:#04X} did not run",
+                        opcode.name,
+                        opcode.mode,
+                        operand,
+                        index
+                    );
+                    checked += 1;
+                }
+            }
+        }
+        // 26 official opcodes
+        assert_eq!(checked, 26 * 5 * 256);
+    }
+}
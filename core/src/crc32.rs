@@ -0,0 +1,47 @@
+// https://www.w3.org/TR/PNG/#D-CRCAppendix
+// the same crc-32 (polynomial 0xEDB88320) is used by png chunks and rom checksums
+static CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+pub fn update(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for byte in data.iter() {
+        c = CRC_TABLE[((c ^ *byte as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    update(0, data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(update(crc32(b"12345"), b"6789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,35 @@
+// rows for spreadsheets and scripts. fields with a comma, a quote or a line break are
+// quoted, quotes inside doubled
+// https://www.rfc-editor.org/rfc/rfc4180
+
+use alloc::string::String;
+
+pub fn write_row<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let field = field.as_ref();
+        if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_row() {
+        let mut out = String::new();
+        write_row(&mut out, &["pc", "label"]);
+        write_row(&mut out, &["8000", "wait, \"vblank\""]);
+        assert_eq!(out, "pc,label\n8000,\"wait, \"\"vblank\"\"\"\n");
+    }
+}
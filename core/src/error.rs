@@ -0,0 +1,19 @@
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmuError {
+    // the rom could not be parsed as an iNES image
+    InvalidRom(String),
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::InvalidRom(reason) => write!(f, "invalid rom: {}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EmuError {}
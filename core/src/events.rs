@@ -0,0 +1,81 @@
+// things that happened inside the emulator that a user or a script may want to know
+// about. frontends drain them once per frame (toasts), tests assert on them
+
+use crate::lint::LintWarning;
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// a frontend that never drains the log loses the oldest events instead of growing it
+const MAX_PENDING_EVENTS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // a cartridge was inserted, at power-on or by Nes::load_cartridge
+    RomLoaded { title: Option<String>, crc32: u32 },
+    // battery backed ram was handed to the frontend to persist
+    SramSaved { bytes: usize },
+    // a save state was restored
+    StateLoaded,
+    // a part of another emulator's savestate there was no place for, see Nes::import_state
+    StateImportSkipped { part: String },
+    // the cpu hit an opcode it does not emulate and skipped it
+    IllegalOpcode { pc: u16, opcode: u8 },
+    // playback or a peer no longer agrees with the local emulation
+    DesyncDetected { frame: u64 },
+    // a disk system side was flipped, None when the disk was ejected
+    DiskSideChanged { side: Option<u8> },
+    // every condition of an achievement held on the same frame
+    AchievementUnlocked { id: u32, title: String },
+    // a homebrew mistake the lint mode caught at `pc`, see Nes::enable_lint
+    Lint { pc: u16, warning: LintWarning },
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    pending: VecDeque<Event>,
+    dropped: u64,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.pending.len() == MAX_PENDING_EVENTS {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back(event);
+    }
+
+    // oldest first
+    pub fn take(&mut self) -> Vec<Event> {
+        self.pending.drain(..).collect()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bounded() {
+        let mut log = EventLog::new();
+        for frame in 0..MAX_PENDING_EVENTS as u64 + 2 {
+            log.push(Event::DesyncDetected { frame: frame });
+        }
+
+        let events = log.take();
+        assert_eq!(events.len(), MAX_PENDING_EVENTS);
+        assert_eq!(events[0], Event::DesyncDetected { frame: 2 });
+        assert_eq!(log.dropped(), 2);
+        assert!(log.take().is_empty());
+    }
+}
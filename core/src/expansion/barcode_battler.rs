@@ -0,0 +1,32 @@
+use super::ExpansionDevice;
+use crate::joypad::Port;
+
+pub const NAME: &str = "barcode_battler";
+
+/*
+https://wiki.nesdev.com/w/index.php/Barcode_Battler
+    $4017 < read: bit 2 serial data from the Barcode Battler II, 1200 baud, the 20 digits
+                  of a swiped card followed by "EPOCH"
+
+    the datach joint rom system reads the same cards, but its reader is on the cartridge
+    board (mapper 157) and is the mapper's business, not the port's
+*/
+// a stub for now: plugged in but never swiped, the line stays idle
+#[derive(Default)]
+pub struct BarcodeBattler {}
+
+impl BarcodeBattler {
+    pub fn new() -> Self {
+        BarcodeBattler::default()
+    }
+}
+
+impl ExpansionDevice for BarcodeBattler {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn peek(&self, _port: Port) -> u8 {
+        0
+    }
+}
@@ -0,0 +1,80 @@
+use super::ExpansionDevice;
+use crate::joypad::Port;
+
+pub const NAME: &str = "family_basic_keyboard";
+
+// rows 0-8 hold the keys, a 10th read tells the game the matrix is over
+const ROWS: u8 = 9;
+
+/*
+https://wiki.nesdev.com/w/index.php/Family_BASIC_Keyboard
+    $4016 > write:
+    7  bit  0
+    ---- ----
+    xxxx xKCR
+          |||
+          ||+- 1: back to row 0
+          |+-- column select, going from 1 to 0 moves on to the next row
+          +--- 1: keyboard enabled
+
+    $4017 < read: bits 1-4 the four keys of the selected row and column, 0 while pressed
+*/
+// the scanning works, the keys are not wired to anything yet so all of them are up
+#[derive(Default)]
+pub struct FamilyBasicKeyboard {
+    row: u8,
+    column: bool,
+    enabled: bool,
+}
+
+impl FamilyBasicKeyboard {
+    pub fn new() -> Self {
+        FamilyBasicKeyboard::default()
+    }
+}
+
+impl ExpansionDevice for FamilyBasicKeyboard {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn write(&mut self, out: u8) {
+        let column = out & 0b010 != 0;
+        if out & 0b001 != 0 {
+            self.row = 0;
+        } else if self.column && !column {
+            self.row = (self.row + 1).min(ROWS);
+        }
+        self.column = column;
+        self.enabled = out & 0b100 != 0;
+    }
+
+    fn peek(&self, port: Port) -> u8 {
+        match port {
+            Port::Two if self.enabled && self.row < ROWS => 0b0001_1110,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_keyboard_scan() {
+        let mut keyboard = FamilyBasicKeyboard::new();
+        assert_eq!(keyboard.peek(Port::Two), 0);
+        keyboard.write(0b101);
+        // every row reads all keys up, then the end of the matrix
+        for _ in 0..ROWS {
+            keyboard.write(0b100);
+            assert_eq!(keyboard.read(Port::Two), 0b0001_1110);
+            keyboard.write(0b110);
+            assert_eq!(keyboard.read(Port::Two), 0b0001_1110);
+        }
+        keyboard.write(0b100);
+        assert_eq!(keyboard.read(Port::Two), 0);
+        assert_eq!(keyboard.read(Port::One), 0);
+    }
+}
@@ -0,0 +1,141 @@
+/*
+https://wiki.nesdev.com/w/index.php/Expansion_port
+    $4016 > write: bits 0-2 are the OUT lines of both ports, bit 0 is also the strobe of the
+                   standard controllers
+    $4016 < read: bit 0 controller 1, bit 1 famicom expansion port
+    $4017 < read: bit 0 controller 2, bits 1-4 famicom expansion port, bits 3-4 also the
+                  nes controller ports (zapper, power pad)
+
+    the famicom's 15 pin port takes keyboards, barcode readers and most of its other odd
+    peripherals, the nes has the same lines on its front ports and the unused port below
+*/
+
+mod barcode_battler;
+mod family_basic;
+
+pub use barcode_battler::BarcodeBattler;
+pub use family_basic::FamilyBasicKeyboard;
+
+use crate::joypad::Port;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// a peripheral on the expansion port. the bus forwards every $4016 write and ORs what the
+// device drives into $4016/$4017 reads next to the standard controllers, so a new device
+// needs nothing from the bus but its registration
+pub trait ExpansionDevice: Send + Sync {
+    // the name it was registered under
+    fn name(&self) -> &'static str;
+
+    // the OUT lines, bits 0-2 of a $4016 write
+    fn write(&mut self, _out: u8) {}
+
+    // the bits the device drives on `port`, everything else reads 0. reading can clock the
+    // device the way it clocks a controller's shift register
+    fn read(&mut self, port: Port) -> u8 {
+        self.peek(port)
+    }
+
+    // what read() would return, without the side effects, for debuggers
+    fn peek(&self, _port: Port) -> u8 {
+        0
+    }
+}
+
+pub type NewDevice = fn() -> Box<dyn ExpansionDevice>;
+
+// the devices a frontend can offer, by name. the built in ones are there from the start,
+// frontends and embedders register their own before showing the list
+pub struct Registry {
+    devices: Vec<(&'static str, NewDevice)>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry {
+            devices: Vec::new(),
+        };
+        registry.register(barcode_battler::NAME, || Box::new(BarcodeBattler::new()));
+        registry.register(family_basic::NAME, || Box::new(FamilyBasicKeyboard::new()));
+        registry
+    }
+}
+
+impl Registry {
+    // a device registered again under the same name replaces the earlier one
+    pub fn register(&mut self, name: &'static str, new: NewDevice) {
+        match self.devices.iter_mut().find(|(known, _)| *known == name) {
+            Some(device) => device.1 = new,
+            None => self.devices.push((name, new)),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.devices.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn create(&self, name: &str) -> Result<Box<dyn ExpansionDevice>, String> {
+        self.devices
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, new)| new())
+            .ok_or_else(|| format!("unknown expansion device {:?}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::cartridge::test::create_rom;
+    use crate::cartridge::Cartridge;
+    use crate::joypad::{JOYPAD_1, JOYPAD_2};
+    use crate::mem::Memory;
+
+    // drives $4017 bit 1 with whatever was last written to the OUT lines
+    struct Echo {
+        out: u8,
+    }
+
+    impl ExpansionDevice for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn write(&mut self, out: u8) {
+            self.out = out;
+        }
+
+        fn peek(&self, port: Port) -> u8 {
+            match port {
+                Port::One => 0,
+                Port::Two => self.out & 0b010,
+            }
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["barcode_battler", "family_basic_keyboard"]
+        );
+        assert!(registry.create("power_glove").is_err());
+        registry.register("echo", || Box::new(Echo { out: 0 }));
+        assert_eq!(registry.names().len(), 3);
+
+        let raw = create_rom(0b0000_0000, 0, vec![0; 0x4000]);
+        let mut bus = Bus::new(Cartridge::new(&raw).unwrap());
+        bus.plug_expansion(Some(registry.create("echo").unwrap()));
+        assert_eq!(bus.expansion_name(), Some("echo"));
+        bus.mem_write(JOYPAD_1, 0b010);
+        assert_eq!(bus.mem_read(JOYPAD_2) & 0b1_1110, 0b010);
+        assert_eq!(bus.mem_read(JOYPAD_1) & 0b0_0110, 0);
+        // unplugged the port reads nothing again
+        assert_eq!(bus.plug_expansion(None).unwrap().name(), "echo");
+        assert_eq!(bus.mem_read(JOYPAD_2) & 0b1_1110, 0);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::cartridge::MirroringType;
+use alloc::string::String;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameInfo {
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+    pub mapper: u8,
+    pub mirroring: MirroringType,
+    pub prg_ram_size: usize,
+    pub title: String,
+}
+
+#[cfg(feature = "gamedb")]
+static GAMES: &str = include_str!("../res/gamedb.txt");
+
+// the text is scanned on every lookup, which only happens when a rom is loaded, so
+// nothing has to stay allocated
+#[cfg(feature = "gamedb")]
+pub fn lookup(crc32: u32, sha1: &[u8; 20]) -> Option<GameInfo> {
+    parse(GAMES).find(|game| game.crc32 == crc32 && game.sha1 == *sha1)
+}
+
+// without the embedded database every rom is taken as its header describes it
+#[cfg(not(feature = "gamedb"))]
+pub fn lookup(_crc32: u32, _sha1: &[u8; 20]) -> Option<GameInfo> {
+    None
+}
+
+// one game per line: crc32 sha1 mapper mirroring prg_ram_kb title, '#' starts a comment
+#[cfg(feature = "gamedb")]
+fn parse(text: &str) -> impl Iterator<Item = GameInfo> + '_ {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(parse_line)
+}
+
+#[cfg(feature = "gamedb")]
+fn parse_line(line: &str) -> Option<GameInfo> {
+    let mut fields = line.splitn(6, ' ');
+    let crc32 = u32::from_str_radix(fields.next()?, 16).ok()?;
+
+    let sha1_hex = fields.next()?;
+    if sha1_hex.len() != 40 {
+        return None;
+    }
+    let mut sha1 = [0u8; 20];
+    for (i, byte) in sha1.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(sha1_hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    let mapper = fields.next()?.parse().ok()?;
+    let mirroring = match fields.next()? {
+        "H" => MirroringType::Horizontal,
+        "V" => MirroringType::Vertical,
+        "4" => MirroringType::FourScreen,
+        _ => return None,
+    };
+    let prg_ram_kb: usize = fields.next()?.parse().ok()?;
+    let title = String::from(fields.next()?.trim());
+
+    Some(GameInfo {
+        crc32: crc32,
+        sha1: sha1,
+        mapper: mapper,
+        mirroring: mirroring,
+        prg_ram_size: prg_ram_kb * 1024,
+        title: title,
+    })
+}
+
+#[cfg(all(test, feature = "gamedb"))]
+mod test {
+    use super::*;
+    use crate::sha1;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_parse() {
+        let games = parse(
+            "# comment\n\
+             0628a9f6 04e892add55e7ade2875d781ded4436ee17c545d 0 H 0 nestest\n\
+             bad line\n\
+             00000001 0000000000000000000000000000000000000000 7 4 8 Some Game\n",
+        )
+        .collect::<Vec<_>>();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].crc32, 0x0628_A9F6);
+        assert_eq!(
+            sha1::to_hex(&games[0].sha1),
+            "04e892add55e7ade2875d781ded4436ee17c545d"
+        );
+        assert_eq!(games[1].mapper, 7);
+        assert_eq!(games[1].mirroring, MirroringType::FourScreen);
+        assert_eq!(games[1].prg_ram_size, 0x2000);
+        assert_eq!(games[1].title, "Some Game");
+    }
+}
@@ -0,0 +1,265 @@
+// a gym-like environment around a console, for training agents against games. the agent
+// sees the picture, picks the buttons and lets frames pass, episodes start over from the
+// same state:
+//
+//     let mut env = GymEnv::new(cartridge, ObservationMode::default());
+//     loop {
+//         let buttons = agent.pick(env.observe());
+//         env.act(buttons);
+//         env.step(4);
+//         if agent.done(env.peek(LIVES)) {
+//             env.reset();
+//         }
+//     }
+//
+// rewards and the end of an episode depend on the game, agents read them from ram with
+// peek. the same calls are what the python and c bindings wrap, so this is kept stable
+
+use crate::cartridge::Cartridge;
+use crate::joypad::{JoypadButton, Port};
+use crate::mem::Memory;
+use crate::nes::{Nes, Snapshot};
+use crate::render::frame::{Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channels {
+    // 3 bytes per pixel
+    Rgb,
+    // 1 byte per pixel, the luma of the color
+    Grayscale,
+}
+
+impl Channels {
+    pub fn count(&self) -> usize {
+        match self {
+            Channels::Rgb => 3,
+            Channels::Grayscale => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObservationMode {
+    pub channels: Channels,
+    // the picture is shrunk by this in both directions, averaging each square of pixels.
+    // 1, 2, 4 or 8, the factors of both 256 and 240 that keep the aspect
+    pub downsample: usize,
+}
+
+impl Default for ObservationMode {
+    // the full picture in color
+    fn default() -> Self {
+        ObservationMode {
+            channels: Channels::Rgb,
+            downsample: 1,
+        }
+    }
+}
+
+impl ObservationMode {
+    // width, height and channels, the shape of observe() as rows of pixels
+    pub fn shape(&self) -> (usize, usize, usize) {
+        (
+            SCREEN_WIDTH / self.downsample,
+            SCREEN_HEIGHT / self.downsample,
+            self.channels.count(),
+        )
+    }
+}
+
+pub struct GymEnv {
+    nes: Nes,
+    mode: ObservationMode,
+    // where reset() goes back to, the console after power-on unless set_start moved it
+    start: Snapshot,
+    start_observation: Vec<u8>,
+    observation: Vec<u8>,
+}
+
+impl GymEnv {
+    // a console that is switched on, the first observation is a black screen
+    pub fn new(cartridge: Cartridge, mode: ObservationMode) -> Self {
+        GymEnv::with_nes(Nes::new(cartridge), mode)
+    }
+
+    // for a console with its own config, it is switched on here
+    pub fn with_nes(mut nes: Nes, mode: ObservationMode) -> Self {
+        assert!(
+            [1, 2, 4, 8].contains(&mode.downsample),
+            "downsample by {}",
+            mode.downsample
+        );
+        nes.reset();
+        let mut observation = Vec::new();
+        observe_frame(nes.frame(), &mode, &mut observation);
+        GymEnv {
+            start: nes.snapshot(),
+            start_observation: observation.clone(),
+            nes: nes,
+            mode: mode,
+            observation: observation,
+        }
+    }
+
+    // the picture of the last frame step() ran, laid out as mode().shape() says
+    pub fn observe(&self) -> &[u8] {
+        &self.observation
+    }
+
+    // holds `buttons` on controller 1 from the next frame on, until the next act()
+    pub fn act(&mut self, buttons: JoypadButton) {
+        self.act_on(Port::One, buttons);
+    }
+
+    pub fn act_on(&mut self, port: Port, buttons: JoypadButton) {
+        let frame = self.nes.stats().frames;
+        self.nes.queue_input(frame, port, buttons);
+    }
+
+    // runs `frames` frames with the buttons held, only the last one is observed
+    pub fn step(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.nes.run_frame();
+        }
+        observe_frame(self.nes.frame(), &self.mode, &mut self.observation);
+    }
+
+    // starts the next episode where the first one started, with every button released
+    pub fn reset(&mut self) {
+        self.nes.restore(&self.start);
+        self.act_on(Port::One, JoypadButton::empty());
+        self.act_on(Port::Two, JoypadButton::empty());
+        self.observation.clone_from(&self.start_observation);
+    }
+
+    // episodes start from now on, past a title screen for example
+    pub fn set_start(&mut self) {
+        self.start = self.nes.snapshot();
+        self.start_observation.clone_from(&self.observation);
+    }
+
+    // a byte of the cpu's address space without side effects, for scores, lives and the like
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.nes.cpu.mem_peek(addr)
+    }
+
+    pub fn mode(&self) -> ObservationMode {
+        self.mode
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.nes.stats().frames
+    }
+
+    pub fn nes(&self) -> &Nes {
+        &self.nes
+    }
+
+    pub fn nes_mut(&mut self) -> &mut Nes {
+        &mut self.nes
+    }
+}
+
+fn observe_frame(frame: &Frame, mode: &ObservationMode, observation: &mut Vec<u8>) {
+    let factor = mode.downsample;
+    let (width, height, _) = mode.shape();
+    let pixels = (factor * factor) as u32;
+    observation.clear();
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 3];
+            for row in y * factor..(y + 1) * factor {
+                let start = (row * frame.width + x * factor) * 4;
+                for pixel in frame.data[start..start + factor * 4].chunks_exact(4) {
+                    sum.iter_mut()
+                        .zip(pixel)
+                        .for_each(|(sum, c)| *sum += *c as u32);
+                }
+            }
+            let [r, g, b] = [sum[0] / pixels, sum[1] / pixels, sum[2] / pixels];
+            match mode.channels {
+                Channels::Rgb => observation.extend_from_slice(&[r as u8, g as u8, b as u8]),
+                // rec. 601 weights
+                Channels::Grayscale => {
+                    observation.push(((r * 299 + g * 587 + b * 114) / 1000) as u8)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nestest() -> Cartridge {
+        Cartridge::new(include_bytes!("../res/test.nes")).unwrap()
+    }
+
+    #[test]
+    fn test_gym_env() {
+        let mut env = GymEnv::new(nestest(), ObservationMode::default());
+        assert_eq!(env.observe().len(), 256 * 240 * 3);
+        assert!(env.observe().iter().all(|byte| *byte == 0));
+        env.step(30);
+        assert_eq!(env.frames(), 30);
+        let title = env.observe().to_vec();
+        assert!(title.iter().any(|byte| *byte != 0));
+
+        env.act(JoypadButton::START);
+        env.step(1);
+        assert_eq!(
+            env.nes_mut().cpu.bus.joypad(Port::One).get_buttons(),
+            JoypadButton::START
+        );
+        env.reset();
+        assert_eq!(env.frames(), 0);
+        assert!(env.observe().iter().all(|byte| *byte == 0));
+        assert!(env
+            .nes_mut()
+            .cpu
+            .bus
+            .joypad(Port::One)
+            .get_buttons()
+            .is_empty());
+        // the same frames again without input show the same picture
+        env.step(30);
+        assert_eq!(env.observe(), &title[..]);
+
+        env.set_start();
+        env.step(10);
+        env.reset();
+        assert_eq!((env.frames(), env.observe()), (30, &title[..]));
+    }
+
+    #[test]
+    fn test_observation_modes() {
+        let mode = ObservationMode {
+            channels: Channels::Grayscale,
+            downsample: 4,
+        };
+        assert_eq!(mode.shape(), (64, 60, 1));
+
+        // a white pixel in each 2x2 square of a black picture
+        let mut frame = Frame::new(256, 240);
+        for y in (0..240).step_by(2) {
+            for x in (0..256).step_by(2) {
+                frame.set_pixel(x, y, [0xFF, 0xFF, 0xFF, 0xFF]);
+            }
+        }
+        let mut observation = Vec::new();
+        observe_frame(&frame, &mode, &mut observation);
+        assert_eq!(observation.len(), 64 * 60);
+        assert!(observation.iter().all(|luma| *luma == 0x3F));
+
+        let rgb = ObservationMode {
+            channels: Channels::Rgb,
+            downsample: 2,
+        };
+        observe_frame(&frame, &rgb, &mut observation);
+        assert_eq!(observation.len(), 128 * 120 * 3);
+        assert_eq!(observation[..3], [0x3F, 0x3F, 0x3F]);
+    }
+}
@@ -0,0 +1,312 @@
+// https://www.rfc-editor.org/rfc/rfc1951 and rfc1950
+// zlib decompression for savestates of other emulators, which compress theirs. the core has
+// no dependencies to pull a crate in for it, and reading is all that is needed. after puff,
+// zlib's small reference inflater: canonical huffman codes decoded a bit at a time
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const MAX_BITS: usize = 15;
+
+// lengths 257-285 and distances 0-29: base and extra bits
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// the order the code length code lengths of a dynamic block come in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    at: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn bits(&mut self, need: u32) -> Result<u32, String> {
+        while self.count < need {
+            let byte = *self.data.get(self.at).ok_or_else(cut_short)?;
+            self.at += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << need) - 1);
+        self.buffer >>= need;
+        self.count -= need;
+        Ok(value)
+    }
+
+    // stored blocks start at the next byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+fn cut_short() -> String {
+    String::from("compressed data cut short")
+}
+
+// codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths.iter() {
+            counts[*length as usize] += 1;
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        Huffman {
+            counts: counts,
+            symbols: symbols,
+        }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("bad huffman code"))
+    }
+}
+
+// the data of a zlib stream, header and adler-32 checked
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 || data[0] & 0x0F != 8 || (data[0] as u16 * 256 + data[1] as u16) % 31 != 0 {
+        return Err(String::from("not zlib data"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(String::from("zlib preset dictionaries are not supported"));
+    }
+    let mut bits = Bits {
+        data: &data[2..],
+        at: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let output = inflate(&mut bits)?;
+    let end = 2 + bits.at;
+    let checksum = data.get(end..end + 4).ok_or_else(cut_short)?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&output)
+    {
+        return Err(String::from("zlib checksum mismatch"));
+    }
+    Ok(output)
+}
+
+fn inflate(bits: &mut Bits) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(bits, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                codes(bits, &mut output, &literals, &distances)?
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(bits)?;
+                codes(bits, &mut output, &literals, &distances)?
+            }
+            _ => return Err(String::from("bad deflate block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, output: &mut Vec<u8>) -> Result<(), String> {
+    bits.align();
+    let header = bits.data.get(bits.at..bits.at + 4).ok_or_else(cut_short)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(String::from("bad stored block length"));
+    }
+    let start = bits.at + 4;
+    let block = bits
+        .data
+        .get(start..start + len as usize)
+        .ok_or_else(cut_short)?;
+    output.extend_from_slice(block);
+    bits.at = start + len as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*index] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // both tables in one run, repeats may cross from one into the other
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(bits)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| String::from("length repeat with nothing before it"))?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(String::from("bad deflate distance"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(String::from("deflate distance before the start"));
+                }
+                // the copy may overlap what it writes, runs of one byte are a distance of 1
+                let start = output.len() - distance;
+                for at in start..start + length {
+                    output.push(output[at]);
+                }
+            }
+            _ => return Err(String::from("bad deflate length")),
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zlib_decompress() {
+        // runs of letters, zlib.compress(runs, 9) picks a dynamic block for them
+        let runs: Vec<u8> = (0..300)
+            .flat_map(|i: usize| core::iter::repeat(b'A' + (i % 7) as u8).take(i % 5 + 1))
+            .collect();
+        let dynamic = [
+            0x78, 0xDA, 0xED, 0x8D, 0xC1, 0x0D, 0x00, 0x31, 0x0C, 0xC2, 0x66, 0x03, 0xDA, 0x64,
+            0xFF, 0x8D, 0x8E, 0xA2, 0x3C, 0x6F, 0x84, 0xF0, 0xB4, 0x8C, 0x0C, 0x52, 0xD2, 0xF1,
+            0xEE, 0x5B, 0x75, 0x03, 0x20, 0x43, 0xCD, 0x4D, 0xCA, 0x2C, 0xD4, 0x3C, 0x66, 0xBC,
+            0xB7, 0x8E, 0xA9, 0xF9, 0x9F, 0x1B, 0x13, 0xF3, 0xA7, 0x62, 0xD6, 0xFC, 0x1B, 0x1B,
+            0xDA, 0xD0, 0x86, 0xFE, 0x42, 0x1F, 0xA4, 0x07, 0xEF, 0x0D,
+        ];
+        // the same line 20 times, fixed codes with long back references
+        let text = b"FeuerNES FeuerNES FeuerNES, a NES emulator written in Rust\n";
+        let fixed = [
+            0x78, 0xDA, 0x73, 0x4B, 0x2D, 0x4D, 0x2D, 0xF2, 0x73, 0x0D, 0x56, 0x70, 0x43, 0x67,
+            0xE8, 0x28, 0x24, 0x2A, 0x80, 0xF8, 0xA9, 0xB9, 0xA5, 0x39, 0x89, 0x25, 0xF9, 0x45,
+            0x0A, 0xE5, 0x45, 0x99, 0x25, 0x25, 0xA9, 0x79, 0x0A, 0x99, 0x79, 0x0A, 0x41, 0xA5,
+            0xC5, 0x25, 0x5C, 0x6E, 0xA3, 0x5A, 0x47, 0xB5, 0x8E, 0x6A, 0x1D, 0xD5, 0x4A, 0x2B,
+            0xAD, 0x00, 0x07, 0xC1, 0x91, 0x3C,
+        ];
+        // level 0, one stored block
+        let mut stored = vec![0x78, 0x01, 0x01, 0x3B, 0x00, 0xC4, 0xFF];
+        stored.extend_from_slice(text);
+        stored.extend_from_slice(&adler32(text).to_be_bytes());
+
+        assert_eq!(zlib_decompress(&dynamic).unwrap(), runs);
+        assert_eq!(zlib_decompress(&fixed).unwrap(), text.repeat(20));
+        assert_eq!(zlib_decompress(&stored).unwrap(), &text[..]);
+
+        let mut corrupt = dynamic.to_vec();
+        corrupt[40] ^= 0x10;
+        assert!(zlib_decompress(&corrupt).is_err());
+        assert_eq!(
+            zlib_decompress(&fixed[..30]),
+            Err(String::from("compressed data cut short"))
+        );
+    }
+}
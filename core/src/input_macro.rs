@@ -0,0 +1,144 @@
+// short button sequences, a fighting game combo or a level select code, recorded from
+// a controller port and played back into it one frame per entry. see
+// Nes::start_macro_recording and Nes::play_macro
+
+use crate::joypad::JoypadButton;
+use crate::movie::{format_buttons, parse_buttons};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputMacro {
+    // the buttons held on each frame
+    pub frames: Vec<JoypadButton>,
+}
+
+impl InputMacro {
+    // frames before the first and after the last button press are only the time it took
+    // to start and stop recording
+    pub fn trimmed(frames: &[JoypadButton]) -> Self {
+        let held = |buttons: &JoypadButton| !buttons.is_empty();
+        let frames = match (frames.iter().position(held), frames.iter().rposition(held)) {
+            (Some(first), Some(last)) => frames[first..=last].to_vec(),
+            _ => Vec::new(),
+        };
+        InputMacro { frames: frames }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // runs of equal frames as `count*RLDUTSBA` (the fm2 button columns), separated by
+    // spaces: "3*...U.... 1*.......A"
+    pub fn to_text(&self) -> String {
+        let mut runs: Vec<(usize, JoypadButton)> = Vec::new();
+        for buttons in self.frames.iter() {
+            match runs.last_mut() {
+                Some((count, last)) if last == buttons => *count += 1,
+                _ => runs.push((1, *buttons)),
+            }
+        }
+        let runs: Vec<String> = runs
+            .iter()
+            .map(|(count, buttons)| format!("{}*{}", count, format_buttons(*buttons)))
+            .collect();
+        runs.join(" ")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut frames = Vec::new();
+        for run in text.split_whitespace() {
+            let mut parts = run.splitn(2, '*');
+            let count: usize = parts
+                .next()
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(|| format!("bad run {:?}", run))?;
+            let buttons = parse_buttons(parts.next().unwrap_or(""))?;
+            frames.extend(core::iter::repeat(buttons).take(count));
+        }
+        Ok(InputMacro { frames: frames })
+    }
+}
+
+// where a macro being played is at, see Nes::play_macro
+pub(crate) struct Playback {
+    input_macro: InputMacro,
+    // the frame its first entry is held on
+    start: u64,
+}
+
+impl Playback {
+    pub fn new(input_macro: InputMacro, start: u64) -> Self {
+        Playback {
+            input_macro: input_macro,
+            start: start,
+        }
+    }
+
+    // None once `frame` is past the end
+    pub fn buttons(&self, frame: u64) -> Option<JoypadButton> {
+        let index = frame.checked_sub(self.start)?;
+        self.input_macro.frames.get(index as usize).copied()
+    }
+
+    pub fn started(&self, frame: u64) -> bool {
+        frame >= self.start
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::joypad::Port;
+    use crate::nes::Nes;
+
+    #[test]
+    fn test_text() {
+        let none = JoypadButton::empty();
+        let up = JoypadButton::UP;
+        let a = JoypadButton::BUTTON_A;
+        let input_macro = InputMacro::trimmed(&[none, up, up, up, none, a, none, none]);
+        assert_eq!(input_macro.frames, vec![up, up, up, none, a]);
+        assert_eq!(input_macro.to_text(), "3*...U.... 1*........ 1*.......A");
+        assert_eq!(InputMacro::parse(&input_macro.to_text()), Ok(input_macro));
+        assert!(InputMacro::trimmed(&[none, none]).is_empty());
+        assert!(InputMacro::parse("x*........").is_err());
+        assert!(InputMacro::parse("2*RL").is_err());
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+
+        nes.start_macro_recording(Port::One);
+        let pressed = [
+            JoypadButton::empty(),
+            JoypadButton::START,
+            JoypadButton::START | JoypadButton::BUTTON_A,
+            JoypadButton::empty(),
+        ];
+        for buttons in pressed.iter() {
+            nes.cpu.bus.joypad1().set_buttons(*buttons);
+            nes.run_frame();
+        }
+        let input_macro = nes.stop_macro_recording().unwrap();
+        assert_eq!(input_macro.frames, pressed[1..3].to_vec());
+
+        // each frame holds the next entry, whatever the player holds meanwhile
+        nes.cpu.bus.joypad1().set_buttons(JoypadButton::SELECT);
+        nes.play_macro(Port::One, input_macro.clone());
+        assert!(nes.macro_playing());
+        let mut played = Vec::new();
+        while nes.macro_playing() {
+            nes.run_frame();
+            played.push(nes.cpu.bus.joypad1().get_buttons());
+        }
+        assert_eq!(played[..2], input_macro.frames[..]);
+        assert_eq!(played.len(), 3);
+    }
+}
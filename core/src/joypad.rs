@@ -0,0 +1,140 @@
+/*
+https://wiki.nesdev.com/w/index.php/Standard_controller
+    $4016 > write: strobe, reloads the shift registers of both controllers while bit 0 is set
+    $4016 < read: serial data of controller 1
+    $4017 < read: serial data of controller 2
+
+    buttons are reported in the order A, B, Select, Start, Up, Down, Left, Right,
+    after 8 reads an official controller returns 1.
+*/
+pub const JOYPAD_1: u16 = 0x4016;
+pub const JOYPAD_2: u16 = 0x4017;
+
+use alloc::vec::Vec;
+
+bitflags::bitflags! {
+    pub struct JoypadButton: u8 {
+        const RIGHT    = 0b1000_0000;
+        const LEFT     = 0b0100_0000;
+        const DOWN     = 0b0010_0000;
+        const UP       = 0b0001_0000;
+        const START    = 0b0000_1000;
+        const SELECT   = 0b0000_0100;
+        const BUTTON_B = 0b0000_0010;
+        const BUTTON_A = 0b0000_0001;
+    }
+}
+
+// the two controller ports on the front of the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Port {
+    One,
+    Two,
+}
+
+#[derive(Clone)]
+pub struct Joypad {
+    strobe: bool,
+    button_index: u8,
+    button_status: JoypadButton,
+    // buttons the controller does not have, see Console::controller2_missing
+    missing: JoypadButton,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            strobe: false,
+            button_index: 0,
+            button_status: JoypadButton::empty(),
+            missing: JoypadButton::empty(),
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 0b0000_0001 != 0;
+        if self.strobe {
+            self.button_index = 0;
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+
+        let response = self.bit();
+        if !self.strobe {
+            self.button_index += 1;
+        }
+        response
+    }
+
+    pub fn peek(&self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+        self.bit()
+    }
+
+    fn bit(&self) -> u8 {
+        let pressed = self.button_status - self.missing;
+        (pressed.bits & (1 << self.button_index)) >> self.button_index
+    }
+
+    pub fn set_missing(&mut self, missing: JoypadButton) {
+        self.missing = missing;
+    }
+
+    pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
+        self.button_status.set(button, pressed);
+    }
+
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.button_status = buttons;
+    }
+
+    pub fn get_buttons(&self) -> JoypadButton {
+        self.button_status
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        vec![
+            self.strobe as u8,
+            self.button_index,
+            self.button_status.bits(),
+        ]
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) {
+        self.strobe = state[0] != 0;
+        self.button_index = state[1];
+        self.button_status = JoypadButton::from_bits_truncate(state[2]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_sequence() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(JoypadButton::BUTTON_A | JoypadButton::START | JoypadButton::RIGHT);
+        joypad.write(1);
+        joypad.write(0);
+
+        let bits: Vec<u8> = (0..9).map(|_| joypad.read()).collect();
+        assert_eq!(bits, vec![1, 0, 0, 1, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_strobe_holds_first_button() {
+        let mut joypad = Joypad::new();
+        joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+        joypad.write(1);
+
+        assert_eq!(joypad.read(), 1);
+        assert_eq!(joypad.read(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+// the emulator core, free of any frontend. with the default features off it only needs
+// `alloc`, so it can run on microcontrollers or inside other engines
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+// println! when std is around, dropped otherwise
+#[cfg(feature = "std")]
+macro_rules! log {
+    ($($arg:tt)*) => { std::println!($($arg)*) };
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! log {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+pub mod achievements;
+pub mod address_decode;
+pub mod apu;
+pub mod assembler;
+pub mod audio;
+pub mod av_sync;
+pub mod bus;
+pub mod cartridge;
+pub mod cloud;
+pub mod compat;
+pub mod config;
+pub mod cpu;
+pub mod crc32;
+pub mod csv;
+pub mod error;
+pub mod events;
+pub mod expansion;
+pub mod gamedb;
+pub mod gym;
+pub mod inflate;
+pub mod input_macro;
+pub mod joypad;
+pub mod json;
+pub mod lint;
+pub mod mapper;
+pub mod mem;
+pub mod movie;
+pub mod nes;
+pub mod opcode;
+pub mod patch;
+pub mod ppu;
+pub mod profiler;
+pub mod ram_lock;
+#[cfg(test)]
+mod regression;
+pub mod render;
+pub mod repro;
+pub mod rollback;
+#[cfg(feature = "std")]
+pub mod rom_watch;
+pub mod savestate;
+pub mod schedule;
+pub mod sha1;
+pub mod spectate;
+pub mod state_import;
+pub mod survey;
+pub mod symbols;
+pub mod sync;
+pub mod test_roms;
+pub mod trace;
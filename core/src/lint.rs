@@ -0,0 +1,284 @@
+// checks for mistakes homebrew games make that emulators forgive and consoles do not.
+// off until Nes::enable_lint, then every instruction is looked at before it runs and
+// findings arrive as Event::Lint, each warning once per pc
+
+use crate::address_decode::cpu_ram_mirror;
+use crate::bus::PPU_WARM_UP_CYCLES;
+use crate::cpu::{AddressMode, CPUStatus, CPU};
+use crate::events::{Event, EventLog};
+use crate::mem::Memory;
+use crate::opcode;
+use crate::ppu::*;
+
+use alloc::collections::BTreeSet;
+use core::fmt;
+
+const NMI_VECTOR: u16 = 0xFFFA;
+const IRQ_VECTOR: u16 = 0xFFFE;
+const STACK_PAGE: u16 = 0x0100;
+// pc, status and the return address
+const INTERRUPT_PUSHES: u8 = 3;
+const RTI: u8 = 0x40;
+const TXS: u8 = 0x9A;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintWarning {
+    // $2007 written while the ppu was fetching for a visible line, the write lands
+    // somewhere else and the scroll is corrupted
+    VramWriteWhileRendering,
+    // $2000, $2001, $2005 or $2006 set before the ppu warmed up, which ignores it. games
+    // wait for two vblanks on $2002 first. clearing $2000 and $2001 right at reset is
+    // fine, they power up cleared
+    NoVblankWait { register: u16 },
+    // a push overwrote a variable the game keeps in page $01 below the stack. the 6502
+    // stack never leaves that page, it grows down into whatever else lives there
+    StackCollision { addr: u16 },
+    // an nmi handler was still doing its vblank updates ($2004, $2007 or oam dma) after
+    // rendering started again
+    NmiOverrun,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintWarning::VramWriteWhileRendering => write!(f, "$2007 written during rendering"),
+            LintWarning::NoVblankWait { register } => write!(
+                f,
+                "${:04X} written before waiting for vblank at boot",
+                register
+            ),
+            LintWarning::StackCollision { addr } => {
+                write!(f, "stack overwrote the variable at ${:04X}", addr)
+            }
+            LintWarning::NmiOverrun => write!(f, "nmi handler ran past vblank"),
+        }
+    }
+}
+
+// the instruction before() saw, for after()
+struct Pending {
+    pc: u16,
+    opcode: u8,
+    // before an interrupt's pushes, and what the instruction ran with
+    interrupted_sp: u8,
+    sp: u8,
+}
+
+pub struct Lint {
+    // page $01 slots the game read as data while they were not part of the stack
+    variables: [bool; 256],
+    // the stack pointer the running nmi handler returns with
+    nmi_sp: Option<u8>,
+    pending: Option<Pending>,
+    reported: BTreeSet<(u16, LintWarning)>,
+}
+
+impl Lint {
+    pub fn new() -> Self {
+        Lint {
+            variables: [false; 256],
+            nmi_sp: None,
+            pending: None,
+            reported: BTreeSet::new(),
+        }
+    }
+
+    // right before the cpu runs its next instruction, or the interrupt it is about to take
+    pub fn before(&mut self, cpu: &CPU, events: &mut EventLog) {
+        let ppu = cpu.bus.ppu();
+        let interrupt = cpu.bus.irq_pending() && !cpu.status.contains(CPUStatus::INTERRUPT_DISABLE);
+        let (pc, sp) = if ppu.nmi_pending() {
+            self.nmi_sp = Some(cpu.sp.wrapping_sub(INTERRUPT_PUSHES));
+            (
+                cpu.mem_peek_u16(NMI_VECTOR),
+                cpu.sp.wrapping_sub(INTERRUPT_PUSHES),
+            )
+        } else if interrupt {
+            (
+                cpu.mem_peek_u16(IRQ_VECTOR),
+                cpu.sp.wrapping_sub(INTERRUPT_PUSHES),
+            )
+        } else {
+            (cpu.pc, cpu.sp)
+        };
+        let op = cpu.mem_peek(pc);
+        let opcode = opcode::OPCODES_MAP[op as usize].unwrap_or(&opcode::UNSUPPORTED);
+        self.pending = Some(Pending {
+            pc: pc,
+            opcode: op,
+            interrupted_sp: cpu.sp,
+            sp: sp,
+        });
+
+        let addr = match opcode.mode {
+            AddressMode::Immediate | AddressMode::NoneAddressing => return,
+            mode => cpu.peek_absolute_address(&mode, pc.wrapping_add(1)),
+        };
+        match opcode.name {
+            "JMP" | "JSR" => {}
+            "STA" => self.check_write(cpu, pc, addr, Some(cpu.acc), events),
+            "STX" => self.check_write(cpu, pc, addr, Some(cpu.rx), events),
+            "STY" => self.check_write(cpu, pc, addr, Some(cpu.ry), events),
+            name => {
+                // a slot outside the stack that the game reads holds one of its variables
+                let stack = STACK_PAGE as usize..STACK_PAGE as usize * 2;
+                if addr < 0x2000 && stack.contains(&cpu_ram_mirror(addr)) {
+                    let slot = (addr & 0xFF) as u8;
+                    if slot <= sp {
+                        self.variables[slot as usize] = true;
+                    }
+                }
+                if matches!(name, "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC") {
+                    self.check_write(cpu, pc, addr, None, events);
+                }
+            }
+        }
+    }
+
+    // once the instruction from before() ran
+    pub fn after(&mut self, cpu: &CPU, events: &mut EventLog) {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+        if pending.opcode == RTI && self.nmi_sp == Some(pending.sp) {
+            self.nmi_sp = None;
+        }
+
+        // what the interrupt and the instruction pushed. a wrap around is a stack overflow,
+        // which the cpu reports itself
+        let top = pending.interrupted_sp;
+        if pending.opcode == TXS || cpu.sp >= top {
+            return;
+        }
+        for slot in cpu.sp + 1..=top {
+            if self.variables[slot as usize] {
+                let addr = STACK_PAGE | slot as u16;
+                self.report(
+                    pending.pc,
+                    LintWarning::StackCollision { addr: addr },
+                    events,
+                );
+            }
+        }
+    }
+
+    // `data` when it is known before the instruction runs
+    fn check_write(
+        &mut self,
+        cpu: &CPU,
+        pc: u16,
+        addr: u16,
+        data: Option<u8>,
+        events: &mut EventLog,
+    ) {
+        let register = match addr {
+            0x2000..=0x3FFF => addr & 0x2007,
+            PPU_REG_OAMDMA => addr,
+            _ => return,
+        };
+        let ppu = cpu.bus.ppu();
+        let warning = match register {
+            PPU_REG_CTRL | PPU_REG_MASK | PPU_REG_SCROLL | PPU_REG_ADDR
+                if cpu.bus.cycles() < PPU_WARM_UP_CYCLES && data != Some(0) =>
+            {
+                LintWarning::NoVblankWait { register: register }
+            }
+            PPU_REG_OAMDATA | PPU_REG_DATA | PPU_REG_OAMDMA
+                if self.nmi_sp.is_some() && ppu.is_rendering() =>
+            {
+                LintWarning::NmiOverrun
+            }
+            PPU_REG_DATA if ppu.is_rendering() => LintWarning::VramWriteWhileRendering,
+            _ => return,
+        };
+        self.report(pc, warning, events);
+    }
+
+    fn report(&mut self, pc: u16, warning: LintWarning, events: &mut EventLog) {
+        if self.reported.insert((pc, warning)) {
+            events.push(Event::Lint {
+                pc: pc,
+                warning: warning,
+            });
+        }
+    }
+}
+
+impl Default for Lint {
+    fn default() -> Self {
+        Lint::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::nes::Nes;
+
+    fn lints(nes: &mut Nes) -> Vec<(u16, LintWarning)> {
+        nes.take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Lint { pc, warning } => Some((pc, warning)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut prg = vec![0xEA; 0x4000];
+        let program: &[(usize, &[u8])] = &[
+            // STA $2000 right at boot, first with 0, then LDA $0140, ORA #$04, STA $2000.
+            // then LDX #$40, TXS, PHA, JMP to itself
+            (0x0000, &[0x8D, 0x00, 0x20, 0xAD, 0x40, 0x01]),
+            (0x0006, &[0x09, 0x04, 0x8D, 0x00, 0x20]),
+            (0x000B, &[0xA2, 0x40, 0x9A, 0x48, 0x4C, 0x0F, 0x80]),
+            // STA $2007, JMP to itself
+            (0x0012, &[0x8D, 0x07, 0x20, 0x4C, 0x15, 0x80]),
+            // an nmi handler that loops for most of a frame before its STA $2007
+            (0x0020, &[0xA0, 0x10, 0xCA, 0xD0, 0xFD, 0x88, 0xD0, 0xFA]),
+            (0x0028, &[0x8D, 0x07, 0x20, 0x40]),
+            (0x3FFA, &[0x20, 0x80, 0x00, 0x80]),
+        ];
+        for (at, bytes) in program.iter() {
+            prg[*at..*at + bytes.len()].copy_from_slice(bytes);
+        }
+        let raw = crate::cartridge::test::create_rom(0, 1, prg);
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.enable_lint();
+        nes.reset();
+
+        while nes.cpu.bus.cycles() < PPU_WARM_UP_CYCLES {
+            nes.step();
+        }
+        assert_eq!(
+            lints(&mut nes),
+            vec![
+                (0x8008, LintWarning::NoVblankWait { register: 0x2000 }),
+                (0x800E, LintWarning::StackCollision { addr: 0x0140 }),
+            ]
+        );
+
+        // rendering on, then $2007 in the middle of a frame
+        nes.cpu.mem_write(PPU_REG_MASK, 0b0001_1000);
+        while !nes.cpu.bus.ppu().is_rendering() {
+            nes.step();
+        }
+        nes.cpu.pc = 0x8012;
+        nes.step();
+        assert_eq!(
+            lints(&mut nes),
+            vec![(0x8012, LintWarning::VramWriteWhileRendering)]
+        );
+
+        nes.cpu.mem_write(PPU_REG_CTRL, 0b1000_0000);
+        let frames = nes.stats().frames;
+        while nes.stats().frames < frames + 3 {
+            nes.step();
+        }
+        assert_eq!(lints(&mut nes), vec![(0x8028, LintWarning::NmiOverrun)]);
+    }
+}
@@ -0,0 +1,130 @@
+use super::{banked_offset, board_debug_state, Mapper};
+use crate::cartridge::MirroringType;
+use crate::json::Value;
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x8000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/AxROM
+    Bank select ($8000-$FFFF)
+    7  bit  0
+    ---- ----
+    xxxM xPPP
+       |  |||
+       |  +++- Select 32 KB PRG ROM bank for CPU $8000-$FFFF
+       +------ Select 1 KB VRAM page for all 4 nametables
+*/
+pub struct AxROM {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: usize,
+    mirroring_type: MirroringType,
+}
+
+impl AxROM {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>) -> Self {
+        let chr_is_ram = chr.is_empty();
+        AxROM {
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            prg_bank: 0,
+            mirroring_type: MirroringType::SingleScreenLower,
+        }
+    }
+}
+
+impl Mapper for AxROM {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let offset = banked_offset(
+            self.prg.len(),
+            PRG_BANK_SIZE,
+            self.prg_bank,
+            (addr - 0x8000) as usize,
+        );
+        self.prg[offset]
+    }
+
+    fn write_prg(&mut self, _addr: u16, data: u8) {
+        self.prg_bank = (data & 0b0000_0111) as usize;
+        self.mirroring_type = if data & 0b0001_0000 != 0 {
+            MirroringType::SingleScreenUpper
+        } else {
+            MirroringType::SingleScreenLower
+        };
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            self.chr[addr as usize] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring_type
+    }
+
+    fn debug_state(&self) -> Value {
+        board_debug_state(self, vec![("prg_bank", Value::from(self.prg_bank))])
+    }
+
+    // bank, mirroring, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![
+            self.prg_bank as u8,
+            (self.mirroring_type == MirroringType::SingleScreenUpper) as u8,
+        ];
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.prg_bank = state[0] as usize;
+        self.mirroring_type = if state[1] != 0 {
+            MirroringType::SingleScreenUpper
+        } else {
+            MirroringType::SingleScreenLower
+        };
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(&state[2..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bank_select() {
+        let mut prg = vec![0; PRG_BANK_SIZE * 4];
+        for bank in 0..4 {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut axrom = AxROM::new(prg, Vec::new());
+        assert_eq!(axrom.read_prg(0x8000), 0);
+        assert_eq!(axrom.mirroring(), MirroringType::SingleScreenLower);
+
+        axrom.write_prg(0x8000, 0b0001_0010);
+        assert_eq!(axrom.read_prg(0x8000), 2);
+        assert_eq!(axrom.mirroring(), MirroringType::SingleScreenUpper);
+    }
+}
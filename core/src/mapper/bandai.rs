@@ -0,0 +1,294 @@
+use super::eeprom::{Eeprom, EepromKind};
+use super::{
+    banked_offset, board_debug_state, ChrBanks, Mapper, PrgRamWindow, CHR_WINDOWS, CHR_WINDOW_SIZE,
+};
+use crate::cartridge::MirroringType;
+use crate::json::Value;
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/Bandai_FCG_board
+
+    registers repeat every 16 bytes, at $6000-$7FFF on the FCG-1/2 and at $8000-$FFFF
+    on the LZ93D50. mapper 16 does not say which, so both are decoded
+
+    $x0-$x7  1KB chr bank at $0000, $0400, ... $1C00
+    $x8      .... PPPP  16KB prg bank at $8000, $C000 is the last bank
+    $x9      .... ..MM  mirroring: vertical, horizontal, one screen lower, upper
+    $xA      .... ...E  irq enable, acknowledges a pending irq. the LZ93D50 also copies
+                        its latch into the counter
+    $xB-$xC  irq latch low and high byte (the counter itself on the FCG)
+    $xD      RDC. ....  eeprom: R lets the eeprom drive SDA, D is SDA, C is SCL
+
+    the counter decrements every cpu cycle while enabled and raises the irq when it is
+    at zero. reads from $6000-$7FFF return the eeprom's SDA in bit 4
+
+    16   24C02 eeprom, 256 bytes
+    159  24C01 eeprom, 128 bytes
+*/
+const REG_PRG_BANK: u16 = 0x8;
+const REG_MIRRORING: u16 = 0x9;
+const REG_IRQ_CONTROL: u16 = 0xA;
+const REG_IRQ_LATCH_LOW: u16 = 0xB;
+const REG_IRQ_LATCH_HIGH: u16 = 0xC;
+const REG_EEPROM: u16 = 0xD;
+
+const EEPROM_SCL: u8 = 0b0010_0000;
+const EEPROM_SDA: u8 = 0b0100_0000;
+const EEPROM_READ: u8 = 0b1000_0000;
+const EEPROM_SDA_OUT: u8 = 0b0001_0000;
+
+pub struct Bandai {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    chr_banks: [u8; CHR_WINDOWS],
+    prg_bank: u8,
+    mirroring: u8,
+    irq_enabled: bool,
+    irq_latch: u16,
+    irq_counter: u16,
+    irq: bool,
+    eeprom_control: u8,
+    eeprom: Eeprom,
+}
+
+impl Bandai {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>, eeprom: EepromKind) -> Self {
+        let chr_is_ram = chr.is_empty();
+        Bandai {
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            chr_banks: [0; CHR_WINDOWS],
+            prg_bank: 0,
+            mirroring: 0,
+            irq_enabled: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq: false,
+            eeprom_control: 0,
+            eeprom: Eeprom::new(eeprom),
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr & 0xF {
+            0x0..=0x7 => self.chr_banks[(addr & 0x7) as usize] = data,
+            REG_PRG_BANK => self.prg_bank = data & 0x0F,
+            REG_MIRRORING => self.mirroring = data & 0b11,
+            REG_IRQ_CONTROL => {
+                self.irq_enabled = data & 1 != 0;
+                self.irq_counter = self.irq_latch;
+                self.irq = false;
+            }
+            REG_IRQ_LATCH_LOW => self.irq_latch = self.irq_latch & 0xFF00 | data as u16,
+            REG_IRQ_LATCH_HIGH => self.irq_latch = self.irq_latch & 0x00FF | (data as u16) << 8,
+            REG_EEPROM => {
+                self.eeprom_control = data;
+                self.eeprom
+                    .write(data & EEPROM_SCL != 0, data & EEPROM_SDA != 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[addr as usize / CHR_WINDOW_SIZE] as usize;
+        banked_offset(self.chr.len(), CHR_WINDOW_SIZE, bank, addr as usize)
+    }
+}
+
+impl Mapper for Bandai {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let bank = match addr {
+            0x8000..=0xBFFF => self.prg_bank as usize,
+            _ => self.prg.len() / PRG_BANK_SIZE - 1,
+        };
+        self.prg[banked_offset(self.prg.len(), PRG_BANK_SIZE, bank, addr as usize)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        self.write_register(addr, data);
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_banks(&self) -> ChrBanks {
+        let mut banks = [0; CHR_WINDOWS];
+        for (window, bank) in banks.iter_mut().enumerate() {
+            *bank = self.chr_banks[window] as usize;
+        }
+        banks
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.mirroring {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            2 => MirroringType::SingleScreenLower,
+            _ => MirroringType::SingleScreenUpper,
+        }
+    }
+
+    // no prg ram, only the eeprom's data line
+    fn prg_ram_window(&self, _addr: u16) -> PrgRamWindow {
+        let sda = self.eeprom_control & EEPROM_READ == 0 || self.eeprom.output();
+        PrgRamWindow::Register {
+            data: if sda { EEPROM_SDA_OUT } else { 0 },
+            mask: EEPROM_SDA_OUT,
+        }
+    }
+
+    fn write_prg_ram_window(&mut self, addr: u16, data: u8) {
+        self.write_register(addr, data);
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        if !self.irq_enabled {
+            return;
+        }
+        for _ in 0..cycles {
+            if self.irq_counter == 0 {
+                self.irq = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.data().to_vec())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.eeprom.load_data(data);
+    }
+
+    fn debug_state(&self) -> Value {
+        board_debug_state(
+            self,
+            vec![
+                ("prg_bank", Value::from(self.prg_bank)),
+                ("irq_enabled", Value::from(self.irq_enabled)),
+                ("irq_latch", Value::from(self.irq_latch)),
+                ("irq_counter", Value::from(self.irq_counter)),
+                ("irq", Value::from(self.irq)),
+                ("eeprom_control", Value::from(self.eeprom_control)),
+            ],
+        )
+    }
+
+    // chr banks, prg bank, mirroring, irq, eeprom, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = self.chr_banks.to_vec();
+        state.extend_from_slice(&[self.prg_bank, self.mirroring, self.irq_enabled as u8]);
+        state.extend_from_slice(&self.irq_latch.to_le_bytes());
+        state.extend_from_slice(&self.irq_counter.to_le_bytes());
+        state.extend_from_slice(&[self.irq as u8, self.eeprom_control]);
+        self.eeprom.save_state(&mut state);
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.chr_banks.copy_from_slice(&state[0..8]);
+        self.prg_bank = state[8];
+        self.mirroring = state[9];
+        self.irq_enabled = state[10] != 0;
+        self.irq_latch = u16::from_le_bytes([state[11], state[12]]);
+        self.irq_counter = u16::from_le_bytes([state[13], state[14]]);
+        self.irq = state[15] != 0;
+        self.eeprom_control = state[16];
+        let chr = self.eeprom.load_state(&state[17..]);
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(chr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registers_and_eeprom() {
+        let mut prg = vec![0; PRG_BANK_SIZE * 8];
+        for bank in 0..8 {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut bandai = Bandai::new(prg, vec![0; 0x8000], EepromKind::C24C02);
+        assert_eq!(bandai.read_prg(0xC000), 7);
+
+        // the LZ93D50 decodes $8000-$FFFF, the FCG $6000-$7FFF
+        bandai.write_prg(0x8008, 3);
+        assert_eq!(bandai.read_prg(0x8000), 3);
+        bandai.write_prg_ram_window(0x6009, 1);
+        assert_eq!(bandai.mirroring(), MirroringType::Horizontal);
+        bandai.write_prg(0xFFF5, 9);
+        assert_eq!(bandai.chr_banks()[5], 9);
+
+        bandai.write_prg(0x800B, 2);
+        bandai.write_prg(0x800C, 0);
+        bandai.write_prg(0x800A, 1);
+        bandai.tick(2);
+        assert!(!bandai.irq_pending());
+        bandai.tick(1);
+        assert!(bandai.irq_pending());
+        bandai.write_prg(0x800A, 0);
+        assert!(!bandai.irq_pending());
+
+        // a start condition, then the eeprom acknowledges its device byte on SDA
+        let control = |scl: bool, sda: bool| {
+            EEPROM_READ | if scl { EEPROM_SCL } else { 0 } | if sda { EEPROM_SDA } else { 0 }
+        };
+        for (scl, sda) in [(false, true), (true, true), (true, false), (false, false)].iter() {
+            bandai.write_prg(0x800D, control(*scl, *sda));
+        }
+        for bit in 0..8 {
+            let sda = 0xA0_u8 >> (7 - bit) & 1 != 0;
+            bandai.write_prg(0x800D, control(false, sda));
+            bandai.write_prg(0x800D, control(true, sda));
+            bandai.write_prg(0x800D, control(false, sda));
+        }
+        assert_eq!(
+            bandai.prg_ram_window(0x6000),
+            PrgRamWindow::Register {
+                data: 0,
+                mask: EEPROM_SDA_OUT
+            }
+        );
+
+        bandai.load_save_data(&[0x42; 4]);
+        assert_eq!(
+            &bandai.save_data().unwrap()[..5],
+            &[0x42, 0x42, 0x42, 0x42, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/Bandai_FCG_board#Serial_EEPROM
+
+    i2c serial eeproms the game bit-bangs through a mapper register. a start is SDA
+    falling while SCL is high, a stop SDA rising while SCL is high, and the receiver
+    samples a bit on each rising edge of SCL, acknowledging every byte by pulling SDA
+    low for a ninth clock
+
+    24C02  256 bytes, msb first: device byte 1010xxxR, then a word address for writes,
+           then data. reads continue from the address until the game does not ack
+    24C01  128 bytes (Xicor X24C01), lsb first: no device byte, the first byte is the
+           7 bit address followed by the read bit
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EepromKind {
+    C24C01,
+    C24C02,
+}
+
+impl EepromKind {
+    fn size(&self) -> usize {
+        match self {
+            EepromKind::C24C01 => 128,
+            EepromKind::C24C02 => 256,
+        }
+    }
+
+    // writes wrap around inside a page
+    fn page_size(&self) -> usize {
+        match self {
+            EepromKind::C24C01 => 4,
+            EepromKind::C24C02 => 8,
+        }
+    }
+}
+
+const DEVICE_MASK: u8 = 0xF0;
+const DEVICE_ADDRESS: u8 = 0xA0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    Device,
+    Address,
+    Write,
+    Read,
+}
+
+#[derive(Clone)]
+pub struct Eeprom {
+    kind: EepromKind,
+    data: Vec<u8>,
+    scl: bool,
+    sda: bool,
+    // what the eeprom drives onto SDA, high when it lets go
+    output: bool,
+    phase: Phase,
+    // bits of the current byte so far, 8 during the acknowledge clock
+    bit: u8,
+    shift: u8,
+    // the eeprom is pulling SDA low to acknowledge the byte it received
+    acking: bool,
+    pointer: usize,
+}
+
+impl Eeprom {
+    pub fn new(kind: EepromKind) -> Self {
+        Eeprom {
+            kind: kind,
+            data: vec![0xFF; kind.size()],
+            scl: false,
+            sda: false,
+            output: true,
+            phase: Phase::Idle,
+            bit: 0,
+            shift: 0,
+            acking: false,
+            pointer: 0,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn load_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+
+    // the level on SDA the game reads back
+    pub fn output(&self) -> bool {
+        self.output
+    }
+
+    // the game sets both lines with every write to the control register
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        let (was_scl, was_sda) = (self.scl, self.sda);
+        self.scl = scl;
+        self.sda = sda;
+        match (was_scl, scl) {
+            (true, true) if was_sda && !sda => self.start(),
+            (true, true) if !was_sda && sda => self.stop(),
+            (false, true) => self.rising_edge(sda),
+            (true, false) => self.falling_edge(),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self) {
+        self.phase = match self.kind {
+            EepromKind::C24C01 => Phase::Address,
+            EepromKind::C24C02 => Phase::Device,
+        };
+        self.bit = 0;
+        self.shift = 0;
+        self.acking = false;
+        self.output = true;
+    }
+
+    fn stop(&mut self) {
+        self.phase = Phase::Idle;
+        self.acking = false;
+        self.output = true;
+    }
+
+    fn rising_edge(&mut self, sda: bool) {
+        if self.acking {
+            self.acking = false;
+            self.bit = 0;
+            return;
+        }
+        match self.phase {
+            Phase::Idle => {}
+            Phase::Read if self.bit < 8 => self.bit += 1,
+            Phase::Read => {
+                // the game acknowledges to get the next byte, a high SDA ends the read
+                if sda {
+                    self.phase = Phase::Idle;
+                } else {
+                    self.pointer = (self.pointer + 1) % self.data.len();
+                    self.bit = 0;
+                }
+            }
+            _ => {
+                self.shift = match self.kind {
+                    EepromKind::C24C01 => self.shift >> 1 | (sda as u8) << 7,
+                    EepromKind::C24C02 => self.shift << 1 | sda as u8,
+                };
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.receive(self.shift);
+                }
+            }
+        }
+    }
+
+    fn falling_edge(&mut self) {
+        self.output = match self.phase {
+            _ if self.acking => false,
+            Phase::Read if self.bit < 8 => {
+                let byte = self.data[self.pointer];
+                let shift = match self.kind {
+                    EepromKind::C24C01 => self.bit,
+                    EepromKind::C24C02 => 7 - self.bit,
+                };
+                byte >> shift & 1 != 0
+            }
+            _ => true,
+        };
+    }
+
+    fn receive(&mut self, byte: u8) {
+        self.acking = true;
+        self.phase = match (self.phase, self.kind) {
+            (Phase::Device, _) if byte & DEVICE_MASK != DEVICE_ADDRESS => {
+                // another chip on the bus
+                self.acking = false;
+                Phase::Idle
+            }
+            (Phase::Device, _) if byte & 1 != 0 => Phase::Read,
+            (Phase::Device, _) => Phase::Address,
+            (Phase::Address, EepromKind::C24C01) => {
+                self.pointer = (byte & 0x7F) as usize;
+                if byte & 0x80 != 0 {
+                    Phase::Read
+                } else {
+                    Phase::Write
+                }
+            }
+            (Phase::Address, EepromKind::C24C02) => {
+                self.pointer = byte as usize;
+                Phase::Write
+            }
+            (phase, _) => {
+                self.data[self.pointer] = byte;
+                let page = self.kind.page_size();
+                self.pointer = self.pointer / page * page + (self.pointer + 1) % page;
+                phase
+            }
+        };
+    }
+
+    // the lines, the transfer in progress, then the contents
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        let phase = match self.phase {
+            Phase::Idle => 0,
+            Phase::Device => 1,
+            Phase::Address => 2,
+            Phase::Write => 3,
+            Phase::Read => 4,
+        };
+        state.extend_from_slice(&[
+            self.scl as u8,
+            self.sda as u8,
+            self.output as u8,
+            phase,
+            self.bit,
+            self.shift,
+            self.acking as u8,
+            self.pointer as u8,
+        ]);
+        state.extend_from_slice(&self.data);
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.scl = state[0] != 0;
+        self.sda = state[1] != 0;
+        self.output = state[2] != 0;
+        self.phase = match state[3] {
+            1 => Phase::Device,
+            2 => Phase::Address,
+            3 => Phase::Write,
+            4 => Phase::Read,
+            _ => Phase::Idle,
+        };
+        self.bit = state[4];
+        self.shift = state[5];
+        self.acking = state[6] != 0;
+        self.pointer = state[7] as usize;
+        let end = 8 + self.data.len();
+        self.data.copy_from_slice(&state[8..end]);
+        &state[end..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the game's side of the bus
+    fn start(eeprom: &mut Eeprom) {
+        eeprom.write(false, true);
+        eeprom.write(true, true);
+        eeprom.write(true, false);
+        eeprom.write(false, false);
+    }
+
+    fn stop(eeprom: &mut Eeprom) {
+        eeprom.write(false, false);
+        eeprom.write(true, false);
+        eeprom.write(true, true);
+    }
+
+    fn clock_bit(eeprom: &mut Eeprom, sda: bool) -> bool {
+        eeprom.write(false, sda);
+        eeprom.write(true, sda);
+        let read = eeprom.output();
+        eeprom.write(false, sda);
+        read
+    }
+
+    // returns whether the eeprom acknowledged
+    fn send(eeprom: &mut Eeprom, byte: u8, lsb_first: bool) -> bool {
+        for bit in 0..8 {
+            let shift = if lsb_first { bit } else { 7 - bit };
+            clock_bit(eeprom, byte >> shift & 1 != 0);
+        }
+        !clock_bit(eeprom, true)
+    }
+
+    fn receive(eeprom: &mut Eeprom, ack: bool) -> u8 {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = byte << 1 | clock_bit(eeprom, true) as u8;
+        }
+        clock_bit(eeprom, !ack);
+        byte
+    }
+
+    #[test]
+    fn test_24c02() {
+        let mut eeprom = Eeprom::new(EepromKind::C24C02);
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA0, false));
+        assert!(send(&mut eeprom, 0x10, false));
+        assert!(send(&mut eeprom, 0x12, false));
+        assert!(send(&mut eeprom, 0x34, false));
+        stop(&mut eeprom);
+        assert_eq!(&eeprom.data()[0x10..0x12], &[0x12, 0x34]);
+
+        // set the address with a dummy write, then read from it with a repeated start
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA0, false));
+        assert!(send(&mut eeprom, 0x10, false));
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA1, false));
+        assert_eq!(receive(&mut eeprom, true), 0x12);
+        assert_eq!(receive(&mut eeprom, false), 0x34);
+        stop(&mut eeprom);
+
+        // someone else's device address is ignored
+        start(&mut eeprom);
+        assert!(!send(&mut eeprom, 0x50, false));
+
+        let mut state = Vec::new();
+        eeprom.save_state(&mut state);
+        let mut loaded = Eeprom::new(EepromKind::C24C02);
+        assert!(loaded.load_state(&state).is_empty());
+        assert_eq!(loaded.data(), eeprom.data());
+    }
+
+    #[test]
+    fn test_24c01() {
+        let mut eeprom = Eeprom::new(EepromKind::C24C01);
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0x05, true));
+        assert!(send(&mut eeprom, 0x5A, true));
+        stop(&mut eeprom);
+        assert_eq!(eeprom.data()[5], 0x5A);
+
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0x85, true));
+        // lsb first
+        assert_eq!(receive(&mut eeprom, false), 0x5A_u8.reverse_bits());
+    }
+}
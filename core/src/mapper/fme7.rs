@@ -0,0 +1,312 @@
+use super::{
+    banked_offset, board_debug_state, ChrBanks, Mapper, PrgRamWindow, CHR_WINDOWS, CHR_WINDOW_SIZE,
+};
+use crate::apu::sunsoft5b::Sunsoft5b;
+use crate::cartridge::MirroringType;
+use crate::json::Value;
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/Sunsoft_FME-7
+
+    $8000-$9FFF  command, the low 4 bits pick what $A000-$BFFF writes
+    $A000-$BFFF  parameter
+    $C000-$FFFF  5B audio, see apu::sunsoft5b
+
+    $0-$7  1KB chr bank at $0000, $0400, ... $1C00
+    $8     ERbB BBBB  8KB bank at $6000: E ram enable, R ram (1) or rom (0)
+    $9-$B  ..bB BBBB  8KB prg banks at $8000, $A000 and $C000, $E000 is the last bank
+    $C     .... ..MM  mirroring: vertical, horizontal, one screen lower, upper
+    $D     C... ...T  irq: C counts down, T raises the irq at the wrap. writing it
+                      acknowledges a pending irq
+    $E-$F  irq counter low and high byte
+
+    the counter decrements every cpu cycle and raises the irq when it wraps from $0000
+    to $FFFF
+*/
+const COMMAND_PRG_RAM: u8 = 0x8;
+const COMMAND_MIRRORING: u8 = 0xC;
+const COMMAND_IRQ_CONTROL: u8 = 0xD;
+const COMMAND_IRQ_COUNTER_LOW: u8 = 0xE;
+const COMMAND_IRQ_COUNTER_HIGH: u8 = 0xF;
+
+const PRG_RAM_SELECT: u8 = 0b0100_0000;
+const PRG_RAM_ENABLE: u8 = 0b1000_0000;
+const IRQ_ENABLE: u8 = 0b0000_0001;
+const IRQ_COUNTER_ENABLE: u8 = 0b1000_0000;
+
+pub struct FME7 {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    command: u8,
+    chr_banks: [u8; CHR_WINDOWS],
+    // $6000, $8000, $A000, $C000
+    prg_banks: [u8; 4],
+    mirroring: u8,
+    irq_control: u8,
+    irq_counter: u16,
+    irq: bool,
+    audio: Sunsoft5b,
+}
+
+impl FME7 {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>) -> Self {
+        let chr_is_ram = chr.is_empty();
+        FME7 {
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            command: 0,
+            chr_banks: [0; CHR_WINDOWS],
+            prg_banks: [0; 4],
+            mirroring: 0,
+            irq_control: 0,
+            irq_counter: 0,
+            irq: false,
+            audio: Sunsoft5b::new(),
+        }
+    }
+
+    fn write_parameter(&mut self, data: u8) {
+        match self.command {
+            0x0..=0x7 => self.chr_banks[self.command as usize] = data,
+            COMMAND_PRG_RAM..=0xB => {
+                self.prg_banks[(self.command - COMMAND_PRG_RAM) as usize] = data;
+            }
+            COMMAND_MIRRORING => self.mirroring = data & 0b11,
+            COMMAND_IRQ_CONTROL => {
+                self.irq_control = data;
+                self.irq = false;
+            }
+            COMMAND_IRQ_COUNTER_LOW => {
+                self.irq_counter = self.irq_counter & 0xFF00 | data as u16;
+            }
+            COMMAND_IRQ_COUNTER_HIGH => {
+                self.irq_counter = self.irq_counter & 0x00FF | (data as u16) << 8;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        banked_offset(self.prg.len(), PRG_BANK_SIZE, bank, addr as usize)
+    }
+}
+
+impl Mapper for FME7 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let bank = match addr {
+            0x8000..=0xDFFF => {
+                (self.prg_banks[1 + (addr as usize - 0x8000) / PRG_BANK_SIZE] & 0b0011_1111)
+                    as usize
+            }
+            _ => self.prg.len() / PRG_BANK_SIZE - 1,
+        };
+        self.prg[self.prg_offset(bank, addr)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.command = data & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(data),
+            0xC000..=0xDFFF => self.audio.select(data),
+            _ => self.audio.write(data),
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        let bank = self.chr_banks[addr as usize / CHR_WINDOW_SIZE] as usize;
+        self.chr[banked_offset(self.chr.len(), CHR_WINDOW_SIZE, bank, addr as usize)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let bank = self.chr_banks[addr as usize / CHR_WINDOW_SIZE] as usize;
+            let offset = banked_offset(self.chr.len(), CHR_WINDOW_SIZE, bank, addr as usize);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_banks(&self) -> ChrBanks {
+        let mut banks = [0; CHR_WINDOWS];
+        for (window, bank) in banks.iter_mut().enumerate() {
+            *bank = self.chr_banks[window] as usize;
+        }
+        banks
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.mirroring {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            2 => MirroringType::SingleScreenLower,
+            _ => MirroringType::SingleScreenUpper,
+        }
+    }
+
+    fn prg_ram_window(&self, addr: u16) -> PrgRamWindow {
+        let bank = self.prg_banks[0];
+        match (bank & PRG_RAM_SELECT != 0, bank & PRG_RAM_ENABLE != 0) {
+            (true, true) => PrgRamWindow::Ram,
+            (true, false) => PrgRamWindow::Disabled,
+            (false, _) => {
+                let bank = (bank & 0b0011_1111) as usize;
+                PrgRamWindow::Rom(self.prg[self.prg_offset(bank, addr)])
+            }
+        }
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.audio.tick(cycles);
+        if self.irq_control & IRQ_COUNTER_ENABLE == 0 {
+            return;
+        }
+        let (counter, wrapped) = self.irq_counter.overflowing_sub(cycles as u16);
+        self.irq_counter = counter;
+        if wrapped && self.irq_control & IRQ_ENABLE != 0 {
+            self.irq = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    // prg_banks are the raw registers for $6000, $8000, $A000 and $C000
+    fn debug_state(&self) -> Value {
+        board_debug_state(
+            self,
+            vec![
+                ("command", Value::from(self.command)),
+                ("prg_banks", Value::from(&self.prg_banks[..])),
+                ("irq_control", Value::from(self.irq_control)),
+                ("irq_counter", Value::from(self.irq_counter)),
+                ("irq", Value::from(self.irq)),
+            ],
+        )
+    }
+
+    // command, chr banks, prg banks, mirroring, irq, the 5B, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.command];
+        state.extend_from_slice(&self.chr_banks);
+        state.extend_from_slice(&self.prg_banks);
+        state.push(self.mirroring);
+        state.push(self.irq_control);
+        state.extend_from_slice(&self.irq_counter.to_le_bytes());
+        state.push(self.irq as u8);
+        self.audio.save_state(&mut state);
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.command = state[0];
+        self.chr_banks.copy_from_slice(&state[1..9]);
+        self.prg_banks.copy_from_slice(&state[9..13]);
+        self.mirroring = state[13];
+        self.irq_control = state[14];
+        self.irq_counter = u16::from_le_bytes([state[15], state[16]]);
+        self.irq = state[17] != 0;
+        let chr = self.audio.load_state(&state[18..]);
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(chr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(fme7: &mut FME7, command: u8, parameter: u8) {
+        fme7.write_prg(0x8000, command);
+        fme7.write_prg(0xA000, parameter);
+    }
+
+    #[test]
+    fn test_banks_and_irq() {
+        // every 8KB prg bank and 1KB chr bank starts with its number
+        let mut prg = vec![0; PRG_BANK_SIZE * 16];
+        for bank in 0..16 {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut chr = vec![0; CHR_WINDOW_SIZE * 32];
+        for bank in 0..32 {
+            chr[bank * CHR_WINDOW_SIZE] = bank as u8;
+        }
+        let mut fme7 = FME7::new(prg, chr);
+        assert_eq!(fme7.read_prg(0xE000), 15);
+
+        command(&mut fme7, 0x9, 3);
+        command(&mut fme7, 0xB, 7);
+        command(&mut fme7, 0x5, 20);
+        assert_eq!((fme7.read_prg(0x8000), fme7.read_prg(0xC000)), (3, 7));
+        assert_eq!(fme7.read_chr(0x1400), 20);
+        assert_eq!(fme7.chr_banks()[5], 20);
+
+        // rom at $6000, then ram switched on and off
+        command(&mut fme7, 0x8, 9);
+        assert_eq!(fme7.prg_ram_window(0x6000), PrgRamWindow::Rom(9));
+        command(&mut fme7, 0x8, PRG_RAM_SELECT | PRG_RAM_ENABLE);
+        assert_eq!(fme7.prg_ram_window(0x6000), PrgRamWindow::Ram);
+        command(&mut fme7, 0x8, PRG_RAM_SELECT);
+        assert_eq!(fme7.prg_ram_window(0x6000), PrgRamWindow::Disabled);
+
+        command(&mut fme7, 0xC, 1);
+        assert_eq!(fme7.mirroring(), MirroringType::Horizontal);
+
+        let debug = fme7.debug_state();
+        assert_eq!(
+            debug.get("mirroring").and_then(Value::as_str),
+            Some("Horizontal")
+        );
+        assert_eq!(
+            debug.get("chr_banks").unwrap().as_array().unwrap()[5].as_u64(),
+            Some(20)
+        );
+        assert_eq!(
+            debug.get("prg_banks").unwrap().as_array().unwrap()[3].as_u64(),
+            Some(7)
+        );
+
+        // fires when the counter wraps past zero, not on reaching it
+        command(&mut fme7, 0xE, 10);
+        command(&mut fme7, 0xF, 0);
+        command(&mut fme7, 0xD, IRQ_COUNTER_ENABLE | IRQ_ENABLE);
+        fme7.tick(10);
+        assert!(!fme7.irq_pending());
+        let state = fme7.save_state();
+        fme7.tick(1);
+        assert!(fme7.irq_pending());
+        assert_eq!(fme7.irq_counter, 0xFFFF);
+
+        fme7.load_state(&state);
+        assert!(!fme7.irq_pending());
+        assert_eq!(fme7.irq_counter, 0);
+
+        // writing the control register acknowledges it
+        fme7.tick(1);
+        command(&mut fme7, 0xD, IRQ_COUNTER_ENABLE | IRQ_ENABLE);
+        assert!(!fme7.irq_pending());
+    }
+}
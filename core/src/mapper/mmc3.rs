@@ -0,0 +1,370 @@
+use super::{banked_offset, board_debug_state, ChrBanks, Mapper, CHR_WINDOWS, CHR_WINDOW_SIZE};
+use crate::cartridge::MirroringType;
+use crate::json::{self, Value};
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/MMC3
+
+    registers are decoded by address range and whether the address is even or odd
+
+    $8000  CPMx xRRR  bank select: R picks the register $8001 writes, P the prg layout,
+                      C swaps the two halves of the pattern tables
+    $8001  bank data
+    $A000  .... ...M  mirroring: vertical, horizontal. four-screen boards ignore it
+    $A001  RWxx xxxx  prg ram enable and write protect
+    $C000  irq latch
+    $C001  reloads the irq counter from the latch at the next A12 rise
+    $E000  disables the irq and acknowledges a pending one
+    $E001  enables the irq
+
+    R0-R1  2KB chr banks at $0000 and $0800 (low bit ignored), $1000 and $1800 with C set
+    R2-R5  1KB chr banks at $1000-$1C00, $0000-$0C00 with C set
+    R6     8KB prg bank at $8000, at $C000 with P set. the other one is the second last bank
+    R7     8KB prg bank at $A000, $E000 is the last bank
+
+    the irq counter is clocked by rises of ppu address line A12, once per scanline when
+    the background and the sprites use different pattern tables (see ppu::a12). a clock
+    with the counter at 0 or a reload pending reloads it from the latch, any other
+    decrements it. with the counter at 0 after either the irq is raised
+
+    the prg ram protect bits are kept for the debug state only. MMC6 boards share the
+    mapper number and lay them out differently, so like most emulators the ram stays on
+*/
+const REG_BANK_SELECT: u16 = 0x8000;
+const REG_BANK_DATA: u16 = 0x8001;
+const REG_MIRRORING: u16 = 0xA000;
+const REG_PRG_RAM_PROTECT: u16 = 0xA001;
+const REG_IRQ_LATCH: u16 = 0xC000;
+const REG_IRQ_RELOAD: u16 = 0xC001;
+const REG_IRQ_DISABLE: u16 = 0xE000;
+const REG_IRQ_ENABLE: u16 = 0xE001;
+
+const PRG_MODE: u8 = 0b0100_0000;
+const CHR_INVERSION: u8 = 0b1000_0000;
+
+pub struct MMC3 {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    bank_select: u8,
+    // R0-R7
+    registers: [u8; 8],
+    // soldered four-screen boards keep theirs
+    fixed_mirroring: MirroringType,
+    mirroring: u8,
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq: bool,
+}
+
+impl MMC3 {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>, mirroring_type: MirroringType) -> Self {
+        let chr_is_ram = chr.is_empty();
+        MMC3 {
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            bank_select: 0,
+            // power-on values are undefined, these show the first banks in order
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            fixed_mirroring: mirroring_type,
+            mirroring: 0,
+            prg_ram_protect: 0,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq: false,
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> usize {
+        let last = self.prg.len() / PRG_BANK_SIZE - 1;
+        let swapped = self.bank_select & PRG_MODE != 0;
+        match (addr, swapped) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.registers[6] as usize,
+            (0xA000..=0xBFFF, _) => self.registers[7] as usize,
+            (0xE000..=0xFFFF, _) => last,
+            _ => last - 1,
+        }
+    }
+
+    // the 1KB page window `window` shows
+    fn chr_page(&self, window: usize) -> usize {
+        let window = if self.bank_select & CHR_INVERSION != 0 {
+            window ^ 4
+        } else {
+            window
+        };
+        match window {
+            0..=3 => (self.registers[window / 2] & 0xFE) as usize + window % 2,
+            _ => self.registers[window - 2] as usize,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let page = self.chr_page(addr as usize / CHR_WINDOW_SIZE);
+        banked_offset(self.chr.len(), CHR_WINDOW_SIZE, page, addr as usize)
+    }
+}
+
+impl Mapper for MMC3 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let bank = self.prg_bank(addr);
+        self.prg[banked_offset(self.prg.len(), PRG_BANK_SIZE, bank, addr as usize)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        match addr & 0xE001 {
+            REG_BANK_SELECT => self.bank_select = data,
+            REG_BANK_DATA => self.registers[(self.bank_select & 0b111) as usize] = data,
+            REG_MIRRORING => self.mirroring = data & 1,
+            REG_PRG_RAM_PROTECT => self.prg_ram_protect = data,
+            REG_IRQ_LATCH => self.irq_latch = data,
+            REG_IRQ_RELOAD => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            REG_IRQ_DISABLE => {
+                self.irq_enabled = false;
+                self.irq = false;
+            }
+            REG_IRQ_ENABLE => self.irq_enabled = true,
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_banks(&self) -> ChrBanks {
+        let mut banks = [0; CHR_WINDOWS];
+        for (window, bank) in banks.iter_mut().enumerate() {
+            *bank = self.chr_page(window);
+        }
+        banks
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match (self.fixed_mirroring, self.mirroring) {
+            (MirroringType::FourScreen, _) => MirroringType::FourScreen,
+            (_, 0) => MirroringType::Vertical,
+            _ => MirroringType::Horizontal,
+        }
+    }
+
+    fn watches_a12(&self) -> bool {
+        true
+    }
+
+    fn a12_rising_edge(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    // chr_banks are the 1KB pages shown, registers R0-R7 as the game wrote them
+    fn debug_state(&self) -> Value {
+        board_debug_state(
+            self,
+            vec![
+                ("bank_select", Value::from(self.bank_select)),
+                ("registers", Value::from(&self.registers[..])),
+                (
+                    "prg_banks",
+                    Value::Array(
+                        [0x8000, 0xA000, 0xC000, 0xE000]
+                            .iter()
+                            .map(|addr| Value::from(self.prg_bank(*addr)))
+                            .collect(),
+                    ),
+                ),
+                ("prg_ram_protect", Value::from(self.prg_ram_protect)),
+                (
+                    "irq",
+                    json::object(vec![
+                        ("latch", Value::from(self.irq_latch)),
+                        ("counter", Value::from(self.irq_counter)),
+                        ("reload", Value::from(self.irq_reload)),
+                        ("enabled", Value::from(self.irq_enabled)),
+                        ("pending", Value::from(self.irq)),
+                    ]),
+                ),
+            ],
+        )
+    }
+
+    // bank select, R0-R7, mirroring, prg ram protect, irq, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.bank_select];
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&[
+            self.mirroring,
+            self.prg_ram_protect,
+            self.irq_latch,
+            self.irq_counter,
+            self.irq_reload as u8,
+            self.irq_enabled as u8,
+            self.irq as u8,
+        ]);
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.bank_select = state[0];
+        self.registers.copy_from_slice(&state[1..9]);
+        self.mirroring = state[9];
+        self.prg_ram_protect = state[10];
+        self.irq_latch = state[11];
+        self.irq_counter = state[12];
+        self.irq_reload = state[13] != 0;
+        self.irq_enabled = state[14] != 0;
+        self.irq = state[15] != 0;
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(&state[16..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::SharedMapper;
+    use crate::ppu::registers::BitwiseRegister;
+    use crate::ppu::PPU;
+    use crate::sync::AtomicRefCell;
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+
+    fn write_register(mmc3: &mut MMC3, register: u8, data: u8) {
+        mmc3.write_prg(REG_BANK_SELECT, mmc3.bank_select & 0xF8 | register);
+        mmc3.write_prg(REG_BANK_DATA, data);
+    }
+
+    #[test]
+    fn test_banks() {
+        // every 8KB prg bank starts with its number
+        let mut prg = vec![0; PRG_BANK_SIZE * 16];
+        for bank in 0..16 {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut mmc3 = MMC3::new(prg, vec![0; 0x20000], MirroringType::Horizontal);
+        write_register(&mut mmc3, 6, 3);
+        write_register(&mut mmc3, 7, 5);
+        let banks = |mmc3: &MMC3| {
+            [0x8000, 0xA000, 0xC000, 0xE000]
+                .iter()
+                .map(|addr| mmc3.read_prg(*addr))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(banks(&mmc3), vec![3, 5, 14, 15]);
+        mmc3.write_prg(REG_BANK_SELECT, PRG_MODE);
+        assert_eq!(banks(&mmc3), vec![14, 5, 3, 15]);
+
+        write_register(&mut mmc3, 0, 9);
+        write_register(&mut mmc3, 5, 40);
+        assert_eq!(mmc3.chr_banks(), [8, 9, 2, 3, 4, 5, 6, 40]);
+        mmc3.write_prg(REG_BANK_SELECT, CHR_INVERSION);
+        assert_eq!(mmc3.chr_banks(), [4, 5, 6, 40, 8, 9, 2, 3]);
+
+        // registers repeat through their whole range
+        mmc3.write_prg(0xBFFE, 0);
+        assert_eq!(mmc3.mirroring(), MirroringType::Vertical);
+        let four_screen = MMC3::new(vec![0; 0x8000], Vec::new(), MirroringType::FourScreen);
+        assert_eq!(four_screen.mirroring(), MirroringType::FourScreen);
+    }
+
+    fn irq_counter(mapper: &SharedMapper) -> Option<u64> {
+        let state = mapper.borrow().debug_state();
+        state.get("irq")?.get("counter")?.as_u64()
+    }
+
+    #[test]
+    fn test_irq_counts_scanlines() {
+        let mapper: Box<dyn Mapper> = Box::new(MMC3::new(
+            vec![0; 0x8000],
+            vec![0; 0x2000],
+            MirroringType::Vertical,
+        ));
+        let mapper = Arc::new(AtomicRefCell::new(mapper));
+        let mut ppu = PPU::new(mapper.clone());
+        // background at $0000, sprites at $1000, rendering on
+        ppu.write_ctrl(0b0000_1000);
+        ppu.mask_register.update_bits(0b0001_1000);
+
+        // the first rise loads the latch, the 5th one takes the counter to 0
+        {
+            let mut mapper = mapper.borrow_mut();
+            mapper.write_prg(REG_IRQ_LATCH, 4);
+            mapper.write_prg(REG_IRQ_RELOAD, 0);
+            mapper.write_prg(REG_IRQ_ENABLE, 0);
+        }
+        let mut lines = 0;
+        while !mapper.borrow().irq_pending() {
+            for _ in 0..341 {
+                ppu.tick(1);
+            }
+            lines += 1;
+        }
+        assert_eq!(lines, 5);
+        assert_eq!(irq_counter(&mapper), Some(0));
+        mapper.borrow_mut().write_prg(REG_IRQ_DISABLE, 0);
+        assert!(!mapper.borrow().irq_pending());
+
+        // with rendering off A12 follows the vram address. pointing $2006 at $1000
+        // counts after a long time low, not right after the last rise
+        ppu.mask_register.update_bits(0);
+        ppu.tick(30);
+        ppu.write_address(0x10);
+        ppu.write_address(0x00);
+        assert_eq!(irq_counter(&mapper), Some(4));
+        ppu.write_address(0x00);
+        ppu.write_address(0x00);
+        ppu.tick(3);
+        ppu.write_address(0x10);
+        ppu.write_address(0x00);
+        assert_eq!(irq_counter(&mapper), Some(4));
+        ppu.write_address(0x00);
+        ppu.write_address(0x00);
+        ppu.tick(12);
+        ppu.write_address(0x10);
+        assert_eq!(irq_counter(&mapper), Some(3));
+    }
+}
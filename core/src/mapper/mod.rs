@@ -0,0 +1,215 @@
+use crate::cartridge::{Cartridge, MirroringType};
+use crate::json::{self, Value};
+use crate::sync::AtomicRefCell;
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub mod axrom;
+pub mod bandai;
+pub mod eeprom;
+pub mod fme7;
+pub mod mmc3;
+pub mod multicart;
+pub mod nrom;
+pub mod vrc4;
+use self::axrom::AxROM;
+use self::bandai::Bandai;
+use self::eeprom::EepromKind;
+use self::fme7::FME7;
+use self::mmc3::MMC3;
+use self::multicart::{LatchBoard, Multicart};
+use self::nrom::NROM;
+use self::vrc4::{VrcWiring, VRC4};
+
+// the finest chr banking there is (MMC3, VRC) switches the pattern tables in 1KB
+// windows, coarser boards switch several windows at once
+pub const CHR_WINDOW_SIZE: usize = 0x400;
+pub const CHR_WINDOWS: usize = 8;
+
+// which 1KB page of Mapper::chr each window of $0000-$1FFF shows
+pub type ChrBanks = [usize; CHR_WINDOWS];
+
+// what the cpu finds at $6000-$7FFF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrgRamWindow {
+    // the cartridge's prg ram, if it has any
+    Ram,
+    // a byte of prg rom the board banked in there
+    Rom(u8),
+    // a board register that drives the `mask` bits, the rest is open bus
+    Register { data: u8, mask: u8 },
+    // ram switched off, nothing answers
+    Disabled,
+}
+
+// https://wiki.nesdev.com/w/index.php/Mapper
+// the cartridge board decides what the cpu sees at $8000-$FFFF and what the ppu
+// sees at $0000-$1FFF (pattern tables)
+pub trait Mapper: Send + Sync {
+    fn read_prg(&self, addr: u16) -> u8;
+    fn write_prg(&mut self, addr: u16, data: u8);
+
+    fn read_chr(&self, addr: u16) -> u8;
+    fn write_chr(&mut self, addr: u16, data: u8);
+
+    // all of the board's chr rom or ram, banked in or not, for debug views
+    fn chr(&self) -> &[u8];
+
+    // boards without chr banking show the first 8KB
+    fn chr_banks(&self) -> ChrBanks {
+        [0, 1, 2, 3, 4, 5, 6, 7]
+    }
+
+    // some boards switch nametable mirroring at runtime, so the ppu asks on every access
+    fn mirroring(&self) -> MirroringType;
+
+    // most boards leave $6000-$7FFF to the prg ram
+    fn prg_ram_window(&self, _addr: u16) -> PrgRamWindow {
+        PrgRamWindow::Ram
+    }
+
+    // writes to $6000-$7FFF while it is not Ram, for boards with registers there
+    fn write_prg_ram_window(&mut self, _addr: u16, _data: u8) {}
+
+    // $4020-$5FFF, None where the board leaves it to open bus like all of the ones here do
+    fn read_expansion_area(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    // boards that keep saves on the board (serial eeprom) instead of in battery backed
+    // prg ram, see Nes::save_sram
+    fn save_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    // boards with a cpu cycle counter (FME-7, VRC) run it here, for every cycle the ppu
+    // and apu run
+    fn tick(&mut self, _cycles: u8) {}
+
+    // boards that count scanlines off ppu address line A12 (MMC3) get every rise that
+    // passes the filter in ppu::a12. decided once when the board is plugged in
+    fn watches_a12(&self) -> bool {
+        false
+    }
+
+    fn a12_rising_edge(&mut self) {}
+
+    // the board holds the cpu's /IRQ line low
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // expansion audio (5B, VRC6, ...) the apu mixes in, 0.0 to 1.0
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    // multicarts hold several games behind an outer bank, everything else one
+    fn games(&self) -> usize {
+        1
+    }
+
+    // maps in `game` the way the multicart's own menu does, the console then resets into it
+    fn select_game(&mut self, _game: usize) {}
+
+    // bank registers, irq counters and mirroring for a debug panel
+    fn debug_state(&self) -> Value {
+        board_debug_state(self, Vec::new())
+    }
+
+    // everything on the board that changes at runtime (registers, chr ram) for snapshots.
+    // load_state only ever gets what save_state of the same board returned
+    fn save_state(&self) -> Vec<u8>;
+    fn load_state(&mut self, state: &[u8]);
+}
+
+// offset into a rom of any size for `addr` inside a switchable window of `bank_size`.
+// bank numbers wrap around the banks the rom really has, and roms that are smaller
+// than a bank or not a multiple of it mirror, so odd dumps can't index out of bounds
+pub fn banked_offset(len: usize, bank_size: usize, bank: usize, addr: usize) -> usize {
+    let banks = len.div_ceil(bank_size).max(1);
+    ((bank % banks) * bank_size + addr % bank_size) % len
+}
+
+// what every board has, mirroring and the 1KB chr pages, then the board's own registers
+pub fn board_debug_state<M: Mapper + ?Sized>(mapper: &M, registers: Vec<(&str, Value)>) -> Value {
+    let mut members = vec![
+        (
+            "mirroring",
+            Value::from(format!("{:?}", mapper.mirroring())),
+        ),
+        ("chr_banks", Value::from(&mapper.chr_banks()[..])),
+    ];
+    members.extend(registers);
+    json::object(members)
+}
+
+// the cpu bus and the ppu bus both talk to the same board
+pub type SharedMapper = Arc<AtomicRefCell<Box<dyn Mapper>>>;
+
+pub fn new_mapper(cartridge: Cartridge) -> SharedMapper {
+    let latch_board = LatchBoard::from_mapper(cartridge.mapper);
+    let mapper: Box<dyn Mapper> = match (cartridge.mapper, latch_board) {
+        (4, _) => Box::new(MMC3::new(
+            cartridge.prg,
+            cartridge.chr,
+            cartridge.mirroring_type,
+        )),
+        (7, _) => Box::new(AxROM::new(cartridge.prg, cartridge.chr)),
+        (21, _) | (22, _) | (23, _) | (25, _) => Box::new(VRC4::new(
+            VrcWiring::from_mapper(cartridge.mapper).unwrap(),
+            cartridge.prg,
+            cartridge.chr,
+        )),
+        (16, _) => Box::new(Bandai::new(
+            cartridge.prg,
+            cartridge.chr,
+            EepromKind::C24C02,
+        )),
+        (69, _) => Box::new(FME7::new(cartridge.prg, cartridge.chr)),
+        (159, _) => Box::new(Bandai::new(
+            cartridge.prg,
+            cartridge.chr,
+            EepromKind::C24C01,
+        )),
+        (_, Some(board)) => Box::new(Multicart::new(
+            board,
+            cartridge.prg,
+            cartridge.chr,
+            cartridge.mirroring_type,
+        )),
+        // unsupported boards run as if they were NROM
+        (_, None) => Box::new(NROM::new(
+            cartridge.prg,
+            cartridge.chr,
+            cartridge.mirroring_type,
+        )),
+    };
+    Arc::new(AtomicRefCell::new(mapper))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BANK_8K: usize = 0x2000;
+
+    #[test]
+    fn test_banked_offset() {
+        // 64KB in 8KB banks, bank numbers past the end wrap around
+        assert_eq!(banked_offset(0x10000, BANK_8K, 3, 0x0010), 0x6010);
+        assert_eq!(banked_offset(0x10000, BANK_8K, 9, 0x2010), 0x2010);
+        assert_eq!(banked_offset(0x10000, BANK_8K, 7, 0x1FFF), 0xFFFF);
+
+        // 24KB is not a power of two, the missing bank mirrors the first one
+        assert_eq!(banked_offset(0x6000, BANK_8K, 2, 0x0001), 0x4001);
+        assert_eq!(banked_offset(0x6000, 0x4000, 1, 0x3000), 0x1000);
+
+        // smaller than one bank
+        assert_eq!(banked_offset(0x800, BANK_8K, 1, 0x1801), 0x0001);
+    }
+}
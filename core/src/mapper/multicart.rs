@@ -0,0 +1,304 @@
+use super::{banked_offset, board_debug_state, ChrBanks, Mapper, CHR_WINDOWS};
+use crate::cartridge::MirroringType;
+use crate::json::Value;
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/Category:Multicart_mappers
+
+    the common pirate multicarts are discrete boards with an address latch: a write
+    anywhere in $8000-$FFFF keeps the address bits (the data is ignored) and they pick
+    an outer prg bank in 16KB or 32KB mode and an 8KB chr bank. every game is an NROM
+    game sitting in its own banks, and the menu at power-on (bank 0) writes the latch
+    and jumps through the game's reset vector
+
+    058  A~[1... .... MOCC CPPP]  P prg 16KB bank, O prg mode, C chr bank, M mirroring
+    200  A~[1... .... .... MBBB]  B prg 16KB (mirrored) and chr bank, M mirroring
+    201  A~[.... .... BBBB BBBB]  B prg 32KB and chr bank, mirroring from the header
+    225  A~[.HMO PPPP PPCC CCCC]  H high bit of P and C, P prg 16KB bank, O prg mode,
+    255                           C chr bank, M mirroring
+
+    prg mode 0 maps the 32KB bank P >> 1, mode 1 the 16KB bank P at $8000 and $C000.
+    M 0 is vertical, 1 horizontal
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatchBoard {
+    Bmc58,
+    Bmc200,
+    Bmc201,
+    Bmc225,
+}
+
+impl LatchBoard {
+    pub fn from_mapper(mapper: u8) -> Option<Self> {
+        match mapper {
+            58 => Some(LatchBoard::Bmc58),
+            200 => Some(LatchBoard::Bmc200),
+            201 => Some(LatchBoard::Bmc201),
+            225 | 255 => Some(LatchBoard::Bmc225),
+            _ => None,
+        }
+    }
+
+    // boards without a 32KB mode only hold NROM-128 games
+    fn game_size(&self) -> usize {
+        match self {
+            LatchBoard::Bmc200 => PRG_BANK_SIZE,
+            _ => PRG_BANK_SIZE * 2,
+        }
+    }
+}
+
+pub struct Multicart {
+    board: LatchBoard,
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    // in 16KB banks, in 32KB mode the low bit is ignored
+    prg_bank: usize,
+    prg_32k: bool,
+    chr_bank: usize,
+    // the header's, for boards that cannot switch it
+    fixed_mirroring: MirroringType,
+    mirroring_type: MirroringType,
+}
+
+impl Multicart {
+    pub fn new(
+        board: LatchBoard,
+        prg: Vec<u8>,
+        chr: Vec<u8>,
+        mirroring_type: MirroringType,
+    ) -> Self {
+        let chr_is_ram = chr.is_empty();
+        let mut multicart = Multicart {
+            board: board,
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            prg_bank: 0,
+            prg_32k: true,
+            chr_bank: 0,
+            fixed_mirroring: mirroring_type,
+            mirroring_type: mirroring_type,
+        };
+        // the latch powers on cleared, which maps in the menu
+        multicart.latch(0x8000);
+        multicart
+    }
+
+    fn latch(&mut self, addr: u16) {
+        let addr = addr as usize;
+        let mirroring = |bit: usize| {
+            if addr & bit != 0 {
+                MirroringType::Horizontal
+            } else {
+                MirroringType::Vertical
+            }
+        };
+        match self.board {
+            LatchBoard::Bmc58 => {
+                self.prg_bank = addr & 0b111;
+                self.prg_32k = addr & 0b0100_0000 == 0;
+                self.chr_bank = (addr >> 3) & 0b111;
+                self.mirroring_type = mirroring(0b1000_0000);
+            }
+            LatchBoard::Bmc200 => {
+                self.prg_bank = addr & 0b111;
+                self.prg_32k = false;
+                self.chr_bank = addr & 0b111;
+                self.mirroring_type = mirroring(0b1000);
+            }
+            LatchBoard::Bmc201 => {
+                self.prg_bank = (addr & 0xFF) << 1;
+                self.prg_32k = true;
+                self.chr_bank = addr & 0xFF;
+                self.mirroring_type = self.fixed_mirroring;
+            }
+            LatchBoard::Bmc225 => {
+                let high = (addr >> 14) & 1;
+                self.prg_bank = high << 6 | (addr >> 6) & 0b11_1111;
+                self.prg_32k = addr & 0x1000 == 0;
+                self.chr_bank = high << 6 | addr & 0b11_1111;
+                self.mirroring_type = mirroring(0x2000);
+            }
+        }
+    }
+}
+
+impl Mapper for Multicart {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let offset = if self.prg_32k {
+            banked_offset(
+                self.prg.len(),
+                PRG_BANK_SIZE * 2,
+                self.prg_bank >> 1,
+                (addr - 0x8000) as usize,
+            )
+        } else {
+            banked_offset(
+                self.prg.len(),
+                PRG_BANK_SIZE,
+                self.prg_bank,
+                (addr - 0x8000) as usize,
+            )
+        };
+        self.prg[offset]
+    }
+
+    fn write_prg(&mut self, addr: u16, _data: u8) {
+        self.latch(addr);
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[banked_offset(self.chr.len(), CHR_BANK_SIZE, self.chr_bank, addr as usize)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            self.chr[addr as usize % CHR_RAM_SIZE] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_banks(&self) -> ChrBanks {
+        let mut banks = [0; CHR_WINDOWS];
+        for (window, bank) in banks.iter_mut().enumerate() {
+            *bank = self.chr_bank * CHR_WINDOWS + window;
+        }
+        banks
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring_type
+    }
+
+    fn games(&self) -> usize {
+        (self.prg.len() / self.board.game_size()).max(1)
+    }
+
+    // what the menu would latch for the game, apart from the mirroring which only the
+    // menu knows, so it stays as it is
+    fn select_game(&mut self, game: usize) {
+        let game = game % self.games();
+        match self.board.game_size() {
+            PRG_BANK_SIZE => {
+                self.prg_bank = game;
+                self.prg_32k = false;
+            }
+            _ => {
+                self.prg_bank = game << 1;
+                self.prg_32k = true;
+            }
+        }
+        self.chr_bank = game;
+    }
+
+    fn debug_state(&self) -> Value {
+        board_debug_state(
+            self,
+            vec![
+                ("board", Value::from(format!("{:?}", self.board))),
+                ("prg_bank", Value::from(self.prg_bank)),
+                ("prg_32k", Value::from(self.prg_32k)),
+                ("chr_bank", Value::from(self.chr_bank)),
+            ],
+        )
+    }
+
+    // prg bank, prg mode, chr bank, mirroring, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![
+            self.prg_bank as u8,
+            self.prg_32k as u8,
+            self.chr_bank as u8,
+            (self.mirroring_type == MirroringType::Horizontal) as u8,
+        ];
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.prg_bank = state[0] as usize;
+        self.prg_32k = state[1] != 0;
+        self.chr_bank = state[2] as usize;
+        self.mirroring_type = if state[3] != 0 {
+            MirroringType::Horizontal
+        } else {
+            MirroringType::Vertical
+        };
+        if self.board == LatchBoard::Bmc201 {
+            self.mirroring_type = self.fixed_mirroring;
+        }
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(&state[4..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // every 16KB prg bank and 8KB chr bank starts with its number
+    fn numbered(prg_banks: usize, chr_banks: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut prg = vec![0; PRG_BANK_SIZE * prg_banks];
+        for bank in 0..prg_banks {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut chr = vec![0; CHR_BANK_SIZE * chr_banks];
+        for bank in 0..chr_banks {
+            chr[bank * CHR_BANK_SIZE] = bank as u8;
+        }
+        (prg, chr)
+    }
+
+    #[test]
+    fn test_address_latch() {
+        let (prg, chr) = numbered(16, 8);
+        let mut cart = Multicart::new(LatchBoard::Bmc225, prg, chr, MirroringType::Vertical);
+        assert_eq!((cart.read_prg(0x8000), cart.read_prg(0xC000)), (0, 1));
+        assert_eq!(cart.games(), 8);
+
+        // 16KB mode, prg bank 5 at both halves, chr bank 3, horizontal
+        cart.write_prg(0x8000 | 0x2000 | 0x1000 | 5 << 6 | 3, 0xFF);
+        assert_eq!((cart.read_prg(0x8000), cart.read_prg(0xC000)), (5, 5));
+        assert_eq!(cart.read_chr(0x0000), 3);
+        assert_eq!(cart.chr_banks()[0], 3 * CHR_WINDOWS);
+        assert_eq!(cart.mirroring(), MirroringType::Horizontal);
+
+        // 32KB mode ignores the low prg bit
+        cart.write_prg(0x8000 | 5 << 6, 0);
+        assert_eq!((cart.read_prg(0x8000), cart.read_prg(0xC000)), (4, 5));
+        assert_eq!(cart.mirroring(), MirroringType::Vertical);
+
+        let state = cart.save_state();
+        cart.select_game(6);
+        assert_eq!((cart.read_prg(0x8000), cart.read_prg(0xC000)), (12, 13));
+        assert_eq!(cart.read_chr(0x0000), 6);
+        cart.load_state(&state);
+        assert_eq!(cart.read_prg(0x8000), 4);
+
+        // NROM-128 games on 200, one per 16KB
+        let (prg, chr) = numbered(4, 4);
+        let mut cart = Multicart::new(LatchBoard::Bmc200, prg, chr, MirroringType::Vertical);
+        assert_eq!(cart.games(), 4);
+        cart.select_game(2);
+        assert_eq!((cart.read_prg(0x8000), cart.read_prg(0xC000)), (2, 2));
+        assert_eq!(cart.read_chr(0x0000), 2);
+    }
+}
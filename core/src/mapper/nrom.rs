@@ -0,0 +1,132 @@
+use super::{banked_offset, Mapper};
+use crate::cartridge::MirroringType;
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/NROM
+    CPU $8000-$BFFF: First 16 KB of ROM.
+    CPU $C000-$FFFF: Last 16 KB of ROM (NROM-256) or mirror of $8000-$BFFF (NROM-128).
+    PPU $0000-$1FFF: 8 KB of CHR ROM, or CHR RAM when the header has no CHR banks.
+
+    boards we don't support fall back to NROM. like most mappers at power-on they get the
+    first 16 KB at $8000 and the last 16 KB at $C000, which keeps the reset vector intact.
+*/
+pub struct NROM {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    // soldered on the board
+    mirroring_type: MirroringType,
+}
+
+impl NROM {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>, mirroring_type: MirroringType) -> Self {
+        let chr_is_ram = chr.is_empty();
+        NROM {
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            mirroring_type: mirroring_type,
+        }
+    }
+}
+
+impl Mapper for NROM {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let addr = (addr - 0x8000) as usize;
+        let bank = if addr < PRG_BANK_SIZE {
+            0
+        } else {
+            // NROM-128 mirrors, NROM-256 and oversized roms map their last bank
+            self.prg.len().div_ceil(PRG_BANK_SIZE) - 1
+        };
+        self.prg[banked_offset(self.prg.len(), PRG_BANK_SIZE, bank, addr)]
+    }
+
+    fn write_prg(&mut self, _addr: u16, _data: u8) {
+        // NROM has no registers, writes to rom go nowhere
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            self.chr[addr as usize] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring_type
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        if self.chr_is_ram {
+            self.chr.clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prg_mirror() {
+        let mut prg = vec![0; 0x4000];
+        prg[0x3FFC] = 0x42;
+        let nrom = NROM::new(prg, vec![0; CHR_RAM_SIZE], MirroringType::Horizontal);
+        assert_eq!(nrom.read_prg(0xBFFC), 0x42);
+        assert_eq!(nrom.read_prg(0xFFFC), 0x42);
+    }
+
+    #[test]
+    fn test_odd_prg_sizes() {
+        let prg: Vec<u8> = (0..0x2000).map(|i| (i >> 8) as u8).collect();
+        let small = NROM::new(prg, vec![0; CHR_RAM_SIZE], MirroringType::Horizontal);
+        assert_eq!(small.read_prg(0x8100), 0x01);
+        assert_eq!(small.read_prg(0xA100), 0x01);
+        assert_eq!(small.read_prg(0xFFFF), 0x1F);
+
+        let mut prg = vec![0; 0x20000];
+        prg[0x1FFFC] = 0x42;
+        let oversized = NROM::new(prg, vec![0; CHR_RAM_SIZE], MirroringType::Horizontal);
+        assert_eq!(oversized.read_prg(0xFFFC), 0x42);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut rom = NROM::new(
+            vec![0; 0x4000],
+            vec![0x11; CHR_RAM_SIZE],
+            MirroringType::Horizontal,
+        );
+        rom.write_chr(0x0010, 0x22);
+        assert_eq!(rom.read_chr(0x0010), 0x11);
+
+        let mut ram = NROM::new(vec![0; 0x4000], Vec::new(), MirroringType::Horizontal);
+        ram.write_chr(0x1FFF, 0x22);
+        assert_eq!(ram.read_chr(0x1FFF), 0x22);
+    }
+}
@@ -0,0 +1,449 @@
+use super::{banked_offset, board_debug_state, ChrBanks, Mapper, CHR_WINDOWS, CHR_WINDOW_SIZE};
+use crate::cartridge::MirroringType;
+use crate::json::{self, Value};
+
+use alloc::vec::Vec;
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 8192;
+
+/*
+https://wiki.nesdev.com/w/index.php/VRC2_and_VRC4
+
+    $8000  ...B BBBB  8KB prg bank at $8000 ($C000 in swap mode)
+    $9000  .... ..MM  mirroring: vertical, horizontal, one screen lower, upper (VRC2
+                      only has the low bit)
+    $9002  .... ..S.  VRC4 prg swap mode: $8000 and $C000 trade places
+    $A000  ...B BBBB  8KB prg bank at $A000
+    $B000-$E003       1KB chr banks, two per $x000 page: low nibble at +0 (+2), high
+                      bits at +1 (+3)
+    $F000-$F003       VRC4 irq latch low and high nibble, control, acknowledge
+
+    $E000 is always the last bank, $C000 ($8000 in swap mode) the one before it.
+    the boards wire the register select lines to different cpu address lines, and the
+    ines mapper numbers mix several wirings:
+
+    21  VRC4a A1 A2, VRC4c A6 A7
+    22  VRC2a A1 A0, chr banks in 2KB steps
+    23  VRC2b A0 A1, VRC4e A2 A3
+    25  VRC4b A1 A0, VRC4d A3 A2, VRC2c A1 A0
+
+    without a submapper both wirings of a number are decoded at once, games only ever
+    toggle the lines of their own
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VrcWiring {
+    Mapper21,
+    Mapper22,
+    Mapper23,
+    Mapper25,
+}
+
+impl VrcWiring {
+    pub fn from_mapper(mapper: u8) -> Option<Self> {
+        match mapper {
+            21 => Some(VrcWiring::Mapper21),
+            22 => Some(VrcWiring::Mapper22),
+            23 => Some(VrcWiring::Mapper23),
+            25 => Some(VrcWiring::Mapper25),
+            _ => None,
+        }
+    }
+
+    // the register (0-3) inside a $x000 page
+    fn register(&self, addr: u16) -> usize {
+        let bit = |line: u16| (addr >> line) as usize & 1;
+        let (a0, a1) = match self {
+            VrcWiring::Mapper21 => (bit(1) | bit(6), bit(2) | bit(7)),
+            VrcWiring::Mapper22 => (bit(1), bit(0)),
+            VrcWiring::Mapper23 => (bit(0) | bit(2), bit(1) | bit(3)),
+            VrcWiring::Mapper25 => (bit(1) | bit(3), bit(0) | bit(2)),
+        };
+        a1 << 1 | a0
+    }
+}
+
+/*
+https://wiki.nesdev.com/w/index.php/VRC_IRQ
+
+    $F000  .... LLLL  latch low nibble
+    $F001  .... LLLL  latch high nibble
+    $F002  .... .MEA  M counts cpu cycles (1) or scanlines (0), E enables, A is the
+                      enable after an acknowledge. writing it reloads the counter
+    $F003             acknowledge, copies A to E
+
+    the 8 bit counter counts up and raises the irq when it overflows, reloading from
+    the latch. in scanline mode a prescaler clocks it every 341 / 3 cpu cycles
+*/
+const IRQ_ENABLE_AFTER_ACK: u8 = 0b001;
+const IRQ_ENABLE: u8 = 0b010;
+const IRQ_CYCLE_MODE: u8 = 0b100;
+const PRESCALER_PERIOD: i16 = 341;
+
+// shared by Konami's VRC4, VRC6 and VRC7
+#[derive(Clone)]
+pub struct VrcIrq {
+    latch: u8,
+    control: u8,
+    counter: u8,
+    prescaler: i16,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn new() -> Self {
+        VrcIrq {
+            latch: 0,
+            control: 0,
+            counter: 0,
+            prescaler: PRESCALER_PERIOD,
+            pending: false,
+        }
+    }
+
+    pub fn write_latch_low(&mut self, data: u8) {
+        self.latch = self.latch & 0xF0 | data & 0x0F;
+    }
+
+    pub fn write_latch_high(&mut self, data: u8) {
+        self.latch = self.latch & 0x0F | (data & 0x0F) << 4;
+    }
+
+    pub fn write_latch(&mut self, data: u8) {
+        self.latch = data;
+    }
+
+    pub fn write_control(&mut self, data: u8) {
+        self.control = data & 0b111;
+        self.pending = false;
+        if self.control & IRQ_ENABLE != 0 {
+            self.counter = self.latch;
+            self.prescaler = PRESCALER_PERIOD;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        if self.control & IRQ_ENABLE_AFTER_ACK != 0 {
+            self.control |= IRQ_ENABLE;
+        } else {
+            self.control &= !IRQ_ENABLE;
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        if self.control & IRQ_ENABLE == 0 {
+            return;
+        }
+        for _ in 0..cycles {
+            if self.control & IRQ_CYCLE_MODE != 0 {
+                self.clock();
+            } else {
+                self.prescaler -= 3;
+                if self.prescaler <= 0 {
+                    self.prescaler += PRESCALER_PERIOD;
+                    self.clock();
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn debug_state(&self) -> Value {
+        json::object(vec![
+            ("latch", Value::from(self.latch)),
+            ("control", Value::from(self.control)),
+            ("counter", Value::from(self.counter)),
+            ("prescaler", Value::Number(self.prescaler as f64)),
+            ("pending", Value::from(self.pending)),
+        ])
+    }
+
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[self.latch, self.control, self.counter]);
+        state.extend_from_slice(&self.prescaler.to_le_bytes());
+        state.push(self.pending as u8);
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.latch = state[0];
+        self.control = state[1];
+        self.counter = state[2];
+        self.prescaler = i16::from_le_bytes([state[3], state[4]]);
+        self.pending = state[5] != 0;
+        &state[6..]
+    }
+}
+
+impl Default for VrcIrq {
+    fn default() -> Self {
+        VrcIrq::new()
+    }
+}
+
+pub struct VRC4 {
+    wiring: VrcWiring,
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; CHR_WINDOWS],
+    mirroring: u8,
+    irq: VrcIrq,
+}
+
+impl VRC4 {
+    pub fn new(wiring: VrcWiring, prg: Vec<u8>, chr: Vec<u8>) -> Self {
+        let chr_is_ram = chr.is_empty();
+        VRC4 {
+            wiring: wiring,
+            prg: prg,
+            chr: if chr_is_ram {
+                vec![0; CHR_RAM_SIZE]
+            } else {
+                chr
+            },
+            chr_is_ram: chr_is_ram,
+            prg_banks: [0; 2],
+            prg_swap: false,
+            chr_banks: [0; CHR_WINDOWS],
+            mirroring: 0,
+            irq: VrcIrq::new(),
+        }
+    }
+
+    fn chr_bank(&self, window: usize) -> usize {
+        match self.wiring {
+            // VRC2a leaves out the lowest chr address line
+            VrcWiring::Mapper22 => (self.chr_banks[window] >> 1) as usize,
+            _ => self.chr_banks[window] as usize,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_bank(addr as usize / CHR_WINDOW_SIZE);
+        banked_offset(self.chr.len(), CHR_WINDOW_SIZE, bank, addr as usize)
+    }
+}
+
+impl Mapper for VRC4 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        // a rom of a single bank has no second to last one, banked_offset mirrors it
+        let second_last = (self.prg.len() / PRG_BANK_SIZE).saturating_sub(2);
+        let bank = match (addr, self.prg_swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.prg_banks[0] as usize,
+            (0xA000..=0xBFFF, _) => self.prg_banks[1] as usize,
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => second_last,
+            _ => second_last + 1,
+        };
+        self.prg[banked_offset(self.prg.len(), PRG_BANK_SIZE, bank, addr as usize)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        let register = self.wiring.register(addr);
+        match (addr & 0xF000, register) {
+            (0x8000, _) => self.prg_banks[0] = data & 0x1F,
+            // VRC2 mirrors its one bit over the whole page
+            (0x9000, _) if self.wiring == VrcWiring::Mapper22 => self.mirroring = data & 0b1,
+            (0x9000, 0) | (0x9000, 1) => self.mirroring = data & 0b11,
+            (0x9000, _) => self.prg_swap = data & 0b10 != 0,
+            (0xA000, _) => self.prg_banks[1] = data & 0x1F,
+            (0xB000..=0xE000, _) => {
+                let window = ((addr & 0xF000) - 0xB000) as usize / 0x1000 * 2 + register / 2;
+                let bank = self.chr_banks[window];
+                self.chr_banks[window] = if register % 2 == 0 {
+                    bank & 0x1F0 | data as u16 & 0x0F
+                } else {
+                    bank & 0x00F | (data as u16 & 0x1F) << 4
+                };
+            }
+            (0xF000, 0) => self.irq.write_latch_low(data),
+            (0xF000, 1) => self.irq.write_latch_high(data),
+            (0xF000, 2) => self.irq.write_control(data),
+            (0xF000, _) => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_banks(&self) -> ChrBanks {
+        let mut banks = [0; CHR_WINDOWS];
+        for (window, bank) in banks.iter_mut().enumerate() {
+            *bank = self.chr_bank(window);
+        }
+        banks
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.mirroring {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            2 => MirroringType::SingleScreenLower,
+            _ => MirroringType::SingleScreenUpper,
+        }
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.irq.tick(cycles);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    // chr_banks are the 1KB pages shown, chr_registers what the game wrote
+    fn debug_state(&self) -> Value {
+        board_debug_state(
+            self,
+            vec![
+                ("wiring", Value::from(format!("{:?}", self.wiring))),
+                ("prg_banks", Value::from(&self.prg_banks[..])),
+                ("prg_swap", Value::from(self.prg_swap)),
+                ("chr_registers", Value::from(&self.chr_banks[..])),
+                ("irq", self.irq.debug_state()),
+            ],
+        )
+    }
+
+    // prg banks, swap mode, chr banks, mirroring, irq, then chr ram
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = self.prg_banks.to_vec();
+        state.push(self.prg_swap as u8);
+        for bank in self.chr_banks.iter() {
+            state.extend_from_slice(&bank.to_le_bytes());
+        }
+        state.push(self.mirroring);
+        self.irq.save_state(&mut state);
+        if self.chr_is_ram {
+            state.extend_from_slice(&self.chr);
+        }
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        self.prg_banks.copy_from_slice(&state[0..2]);
+        self.prg_swap = state[2] != 0;
+        for (window, bank) in self.chr_banks.iter_mut().enumerate() {
+            *bank = u16::from_le_bytes([state[3 + window * 2], state[4 + window * 2]]);
+        }
+        self.mirroring = state[19];
+        let chr = self.irq.load_state(&state[20..]);
+        if self.chr_is_ram {
+            self.chr.copy_from_slice(chr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registers_and_irq() {
+        // every 8KB prg bank and 1KB chr bank starts with its number
+        let mut prg = vec![0; PRG_BANK_SIZE * 16];
+        for bank in 0..16 {
+            prg[bank * PRG_BANK_SIZE] = bank as u8;
+        }
+        let mut chr = vec![0; CHR_WINDOW_SIZE * 64];
+        for bank in 0..64 {
+            chr[bank * CHR_WINDOW_SIZE] = bank as u8;
+        }
+
+        // VRC4e, registers on A2 and A3
+        let mut vrc = VRC4::new(VrcWiring::Mapper23, prg.clone(), chr.clone());
+        vrc.write_prg(0x8000, 3);
+        vrc.write_prg(0xA000, 4);
+        assert_eq!(vrc.read_prg(0x8000), 3);
+        assert_eq!(vrc.read_prg(0xA000), 4);
+        assert_eq!((vrc.read_prg(0xC000), vrc.read_prg(0xE000)), (14, 15));
+        vrc.write_prg(0x9008, 0b10);
+        assert_eq!((vrc.read_prg(0x8000), vrc.read_prg(0xC000)), (14, 3));
+
+        // the second bank of the $C000 page is window 3, written as two nibbles
+        vrc.write_prg(0xC008, 0x0A);
+        vrc.write_prg(0xC00C, 0x02);
+        assert_eq!(vrc.chr_banks()[3], 0x2A);
+        assert_eq!(vrc.read_chr(0x0C00), 0x2A);
+
+        vrc.write_prg(0x9000, 1);
+        assert_eq!(vrc.mirroring(), MirroringType::Horizontal);
+
+        // cycle mode from $FE: one cycle to $FF, the next overflows
+        vrc.write_prg(0xF000, 0xE);
+        vrc.write_prg(0xF004, 0xF);
+        vrc.write_prg(0xF008, IRQ_CYCLE_MODE | IRQ_ENABLE);
+        vrc.tick(1);
+        assert!(!vrc.irq_pending());
+        let state = vrc.save_state();
+        vrc.tick(1);
+        assert!(vrc.irq_pending());
+        vrc.load_state(&state);
+        assert!(!vrc.irq_pending());
+        vrc.tick(1);
+        vrc.write_prg(0xF00C, 0);
+        assert!(!vrc.irq_pending());
+
+        // VRC2a drops the low bit of the chr bank
+        let mut vrc = VRC4::new(VrcWiring::Mapper22, prg, chr);
+        vrc.write_prg(0xB000, 0x06);
+        assert_eq!(vrc.read_chr(0x0000), 3);
+    }
+
+    #[test]
+    fn test_one_prg_bank() {
+        let mut prg = vec![0; PRG_BANK_SIZE];
+        prg[0] = 0x42;
+        let mut vrc = VRC4::new(VrcWiring::Mapper23, prg, Vec::new());
+        vrc.write_prg(0x8000, 5);
+        for addr in [0x8000, 0xA000, 0xC000, 0xE000].iter() {
+            assert_eq!(vrc.read_prg(*addr), 0x42);
+        }
+        vrc.write_prg(0x9008, 0b10);
+        assert_eq!(vrc.read_prg(0x8000), 0x42);
+    }
+
+    #[test]
+    fn test_scanline_prescaler() {
+        let mut irq = VrcIrq::new();
+        irq.write_latch(0xFE);
+        irq.write_control(IRQ_ENABLE);
+        // two scanlines of 341 / 3 cpu cycles
+        for _ in 0..113 {
+            irq.tick(1);
+        }
+        assert!(!irq.pending());
+        for _ in 0..115 {
+            irq.tick(1);
+        }
+        assert!(irq.pending());
+    }
+}
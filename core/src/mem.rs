@@ -0,0 +1,28 @@
+pub trait Memory {
+    // reads may have side effects (ppu/apu/joypad registers)
+    fn mem_read(&mut self, addr: u16) -> u8;
+    fn mem_write(&mut self, addr: u16, data: u8);
+
+    // what a read would return right now, without touching any state (for debuggers/tracing)
+    fn mem_peek(&self, addr: u16) -> u8;
+
+    fn mem_peek_u16(&self, addr: u16) -> u16 {
+        let lo = self.mem_peek(addr) as u16;
+        let hi = self.mem_peek(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    // little-endian
+    fn mem_read_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.mem_read(addr) as u16;
+        let hi = self.mem_read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | (lo as u16)
+    }
+
+    fn mem_write_u16(&mut self, addr: u16, data: u16) {
+        let hi = (data >> 8) as u8;
+        let lo = (data & 0xFF) as u8;
+        self.mem_write(addr, lo);
+        self.mem_write(addr.wrapping_add(1), hi);
+    }
+}
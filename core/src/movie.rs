@@ -0,0 +1,170 @@
+/*
+http://fceux.com/web/help/fm2.html
+
+    input movies in the subset of fceux's fm2 format that this emulator can replay:
+    header lines are `key value` and ignored, every frame is one line
+
+        |commands|RLDUTSBA|RLDUTSBA|
+
+    commands is a bitfield, 1 soft resets before the frame and 2 power-cycles (only allowed
+    on the first frame, where the movie starts from power-on anyway). a '.' or a
+    space in a button column is released, anything else pressed. a missing second
+    controller column means nothing is plugged in there
+*/
+
+use crate::joypad::{JoypadButton, Port};
+use crate::nes::Nes;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const COMMAND_SOFT_RESET: u8 = 0b01;
+const COMMAND_POWER: u8 = 0b10;
+
+// fm2 column order, leftmost character first
+const BUTTON_COLUMNS: [JoypadButton; 8] = [
+    JoypadButton::RIGHT,
+    JoypadButton::LEFT,
+    JoypadButton::DOWN,
+    JoypadButton::UP,
+    JoypadButton::START,
+    JoypadButton::SELECT,
+    JoypadButton::BUTTON_B,
+    JoypadButton::BUTTON_A,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovieFrame {
+    pub reset: bool,
+    pub joypad1: JoypadButton,
+    pub joypad2: JoypadButton,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub frames: Vec<MovieFrame>,
+}
+
+pub(crate) fn parse_buttons(column: &str) -> Result<JoypadButton, String> {
+    if column.is_empty() {
+        return Ok(JoypadButton::empty());
+    }
+    if column.chars().count() != BUTTON_COLUMNS.len() {
+        return Err(format!("expected 8 buttons, got {:?}", column));
+    }
+
+    let mut buttons = JoypadButton::empty();
+    for (c, button) in column.chars().zip(BUTTON_COLUMNS.iter()) {
+        if c != '.' && c != ' ' {
+            buttons.insert(*button);
+        }
+    }
+    Ok(buttons)
+}
+
+pub(crate) fn format_buttons(buttons: JoypadButton) -> String {
+    "RLDUTSBA"
+        .chars()
+        .zip(BUTTON_COLUMNS.iter())
+        .map(|(c, button)| if buttons.contains(*button) { c } else { '.' })
+        .collect()
+}
+
+impl Movie {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut frames = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if !line.starts_with('|') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split('|').collect();
+            if columns.len() < 4 {
+                return Err(format!("line {}: missing columns", number + 1));
+            }
+            let commands: u8 = columns[1]
+                .trim()
+                .parse()
+                .map_err(|_| format!("line {}: bad commands {:?}", number + 1, columns[1]))?;
+            if commands & COMMAND_POWER != 0 && !frames.is_empty() {
+                return Err(format!(
+                    "line {}: power cycling is not supported",
+                    number + 1
+                ));
+            }
+            let joypad1 =
+                parse_buttons(columns[2]).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            let joypad2 =
+                parse_buttons(columns[3]).map_err(|e| format!("line {}: {}", number + 1, e))?;
+
+            frames.push(MovieFrame {
+                reset: commands & COMMAND_SOFT_RESET != 0,
+                joypad1: joypad1,
+                joypad2: joypad2,
+            });
+        }
+        Ok(Movie { frames: frames })
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut text = String::from("version 3\n");
+        for frame in self.frames.iter() {
+            let commands = if frame.reset { COMMAND_SOFT_RESET } else { 0 };
+            text += &format!(
+                "|{}|{}|{}||\n",
+                commands,
+                format_buttons(frame.joypad1),
+                format_buttons(frame.joypad2)
+            );
+        }
+        text
+    }
+
+    // resets the console and runs one frame per movie line with its buttons held
+    pub fn play(&self, nes: &mut Nes) {
+        nes.reset();
+        let start = nes.stats().frames;
+        for (i, frame) in self.frames.iter().enumerate() {
+            nes.queue_input(start + i as u64, Port::One, frame.joypad1);
+            nes.queue_input(start + i as u64, Port::Two, frame.joypad2);
+        }
+        for frame in self.frames.iter() {
+            if frame.reset {
+                nes.reset();
+            }
+            nes.run_frame();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let movie =
+            Movie::parse("version 3\nromFilename test\n|0|....T...|........||\n|1|R......A|||\n")
+                .unwrap();
+        assert_eq!(
+            movie.frames,
+            vec![
+                MovieFrame {
+                    reset: false,
+                    joypad1: JoypadButton::START,
+                    joypad2: JoypadButton::empty(),
+                },
+                MovieFrame {
+                    reset: true,
+                    joypad1: JoypadButton::RIGHT | JoypadButton::BUTTON_A,
+                    joypad2: JoypadButton::empty(),
+                },
+            ]
+        );
+        assert_eq!(Movie::parse(&movie.to_fm2()).unwrap(), movie);
+
+        assert!(Movie::parse("|0|RLDU|||\n").is_err());
+        assert!(Movie::parse("|x|........|||\n").is_err());
+    }
+}
@@ -0,0 +1,1441 @@
+use crate::address_decode::cpu_ram_mirror;
+use crate::assembler::{self, Overwritten};
+use crate::av_sync::{AvSync, AvTiming};
+use crate::bus::{Bus, PrgPatch};
+use crate::cartridge::Cartridge;
+use crate::config::{Accuracy, Config, Console, PowerOnCpu, PowerOnRam, PowerOnRng};
+use crate::cpu::hooks::Hooks;
+use crate::cpu::{CPUStatus, CpuState, CPU};
+use crate::events::{Event, EventLog};
+use crate::expansion::ExpansionDevice;
+use crate::input_macro::{InputMacro, Playback};
+use crate::joypad::{JoypadButton, Port};
+use crate::json::Value;
+use crate::lint::Lint;
+use crate::mapper::ChrBanks;
+use crate::mem::Memory;
+use crate::movie::{Movie, MovieFrame};
+use crate::ppu::frame_stats::FrameStats;
+use crate::ppu::registers::status::PPUSTATUS;
+use crate::ppu::registers::BitwiseRegister;
+use crate::ram_lock::{LockTiming, RamLock};
+use crate::render::frame::{Frame, Rect, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::render::overlay::{text_width, Overlay, GLYPH_HEIGHT};
+use crate::render::palette;
+use crate::render::pattern_table::render_pattern_table;
+use crate::render::ppu_renderer::{self, Layer};
+use crate::repro::{ReproBundle, ReproCapture};
+use crate::savestate::{self, ChunkId};
+use crate::schedule::{Schedule, ScheduleId};
+use crate::state_import;
+use crate::trace::TraceLog;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// never produced by the ppu, so the first frame after power-on is converted in full
+const NO_PALETTE_INDEX: u8 = 0xFF;
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+const STATS_HUD_TEXT: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const STATS_HUD_BACKGROUND: [u8; 4] = [0x00, 0x00, 0x00, 0xA0];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub frames: u64,
+    pub cpu_cycles: u64,
+    pub instructions: u64,
+    pub scanline: u16,
+    pub dot: u16,
+    // samples handed to the audio callback with the last frame, about 735 at 60 fps
+    pub audio_samples_last_frame: usize,
+    // samples thrown away because no audio callback was set
+    pub audio_samples_dropped: u64,
+}
+
+impl Stats {
+    // the counters in the bottom left corner, for frontends without a ui of their own
+    pub fn draw_hud(&self, overlay: &mut Overlay) {
+        let text = format!(
+            "FRAME {}\nCYCLES {}\nINSTR {}\nLINE {} DOT {}\nAUDIO {} DROP {}",
+            self.frames,
+            self.cpu_cycles,
+            self.instructions,
+            self.scanline,
+            self.dot,
+            self.audio_samples_last_frame,
+            self.audio_samples_dropped
+        );
+        let width = text_width(&text);
+        let height = text.lines().count() * GLYPH_HEIGHT;
+        let y = SCREEN_HEIGHT - height - 2;
+        overlay.fill_rect(
+            Rect::new(1, y - 1, width + 2, height + 1),
+            STATS_HUD_BACKGROUND,
+        );
+        overlay.text(2, y, &text, STATS_HUD_TEXT);
+    }
+}
+
+// the console at a frame boundary, kept in memory to go back to. cheap enough to take
+// every frame, which is what rollback netplay does
+#[derive(Clone)]
+pub struct Snapshot {
+    cpu: CpuState,
+    stats: Stats,
+}
+
+// the frontend's callbacks, see set_frame_callback and friends
+type FrameCallback = Box<dyn FnMut(&Frame) + Send>;
+type AudioCallback = Box<dyn FnMut(&[f32]) + Send>;
+type InputProvider = Box<dyn FnMut(Port) -> JoypadButton + Send>;
+
+// Send, so every console can run on a thread of its own. callbacks have to be Send too.
+// consoles share nothing, any number of them can run side by side
+pub struct Nes {
+    pub cpu: CPU,
+    stats: Stats,
+    title: Option<String>,
+    // of the cartridge's prg and chr, savestates carry it
+    crc32: u32,
+    config: Config,
+    // fills a, x and y on the first reset, which is the power-on one
+    power_on_rng: Option<PowerOnRng>,
+
+    // system palette index per pixel, converted into `frame` once a frame is done
+    screen: Vec<u8>,
+    // what the last frame showed, only rows that differ from it are converted again
+    previous_screen: Vec<u8>,
+    // the emphasis each row of `frame` was converted with
+    previous_emphasis: Vec<u8>,
+    // what each pixel of `screen` shows, for the debug tint
+    layers: Vec<Layer>,
+    debug_tint: bool,
+    frame: Frame,
+    overlay: Overlay,
+    // rows the overlay drew on last frame, converted again even if the picture is the same
+    overlay_rows: Range<usize>,
+    // FrameStats::draw_hud and Stats::draw_hud over the user's overlay
+    frame_stats_hud: bool,
+    stats_hud: bool,
+    // see set_sprite_limit
+    sprite_limit: bool,
+    events: EventLog,
+    // buttons to press once `stats.frames` reaches the key, see queue_input
+    queued_input: BTreeMap<(u64, Port), JoypadButton>,
+    // see at_frame and every
+    schedule: Schedule,
+    // the buttons of a port every frame since start_macro_recording
+    macro_recording: Option<(Port, Vec<JoypadButton>)>,
+    macro_playback: Option<(Port, Playback)>,
+    recording: Option<Movie>,
+    // stats.frames when the recording started
+    recording_start: u64,
+    // a reset happened since the last recorded frame
+    reset_since_recorded_frame: bool,
+    // frames run again after a rollback are not shown or heard a second time
+    pub(crate) resimulating: bool,
+    // see lock_ram
+    ram_locks: Vec<RamLock>,
+    // what every patch_code overwrote, the last one at the end
+    code_patches: Vec<Vec<(u16, Overwritten)>>,
+    // set while the timing is AvTiming::Exact
+    av_sync: Option<AvSync>,
+    // the last instructions run, while tracing is on
+    trace_log: Option<TraceLog>,
+    lint: Option<Lint>,
+    // set up by capture_repro_on_desync, the bundle waits for take_repro_bundle
+    repro: Option<ReproCapture>,
+    repro_bundle: Option<ReproBundle>,
+
+    frame_callback: Option<FrameCallback>,
+    audio_callback: Option<AudioCallback>,
+    input_provider: Option<InputProvider>,
+}
+
+impl Nes {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Nes::with_config(cartridge, &Config::default())
+    }
+
+    pub fn with_config(cartridge: Cartridge, config: &Config) -> Self {
+        let title = cartridge.title.clone();
+        let crc32 = cartridge.crc32;
+        let loaded = rom_loaded(&cartridge);
+        let mut nes = Nes {
+            cpu: CPU::new(power_on_bus(cartridge, config)),
+            stats: Stats::default(),
+            title: title,
+            crc32: crc32,
+            config: config.clone(),
+            power_on_rng: power_on_rng(config),
+
+            screen: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous_screen: vec![NO_PALETTE_INDEX; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous_emphasis: vec![0; SCREEN_HEIGHT],
+            layers: vec![Layer::Backdrop; SCREEN_WIDTH * SCREEN_HEIGHT],
+            debug_tint: false,
+            frame: Frame::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            overlay: Overlay::new(),
+            overlay_rows: 0..0,
+            frame_stats_hud: false,
+            stats_hud: false,
+            sprite_limit: true,
+            events: EventLog::new(),
+            queued_input: BTreeMap::new(),
+            schedule: Schedule::new(),
+            macro_recording: None,
+            macro_playback: None,
+            recording: None,
+            recording_start: 0,
+            reset_since_recorded_frame: false,
+            resimulating: false,
+            ram_locks: Vec::new(),
+            code_patches: Vec::new(),
+            av_sync: None,
+            trace_log: None,
+            lint: None,
+            repro: None,
+            repro_bundle: None,
+
+            frame_callback: None,
+            audio_callback: None,
+            input_provider: None,
+        };
+        nes.events.push(loaded);
+        nes
+    }
+
+    // power-cycles the console with another cartridge. callbacks, cpu hooks and the
+    // debugger settings survive, so a frontend can switch games without tearing down
+    // its gl context or audio output. like a new Nes it starts running after reset()
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.title = cartridge.title.clone();
+        self.crc32 = cartridge.crc32;
+        self.events.push(rom_loaded(&cartridge));
+        let mut cpu = CPU::new(power_on_bus(cartridge, &self.config));
+        cpu.hooks = core::mem::replace(&mut self.cpu.hooks, Hooks::new());
+        cpu.break_on_stack_fault = self.cpu.break_on_stack_fault;
+        // peripherals stay plugged in when the cartridge is swapped
+        cpu.bus.plug_expansion(self.cpu.bus.plug_expansion(None));
+        self.cpu = cpu;
+
+        self.stats = Stats::default();
+        self.queued_input.clear();
+        self.schedule.clear();
+        self.macro_recording = None;
+        self.macro_playback = None;
+        self.recording = None;
+        self.repro = None;
+        self.ram_locks.clear();
+        self.code_patches.clear();
+        if self.av_sync.is_some() {
+            self.av_sync = Some(AvSync::new());
+        }
+        if let Some(trace_log) = self.trace_log.as_mut() {
+            trace_log.clear();
+        }
+        if self.lint.is_some() {
+            self.lint = Some(Lint::new());
+        }
+        self.power_on_rng = power_on_rng(&self.config);
+        self.previous_screen = vec![NO_PALETTE_INDEX; SCREEN_WIDTH * SCREEN_HEIGHT];
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.bus.reset();
+        self.cpu.reset();
+        self.reset_since_recorded_frame = true;
+        if let Some(mut rng) = self.power_on_rng.take() {
+            self.cpu.acc = rng.next_u8();
+            self.cpu.rx = rng.next_u8();
+            self.cpu.ry = rng.next_u8();
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cpu: self.cpu.save_state(),
+            stats: self.stats,
+        }
+    }
+
+    // only snapshots of this console with the current cartridge. input queued for frames
+    // that are now in the future again stays queued
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.cpu.load_state(&snapshot.cpu);
+        self.stats = snapshot.stats;
+        self.restored();
+    }
+
+    // a snapshot as bytes that outlive the console, see savestate
+    pub fn save_state(&self) -> Vec<u8> {
+        savestate::write(&self.state_chunks())
+    }
+
+    // only states of a console with the same cartridge. nothing changes when it does not fit
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let chunks = savestate::read(state)?;
+        savestate::check(&chunks, &self.state_chunks())?;
+
+        let cpu = chunks[1].1;
+        self.cpu.pc = u16::from_le_bytes([cpu[0], cpu[1]]);
+        self.cpu.sp = cpu[2];
+        self.cpu.acc = cpu[3];
+        self.cpu.rx = cpu[4];
+        self.cpu.ry = cpu[5];
+        self.cpu.status = CPUStatus::from_bits_truncate(cpu[6]);
+        self.cpu.bus.load_state_chunks(&chunks[2..8]);
+        self.events.push(Event::StateLoaded);
+
+        let stats = chunks[8].1;
+        let u64_at = |at: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&stats[at..at + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        self.stats = Stats {
+            frames: u64_at(0),
+            cpu_cycles: u64_at(8),
+            instructions: u64_at(16),
+            scanline: u16::from_le_bytes([stats[24], stats[25]]),
+            dot: u16::from_le_bytes([stats[26], stats[27]]),
+            audio_samples_last_frame: u64_at(28) as usize,
+            audio_samples_dropped: u64_at(36),
+        };
+        self.restored();
+        Ok(())
+    }
+
+    // a savestate of FCEUX, as far as it goes, see state_import. what was left out is
+    // reported as Event::StateImportSkipped
+    pub fn import_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let imported = state_import::import_fceux(state)?;
+        self.cpu.pc = imported.pc;
+        self.cpu.sp = imported.sp;
+        self.cpu.acc = imported.acc;
+        self.cpu.rx = imported.rx;
+        self.cpu.ry = imported.ry;
+        self.cpu.status = CPUStatus::from_bits_truncate(imported.status);
+        for (addr, data) in imported.ram.iter().enumerate() {
+            self.cpu.bus.write_ram(addr as u16, *data);
+        }
+        self.cpu.bus.load_prg_ram(&imported.prg_ram);
+
+        let ppu = self.cpu.bus.ppu_mut();
+        let len = imported.nametables.len().min(ppu.bus.vram.len());
+        ppu.bus.vram[..len].copy_from_slice(&imported.nametables[..len]);
+        if imported.palette.len() == ppu.bus.palette.len() {
+            ppu.bus.palette.copy_from_slice(&imported.palette);
+        }
+        if imported.oam.len() == ppu.oam.len() {
+            ppu.oam.copy_from_slice(&imported.oam);
+        }
+        let [ctrl, mask, status, oam_address] = imported.ppu_registers;
+        ppu.ctrl_register.update_bits(ctrl);
+        ppu.mask_register.update_bits(mask);
+        ppu.status_register = PPUSTATUS::from_bits_truncate(status);
+        ppu.oam_address_register.write_oam_address(oam_address);
+        ppu.set_scroll_state(
+            imported.vram_addr,
+            imported.temp_addr,
+            imported.fine_x,
+            imported.second_write,
+        );
+        ppu.set_read_buffer(imported.read_buffer);
+
+        for part in imported.skipped {
+            self.events.push(Event::StateImportSkipped { part: part });
+        }
+        self.events.push(Event::StateLoaded);
+        self.restored();
+        Ok(())
+    }
+
+    // the cartridge's crc32, cpu, the bus's chunks, then stats
+    fn state_chunks(&self) -> Vec<(ChunkId, Vec<u8>)> {
+        let mut cpu = self.cpu.pc.to_le_bytes().to_vec();
+        cpu.extend_from_slice(&[
+            self.cpu.sp,
+            self.cpu.acc,
+            self.cpu.rx,
+            self.cpu.ry,
+            self.cpu.status.bits(),
+        ]);
+        let mut stats = Vec::new();
+        stats.extend_from_slice(&self.stats.frames.to_le_bytes());
+        stats.extend_from_slice(&self.stats.cpu_cycles.to_le_bytes());
+        stats.extend_from_slice(&self.stats.instructions.to_le_bytes());
+        stats.extend_from_slice(&self.stats.scanline.to_le_bytes());
+        stats.extend_from_slice(&self.stats.dot.to_le_bytes());
+        stats.extend_from_slice(&(self.stats.audio_samples_last_frame as u64).to_le_bytes());
+        stats.extend_from_slice(&self.stats.audio_samples_dropped.to_le_bytes());
+
+        let mut chunks = vec![
+            (savestate::CHUNK_CART, self.crc32.to_le_bytes().to_vec()),
+            (savestate::CHUNK_CPU, cpu),
+        ];
+        chunks.extend(self.cpu.bus.state_chunks());
+        chunks.push((savestate::CHUNK_STATS, stats));
+        chunks
+    }
+
+    // after restore or load_state
+    fn restored(&mut self) {
+        // the frames after the snapshot are recorded again as they are run again
+        if let Some(movie) = self.recording.as_mut() {
+            let kept = self.stats.frames.saturating_sub(self.recording_start);
+            movie.frames.truncate(kept as usize);
+        }
+        if let Some(repro) = self.repro.as_mut() {
+            repro.restored(self.stats.frames);
+        }
+    }
+
+    // presses `buttons` on `port` from the given frame on (counted like stats().frames, so
+    // frame 0 is the one after power-on) until another input replaces them. frames that
+    // already started take the buttons right away
+    pub fn queue_input(&mut self, frame: u64, port: Port, buttons: JoypadButton) {
+        if frame <= self.stats.frames {
+            self.cpu.bus.joypad(port).set_buttons(buttons);
+        } else {
+            self.queued_input.insert((frame, port), buttons);
+        }
+    }
+
+    fn apply_queued_input(&mut self) {
+        let due = self.stats.frames;
+        while let Some((&(frame, port), &buttons)) = self.queued_input.iter().next() {
+            if frame > due {
+                break;
+            }
+            self.queued_input.remove(&(frame, port));
+            self.cpu.bus.joypad(port).set_buttons(buttons);
+        }
+    }
+
+    // calls `callback` once, as the given frame starts (counted like stats().frames),
+    // after its queued input was applied. for a frame that already started it runs as the
+    // next one starts. a rollback to before the frame does not run it again
+    pub fn at_frame<F>(&mut self, frame: u64, callback: F) -> ScheduleId
+    where
+        F: FnMut(&mut Nes) + Send + 'static,
+    {
+        self.schedule.at(frame, Box::new(callback))
+    }
+
+    // calls `callback` as every `frames`th frame from now on starts, until cancelled
+    pub fn every<F>(&mut self, frames: u64, callback: F) -> ScheduleId
+    where
+        F: FnMut(&mut Nes) + Send + 'static,
+    {
+        self.schedule
+            .every(self.stats.frames, frames, Box::new(callback))
+    }
+
+    // also from inside a scheduled callback, including its own
+    pub fn cancel(&mut self, id: ScheduleId) {
+        self.schedule.cancel(id);
+    }
+
+    fn run_schedule(&mut self) {
+        let mut due = self.schedule.take_due(self.stats.frames);
+        for task in due.iter_mut() {
+            task.run(self);
+        }
+        self.schedule.put_back(due);
+    }
+
+    // records what `port` holds every frame from now on, for a macro
+    pub fn start_macro_recording(&mut self, port: Port) {
+        self.macro_recording = Some((port, Vec::new()));
+    }
+
+    // the recording without the idle frames before the first and after the last press
+    pub fn stop_macro_recording(&mut self) -> Option<InputMacro> {
+        self.macro_recording
+            .take()
+            .map(|(_, frames)| InputMacro::trimmed(&frames))
+    }
+
+    pub fn macro_recording(&self) -> bool {
+        self.macro_recording.is_some()
+    }
+
+    // holds one entry of `input_macro` on `port` per frame from the next frame on, over
+    // whatever the input provider or queue_input set, then releases every button.
+    // replaces a macro that is still playing
+    pub fn play_macro(&mut self, port: Port, input_macro: InputMacro) {
+        let start = self.stats.frames + 1;
+        self.macro_playback = Some((port, Playback::new(input_macro, start)));
+    }
+
+    pub fn macro_playing(&self) -> bool {
+        self.macro_playback.is_some()
+    }
+
+    fn apply_macro(&mut self) {
+        let frame = self.stats.frames;
+        let (port, buttons) = match self.macro_playback.as_ref() {
+            Some((port, playback)) if playback.started(frame) => (*port, playback.buttons(frame)),
+            _ => return,
+        };
+        if buttons.is_none() {
+            self.macro_playback = None;
+        }
+        self.cpu
+            .bus
+            .joypad(port)
+            .set_buttons(buttons.unwrap_or_else(JoypadButton::empty));
+    }
+
+    // freezes cpu ram ($0000-$1FFF) or prg ram ($6000-$7FFF) at `lock.value`, see
+    // ram_lock. replaces the lock on the same address, mirrors count as the same
+    pub fn lock_ram(&mut self, mut lock: RamLock) -> Result<(), String> {
+        lock.addr = match lock.addr {
+            0x0000..=0x1FFF => cpu_ram_mirror(lock.addr) as u16,
+            0x6000..=0x7FFF => lock.addr,
+            _ => return Err(format!("${:04X} is not ram", lock.addr)),
+        };
+        self.unlock_ram(lock.addr);
+        self.ram_locks.push(lock);
+        self.cpu.bus.write_ram(lock.addr, lock.value);
+        Ok(())
+    }
+
+    pub fn unlock_ram(&mut self, addr: u16) {
+        let addr = if addr < 0x2000 {
+            cpu_ram_mirror(addr) as u16
+        } else {
+            addr
+        };
+        self.ram_locks.retain(|lock| lock.addr != addr);
+    }
+
+    pub fn ram_locks(&self) -> &[RamLock] {
+        &self.ram_locks
+    }
+
+    // the locks with `timing`, or all of them for LockTiming::Frame
+    fn apply_ram_locks(&mut self, timing: LockTiming) {
+        for lock in self.ram_locks.iter() {
+            if timing == LockTiming::Frame || lock.timing == timing {
+                self.cpu.bus.write_ram(lock.addr, lock.value);
+            }
+        }
+    }
+
+    // assembles `source` (see assembler) at `addr` and puts it over ram, prg ram or prg
+    // rom, for quick experiments from the debugger. prg rom is patched through an overlay
+    // on the bus, so the cartridge keeps its data and bank switching hides the patch like
+    // it would the code. returns how many bytes were written
+    pub fn patch_code(&mut self, addr: u16, source: &str) -> Result<usize, String> {
+        let code = assembler::assemble(source, addr)?;
+        let end = addr as usize + code.len();
+        if end > 0x10000 {
+            return Err(String::from("the code runs past $FFFF"));
+        }
+        if let Some(bad) = (addr as usize..end).find(|at| *at >= 0x2000 && *at < 0x6000) {
+            return Err(format!("${:04X} is not ram or prg", bad));
+        }
+
+        let mut overwritten = Vec::new();
+        for (at, data) in (addr..).zip(code.iter().copied()) {
+            if at >= 0x8000 {
+                let bus = &mut self.cpu.bus;
+                overwritten.push((at, Overwritten::Prg(bus.prg_patch(at))));
+                let patch = PrgPatch {
+                    original: bus.peek_prg_rom(at),
+                    data: data,
+                };
+                bus.set_prg_patch(at, Some(patch));
+            } else {
+                overwritten.push((at, Overwritten::Ram(self.cpu.mem_peek(at))));
+                self.cpu.mem_write(at, data);
+            }
+        }
+        self.code_patches.push(overwritten);
+        Ok(code.len())
+    }
+
+    // takes back the last patch_code, false when there is none left
+    pub fn undo_code_patch(&mut self) -> bool {
+        let overwritten = match self.code_patches.pop() {
+            Some(overwritten) => overwritten,
+            None => return false,
+        };
+        for (at, before) in overwritten.into_iter().rev() {
+            match before {
+                Overwritten::Ram(data) => self.cpu.mem_write(at, data),
+                Overwritten::Prg(patch) => self.cpu.bus.set_prg_patch(at, patch),
+            }
+        }
+        true
+    }
+
+    pub fn code_patches(&self) -> usize {
+        self.code_patches.len()
+    }
+
+    // records the buttons held during every frame from now on, and resets, as a movie
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Movie { frames: Vec::new() });
+        self.recording_start = self.stats.frames;
+        self.reset_since_recorded_frame = false;
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    // called with the finished picture every time the ppu enters vblank
+    pub fn set_frame_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&Frame) + Send + 'static,
+    {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    // called once per frame with the samples produced during it
+    pub fn set_audio_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        self.audio_callback = Some(Box::new(callback));
+    }
+
+    // AvTiming::Exact is meant for exporting video, where frames are not paced by a clock
+    // and every frame has to carry the same length of audio. switching starts the count
+    // of exact frames over
+    pub fn set_av_timing(&mut self, timing: AvTiming) {
+        self.av_sync = match timing {
+            AvTiming::Live => None,
+            AvTiming::Exact => Some(AvSync::new()),
+        };
+    }
+
+    pub fn av_timing(&self) -> AvTiming {
+        match self.av_sync {
+            Some(_) => AvTiming::Exact,
+            None => AvTiming::Live,
+        }
+    }
+
+    // asked for the buttons of both ports each time the game strobes the controllers,
+    // before it reads the first bit. input arrives at the same point of the emulated frame
+    // however the frontend is scheduled, and replaces what queue_input set
+    pub fn set_input_provider<F>(&mut self, provider: F)
+    where
+        F: FnMut(Port) -> JoypadButton + Send + 'static,
+    {
+        self.input_provider = Some(Box::new(provider));
+    }
+
+    // takes effect right away, and is kept for the next cartridge
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.config.accuracy = accuracy;
+        self.cpu.bus.set_accuracy(accuracy);
+    }
+
+    pub fn accuracy(&self) -> Accuracy {
+        self.config.accuracy
+    }
+
+    pub fn set_overclock_scanlines(&mut self, scanlines: u16) {
+        self.config.overclock_scanlines = scanlines;
+        self.cpu.bus.set_overclock_scanlines(scanlines);
+    }
+
+    pub fn overclock_scanlines(&self) -> u16 {
+        self.config.overclock_scanlines
+    }
+
+    // like the accuracy, right away and for the next cartridge too
+    pub fn set_console(&mut self, console: Console) {
+        self.config.console = console;
+        self.cpu.bus.set_console(console);
+    }
+
+    pub fn console(&self) -> Console {
+        self.config.console
+    }
+
+    // like the accuracy, see config::UNSTABLE_MAGIC
+    pub fn set_unstable_magic(&mut self, magic: u8) {
+        self.config.unstable_magic = magic;
+        self.cpu.bus.set_unstable_magic(magic);
+    }
+
+    pub fn unstable_magic(&self) -> u8 {
+        self.config.unstable_magic
+    }
+
+    // whether controller 2's microphone hears something, for as long as it does. only read
+    // by games on a famicom
+    pub fn set_microphone(&mut self, loud: bool) {
+        self.cpu.bus.set_microphone(loud);
+    }
+
+    // a device from expansion::Registry, or None to unplug the one there is
+    pub fn plug_expansion(&mut self, device: Option<Box<dyn ExpansionDevice>>) {
+        self.cpu.bus.plug_expansion(device);
+    }
+
+    pub fn expansion_name(&self) -> Option<&'static str> {
+        self.cpu.bus.expansion_name()
+    }
+
+    // battery backed ram (or the board's eeprom) for the frontend to persist
+    pub fn save_sram(&mut self) -> Vec<u8> {
+        let sram = self.cpu.bus.save_data();
+        self.events.push(Event::SramSaved { bytes: sram.len() });
+        sram
+    }
+
+    // what an earlier save_sram returned for the same game
+    pub fn load_sram(&mut self, sram: &[u8]) {
+        self.cpu.bus.load_save_data(sram);
+    }
+
+    // the bytes save_sram returns, 0 for games without prg ram or an eeprom
+    pub fn sram_size(&self) -> usize {
+        self.cpu.bus.save_data().len()
+    }
+
+    // everything that happened since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events.take()
+    }
+
+    pub(crate) fn push_event(&mut self, event: Event) {
+        if let Event::DesyncDetected { frame } = event {
+            self.capture_repro(frame);
+        }
+        self.events.push(event);
+    }
+
+    // records input and state hashes from power-on so that the first DesyncDetected
+    // (from rollback or a spectator) leaves a ReproBundle for take_repro_bundle, with
+    // `window` frames before the divergence. `rom` is the file the cartridge came from.
+    // uses the movie recording, so it has to start before the first frame and
+    // stop_recording ends it
+    pub fn capture_repro_on_desync(&mut self, rom: &[u8], window: u64) -> Result<(), String> {
+        if self.stats.frames != 0 {
+            return Err(String::from("repro capture has to start at power-on"));
+        }
+        self.start_recording();
+        self.repro = Some(ReproCapture::new(rom, window));
+        Ok(())
+    }
+
+    fn capture_repro(&mut self, frame: u64) {
+        if self.repro_bundle.is_some() || self.recording_start != 0 {
+            return;
+        }
+        if let (Some(repro), Some(movie)) = (self.repro.as_ref(), self.recording.as_ref()) {
+            self.repro_bundle = Some(repro.bundle(&self.config, movie, frame));
+        }
+    }
+
+    pub fn take_repro_bundle(&mut self) -> Option<ReproBundle> {
+        self.repro_bundle.take()
+    }
+
+    // keeps the last `entries` instructions, see trace::DEFAULT_TRACE_ENTRIES. tracing
+    // costs a bit of speed, so it is off until a frontend asks for it
+    pub fn enable_trace(&mut self, entries: usize) {
+        self.trace_log = Some(TraceLog::new(entries));
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.trace_log = None;
+    }
+
+    pub fn trace_log(&self) -> Option<&TraceLog> {
+        self.trace_log.as_ref()
+    }
+
+    // reports homebrew mistakes as Event::Lint, see the lint module. like tracing it
+    // looks at every instruction, so it is off until asked for
+    pub fn enable_lint(&mut self) {
+        self.lint = Some(Lint::new());
+    }
+
+    pub fn disable_lint(&mut self) {
+        self.lint = None;
+    }
+
+    pub fn lint_enabled(&self) -> bool {
+        self.lint.is_some()
+    }
+
+    // canonical title from the game database
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    // drawn over every frame from the next one on
+    pub fn overlay(&mut self) -> &mut Overlay {
+        &mut self.overlay
+    }
+
+    // sprites per scanline and scroll splits of the last frame
+    pub fn frame_stats(&self) -> &FrameStats {
+        self.cpu.bus.ppu().frame_stats()
+    }
+
+    pub fn show_frame_stats(&mut self, show: bool) {
+        self.frame_stats_hud = show;
+    }
+
+    // draws stats() over every frame, as they were when the frame was done
+    pub fn show_stats(&mut self, show: bool) {
+        self.stats_hud = show;
+    }
+
+    // off draws every sprite of a scanline instead of the first 8, so games that flicker
+    // sprites to show more than the hardware can stop flickering. only the picture
+    // changes, the game still sees the sprite overflow flag
+    pub fn set_sprite_limit(&mut self, on: bool) {
+        self.sprite_limit = on;
+    }
+
+    pub fn sprite_limit(&self) -> bool {
+        self.sprite_limit
+    }
+
+    // colors background, sprite and sprite 0 pixels differently from the next frame on,
+    // see ppu_renderer::debug_tint
+    pub fn set_debug_tint(&mut self, on: bool) {
+        self.debug_tint = on;
+        // every row has to be converted again without it
+        self.previous_screen = vec![NO_PALETTE_INDEX; SCREEN_WIDTH * SCREEN_HEIGHT];
+    }
+
+    pub fn debug_tint(&self) -> bool {
+        self.debug_tint
+    }
+
+    // games on a multicart, 1 for every other cartridge
+    pub fn games(&self) -> usize {
+        self.cpu.bus.games()
+    }
+
+    // starts `game` of a multicart, skipping its menu. the selection is not part of a
+    // movie recording, only the reset it causes
+    pub fn select_game(&mut self, game: usize) {
+        self.cpu.bus.select_game(game);
+        self.reset();
+    }
+
+    // the board's bank registers, irq counters and mirroring, see Mapper::debug_state
+    pub fn mapper_debug_state(&self) -> Value {
+        self.cpu.bus.mapper_debug_state()
+    }
+
+    // the chr banks the board shows right now, FrameStats::chr_banks has them per scanline
+    pub fn chr_banks(&self) -> ChrBanks {
+        self.cpu.bus.ppu().bus.chr_banks()
+    }
+
+    // pattern table 0 or 1 as `banks` map it in palette 0-7, for a chr viewer
+    pub fn pattern_table(&self, table: usize, banks: &ChrBanks, palette: usize) -> Frame {
+        render_pattern_table(&self.cpu.bus.ppu().bus, banks, table, palette)
+    }
+
+    // system palette indices of the last frame without the overlay, for VideoFilter
+    pub fn screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    pub fn stats(&self) -> Stats {
+        let ppu = self.cpu.bus.ppu();
+        Stats {
+            cpu_cycles: self.cpu.bus.cycles() as u64,
+            scanline: ppu.scanline(),
+            dot: ppu.dot(),
+            ..self.stats
+        }
+    }
+
+    // steps until the ppu finishes the next picture
+    pub fn run_frame(&mut self) {
+        let target = self.stats.frames + 1;
+        while self.stats.frames < target {
+            self.step();
+        }
+    }
+
+    // FNV-1a over the cpu registers, both rams and the ppu memories. two runs that agree
+    // on it are, for all a game can observe, in the same state
+    pub fn state_hash(&self) -> u64 {
+        let cpu = &self.cpu;
+        let ppu = cpu.bus.ppu();
+        let registers = [
+            cpu.acc,
+            cpu.rx,
+            cpu.ry,
+            cpu.sp,
+            cpu.status.bits(),
+            (cpu.pc >> 8) as u8,
+            cpu.pc as u8,
+        ];
+
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes.iter() {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        write(&registers);
+        for addr in 0..0x800 {
+            write(&[cpu.mem_peek(addr)]);
+        }
+        write(cpu.bus.prg_ram());
+        write(&ppu.bus.vram);
+        write(&ppu.bus.palette);
+        write(&ppu.oam);
+        hash
+    }
+
+    pub fn step(&mut self) {
+        self.step_with_callback(|_| {});
+    }
+
+    pub fn step_with_callback<T>(&mut self, callback: T)
+    where
+        T: FnMut(&mut CPU) -> (),
+    {
+        if let Some(trace_log) = self.trace_log.as_mut() {
+            trace_log.record(self.stats.frames, &self.cpu);
+        }
+        if let Some(lint) = self.lint.as_mut() {
+            lint.before(&self.cpu, &mut self.events);
+        }
+        self.cpu.interprect_with_callback(callback);
+        if let Some(lint) = self.lint.as_mut() {
+            lint.after(&self.cpu, &mut self.events);
+        }
+        self.stats.instructions += 1;
+        if !self.ram_locks.is_empty() {
+            self.apply_ram_locks(LockTiming::Instruction);
+        }
+        if self.cpu.bus.take_strobe() {
+            if let Some(provider) = self.input_provider.as_mut() {
+                for port in [Port::One, Port::Two].iter() {
+                    let buttons = provider(*port);
+                    self.cpu.bus.joypad(*port).set_buttons(buttons);
+                }
+            }
+            self.apply_macro();
+        }
+        if let Some((opcode, pc)) = self.cpu.take_illegal_opcode() {
+            self.events.push(Event::IllegalOpcode {
+                pc: pc,
+                opcode: opcode,
+            });
+        }
+
+        if self.cpu.bus.take_frame_complete() {
+            self.output_frame();
+        }
+    }
+
+    fn output_frame(&mut self) {
+        if let Some(movie) = self.recording.as_mut() {
+            movie.frames.push(MovieFrame {
+                reset: self.reset_since_recorded_frame,
+                joypad1: self.cpu.bus.joypad1().get_buttons(),
+                joypad2: self.cpu.bus.joypad2().get_buttons(),
+            });
+            self.reset_since_recorded_frame = false;
+        }
+        if let Some((port, frames)) = self.macro_recording.as_mut() {
+            let buttons = self.cpu.bus.joypad(*port).get_buttons();
+            frames.push(buttons);
+        }
+        self.stats.frames += 1;
+        // hashed only while recording a repro
+        let hash = self.repro.as_ref().map(|_| self.state_hash());
+        if let (Some(repro), Some(hash)) = (self.repro.as_mut(), hash) {
+            repro.frame_done(self.stats.frames, hash);
+        }
+        self.apply_queued_input();
+        self.apply_macro();
+        self.apply_ram_locks(LockTiming::Frame);
+        self.run_schedule();
+        if self.resimulating {
+            self.cpu.bus.apu().take_samples();
+            return;
+        }
+
+        ppu_renderer::render(
+            self.cpu.bus.ppu(),
+            &mut self.screen,
+            &mut self.layers,
+            self.sprite_limit,
+        );
+        self.frame.clear_dirty();
+        let stats = self.cpu.bus.ppu().frame_stats();
+        for y in 0..SCREEN_HEIGHT {
+            let row = y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH;
+            let emphasis = stats.color_effects_at(y as u16).emphasis();
+            let changed = self.screen[row.clone()] != self.previous_screen[row.clone()]
+                || emphasis != self.previous_emphasis[y];
+            if changed || self.overlay_rows.contains(&y) || self.debug_tint {
+                palette::to_rgba(&self.screen[row.clone()], self.frame.row_mut(y));
+                if emphasis != 0 {
+                    palette::emphasize(self.frame.row_mut(y), emphasis);
+                }
+                if self.debug_tint {
+                    ppu_renderer::debug_tint(self.frame.row_mut(y), &self.layers[row]);
+                }
+            }
+            self.previous_emphasis[y] = emphasis;
+        }
+        self.previous_screen.copy_from_slice(&self.screen);
+        self.overlay_rows = self.overlay.draw(&mut self.frame);
+        if self.frame_stats_hud || self.stats_hud {
+            let mut hud = Overlay::new();
+            if self.frame_stats_hud {
+                self.cpu.bus.ppu().frame_stats().draw_hud(&mut hud);
+            }
+            if self.stats_hud {
+                self.stats().draw_hud(&mut hud);
+            }
+            let rows = hud.draw(&mut self.frame);
+            self.overlay_rows = if self.overlay_rows.is_empty() {
+                rows
+            } else {
+                self.overlay_rows.start.min(rows.start)..self.overlay_rows.end.max(rows.end)
+            };
+        }
+        if let Some(callback) = self.frame_callback.as_mut() {
+            callback(&self.frame);
+        }
+
+        let mut samples = self.cpu.bus.apu().take_samples();
+        if let Some(av_sync) = self.av_sync.as_mut() {
+            samples = av_sync.frame(&samples);
+        }
+        self.stats.audio_samples_last_frame = samples.len();
+        match self.audio_callback.as_mut() {
+            Some(callback) => callback(&samples),
+            None => self.stats.audio_samples_dropped += samples.len() as u64,
+        }
+    }
+}
+
+fn rom_loaded(cartridge: &Cartridge) -> Event {
+    Event::RomLoaded {
+        title: cartridge.title.clone(),
+        crc32: cartridge.crc32,
+    }
+}
+
+fn power_on_bus(cartridge: Cartridge, config: &Config) -> Bus {
+    let mut bus = Bus::new(cartridge);
+    bus.set_accuracy(config.accuracy);
+    bus.set_overclock_scanlines(config.overclock_scanlines);
+    bus.set_console(config.console);
+    bus.set_unstable_magic(config.unstable_magic);
+    match config.power_on_ram {
+        PowerOnRam::Zero => {}
+        PowerOnRam::Random { seed } => bus.randomize_memory(&mut PowerOnRng::new(seed)),
+        PowerOnRam::Pattern => bus.fill_memory_pattern(),
+    }
+    bus
+}
+
+fn power_on_rng(config: &Config) -> Option<PowerOnRng> {
+    match config.power_on_cpu {
+        PowerOnCpu::Zero => None,
+        PowerOnCpu::Random { seed } => Some(PowerOnRng::new(seed)),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    // a reset console with joypad.nes, which strobes the controllers once per nmi and
+    // moves its sprite with them
+    pub fn create_nes() -> Nes {
+        let raw = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes
+    }
+
+    #[test]
+    fn test_power_on_ram() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let zero = Nes::new(Cartridge::new(&raw).unwrap());
+        assert!((0..0x800).all(|addr| zero.cpu.mem_peek(addr) == 0));
+
+        let config = Config {
+            power_on_ram: PowerOnRam::Random { seed: 7 },
+            ..Config::default()
+        };
+        let a = Nes::with_config(Cartridge::new(&raw).unwrap(), &config);
+        let b = Nes::with_config(Cartridge::new(&raw).unwrap(), &config);
+        assert!((0..0x800).any(|addr| a.cpu.mem_peek(addr) != 0));
+        assert!((0..0x800).all(|addr| a.cpu.mem_peek(addr) == b.cpu.mem_peek(addr)));
+        assert!(a
+            .cpu
+            .bus
+            .ppu()
+            .bus
+            .palette
+            .iter()
+            .all(|entry| *entry < 0x40));
+    }
+
+    #[test]
+    fn test_realistic_power_on() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::with_config(Cartridge::new(&raw).unwrap(), &Config::realistic(3));
+        assert_eq!(nes.cpu.mem_peek(0x0003), 0x00);
+        assert_eq!(nes.cpu.mem_peek(0x0004), 0xFF);
+
+        nes.reset();
+        let registers = (nes.cpu.acc, nes.cpu.rx, nes.cpu.ry);
+        assert_ne!(registers, (0, 0, 0));
+        assert_eq!(nes.cpu.status.bits(), 0x34);
+        assert_eq!(nes.cpu.sp, 0xFD);
+
+        // only power-on leaves a, x and y undefined
+        nes.reset();
+        assert_eq!((nes.cpu.acc, nes.cpu.rx, nes.cpu.ry), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_instances_on_threads() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let frames = Arc::new(AtomicRefCell::new(0));
+        let spawn = |frames: Arc<AtomicRefCell<usize>>| {
+            let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+            nes.set_frame_callback(move |_| *frames.borrow_mut() += 1);
+            std::thread::spawn(move || {
+                nes.reset();
+                for _ in 0..10 {
+                    nes.run_frame();
+                }
+                nes
+            })
+        };
+        let a = spawn(frames.clone());
+        let b = spawn(Arc::new(AtomicRefCell::new(0)));
+        let (a, b) = (a.join().unwrap(), b.join().unwrap());
+        assert_eq!(*frames.borrow(), 10);
+        assert_eq!(a.stats().frames, 10);
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+
+        let frames = Arc::new(AtomicRefCell::new(0));
+        let samples = Arc::new(AtomicRefCell::new(0));
+        let frames_counter = frames.clone();
+        let samples_counter = samples.clone();
+        nes.set_frame_callback(move |frame| {
+            assert_eq!(frame.data.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+            if *frames_counter.borrow() == 0 {
+                let screen = Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+                assert_eq!(frame.dirty_rects(), &[screen]);
+            }
+            *frames_counter.borrow_mut() += 1;
+        });
+        nes.set_audio_callback(move |buffer| *samples_counter.borrow_mut() += buffer.len());
+
+        nes.reset();
+        while *frames.borrow() < 3 {
+            nes.step();
+        }
+
+        // about 735 samples per frame at 44.1kHz
+        assert!(*samples.borrow() > 3 * 700);
+
+        let stats = nes.stats();
+        assert_eq!(stats.frames, 3);
+        assert!(stats.instructions > 0);
+        assert!(stats.cpu_cycles > stats.instructions);
+        assert_eq!(stats.audio_samples_dropped, 0);
+        assert!(stats.audio_samples_last_frame > 700);
+    }
+
+    #[test]
+    fn test_stats_counters() {
+        let rom = include_bytes!("../res/test.nes");
+        let mut nes = Nes::new(Cartridge::new(rom).unwrap());
+        nes.reset();
+        assert_eq!(nes.stats().frames, 0);
+
+        // without an audio callback every sample of a frame is dropped. the first frame
+        // after power-on is a short one
+        nes.run_frame();
+        let stats = nes.stats();
+        assert_eq!(stats.frames, 1);
+        assert!(stats.audio_samples_last_frame > 0);
+        assert_eq!(
+            stats.audio_samples_dropped,
+            stats.audio_samples_last_frame as u64
+        );
+        nes.run_frame();
+        assert_eq!(nes.stats().frames, 2);
+        assert!(nes.stats().audio_samples_last_frame > 700);
+        assert_eq!(
+            nes.stats().audio_samples_dropped,
+            stats.audio_samples_dropped + nes.stats().audio_samples_last_frame as u64
+        );
+
+        // one instruction a step
+        let instructions = nes.stats().instructions;
+        nes.step();
+        nes.step();
+        assert_eq!(nes.stats().instructions, instructions + 2);
+
+        // with one the callback gets them and nothing more is dropped
+        let delivered = Arc::new(AtomicRefCell::new(0));
+        let delivered_counter = delivered.clone();
+        nes.set_audio_callback(move |samples| *delivered_counter.borrow_mut() = samples.len());
+        let dropped = nes.stats().audio_samples_dropped;
+        nes.run_frame();
+        assert_eq!(nes.stats().audio_samples_dropped, dropped);
+        assert_eq!(*delivered.borrow(), nes.stats().audio_samples_last_frame);
+
+        // a savestate brings the counters back to where they were
+        let state = nes.save_state();
+        let saved = nes.stats();
+        nes.run_frame();
+        nes.run_frame();
+        assert_ne!(nes.stats(), saved);
+        nes.load_state(&state).unwrap();
+        assert_eq!(nes.stats(), saved);
+    }
+
+    #[test]
+    fn test_stats_hud() {
+        let rom = include_bytes!("../res/test.nes");
+        let mut plain = Nes::new(Cartridge::new(rom).unwrap());
+        let mut hud = Nes::new(Cartridge::new(rom).unwrap());
+        hud.show_stats(true);
+        for nes in [&mut plain, &mut hud].iter_mut() {
+            nes.reset();
+            nes.run_frame();
+        }
+
+        let row = |nes: &Nes, y: usize| nes.frame().row(y).to_vec();
+        assert_eq!(row(&plain, 0), row(&hud, 0));
+        assert_ne!(row(&plain, SCREEN_HEIGHT - 4), row(&hud, SCREEN_HEIGHT - 4));
+        hud.show_stats(false);
+        plain.run_frame();
+        hud.run_frame();
+        assert_eq!(plain.frame().data, hud.frame().data);
+    }
+
+    #[test]
+    fn test_load_cartridge() {
+        let nestest = include_bytes!("../res/test.nes").to_vec();
+        let snake = include_bytes!("../res/snake.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&nestest).unwrap());
+
+        let frames = Arc::new(AtomicRefCell::new(0));
+        let frames_counter = frames.clone();
+        nes.set_frame_callback(move |_| *frames_counter.borrow_mut() += 1);
+        let resets = Arc::new(AtomicRefCell::new(0));
+        let resets_counter = resets.clone();
+        nes.cpu
+            .hooks
+            .on_interrupt(move |_| *resets_counter.borrow_mut() += 1);
+        nes.cpu.break_on_stack_fault = true;
+
+        nes.reset();
+        while nes.stats().frames < 2 {
+            nes.step();
+        }
+
+        nes.load_cartridge(Cartridge::new(&snake).unwrap());
+        assert_eq!(nes.title(), Some("Snake"));
+        assert_eq!(nes.stats().frames, 0);
+        assert!(nes.cpu.break_on_stack_fault);
+
+        nes.reset();
+        assert_eq!(nes.cpu.pc, nes.cpu.mem_peek_u16(0xFFFC));
+        while nes.stats().frames < 1 {
+            nes.step();
+        }
+        assert_eq!(*frames.borrow(), 3);
+        assert!(*resets.borrow() >= 2);
+    }
+
+    #[test]
+    fn test_events() {
+        // LDA #$01, then an opcode the cpu does not emulate
+        let mut prg = vec![0xEA; 0x4000];
+        prg[..3].copy_from_slice(&[0xA9, 0x01, 0x02]);
+        prg[0x3FFC] = 0x00;
+        prg[0x3FFD] = 0x80;
+        let raw = crate::cartridge::test::create_rom(0b0000_0010, 1, prg);
+        let cartridge = Cartridge::new(&raw).unwrap();
+        let crc32 = cartridge.crc32;
+        let mut nes = Nes::new(cartridge);
+
+        nes.reset();
+        nes.step();
+        nes.step();
+        assert_eq!(nes.save_sram().len(), 0x2000);
+        assert_eq!(
+            nes.take_events(),
+            vec![
+                Event::RomLoaded {
+                    title: None,
+                    crc32: crc32
+                },
+                Event::IllegalOpcode {
+                    pc: 0x8002,
+                    opcode: 0x02
+                },
+                Event::SramSaved { bytes: 0x2000 },
+            ]
+        );
+
+        nes.step();
+        assert!(nes.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_queue_input() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes.queue_input(0, Port::One, JoypadButton::START);
+        nes.queue_input(2, Port::Two, JoypadButton::UP);
+        nes.queue_input(3, Port::One, JoypadButton::empty());
+        assert_eq!(nes.cpu.bus.joypad1().get_buttons(), JoypadButton::START);
+
+        let mut held = Vec::new();
+        for _ in 0..4 {
+            nes.run_frame();
+            held.push((
+                nes.cpu.bus.joypad1().get_buttons(),
+                nes.cpu.bus.joypad2().get_buttons(),
+            ));
+        }
+        assert_eq!(
+            held,
+            vec![
+                (JoypadButton::START, JoypadButton::empty()),
+                (JoypadButton::START, JoypadButton::UP),
+                (JoypadButton::empty(), JoypadButton::UP),
+                (JoypadButton::empty(), JoypadButton::UP),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recording_replays() {
+        let raw = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes.start_recording();
+        nes.queue_input(5, Port::One, JoypadButton::RIGHT);
+        nes.queue_input(20, Port::One, JoypadButton::DOWN | JoypadButton::BUTTON_A);
+        nes.queue_input(30, Port::One, JoypadButton::empty());
+        for frame in 0..40 {
+            if frame == 25 {
+                nes.reset();
+            }
+            nes.run_frame();
+        }
+        let movie = nes.stop_recording().unwrap();
+        assert_eq!(movie.frames.len(), 40);
+        assert!(movie.frames[25].reset);
+
+        let mut replay = Nes::new(Cartridge::new(&raw).unwrap());
+        movie.play(&mut replay);
+        assert_eq!(replay.state_hash(), nes.state_hash());
+    }
+
+    #[test]
+    fn test_input_provider() {
+        let raw = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        let polls = Arc::new(AtomicRefCell::new(Vec::new()));
+        let polled = polls.clone();
+        nes.set_input_provider(move |port| {
+            polled.borrow_mut().push(port);
+            match port {
+                Port::One => JoypadButton::RIGHT,
+                Port::Two => JoypadButton::empty(),
+            }
+        });
+
+        nes.reset();
+        for _ in 0..10 {
+            nes.run_frame();
+        }
+        // joypad.nes strobes once per nmi and moves its sprite right while RIGHT is held
+        assert_eq!(polls.borrow().len(), 2 * 9);
+        assert_eq!(polls.borrow()[..2], [Port::One, Port::Two]);
+        assert_eq!(nes.cpu.mem_peek(0x10), 9);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let raw = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes.run_frame();
+        let background = nes.frame().pixel(100, 100);
+
+        let color = [0x12, 0x34, 0x56, 0xFF];
+        nes.overlay().fill_rect(Rect::new(90, 95, 20, 10), color);
+        nes.run_frame();
+        assert_eq!(nes.frame().pixel(100, 100), color);
+        nes.run_frame();
+        assert_eq!(nes.frame().pixel(100, 100), color);
+
+        // the picture below has not changed, the rows are converted again anyway
+        nes.overlay().clear();
+        nes.run_frame();
+        assert_eq!(nes.frame().pixel(100, 100), background);
+    }
+
+    #[test]
+    fn test_overclock() {
+        let raw = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let cycles_per_frame = |scanlines: u16| {
+            let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+            nes.set_overclock_scanlines(scanlines);
+            nes.reset();
+            nes.run_frame();
+            let start = nes.stats().cpu_cycles;
+            for _ in 0..10 {
+                nes.run_frame();
+            }
+            (nes.stats().cpu_cycles - start) / 10
+        };
+
+        // 29780.5 cycles a frame, 113.67 a scanline
+        let normal = cycles_per_frame(0);
+        assert!(normal >= 29780 && normal <= 29781);
+        let overclocked = cycles_per_frame(100);
+        assert!(overclocked >= normal + 100 * 113 && overclocked <= normal + 100 * 113 + 7);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::crc32;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// source, target and patch crc32
+const BPS_FOOTER_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    // by the magic number, file extensions are not to be trusted
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+}
+
+// the crc32 of the file a patch was made for. only BPS records it, IPS patches are
+// matched to their rom by file name
+pub fn source_crc32(patch: &[u8]) -> Option<u32> {
+    if PatchFormat::detect(patch) != Some(PatchFormat::Bps) {
+        return None;
+    }
+    let footer = patch.get(patch.len().checked_sub(BPS_FOOTER_SIZE)?..)?;
+    Some(u32::from_le_bytes([
+        footer[0], footer[1], footer[2], footer[3],
+    ]))
+}
+
+// the patched copy of `source`, which is the whole .nes file headers included, the way
+// rom hacks and translations are distributed
+pub fn apply(format: PatchFormat, source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    match format {
+        PatchFormat::Ips => apply_ips(source, patch),
+        PatchFormat::Bps => apply_bps(source, patch),
+    }
+}
+
+/*
+http://fileformats.archiveteam.org/wiki/IPS_(binary_patch_format)
+
+    "PATCH", then records until "EOF": a 24 bit big endian offset and a 16 bit size
+    followed by that many bytes. a size of 0 is a run instead, a 16 bit count and the
+    byte to repeat. records past the end grow the file, a 24 bit size after "EOF"
+    truncates it
+*/
+fn apply_ips(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(String::from("not an IPS patch"));
+    }
+    let truncated = || String::from("IPS patch is truncated");
+    let mut target = source.to_vec();
+    let mut pos = IPS_MAGIC.len();
+    loop {
+        let record = patch.get(pos..pos + 3).ok_or_else(truncated)?;
+        if record == IPS_EOF {
+            pos += 3;
+            break;
+        }
+        let offset = be(record);
+        let size = be(patch.get(pos + 3..pos + 5).ok_or_else(truncated)?);
+        pos += 5;
+        let data = if size == 0 {
+            let run = patch.get(pos..pos + 3).ok_or_else(truncated)?;
+            pos += 3;
+            vec![run[2]; be(&run[0..2])]
+        } else {
+            let data = patch.get(pos..pos + size).ok_or_else(truncated)?;
+            pos += size;
+            data.to_vec()
+        };
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    if let Some(size) = patch.get(pos..pos + 3) {
+        target.truncate(be(size));
+    }
+    Ok(target)
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, byte| n << 8 | *byte as usize)
+}
+
+/*
+https://github.com/blakesmith/rombp/blob/master/docs/bps_spec.md
+
+    "BPS1", the source, target and metadata sizes as varints, the metadata, then actions
+    until the 12 byte footer. each action is a varint, the low 2 bits the command and
+    the rest the length - 1
+
+    0  SourceRead  copy from the source at the same offset as the output
+    1  TargetRead  copy bytes that follow in the patch
+    2  SourceCopy  copy from the source at a relative offset (varint, bit 0 the sign)
+    3  TargetCopy  copy from the output so far at a relative offset, may overlap itself
+
+    the footer has the crc32 of the source, the target and the patch before it, so a
+    patch for another dump or revision is refused instead of producing garbage
+*/
+fn apply_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !patch.starts_with(BPS_MAGIC) || patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(String::from("not a BPS patch"));
+    }
+    let actions_end = patch.len() - BPS_FOOTER_SIZE;
+    let footer = |at: usize| {
+        let at = actions_end + at * 4;
+        u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]])
+    };
+    if crc32::crc32(&patch[..patch.len() - 4]) != footer(2) {
+        return Err(String::from("BPS patch is corrupt"));
+    }
+    if crc32::crc32(source) != footer(0) {
+        return Err(String::from("BPS patch is for a different rom"));
+    }
+
+    let mut reader = Reader {
+        patch: &patch[..actions_end],
+        pos: BPS_MAGIC.len(),
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != source.len() {
+        return Err(String::from("BPS patch is for a different rom"));
+    }
+
+    let bad_offset = || String::from("BPS patch reads outside the rom");
+    let mut target = Vec::new();
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    while reader.pos < reader.patch.len() {
+        let action = reader.varint()?;
+        let length = (action >> 2) + 1;
+        if target.len().saturating_add(length) > target_size {
+            return Err(String::from("BPS patch writes past the end of the rom"));
+        }
+        match action & 0b11 {
+            0 => {
+                let data = slice(source, target.len(), length).ok_or_else(bad_offset)?;
+                target.extend_from_slice(data);
+            }
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            2 => {
+                source_offset = reader.relative(source_offset)?;
+                let data = slice(source, source_offset, length).ok_or_else(bad_offset)?;
+                target.extend_from_slice(data);
+                source_offset += length;
+            }
+            _ => {
+                target_offset = reader.relative(target_offset)?;
+                if target_offset >= target.len() {
+                    return Err(bad_offset());
+                }
+                // byte by byte, a copy of the last few bytes repeats them
+                for _ in 0..length {
+                    target.push(target[target_offset]);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32::crc32(&target) != footer(1) {
+        return Err(String::from(
+            "BPS patch produced a different rom than expected",
+        ));
+    }
+    Ok(target)
+}
+
+// `len` bytes at `at`, without overflowing on the offsets a broken patch makes up
+fn slice(bytes: &[u8], at: usize, len: usize) -> Option<&[u8]> {
+    bytes.get(at..)?.get(..len)
+}
+
+struct Reader<'a> {
+    patch: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = slice(self.patch, self.pos, len)
+            .ok_or_else(|| String::from("BPS patch is truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    // 7 bits per byte, the last one has bit 7 set. every continuation adds one more so
+    // each number has exactly one encoding
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.bytes(1)?[0] as usize;
+            value = value
+                .checked_add((byte & 0x7F).checked_mul(shift).ok_or_else(overflow)?)
+                .ok_or_else(overflow)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+    }
+
+    // `offset` moved by a signed varint
+    fn relative(&mut self, offset: usize) -> Result<usize, String> {
+        let data = self.varint()?;
+        let distance = data >> 1;
+        let moved = if data & 1 != 0 {
+            offset.checked_sub(distance)
+        } else {
+            offset.checked_add(distance)
+        };
+        moved.ok_or_else(|| String::from("BPS patch reads outside the rom"))
+    }
+}
+
+fn overflow() -> String {
+    String::from("BPS patch has a number too large")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn varint(mut value: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte | 0x80);
+                return;
+            }
+            out.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(source.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(0, &mut patch);
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32::crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32::crc32(target).to_le_bytes());
+        let crc = crc32::crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_ips() {
+        let mut patch = IPS_MAGIC.to_vec();
+        // 2 bytes at 1, a run of 3 at 6 that grows the file, then truncate to 8
+        patch.extend_from_slice(&[0, 0, 1, 0, 2, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0, 0, 6, 0, 0, 0, 3, 0xCC]);
+        patch.extend_from_slice(IPS_EOF);
+        assert_eq!(
+            apply(PatchFormat::Ips, &[0; 4], &patch),
+            Ok(vec![0, 0xAA, 0xBB, 0, 0, 0, 0xCC, 0xCC, 0xCC])
+        );
+        patch.extend_from_slice(&[0, 0, 8]);
+        assert_eq!(apply_ips(&[0; 4], &patch).unwrap().len(), 8);
+
+        assert!(apply_ips(&[0; 4], &patch[..patch.len() - 6]).is_err());
+        assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Ips));
+        assert_eq!(PatchFormat::detect(b"NES\x1A"), None);
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = b"ABCDEFGH";
+        let target = b"ABCxyxyxyFGH";
+        let mut actions = Vec::new();
+        // SourceRead "ABC", TargetRead "xy", TargetCopy 4 bytes from the "x" at 3, which
+        // overlaps itself, then SourceCopy "FGH" from 5
+        varint((3 - 1) << 2, &mut actions);
+        varint((2 - 1) << 2 | 1, &mut actions);
+        actions.extend_from_slice(b"xy");
+        varint((4 - 1) << 2 | 3, &mut actions);
+        varint(3 << 1, &mut actions);
+        varint((3 - 1) << 2 | 2, &mut actions);
+        varint(5 << 1, &mut actions);
+        let patch = bps(source, target, &actions);
+        assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Bps));
+        assert_eq!(source_crc32(&patch), Some(crc32::crc32(source)));
+        assert_eq!(source_crc32(BPS_MAGIC), None);
+        assert_eq!(apply(PatchFormat::Bps, source, &patch), Ok(target.to_vec()));
+
+        // another dump, or a damaged patch
+        assert_eq!(
+            apply_bps(b"ABCDEFGX", &patch),
+            Err(String::from("BPS patch is for a different rom"))
+        );
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 1;
+        assert_eq!(
+            apply_bps(source, &corrupt),
+            Err(String::from("BPS patch is corrupt"))
+        );
+    }
+}
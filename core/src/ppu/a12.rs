@@ -0,0 +1,162 @@
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/MMC3#IRQ_Specifics
+
+    the MMC3 counts scanlines by watching ppu address line A12, which is high whenever
+    the ppu fetches from pattern table 1 ($1000-$1FFF). with the background and the
+    sprites in different tables it rises once per scanline, at dot 261 (sprites at
+    $1000) or 325 (background at $1000)
+
+    background fetches toggle A12 every 8 dots, so the MMC3 only counts a rise after A12
+    was low for a while (3 falling edges of M2). counted in ppu dots that is 10, longer
+    than the 4 dot gaps between two tiles and the 9 dots between the prefetch of the
+    next line and its first tile, shorter than the 64 dots of sprite fetches
+
+    https://wiki.nesdev.com/w/index.php/PPU_rendering#Line-by-line_timing
+    every 8 dot fetch is nametable (2 dots), attribute or garbage (2), then the low and
+    high pattern byte (2 each). only the pattern fetches can put A12 high
+
+    dots 1-256    background tiles of this line
+    dots 257-320  patterns of the 8 sprites for the next line, empty slots fetch tile $FF
+    dots 321-336  the first two background tiles of the next line
+    dots 337-340  two nametable fetches
+*/
+pub const A12_FILTER_DOTS: u16 = 10;
+
+const FETCH_DOTS: u16 = 8;
+const PATTERN_FETCH_START: u16 = 4;
+const SPRITE_FETCHES_START: u16 = 257;
+const SPRITE_FETCHES_END: u16 = 320;
+const PREFETCH_END: u16 = 336;
+const SPRITES_PER_LINE: usize = 8;
+
+// the level of A12 on `dot` of a line the ppu renders. `sprite_tables` has the pattern
+// table of each sprite fetched on this line, the same table for all 8x8 sprites
+pub fn fetch_a12(dot: u16, background_table: u16, sprite_tables: &[u16; SPRITES_PER_LINE]) -> bool {
+    let table = match dot {
+        1..=256 | 321..=PREFETCH_END => background_table,
+        SPRITE_FETCHES_START..=SPRITE_FETCHES_END => {
+            sprite_tables[((dot - SPRITE_FETCHES_START) / FETCH_DOTS) as usize]
+        }
+        _ => return false,
+    };
+    (dot - 1) % FETCH_DOTS >= PATTERN_FETCH_START && table & 0x1000 != 0
+}
+
+// 8x16 sprites pick their table with bit 0 of the tile, so it depends on which sprites
+// sprite evaluation found for the next line
+pub fn sprite_tables(oam: &[u8; 256], scanline: u16, height: u16, table: u16) -> [u16; 8] {
+    if height != 16 {
+        return [table; SPRITES_PER_LINE];
+    }
+    let mut tables = [0x1000; SPRITES_PER_LINE];
+    let on_line = oam
+        .chunks_exact(4)
+        .filter(|sprite| scanline.wrapping_sub(sprite[0] as u16) < height)
+        .take(SPRITES_PER_LINE);
+    for (slot, sprite) in on_line.enumerate() {
+        tables[slot] = (sprite[1] as u16 & 1) * 0x1000;
+    }
+    tables
+}
+
+// rising edges of A12 that a board with the MMC3's filter would count
+#[derive(Debug, Clone)]
+pub struct A12Watcher {
+    high: bool,
+    low_dots: u16,
+}
+
+impl A12Watcher {
+    pub fn new() -> Self {
+        A12Watcher {
+            high: false,
+            low_dots: A12_FILTER_DOTS,
+        }
+    }
+
+    // A12 was at `high` for `dots` dots. true on a rise the filter lets through
+    pub fn update(&mut self, high: bool, dots: u16) -> bool {
+        let counted = high && !self.high && self.low_dots >= A12_FILTER_DOTS;
+        if high {
+            self.low_dots = 0;
+        } else {
+            self.low_dots = self.low_dots.saturating_add(dots);
+        }
+        self.high = high;
+        counted
+    }
+
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.push(self.high as u8);
+        state.extend_from_slice(&self.low_dots.to_le_bytes());
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.high = state[0] != 0;
+        self.low_dots = u16::from_le_bytes([state[1], state[2]]);
+        &state[3..]
+    }
+}
+
+impl Default for A12Watcher {
+    fn default() -> Self {
+        A12Watcher::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the dots of one rendered line with a counted rise
+    fn counted_rises(background_table: u16, sprite_tables: &[u16; 8]) -> Vec<u16> {
+        let mut watcher = A12Watcher::new();
+        let mut rises = Vec::new();
+        // two lines, the first one only settles the filter
+        for line in 0..2 {
+            for dot in 0..341 {
+                if watcher.update(fetch_a12(dot, background_table, sprite_tables), 1) && line == 1 {
+                    rises.push(dot);
+                }
+            }
+        }
+        rises
+    }
+
+    #[test]
+    fn test_one_rise_per_line() {
+        assert_eq!(counted_rises(0x0000, &[0x1000; 8]), vec![261]);
+        assert_eq!(counted_rises(0x1000, &[0x0000; 8]), vec![325]);
+        // both in the same table, nothing to count
+        assert_eq!(counted_rises(0x0000, &[0x0000; 8]), Vec::<u16>::new());
+        assert_eq!(counted_rises(0x1000, &[0x1000; 8]), Vec::<u16>::new());
+        // 8x16 sprites from table 0 with the empty slots at $1000
+        let mut tables = [0x1000; 8];
+        tables[0] = 0x0000;
+        tables[1] = 0x0000;
+        assert_eq!(counted_rises(0x0000, &tables), vec![277]);
+
+        // a quick toggle through $2006 right after a rise is filtered out
+        let mut watcher = A12Watcher::new();
+        assert!(watcher.update(true, 1));
+        assert!(!watcher.update(false, 3));
+        assert!(!watcher.update(true, 1));
+    }
+
+    #[test]
+    fn test_sprite_tables() {
+        let mut oam = [0xFF; 256];
+        // tile 3 (table 1) on lines 20-35, tile 4 (table 0) on lines 30-45
+        oam[0..4].copy_from_slice(&[20, 3, 0, 0]);
+        oam[4..8].copy_from_slice(&[30, 4, 0, 0]);
+        assert_eq!(sprite_tables(&oam, 10, 8, 0x1000), [0x1000; 8]);
+        assert_eq!(sprite_tables(&oam, 10, 16, 0x0000), [0x1000; 8]);
+        assert_eq!(
+            sprite_tables(&oam, 32, 16, 0x0000),
+            [0x1000, 0x0000, 0x1000, 0x1000, 0x1000, 0x1000, 0x1000, 0x1000]
+        );
+    }
+}
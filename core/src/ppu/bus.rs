@@ -0,0 +1,194 @@
+use crate::address_decode::{nametable_mirror, palette_mirror};
+use crate::cartridge::MirroringType;
+use crate::mapper::{banked_offset, ChrBanks, SharedMapper, CHR_WINDOW_SIZE};
+
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/PPU_memory_map
+    Address range	Size	Description
+    $0000-$0FFF	$1000	Pattern table 0
+    $1000-$1FFF	$1000	Pattern table 1
+    $2000-$23FF	$0400	Nametable 0
+    $2400-$27FF	$0400	Nametable 1
+    $2800-$2BFF	$0400	Nametable 2
+    $2C00-$2FFF	$0400	Nametable 3
+    $3000-$3EFF	$0F00	Mirrors of $2000-$2EFF
+    $3F00-$3F1F	$0020	Palette RAM indexes
+    $3F20-$3FFF	$00E0	Mirrors of $3F00-$3F1F
+*/
+
+// the console has 2KB of nametable ram, four-screen boards add another 2KB on the cartridge
+const VRAM_SIZE: usize = 2048;
+const FOUR_SCREEN_VRAM_SIZE: usize = 4096;
+
+#[derive(Clone)]
+pub struct PpuBus {
+    mapper: SharedMapper,
+    // the board counts A12 rises, see ppu::a12
+    watches_a12: bool,
+    pub vram: Vec<u8>,
+    pub palette: [u8; 32],
+}
+
+impl PpuBus {
+    // a restored snapshot keeps talking to the board it is restored into
+    pub(crate) fn set_mapper(&mut self, mapper: SharedMapper) {
+        self.mapper = mapper;
+    }
+
+    pub fn new(mapper: SharedMapper) -> Self {
+        let vram_size = match mapper.borrow().mirroring() {
+            MirroringType::FourScreen => FOUR_SCREEN_VRAM_SIZE,
+            _ => VRAM_SIZE,
+        };
+        let watches_a12 = mapper.borrow().watches_a12();
+        PpuBus {
+            mapper: mapper,
+            watches_a12: watches_a12,
+            vram: vec![0; vram_size],
+            palette: [0; 32],
+        }
+    }
+
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => self.mapper.borrow().read_chr(addr),
+            0x2000..=0x3EFF => self.vram[self.nametable_index(addr)],
+            _ => self.palette[palette_mirror(addr)],
+        }
+    }
+
+    pub fn write_vram(&mut self, addr: u16, data: u8) {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => self.mapper.borrow_mut().write_chr(addr, data),
+            0x2000..=0x3EFF => {
+                let index = self.nametable_index(addr);
+                self.vram[index] = data;
+            }
+            _ => self.palette[palette_mirror(addr)] = data,
+        }
+    }
+
+    pub fn watches_a12(&self) -> bool {
+        self.watches_a12
+    }
+
+    pub fn a12_rising_edge(&self) {
+        self.mapper.borrow_mut().a12_rising_edge();
+    }
+
+    // the chr pages the board shows right now
+    pub fn chr_banks(&self) -> ChrBanks {
+        self.mapper.borrow().chr_banks()
+    }
+
+    // pattern table byte at `addr` as `banks` map it, which need not be the banks the
+    // board shows right now. lets debug views show what earlier scanlines were drawn with
+    pub fn read_chr_banked(&self, banks: &ChrBanks, addr: u16) -> u8 {
+        let addr = addr as usize & 0x1FFF;
+        let mapper = self.mapper.borrow();
+        let chr = mapper.chr();
+        chr[banked_offset(
+            chr.len(),
+            CHR_WINDOW_SIZE,
+            banks[addr / CHR_WINDOW_SIZE],
+            addr,
+        )]
+    }
+
+    // the board decides the mirroring, and may change it between two fetches
+    fn nametable_index(&self, addr: u16) -> usize {
+        nametable_mirror(self.mapper.borrow().mirroring(), addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::axrom::AxROM;
+    use crate::mapper::nrom::NROM;
+    use crate::mapper::Mapper;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    fn create_bus(chr: Vec<u8>, mirroring_type: MirroringType) -> PpuBus {
+        let mapper: Box<dyn Mapper> = Box::new(NROM::new(vec![0; 0x4000], chr, mirroring_type));
+        PpuBus::new(Arc::new(AtomicRefCell::new(mapper)))
+    }
+
+    #[test]
+    fn test_chr_through_mapper() {
+        let mut chr = vec![0; 0x2000];
+        chr[0x1234] = 0x56;
+        let bus = create_bus(chr, MirroringType::Horizontal);
+        assert_eq!(bus.read_vram(0x1234), 0x56);
+
+        let mut chr_ram_bus = create_bus(Vec::new(), MirroringType::Horizontal);
+        chr_ram_bus.write_vram(0x0042, 0x99);
+        assert_eq!(chr_ram_bus.read_vram(0x0042), 0x99);
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let mut horizontal = create_bus(Vec::new(), MirroringType::Horizontal);
+        horizontal.write_vram(0x2005, 0x11);
+        horizontal.write_vram(0x2805, 0x22);
+        assert_eq!(horizontal.read_vram(0x2405), 0x11);
+        assert_eq!(horizontal.read_vram(0x2C05), 0x22);
+        // $3000-$3EFF mirrors $2000-$2EFF
+        assert_eq!(horizontal.read_vram(0x3005), 0x11);
+
+        let mut vertical = create_bus(Vec::new(), MirroringType::Vertical);
+        vertical.write_vram(0x2005, 0x11);
+        vertical.write_vram(0x2405, 0x22);
+        assert_eq!(vertical.read_vram(0x2805), 0x11);
+        assert_eq!(vertical.read_vram(0x2C05), 0x22);
+    }
+
+    #[test]
+    fn test_four_screen() {
+        let mut bus = create_bus(Vec::new(), MirroringType::FourScreen);
+        assert_eq!(bus.vram.len(), 4096);
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].iter().enumerate() {
+            bus.write_vram(addr + 0x3BF, i as u8 + 1);
+        }
+        assert_eq!(bus.read_vram(0x23BF), 1);
+        assert_eq!(bus.read_vram(0x27BF), 2);
+        assert_eq!(bus.read_vram(0x2BBF), 3);
+        assert_eq!(bus.read_vram(0x2FBF), 4);
+        // $3000-$3EFF mirrors $2000-$2EFF
+        assert_eq!(bus.read_vram(0x3BBF), 3);
+    }
+
+    #[test]
+    fn test_mirroring_switched_by_mapper() {
+        let mapper: Box<dyn Mapper> = Box::new(AxROM::new(vec![0; 0x8000], Vec::new()));
+        let mapper = Arc::new(AtomicRefCell::new(mapper));
+        let mut bus = PpuBus::new(mapper.clone());
+
+        bus.write_vram(0x2000, 0x11);
+        assert_eq!(bus.read_vram(0x2C00), 0x11);
+
+        // the game flips to the upper page between two nametable fetches
+        mapper.borrow_mut().write_prg(0x8000, 0b0001_0000);
+        assert_eq!(bus.read_vram(0x2000), 0x00);
+        bus.write_vram(0x2400, 0x22);
+        assert_eq!(bus.read_vram(0x2800), 0x22);
+
+        mapper.borrow_mut().write_prg(0x8000, 0b0000_0000);
+        assert_eq!(bus.read_vram(0x2400), 0x11);
+    }
+
+    #[test]
+    fn test_palette_mirroring() {
+        let mut bus = create_bus(Vec::new(), MirroringType::Horizontal);
+        bus.write_vram(0x3F10, 0x0F);
+        assert_eq!(bus.read_vram(0x3F00), 0x0F);
+        assert_eq!(bus.read_vram(0x3F20), 0x0F);
+
+        bus.write_vram(0x3F11, 0x21);
+        assert_eq!(bus.read_vram(0x3F01), 0x00);
+        assert_eq!(bus.read_vram(0x3F31), 0x21);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::mapper::ChrBanks;
+use crate::ppu::registers::mask::PPUMASK;
+use crate::render::frame::{Rect, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::render::overlay::{text_width, Overlay, GLYPH_HEIGHT};
+
+use alloc::vec::Vec;
+
+// https://wiki.nesdev.com/w/index.php/Sprite_overflow_games
+// the hardware shows the first 8 sprites on a scanline and drops the rest, games cycle
+// the oam order so the dropped ones flicker instead of vanishing
+pub const MAX_SPRITES_PER_SCANLINE: u8 = 8;
+
+const HUD_BAR_WIDTH: usize = 2;
+const HUD_SPRITES: [u8; 4] = [0x00, 0xFF, 0x00, 0xA0];
+const HUD_OVERFLOW: [u8; 4] = [0xFF, 0x00, 0x00, 0xC0];
+const HUD_SPLIT: [u8; 4] = [0xFF, 0xFF, 0x00, 0x80];
+const HUD_TEXT: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const HUD_TEXT_BACKGROUND: [u8; 4] = [0x00, 0x00, 0x00, 0xA0];
+
+// what the ppu did during the last finished frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameStats {
+    // sprites in range of each visible scanline
+    pub sprites_per_scanline: Vec<u8>,
+    // scanlines with more than MAX_SPRITES_PER_SCANLINE sprites
+    pub overflow_scanlines: usize,
+    // scanlines on which the game changed the scroll, the nametable or the vram address
+    // while rendering, usually a status bar or a parallax split
+    pub splits: Vec<u16>,
+    // the chr banks each visible scanline started with, one entry per change and the
+    // first one for scanline 0. boards like MMC3 switch banks under a status bar or
+    // every few lines for animated backgrounds
+    pub chr_banks: Vec<(u16, ChrBanks)>,
+    // the greyscale and emphasis bits of PPUMASK each visible scanline started with, the
+    // same way. games flash the screen or darken part of it by changing them mid-frame
+    pub color_effects: Vec<(u16, PPUMASK)>,
+    // the visible scanlines whose sprites were looked for from a nonzero OAMADDR, and
+    // where. a write to it while rendering moves the start, see PPU::sprite_evaluation_dots
+    pub oam_starts: Vec<(u16, u8)>,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        FrameStats {
+            sprites_per_scanline: vec![0; SCREEN_HEIGHT],
+            overflow_scanlines: 0,
+            splits: Vec::new(),
+            chr_banks: Vec::new(),
+            color_effects: Vec::new(),
+            oam_starts: Vec::new(),
+        }
+    }
+
+    // counts sprites the way the renderer places them: one scanline below their oam y
+    pub(crate) fn count_sprites(&mut self, oam: &[u8; 256], sprite_height: usize) {
+        self.sprites_per_scanline
+            .iter_mut()
+            .for_each(|count| *count = 0);
+        for sprite in oam.chunks_exact(4) {
+            let top = sprite[0] as usize + 1;
+            for y in top..(top + sprite_height).min(SCREEN_HEIGHT) {
+                self.sprites_per_scanline[y] += 1;
+            }
+        }
+        self.overflow_scanlines = self
+            .sprites_per_scanline
+            .iter()
+            .filter(|count| **count > MAX_SPRITES_PER_SCANLINE)
+            .count();
+    }
+
+    pub fn max_sprites_per_scanline(&self) -> u8 {
+        self.sprites_per_scanline.iter().copied().max().unwrap_or(0)
+    }
+
+    // the banks `scanline` was drawn with
+    pub fn chr_banks_at(&self, scanline: u16) -> Option<ChrBanks> {
+        self.chr_banks
+            .iter()
+            .take_while(|(start, _)| *start <= scanline)
+            .last()
+            .map(|(_, banks)| *banks)
+    }
+
+    pub fn color_effects_at(&self, scanline: u16) -> PPUMASK {
+        self.color_effects
+            .iter()
+            .take_while(|(start, _)| *start <= scanline)
+            .last()
+            .map(|(_, mask)| *mask)
+            .unwrap_or_else(PPUMASK::empty)
+    }
+
+    // where the sprites of `scanline` start in oam, 0 for most lines
+    pub fn oam_start_at(&self, scanline: u16) -> u8 {
+        self.oam_starts
+            .iter()
+            .find(|(line, _)| *line == scanline)
+            .map_or(0, |(_, start)| *start)
+    }
+
+    // a bar per scanline as long as its sprite count, red past the hardware limit, a
+    // line across each split and a summary in the top right corner
+    pub fn draw_hud(&self, overlay: &mut Overlay) {
+        for (y, count) in self.sprites_per_scanline.iter().enumerate() {
+            let color = if *count > MAX_SPRITES_PER_SCANLINE {
+                HUD_OVERFLOW
+            } else {
+                HUD_SPRITES
+            };
+            let width = *count as usize * HUD_BAR_WIDTH;
+            overlay.fill_rect(Rect::new(0, y, width, 1), color);
+        }
+        for split in self.splits.iter() {
+            overlay.fill_rect(Rect::new(0, *split as usize, SCREEN_WIDTH, 1), HUD_SPLIT);
+        }
+
+        let summary = format!(
+            "SPR {} OVF {} SPLIT {}",
+            self.max_sprites_per_scanline(),
+            self.overflow_scanlines,
+            self.splits.len()
+        );
+        let width = text_width(&summary);
+        let x = SCREEN_WIDTH - width - 2;
+        overlay.fill_rect(
+            Rect::new(x - 1, 0, width + 2, GLYPH_HEIGHT + 1),
+            HUD_TEXT_BACKGROUND,
+        );
+        overlay.text(x, 1, &summary, HUD_TEXT);
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_count_sprites() {
+        let mut oam = [0xFF; 256];
+        // ten sprites at y 20 and one 8 lines further down
+        for sprite in oam.chunks_exact_mut(4).take(10) {
+            sprite[0] = 20;
+        }
+        oam[40] = 28;
+
+        let mut stats = FrameStats::new();
+        stats.count_sprites(&oam, 8);
+        assert_eq!(stats.sprites_per_scanline[20], 0);
+        assert_eq!(stats.sprites_per_scanline[21], 10);
+        assert_eq!(stats.sprites_per_scanline[29], 1);
+        assert_eq!(stats.max_sprites_per_scanline(), 10);
+        assert_eq!(stats.overflow_scanlines, 8);
+
+        // 8x16 sprites cover twice as many lines, y 0xFF hides a sprite
+        stats.count_sprites(&oam, 16);
+        assert_eq!(stats.overflow_scanlines, 16);
+        assert_eq!(stats.sprites_per_scanline[0], 0);
+    }
+}
@@ -0,0 +1,1028 @@
+use crate::config::PowerOnRng;
+use crate::mapper::{ChrBanks, SharedMapper};
+
+use alloc::vec::Vec;
+
+pub mod a12;
+pub mod bus;
+pub mod frame_stats;
+pub mod registers;
+use self::a12::{fetch_a12, sprite_tables, A12Watcher};
+use self::bus::PpuBus;
+use self::frame_stats::{FrameStats, MAX_SPRITES_PER_SCANLINE};
+use self::registers::address::*;
+use self::registers::controller::*;
+use self::registers::data::*;
+use self::registers::mask::*;
+use self::registers::oam_address::*;
+use self::registers::oam_data::*;
+use self::registers::scroll::*;
+use self::registers::status::*;
+use self::registers::BitwiseRegister;
+
+pub const PPU_REG_CTRL: u16 = 0x2000;
+pub const PPU_REG_MASK: u16 = 0x2001;
+pub const PPU_REG_STATUS: u16 = 0x2002;
+pub const PPU_REG_OAMADDR: u16 = 0x2003;
+pub const PPU_REG_OAMDATA: u16 = 0x2004;
+pub const PPU_REG_SCROLL: u16 = 0x2005;
+pub const PPU_REG_ADDR: u16 = 0x2006;
+pub const PPU_REG_DATA: u16 = 0x2007;
+pub const PPU_REG_OAMDMA: u16 = 0x4014;
+
+const SCANLINE_CYCLES_COST: u16 = 341;
+const SCANLINE_TRIGGER_NMI: u16 = 241;
+const SCANLINE_PER_FRAME: u16 = 262;
+const VISIBLE_SCANLINES: u16 = 240;
+
+/*
+https://wiki.nesdev.com/w/index.php/NMI#Race_condition
+    reading $2002 one dot before the vblank flag is set reads it clear, and the flag and the
+    nmi never happen that frame. reading on the dot it is set or one dot later reads it set
+    but still cancels the nmi.
+
+    the flag is set on the first dot of the vblank line here, and a read sees the ppu where
+    the previous instruction left it, so the windows are as wide as the emulation allows.
+*/
+const VBLANK_RACE_DOTS_AFTER: u16 = 2;
+
+// https://wiki.nesdev.com/w/index.php/PPU_frame_timing#Even.2FOdd_Frames
+// with rendering on, the pre-render line of every odd frame skips its last idle dot, so
+// two frames take 1 dot less than 2 * 262 * 341
+const PRE_RENDER_SCANLINE: u16 = SCANLINE_PER_FRAME - 1;
+
+/*
+https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation
+
+    while drawing a line the ppu looks for the sprites of the next one and keeps the first
+    8. finding a 9th sets the overflow flag until the pre-render line. the hardware's
+    search is buggy past the 8th sprite and misses some overflows or reports false ones,
+    this sets the flag whenever a line really has more than 8
+*/
+fn sprite_overflow(oam: &[u8; 256], scanline: u16, height: u16) -> bool {
+    let on_line = oam
+        .chunks_exact(4)
+        .filter(|sprite| scanline.wrapping_sub(sprite[0] as u16) < height)
+        .count();
+    on_line > MAX_SPRITES_PER_SCANLINE as usize
+}
+
+// reads of oam between dot 65 and 256, one every other dot
+const EVALUATION_READS: usize = 96;
+
+// sprite evaluation of `scanline` from oam[start] the way the circuit does it: the address
+// of every read in order, and the secondary oam it fills with the sprites of the next line.
+// past the last sprite it keeps reading y bytes until the time is up. an unaligned start
+// reads every sprite shifted, a tile index or an attribute byte becomes a y position
+fn evaluate_sprites(oam: &[u8; 256], start: u8, scanline: u16, height: u16) -> (Vec<u8>, [u8; 32]) {
+    let mut reads = Vec::with_capacity(EVALUATION_READS + 3);
+    let mut secondary = [0xFF; 32];
+    let mut found = 0;
+    let mut addr = start as usize;
+    while reads.len() < EVALUATION_READS {
+        reads.push(addr as u8);
+        let y = oam[addr & 0xFF] as u16;
+        if addr < 0x100 && found < 8 && scanline.wrapping_sub(y) < height {
+            for i in 0..4 {
+                secondary[found * 4 + i] = oam[(addr + i) & 0xFF];
+            }
+            reads.extend((1..4).map(|i| (addr + i) as u8));
+            found += 1;
+        }
+        addr += 4;
+    }
+    reads.truncate(EVALUATION_READS);
+    (reads, secondary)
+}
+
+#[derive(Clone)]
+pub struct PPU {
+    pub bus: PpuBus,
+    pub oam: [u8; 256],
+
+    // registers from $2000 to $2007
+    pub ctrl_register: PPUCTRL,
+    pub mask_register: PPUMASK,
+    pub status_register: PPUSTATUS,
+    pub oam_address_register: OAMADDR,
+    pub oam_data_register: OAMDATA,
+    pub scroll_register: PPUSCROLL,
+    pub address_register: PPUADDR,
+    pub data_register: PPUDATA,
+    // see Accuracy::oam_glitches
+    oam_glitches: bool,
+    // OAMADDR at dot 65 of the current line, where its sprite evaluation started
+    evaluation_start: u8,
+    // the write toggle of $2005 and $2006, set when the next write to either is the second.
+    // there is one for both, a $2005 write followed by a $2006 one writes the low byte
+    second_write: bool,
+
+    cycles: u16,
+    scanlines: u16,
+    should_nmi_flag: bool,
+    // $2002 was read right before vblank, see VBLANK_RACE_DOTS_AFTER
+    suppress_vblank: bool,
+    odd_frame: bool,
+    frame_complete_flag: bool,
+    internal_last_read_byte: u8,
+    // scanlines of this frame that changed the scroll, see FrameStats::splits
+    splits: Vec<u16>,
+    // chr banks of this frame, see FrameStats::chr_banks
+    chr_banks: Vec<(u16, ChrBanks)>,
+    // see FrameStats::color_effects
+    color_effects: Vec<(u16, PPUMASK)>,
+    // see FrameStats::oam_starts
+    oam_starts: Vec<(u16, u8)>,
+    frame_stats: FrameStats,
+    a12: A12Watcher,
+}
+
+impl PPU {
+    pub fn new(mapper: SharedMapper) -> Self {
+        let bus = PpuBus::new(mapper);
+        // power-on is the start of scanline 0
+        let chr_banks = vec![(0, bus.chr_banks())];
+        PPU {
+            bus: bus,
+            oam: [0; 256],
+
+            ctrl_register: PPUCTRL::new(),
+            mask_register: PPUMASK::new(),
+            status_register: PPUSTATUS::new(),
+            oam_address_register: OAMADDR::new(),
+            oam_data_register: OAMDATA::new(),
+            scroll_register: PPUSCROLL::new(),
+            address_register: PPUADDR::new(),
+            data_register: PPUDATA::new(),
+            oam_glitches: false,
+            evaluation_start: 0,
+            second_write: false,
+
+            cycles: 0,
+            scanlines: 0,
+            should_nmi_flag: false,
+            suppress_vblank: false,
+            odd_frame: false,
+            frame_complete_flag: false,
+            internal_last_read_byte: 0,
+            splits: Vec::new(),
+            chr_banks: chr_banks,
+            color_effects: vec![(0, PPUMASK::empty())],
+            oam_starts: Vec::new(),
+            frame_stats: FrameStats::new(),
+            a12: A12Watcher::new(),
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/PPU_power_up_state
+        after reset: PPUCTRL, PPUMASK and PPUSCROLL are 0, the $2005/$2006 write toggle is
+        cleared, the read buffer is empty and the next frame is an even one. the vram
+        address, OAMADDR, PPUSTATUS, oam, nametables and palette keep what they had
+    */
+    pub fn reset(&mut self) {
+        self.ctrl_register = PPUCTRL::new();
+        self.mask_register = PPUMASK::new();
+        self.scroll_register = PPUSCROLL::new();
+        self.second_write = false;
+        self.internal_last_read_byte = 0;
+        self.odd_frame = false;
+        self.should_nmi_flag = false;
+    }
+
+    pub fn read(&mut self) -> u8 {
+        let addr = self.address_register.get_address();
+        self.address_register
+            .increment_address(self.ctrl_register.get_vram_address_increment());
+
+        let data = self.bus.read_vram(addr);
+        // palette reads are not latched
+        if addr & 0x3FFF < 0x3F00 {
+            self.internal_last_read_byte = data;
+        }
+        self.watch_address_a12();
+        data
+    }
+
+    pub fn write(&mut self, data: u8) {
+        let addr = self.address_register.get_address();
+        self.address_register
+            .increment_address(self.ctrl_register.get_vram_address_increment());
+
+        self.bus.write_vram(addr, data);
+        self.watch_address_a12();
+    }
+
+    pub fn write_oam_data(&mut self, data: u8) {
+        let addr = self.oam_address_register.get_oam_address();
+        if self.oam_glitches && self.is_rendering() {
+            // oam is busy with the sprites, the write is lost and only the sprite number
+            // in the upper 6 bits of OAMADDR moves on
+            self.oam_address_register
+                .write_oam_address(addr.wrapping_add(4));
+            return;
+        }
+        self.oam[addr as usize] = data;
+        self.oam_data_register.write_oam_data(data);
+        self.oam_address_register.increment();
+    }
+
+    pub fn read_oam_data(&self) -> u8 {
+        if self.oam_glitches && self.is_rendering() && self.scanlines < VISIBLE_SCANLINES {
+            return self.evaluation_data();
+        }
+        // reads do not increment OAMADDR
+        self.oam[self.oam_address_register.get_oam_address() as usize]
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation
+    https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDATA
+
+        during a visible line $2004 reads whatever the sprite circuit is reading:
+          dots 1-64     $FF, secondary oam is being cleared
+          dots 65-256   the oam byte sprite evaluation is at
+          dots 257-320  secondary oam, the 8 sprites of the next line as they are fetched
+          dots 321-0    the first byte of secondary oam
+    */
+    fn evaluation_data(&self) -> u8 {
+        let height = self.ctrl_register.get_sprite_size() as u16;
+        let evaluate =
+            || evaluate_sprites(&self.oam, self.evaluation_start, self.scanlines, height);
+        match self.cycles {
+            1..=64 => 0xFF,
+            65..=256 => {
+                let (reads, _) = evaluate();
+                self.oam[reads[(self.cycles - 65) as usize / 2] as usize]
+            }
+            257..=320 => {
+                // y, tile, attributes and then x for the rest of the 8 dots of each sprite
+                let dot = (self.cycles - 257) as usize;
+                let (_, secondary) = evaluate();
+                secondary[dot / 8 * 4 + (dot % 8).min(3)]
+            }
+            _ => evaluate().1[0],
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/PPU_registers#OAMADDR
+
+        sprite evaluation starts at OAMADDR on dot 65 of a visible line, and the sprite
+        fetches of dots 257-320 leave it at 0 on every rendered line. a game writing it
+        while rendering moves the start, sprites before it are skipped for a line and
+        an unaligned one reads the sprites shifted by a byte or more
+    */
+    fn sprite_evaluation_dots(&mut self, from: u16, to: u16) {
+        if from < 65 && to >= 65 && self.scanlines < VISIBLE_SCANLINES {
+            let start = self.oam_address_register.get_oam_address();
+            self.evaluation_start = start;
+            if start != 0 {
+                self.oam_starts.push((self.scanlines + 1, start));
+            }
+        }
+        if from < 257 && to >= 257 {
+            self.oam_address_register.write_oam_address(0);
+        }
+    }
+
+    /*
+    https://wiki.nesdev.com/w/index.php/PPU_registers#OAMADDR
+
+        when rendering starts with OAMADDR at 8 or more, the 8 bytes at OAMADDR & $F8 are
+        copied over the first 8 bytes of oam. seen on the 2C02G, other revisions may differ
+    */
+    fn corrupt_oam_on_render_start(&mut self) {
+        let addr = self.oam_address_register.get_oam_address() as usize;
+        if addr >= 8 {
+            let row = addr & 0xF8;
+            self.oam.copy_within(row..row + 8, 0);
+        }
+    }
+
+    // see Accuracy::oam_glitches
+    pub fn set_oam_glitches(&mut self, on: bool) {
+        self.oam_glitches = on;
+    }
+
+    // https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDMA
+    // the copy starts at OAMADDR and wraps around within oam, leaving OAMADDR where it
+    // was. the bus hands over all 256 bytes at once, before the ppu has run the dma's
+    // cycles, so it skips the rendering time glitch of write_oam_data that would drop it
+    pub fn write_oam_dma(&mut self, data: &[u8; 256]) {
+        let start = self.oam_address_register.get_oam_address();
+        for (i, byte) in data.iter().enumerate() {
+            self.oam[start.wrapping_add(i as u8) as usize] = *byte;
+        }
+        self.oam_data_register.write_oam_data(data[255]);
+    }
+
+    pub fn randomize_memory(&mut self, rng: &mut PowerOnRng) {
+        rng.fill(&mut self.bus.vram);
+        rng.fill(&mut self.oam);
+        rng.fill(&mut self.bus.palette);
+        // palette entries are 6 bits wide
+        for entry in self.bus.palette.iter_mut() {
+            *entry &= 0x3F;
+        }
+    }
+
+    // $2007 without advancing the address
+    pub fn peek(&self) -> u8 {
+        self.bus.read_vram(self.address_register.get_address())
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        self.status_register.get_bits()
+    }
+
+    // https://wiki.nesdev.com/w/index.php/PPU_registers#Controller_.28.242000.29_.3E_write
+    // turning nmi generation on while the vblank flag is still set raises one right away
+    pub fn write_ctrl(&mut self, data: u8) {
+        let nmi_was_enabled = self.ctrl_register.get_generate_nmi();
+        let nametable = self.ctrl_register.get_nametable_address();
+        self.ctrl_register.update_bits(data);
+        if self.ctrl_register.get_nametable_address() != nametable {
+            self.note_split();
+        }
+        if !nmi_was_enabled
+            && self.ctrl_register.get_generate_nmi()
+            && self.status_register.get_vertical_blank()
+        {
+            self.should_nmi_flag = true;
+        }
+    }
+
+    pub fn write_scroll(&mut self, data: u8) {
+        self.scroll_register.write(data, self.second_write);
+        self.second_write = !self.second_write;
+        self.note_split();
+    }
+
+    pub fn write_address(&mut self, data: u8) {
+        self.address_register.write_address(data, self.second_write);
+        self.second_write = !self.second_write;
+        self.note_split();
+        self.watch_address_a12();
+    }
+
+    // outside of rendering the ppu address bus holds the vram address, so games can clock
+    // an MMC3 by pointing $2006 at $1000 and back
+    fn watch_address_a12(&mut self) {
+        if self.bus.watches_a12() && !self.rendering_enabled() {
+            let high = self.address_register.get_address() & 0x1000 != 0;
+            if self.a12.update(high, 0) {
+                self.bus.a12_rising_edge();
+            }
+        }
+    }
+
+    // runs the next `dots` dots past the A12 watcher, see ppu::a12
+    fn watch_a12(&mut self, dots: u16) {
+        if !self.rendering_enabled() {
+            let high = self.address_register.get_address() & 0x1000 != 0;
+            if self.a12.update(high, dots) {
+                self.bus.a12_rising_edge();
+            }
+            return;
+        }
+
+        let background_table = self.ctrl_register.get_background_pattern_table_address();
+        let sprite_table = self.ctrl_register.get_sprite_pattern_table_address();
+        let sprite_height = self.ctrl_register.get_sprite_size() as u16;
+        let (mut scanline, mut dot) = (self.scanlines, self.cycles);
+        let mut tables = sprite_tables(&self.oam, scanline, sprite_height, sprite_table);
+        for _ in 0..dots {
+            let rendered = scanline < VISIBLE_SCANLINES || scanline == PRE_RENDER_SCANLINE;
+            let high = rendered && fetch_a12(dot, background_table, &tables);
+            if self.a12.update(high, 1) {
+                self.bus.a12_rising_edge();
+            }
+            dot += 1;
+            if dot >= self.scanline_dots(scanline) {
+                dot = 0;
+                scanline = (scanline + 1) % SCANLINE_PER_FRAME;
+                tables = sprite_tables(&self.oam, scanline, sprite_height, sprite_table);
+            }
+        }
+    }
+
+    // scroll changes outside of the visible scanlines only set up the next frame
+    fn note_split(&mut self) {
+        if self.scanlines >= SCANLINE_TRIGGER_NMI - 1 || !self.rendering_enabled() {
+            return;
+        }
+        if self.splits.last() != Some(&self.scanlines) {
+            self.splits.push(self.scanlines);
+        }
+    }
+
+    // checked once per scanline, boards switch chr banks between lines (MMC3 from its
+    // scanline irq) so this is as fine as a timeline needs to be
+    fn note_chr_banks(&mut self) {
+        if self.scanlines >= SCANLINE_TRIGGER_NMI - 1 {
+            return;
+        }
+        let banks = self.bus.chr_banks();
+        if self.chr_banks.last().map(|(_, last)| last) != Some(&banks) {
+            self.chr_banks.push((self.scanlines, banks));
+        }
+    }
+
+    // also once per scanline, a change shows from the next line on
+    fn note_color_effects(&mut self) {
+        if self.scanlines >= SCANLINE_TRIGGER_NMI - 1 {
+            return;
+        }
+        let effects = self.mask_register & PPUMASK::COLOR_EFFECTS;
+        if self.color_effects.last().map(|(_, last)| last) != Some(&effects) {
+            self.color_effects.push((self.scanlines, effects));
+        }
+    }
+
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        if self.scanlines == SCANLINE_TRIGGER_NMI - 1 && self.cycles == SCANLINE_CYCLES_COST - 1 {
+            self.suppress_vblank = true;
+        }
+        if self.scanlines == SCANLINE_TRIGGER_NMI && self.cycles < VBLANK_RACE_DOTS_AFTER {
+            self.should_nmi_flag = false;
+        }
+
+        let status = self.status_register.get_bits();
+        // reading $2002 clears vblank and the write toggle shared by $2005/$2006
+        self.status_register.set_vertical_blank(false);
+        self.second_write = false;
+        status
+    }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask_register.get_show_background() || self.mask_register.get_show_sprites()
+    }
+
+    // the ppu is fetching for a visible line, $2007 and oam accesses corrupt the picture
+    pub fn is_rendering(&self) -> bool {
+        let rendered = self.scanlines < VISIBLE_SCANLINES || self.scanlines == PRE_RENDER_SCANLINE;
+        rendered && self.rendering_enabled()
+    }
+
+    fn scanline_dots(&self, scanline: u16) -> u16 {
+        if scanline == PRE_RENDER_SCANLINE && self.odd_frame && self.rendering_enabled() {
+            SCANLINE_CYCLES_COST - 1
+        } else {
+            SCANLINE_CYCLES_COST
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u16) {
+        if self.bus.watches_a12() {
+            self.watch_a12(cycles);
+        }
+        let from = self.cycles;
+        self.cycles += cycles;
+        if self.oam_glitches && self.is_rendering() {
+            self.sprite_evaluation_dots(from, self.cycles);
+        }
+
+        let scanline_dots = self.scanline_dots(self.scanlines);
+        if self.cycles >= scanline_dots {
+            self.cycles -= scanline_dots;
+            self.scanlines += 1;
+
+            if self.scanlines == SCANLINE_TRIGGER_NMI {
+                if !self.suppress_vblank {
+                    self.status_register.set_vertical_blank(true);
+                    if self.ctrl_register.get_generate_nmi() {
+                        self.should_nmi_flag = true;
+                    }
+                }
+                self.suppress_vblank = false;
+                self.status_register.set_sprite_zero_hit(false);
+
+                // the visible part of the picture is done
+                self.frame_complete_flag = true;
+                let sprite_height = self.ctrl_register.get_sprite_size() as usize;
+                self.frame_stats.count_sprites(&self.oam, sprite_height);
+                self.frame_stats.splits = core::mem::take(&mut self.splits);
+                self.frame_stats.chr_banks = core::mem::take(&mut self.chr_banks);
+                self.frame_stats.color_effects = core::mem::take(&mut self.color_effects);
+                self.frame_stats.oam_starts = core::mem::take(&mut self.oam_starts);
+            }
+
+            if self.scanlines == PRE_RENDER_SCANLINE
+                && self.oam_glitches
+                && self.rendering_enabled()
+            {
+                self.corrupt_oam_on_render_start();
+            }
+
+            if self.scanlines >= SCANLINE_PER_FRAME {
+                self.scanlines = 0;
+                self.odd_frame = !self.odd_frame;
+                self.should_nmi_flag = false;
+                self.status_register.set_sprite_zero_hit(false);
+                self.status_register.set_sprite_overflow(false);
+                self.status_register.set_vertical_blank(false);
+            }
+
+            let height = self.ctrl_register.get_sprite_size() as u16;
+            if self.scanlines < VISIBLE_SCANLINES
+                && self.rendering_enabled()
+                && sprite_overflow(&self.oam, self.scanlines, height)
+            {
+                self.status_register.set_sprite_overflow(true);
+            }
+            self.note_chr_banks();
+            self.note_color_effects();
+        }
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanlines
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.cycles
+    }
+
+    // the scanline vblank starts on
+    pub fn on_vblank_scanline(&self) -> bool {
+        self.scanlines == SCANLINE_TRIGGER_NMI
+    }
+
+    pub fn take_frame_complete(&mut self) -> bool {
+        let flag = self.frame_complete_flag;
+        self.frame_complete_flag = false;
+        flag
+    }
+
+    // the cpu takes the nmi before its next instruction
+    pub fn nmi_pending(&self) -> bool {
+        self.should_nmi_flag
+    }
+
+    pub fn should_nmi(&mut self) -> bool {
+        if self.should_nmi_flag {
+            self.should_nmi_flag = false;
+            return true;
+        }
+        return false;
+    }
+
+    // the scroll the way ppus that work like the hardware keep it: the vram address `v`,
+    // the temporary address `t` with coarse scroll and nametable, fine x and the write
+    // toggle shared by $2005 and $2006. for savestates of other emulators
+    pub fn set_scroll_state(&mut self, v: u16, t: u16, fine_x: u8, second_write: bool) {
+        let x = ((t & 0x1F) << 3) as u8 | fine_x & 0x07;
+        let y = (((t >> 5) & 0x1F) << 3) as u8 | ((t >> 12) & 0x07) as u8;
+        self.scroll_register.set(x, y);
+        self.address_register.set(v);
+        self.second_write = second_write;
+        let ctrl = self.ctrl_register.bits() & !0x03 | ((t >> 10) & 0x03) as u8;
+        self.ctrl_register.update_bits(ctrl);
+    }
+
+    pub fn set_read_buffer(&mut self, data: u8) {
+        self.internal_last_read_byte = data;
+    }
+
+    // oam, registers, timing, the A12 filter, then nametables and palette. the timelines
+    // of the frame in progress are left out, see load_state
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&self.oam);
+        state.extend_from_slice(&[
+            self.ctrl_register.bits(),
+            self.mask_register.bits(),
+            self.status_register.bits(),
+            self.oam_address_register.get_oam_address(),
+            self.oam_data_register.read_oam_data(),
+            self.data_register.read_data(),
+        ]);
+        self.scroll_register.save_state(state);
+        self.address_register.save_state(state);
+        state.extend_from_slice(&self.cycles.to_le_bytes());
+        state.extend_from_slice(&self.scanlines.to_le_bytes());
+        state.extend_from_slice(&[
+            self.should_nmi_flag as u8,
+            self.suppress_vblank as u8,
+            self.odd_frame as u8,
+            self.frame_complete_flag as u8,
+            self.internal_last_read_byte,
+            self.second_write as u8,
+        ]);
+        self.a12.save_state(state);
+        state.extend_from_slice(&self.bus.vram);
+        state.extend_from_slice(&self.bus.palette);
+    }
+
+    // returns the bytes after its own. the frame in progress starts its split, chr bank and
+    // color effect timelines over at the current scanline
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.oam.copy_from_slice(&state[..256]);
+        self.ctrl_register = PPUCTRL::from_bits_truncate(state[256]);
+        self.mask_register = PPUMASK::from_bits_truncate(state[257]);
+        self.status_register = PPUSTATUS::from_bits_truncate(state[258]);
+        self.oam_address_register.write_oam_address(state[259]);
+        self.oam_data_register.write_oam_data(state[260]);
+        self.data_register.write_data(state[261]);
+        let state = self.scroll_register.load_state(&state[262..]);
+        let state = self.address_register.load_state(state);
+        self.cycles = u16::from_le_bytes([state[0], state[1]]);
+        self.scanlines = u16::from_le_bytes([state[2], state[3]]);
+        self.should_nmi_flag = state[4] != 0;
+        self.suppress_vblank = state[5] != 0;
+        self.odd_frame = state[6] != 0;
+        self.frame_complete_flag = state[7] != 0;
+        self.internal_last_read_byte = state[8];
+        self.second_write = state[9] != 0;
+        let state = self.a12.load_state(&state[10..]);
+        let vram = self.bus.vram.len();
+        self.bus.vram.copy_from_slice(&state[..vram]);
+        self.bus.palette.copy_from_slice(&state[vram..vram + 32]);
+
+        self.splits.clear();
+        self.chr_banks = vec![(self.scanlines, self.bus.chr_banks())];
+        self.color_effects = vec![(self.scanlines, self.mask_register & PPUMASK::COLOR_EFFECTS)];
+        &state[vram + 32..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::MirroringType;
+    use crate::mapper::nrom::NROM;
+    use crate::mapper::{banked_offset, Mapper, CHR_WINDOW_SIZE};
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    // a board that switches each 1KB chr window with a write to $8000-$8007
+    struct BankedChr {
+        chr: Vec<u8>,
+        banks: ChrBanks,
+    }
+
+    impl Mapper for BankedChr {
+        fn read_prg(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn write_prg(&mut self, addr: u16, data: u8) {
+            self.banks[addr as usize % 8] = data as usize;
+        }
+
+        fn read_chr(&self, addr: u16) -> u8 {
+            let addr = addr as usize;
+            let bank = self.banks[addr / CHR_WINDOW_SIZE];
+            self.chr[banked_offset(self.chr.len(), CHR_WINDOW_SIZE, bank, addr)]
+        }
+
+        fn write_chr(&mut self, _addr: u16, _data: u8) {}
+
+        fn chr(&self) -> &[u8] {
+            &self.chr
+        }
+
+        fn chr_banks(&self) -> ChrBanks {
+            self.banks
+        }
+
+        fn mirroring(&self) -> MirroringType {
+            MirroringType::Horizontal
+        }
+
+        fn save_state(&self) -> Vec<u8> {
+            Vec::new()
+        }
+
+        fn load_state(&mut self, _state: &[u8]) {}
+    }
+
+    fn create_ppu() -> PPU {
+        let mapper: Box<dyn Mapper> = Box::new(NROM::new(
+            vec![0; 0x4000],
+            vec![0; 0x2000],
+            MirroringType::Horizontal,
+        ));
+        PPU::new(Arc::new(AtomicRefCell::new(mapper)))
+    }
+
+    // tick only handles one scanline at a time
+    fn tick_dots(ppu: &mut PPU, dots: usize) {
+        for _ in 0..dots {
+            ppu.tick(1);
+        }
+    }
+
+    // runs the ppu up to the given dot of the scanline before vblank
+    fn tick_to_vblank_line(ppu: &mut PPU, dot: u16) {
+        let line = (SCANLINE_TRIGGER_NMI - 1) as usize * SCANLINE_CYCLES_COST as usize;
+        tick_dots(ppu, line + dot as usize);
+    }
+
+    #[test]
+    fn test_nmi_enabled_during_vblank() {
+        let mut ppu = create_ppu();
+        tick_to_vblank_line(&mut ppu, SCANLINE_CYCLES_COST);
+        assert!(ppu.status_register.get_vertical_blank());
+        assert!(!ppu.should_nmi());
+
+        ppu.write_ctrl(0b1000_0000);
+        assert!(ppu.should_nmi());
+        // only the change from off to on raises it
+        ppu.write_ctrl(0b1000_0000);
+        assert!(!ppu.should_nmi());
+
+        // once the flag is read the window is gone
+        ppu.write_ctrl(0);
+        ppu.read_status();
+        ppu.write_ctrl(0b1000_0000);
+        assert!(!ppu.should_nmi());
+    }
+
+    #[test]
+    fn test_status_read_before_vblank() {
+        let mut ppu = create_ppu();
+        ppu.write_ctrl(0b1000_0000);
+        tick_to_vblank_line(&mut ppu, SCANLINE_CYCLES_COST - 1);
+        assert_eq!(ppu.read_status() & 0b1000_0000, 0);
+
+        ppu.tick(1);
+        assert!(!ppu.status_register.get_vertical_blank());
+        assert!(!ppu.should_nmi());
+        assert!(ppu.take_frame_complete());
+
+        // the next frame is not affected
+        tick_dots(
+            &mut ppu,
+            SCANLINE_PER_FRAME as usize * SCANLINE_CYCLES_COST as usize,
+        );
+        assert!(ppu.status_register.get_vertical_blank());
+        assert!(ppu.should_nmi());
+    }
+
+    #[test]
+    fn test_status_read_as_vblank_starts() {
+        let mut ppu = create_ppu();
+        ppu.write_ctrl(0b1000_0000);
+        tick_to_vblank_line(&mut ppu, SCANLINE_CYCLES_COST);
+        assert_eq!(ppu.read_status() & 0b1000_0000, 0b1000_0000);
+        assert!(!ppu.should_nmi());
+
+        let mut ppu = create_ppu();
+        ppu.write_ctrl(0b1000_0000);
+        tick_to_vblank_line(&mut ppu, SCANLINE_CYCLES_COST + VBLANK_RACE_DOTS_AFTER);
+        ppu.read_status();
+        assert!(ppu.should_nmi());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut ppu = create_ppu();
+        ppu.write_address(0x23);
+        ppu.write_address(0x45);
+        ppu.write(0x99);
+        ppu.write_address(0x23);
+        ppu.write_address(0x45);
+        ppu.read();
+        // a scroll and half of an address
+        ppu.write_scroll(12);
+        ppu.write_scroll(34);
+        ppu.write_address(0x24);
+        ppu.write_ctrl(0b1000_0001);
+        ppu.mask_register.update_bits(0b0001_1110);
+        ppu.oam_address_register.write_oam_address(0x20);
+
+        ppu.reset();
+        assert_eq!(ppu.ctrl_register.bits(), 0);
+        assert!(!ppu.rendering_enabled());
+        assert_eq!(ppu.scroll_register.get_scroll(), (0, 0));
+        assert_eq!(ppu.internal_last_read_byte, 0);
+        // the address stays where it was, the next $2006 write is the high byte again
+        assert_eq!(ppu.address_register.get_address(), 0x2400);
+        assert_eq!(ppu.oam_address_register.get_oam_address(), 0x20);
+        ppu.write_address(0x23);
+        ppu.write_address(0x45);
+        assert_eq!(ppu.peek(), 0x99);
+    }
+
+    #[test]
+    fn test_shared_write_toggle() {
+        let mut ppu = create_ppu();
+        ppu.write_address(0x21);
+        ppu.write_address(0x00);
+        // $2005 takes the first write and $2006 the second, which is only the low byte
+        ppu.write_scroll(0x08);
+        ppu.write_address(0x45);
+        assert_eq!(ppu.scroll_register.get_scroll(), (0x08, 0));
+        assert_eq!(ppu.address_register.get_address(), 0x2145);
+
+        // $2006 takes the first write and $2005 the second, the y scroll
+        ppu.write_address(0x23);
+        ppu.write_scroll(0x10);
+        assert_eq!(ppu.scroll_register.get_scroll(), (0x08, 0x10));
+        assert_eq!(ppu.address_register.get_address(), 0x2300);
+
+        // reading $2002 halfway starts over at the first write of either
+        ppu.write_scroll(0x20);
+        ppu.read_status();
+        ppu.write_scroll(0x30);
+        assert_eq!(ppu.scroll_register.get_scroll(), (0x30, 0x10));
+        ppu.read_status();
+        ppu.write_address(0x24);
+        ppu.write_address(0x10);
+        assert_eq!(ppu.address_register.get_address(), 0x2410);
+    }
+
+    #[test]
+    fn test_oam_dma_is_not_glitched() {
+        let mut ppu = create_ppu();
+        ppu.set_oam_glitches(true);
+        ppu.mask_register.update_bits(0b0001_1000);
+        let mut data = [0u8; 256];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        // the copy lands whole while rendering as well as outside of it
+        tick_dots(&mut ppu, 100);
+        assert!(ppu.is_rendering());
+        ppu.oam_address_register.write_oam_address(0x10);
+        ppu.write_oam_dma(&data);
+        assert_eq!(ppu.oam[0x10], 0x00);
+        assert_eq!(ppu.oam[0x0F], 0xFF);
+        assert_eq!(ppu.oam_address_register.get_oam_address(), 0x10);
+
+        ppu.mask_register.update_bits(0);
+        ppu.oam_address_register.write_oam_address(0);
+        ppu.write_oam_dma(&data);
+        assert_eq!(ppu.oam, data);
+    }
+
+    #[test]
+    fn test_oam_glitches() {
+        let mut ppu = create_ppu();
+        ppu.set_oam_glitches(true);
+        ppu.mask_register.update_bits(0b0001_1000);
+        ppu.oam = [0xFF; 256];
+        ppu.oam[..4].copy_from_slice(&[0, 0x11, 0x22, 0x33]);
+        for (i, byte) in ppu.oam[0x20..0x28].iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        let line = SCANLINE_CYCLES_COST as usize;
+
+        // secondary oam is cleared, then sprite 0's y and tile are read for line 1
+        tick_dots(&mut ppu, 10);
+        assert_eq!(ppu.read_oam_data(), 0xFF);
+        tick_dots(&mut ppu, 57);
+        assert_eq!(ppu.read_oam_data(), 0x11);
+        // a write does not reach oam but moves on to the next sprite
+        ppu.write_oam_data(0x99);
+        assert_eq!(ppu.oam[0], 0);
+        assert_eq!(ppu.oam_address_register.get_oam_address(), 4);
+
+        // the sprite fetches read secondary oam and leave OAMADDR at 0
+        tick_dots(&mut ppu, 191);
+        assert_eq!(ppu.read_oam_data(), 0x11);
+        assert_eq!(ppu.oam_address_register.get_oam_address(), 0);
+        tick_dots(&mut ppu, 7);
+        assert_eq!(ppu.read_oam_data(), 0xFF);
+
+        // the sprites of line 2 are looked for from byte 5 on
+        ppu.oam_address_register.write_oam_address(0x05);
+        tick_dots(&mut ppu, line - 265 + 66);
+        tick_dots(
+            &mut ppu,
+            SCANLINE_TRIGGER_NMI as usize * line + 1 - (line + 66),
+        );
+        assert_eq!(ppu.frame_stats().oam_starts, vec![(2, 5)]);
+        assert_eq!(ppu.frame_stats().oam_start_at(2), 5);
+        assert_eq!(ppu.frame_stats().oam_start_at(3), 0);
+
+        // rendering starts with OAMADDR at $21, the row at $20 lands on sprites 0 and 1
+        ppu.oam_address_register.write_oam_address(0x21);
+        tick_dots(
+            &mut ppu,
+            (PRE_RENDER_SCANLINE - SCANLINE_TRIGGER_NMI) as usize * line,
+        );
+        assert_eq!(ppu.oam[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_odd_frame_skips_a_dot() {
+        let three_frames = 3 * SCANLINE_PER_FRAME as usize * SCANLINE_CYCLES_COST as usize;
+
+        let mut ppu = create_ppu();
+        tick_dots(&mut ppu, three_frames);
+        assert_eq!((ppu.scanline(), ppu.dot()), (0, 0));
+
+        // even, odd (one dot short), even: one dot into the fourth frame
+        let mut ppu = create_ppu();
+        ppu.mask_register.update_bits(0b0000_1000);
+        tick_dots(&mut ppu, three_frames);
+        assert_eq!((ppu.scanline(), ppu.dot()), (0, 1));
+    }
+
+    #[test]
+    fn test_frame_stats_splits() {
+        let mut ppu = create_ppu();
+        ppu.mask_register.update_bits(0b0000_1000);
+        // a scroll change and a nametable plus $2006 change further down, the write
+        // during vblank only sets up the next frame
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize * 30);
+        ppu.write_scroll(0);
+        ppu.write_scroll(0);
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize * 70);
+        ppu.write_ctrl(0b0000_0001);
+        ppu.write_address(0x20);
+        ppu.write_address(0x00);
+        ppu.write_ctrl(0b0000_0001);
+        tick_to_vblank_line(&mut ppu, 0);
+        ppu.write_scroll(0);
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize);
+
+        assert!(ppu.take_frame_complete());
+        assert_eq!(ppu.frame_stats().splits, vec![30, 100]);
+    }
+
+    #[test]
+    fn test_frame_stats_chr_banks() {
+        let mapper: Box<dyn Mapper> = Box::new(BankedChr {
+            chr: vec![0; 0x8000],
+            banks: [0, 1, 2, 3, 4, 5, 6, 7],
+        });
+        let mapper = Arc::new(AtomicRefCell::new(mapper));
+        let mut ppu = PPU::new(mapper.clone());
+
+        // a switch under a status bar, one that changes nothing and one in vblank that
+        // already counts for the next frame
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize * 31 + 260);
+        mapper.borrow_mut().write_prg(0x8004, 20);
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize * 50);
+        mapper.borrow_mut().write_prg(0x8004, 20);
+        tick_to_vblank_line(&mut ppu, 1);
+        mapper.borrow_mut().write_prg(0x8000, 16);
+        tick_dots(&mut ppu, SCANLINE_CYCLES_COST as usize);
+
+        let stats = ppu.frame_stats();
+        let switched = [0, 1, 2, 3, 20, 5, 6, 7];
+        assert_eq!(
+            stats.chr_banks,
+            vec![(0, [0, 1, 2, 3, 4, 5, 6, 7]), (32, switched)]
+        );
+        assert_eq!(stats.chr_banks_at(31), Some([0, 1, 2, 3, 4, 5, 6, 7]));
+        assert_eq!(stats.chr_banks_at(200), Some(switched));
+        assert_eq!(ppu.bus.chr_banks(), [16, 1, 2, 3, 20, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let line = SCANLINE_CYCLES_COST as usize;
+        let overflow = |ppu: &PPU| ppu.status_register.contains(PPUSTATUS::SPR_OVERFLOW);
+        let mut ppu = create_ppu();
+        ppu.mask_register.update_bits(0b0001_0000);
+        // nine sprites found while drawing line 20, the rest below the screen
+        ppu.oam = [0xFF; 256];
+        for sprite in ppu.oam.chunks_exact_mut(4).take(9) {
+            sprite[0] = 20;
+        }
+
+        tick_dots(&mut ppu, 19 * line);
+        assert!(!overflow(&ppu));
+        tick_dots(&mut ppu, line);
+        assert!(overflow(&ppu));
+        tick_dots(&mut ppu, (SCANLINE_PER_FRAME - 20) as usize * line);
+        assert!(!overflow(&ppu));
+    }
+
+    #[test]
+    fn test_frame_stats_color_effects() {
+        let line = SCANLINE_CYCLES_COST as usize;
+        let mut ppu = create_ppu();
+
+        // written in the middle of lines 100, 150 and 160, each shows from the next line
+        tick_dots(&mut ppu, 100 * line + 200);
+        ppu.mask_register.update_bits(0b1001_1000);
+        tick_dots(&mut ppu, 50 * line);
+        ppu.mask_register.update_bits(0b1001_1001);
+        tick_dots(&mut ppu, 10 * line);
+        ppu.mask_register.update_bits(0b1001_1000);
+        tick_to_vblank_line(&mut ppu, 1);
+
+        let stats = ppu.frame_stats();
+        assert_eq!(stats.color_effects.len(), 4);
+        assert_eq!(stats.color_effects_at(100), PPUMASK::empty());
+        assert_eq!(stats.color_effects_at(101).emphasis(), 0b100);
+        assert!(!stats.color_effects_at(150).get_grey_scale());
+        assert!(stats.color_effects_at(151).get_grey_scale());
+        assert!(stats.color_effects_at(160).get_grey_scale());
+        assert!(!stats.color_effects_at(161).get_grey_scale());
+
+        let mut rgba = [200, 100, 50, 255];
+        crate::render::palette::emphasize(&mut rgba, stats.color_effects_at(239).emphasis());
+        assert_eq!(rgba, [163, 81, 50, 255]);
+    }
+}
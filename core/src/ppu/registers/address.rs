@@ -0,0 +1,63 @@
+/*
+https://wiki.nesdev.com/w/index.php/PPU_registers#PPUADDR
+    Address ($2006) >> write x2
+    Common name: PPUADDR
+    Description: PPU address register
+    Access: write twice
+    the first write is the high byte, the second the low one. which one is next is kept by
+    the ppu, the toggle is shared with PPUSCROLL
+*/
+use alloc::vec::Vec;
+
+#[derive(Clone)]
+pub struct PPUADDR {
+    vram_addr: u16,
+}
+
+impl PPUADDR {
+    pub fn new() -> Self {
+        PPUADDR { vram_addr: 0 }
+    }
+
+    pub fn get_address(&self) -> u16 {
+        self.vram_addr
+    }
+
+    pub fn write_address(&mut self, addr: u8, second_write: bool) {
+        if second_write {
+            // the high byte stays, a $2005 write in between may have left any low byte
+            self.vram_addr = self.vram_addr & 0xFF00 | addr as u16;
+        } else {
+            self.vram_addr = (addr as u16) << 8;
+        }
+
+        self.mirror_down();
+    }
+
+    pub fn increment_address(&mut self, inc: u8) {
+        self.vram_addr = self.vram_addr.wrapping_add(inc as u16);
+
+        self.mirror_down();
+    }
+
+    fn mirror_down(&mut self) {
+        if self.vram_addr > 0x3FFF {
+            self.vram_addr &= 0x3FFF;
+        }
+    }
+
+    pub fn set(&mut self, addr: u16) {
+        self.vram_addr = addr;
+        self.mirror_down();
+    }
+
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&self.vram_addr.to_le_bytes());
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.vram_addr = u16::from_le_bytes([state[0], state[1]]);
+        &state[2..]
+    }
+}
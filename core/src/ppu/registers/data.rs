@@ -5,6 +5,7 @@ https://wiki.nesdev.com/w/index.php/PPU_registers#PPUDATA
     Description: PPU data port
     Access: read, write
 */
+#[derive(Clone)]
 pub struct PPUDATA {
     data: u8,
 }
@@ -29,12 +29,25 @@ bitflags::bitflags! {
         const SHOW_BG     = 0b0000_1000;
         const SHOW_SPR    = 0b0001_0000;
         const EMPHA_RED   = 0b0010_0000;
-        const EMPHA_GREEN = 0b0010_0000;
-        const EMPHA_BLUE  = 0b0010_0000;
+        const EMPHA_GREEN = 0b0100_0000;
+        const EMPHA_BLUE  = 0b1000_0000;
     }
 }
 
 impl PPUMASK {
+    // the bits that change colors rather than what is drawn
+    pub const COLOR_EFFECTS: PPUMASK = PPUMASK::from_bits_truncate(
+        PPUMASK::GREY_SCALE.bits
+            | PPUMASK::EMPHA_RED.bits
+            | PPUMASK::EMPHA_GREEN.bits
+            | PPUMASK::EMPHA_BLUE.bits,
+    );
+
+    // red, green and blue emphasis as bits 0-2
+    pub fn emphasis(&self) -> u8 {
+        self.bits >> 5
+    }
+
     pub fn new() -> Self {
         PPUMASK::from_bits_truncate(0b0000_0000)
     }
@@ -6,6 +6,7 @@ https://wiki.nesdev.com/w/index.php/PPU_registers#OAMADDR
     Access: write
 */
 
+#[derive(Clone)]
 pub struct OAMADDR {
     oam_address: u8,
 }
@@ -18,4 +19,12 @@ impl OAMADDR {
     pub fn write_oam_address(&mut self, addr: u8) {
         self.oam_address = addr;
     }
+
+    pub fn get_oam_address(&self) -> u8 {
+        self.oam_address
+    }
+
+    pub fn increment(&mut self) {
+        self.oam_address = self.oam_address.wrapping_add(1);
+    }
 }
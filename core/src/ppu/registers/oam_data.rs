@@ -5,6 +5,7 @@ https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDATA
     Description: OAM data port
     Access: read, write
 */
+#[derive(Clone)]
 pub struct OAMDATA {
     oam_data: u8,
 }
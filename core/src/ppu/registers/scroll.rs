@@ -0,0 +1,54 @@
+/*
+https://wiki.nesdev.com/w/index.php/PPU_registers#PPUSCROLL
+    Scroll ($2005) >> write x2
+    Common name: PPUSCROLL
+    Description: PPU scrolling position register
+    Access: write twice
+    the first write is x, the second y. which one is next is kept by the ppu, the toggle is
+    shared with PPUADDR
+*/
+
+use alloc::vec::Vec;
+
+#[derive(Clone)]
+pub struct PPUSCROLL {
+    cam_position_x: u8,
+    cam_position_y: u8,
+}
+
+impl PPUSCROLL {
+    pub fn new() -> Self {
+        PPUSCROLL {
+            cam_position_x: 0,
+            cam_position_y: 0,
+        }
+    }
+
+    pub fn write(&mut self, cam_position: u8, second_write: bool) {
+        if second_write {
+            self.cam_position_y = cam_position;
+        } else {
+            self.cam_position_x = cam_position;
+        }
+    }
+
+    pub fn get_scroll(&self) -> (u8, u8) {
+        (self.cam_position_x, self.cam_position_y)
+    }
+
+    pub fn set(&mut self, x: u8, y: u8) {
+        self.cam_position_x = x;
+        self.cam_position_y = y;
+    }
+
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[self.cam_position_x, self.cam_position_y]);
+    }
+
+    // returns the bytes after its own
+    pub fn load_state<'a>(&mut self, state: &'a [u8]) -> &'a [u8] {
+        self.cam_position_x = state[0];
+        self.cam_position_y = state[1];
+        &state[2..]
+    }
+}
@@ -0,0 +1,419 @@
+use crate::cpu::hooks::{Hooks, InstructionEvent, InterruptEvent};
+use crate::csv;
+use crate::json::{self, Value};
+use crate::opcode;
+use crate::symbols::Symbols;
+use crate::sync::AtomicRefCell;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+// games that return with a jump or pull their return address never pop their frame,
+// past this depth the oldest frames are given up on
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SubroutineCounters {
+    calls: u64,
+    inclusive_cycles: u64,
+    exclusive_cycles: u64,
+}
+
+#[derive(Clone, Copy)]
+struct CallFrame {
+    address: u16,
+    // total cycles when it was entered
+    entered: u64,
+    interrupt: bool,
+}
+
+struct Counters {
+    // per pc
+    instructions: Vec<u64>,
+    cycles: Vec<u64>,
+    // per opcode byte
+    opcodes: Vec<u64>,
+    total_instructions: u64,
+    total_cycles: u64,
+    subroutines: BTreeMap<u16, SubroutineCounters>,
+    call_stack: Vec<CallFrame>,
+    // a JSR ran, its target is the pc of the next instruction
+    pending_call: bool,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Counters {
+            instructions: vec![0; 0x10000],
+            cycles: vec![0; 0x10000],
+            opcodes: vec![0; 0x100],
+            total_instructions: 0,
+            total_cycles: 0,
+            subroutines: BTreeMap::new(),
+            call_stack: Vec::new(),
+            pending_call: false,
+        }
+    }
+
+    fn enter(&mut self, address: u16, interrupt: bool) {
+        if self.call_stack.len() == MAX_CALL_DEPTH {
+            self.call_stack.remove(0);
+        }
+        self.call_stack.push(CallFrame {
+            address: address,
+            entered: self.total_cycles,
+            interrupt: interrupt,
+        });
+        self.subroutines.entry(address).or_default().calls += 1;
+    }
+
+    fn leave(&mut self) {
+        if let Some(frame) = self.call_stack.pop() {
+            let subroutine = self.subroutines.entry(frame.address).or_default();
+            subroutine.inclusive_cycles += self.total_cycles - frame.entered;
+        }
+    }
+
+    fn instruction(&mut self, event: &InstructionEvent) {
+        if self.pending_call {
+            self.pending_call = false;
+            self.enter(event.pc, false);
+        }
+
+        let cycles = event.cycles as u64;
+        self.instructions[event.pc as usize] += 1;
+        self.cycles[event.pc as usize] += cycles;
+        self.opcodes[event.opcode as usize] += 1;
+        self.total_instructions += 1;
+        self.total_cycles += cycles;
+        if let Some(frame) = self.call_stack.last() {
+            self.subroutines
+                .entry(frame.address)
+                .or_default()
+                .exclusive_cycles += cycles;
+        }
+
+        match event.opcode {
+            JSR => self.pending_call = true,
+            // an RTS out of an interrupt handler is a stack trick, not a return
+            RTS if self.call_stack.last().is_some_and(|frame| !frame.interrupt) => {
+                self.leave();
+            }
+            RTI => {
+                // subroutines the handler left without returning end with it
+                while let Some(frame) = self.call_stack.last().copied() {
+                    self.leave();
+                    if frame.interrupt {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn interrupt(&mut self, event: &InterruptEvent) {
+        if self.pending_call {
+            // the interrupt hit right after the JSR, before the subroutine's first opcode
+            self.pending_call = false;
+            self.enter(event.return_pc, false);
+        }
+        self.enter(event.handler_pc, true);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotSpot {
+    pub pc: u16,
+    pub label: Option<String>,
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subroutine {
+    // the JSR target or interrupt handler
+    pub address: u16,
+    pub label: Option<String>,
+    pub calls: u64,
+    // with and without the subroutines it calls
+    pub inclusive_cycles: u64,
+    pub exclusive_cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpcodeCount {
+    pub opcode: u8,
+    pub name: &'static str,
+    pub count: u64,
+}
+
+// everything sorted by cycles (opcodes by count), hottest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub hot_spots: Vec<HotSpot>,
+    pub subroutines: Vec<Subroutine>,
+    pub opcodes: Vec<OpcodeCount>,
+}
+
+// for scripts and spreadsheets, addresses and counts as plain decimal numbers and a
+// missing label as null (json) or an empty field (csv)
+impl Report {
+    pub fn to_json(&self) -> Value {
+        let hot_spots = self.hot_spots.iter().map(|hot_spot| {
+            json::object(vec![
+                ("pc", Value::from(hot_spot.pc)),
+                ("label", Value::from(hot_spot.label.as_deref())),
+                ("instructions", Value::from(hot_spot.instructions)),
+                ("cycles", Value::from(hot_spot.cycles)),
+            ])
+        });
+        let subroutines = self.subroutines.iter().map(|subroutine| {
+            json::object(vec![
+                ("address", Value::from(subroutine.address)),
+                ("label", Value::from(subroutine.label.as_deref())),
+                ("calls", Value::from(subroutine.calls)),
+                ("inclusive_cycles", Value::from(subroutine.inclusive_cycles)),
+                ("exclusive_cycles", Value::from(subroutine.exclusive_cycles)),
+            ])
+        });
+        let opcodes = self.opcodes.iter().map(|count| {
+            json::object(vec![
+                ("opcode", Value::from(count.opcode)),
+                ("name", Value::from(count.name)),
+                ("count", Value::from(count.count)),
+            ])
+        });
+        json::object(vec![
+            ("instructions", Value::from(self.instructions)),
+            ("cycles", Value::from(self.cycles)),
+            ("hot_spots", Value::Array(hot_spots.collect())),
+            ("subroutines", Value::Array(subroutines.collect())),
+            ("opcodes", Value::Array(opcodes.collect())),
+        ])
+    }
+
+    // one table per csv, each with a header row
+    pub fn hot_spots_csv(&self) -> String {
+        let mut text = String::new();
+        csv::write_row(&mut text, &["pc", "label", "instructions", "cycles"]);
+        for hot_spot in self.hot_spots.iter() {
+            csv::write_row(
+                &mut text,
+                &[
+                    format!("{}", hot_spot.pc),
+                    hot_spot.label.clone().unwrap_or_default(),
+                    format!("{}", hot_spot.instructions),
+                    format!("{}", hot_spot.cycles),
+                ],
+            );
+        }
+        text
+    }
+
+    pub fn subroutines_csv(&self) -> String {
+        let mut text = String::new();
+        let header = [
+            "address",
+            "label",
+            "calls",
+            "inclusive_cycles",
+            "exclusive_cycles",
+        ];
+        csv::write_row(&mut text, &header);
+        for subroutine in self.subroutines.iter() {
+            csv::write_row(
+                &mut text,
+                &[
+                    format!("{}", subroutine.address),
+                    subroutine.label.clone().unwrap_or_default(),
+                    format!("{}", subroutine.calls),
+                    format!("{}", subroutine.inclusive_cycles),
+                    format!("{}", subroutine.exclusive_cycles),
+                ],
+            );
+        }
+        text
+    }
+
+    pub fn opcodes_csv(&self) -> String {
+        let mut text = String::new();
+        csv::write_row(&mut text, &["opcode", "name", "count"]);
+        for count in self.opcodes.iter() {
+            csv::write_row(
+                &mut text,
+                &[
+                    format!("{}", count.opcode),
+                    String::from(count.name),
+                    format!("{}", count.count),
+                ],
+            );
+        }
+        text
+    }
+}
+
+// counts instructions and cycles per pc, per opcode and per subroutine through the
+// cpu hooks. the hooks stay registered but go quiet once the profiler is dropped
+pub struct Profiler {
+    counters: Arc<AtomicRefCell<Counters>>,
+}
+
+impl Profiler {
+    pub fn attach(hooks: &mut Hooks) -> Self {
+        let counters = Arc::new(AtomicRefCell::new(Counters::new()));
+
+        let weak: Weak<AtomicRefCell<Counters>> = Arc::downgrade(&counters);
+        hooks.on_instruction_executed(move |event| {
+            if let Some(counters) = weak.upgrade() {
+                counters.borrow_mut().instruction(event);
+            }
+        });
+        let weak = Arc::downgrade(&counters);
+        hooks.on_interrupt(move |event| {
+            if let Some(counters) = weak.upgrade() {
+                counters.borrow_mut().interrupt(event);
+            }
+        });
+
+        Profiler { counters: counters }
+    }
+
+    // starts counting over, calls in progress are forgotten
+    pub fn reset(&self) {
+        *self.counters.borrow_mut() = Counters::new();
+    }
+
+    // the `limit` hottest pcs and subroutines, labeled from `symbols` where they have one
+    pub fn report(&self, limit: usize, symbols: Option<&Symbols>) -> Report {
+        let counters = self.counters.borrow();
+        let label = |address: u16| {
+            symbols
+                .and_then(|symbols| symbols.get(address))
+                .map(String::from)
+        };
+
+        let mut hot_spots: Vec<HotSpot> = (0..0x10000)
+            .filter(|pc| counters.instructions[*pc] > 0)
+            .map(|pc| HotSpot {
+                pc: pc as u16,
+                label: label(pc as u16),
+                instructions: counters.instructions[pc],
+                cycles: counters.cycles[pc],
+            })
+            .collect();
+        hot_spots.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.pc.cmp(&b.pc)));
+        hot_spots.truncate(limit);
+
+        let mut subroutines: Vec<Subroutine> = counters
+            .subroutines
+            .iter()
+            .map(|(address, subroutine)| Subroutine {
+                address: *address,
+                label: label(*address),
+                calls: subroutine.calls,
+                inclusive_cycles: subroutine.inclusive_cycles,
+                exclusive_cycles: subroutine.exclusive_cycles,
+            })
+            .collect();
+        subroutines.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(a.address.cmp(&b.address))
+        });
+        subroutines.truncate(limit);
+
+        let mut opcodes: Vec<OpcodeCount> = (0..0x100)
+            .filter(|op| counters.opcodes[*op] > 0)
+            .map(|op| OpcodeCount {
+                opcode: op as u8,
+                name: opcode::OPCODES_MAP[op].unwrap_or(&opcode::UNSUPPORTED).name,
+                count: counters.opcodes[op],
+            })
+            .collect();
+        opcodes.sort_by(|a, b| b.count.cmp(&a.count).then(a.opcode.cmp(&b.opcode)));
+
+        Report {
+            instructions: counters.total_instructions,
+            cycles: counters.total_cycles,
+            hot_spots: hot_spots,
+            subroutines: subroutines,
+            opcodes: opcodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::create_cpu;
+
+    #[test]
+    fn test_report() {
+        #[rustfmt::skip]
+        let mut program = vec![
+            0x20, 0x10, 0x80, // JSR $8010
+            0x20, 0x10, 0x80, // JSR $8010
+            0x4C, 0x06, 0x80, // JMP $8006
+        ];
+        program.resize(0x10, 0xEA);
+        program.extend_from_slice(&[0xEA, 0x60]); // $8010: NOP, RTS
+        let mut cpu = create_cpu(&program);
+        let profiler = Profiler::attach(&mut cpu.hooks);
+        for _ in 0..9 {
+            cpu.interprect();
+        }
+
+        let mut symbols = Symbols::new();
+        symbols.insert(0x8010, "wait");
+        let report = profiler.report(2, Some(&symbols));
+        assert_eq!(report.instructions, 9);
+        assert_eq!(report.cycles, 6 + 2 + 6 + 6 + 2 + 6 + 3 * 3);
+
+        // two RTS, then the JMP loop
+        assert_eq!(report.hot_spots.len(), 2);
+        assert_eq!(
+            (report.hot_spots[0].pc, report.hot_spots[0].cycles),
+            (0x8011, 12)
+        );
+        assert_eq!(
+            (report.hot_spots[1].pc, report.hot_spots[1].cycles),
+            (0x8006, 9)
+        );
+
+        assert_eq!(
+            report.subroutines,
+            vec![Subroutine {
+                address: 0x8010,
+                label: Some(String::from("wait")),
+                calls: 2,
+                inclusive_cycles: 16,
+                exclusive_cycles: 16,
+            }]
+        );
+        assert_eq!(report.opcodes[0].name, "JMP");
+        assert_eq!(report.opcodes[0].count, 3);
+
+        let json = report.to_json();
+        assert_eq!(json.get("cycles"), Some(&Value::from(report.cycles)));
+        let subroutine = &json.get("subroutines").unwrap().as_array().unwrap()[0];
+        assert_eq!(subroutine.get("label"), Some(&Value::from("wait")));
+        assert_eq!(
+            report.subroutines_csv(),
+            "address,label,calls,inclusive_cycles,exclusive_cycles\n32784,wait,2,16,16\n"
+        );
+        assert_eq!(report.hot_spots_csv().lines().count(), 3);
+        assert!(report.opcodes_csv().contains("\n76,JMP,3\n"));
+
+        drop(profiler);
+        cpu.interprect();
+    }
+}
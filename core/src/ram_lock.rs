@@ -0,0 +1,114 @@
+// frozen addresses, the classic infinite lives cheat. the game may write what it wants,
+// the locked value is put back after every instruction or once per frame. once per frame
+// is enough for counters the game only touches now and then and lets it see the real
+// value in between, every instruction holds against code that uses the value right after
+// changing it. see Nes::lock_ram
+
+use alloc::string::String;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockTiming {
+    Instruction,
+    Frame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RamLock {
+    pub addr: u16,
+    pub value: u8,
+    pub timing: LockTiming,
+}
+
+impl RamLock {
+    // "0075 09 frame", hex address and value then `instruction` or `frame`
+    pub fn to_text(&self) -> String {
+        let timing = match self.timing {
+            LockTiming::Instruction => "instruction",
+            LockTiming::Frame => "frame",
+        };
+        format!("{:04X} {:02X} {}", self.addr, self.value, timing)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bad = || format!("bad ram lock {:?}", text);
+        let mut fields = text.split_whitespace();
+        let addr = fields
+            .next()
+            .and_then(|addr| u16::from_str_radix(addr, 16).ok())
+            .ok_or_else(bad)?;
+        let value = fields
+            .next()
+            .and_then(|value| u8::from_str_radix(value, 16).ok())
+            .ok_or_else(bad)?;
+        let timing = match fields.next() {
+            Some("instruction") => LockTiming::Instruction,
+            Some("frame") => LockTiming::Frame,
+            _ => return Err(bad()),
+        };
+        Ok(RamLock {
+            addr: addr,
+            value: value,
+            timing: timing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::mem::Memory;
+    use crate::nes::Nes;
+
+    #[test]
+    fn test_ram_lock() {
+        // INC $10, INC $11, LDA $10, STA $12, JMP $8000
+        let mut prg = vec![0xEA; 0x4000];
+        let program = [
+            0xE6, 0x10, 0xE6, 0x11, 0xA5, 0x10, 0x85, 0x12, 0x4C, 0x00, 0x80,
+        ];
+        const LOOP_INSTRUCTIONS: usize = 5;
+        prg[..program.len()].copy_from_slice(&program);
+        prg[0x3FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        let raw = crate::cartridge::test::create_rom(0, 1, prg);
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+
+        let lock = |addr, timing| RamLock {
+            addr: addr,
+            value: 0x40,
+            timing: timing,
+        };
+        // $0810 mirrors $0010
+        nes.lock_ram(lock(0x0810, LockTiming::Instruction)).unwrap();
+        nes.lock_ram(lock(0x0011, LockTiming::Frame)).unwrap();
+        assert_eq!(nes.ram_locks()[0].addr, 0x0010);
+        assert!(nes.lock_ram(lock(0x2000, LockTiming::Frame)).is_err());
+
+        let frames = nes.stats().frames;
+        while nes.stats().frames == frames {
+            nes.step();
+        }
+        // every INC $10 is undone before the LDA reads it back, the per-frame lock was
+        // just put back and counts up again for one round of the loop
+        assert_eq!(nes.cpu.mem_peek(0x10), 0x40);
+        assert_eq!(nes.cpu.mem_peek(0x12), 0x40);
+        assert_eq!(nes.cpu.mem_peek(0x11), 0x40);
+        for _ in 0..LOOP_INSTRUCTIONS {
+            nes.step();
+        }
+        assert_eq!(nes.cpu.mem_peek(0x11), 0x41);
+
+        nes.unlock_ram(0x0010);
+        for _ in 0..LOOP_INSTRUCTIONS {
+            nes.step();
+        }
+        assert_eq!(nes.ram_locks().len(), 1);
+        assert_eq!(nes.cpu.mem_peek(0x10), 0x41);
+
+        let text = nes.ram_locks()[0].to_text();
+        assert_eq!(text, "0011 40 frame");
+        assert_eq!(RamLock::parse(&text), Ok(nes.ram_locks()[0]));
+        assert!(RamLock::parse("0011 40").is_err());
+    }
+}
@@ -0,0 +1,421 @@
+// headless golden-frame regression tests: every rom listed in the golden file runs for
+// the given number of frames and the hash of the last frame has to match.
+//
+// golden file lines: <rom path relative to the crate> <frames> <fnv-1a hash of the rgba frame>
+// run with FEUERNES_UPDATE_GOLDEN=1 to rewrite the hashes (and golden images next to the
+// golden file) after an intended change. on a mismatch the actual frame and a side by side
+// diff against the golden image are written to target/regression for triage.
+//
+// the movie file does the same for input movies, comparing the emulator state hash after
+// the last frame instead of the picture, so cpu, ppu and mapper changes that a game only
+// shows once it is played are caught too.
+//
+// movie file lines: <rom path> <fm2 movie path> <Nes::state_hash after the movie>
+// a failing movie leaves a repro bundle of its last frames in target/regression.
+//
+// the compatibility database is made from the same roms: each one is surveyed, and one
+// whose movie plays back in sync counts as working whatever the survey saw.
+//
+// blargg's apu test roms check themselves and report through prg ram. they cover the apu's
+// registers, length counters and irq timing, not what it sounds like. they are not in the
+// tree, so their test is ignored: copy apu_test/rom_singles into res/apu_test and run it
+// with `cargo test -- --ignored test_apu_test_roms`.
+//
+// nothing here checks audio output. rendering an nsf track and comparing its spectrum
+// against a golden waits for the apu channels to be synthesized (see APU::mix, which only
+// passes expansion audio through) and for an nsf player, until then a golden would only
+// ever hold silence.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::cartridge::Cartridge;
+use crate::compat::{self, CompatEntry};
+use crate::config::Config;
+use crate::movie::Movie;
+use crate::nes::Nes;
+use crate::render::diff::diff_frames;
+use crate::render::frame::Frame;
+use crate::render::png;
+use crate::repro::{ReproBundle, REPRO_WINDOW_FRAMES};
+use crate::survey::{self, DEFAULT_SURVEY_FRAMES};
+use crate::sync::AtomicRefCell;
+use crate::test_roms::{run_blargg, BLARGG_SIGNATURE};
+
+const GOLDEN_FILE: &str = "res/regression/golden.txt";
+const MOVIE_FILE: &str = "res/regression/movies.txt";
+const COMPAT_FILE: &str = "res/compat.json";
+const UPDATE_GOLDEN_ENV: &str = "FEUERNES_UPDATE_GOLDEN";
+const GOLDEN_IMAGE_DIR: &str = "res/regression";
+const FAILURE_OUTPUT_DIR: &str = "target/regression";
+
+const APU_TEST_DIR: &str = "res/apu_test";
+const APU_TEST_ROMS: [&str; 8] = [
+    "1-len_ctr.nes",
+    "2-len_table.nes",
+    "3-irq_flag.nes",
+    "4-jitter.nes",
+    "5-len_timing.nes",
+    "6-irq_flag_timing.nes",
+    "7-dmc_basics.nes",
+    "8-dmc_rates.nes",
+];
+
+// cap per frame in case a rom never reaches vblank
+const MAX_INSTRUCTIONS_PER_FRAME: usize = 100_000;
+
+pub struct GoldenEntry {
+    pub rom: String,
+    pub frames: usize,
+    pub hash: u64,
+}
+
+pub struct MovieEntry {
+    pub rom: String,
+    pub movie: String,
+    pub hash: u64,
+}
+
+pub fn crate_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+impl GoldenEntry {
+    fn image_name(&self) -> String {
+        let stem = Path::new(&self.rom)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("rom");
+        format!("{}-{}", stem, self.frames)
+    }
+
+    pub fn golden_image_path(&self) -> PathBuf {
+        crate_path(GOLDEN_IMAGE_DIR).join(format!("{}.png", self.image_name()))
+    }
+}
+
+pub fn parse_golden(text: &str) -> Vec<GoldenEntry> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields.len(), 3, "malformed golden line: {}", line);
+            GoldenEntry {
+                rom: String::from(fields[0]),
+                frames: fields[1].parse().expect("frame count"),
+                hash: u64::from_str_radix(fields[2], 16).expect("frame hash"),
+            }
+        })
+        .collect()
+}
+
+pub fn parse_movies(text: &str) -> Vec<MovieEntry> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields.len(), 3, "malformed movie line: {}", line);
+            MovieEntry {
+                rom: String::from(fields[0]),
+                movie: String::from(fields[1]),
+                hash: u64::from_str_radix(fields[2], 16).expect("state hash"),
+            }
+        })
+        .collect()
+}
+
+pub fn hash_frame(frame: &Frame) -> u64 {
+    // FNV-1a, stable across rust versions unlike DefaultHasher
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in frame.data.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+// runs the rom without input and returns the last of `frames` frames
+pub fn run_rom(rom: &[u8], frames: usize) -> Frame {
+    let mut nes = Nes::new(Cartridge::new(&rom.to_vec()).unwrap());
+    let count = Arc::new(AtomicRefCell::new(0));
+    let counter = count.clone();
+    nes.set_frame_callback(move |_| *counter.borrow_mut() += 1);
+
+    nes.reset();
+    for _ in 0..frames {
+        let target = *count.borrow() + 1;
+        for _ in 0..MAX_INSTRUCTIONS_PER_FRAME {
+            nes.step();
+            if *count.borrow() >= target {
+                break;
+            }
+        }
+    }
+    nes.frame().clone()
+}
+
+// plays the movie from power-on and returns the state hash after its last frame
+pub fn run_movie(rom: &[u8], movie: &Movie) -> u64 {
+    let mut nes = Nes::new(Cartridge::new(&rom.to_vec()).unwrap());
+    movie.play(&mut nes);
+    nes.state_hash()
+}
+
+// the compatibility of every golden and movie rom, in the order they are first listed
+pub fn compat_entries(golden: &[GoldenEntry], movies: &[MovieEntry]) -> Vec<CompatEntry> {
+    let mut roms: Vec<&str> = Vec::new();
+    for rom in golden
+        .iter()
+        .map(|entry| &entry.rom)
+        .chain(movies.iter().map(|entry| &entry.rom))
+    {
+        if !roms.contains(&rom.as_str()) {
+            roms.push(rom);
+        }
+    }
+
+    let mut entries = Vec::new();
+    for rom_path in roms {
+        let rom = fs::read(crate_path(rom_path)).expect("regression rom");
+        let name = Path::new(rom_path).file_name().unwrap().to_string_lossy();
+        let mut entry =
+            match CompatEntry::from_survey(&survey::survey_rom(&name, &rom, DEFAULT_SURVEY_FRAMES))
+            {
+                Some(entry) => entry,
+                None => continue,
+            };
+        for movie_entry in movies.iter().filter(|movie| movie.rom == rom_path) {
+            let fm2 = fs::read_to_string(crate_path(&movie_entry.movie)).expect("movie");
+            let movie = Movie::parse(&fm2).expect("valid movie");
+            if run_movie(&rom, &movie) == movie_entry.hash {
+                let movie_name = Path::new(&movie_entry.movie).file_name().unwrap();
+                entry.movie_in_sync(&movie_name.to_string_lossy());
+            }
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+// writes the actual frame and, when a golden image exists, the diff; returns a summary line
+pub fn write_failure_images(entry: &GoldenEntry, actual: &Frame) -> String {
+    let out_dir = crate_path(FAILURE_OUTPUT_DIR);
+    fs::create_dir_all(&out_dir).expect("create regression output dir");
+
+    let actual_path = out_dir.join(format!("{}-actual.png", entry.image_name()));
+    fs::write(&actual_path, png::encode(actual)).expect("write actual frame");
+
+    let golden = fs::read(entry.golden_image_path())
+        .ok()
+        .and_then(|data| png::decode(&data).ok());
+    match golden {
+        Some(expected) => {
+            let diff = diff_frames(&expected, actual);
+            let diff_path = out_dir.join(format!("{}-diff.png", entry.image_name()));
+            fs::write(&diff_path, png::encode(&diff.image)).expect("write frame diff");
+            format!(
+                "{} pixels differ, see {}",
+                diff.differing_pixels,
+                diff_path.display()
+            )
+        }
+        None => format!("no golden image, actual frame in {}", actual_path.display()),
+    }
+}
+
+// bundles the end of a failing movie so it can be stepped through on another build
+pub fn write_repro_bundle(entry: &MovieEntry, rom: &[u8], movie: &Movie) -> String {
+    let out_dir = crate_path(FAILURE_OUTPUT_DIR);
+    fs::create_dir_all(&out_dir).expect("create regression output dir");
+
+    let bundle = ReproBundle::record(rom, &Config::default(), movie, REPRO_WINDOW_FRAMES)
+        .expect("regression rom");
+    let stem = Path::new(&entry.movie)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = out_dir.join(format!("{}.fnrp", stem));
+    fs::write(&path, bundle.to_bytes()).expect("write repro bundle");
+    format!("repro bundle in {}", path.display())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_golden_frames() {
+        let golden_path = crate_path(GOLDEN_FILE);
+        let text = fs::read_to_string(&golden_path).expect("golden file");
+        let mut entries = parse_golden(&text);
+        assert!(!entries.is_empty());
+
+        let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok();
+        let mut failures = Vec::new();
+        for entry in entries.iter_mut() {
+            let rom = fs::read(crate_path(&entry.rom)).expect("regression rom");
+            let frame = run_rom(&rom, entry.frames);
+            let hash = hash_frame(&frame);
+            if update {
+                fs::write(entry.golden_image_path(), png::encode(&frame))
+                    .expect("write golden image");
+            } else if hash != entry.hash {
+                failures.push(format!(
+                    "{} after {} frames: expected {:016x}, got {:016x} ({})",
+                    entry.rom,
+                    entry.frames,
+                    entry.hash,
+                    hash,
+                    write_failure_images(entry, &frame)
+                ));
+            }
+            entry.hash = hash;
+        }
+
+        if update {
+            let mut text = String::from("# rom frames fnv1a-hash\n");
+            for entry in entries.iter() {
+                text += &format!("{} {} {:016x}\n", entry.rom, entry.frames, entry.hash);
+            }
+            fs::write(&golden_path, text).expect("write golden file");
+            return;
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_movies() {
+        let movie_path = crate_path(MOVIE_FILE);
+        let text = fs::read_to_string(&movie_path).expect("movie file");
+        let mut entries = parse_movies(&text);
+        assert!(!entries.is_empty());
+
+        let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok();
+        let mut failures = Vec::new();
+        for entry in entries.iter_mut() {
+            let rom = fs::read(crate_path(&entry.rom)).expect("regression rom");
+            let fm2 = fs::read_to_string(crate_path(&entry.movie)).expect("movie");
+            let movie = Movie::parse(&fm2).expect("valid movie");
+            let hash = run_movie(&rom, &movie);
+            if !update && hash != entry.hash {
+                failures.push(format!(
+                    "{} playing {} for {} frames: expected {:016x}, got {:016x} ({})",
+                    entry.rom,
+                    entry.movie,
+                    movie.frames.len(),
+                    entry.hash,
+                    hash,
+                    write_repro_bundle(entry, &rom, &movie)
+                ));
+            }
+            entry.hash = hash;
+        }
+
+        if update {
+            // keep the comments, they say which boards are still missing
+            let mut text: String = text
+                .lines()
+                .take_while(|line| line.starts_with('#'))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            for entry in entries.iter() {
+                text += &format!("{} {} {:016x}\n", entry.rom, entry.movie, entry.hash);
+            }
+            fs::write(&movie_path, text).expect("write movie file");
+            return;
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_compat_database() {
+        let golden = parse_golden(&fs::read_to_string(crate_path(GOLDEN_FILE)).expect("golden"));
+        let movies = parse_movies(&fs::read_to_string(crate_path(MOVIE_FILE)).expect("movies"));
+        let entries = compat_entries(&golden, &movies);
+
+        let compat_path = crate_path(COMPAT_FILE);
+        if std::env::var(UPDATE_GOLDEN_ENV).is_ok() {
+            fs::write(&compat_path, compat::to_json(&entries)).expect("write compat file");
+            return;
+        }
+        let text = fs::read_to_string(&compat_path).expect("compat file");
+        assert_eq!(
+            compat::parse(&text).expect("valid compat file"),
+            entries,
+            "{} is out of date, run the tests with {}=1",
+            COMPAT_FILE,
+            UPDATE_GOLDEN_ENV
+        );
+    }
+
+    #[test]
+    #[ignore = "needs blargg's apu_test/rom_singles copied into core/res/apu_test"]
+    fn test_apu_test_roms() {
+        let mut failures = Vec::new();
+        for name in APU_TEST_ROMS.iter() {
+            let path = crate_path(APU_TEST_DIR).join(name);
+            let rom = match fs::read(&path) {
+                Ok(rom) => rom,
+                Err(e) => {
+                    failures.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            if let Err(message) = run_blargg(&rom) {
+                failures.push(format!("{}: {}", name, message));
+            }
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    // a stand-in for blargg's roms, reporting `code` with a message after asking for a reset
+    fn blargg_rom(code: u8) -> Vec<u8> {
+        let mut program = vec![
+            0xAD, 0x00, 0x60, // LDA $6000
+            0xC9, 0x81, //       CMP #$81
+            0xF0, 0x17, //       BEQ report, the reset was pressed
+        ];
+        for (i, byte) in BLARGG_SIGNATURE.iter().enumerate() {
+            program.extend(&[0xA9, *byte, 0x8D, 0x01 + i as u8, 0x60]); // LDA #byte, STA $6001+i
+        }
+        program.extend(&[
+            0xA9, 0x81, 0x8D, 0x00, 0x60, // LDA #$81, STA $6000
+            0x4C, 0x1B, 0x80, //             JMP * while waiting for the reset
+        ]);
+        // report, at $801E:
+        for (i, byte) in b"bad\0".iter().enumerate() {
+            program.extend(&[0xA9, *byte, 0x8D, 0x04 + i as u8, 0x60]);
+        }
+        program.extend(&[
+            0xA9, code, 0x8D, 0x00, 0x60, // LDA #code, STA $6000
+            0x4C, 0x37, 0x80, //             JMP *
+        ]);
+
+        let mut prg = vec![0xEA; 0x4000];
+        prg[..program.len()].copy_from_slice(&program);
+        prg[0x3FFC] = 0x00;
+        prg[0x3FFD] = 0x80;
+        crate::cartridge::test::create_rom(0b0000_0000, 1, prg)
+    }
+
+    #[test]
+    fn test_blargg_protocol() {
+        assert_eq!(run_blargg(&blargg_rom(0)), Ok(()));
+        assert_eq!(
+            run_blargg(&blargg_rom(3)),
+            Err(String::from("result 3: bad"))
+        );
+    }
+
+    #[test]
+    fn test_parse_golden() {
+        let entries = parse_golden("# comment\n\nres/a.nes 10 00000000000000ff\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].rom, "res/a.nes");
+        assert_eq!(entries[0].frames, 10);
+        assert_eq!(entries[0].hash, 0xFF);
+    }
+}
@@ -0,0 +1,76 @@
+use super::frame::Frame;
+
+// pixels that match are dimmed in the mask, pixels that differ are painted red
+const MASK_DIFFERENT: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+pub struct FrameDiff {
+    pub differing_pixels: usize,
+    // expected | actual | mask, side by side
+    pub image: Frame,
+}
+
+pub fn diff_frames(expected: &Frame, actual: &Frame) -> FrameDiff {
+    let width = expected.width.max(actual.width);
+    let height = expected.height.max(actual.height);
+    let mut image = Frame::new(width * 3, height);
+    let mut differing_pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let expected_pixel = pixel(expected, x, y);
+            let actual_pixel = pixel(actual, x, y);
+            let mask = if expected_pixel == actual_pixel {
+                let gray = luma(&expected_pixel) / 4;
+                [gray, gray, gray, 0xFF]
+            } else {
+                differing_pixels += 1;
+                MASK_DIFFERENT
+            };
+
+            for (panel, color) in [expected_pixel, actual_pixel, mask].iter().enumerate() {
+                let index = (y * image.width + panel * width + x) * 4;
+                image.data[index..index + 4].copy_from_slice(color);
+            }
+        }
+    }
+
+    FrameDiff {
+        differing_pixels: differing_pixels,
+        image: image,
+    }
+}
+
+// transparent black outside of the frame, so frames of different sizes still compare
+fn pixel(frame: &Frame, x: usize, y: usize) -> [u8; 4] {
+    if x >= frame.width || y >= frame.height {
+        return [0; 4];
+    }
+    let index = (y * frame.width + x) * 4;
+    let mut color = [0; 4];
+    color.copy_from_slice(&frame.data[index..index + 4]);
+    color
+}
+
+fn luma(color: &[u8; 4]) -> u8 {
+    ((color[0] as u32 * 299 + color[1] as u32 * 587 + color[2] as u32 * 114) / 1000) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_frames() {
+        let expected = Frame::new(4, 2);
+        let mut actual = Frame::new(4, 2);
+        actual.data[(1 * 4 + 2) * 4] = 0xFF;
+
+        let diff = diff_frames(&expected, &actual);
+        assert_eq!(diff.differing_pixels, 1);
+        assert_eq!(diff.image.width, 12);
+
+        let mask_index = (1 * 12 + 8 + 2) * 4;
+        assert_eq!(diff.image.data[mask_index..mask_index + 4], MASK_DIFFERENT);
+        assert_eq!(diff_frames(&expected, &expected).differing_pixels, 0);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{clamped, put, rgba, VideoFilter};
+use crate::render::frame::Frame;
+
+/*
+https://en.wikipedia.org/wiki/Hqx
+
+    a compact take on hq2x. neighbours count as the same color when they are close in
+    YUV, with hqx's thresholds, and each of the four output pixels looks at the two edge
+    neighbours and the diagonal on its side:
+
+        D A .
+        C P .     top left output pixel of P
+        . . .
+
+    when A and C are alike but P is not, P sits on the outside of an edge and the
+    corner is blended towards them, half as much when the diagonal D agrees with P
+    (a thin line rather than a corner). otherwise P stays. the real hq2x picks
+    between more blends with a 256 case table, this keeps the part that smooths
+    staircases and leaves flat areas and dithering alone
+*/
+const THRESHOLD_Y: i32 = 0x30;
+const THRESHOLD_U: i32 = 0x07;
+const THRESHOLD_V: i32 = 0x06;
+
+pub struct Hq2x;
+
+fn yuv(color: [u8; 4]) -> (i32, i32, i32) {
+    let (r, g, b) = (color[0] as i32, color[1] as i32, color[2] as i32);
+    let y = (r * 299 + g * 587 + b * 114) / 1000;
+    let u = (-r * 169 - g * 331 + b * 500) / 1000 + 128;
+    let v = (r * 500 - g * 419 - b * 81) / 1000 + 128;
+    (y, u, v)
+}
+
+fn alike(a: u8, b: u8) -> bool {
+    if a == b {
+        return true;
+    }
+    let (ya, ua, va) = yuv(rgba(a));
+    let (yb, ub, vb) = yuv(rgba(b));
+    (ya - yb).abs() <= THRESHOLD_Y
+        && (ua - ub).abs() <= THRESHOLD_U
+        && (va - vb).abs() <= THRESHOLD_V
+}
+
+// weighted average of colors given as (color, weight)
+fn blend(colors: &[([u8; 4], u32)]) -> [u8; 4] {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+    let mut out = [0xFF; 4];
+    for channel in 0..3 {
+        let sum: u32 = colors
+            .iter()
+            .map(|(color, weight)| color[channel] as u32 * weight)
+            .sum();
+        out[channel] = (sum / total) as u8;
+    }
+    out
+}
+
+fn corner(p: u8, edge1: u8, edge2: u8, diagonal: u8) -> [u8; 4] {
+    if !alike(edge1, edge2) || alike(p, edge1) {
+        return rgba(p);
+    }
+    let corner = alike(diagonal, edge1);
+    let (p, edge1, edge2) = (rgba(p), rgba(edge1), rgba(edge2));
+    if corner {
+        // 2:1:1
+        blend(&[(p, 2), (edge1, 1), (edge2, 1)])
+    } else {
+        // 6:1:1
+        blend(&[(p, 6), (edge1, 1), (edge2, 1)])
+    }
+}
+
+impl VideoFilter for Hq2x {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * 2, height * 2)
+    }
+
+    fn apply(&mut self, indices: &[u8], width: usize, height: usize, output: &mut Frame) {
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x as isize, y as isize);
+                let at = |dx: isize, dy: isize| clamped(indices, width, height, sx + dx, sy + dy);
+                let p = at(0, 0);
+                let (up, down, left, right) = (at(0, -1), at(0, 1), at(-1, 0), at(1, 0));
+
+                let corners = [
+                    corner(p, up, left, at(-1, -1)),
+                    corner(p, up, right, at(1, -1)),
+                    corner(p, down, left, at(-1, 1)),
+                    corner(p, down, right, at(1, 1)),
+                ];
+                for (i, color) in corners.iter().enumerate() {
+                    put(output, x * 2 + i % 2, y * 2 + i / 2, *color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::filter_frame;
+    use super::*;
+
+    #[test]
+    fn test_hq2x() {
+        // white (0x30) against black (0x0F): the black pixel in the corner of the white L
+        // is blended, the flat white area is not
+        #[rustfmt::skip]
+        let indices = [
+            0x30, 0x30, 0x30,
+            0x30, 0x0F, 0x0F,
+            0x30, 0x0F, 0x0F,
+        ];
+        let output = filter_frame(&mut Hq2x, &indices, 3, 3);
+        assert_eq!(output.pixel(0, 0), rgba(0x30));
+        assert_eq!(output.pixel(3, 3), rgba(0x0F));
+        assert_eq!(
+            output.pixel(2, 2),
+            blend(&[(rgba(0x0F), 2), (rgba(0x30), 2)])
+        );
+        assert_eq!(output.pixel(5, 5), rgba(0x0F));
+        assert!(alike(0x30, 0x20));
+        assert!(!alike(0x30, 0x0F));
+    }
+}
@@ -0,0 +1,91 @@
+// cpu side scalers for frontends without programmable shaders (canvas 2d, terminals,
+// screenshots). they take the system palette indices of a frame, see Nes::screen, and
+// write an RGBA frame of their own size. the overlay is drawn on the unfiltered frame
+// only, frontends that filter draw their own
+use super::frame::Frame;
+use super::palette::SYSTEM_PALETTE;
+
+use alloc::boxed::Box;
+
+pub mod hqx;
+pub mod nearest;
+pub mod ntsc;
+pub mod scale2x;
+
+pub trait VideoFilter {
+    // size of the output for an input of `width` x `height`
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize);
+
+    // `output` has to be output_size() large, its previous contents are overwritten
+    fn apply(&mut self, indices: &[u8], width: usize, height: usize, output: &mut Frame);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest2x,
+    Nearest3x,
+    Scale2x,
+    Hq2x,
+    Ntsc,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 5] = [
+        Filter::Nearest2x,
+        Filter::Nearest3x,
+        Filter::Scale2x,
+        Filter::Hq2x,
+        Filter::Ntsc,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Nearest2x => "Nearest 2x",
+            Filter::Nearest3x => "Nearest 3x",
+            Filter::Scale2x => "Scale2x",
+            Filter::Hq2x => "hq2x",
+            Filter::Ntsc => "NTSC composite",
+        }
+    }
+
+    pub fn create(&self) -> Box<dyn VideoFilter> {
+        match self {
+            Filter::Nearest2x => Box::new(nearest::Nearest::new(2)),
+            Filter::Nearest3x => Box::new(nearest::Nearest::new(3)),
+            Filter::Scale2x => Box::new(scale2x::Scale2x),
+            Filter::Hq2x => Box::new(hqx::Hq2x),
+            Filter::Ntsc => Box::new(ntsc::Ntsc::new()),
+        }
+    }
+}
+
+// runs `filter` into a new frame of the right size
+pub fn filter_frame(
+    filter: &mut dyn VideoFilter,
+    indices: &[u8],
+    width: usize,
+    height: usize,
+) -> Frame {
+    let (output_width, output_height) = filter.output_size(width, height);
+    let mut output = Frame::new(output_width, output_height);
+    filter.apply(indices, width, height, &mut output);
+    output.mark_all_dirty();
+    output
+}
+
+pub(crate) fn rgba(index: u8) -> [u8; 4] {
+    let (r, g, b) = SYSTEM_PALETTE[(index & 0x3F) as usize];
+    [r, g, b, 0xFF]
+}
+
+// the pixel at x, y with the edges repeated outwards
+pub(crate) fn clamped(indices: &[u8], width: usize, height: usize, x: isize, y: isize) -> u8 {
+    let x = x.max(0).min(width as isize - 1) as usize;
+    let y = y.max(0).min(height as isize - 1) as usize;
+    indices[y * width + x]
+}
+
+pub(crate) fn put(output: &mut Frame, x: usize, y: usize, color: [u8; 4]) {
+    let index = (y * output.width + x) * 4;
+    output.data[index..index + 4].copy_from_slice(&color);
+}
@@ -0,0 +1,44 @@
+use super::{put, rgba, VideoFilter};
+use crate::render::frame::Frame;
+
+// every pixel becomes a `scale` x `scale` block
+pub struct Nearest {
+    scale: usize,
+}
+
+impl Nearest {
+    pub fn new(scale: usize) -> Self {
+        Nearest {
+            scale: scale.max(1),
+        }
+    }
+}
+
+impl VideoFilter for Nearest {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * self.scale, height * self.scale)
+    }
+
+    fn apply(&mut self, indices: &[u8], width: usize, height: usize, output: &mut Frame) {
+        for y in 0..height * self.scale {
+            for x in 0..width * self.scale {
+                let index = indices[(y / self.scale) * width + x / self.scale];
+                put(output, x, y, rgba(index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::filter_frame;
+    use super::*;
+
+    #[test]
+    fn test_nearest() {
+        let output = filter_frame(&mut Nearest::new(3), &[0x00, 0x30], 2, 1);
+        assert_eq!((output.width, output.height), (6, 3));
+        assert_eq!(output.pixel(2, 2), rgba(0x00));
+        assert_eq!(output.pixel(3, 0), rgba(0x30));
+    }
+}
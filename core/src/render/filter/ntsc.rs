@@ -0,0 +1,176 @@
+use super::{put, VideoFilter};
+use crate::render::frame::Frame;
+
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/NTSC_video
+
+    the ppu does not output colors but a composite signal: every pixel is 8 samples of a
+    square wave at the 12 sample color subcarrier. the hue picks the phase of the wave and
+    the level its low and high voltage. a tv gets luma back by averaging over a subcarrier
+    period and chroma by correlating with it, and since the window spans neighbouring
+    pixels, colors bleed into each other. that is what turns dithering into transparency
+    and gives vertical lines their colored fringes.
+
+    each scanline starts 4 samples later in the subcarrier (341 dots * 8 samples % 12),
+    which makes the artifacts crawl diagonally. the output has two pixels per input pixel,
+    one every 4 samples
+*/
+const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_OUTPUT_PIXEL: usize = 4;
+const SUBCARRIER_SAMPLES: usize = 12;
+const SCANLINE_PHASE_STEP: usize = 4;
+
+// voltages relative to sync for the four levels, low and high half of the wave
+const LEVELS_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+
+// cos and sin of 30 degree steps, one per sample of the subcarrier. core has no
+// trigonometry without std
+#[rustfmt::skip]
+const COS: [f32; 12] = [
+    1.0, 0.866_025_4, 0.5, 0.0, -0.5, -0.866_025_4, -1.0, -0.866_025_4, -0.5, 0.0, 0.5, 0.866_025_4,
+];
+#[rustfmt::skip]
+const SIN: [f32; 12] = [
+    0.0, 0.5, 0.866_025_4, 1.0, 0.866_025_4, 0.5, 0.0, -0.5, -0.866_025_4, -1.0, -0.866_025_4, -0.5,
+];
+
+// the decoder's hue and saturation knobs: I/Q are turned by 117 degrees and doubled,
+// which puts the colors closest to the 2C02 palette in palette.rs
+const HUE_COS: f32 = -0.907_981;
+const HUE_SIN: f32 = 1.782_013;
+
+#[derive(Default)]
+pub struct Ntsc {
+    // normalized signal of one scanline, reused between lines
+    signal: Vec<f32>,
+}
+
+impl Ntsc {
+    pub fn new() -> Self {
+        Ntsc::default()
+    }
+}
+
+// the square wave is high for half of the subcarrier period, starting at the hue
+fn in_color_phase(hue: usize, phase: usize) -> bool {
+    (hue + phase) % SUBCARRIER_SAMPLES < SUBCARRIER_SAMPLES / 2
+}
+
+// one sample of palette index `index`, 0 is black and 1 white
+fn sample(index: u8, phase: usize) -> f32 {
+    let hue = (index & 0x0F) as usize;
+    let mut level = ((index >> 4) & 0b11) as usize;
+    // columns $E and $F are black, which is level 1 low
+    if hue > 13 {
+        level = 1;
+    }
+
+    let mut low = LEVELS_LOW[level];
+    let mut high = LEVELS_HIGH[level];
+    if hue == 0 {
+        low = high;
+    }
+    if hue > 12 {
+        high = low;
+    }
+    let voltage = if in_color_phase(hue, phase) {
+        high
+    } else {
+        low
+    };
+    (voltage - BLACK) / (WHITE - BLACK)
+}
+
+fn to_byte(value: f32) -> u8 {
+    if value <= 0.0 {
+        0
+    } else if value >= 1.0 {
+        0xFF
+    } else {
+        (value * 255.0 + 0.5) as u8
+    }
+}
+
+impl VideoFilter for Ntsc {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * SAMPLES_PER_PIXEL / SAMPLES_PER_OUTPUT_PIXEL, height)
+    }
+
+    fn apply(&mut self, indices: &[u8], width: usize, height: usize, output: &mut Frame) {
+        let samples = width * SAMPLES_PER_PIXEL;
+        for y in 0..height {
+            let start_phase = y * SCANLINE_PHASE_STEP % SUBCARRIER_SAMPLES;
+            self.signal.clear();
+            for x in 0..samples {
+                let index = indices[y * width + x / SAMPLES_PER_PIXEL];
+                self.signal.push(sample(index, start_phase + x));
+            }
+
+            for out_x in 0..samples / SAMPLES_PER_OUTPUT_PIXEL {
+                let center = out_x * SAMPLES_PER_OUTPUT_PIXEL + SAMPLES_PER_OUTPUT_PIXEL / 2;
+                let (mut luma, mut i, mut q) = (0.0, 0.0, 0.0);
+                // a subcarrier period around the center, black past the edges
+                for x in center.saturating_sub(6)..(center + 6).min(samples) {
+                    let level = self.signal[x] / SUBCARRIER_SAMPLES as f32;
+                    let phase = (start_phase + x) % SUBCARRIER_SAMPLES;
+                    luma += level;
+                    i += level * COS[phase];
+                    q += level * SIN[phase];
+                }
+                let (i, q) = (i * HUE_COS - q * HUE_SIN, i * HUE_SIN + q * HUE_COS);
+
+                let color = [
+                    to_byte(luma + 0.946_882 * i + 0.623_557 * q),
+                    to_byte(luma - 0.274_788 * i - 0.635_691 * q),
+                    to_byte(luma - 1.108_545 * i + 1.709_007 * q),
+                    0xFF,
+                ];
+                put(output, out_x, y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{filter_frame, rgba};
+    use super::*;
+
+    fn solid(index: u8) -> [u8; 4] {
+        let indices = [index; 16 * 2];
+        filter_frame(&mut Ntsc::new(), &indices, 16, 2).pixel(16, 1)
+    }
+
+    #[test]
+    fn test_solid_colors() {
+        assert_eq!(solid(0x0F), [0, 0, 0, 0xFF]);
+        assert_eq!(solid(0x30), [0xFF, 0xFF, 0xFF, 0xFF]);
+        let gray = solid(0x00);
+        assert!(gray[0] == gray[1] && gray[1] == gray[2] && gray[0] > 0x40);
+
+        let [r, g, b, _] = solid(0x16);
+        assert!(r > g && r > b, "red {:?}", (r, g, b));
+        let [r, g, b, _] = solid(0x1A);
+        assert!(g > r && g > b, "green {:?}", (r, g, b));
+        let [r, g, b, _] = solid(0x12);
+        assert!(b > r && b > g, "blue {:?}", (r, g, b));
+    }
+
+    #[test]
+    fn test_colors_bleed() {
+        // alternating columns of red and blue, each picks up some of the other
+        let indices: Vec<u8> = (0..32)
+            .map(|x| if x % 2 == 0 { 0x16 } else { 0x12 })
+            .collect();
+        let output = filter_frame(&mut Ntsc::new(), &indices, 32, 1);
+        let over_blue = output.pixel(30, 0);
+        let over_red = output.pixel(32, 0);
+        assert!(over_blue[0] > rgba(0x12)[0] + 0x40, "{:?}", over_blue);
+        assert!(over_red[2] > rgba(0x16)[2] + 0x40, "{:?}", over_red);
+    }
+}
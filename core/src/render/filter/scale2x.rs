@@ -0,0 +1,71 @@
+use super::{clamped, put, rgba, VideoFilter};
+use crate::render::frame::Frame;
+
+/*
+https://www.scale2x.it/algorithm
+
+    EPX/Scale2x: each pixel P becomes four, a corner takes the color of its two
+    neighbours when they agree and the edge does not continue past them
+
+          A            1 2
+        C P B   ->     3 4
+          D
+
+        1 = C == A && C != D && A != B ? A : P
+        2 = A == B && A != C && B != D ? B : P
+        3 = D == C && D != B && C != A ? C : P
+        4 = B == D && B != A && D != C ? D : P
+
+    comparing palette indices instead of colors makes the test exact
+*/
+pub struct Scale2x;
+
+impl VideoFilter for Scale2x {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * 2, height * 2)
+    }
+
+    fn apply(&mut self, indices: &[u8], width: usize, height: usize, output: &mut Frame) {
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x as isize, y as isize);
+                let p = indices[y * width + x];
+                let a = clamped(indices, width, height, sx, sy - 1);
+                let b = clamped(indices, width, height, sx + 1, sy);
+                let c = clamped(indices, width, height, sx - 1, sy);
+                let d = clamped(indices, width, height, sx, sy + 1);
+
+                let corners = [
+                    if c == a && c != d && a != b { a } else { p },
+                    if a == b && a != c && b != d { b } else { p },
+                    if d == c && d != b && c != a { c } else { p },
+                    if b == d && b != a && d != c { d } else { p },
+                ];
+                for (i, corner) in corners.iter().enumerate() {
+                    put(output, x * 2 + i % 2, y * 2 + i / 2, rgba(*corner));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::filter_frame;
+    use super::*;
+
+    #[test]
+    fn test_scale2x() {
+        // a diagonal step gets its corner filled in
+        #[rustfmt::skip]
+        let indices = [
+            0x30, 0x0F,
+            0x0F, 0x0F,
+        ];
+        let output = filter_frame(&mut Scale2x, &indices, 2, 2);
+        assert_eq!(output.pixel(0, 0), rgba(0x30));
+        assert_eq!(output.pixel(1, 1), rgba(0x0F));
+        assert_eq!(output.pixel(1, 0), rgba(0x30));
+        assert_eq!(output.pixel(3, 3), rgba(0x0F));
+    }
+}
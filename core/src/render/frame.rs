@@ -0,0 +1,147 @@
+use alloc::vec::Vec;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+// past this many separate regions it is cheaper to upload their bounding box
+const MAX_DIRTY_RECTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+// an RGBA image of one emulated frame. the dirty rects list what changed since they
+// were last cleared, so frontends can upload only those parts to the gpu
+#[derive(Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+    dirty: Vec<Rect>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Frame {
+            width: width,
+            height: height,
+            data: vec![0; width * height * 4],
+            dirty: Vec::new(),
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let index = (y * self.width + x) * 4;
+        let mut color = [0u8; 4];
+        color.copy_from_slice(&self.data[index..index + 4]);
+        color
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let index = (y * self.width + x) * 4;
+        self.data[index..index + 4].copy_from_slice(&color);
+        self.mark_dirty(Rect::new(x, y, 1, 1));
+    }
+
+    pub fn row(&self, y: usize) -> &[u8] {
+        let stride = self.width * 4;
+        &self.data[y * stride..(y + 1) * stride]
+    }
+
+    // the whole row counts as changed
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        self.mark_dirty(Rect::new(0, y, self.width, 1));
+        let stride = self.width * 4;
+        &mut self.data[y * stride..(y + 1) * stride]
+    }
+
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        if let Some(last) = self.dirty.last_mut() {
+            // rows written top to bottom grow into one rect
+            let same_columns = last.x == rect.x && last.width == rect.width;
+            if same_columns && rect.y >= last.y && rect.y <= last.y + last.height {
+                *last = last.union(&rect);
+                return;
+            }
+        }
+
+        self.dirty.push(rect);
+        if self.dirty.len() > MAX_DIRTY_RECTS {
+            let bounds = self.dirty.iter().fold(rect, |bounds, r| bounds.union(r));
+            self.dirty = vec![bounds];
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = vec![Rect::new(0, 0, self.width, self.height)];
+    }
+
+    pub fn dirty_rects(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+    }
+}
+
+// two frames are equal when they show the same picture
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.data == other.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pixels_and_rows() {
+        let mut frame = Frame::new(4, 3);
+        frame.set_pixel(1, 2, [1, 2, 3, 4]);
+        assert_eq!(frame.pixel(1, 2), [1, 2, 3, 4]);
+        assert_eq!(frame.row(2)[4..8], [1, 2, 3, 4]);
+        assert_eq!(frame.dirty_rects(), &[Rect::new(1, 2, 1, 1)]);
+
+        frame.clear_dirty();
+        frame.row_mut(0)[0] = 0xFF;
+        frame.row_mut(1)[0] = 0xFF;
+        assert_eq!(frame.dirty_rects(), &[Rect::new(0, 0, 4, 2)]);
+    }
+
+    #[test]
+    fn test_dirty_rects_collapse() {
+        let mut frame = Frame::new(64, 64);
+        for i in 0..MAX_DIRTY_RECTS {
+            frame.set_pixel(i * 2, i * 3, [0xFF; 4]);
+        }
+        assert_eq!(frame.dirty_rects().len(), MAX_DIRTY_RECTS);
+
+        frame.set_pixel(40, 1, [0xFF; 4]);
+        assert_eq!(frame.dirty_rects(), &[Rect::new(0, 0, 41, 46)]);
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::frame::Frame;
+
+// triple buffering: the writer fills the back slot, the reader shows the front slot and
+// finished frames are swapped through the middle one. writer and reader never hold the
+// same slot, so the renderer can't sample a half drawn frame and neither side waits.
+const SLOTS: usize = 3;
+// set in `middle` when it holds a frame the reader hasn't picked up yet
+const FRESH: usize = 0b100;
+
+pub struct FrameSlot {
+    frame: Frame,
+    // counts published frames, lets the reader notice the ones it missed
+    sequence: u64,
+}
+
+struct Shared {
+    slots: [Mutex<FrameSlot>; SLOTS],
+    middle: AtomicUsize,
+}
+
+impl Shared {
+    // a slot is only ever locked by its current owner, this never blocks
+    fn lock(&self, index: usize) -> MutexGuard<'_, FrameSlot> {
+        self.slots[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+pub struct FrameWriter {
+    shared: Arc<Shared>,
+    back: usize,
+    sequence: u64,
+}
+
+pub struct FrameReader {
+    shared: Arc<Shared>,
+    front: usize,
+    sequence: u64,
+}
+
+pub fn frame_buffer(width: usize, height: usize) -> (FrameWriter, FrameReader) {
+    let slot = || {
+        Mutex::new(FrameSlot {
+            frame: Frame::new(width, height),
+            sequence: 0,
+        })
+    };
+    let shared = Arc::new(Shared {
+        slots: [slot(), slot(), slot()],
+        middle: AtomicUsize::new(1),
+    });
+
+    let writer = FrameWriter {
+        shared: shared.clone(),
+        back: 0,
+        sequence: 0,
+    };
+    let reader = FrameReader {
+        shared: shared,
+        front: 2,
+        sequence: 0,
+    };
+    (writer, reader)
+}
+
+impl FrameWriter {
+    // copies the frame into the back slot and hands it to the reader
+    pub fn publish(&mut self, frame: &Frame) {
+        self.sequence += 1;
+        {
+            let mut slot = self.shared.lock(self.back);
+            slot.frame.clone_from(frame);
+            slot.sequence = self.sequence;
+        }
+        let previous = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = previous & !FRESH;
+    }
+}
+
+impl FrameReader {
+    // the newest published frame, or None when nothing was published since the last call.
+    // its dirty rects are relative to the frame before it, when the reader skipped some
+    // frames the whole picture is marked dirty instead
+    pub fn take_latest(&mut self) -> Option<MutexGuard<'_, FrameSlot>> {
+        if self.shared.middle.load(Ordering::Acquire) & FRESH == 0 {
+            return None;
+        }
+        let previous = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = previous & !FRESH;
+
+        let mut slot = self.shared.lock(self.front);
+        if slot.sequence != self.sequence + 1 {
+            slot.frame.mark_all_dirty();
+        }
+        self.sequence = slot.sequence;
+        Some(slot)
+    }
+}
+
+impl FrameSlot {
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::frame::Rect;
+    use std::thread;
+
+    #[test]
+    fn test_handoff() {
+        let (mut writer, mut reader) = frame_buffer(2, 2);
+        assert!(reader.take_latest().is_none());
+
+        let mut frame = Frame::new(2, 2);
+        frame.set_pixel(0, 0, [1; 4]);
+        writer.publish(&frame);
+        {
+            let slot = reader.take_latest().unwrap();
+            assert_eq!(slot.frame().pixel(0, 0), [1; 4]);
+            assert_eq!(slot.frame().dirty_rects(), &[Rect::new(0, 0, 1, 1)]);
+        }
+        assert!(reader.take_latest().is_none());
+
+        // the reader only sees the newest of several frames, fully dirty
+        frame.clear_dirty();
+        frame.set_pixel(1, 1, [2; 4]);
+        writer.publish(&frame);
+        frame.clear_dirty();
+        frame.set_pixel(1, 0, [3; 4]);
+        writer.publish(&frame);
+        let slot = reader.take_latest().unwrap();
+        assert_eq!(slot.frame().pixel(1, 0), [3; 4]);
+        assert_eq!(slot.frame().dirty_rects(), &[Rect::new(0, 0, 2, 2)]);
+    }
+
+    #[test]
+    fn test_no_torn_frames() {
+        let (mut writer, mut reader) = frame_buffer(16, 16);
+        let producer = thread::spawn(move || {
+            let mut frame = Frame::new(16, 16);
+            for value in 1..=200u8 {
+                for byte in frame.data.iter_mut() {
+                    *byte = value;
+                }
+                writer.publish(&frame);
+            }
+        });
+
+        let mut last = 0;
+        while last < 200 {
+            if let Some(slot) = reader.take_latest() {
+                let value = slot.frame().data[0];
+                assert!(slot.frame().data.iter().all(|byte| *byte == value));
+                assert!(value > last);
+                last = value;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+pub mod diff;
+pub mod filter;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod frame_buffer;
+pub mod overlay;
+pub mod palette;
+pub mod palette_simd;
+pub mod pattern_table;
+pub mod png;
+pub mod ppu_renderer;
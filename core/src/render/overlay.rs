@@ -0,0 +1,259 @@
+use super::frame::{Frame, Rect};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// glyphs are 3x5 pixels with a pixel of spacing around them
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+
+enum Command {
+    Text {
+        x: usize,
+        y: usize,
+        text: String,
+        color: [u8; 4],
+    },
+    Rect {
+        rect: Rect,
+        color: [u8; 4],
+        filled: bool,
+    },
+}
+
+// text and rectangles drawn over every frame the console outputs, for huds, popups and
+// status lines. shapes stay until cleared, so a hud that changes clears and redraws
+// itself every frame. colors are RGBA, alpha below 0xFF blends with the picture
+#[derive(Default)]
+pub struct Overlay {
+    commands: Vec<Command>,
+}
+
+// rows of a glyph top to bottom, bit 2 is the leftmost pixel
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 1, 1],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        ':' => [0, 2, 0, 2, 0],
+        '!' => [2, 2, 2, 0, 2],
+        '-' => [0, 0, 7, 0, 0],
+        '+' => [0, 2, 7, 2, 0],
+        '*' => [0, 5, 2, 5, 0],
+        '/' => [1, 1, 2, 4, 4],
+        '%' => [5, 1, 2, 4, 5],
+        '(' => [1, 2, 2, 2, 1],
+        ')' => [4, 2, 2, 2, 4],
+        '\'' => [2, 2, 0, 0, 0],
+        '=' => [0, 7, 0, 7, 0],
+        '<' => [1, 2, 4, 2, 1],
+        '>' => [4, 2, 1, 2, 4],
+        '#' => [5, 7, 5, 7, 5],
+        '_' => [0, 0, 0, 0, 7],
+        // '?' and anything the font does not have
+        _ => [6, 1, 2, 0, 2],
+    }
+}
+
+fn blend(pixel: &mut [u8], color: [u8; 4]) {
+    let alpha = color[3] as u16;
+    for (channel, source) in pixel.iter_mut().zip(color.iter()).take(3) {
+        *channel = ((*source as u16 * alpha + *channel as u16 * (0xFF - alpha)) / 0xFF) as u8;
+    }
+    pixel[3] = 0xFF;
+}
+
+fn plot(frame: &mut Frame, x: usize, y: usize, color: [u8; 4]) {
+    if x < frame.width && y < frame.height {
+        let index = (y * frame.width + x) * 4;
+        blend(&mut frame.data[index..index + 4], color);
+    }
+}
+
+// the part of `rect` that is on the frame
+fn clip(frame: &Frame, rect: &Rect) -> Option<Rect> {
+    let right = (rect.x + rect.width).min(frame.width);
+    let bottom = (rect.y + rect.height).min(frame.height);
+    if rect.x >= right || rect.y >= bottom {
+        return None;
+    }
+    Some(Rect::new(rect.x, rect.y, right - rect.x, bottom - rect.y))
+}
+
+// width of the widest line in pixels, without the trailing spacing
+pub fn text_width(text: &str) -> usize {
+    let columns = text.lines().map(|line| line.chars().count()).max();
+    (columns.unwrap_or(0) * GLYPH_WIDTH).saturating_sub(1)
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Overlay::default()
+    }
+
+    // '\n' starts a new line below x. lowercase letters are drawn as capitals
+    pub fn text(&mut self, x: usize, y: usize, text: &str, color: [u8; 4]) {
+        self.commands.push(Command::Text {
+            x: x,
+            y: y,
+            text: String::from(text),
+            color: color,
+        });
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: [u8; 4]) {
+        self.commands.push(Command::Rect {
+            rect: rect,
+            color: color,
+            filled: true,
+        });
+    }
+
+    // a one pixel border
+    pub fn stroke_rect(&mut self, rect: Rect, color: [u8; 4]) {
+        self.commands.push(Command::Rect {
+            rect: rect,
+            color: color,
+            filled: false,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    // composites everything in order and returns the rows that were drawn on, which the
+    // next frame has to convert again even when the picture below did not change
+    pub fn draw(&self, frame: &mut Frame) -> Range<usize> {
+        let mut top = frame.height;
+        let mut bottom = 0;
+        let mut touched = |frame: &mut Frame, rect: Rect| {
+            top = top.min(rect.y);
+            bottom = bottom.max(rect.y + rect.height);
+            frame.mark_dirty(rect);
+        };
+
+        for command in self.commands.iter() {
+            match command {
+                Command::Rect {
+                    rect,
+                    color,
+                    filled,
+                } => {
+                    let clipped = match clip(frame, rect) {
+                        Some(clipped) => clipped,
+                        None => continue,
+                    };
+                    let right = rect.x + rect.width - 1;
+                    let bottom = rect.y + rect.height - 1;
+                    for y in clipped.y..clipped.y + clipped.height {
+                        for x in clipped.x..clipped.x + clipped.width {
+                            let edge = x == rect.x || x == right || y == rect.y || y == bottom;
+                            if *filled || edge {
+                                plot(frame, x, y, *color);
+                            }
+                        }
+                    }
+                    touched(frame, clipped);
+                }
+                Command::Text { x, y, text, color } => {
+                    for (line, text) in text.lines().enumerate() {
+                        let top = y + line * GLYPH_HEIGHT;
+                        for (column, c) in text.chars().enumerate() {
+                            let left = x + column * GLYPH_WIDTH;
+                            for (row, bits) in glyph(c).iter().enumerate() {
+                                for bit in 0..3 {
+                                    if bits & (0b100 >> bit) != 0 {
+                                        plot(frame, left + bit, top + row, *color);
+                                    }
+                                }
+                            }
+                        }
+                        let width = text.chars().count() * GLYPH_WIDTH;
+                        if let Some(clipped) = clip(frame, &Rect::new(*x, top, width, GLYPH_HEIGHT))
+                        {
+                            touched(frame, clipped);
+                        }
+                    }
+                }
+            }
+        }
+
+        if top >= bottom {
+            return 0..0;
+        }
+        top..bottom
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    #[test]
+    fn test_draw() {
+        let mut frame = Frame::new(16, 16);
+        let mut overlay = Overlay::new();
+        assert_eq!(overlay.draw(&mut frame), 0..0);
+
+        overlay.text(1, 2, "1", WHITE);
+        overlay.stroke_rect(Rect::new(10, 10, 3, 3), [0xFF, 0x00, 0x00, 0xFF]);
+        overlay.fill_rect(Rect::new(14, 0, 8, 1), [0x00, 0x00, 0xFF, 0x80]);
+        assert_eq!(overlay.draw(&mut frame), 0..13);
+
+        // the 1 is .#. ##. .#. .#. ###
+        assert_eq!(frame.pixel(2, 2), WHITE);
+        assert_eq!(frame.pixel(1, 2), [0, 0, 0, 0]);
+        assert_eq!(frame.pixel(1, 3), WHITE);
+        assert_eq!(frame.pixel(3, 6), WHITE);
+        // border only
+        assert_eq!(frame.pixel(10, 12), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(frame.pixel(11, 11), [0, 0, 0, 0]);
+        // half blended and clipped at the edge
+        assert_eq!(frame.pixel(15, 0), [0x00, 0x00, 0x80, 0xFF]);
+        assert_eq!(text_width("ab\nabc"), 11);
+    }
+}
@@ -0,0 +1,77 @@
+// https://wiki.nesdev.com/w/index.php/PPU_palettes#2C02
+#[rustfmt::skip]
+pub static SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
+    (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96),
+    (0xA1, 0x00, 0x5E), (0xC7, 0x00, 0x28), (0xBA, 0x06, 0x00), (0x8C, 0x17, 0x00),
+    (0x5C, 0x2F, 0x00), (0x10, 0x45, 0x00), (0x05, 0x4A, 0x00), (0x00, 0x47, 0x2E),
+    (0x00, 0x41, 0x66), (0x00, 0x00, 0x00), (0x05, 0x05, 0x05), (0x05, 0x05, 0x05),
+    (0xC7, 0xC7, 0xC7), (0x00, 0x77, 0xFF), (0x21, 0x55, 0xFF), (0x82, 0x37, 0xFA),
+    (0xEB, 0x2F, 0xB5), (0xFF, 0x29, 0x50), (0xFF, 0x22, 0x00), (0xD6, 0x32, 0x00),
+    (0xC4, 0x62, 0x00), (0x35, 0x80, 0x00), (0x05, 0x8F, 0x00), (0x00, 0x8A, 0x55),
+    (0x00, 0x99, 0xCC), (0x21, 0x21, 0x21), (0x09, 0x09, 0x09), (0x09, 0x09, 0x09),
+    (0xFF, 0xFF, 0xFF), (0x0F, 0xD7, 0xFF), (0x69, 0xA2, 0xFF), (0xD4, 0x80, 0xFF),
+    (0xFF, 0x45, 0xF3), (0xFF, 0x61, 0x8B), (0xFF, 0x88, 0x33), (0xFF, 0x9C, 0x12),
+    (0xFA, 0xBC, 0x20), (0x9F, 0xE3, 0x0E), (0x2B, 0xF0, 0x35), (0x0C, 0xF0, 0xA4),
+    (0x05, 0xFB, 0xFF), (0x5E, 0x5E, 0x5E), (0x0D, 0x0D, 0x0D), (0x0D, 0x0D, 0x0D),
+    (0xFF, 0xFF, 0xFF), (0xA6, 0xFC, 0xFF), (0xB3, 0xEC, 0xFF), (0xDA, 0xAB, 0xEB),
+    (0xFF, 0xA8, 0xF9), (0xFF, 0xAB, 0xB3), (0xFF, 0xD2, 0xB0), (0xFF, 0xEF, 0xA6),
+    (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
+    (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11),
+];
+
+/*
+https://wiki.nesdev.com/w/index.php/Colour_emphasis
+
+    each emphasis bit of PPUMASK darkens the two other color channels, to about 81.6%
+    of their brightness
+*/
+const EMPHASIS_ATTENUATION: u16 = 209;
+
+// darkens RGBA pixels for the emphasis bits of PPUMASK, red in bit 0 to blue in bit 2
+pub fn emphasize(rgba: &mut [u8], emphasis: u8) {
+    // a channel is darkened when any other channel is emphasized
+    let darken = [
+        emphasis & 0b110 != 0,
+        emphasis & 0b101 != 0,
+        emphasis & 0b011 != 0,
+    ];
+    for pixel in rgba.chunks_exact_mut(4) {
+        for (channel, darken) in pixel.iter_mut().zip(darken.iter()) {
+            if *darken {
+                *channel = (*channel as u16 * EMPHASIS_ATTENUATION / 256) as u8;
+            }
+        }
+    }
+}
+
+// converts system palette indices (one per pixel) to RGBA
+pub fn to_rgba(indices: &[u8], rgba: &mut [u8]) {
+    #[allow(unused_mut)]
+    let mut done = 0;
+
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    {
+        done = super::palette_simd::to_rgba_wasm_simd128(indices, rgba);
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    {
+        if std::is_x86_feature_detected!("ssse3") {
+            done = unsafe { super::palette_simd::to_rgba_ssse3(indices, rgba) };
+        }
+    }
+
+    // whatever is left over from the 16 pixel chunks, or everything without simd
+    let rest = (done * 4).min(rgba.len());
+    to_rgba_scalar(&indices[done..], &mut rgba[rest..]);
+}
+
+pub fn to_rgba_scalar(indices: &[u8], rgba: &mut [u8]) {
+    for (index, pixel) in indices.iter().zip(rgba.chunks_exact_mut(4)) {
+        let (r, g, b) = SYSTEM_PALETTE[(*index & 0x3F) as usize];
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+        pixel[3] = 255;
+    }
+}
@@ -0,0 +1,205 @@
+// simd versions of palette::to_rgba, 16 pixels at a time.
+//
+// the 64 entry palette is split per channel into four 16 byte tables. a byte shuffle
+// looks up the low 4 bits of all 16 indices in each table at once, and the high 2 bits
+// pick which of the four results a pixel keeps. the r, g, b and a vectors are then
+// interleaved into 64 bytes of RGBA.
+//
+// the wasm path needs simd128, which is opt-in for browsers that lack it:
+//     RUSTFLAGS="-C target-feature=+simd128" cargo build --target wasm32-unknown-unknown
+// x86_64 (tests, benchmarks, native frontends) picks ssse3 at runtime, which needs std.
+
+use super::palette::SYSTEM_PALETTE;
+
+pub const LANES: usize = 16;
+
+// tables[channel][high bits] holds the 16 colors sharing those high bits
+fn channel_tables() -> [[[u8; LANES]; 4]; 3] {
+    let mut tables = [[[0u8; LANES]; 4]; 3];
+    for (index, (r, g, b)) in SYSTEM_PALETTE.iter().enumerate() {
+        let (high, low) = (index >> 4, index & 0x0F);
+        tables[0][high][low] = *r;
+        tables[1][high][low] = *g;
+        tables[2][high][low] = *b;
+    }
+    tables
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn to_rgba_wasm_simd128(indices: &[u8], rgba: &mut [u8]) -> usize {
+    use core::arch::wasm32::*;
+
+    let tables = channel_tables();
+    let mut channels = [[u8x16_splat(0); 4]; 3];
+    for (channel, table) in channels.iter_mut().zip(tables.iter()) {
+        for (bank, colors) in channel.iter_mut().zip(table.iter()) {
+            *bank = unsafe { v128_load(colors.as_ptr() as *const v128) };
+        }
+    }
+
+    let alpha = u8x16_splat(0xFF);
+    let chunks = indices.len().min(rgba.len() / 4) / LANES;
+    for chunk in 0..chunks {
+        let index = unsafe { v128_load(indices[chunk * LANES..].as_ptr() as *const v128) };
+        let index = v128_and(index, u8x16_splat(0x3F));
+        let low = v128_and(index, u8x16_splat(0x0F));
+        let high = u8x16_shr(index, 4);
+
+        let lookup = |tables: &[v128; 4]| {
+            let mut color = u8x16_splat(0);
+            for (bank, table) in tables.iter().enumerate() {
+                let selected = u8x16_eq(high, u8x16_splat(bank as u8));
+                color = v128_or(color, v128_and(u8x16_swizzle(*table, low), selected));
+            }
+            color
+        };
+        let (r, g, b) = (
+            lookup(&channels[0]),
+            lookup(&channels[1]),
+            lookup(&channels[2]),
+        );
+
+        let rg_low = u8x16_shuffle::<0, 16, 1, 17, 2, 18, 3, 19, 4, 20, 5, 21, 6, 22, 7, 23>(r, g);
+        let rg_high =
+            u8x16_shuffle::<8, 24, 9, 25, 10, 26, 11, 27, 12, 28, 13, 29, 14, 30, 15, 31>(r, g);
+        let ba_low =
+            u8x16_shuffle::<0, 16, 1, 17, 2, 18, 3, 19, 4, 20, 5, 21, 6, 22, 7, 23>(b, alpha);
+        let ba_high =
+            u8x16_shuffle::<8, 24, 9, 25, 10, 26, 11, 27, 12, 28, 13, 29, 14, 30, 15, 31>(b, alpha);
+
+        let pixels = [
+            u16x8_shuffle::<0, 8, 1, 9, 2, 10, 3, 11>(rg_low, ba_low),
+            u16x8_shuffle::<4, 12, 5, 13, 6, 14, 7, 15>(rg_low, ba_low),
+            u16x8_shuffle::<0, 8, 1, 9, 2, 10, 3, 11>(rg_high, ba_high),
+            u16x8_shuffle::<4, 12, 5, 13, 6, 14, 7, 15>(rg_high, ba_high),
+        ];
+        let out = &mut rgba[chunk * LANES * 4..(chunk + 1) * LANES * 4];
+        for (i, quad) in pixels.iter().enumerate() {
+            unsafe { v128_store(out[i * LANES..].as_mut_ptr() as *mut v128, *quad) };
+        }
+    }
+    chunks * LANES
+}
+
+/// Converts whole chunks of 16 pixels and returns how many pixels were done, the caller
+/// finishes the rest with the scalar loop. `rgba` takes 4 bytes a pixel, when it is
+/// shorter than `indices.len() * 4` only the pixels that fit are converted.
+///
+/// # Safety
+/// The cpu must support SSSE3, check with `is_x86_feature_detected!("ssse3")` first.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+pub unsafe fn to_rgba_ssse3(indices: &[u8], rgba: &mut [u8]) -> usize {
+    use core::arch::x86_64::*;
+
+    let tables = channel_tables();
+    let mut channels = [[_mm_setzero_si128(); 4]; 3];
+    for (channel, table) in channels.iter_mut().zip(tables.iter()) {
+        for (bank, colors) in channel.iter_mut().zip(table.iter()) {
+            *bank = _mm_loadu_si128(colors.as_ptr() as *const __m128i);
+        }
+    }
+
+    let alpha = _mm_set1_epi8(-1);
+    let chunks = indices.len().min(rgba.len() / 4) / LANES;
+    for chunk in 0..chunks {
+        let index = _mm_loadu_si128(indices[chunk * LANES..].as_ptr() as *const __m128i);
+        let low = _mm_and_si128(index, _mm_set1_epi8(0x0F));
+        // there is no byte shift, shift words and drop what crossed over
+        let high = _mm_and_si128(_mm_srli_epi16(index, 4), _mm_set1_epi8(0x03));
+
+        let r = lookup_ssse3(&channels[0], low, high);
+        let g = lookup_ssse3(&channels[1], low, high);
+        let b = lookup_ssse3(&channels[2], low, high);
+
+        let rg_low = _mm_unpacklo_epi8(r, g);
+        let rg_high = _mm_unpackhi_epi8(r, g);
+        let ba_low = _mm_unpacklo_epi8(b, alpha);
+        let ba_high = _mm_unpackhi_epi8(b, alpha);
+
+        let pixels = [
+            _mm_unpacklo_epi16(rg_low, ba_low),
+            _mm_unpackhi_epi16(rg_low, ba_low),
+            _mm_unpacklo_epi16(rg_high, ba_high),
+            _mm_unpackhi_epi16(rg_high, ba_high),
+        ];
+        let out = &mut rgba[chunk * LANES * 4..(chunk + 1) * LANES * 4];
+        for (i, quad) in pixels.iter().enumerate() {
+            _mm_storeu_si128(out[i * LANES..].as_mut_ptr() as *mut __m128i, *quad);
+        }
+    }
+    chunks * LANES
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn lookup_ssse3(
+    tables: &[core::arch::x86_64::__m128i; 4],
+    low: core::arch::x86_64::__m128i,
+    high: core::arch::x86_64::__m128i,
+) -> core::arch::x86_64::__m128i {
+    use core::arch::x86_64::*;
+
+    let mut color = _mm_setzero_si128();
+    for (bank, table) in tables.iter().enumerate() {
+        let selected = _mm_cmpeq_epi8(high, _mm_set1_epi8(bank as i8));
+        color = _mm_or_si128(
+            color,
+            _mm_and_si128(_mm_shuffle_epi8(*table, low), selected),
+        );
+    }
+    color
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::palette;
+    use std::time::Instant;
+
+    fn test_indices(len: usize) -> Vec<u8> {
+        // includes bits 6 and 7 to check they are ignored like in the scalar version
+        (0..len).map(|i| (i * 37 + i / 64) as u8).collect()
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        for len in [0, 15, 16, 64, 100, 256 * 240].iter() {
+            let indices = test_indices(*len);
+            let mut expected = vec![0u8; len * 4];
+            let mut actual = vec![0u8; len * 4];
+            palette::to_rgba_scalar(&indices, &mut expected);
+            palette::to_rgba(&indices, &mut actual);
+            assert!(expected == actual, "mismatch for {} pixels", len);
+        }
+    }
+
+    // cargo test --release bench_palette -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_palette() {
+        let indices = test_indices(256 * 240);
+        let mut rgba = vec![0u8; indices.len() * 4];
+        let rounds = 2000;
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            palette::to_rgba_scalar(&indices, &mut rgba);
+        }
+        let scalar = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            palette::to_rgba(&indices, &mut rgba);
+        }
+        let simd = start.elapsed();
+
+        println!(
+            "{} frames: scalar {:?}, simd {:?} ({:.1}x)",
+            rounds,
+            scalar,
+            simd,
+            scalar.as_secs_f64() / simd.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use super::frame::Frame;
+use super::palette;
+use crate::mapper::ChrBanks;
+use crate::ppu::bus::PpuBus;
+
+use alloc::vec::Vec;
+
+/*
+https://wiki.nesdev.com/w/index.php/PPU_pattern_tables
+
+    each pattern table is 256 tiles of 16 bytes, the low bit plane of a tile's 8 rows
+    followed by the high one. a viewer lays them out in 16 rows of 16 tiles
+*/
+pub const PATTERN_TABLE_SIZE: usize = 128;
+const TILES_PER_ROW: usize = 16;
+
+// pattern table `table` (0 at $0000, 1 at $1000) as `banks` map it, colored with
+// background palette 0-3 or sprite palette 4-7 of the palette ram
+pub fn render_pattern_table(bus: &PpuBus, banks: &ChrBanks, table: usize, palette: usize) -> Frame {
+    let colors: Vec<u8> = (0..4)
+        .map(|value| match value {
+            0 => bus.read_vram(0x3F00),
+            _ => bus.read_vram(0x3F00 + (palette % 8 * 4 + value) as u16),
+        })
+        .map(|color| color & 0x3F)
+        .collect();
+
+    let mut indices = vec![0; PATTERN_TABLE_SIZE * PATTERN_TABLE_SIZE];
+    for tile in 0..TILES_PER_ROW * TILES_PER_ROW {
+        let base = (table % 2 * 0x1000 + tile * 16) as u16;
+        for y in 0..8 {
+            let lo = bus.read_chr_banked(banks, base + y as u16);
+            let hi = bus.read_chr_banked(banks, base + y as u16 + 8);
+            for x in 0..8 {
+                let value = ((hi >> (7 - x)) & 1) << 1 | ((lo >> (7 - x)) & 1);
+                let row = tile / TILES_PER_ROW * 8 + y;
+                let column = tile % TILES_PER_ROW * 8 + x;
+                indices[row * PATTERN_TABLE_SIZE + column] = colors[value as usize];
+            }
+        }
+    }
+
+    let mut frame = Frame::new(PATTERN_TABLE_SIZE, PATTERN_TABLE_SIZE);
+    for y in 0..PATTERN_TABLE_SIZE {
+        let row = &indices[y * PATTERN_TABLE_SIZE..(y + 1) * PATTERN_TABLE_SIZE];
+        palette::to_rgba(row, frame.row_mut(y));
+    }
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::MirroringType;
+    use crate::mapper::nrom::NROM;
+    use crate::mapper::Mapper;
+    use crate::render::palette::SYSTEM_PALETTE;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    fn color(index: u8) -> [u8; 4] {
+        let (r, g, b) = SYSTEM_PALETTE[index as usize];
+        [r, g, b, 0xFF]
+    }
+
+    #[test]
+    fn test_render_pattern_table() {
+        // 16KB of chr, tile 1 of the first 8KB has its top row in color 1 and tile 1 of
+        // the second 8KB in color 3
+        let mut chr = vec![0; 0x4000];
+        chr[0x0010] = 0xFF;
+        chr[0x2010] = 0xFF;
+        chr[0x2018] = 0xFF;
+        let mapper: Box<dyn Mapper> =
+            Box::new(NROM::new(vec![0; 0x4000], chr, MirroringType::Horizontal));
+        let mut bus = PpuBus::new(Arc::new(AtomicRefCell::new(mapper)));
+        bus.palette[0] = 0x0F;
+        bus.palette[1] = 0x16;
+        bus.palette[3] = 0x30;
+
+        let first = bus.chr_banks();
+        let frame = render_pattern_table(&bus, &first, 0, 0);
+        assert_eq!(frame.pixel(8, 0), color(0x16));
+        assert_eq!(frame.pixel(8, 1), color(0x0F));
+        assert_eq!(frame.pixel(0, 0), color(0x0F));
+
+        let second = [8, 9, 10, 11, 12, 13, 14, 15];
+        let frame = render_pattern_table(&bus, &second, 0, 0);
+        assert_eq!(frame.pixel(15, 0), color(0x30));
+    }
+}
@@ -0,0 +1,156 @@
+use super::frame::Frame;
+use crate::crc32;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/*
+https://www.w3.org/TR/PNG/
+    frames are written as 8-bit RGBA with uncompressed (stored) deflate blocks, which keeps
+    the encoder tiny. decode only understands that subset, so it can read back what encode
+    wrote (golden images), not arbitrary png files.
+*/
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+const COLOR_TYPE_RGBA: u8 = 6;
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend(&(data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32::update(crc32::crc32(kind), data);
+    out.extend(&crc.to_be_bytes());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+pub fn encode(frame: &Frame) -> Vec<u8> {
+    let mut out = PNG_SIGNATURE.to_vec();
+
+    let mut header = Vec::new();
+    header.extend(&(frame.width as u32).to_be_bytes());
+    header.extend(&(frame.height as u32).to_be_bytes());
+    // bit depth, color type, compression, filter, interlace
+    header.extend(&[8, COLOR_TYPE_RGBA, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header);
+
+    // every scanline starts with its filter type, 0 = none
+    let mut raw = Vec::with_capacity((frame.width * 4 + 1) * frame.height);
+    for row in frame.data.chunks(frame.width * 4) {
+        raw.push(0);
+        raw.extend(row);
+    }
+
+    // zlib stream: header, stored deflate blocks, adler32 of the raw data
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(MAX_STORED_BLOCK).collect::<Vec<_>>();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        zlib.extend(&(block.len() as u16).to_le_bytes());
+        zlib.extend(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend(*block);
+    }
+    if blocks.is_empty() {
+        zlib.extend(&[1, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    zlib.extend(&adler32(&raw).to_be_bytes());
+    write_chunk(&mut out, b"IDAT", &zlib);
+
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, String> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| String::from("truncated png"))
+}
+
+pub fn decode(data: &[u8]) -> Result<Frame, String> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(String::from("not a png file"));
+    }
+
+    let (mut width, mut height) = (0, 0);
+    let mut zlib = Vec::new();
+    let mut pos = 8;
+    while pos < data.len() {
+        let len = read_u32(data, pos)? as usize;
+        let kind = data.get(pos + 4..pos + 8).ok_or("truncated png")?;
+        let body = data.get(pos + 8..pos + 8 + len).ok_or("truncated png")?;
+        match kind {
+            b"IHDR" => {
+                width = read_u32(body, 0)? as usize;
+                height = read_u32(body, 4)? as usize;
+                if body.get(8..13) != Some(&[8, COLOR_TYPE_RGBA, 0, 0, 0][..]) {
+                    return Err(String::from("only 8-bit rgba png is supported"));
+                }
+            }
+            b"IDAT" => zlib.extend(body),
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+
+    // inflate stored blocks only
+    let mut raw = Vec::new();
+    let mut pos = 2;
+    loop {
+        let header = *zlib.get(pos).ok_or("truncated zlib stream")?;
+        if header & 0b110 != 0 {
+            return Err(String::from(
+                "only uncompressed deflate blocks are supported",
+            ));
+        }
+        let len = zlib
+            .get(pos + 1..pos + 3)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or("truncated zlib stream")?;
+        raw.extend(
+            zlib.get(pos + 5..pos + 5 + len)
+                .ok_or("truncated zlib stream")?,
+        );
+        pos += 5 + len;
+        if header & 1 != 0 {
+            break;
+        }
+    }
+
+    let mut frame = Frame::new(width, height);
+    let stride = width * 4;
+    for (y, row) in raw.chunks(stride + 1).take(height).enumerate() {
+        if row[0] != 0 || row.len() != stride + 1 {
+            return Err(String::from("only unfiltered scanlines are supported"));
+        }
+        frame.data[y * stride..(y + 1) * stride].copy_from_slice(&row[1..]);
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut frame = Frame::new(300, 240);
+        for (i, byte) in frame.data.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        let png = encode(&frame);
+        assert_eq!(png[0..8], PNG_SIGNATURE);
+        assert!(decode(&png).unwrap() == frame);
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+}
@@ -0,0 +1,270 @@
+use super::frame::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::ppu::frame_stats::MAX_SPRITES_PER_SCANLINE;
+use crate::ppu::PPU;
+
+use alloc::vec::Vec;
+
+// what a pixel of the picture shows, for the debug tint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    Backdrop,
+    Background,
+    Sprite,
+    // sprite 0, also where it is behind the background. its hit happens where these
+    // pixels are on top of background ones
+    SpriteZero,
+}
+
+// mixed halfway into the pixels of each layer, the backdrop keeps its color
+const TINT_BACKGROUND: (u8, u8, u8) = (0x00, 0x40, 0xFF);
+const TINT_SPRITE: (u8, u8, u8) = (0x00, 0xFF, 0x40);
+const TINT_SPRITE_ZERO: (u8, u8, u8) = (0xFF, 0x00, 0xFF);
+
+// renders the selected nametable and the sprites in OAM as system palette indices,
+// one byte per pixel, and which layer each pixel came from. like the hardware only the
+// first 8 sprites of a scanline are drawn unless `sprite_limit` is off, which gets rid of
+// the flicker games use to show more
+pub fn render(ppu: &PPU, screen: &mut [u8], layers: &mut [Layer], sprite_limit: bool) {
+    let mut opaque = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+    render_background(ppu, screen, &mut opaque);
+    for (layer, opaque) in layers.iter_mut().zip(opaque.iter()) {
+        *layer = if *opaque {
+            Layer::Background
+        } else {
+            Layer::Backdrop
+        };
+    }
+    render_sprites(ppu, screen, layers, &opaque, sprite_limit);
+    apply_greyscale(ppu, screen);
+}
+
+// colors RGBA pixels by the layer they came from, so it shows which sprites are behind
+// the background and where sprite 0 can hit it
+pub fn debug_tint(rgba: &mut [u8], layers: &[Layer]) {
+    for (pixel, layer) in rgba.chunks_exact_mut(4).zip(layers.iter()) {
+        let (r, g, b) = match layer {
+            Layer::Backdrop => continue,
+            Layer::Background => TINT_BACKGROUND,
+            Layer::Sprite => TINT_SPRITE,
+            Layer::SpriteZero => TINT_SPRITE_ZERO,
+        };
+        pixel[0] = ((pixel[0] as u16 + r as u16) / 2) as u8;
+        pixel[1] = ((pixel[1] as u16 + g as u16) / 2) as u8;
+        pixel[2] = ((pixel[2] as u16 + b as u16) / 2) as u8;
+    }
+}
+
+// greyscale keeps only the brightness column of a color, on the lines it was on for.
+// emphasis is applied when converting to RGBA, see palette::emphasize
+fn apply_greyscale(ppu: &PPU, screen: &mut [u8]) {
+    let stats = ppu.frame_stats();
+    for (y, row) in screen.chunks_exact_mut(SCREEN_WIDTH).enumerate() {
+        if stats.color_effects_at(y as u16).get_grey_scale() {
+            row.iter_mut().for_each(|index| *index &= 0x30);
+        }
+    }
+}
+
+// the 4 bytes of the `i`th sprite of a scanline whose sprites start at oam[start]
+fn sprite_at(ppu: &PPU, start: u8, i: usize) -> [u8; 4] {
+    let offset = start as usize + i * 4;
+    let byte = |n: usize| ppu.oam[(offset + n) & 0xFF];
+    [byte(0), byte(1), byte(2), byte(3)]
+}
+
+// sprites of a scanline, counted from where its sprite evaluation started: 64 of them,
+// fewer when a game moved the start with OAMADDR (see FrameStats::oam_starts)
+fn sprites_from(start: u8) -> usize {
+    (0x100 - start as usize).div_ceil(4)
+}
+
+// for every scanline a bit per sprite from its start, set for the ones drawn there
+fn shown_sprites(ppu: &PPU, height: usize, sprite_limit: bool) -> Vec<u64> {
+    let stats = ppu.frame_stats();
+    let mut shown = vec![0u64; SCREEN_HEIGHT];
+    for (y, shown) in shown.iter_mut().enumerate() {
+        let start = stats.oam_start_at(y as u16);
+        let mut count = 0;
+        for i in 0..sprites_from(start) {
+            let top = sprite_at(ppu, start, i)[0] as usize + 1;
+            if y < top || y >= top + height {
+                continue;
+            }
+            if sprite_limit && count == MAX_SPRITES_PER_SCANLINE {
+                break;
+            }
+            count += 1;
+            *shown |= 1 << i;
+        }
+    }
+    shown
+}
+
+fn tile_pixel(ppu: &PPU, bank: u16, tile: u16, x: usize, y: usize) -> u8 {
+    let lo = ppu.bus.read_vram(bank + tile * 16 + y as u16);
+    let hi = ppu.bus.read_vram(bank + tile * 16 + y as u16 + 8);
+    ((hi >> (7 - x)) & 1) << 1 | ((lo >> (7 - x)) & 1)
+}
+
+fn render_background(ppu: &PPU, screen: &mut [u8], opaque: &mut [bool]) {
+    let bank = ppu.ctrl_register.get_background_pattern_table_address();
+    let nametable = ppu.ctrl_register.get_nametable_address();
+    let backdrop = ppu.bus.read_vram(0x3F00);
+
+    for tile_y in 0..(SCREEN_HEIGHT / 8) {
+        for tile_x in 0..(SCREEN_WIDTH / 8) {
+            let tile_addr = nametable + (tile_y * 32 + tile_x) as u16;
+            let tile = ppu.bus.read_vram(tile_addr) as u16;
+
+            // https://wiki.nesdev.com/w/index.php/PPU_attribute_tables
+            let attr_addr = nametable + 0x3C0 + (tile_y / 4 * 8 + tile_x / 4) as u16;
+            let attr = ppu.bus.read_vram(attr_addr);
+            let shift = (tile_y % 4) / 2 * 4 + (tile_x % 4) / 2 * 2;
+            let palette = (attr >> shift) & 0b11;
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let value = tile_pixel(ppu, bank, tile, x, y);
+                    let color = if value == 0 {
+                        backdrop
+                    } else {
+                        ppu.bus.read_vram(0x3F00 + (palette * 4 + value) as u16)
+                    };
+
+                    let index = (tile_y * 8 + y) * SCREEN_WIDTH + tile_x * 8 + x;
+                    screen[index] = color & 0x3F;
+                    opaque[index] = value != 0;
+                }
+            }
+        }
+    }
+}
+
+fn render_sprites(
+    ppu: &PPU,
+    screen: &mut [u8],
+    layers: &mut [Layer],
+    opaque: &[bool],
+    sprite_limit: bool,
+) {
+    let height = ppu.ctrl_register.get_sprite_size() as usize;
+    let shown = shown_sprites(ppu, height, sprite_limit);
+    let stats = ppu.frame_stats();
+
+    for (y, shown) in shown.iter().enumerate() {
+        let start = stats.oam_start_at(y as u16);
+        // lower OAM entries have priority, so draw them last
+        for i in (0..sprites_from(start)).rev() {
+            if shown & 1 << i == 0 {
+                continue;
+            }
+            let [sprite_y, tile, attr, sprite_x] = sprite_at(ppu, start, i);
+            // sprite data is delayed by one scanline
+            let row = y - (sprite_y as usize + 1);
+            let tile = tile as u16;
+            let sprite_x = sprite_x as usize;
+
+            let flip_h = attr & 0b0100_0000 != 0;
+            let flip_v = attr & 0b1000_0000 != 0;
+            let behind_background = attr & 0b0010_0000 != 0;
+            let palette = 0x10 + (attr & 0b11) * 4;
+
+            // 8x16 sprites take their bank from bit 0 of the tile index
+            let (bank, tile) = if height == 16 {
+                ((tile & 1) * 0x1000, tile & 0xFE)
+            } else {
+                (ppu.ctrl_register.get_sprite_pattern_table_address(), tile)
+            };
+
+            let sprite_row = if flip_v { height - 1 - row } else { row };
+            let row_tile = tile + (sprite_row / 8) as u16;
+            for col in 0..8 {
+                let x = sprite_x + col;
+                if x >= SCREEN_WIDTH {
+                    break;
+                }
+
+                let sprite_col = if flip_h { 7 - col } else { col };
+                let value = tile_pixel(ppu, bank, row_tile, sprite_col, sprite_row % 8);
+                if value == 0 {
+                    continue;
+                }
+
+                // the first sprite evaluated is the one that can hit the background
+                let index = y * SCREEN_WIDTH + x;
+                if i == 0 {
+                    layers[index] = Layer::SpriteZero;
+                }
+                if behind_background && opaque[index] {
+                    continue;
+                }
+                screen[index] = ppu.bus.read_vram(0x3F00 + (palette + value) as u16) & 0x3F;
+                if i != 0 {
+                    layers[index] = Layer::Sprite;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::MirroringType;
+    use crate::mapper::nrom::NROM;
+    use crate::mapper::Mapper;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sprite_limit() {
+        let mapper: Box<dyn Mapper> = Box::new(NROM::new(
+            vec![0; 0x4000],
+            vec![0; 0x2000],
+            MirroringType::Horizontal,
+        ));
+        let mut ppu = PPU::new(Arc::new(AtomicRefCell::new(mapper)));
+        // ten sprites from line 21 on, the others below the screen
+        ppu.oam = [0xFF; 256];
+        for sprite in ppu.oam.chunks_exact_mut(4).take(10) {
+            sprite[0] = 20;
+        }
+
+        let shown = shown_sprites(&ppu, 8, true);
+        assert_eq!((shown[20], shown[21], shown[28]), (0, 0xFF, 0xFF));
+        let shown = shown_sprites(&ppu, 8, false);
+        assert_eq!((shown[21], shown[29]), (0x3FF, 0));
+    }
+
+    #[test]
+    fn test_debug_tint() {
+        // every tile opaque, sprite 0 behind the background at x 16, sprite 1 at x 40
+        let mapper: Box<dyn Mapper> = Box::new(NROM::new(
+            vec![0; 0x4000],
+            vec![0xFF; 0x2000],
+            MirroringType::Horizontal,
+        ));
+        let mut ppu = PPU::new(Arc::new(AtomicRefCell::new(mapper)));
+        ppu.oam = [0xFF; 256];
+        ppu.oam[..8].copy_from_slice(&[20, 0, 0b0010_0000, 16, 20, 0, 0, 40]);
+
+        let mut screen = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut layers = vec![Layer::Backdrop; SCREEN_WIDTH * SCREEN_HEIGHT];
+        render(&ppu, &mut screen, &mut layers, true);
+        let row = &layers[21 * SCREEN_WIDTH..22 * SCREEN_WIDTH];
+        assert_eq!(
+            (row[0], row[16], row[23], row[40], row[48]),
+            (
+                Layer::Background,
+                Layer::SpriteZero,
+                Layer::SpriteZero,
+                Layer::Sprite,
+                Layer::Background
+            )
+        );
+
+        let mut rgba = [0x80, 0x80, 0x80, 0xFF, 0x80, 0x80, 0x80, 0xFF];
+        debug_tint(&mut rgba, &[Layer::Backdrop, Layer::SpriteZero]);
+        assert_eq!(rgba, [0x80, 0x80, 0x80, 0xFF, 0xBF, 0x40, 0xBF, 0xFF]);
+    }
+}
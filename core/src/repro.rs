@@ -0,0 +1,359 @@
+// self-contained reproductions of a divergence (netplay desync, a regression movie that
+// ends in the wrong state) for bug reports. the console has no serialized savestate, but
+// it is deterministic, so a bundle carries what recreates one: the rom, the power-on
+// config and every frame of input since power-on. loading replays that input without
+// output up to the start of the window, N frames before the divergence, and the window
+// itself is then stepped frame by frame against the state hashes of the original run.
+//
+// layout, numbers little endian:
+//
+//     "FNRP" version:u8
+//     power_on_ram:u8 seed:u64 power_on_cpu:u8 seed:u64 accuracy:u8 overclock:u16 console:u8
+//     unstable_magic:u8
+//     start_frame:u64 diverged_frame:u64
+//     hashes:u32 (frame:u64 hash:u64)*
+//     rom_len:u32 rom
+//     fm2_len:u32 fm2 movie text
+
+use crate::cartridge::Cartridge;
+use crate::config::{Accuracy, Config, Console, PowerOnCpu, PowerOnRam};
+use crate::joypad::Port;
+use crate::movie::{Movie, MovieFrame};
+use crate::nes::Nes;
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// frames before the divergence a bundle starts its window at
+pub const REPRO_WINDOW_FRAMES: u64 = 120;
+
+const MAGIC: &[u8; 4] = b"FNRP";
+const VERSION: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReproBundle {
+    pub rom: Vec<u8>,
+    pub config: Config,
+    // every frame since power-on up to where the original run stopped
+    pub movie: Movie,
+    // the window starts before this frame, see Nes::stats().frames
+    pub start_frame: u64,
+    pub diverged_frame: u64,
+    // Nes::state_hash after each frame of the window in the original run, keyed by
+    // stats().frames after the frame
+    pub hashes: Vec<(u64, u64)>,
+}
+
+// what Nes keeps while capturing, see Nes::capture_repro_on_desync
+pub(crate) struct ReproCapture {
+    pub(crate) rom: Vec<u8>,
+    pub(crate) window: u64,
+    pub(crate) hashes: VecDeque<(u64, u64)>,
+}
+
+impl ReproCapture {
+    pub(crate) fn new(rom: &[u8], window: u64) -> Self {
+        ReproCapture {
+            rom: rom.to_vec(),
+            window: window,
+            hashes: VecDeque::new(),
+        }
+    }
+
+    // hashes of frames older than a window before `frame` can never end up in a bundle
+    pub(crate) fn frame_done(&mut self, frame: u64, hash: u64) {
+        self.hashes.push_back((frame, hash));
+        // a divergence is reported up to a netplay window after the frame it happened on
+        let keep = self.window * 2;
+        while self
+            .hashes
+            .front()
+            .is_some_and(|(oldest, _)| *oldest + keep < frame)
+        {
+            self.hashes.pop_front();
+        }
+    }
+
+    // the frames after a restored snapshot are run and hashed again
+    pub(crate) fn restored(&mut self, frame: u64) {
+        self.hashes.retain(|(hashed, _)| *hashed <= frame);
+    }
+
+    pub(crate) fn bundle(&self, config: &Config, movie: &Movie, diverged: u64) -> ReproBundle {
+        let start_frame = diverged.saturating_sub(self.window);
+        ReproBundle {
+            rom: self.rom.clone(),
+            config: config.clone(),
+            movie: movie.clone(),
+            start_frame: start_frame,
+            diverged_frame: diverged,
+            hashes: self
+                .hashes
+                .iter()
+                .filter(|(frame, _)| *frame > start_frame)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+fn run_movie_frame(nes: &mut Nes, frame: &MovieFrame) {
+    nes.cpu.bus.joypad(Port::One).set_buttons(frame.joypad1);
+    nes.cpu.bus.joypad(Port::Two).set_buttons(frame.joypad2);
+    if frame.reset {
+        nes.reset();
+    }
+    nes.run_frame();
+}
+
+impl ReproBundle {
+    // plays `movie` from power-on and keeps the hashes of the `window` frames before
+    // its end, for a divergence found after the fact like a failing regression movie
+    pub fn record(rom: &[u8], config: &Config, movie: &Movie, window: u64) -> Result<Self, String> {
+        let mut nes = Nes::with_config(Cartridge::new(rom)?, config);
+        nes.reset();
+        let mut capture = ReproCapture::new(rom, window);
+        for frame in movie.frames.iter() {
+            run_movie_frame(&mut nes, frame);
+            capture.frame_done(nes.stats().frames, nes.state_hash());
+        }
+        Ok(capture.bundle(config, movie, movie.frames.len() as u64))
+    }
+
+    // the console at the start of the window
+    pub fn load_state(&self) -> Result<Nes, String> {
+        let mut nes = Nes::with_config(Cartridge::new(&self.rom)?, &self.config);
+        nes.reset();
+        nes.take_events();
+        nes.resimulating = true;
+        for frame in self.movie.frames.iter().take(self.start_frame as usize) {
+            run_movie_frame(&mut nes, frame);
+        }
+        nes.resimulating = false;
+        Ok(nes)
+    }
+
+    // input of the window, the frame at start_frame first
+    pub fn window(&self) -> &[MovieFrame] {
+        let start = (self.start_frame as usize).min(self.movie.frames.len());
+        &self.movie.frames[start..]
+    }
+
+    // runs one frame of the window on a console from load_state
+    pub fn step(&self, nes: &mut Nes) -> bool {
+        match self.movie.frames.get(nes.stats().frames as usize) {
+            Some(frame) => {
+                run_movie_frame(nes, frame);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // plays the window and returns the first frame whose state differs from the
+    // original run, None when this build reproduces it exactly
+    pub fn first_mismatch(&self) -> Result<Option<u64>, String> {
+        let mut nes = self.load_state()?;
+        while self.step(&mut nes) {
+            let frame = nes.stats().frames;
+            let recorded = self.hashes.iter().find(|(hashed, _)| *hashed == frame);
+            if let Some((_, hash)) = recorded {
+                if *hash != nes.state_hash() {
+                    return Ok(Some(frame));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        let (ram, ram_seed) = match self.config.power_on_ram {
+            PowerOnRam::Zero => (0, 0),
+            PowerOnRam::Random { seed } => (1, seed),
+            PowerOnRam::Pattern => (2, 0),
+        };
+        let (cpu, cpu_seed) = match self.config.power_on_cpu {
+            PowerOnCpu::Zero => (0, 0),
+            PowerOnCpu::Random { seed } => (1, seed),
+        };
+        let accuracy = match self.config.accuracy {
+            Accuracy::Fast => 0,
+            Accuracy::Balanced => 1,
+            Accuracy::Cycle => 2,
+        };
+        bytes.push(ram);
+        bytes.extend_from_slice(&ram_seed.to_le_bytes());
+        bytes.push(cpu);
+        bytes.extend_from_slice(&cpu_seed.to_le_bytes());
+        bytes.push(accuracy);
+        bytes.extend_from_slice(&self.config.overclock_scanlines.to_le_bytes());
+        bytes.push(match self.config.console {
+            Console::Nes => 0,
+            Console::Famicom => 1,
+            Console::AvFamicom => 2,
+        });
+        bytes.push(self.config.unstable_magic);
+
+        bytes.extend_from_slice(&self.start_frame.to_le_bytes());
+        bytes.extend_from_slice(&self.diverged_frame.to_le_bytes());
+        bytes.extend_from_slice(&(self.hashes.len() as u32).to_le_bytes());
+        for (frame, hash) in self.hashes.iter() {
+            bytes.extend_from_slice(&frame.to_le_bytes());
+            bytes.extend_from_slice(&hash.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.rom.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.rom);
+        let fm2 = self.movie.to_fm2();
+        bytes.extend_from_slice(&(fm2.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(fm2.as_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader {
+            bytes: bytes,
+            pos: 0,
+        };
+        if reader.take(4)? != MAGIC {
+            return Err(String::from("not a repro bundle"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("unsupported repro bundle version {}", version));
+        }
+
+        let power_on_ram = match (reader.u8()?, reader.u64()?) {
+            (0, _) => PowerOnRam::Zero,
+            (1, seed) => PowerOnRam::Random { seed: seed },
+            (2, _) => PowerOnRam::Pattern,
+            (other, _) => return Err(format!("bad power-on ram {}", other)),
+        };
+        let power_on_cpu = match (reader.u8()?, reader.u64()?) {
+            (0, _) => PowerOnCpu::Zero,
+            (1, seed) => PowerOnCpu::Random { seed: seed },
+            (other, _) => return Err(format!("bad power-on cpu {}", other)),
+        };
+        let accuracy = match reader.u8()? {
+            0 => Accuracy::Fast,
+            1 => Accuracy::Balanced,
+            2 => Accuracy::Cycle,
+            other => return Err(format!("bad accuracy {}", other)),
+        };
+        let overclock_scanlines = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+        let console = match reader.u8()? {
+            0 => Console::Nes,
+            1 => Console::Famicom,
+            2 => Console::AvFamicom,
+            other => return Err(format!("bad console {}", other)),
+        };
+        let unstable_magic = reader.u8()?;
+
+        let start_frame = reader.u64()?;
+        let diverged_frame = reader.u64()?;
+        let mut hashes = Vec::new();
+        for _ in 0..reader.u32()? {
+            hashes.push((reader.u64()?, reader.u64()?));
+        }
+
+        let rom_len = reader.u32()? as usize;
+        let rom = reader.take(rom_len)?.to_vec();
+        let fm2_len = reader.u32()? as usize;
+        let fm2 = core::str::from_utf8(reader.take(fm2_len)?)
+            .map_err(|_| String::from("movie is not utf-8"))?;
+
+        Ok(ReproBundle {
+            rom: rom,
+            config: Config {
+                power_on_ram: power_on_ram,
+                power_on_cpu: power_on_cpu,
+                accuracy: accuracy,
+                overclock_scanlines: overclock_scanlines,
+                console: console,
+                unstable_magic: unstable_magic,
+            },
+            movie: Movie::parse(fm2)?,
+            start_frame: start_frame,
+            diverged_frame: diverged_frame,
+            hashes: hashes,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err(String::from("repro bundle is truncated"));
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut buffer = [0; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buffer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::joypad::JoypadButton;
+
+    #[test]
+    fn test_capture_on_desync() {
+        let rom = include_bytes!("../res/regression/joypad.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&rom).unwrap());
+        nes.reset();
+        nes.capture_repro_on_desync(&rom, 4).unwrap();
+        nes.queue_input(3, Port::One, JoypadButton::START);
+        nes.queue_input(6, Port::One, JoypadButton::empty());
+        for _ in 0..10 {
+            nes.run_frame();
+        }
+        assert!(nes.take_repro_bundle().is_none());
+
+        nes.push_event(Event::DesyncDetected { frame: 8 });
+        let bundle = nes.take_repro_bundle().unwrap();
+        assert!(nes.take_repro_bundle().is_none());
+        assert_eq!((bundle.start_frame, bundle.diverged_frame), (4, 8));
+        assert_eq!(bundle.window().len(), 6);
+        assert_eq!(bundle.hashes.first().map(|(frame, _)| *frame), Some(5));
+        assert_eq!(bundle.hashes.last().map(|(frame, _)| *frame), Some(10));
+
+        let loaded = ReproBundle::from_bytes(&bundle.to_bytes()).unwrap();
+        assert_eq!(loaded, bundle);
+        assert_eq!(loaded.load_state().unwrap().stats().frames, 4);
+        assert_eq!(loaded.first_mismatch(), Ok(None));
+
+        let mut broken = loaded.clone();
+        broken.hashes[2].1 ^= 1;
+        assert_eq!(broken.first_mismatch(), Ok(Some(7)));
+
+        assert!(ReproBundle::from_bytes(b"FNRP").is_err());
+    }
+}
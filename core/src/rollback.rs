@@ -0,0 +1,336 @@
+// GGPO-style rollback for two player netplay, independent of how inputs travel between
+// the peers. every frame runs right away with the local buttons and a guess for the remote
+// ones (whatever the peer held last). when the real remote input for a frame arrives and
+// differs from the guess, the console goes back to the snapshot taken before that frame
+// and runs up to the present again with what is known now.
+//
+// each side calls advance once per frame and sends the returned frame number with its
+// buttons to the peer, which hands them to add_remote_input in the order they were sent.
+// frames whose input is known on both sides settle and come out of take_settled, with a
+// state hash every CHECKSUM_INTERVAL frames to compare against the peer or spectators.
+
+use crate::events::Event;
+use crate::joypad::{JoypadButton, Port};
+use crate::nes::{Nes, Snapshot};
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+// how far the local side may run ahead of the last confirmed remote input. beyond that
+// advance refuses and the frontend has to wait for the peer
+pub const MAX_ROLLBACK_FRAMES: u64 = 8;
+
+// settled frames that end on a multiple of this carry a state hash
+pub const CHECKSUM_INTERVAL: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RollbackError {
+    // more than MAX_ROLLBACK_FRAMES unconfirmed frames, wait for the peer
+    TooFarAhead { frame: u64 },
+    // remote input has to arrive frame by frame without gaps
+    OutOfOrder { expected: u64, frame: u64 },
+}
+
+// a frame that will never be rolled back again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SettledFrame {
+    pub frame: u64,
+    pub joypad1: JoypadButton,
+    pub joypad2: JoypadButton,
+    // Nes::state_hash once the frame has run, for every CHECKSUM_INTERVAL-th frame
+    pub state_hash: Option<u64>,
+}
+
+pub struct Rollback {
+    local_port: Port,
+    local_input: BTreeMap<u64, JoypadButton>,
+    remote_input: BTreeMap<u64, JoypadButton>,
+    // the remote buttons each unconfirmed frame ran with
+    predicted: BTreeMap<u64, JoypadButton>,
+    // taken at the start of every unconfirmed frame
+    snapshots: VecDeque<(u64, Snapshot)>,
+    // next remote frame expected
+    confirmed: u64,
+    // state hashes at the start of frames on a CHECKSUM_INTERVAL boundary
+    hashes: BTreeMap<u64, u64>,
+    // next frame to settle
+    settled: u64,
+    settled_frames: Vec<SettledFrame>,
+    rollbacks: u64,
+}
+
+impl Rollback {
+    // starts at the frame the console is at, both sides have to start from the same one
+    pub fn new(nes: &Nes, local_port: Port) -> Self {
+        Rollback {
+            local_port: local_port,
+            local_input: BTreeMap::new(),
+            remote_input: BTreeMap::new(),
+            predicted: BTreeMap::new(),
+            snapshots: VecDeque::new(),
+            confirmed: nes.stats().frames,
+            hashes: BTreeMap::new(),
+            settled: nes.stats().frames,
+            settled_frames: Vec::new(),
+            rollbacks: 0,
+        }
+    }
+
+    fn remote_port(&self) -> Port {
+        match self.local_port {
+            Port::One => Port::Two,
+            Port::Two => Port::One,
+        }
+    }
+
+    // the real remote buttons when they are known, otherwise the last ones that are
+    fn remote_buttons(&self, frame: u64) -> JoypadButton {
+        self.remote_input
+            .range(..=frame)
+            .next_back()
+            .map(|(_, buttons)| *buttons)
+            .unwrap_or_else(JoypadButton::empty)
+    }
+
+    fn run_frame(&mut self, nes: &mut Nes, frame: u64) {
+        let local = self.local_input[&frame];
+        let remote = self.remote_buttons(frame);
+        if frame >= self.confirmed {
+            self.predicted.insert(frame, remote);
+            self.snapshots.push_back((frame, nes.snapshot()));
+        }
+        if frame > 0 && frame % CHECKSUM_INTERVAL == 0 {
+            self.hashes.insert(frame, nes.state_hash());
+        }
+
+        let remote_port = self.remote_port();
+        nes.cpu.bus.joypad(self.local_port).set_buttons(local);
+        nes.cpu.bus.joypad(remote_port).set_buttons(remote);
+        nes.run_frame();
+    }
+
+    // runs one frame with the local buttons, returns the frame they belong to
+    pub fn advance(&mut self, nes: &mut Nes, local: JoypadButton) -> Result<u64, RollbackError> {
+        let frame = nes.stats().frames;
+        if frame >= self.confirmed + MAX_ROLLBACK_FRAMES {
+            return Err(RollbackError::TooFarAhead { frame: frame });
+        }
+
+        self.local_input.insert(frame, local);
+        self.run_frame(nes, frame);
+        self.settle(nes);
+        Ok(frame)
+    }
+
+    // the peer's buttons for `frame`. a frame that already ran with a wrong guess is
+    // corrected by going back and running everything since again
+    pub fn add_remote_input(
+        &mut self,
+        nes: &mut Nes,
+        frame: u64,
+        buttons: JoypadButton,
+    ) -> Result<(), RollbackError> {
+        if frame != self.confirmed {
+            return Err(RollbackError::OutOfOrder {
+                expected: self.confirmed,
+                frame: frame,
+            });
+        }
+        self.remote_input.insert(frame, buttons);
+        self.confirmed += 1;
+
+        let mispredicted = match self.predicted.remove(&frame) {
+            Some(predicted) => predicted != buttons,
+            // not run yet
+            None => false,
+        };
+        if mispredicted {
+            self.roll_back(nes, frame);
+        }
+        self.settle(nes);
+        Ok(())
+    }
+
+    fn roll_back(&mut self, nes: &mut Nes, from: u64) {
+        let present = nes.stats().frames;
+        let snapshot = match self.snapshots.iter().find(|(frame, _)| *frame == from) {
+            Some((_, snapshot)) => snapshot.clone(),
+            None => {
+                // cannot happen while advance keeps the window, but a desync has to show
+                nes.push_event(Event::DesyncDetected { frame: from });
+                return;
+            }
+        };
+
+        self.rollbacks += 1;
+        nes.restore(&snapshot);
+        self.snapshots.retain(|(frame, _)| *frame < from);
+        self.predicted.retain(|frame, _| *frame < from);
+
+        nes.resimulating = true;
+        for frame in from..present {
+            self.run_frame(nes, frame);
+        }
+        nes.resimulating = false;
+    }
+
+    // frames that ran and have both inputs confirmed never roll back again
+    fn settle(&mut self, nes: &Nes) {
+        let present = nes.stats().frames;
+        while self.settled < self.confirmed && self.settled < present {
+            let frame = self.settled;
+            let local = self.local_input[&frame];
+            let remote = self.remote_buttons(frame);
+            let (joypad1, joypad2) = match self.local_port {
+                Port::One => (local, remote),
+                Port::Two => (remote, local),
+            };
+
+            let end = frame + 1;
+            let state_hash = if end % CHECKSUM_INTERVAL != 0 {
+                None
+            } else if end == present {
+                Some(nes.state_hash())
+            } else {
+                self.hashes.get(&end).copied()
+            };
+            self.settled_frames.push(SettledFrame {
+                frame: frame,
+                joypad1: joypad1,
+                joypad2: joypad2,
+                state_hash: state_hash,
+            });
+            self.settled += 1;
+        }
+
+        while let Some((frame, _)) = self.snapshots.front() {
+            if *frame >= self.confirmed {
+                break;
+            }
+            self.snapshots.pop_front();
+        }
+        let settled = self.settled;
+        self.local_input.retain(|frame, _| *frame >= settled);
+        self.hashes.retain(|frame, _| *frame > settled);
+        // the last confirmed remote input is the guess for what comes next
+        let last_known = settled.min(self.confirmed.saturating_sub(1));
+        self.remote_input.retain(|frame, _| *frame >= last_known);
+    }
+
+    // everything that settled since the last call, oldest first
+    pub fn take_settled(&mut self) -> Vec<SettledFrame> {
+        core::mem::take(&mut self.settled_frames)
+    }
+
+    // how many times a wrong guess had to be corrected
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nes::test::create_nes;
+
+    const FRAMES: u64 = 90;
+    const LATENCY: u64 = 3;
+
+    fn buttons(port: Port, frame: u64) -> JoypadButton {
+        let pattern = [
+            JoypadButton::empty(),
+            JoypadButton::RIGHT,
+            JoypadButton::RIGHT | JoypadButton::BUTTON_A,
+            JoypadButton::DOWN,
+            JoypadButton::LEFT | JoypadButton::UP,
+        ];
+        let period = if port == Port::One { 7 } else { 11 };
+        pattern[(frame / period) as usize % pattern.len()]
+    }
+
+    #[test]
+    fn test_peers_converge() {
+        let mut reference = create_nes();
+        for frame in 0..FRAMES {
+            reference
+                .cpu
+                .bus
+                .joypad1()
+                .set_buttons(buttons(Port::One, frame));
+            reference
+                .cpu
+                .bus
+                .joypad2()
+                .set_buttons(buttons(Port::Two, frame));
+            reference.run_frame();
+        }
+
+        let mut host = create_nes();
+        let mut guest = create_nes();
+        let mut host_session = Rollback::new(&host, Port::One);
+        let mut guest_session = Rollback::new(&guest, Port::Two);
+        for frame in 0..FRAMES {
+            assert_eq!(
+                host_session.advance(&mut host, buttons(Port::One, frame)),
+                Ok(frame)
+            );
+            assert_eq!(
+                guest_session.advance(&mut guest, buttons(Port::Two, frame)),
+                Ok(frame)
+            );
+
+            // each side hears about the other LATENCY frames late
+            if frame >= LATENCY {
+                let sent = frame - LATENCY;
+                host_session
+                    .add_remote_input(&mut host, sent, buttons(Port::Two, sent))
+                    .unwrap();
+                guest_session
+                    .add_remote_input(&mut guest, sent, buttons(Port::One, sent))
+                    .unwrap();
+            }
+        }
+        for sent in FRAMES - LATENCY..FRAMES {
+            host_session
+                .add_remote_input(&mut host, sent, buttons(Port::Two, sent))
+                .unwrap();
+            guest_session
+                .add_remote_input(&mut guest, sent, buttons(Port::One, sent))
+                .unwrap();
+        }
+
+        assert!(guest_session.rollbacks() > 0);
+        assert_eq!(host.stats().frames, FRAMES);
+        assert_eq!(guest.stats().frames, FRAMES);
+        assert_eq!(host.state_hash(), reference.state_hash());
+        assert_eq!(guest.state_hash(), reference.state_hash());
+    }
+
+    #[test]
+    fn test_waits_for_peer() {
+        let mut nes = create_nes();
+        let mut session = Rollback::new(&nes, Port::One);
+        for frame in 0..MAX_ROLLBACK_FRAMES {
+            assert_eq!(session.advance(&mut nes, JoypadButton::empty()), Ok(frame));
+        }
+        assert_eq!(
+            session.advance(&mut nes, JoypadButton::empty()),
+            Err(RollbackError::TooFarAhead {
+                frame: MAX_ROLLBACK_FRAMES
+            })
+        );
+        assert_eq!(
+            session.add_remote_input(&mut nes, 1, JoypadButton::empty()),
+            Err(RollbackError::OutOfOrder {
+                expected: 0,
+                frame: 1
+            })
+        );
+
+        session
+            .add_remote_input(&mut nes, 0, JoypadButton::empty())
+            .unwrap();
+        assert!(session.advance(&mut nes, JoypadButton::empty()).is_ok());
+        assert_eq!(session.rollbacks(), 0);
+    }
+}
@@ -0,0 +1,145 @@
+// reloads a homebrew build whenever the assembler writes a new one, for frontends running
+// on a desktop. the frontend polls between frames, a second apart is plenty:
+//
+//     let mut watcher = RomWatcher::new("game.nes");
+//     loop {
+//         match watcher.poll() {
+//             Some(Ok(rom)) => reload(&mut nes, &rom)?,
+//             Some(Err(reason)) => eprintln!("{}", reason),
+//             None => {}
+//         }
+//         nes.run_frame();
+//     }
+//
+// the file is read on every poll and compared by its crc32 rather than its mtime, which
+// some file systems keep to the second and would miss two quick builds. a build caught
+// while the assembler is still writing it does not parse, the next poll gets the whole one
+
+use crate::cartridge::Cartridge;
+use crate::crc32::crc32;
+use crate::nes::Nes;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub struct RomWatcher {
+    path: PathBuf,
+    // of the last build poll gave, None until it gave one and after the file went away
+    crc32: Option<u32>,
+    // set while the file cannot be read, so that is reported once rather than every poll
+    failing: bool,
+}
+
+impl RomWatcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        RomWatcher {
+            path: path.as_ref().to_path_buf(),
+            crc32: None,
+            failing: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // the file when it changed since the last poll, the first poll always gives it. Err
+    // when it cannot be read, then None until it can again
+    pub fn poll(&mut self) -> Option<Result<Vec<u8>, String>> {
+        let rom = match fs::read(&self.path) {
+            Ok(rom) => rom,
+            Err(error) => {
+                self.crc32 = None;
+                if self.failing {
+                    return None;
+                }
+                self.failing = true;
+                return Some(Err(format!("{}: {}", self.path.display(), error)));
+            }
+        };
+        self.failing = false;
+        let checksum = crc32(&rom);
+        if self.crc32.replace(checksum) == Some(checksum) {
+            return None;
+        }
+        Some(Ok(rom))
+    }
+}
+
+// power-cycles `nes` with a new build. like Nes::load_cartridge the callbacks, cpu hooks
+// and debugger settings stay, a build that does not parse leaves the running one alone
+pub fn reload(nes: &mut Nes, rom: &[u8]) -> Result<(), String> {
+    let cartridge = Cartridge::new(rom)?;
+    nes.load_cartridge(cartridge);
+    nes.reset();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mem::Memory;
+    use crate::sync::AtomicRefCell;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_rom_watcher() {
+        let nestest = include_bytes!("../res/test.nes");
+        let snake = include_bytes!("../res/snake.nes");
+        let path = std::env::temp_dir().join(format!("feuernes-watch-{}.nes", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut watcher = RomWatcher::new(&path);
+        assert!(matches!(watcher.poll(), Some(Err(_))));
+        assert_eq!(watcher.poll(), None);
+
+        fs::write(&path, &nestest[..]).unwrap();
+        assert_eq!(watcher.poll(), Some(Ok(nestest.to_vec())));
+        assert_eq!(watcher.poll(), None);
+        fs::write(&path, &nestest[..]).unwrap();
+        assert_eq!(watcher.poll(), None);
+
+        fs::write(&path, &snake[..]).unwrap();
+        assert_eq!(watcher.poll(), Some(Ok(snake.to_vec())));
+
+        // a deleted file is reported once, the build after it is new again
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(watcher.poll(), Some(Err(_))));
+        assert_eq!(watcher.poll(), None);
+        fs::write(&path, &snake[..]).unwrap();
+        assert_eq!(watcher.poll(), Some(Ok(snake.to_vec())));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reload() {
+        let nestest = include_bytes!("../res/test.nes");
+        let snake = include_bytes!("../res/snake.nes");
+        let mut nes = Nes::new(Cartridge::new(nestest).unwrap());
+        let instructions = Arc::new(AtomicRefCell::new(0));
+        let counter = instructions.clone();
+        nes.cpu
+            .hooks
+            .on_instruction_executed(move |_| *counter.borrow_mut() += 1);
+        nes.cpu.break_on_stack_fault = true;
+        nes.reset();
+        nes.run_frame();
+
+        // a broken build leaves the running game alone
+        let frames = nes.stats().frames;
+        assert!(reload(&mut nes, &[0; 16]).is_err());
+        assert_eq!(nes.stats().frames, frames);
+
+        reload(&mut nes, snake).unwrap();
+        assert_eq!(nes.title(), Some("Snake"));
+        assert_eq!(nes.cpu.pc, nes.cpu.mem_peek_u16(0xFFFC));
+        // the debugger keeps stopping on stack faults and the hooks keep firing
+        assert!(nes.cpu.break_on_stack_fault);
+        let before = *instructions.borrow();
+        nes.run_frame();
+        assert!(*instructions.borrow() > before);
+    }
+}
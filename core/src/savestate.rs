@@ -0,0 +1,268 @@
+// savestates, the console as bytes to keep between sessions or pass around. Nes::snapshot
+// is the in-memory version of the same thing. layout, numbers little endian:
+//
+//     "FNST" version:u8
+//     (id:4 len:u32 data)*
+//
+// the chunks are "CART", the crc32 of the cartridge's prg and chr, then "CPU ", "BUS ", "PPU ", "APU ", "PAD1", "PAD2", "STAT" and the board's.
+// the board sits behind Box<dyn Mapper>, so its chunk holds whatever Mapper::save_state
+// returned and the id is what says which board wrote it: "M" and the ines mapper number
+// in three digits, "M004" for MMC3.
+//
+// a state loads into a console with the same cartridge only. the loaded chunks have to
+// match the console's own in id, order and length and the crc32s have to be the same, so
+// a state of another board or another dump of the game is turned down before any of it
+// is read
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const MAGIC: &[u8; 4] = b"FNST";
+const VERSION: u8 = 3;
+
+pub type ChunkId = [u8; 4];
+
+pub const CHUNK_CART: ChunkId = *b"CART";
+pub const CHUNK_CPU: ChunkId = *b"CPU ";
+pub const CHUNK_BUS: ChunkId = *b"BUS ";
+pub const CHUNK_PPU: ChunkId = *b"PPU ";
+pub const CHUNK_APU: ChunkId = *b"APU ";
+pub const CHUNK_PAD1: ChunkId = *b"PAD1";
+pub const CHUNK_PAD2: ChunkId = *b"PAD2";
+pub const CHUNK_STATS: ChunkId = *b"STAT";
+
+pub fn mapper_chunk_id(mapper: u8) -> ChunkId {
+    let digits = format!("{:03}", mapper);
+    let digits = digits.as_bytes();
+    [b'M', digits[0], digits[1], digits[2]]
+}
+
+pub fn write(chunks: &[(ChunkId, Vec<u8>)]) -> Vec<u8> {
+    let mut state = MAGIC.to_vec();
+    state.push(VERSION);
+    for (id, data) in chunks.iter() {
+        state.extend_from_slice(id);
+        state.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        state.extend_from_slice(data);
+    }
+    state
+}
+
+pub fn read(state: &[u8]) -> Result<Vec<(ChunkId, &[u8])>, String> {
+    if state.len() < 5 || &state[..4] != MAGIC {
+        return Err(String::from("not a savestate"));
+    }
+    if state[4] != VERSION {
+        return Err(format!("unknown savestate version {}", state[4]));
+    }
+    let mut chunks = Vec::new();
+    let mut rest = &state[5..];
+    while !rest.is_empty() {
+        if rest.len() < 8 {
+            return Err(String::from("savestate cut short"));
+        }
+        let id = [rest[0], rest[1], rest[2], rest[3]];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        if rest.len() - 8 < len {
+            return Err(String::from("savestate cut short"));
+        }
+        chunks.push((id, &rest[8..8 + len]));
+        rest = &rest[8 + len..];
+    }
+    Ok(chunks)
+}
+
+// `loaded` against what the console would save itself
+pub fn check(loaded: &[(ChunkId, &[u8])], own: &[(ChunkId, Vec<u8>)]) -> Result<(), String> {
+    if loaded.len() != own.len() {
+        return Err(format!(
+            "savestate has {} chunks, expected {}",
+            loaded.len(),
+            own.len()
+        ));
+    }
+    for ((id, _), (own_id, _)) in loaded.iter().zip(own.iter()) {
+        if id != own_id {
+            if id[0] == b'M' && own_id[0] == b'M' {
+                return Err(format!(
+                    "savestate is for mapper {}, the cartridge is mapper {}",
+                    chunk_mapper(id),
+                    chunk_mapper(own_id)
+                ));
+            }
+            return Err(format!(
+                "savestate has chunk {:?} where {:?} belongs",
+                chunk_name(id),
+                chunk_name(own_id)
+            ));
+        }
+    }
+    for ((id, data), (_, own_data)) in loaded.iter().zip(own.iter()) {
+        if *id == CHUNK_CART && data != own_data {
+            return Err(format!(
+                "savestate is for cartridge {}, the cartridge is {}",
+                chunk_crc32(data),
+                chunk_crc32(own_data)
+            ));
+        }
+    }
+    for ((id, data), (_, own_data)) in loaded.iter().zip(own.iter()) {
+        if data.len() != own_data.len() {
+            return Err(format!(
+                "chunk {:?} does not fit this cartridge",
+                chunk_name(id)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn chunk_name(id: &ChunkId) -> String {
+    id.iter().map(|byte| *byte as char).collect()
+}
+
+// the crc32 in a "CART" chunk, in hex like the compat reports
+fn chunk_crc32(data: &[u8]) -> String {
+    match data {
+        [a, b, c, d] => format!("{:08X}", u32::from_le_bytes([*a, *b, *c, *d])),
+        _ => String::from("?"),
+    }
+}
+
+// the number in a board's chunk id
+fn chunk_mapper(id: &ChunkId) -> u16 {
+    chunk_name(id)[1..].parse().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::mem::Memory;
+    use crate::nes::Nes;
+
+    // an MMC3 game that sets its scanline irq to fire 100 lines down every frame, the
+    // handler counts the irqs at $10 and acknowledges them
+    fn mmc3_irq_rom() -> Vec<u8> {
+        let mut prg = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x40, 0x8D, 0x17, 0x40, // LDA #$40, STA $4017 (no apu frame irq)
+            0xA9, 0x08, 0x8D, 0x00, 0x20, // LDA #$08, STA $2000 (sprites at $1000)
+            0xA9, 0x18, 0x8D, 0x01, 0x20, // LDA #$18, STA $2001 (rendering on)
+            0xA9, 0x64, 0x8D, 0x00, 0xC0, // LDA #100, STA $C000 (latch)
+            0x8D, 0x01, 0xC0, // STA $C001 (reload)
+            0x8D, 0x01, 0xE0, // STA $E001 (enable)
+            0x58, // CLI
+            0x4C, 0x1B, 0xE0, // JMP * (the loop)
+        ];
+        let handler = [
+            0xE6, 0x10, // INC $10
+            0x8D, 0x00, 0xE0, // STA $E000 (acknowledge)
+            0x8D, 0x01, 0xE0, // STA $E001
+            0x8D, 0x01, 0xC0, // STA $C001
+            0x40, // RTI
+        ];
+        // the last 8KB bank is fixed at $E000
+        prg[0x6000..0x6000 + program.len()].copy_from_slice(&program);
+        prg[0x7000..0x7000 + handler.len()].copy_from_slice(&handler);
+        // nmi goes straight to the RTI, it stays off anyway
+        prg[0x7FFA..].copy_from_slice(&[0x0B, 0xF0, 0x00, 0xE0, 0x00, 0xF0]);
+        crate::cartridge::test::create_rom(0x40, 1, prg)
+    }
+
+    fn mmc3_irq_game() -> Nes {
+        let mut nes = Nes::new(Cartridge::new(&mmc3_irq_rom()).unwrap());
+        nes.reset();
+        nes
+    }
+
+    fn irq_counter(nes: &Nes) -> u64 {
+        let state = nes.cpu.bus.mapper_debug_state();
+        state
+            .get("irq")
+            .unwrap()
+            .get("counter")
+            .unwrap()
+            .as_u64()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_mmc3_mid_irq() {
+        let mut nes = mmc3_irq_game();
+        for _ in 0..3 {
+            nes.run_frame();
+        }
+        // into the next frame until the counter is halfway down
+        while irq_counter(&nes) != 50 {
+            nes.step();
+        }
+        let state = nes.save_state();
+        let irqs = nes.cpu.mem_peek(0x10);
+        assert!(irqs >= 3);
+
+        let run = |nes: &mut Nes| {
+            let mut trace = Vec::new();
+            for _ in 0..20_000 {
+                nes.step();
+                trace.push((nes.cpu.pc, nes.stats().cpu_cycles, irq_counter(nes)));
+            }
+            (trace, nes.cpu.mem_peek(0x10))
+        };
+        let (trace, later_irqs) = run(&mut nes);
+        assert!(later_irqs > irqs);
+
+        // into a fresh console with the same cartridge
+        let mut loaded = mmc3_irq_game();
+        loaded.run_frame();
+        loaded.load_state(&state).unwrap();
+        assert_eq!(irq_counter(&loaded), 50);
+        assert_eq!(loaded.cpu.mem_peek(0x10), irqs);
+        assert_eq!(run(&mut loaded), (trace, later_irqs));
+
+        // and back into the console that saved it
+        nes.load_state(&state).unwrap();
+        assert_eq!(loaded.save_state().len(), state.len());
+        assert_eq!(nes.save_state(), state);
+    }
+
+    #[test]
+    fn test_other_cartridge() {
+        let mut nes = mmc3_irq_game();
+        let state = nes.save_state();
+        let raw = include_bytes!("../res/snake.nes").to_vec();
+        let mut other = Nes::new(Cartridge::new(&raw).unwrap());
+        other.reset();
+        let before = other.save_state();
+
+        assert_eq!(
+            other.load_state(&state),
+            Err(String::from(
+                "savestate is for mapper 4, the cartridge is mapper 0"
+            ))
+        );
+        assert_eq!(other.save_state(), before);
+
+        // another dump of the same game, one byte off in a bank it never maps
+        let mut raw = mmc3_irq_rom();
+        raw[16] = 0xEB;
+        let mut dump = Nes::new(Cartridge::new(&raw).unwrap());
+        dump.reset();
+        let before = dump.save_state();
+        let error = dump.load_state(&state).unwrap_err();
+        assert!(
+            error.starts_with("savestate is for cartridge "),
+            "{}",
+            error
+        );
+        assert_eq!(dump.save_state(), before);
+        assert_eq!(
+            nes.load_state(&state[..state.len() - 1]),
+            Err(String::from("savestate cut short"))
+        );
+        assert_eq!(
+            nes.load_state(b"FNRP"),
+            Err(String::from("not a savestate"))
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// callbacks that run at exact frames, for scripted demos and tests that poke memory or
+// change settings at a given point of a run. frames are counted like Nes::stats().frames,
+// the callbacks run as that frame starts, after queued input was applied
+
+use crate::nes::Nes;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleId(u64);
+
+#[derive(Clone, Copy)]
+enum When {
+    At(u64),
+    // every `period` frames after `from`
+    Every { from: u64, period: u64 },
+}
+
+impl When {
+    fn due(&self, frame: u64) -> bool {
+        match *self {
+            When::At(at) => frame >= at,
+            When::Every { from, period } => frame > from && (frame - from) % period == 0,
+        }
+    }
+}
+
+pub(crate) struct Task {
+    id: ScheduleId,
+    when: When,
+    callback: Box<dyn FnMut(&mut Nes) + Send>,
+}
+
+pub(crate) struct Schedule {
+    tasks: Vec<Task>,
+    next_id: u64,
+    // tasks that cancelled themselves while they ran, see put_back
+    cancelled: Vec<ScheduleId>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule {
+            tasks: Vec::new(),
+            next_id: 0,
+            cancelled: Vec::new(),
+        }
+    }
+
+    pub fn at(&mut self, frame: u64, callback: Box<dyn FnMut(&mut Nes) + Send>) -> ScheduleId {
+        self.add(When::At(frame), callback)
+    }
+
+    // an interval of 0 is taken as 1, every frame
+    pub fn every(
+        &mut self,
+        from: u64,
+        period: u64,
+        callback: Box<dyn FnMut(&mut Nes) + Send>,
+    ) -> ScheduleId {
+        let when = When::Every {
+            from: from,
+            period: period.max(1),
+        };
+        self.add(when, callback)
+    }
+
+    fn add(&mut self, when: When, callback: Box<dyn FnMut(&mut Nes) + Send>) -> ScheduleId {
+        let id = ScheduleId(self.next_id);
+        self.next_id += 1;
+        self.tasks.push(Task {
+            id: id,
+            when: when,
+            callback: callback,
+        });
+        id
+    }
+
+    pub fn cancel(&mut self, id: ScheduleId) {
+        self.tasks.retain(|task| task.id != id);
+        self.cancelled.push(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+    }
+
+    // taken out to be called with the console, in the order they were scheduled
+    pub fn take_due(&mut self, frame: u64) -> Vec<Task> {
+        self.cancelled.clear();
+        let (due, waiting) = self.tasks.drain(..).partition(|task| task.when.due(frame));
+        self.tasks = waiting;
+        due
+    }
+
+    // the repeating ones among what take_due returned, ahead of any scheduled meanwhile
+    pub fn put_back(&mut self, ran: Vec<Task>) {
+        let cancelled = core::mem::take(&mut self.cancelled);
+        let mut tasks: Vec<Task> = ran
+            .into_iter()
+            .filter(|task| matches!(task.when, When::Every { .. }))
+            .filter(|task| !cancelled.contains(&task.id))
+            .collect();
+        tasks.append(&mut self.tasks);
+        self.tasks = tasks;
+    }
+}
+
+impl Task {
+    pub fn run(&mut self, nes: &mut Nes) {
+        (self.callback)(nes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cartridge::Cartridge;
+    use crate::nes::Nes;
+    use crate::sync::AtomicRefCell;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_schedule() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+
+        let frames = Arc::new(AtomicRefCell::new(Vec::new()));
+        let seen = frames.clone();
+        nes.at_frame(3, move |nes| {
+            seen.borrow_mut().push(nes.stats().frames);
+            nes.set_overclock_scanlines(50);
+        });
+        let seen = frames.clone();
+        let every = nes.every(2, move |nes| {
+            seen.borrow_mut().push(100 + nes.stats().frames);
+        });
+        // a one-shot that schedules another for a frame that already started
+        nes.at_frame(5, |nes| {
+            nes.at_frame(1, |nes| nes.set_overclock_scanlines(100));
+        });
+
+        while nes.stats().frames < 4 {
+            nes.step();
+        }
+        assert_eq!(*frames.borrow(), vec![102, 3, 104]);
+        assert_eq!(nes.overclock_scanlines(), 50);
+        nes.cancel(every);
+        while nes.stats().frames < 8 {
+            nes.step();
+        }
+        assert_eq!(*frames.borrow(), vec![102, 3, 104]);
+        assert_eq!(nes.overclock_scanlines(), 100);
+    }
+}
@@ -0,0 +1,315 @@
+// read-only spectators of a netplay session. the host turns its settled frames into a
+// stream of input changes and periodic state hashes; a spectator with the same rom runs
+// the same frames from power-on and reports a desync when its hash disagrees. there is no
+// serialized savestate, so spectators have to join before the first frame.
+//
+// messages are a tag byte followed by the little endian frame number:
+//
+//     0  input     joypad1, joypad2     buttons from this frame on
+//     1  advance                        input is known for every frame before this one
+//     2  checksum  u64 le               Nes::state_hash once this many frames have run
+
+use crate::events::Event;
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+use crate::rollback::SettledFrame;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+const TAG_INPUT: u8 = 0;
+const TAG_ADVANCE: u8 = 1;
+const TAG_CHECKSUM: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectatorMessage {
+    Input {
+        frame: u64,
+        joypad1: JoypadButton,
+        joypad2: JoypadButton,
+    },
+    Advance {
+        frame: u64,
+    },
+    Checksum {
+        frame: u64,
+        hash: u64,
+    },
+}
+
+impl SpectatorMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let (tag, frame) = match *self {
+            SpectatorMessage::Input { frame, .. } => (TAG_INPUT, frame),
+            SpectatorMessage::Advance { frame } => (TAG_ADVANCE, frame),
+            SpectatorMessage::Checksum { frame, .. } => (TAG_CHECKSUM, frame),
+        };
+
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&frame.to_le_bytes());
+        match *self {
+            SpectatorMessage::Input {
+                joypad1, joypad2, ..
+            } => bytes.extend_from_slice(&[joypad1.bits(), joypad2.bits()]),
+            SpectatorMessage::Advance { .. } => {}
+            SpectatorMessage::Checksum { hash, .. } => bytes.extend_from_slice(&hash.to_le_bytes()),
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 9 {
+            return Err(format!("message too short: {} bytes", bytes.len()));
+        }
+        let frame = u64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        let payload = &bytes[9..];
+
+        match (bytes[0], payload.len()) {
+            (TAG_INPUT, 2) => Ok(SpectatorMessage::Input {
+                frame: frame,
+                joypad1: JoypadButton::from_bits_truncate(payload[0]),
+                joypad2: JoypadButton::from_bits_truncate(payload[1]),
+            }),
+            (TAG_ADVANCE, 0) => Ok(SpectatorMessage::Advance { frame: frame }),
+            (TAG_CHECKSUM, 8) => Ok(SpectatorMessage::Checksum {
+                frame: frame,
+                hash: u64::from_le_bytes(payload.try_into().unwrap()),
+            }),
+            (tag, length) => Err(format!(
+                "bad message: tag {} with {} payload bytes",
+                tag, length
+            )),
+        }
+    }
+}
+
+// host side, fed with Rollback::take_settled
+#[derive(Default)]
+pub struct Broadcaster {
+    last: Option<(JoypadButton, JoypadButton)>,
+}
+
+impl Broadcaster {
+    pub fn new() -> Self {
+        Broadcaster::default()
+    }
+
+    pub fn broadcast(&mut self, settled: &[SettledFrame]) -> Vec<SpectatorMessage> {
+        let mut messages = Vec::new();
+        for frame in settled.iter() {
+            let buttons = (frame.joypad1, frame.joypad2);
+            if self.last != Some(buttons) {
+                self.last = Some(buttons);
+                messages.push(SpectatorMessage::Input {
+                    frame: frame.frame,
+                    joypad1: frame.joypad1,
+                    joypad2: frame.joypad2,
+                });
+            }
+            if let Some(hash) = frame.state_hash {
+                messages.push(SpectatorMessage::Checksum {
+                    frame: frame.frame + 1,
+                    hash: hash,
+                });
+            }
+        }
+        if let Some(frame) = settled.last() {
+            messages.push(SpectatorMessage::Advance {
+                frame: frame.frame + 1,
+            });
+        }
+        messages
+    }
+}
+
+pub struct Spectator {
+    input: BTreeMap<u64, (JoypadButton, JoypadButton)>,
+    checksums: BTreeMap<u64, u64>,
+    // input is known for every frame before this one
+    available: u64,
+    desyncs: u64,
+}
+
+impl Spectator {
+    pub fn new(nes: &Nes) -> Self {
+        Spectator {
+            input: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            available: nes.stats().frames,
+            desyncs: 0,
+        }
+    }
+
+    pub fn receive(&mut self, message: SpectatorMessage) {
+        match message {
+            SpectatorMessage::Input {
+                frame,
+                joypad1,
+                joypad2,
+            } => {
+                self.input.insert(frame, (joypad1, joypad2));
+            }
+            SpectatorMessage::Advance { frame } => self.available = self.available.max(frame),
+            SpectatorMessage::Checksum { frame, hash } => {
+                self.checksums.insert(frame, hash);
+            }
+        }
+    }
+
+    // how many frames the host is ahead, for the frontend to decide how fast to catch up
+    pub fn behind(&self, nes: &Nes) -> u64 {
+        self.available.saturating_sub(nes.stats().frames)
+    }
+
+    // runs the next frame if its input has arrived. a checksum that disagrees pushes
+    // Event::DesyncDetected, the spectator keeps running but will not recover on its own
+    pub fn run_frame(&mut self, nes: &mut Nes) -> bool {
+        let frame = nes.stats().frames;
+        if frame >= self.available {
+            return false;
+        }
+
+        if let Some((joypad1, joypad2)) = self.input.remove(&frame) {
+            nes.cpu.bus.joypad1().set_buttons(joypad1);
+            nes.cpu.bus.joypad2().set_buttons(joypad2);
+        }
+        nes.run_frame();
+
+        let ran = frame + 1;
+        if let Some(hash) = self.checksums.remove(&ran) {
+            if hash != nes.state_hash() {
+                self.desyncs += 1;
+                nes.push_event(Event::DesyncDetected { frame: ran });
+            }
+        }
+        true
+    }
+
+    pub fn desyncs(&self) -> u64 {
+        self.desyncs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::joypad::Port;
+    use crate::nes::test::create_nes;
+    use crate::rollback::{Rollback, CHECKSUM_INTERVAL};
+
+    const LATENCY: u64 = 2;
+
+    fn buttons(port: Port, frame: u64) -> JoypadButton {
+        let pattern = [
+            JoypadButton::RIGHT,
+            JoypadButton::empty(),
+            JoypadButton::DOWN | JoypadButton::BUTTON_B,
+            JoypadButton::LEFT,
+        ];
+        let period = if port == Port::One { 13 } else { 5 };
+        pattern[(frame / period) as usize % pattern.len()]
+    }
+
+    // the bytes a host sends during a session of `frames` frames with a peer LATENCY
+    // frames away
+    fn host_stream(frames: u64) -> Vec<Vec<u8>> {
+        let mut host = create_nes();
+        let mut session = Rollback::new(&host, Port::One);
+        let mut broadcaster = Broadcaster::new();
+        let mut stream = Vec::new();
+        for frame in 0..frames + LATENCY {
+            if frame < frames {
+                session
+                    .advance(&mut host, buttons(Port::One, frame))
+                    .unwrap();
+            }
+            if frame >= LATENCY {
+                let sent = frame - LATENCY;
+                session
+                    .add_remote_input(&mut host, sent, buttons(Port::Two, sent))
+                    .unwrap();
+            }
+            for message in broadcaster.broadcast(&session.take_settled()) {
+                stream.push(message.encode());
+            }
+        }
+        stream
+    }
+
+    #[test]
+    fn test_spectator_follows_host() {
+        let frames = 2 * CHECKSUM_INTERVAL + 10;
+        let stream = host_stream(frames);
+
+        let mut reference = create_nes();
+        for frame in 0..frames {
+            reference
+                .cpu
+                .bus
+                .joypad1()
+                .set_buttons(buttons(Port::One, frame));
+            reference
+                .cpu
+                .bus
+                .joypad2()
+                .set_buttons(buttons(Port::Two, frame));
+            reference.run_frame();
+        }
+
+        let mut nes = create_nes();
+        let mut spectator = Spectator::new(&nes);
+        let mut checksums = 0;
+        for bytes in stream.iter() {
+            let message = SpectatorMessage::decode(bytes).unwrap();
+            if let SpectatorMessage::Checksum { .. } = message {
+                checksums += 1;
+            }
+            spectator.receive(message);
+        }
+        assert_eq!(checksums, 2);
+        assert_eq!(spectator.behind(&nes), frames);
+        while spectator.run_frame(&mut nes) {}
+
+        assert_eq!(nes.stats().frames, frames);
+        assert_eq!(nes.state_hash(), reference.state_hash());
+        assert_eq!(spectator.desyncs(), 0);
+    }
+
+    #[test]
+    fn test_spectator_detects_desync() {
+        let mut nes = create_nes();
+        let mut spectator = Spectator::new(&nes);
+        spectator.receive(SpectatorMessage::Advance { frame: 2 });
+        spectator.receive(SpectatorMessage::Checksum { frame: 2, hash: 0 });
+        while spectator.run_frame(&mut nes) {}
+
+        assert_eq!(nes.stats().frames, 2);
+        assert_eq!(spectator.desyncs(), 1);
+        assert!(nes
+            .take_events()
+            .contains(&Event::DesyncDetected { frame: 2 }));
+    }
+
+    #[test]
+    fn test_encode() {
+        let messages = [
+            SpectatorMessage::Input {
+                frame: 0x1234,
+                joypad1: JoypadButton::START,
+                joypad2: JoypadButton::UP | JoypadButton::BUTTON_A,
+            },
+            SpectatorMessage::Advance { frame: 7 },
+            SpectatorMessage::Checksum {
+                frame: 60,
+                hash: 0xdead_beef_cafe_f00d,
+            },
+        ];
+        for message in messages.iter() {
+            assert_eq!(SpectatorMessage::decode(&message.encode()), Ok(*message));
+        }
+        assert!(SpectatorMessage::decode(&[TAG_ADVANCE, 0, 0]).is_err());
+        assert!(SpectatorMessage::decode(&[9, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+// savestates of FCEUX (.fc0-.fc9, .fcs) for people moving their sessions over. the import
+// is best-effort: cpu registers, ram, prg ram, nametables, palette, oam and the ppu's
+// registers and scroll carry over, everything else (sound, input, board registers) stays
+// as the console has it and is reported as an event. games on boards without registers
+// continue where they were, others usually need a moment or a reset. layout, numbers
+// little endian:
+//
+//     "FCSX" size:u32 version:u32 compressed_size:u32, 0xFFFFFFFF when not compressed
+//     zlib data, or `size` bytes of:
+//         (section:u8 len:u32 (name:4 len:u32 data)*)*
+//
+// section 1 is the cpu, 3 the ppu and 0x10 the cartridge, names are padded with zeros
+
+use crate::inflate;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const MAGIC: &[u8; 4] = b"FCSX";
+const NOT_COMPRESSED: u32 = 0xFFFF_FFFF;
+
+const SECTION_CPU: u8 = 1;
+const SECTION_PPU: u8 = 3;
+const SECTION_CARTRIDGE: u8 = 0x10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedState {
+    pub pc: u16,
+    pub acc: u8,
+    pub rx: u8,
+    pub ry: u8,
+    pub sp: u8,
+    pub status: u8,
+    pub ram: Vec<u8>,
+    // empty when the state has none
+    pub prg_ram: Vec<u8>,
+    pub nametables: Vec<u8>,
+    pub palette: Vec<u8>,
+    pub oam: Vec<u8>,
+    // $2000, $2001, $2002 and $2003
+    pub ppu_registers: [u8; 4],
+    // the vram address, the temporary one, fine x and whether the next $2005/$2006 write
+    // is the second
+    pub vram_addr: u16,
+    pub temp_addr: u16,
+    pub fine_x: u8,
+    pub second_write: bool,
+    pub read_buffer: u8,
+    // the parts of the state that were left out
+    pub skipped: Vec<String>,
+}
+
+pub fn import_fceux(state: &[u8]) -> Result<ImportedState, String> {
+    if state.len() < 16 || &state[..4] != MAGIC {
+        return Err(String::from("not an FCEUX savestate"));
+    }
+    let u32_at =
+        |at: usize| u32::from_le_bytes([state[at], state[at + 1], state[at + 2], state[at + 3]]);
+    let size = u32_at(4) as usize;
+    let data = if u32_at(12) == NOT_COMPRESSED {
+        state[16..].to_vec()
+    } else {
+        inflate::zlib_decompress(&state[16..])?
+    };
+    if data.len() < size {
+        return Err(String::from("savestate cut short"));
+    }
+
+    let mut imported = ImportedState {
+        pc: 0,
+        acc: 0,
+        rx: 0,
+        ry: 0,
+        sp: 0,
+        status: 0,
+        ram: Vec::new(),
+        prg_ram: Vec::new(),
+        nametables: Vec::new(),
+        palette: Vec::new(),
+        oam: Vec::new(),
+        ppu_registers: [0; 4],
+        vram_addr: 0,
+        temp_addr: 0,
+        fine_x: 0,
+        second_write: false,
+        read_buffer: 0,
+        skipped: Vec::new(),
+    };
+    let mut rest = &data[..size];
+    while !rest.is_empty() {
+        let (section, body, after) = split(rest, 1)?;
+        for (name, value) in entries(body)? {
+            if !imported.take(section[0], &name, value) {
+                imported.skipped.push(part_name(section[0], &name));
+            }
+        }
+        rest = after;
+    }
+    if imported.ram.len() != 0x800 {
+        return Err(String::from("savestate has no cpu ram"));
+    }
+    Ok(imported)
+}
+
+// id, body and what follows
+type Split<'a> = (&'a [u8], &'a [u8], &'a [u8]);
+
+// the `id_len` byte id, a u32 length and that many bytes, then what follows
+fn split(data: &[u8], id_len: usize) -> Result<Split<'_>, String> {
+    let cut_short = || String::from("savestate cut short");
+    let header = data.get(..id_len + 4).ok_or_else(cut_short)?;
+    let len = u32::from_le_bytes([
+        header[id_len],
+        header[id_len + 1],
+        header[id_len + 2],
+        header[id_len + 3],
+    ]) as usize;
+    let start = id_len + 4;
+    let body = data.get(start..start + len).ok_or_else(cut_short)?;
+    Ok((&header[..id_len], body, &data[start + len..]))
+}
+
+fn entries(mut section: &[u8]) -> Result<Vec<(String, &[u8])>, String> {
+    let mut entries = Vec::new();
+    while !section.is_empty() {
+        let (name, value, rest) = split(section, 4)?;
+        let name = name
+            .iter()
+            .take_while(|byte| **byte != 0)
+            .map(|byte| *byte as char)
+            .collect();
+        entries.push((name, value));
+        section = rest;
+    }
+    Ok(entries)
+}
+
+fn part_name(section: u8, name: &str) -> String {
+    let section = match section {
+        SECTION_CPU => "cpu",
+        2 => "cpu timing",
+        SECTION_PPU => "ppu",
+        4 => "input",
+        5 => "sound",
+        6 => "movie",
+        SECTION_CARTRIDGE => "cartridge",
+        31 => "new ppu",
+        _ => "unknown",
+    };
+    format!("{} {}", section, name)
+}
+
+impl ImportedState {
+    // false for what the console has no place for
+    fn take(&mut self, section: u8, name: &str, value: &[u8]) -> bool {
+        let byte = value.first().copied().unwrap_or(0);
+        let word = match value {
+            [low, high, ..] => u16::from_le_bytes([*low, *high]),
+            _ => byte as u16,
+        };
+        match (section, name, value.len()) {
+            (SECTION_CPU, "PC", 2) => self.pc = word,
+            (SECTION_CPU, "A", 1) => self.acc = byte,
+            (SECTION_CPU, "X", 1) => self.rx = byte,
+            (SECTION_CPU, "Y", 1) => self.ry = byte,
+            (SECTION_CPU, "S", 1) => self.sp = byte,
+            (SECTION_CPU, "P", 1) => self.status = byte,
+            (SECTION_CPU, "RAM", 0x800) => self.ram = value.to_vec(),
+            (SECTION_PPU, "NTAR", _) => self.nametables = value.to_vec(),
+            (SECTION_PPU, "PRAM", 32) => self.palette = value.to_vec(),
+            (SECTION_PPU, "SPRA", 256) => self.oam = value.to_vec(),
+            (SECTION_PPU, "PPUR", 4) => self.ppu_registers.copy_from_slice(value),
+            (SECTION_PPU, "XOFF", 1) => self.fine_x = byte & 0x07,
+            (SECTION_PPU, "VTGL", 1) => self.second_write = byte != 0,
+            (SECTION_PPU, "RADD", 2) => self.vram_addr = word,
+            (SECTION_PPU, "TADD", 2) => self.temp_addr = word,
+            (SECTION_PPU, "VBUF", 1) => self.read_buffer = byte,
+            // latches the ppu here does not have, nothing is lost without them
+            (SECTION_PPU, "KOOK", _) | (SECTION_PPU, "DEAD", _) => {}
+            (SECTION_PPU, "PSPL", _) | (SECTION_PPU, "PGEN", _) => {}
+            (SECTION_CARTRIDGE, "WRAM", _) => self.prg_ram = value.to_vec(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::events::Event;
+    use crate::mem::Memory;
+    use crate::nes::Nes;
+
+    fn section(id: u8, entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, value) in entries.iter() {
+            let mut padded = name.as_bytes().to_vec();
+            padded.resize(4, 0);
+            body.extend_from_slice(&padded);
+            body.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            body.extend_from_slice(value);
+        }
+        let mut section = vec![id];
+        section.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        section.extend_from_slice(&body);
+        section
+    }
+
+    // an uncompressed state the way FCEUX 2.x writes it, with its cpu at $C123
+    fn fceux_state() -> Vec<u8> {
+        let mut ram = vec![0; 0x800];
+        ram[0x10] = 0x42;
+        let mut data = section(
+            SECTION_CPU,
+            &[
+                ("PC", vec![0x23, 0xC1]),
+                ("A", vec![1]),
+                ("P", vec![0x24]),
+                ("X", vec![2]),
+                ("Y", vec![3]),
+                ("S", vec![0xF0]),
+                ("RAM", ram),
+            ],
+        );
+        data.extend(section(2, &[("JAMM", vec![0])]));
+        let mut nametables = vec![0; 0x800];
+        nametables[0x400] = 0x77;
+        data.extend(section(
+            SECTION_PPU,
+            &[
+                ("NTAR", nametables),
+                ("PRAM", vec![0x0F; 32]),
+                ("SPRA", vec![0xFE; 256]),
+                ("PPUR", vec![0x88, 0x1E, 0x80, 0x10]),
+                ("XOFF", vec![5]),
+                ("VTGL", vec![0]),
+                ("RADD", vec![0x00, 0x24]),
+                // coarse x 3, coarse y 2, fine y 1, nametable 1
+                ("TADD", vec![0x43, 0x14]),
+                ("VBUF", vec![0x99]),
+            ],
+        ));
+        data.extend(section(5, &[("FHCN", vec![0; 4])]));
+
+        let mut state = MAGIC.to_vec();
+        state.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        state.extend_from_slice(&22020u32.to_le_bytes());
+        state.extend_from_slice(&NOT_COMPRESSED.to_le_bytes());
+        state.extend_from_slice(&data);
+        state
+    }
+
+    #[test]
+    fn test_import_fceux() {
+        let imported = import_fceux(&fceux_state()).unwrap();
+        assert_eq!(
+            (imported.pc, imported.sp, imported.status),
+            (0xC123, 0xF0, 0x24)
+        );
+        assert_eq!(imported.temp_addr, 0x1443);
+        assert_eq!(
+            imported.skipped,
+            vec![String::from("cpu timing JAMM"), String::from("sound FHCN")]
+        );
+
+        let raw = include_bytes!("../res/snake.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes.take_events();
+        nes.import_state(&fceux_state()).unwrap();
+        assert_eq!((nes.cpu.pc, nes.cpu.acc, nes.cpu.rx), (0xC123, 1, 2));
+        assert_eq!(nes.cpu.mem_peek(0x10), 0x42);
+        let ppu = nes.cpu.bus.ppu();
+        assert_eq!(ppu.bus.vram[0x400], 0x77);
+        assert_eq!(ppu.oam[0], 0xFE);
+        assert_eq!(ppu.scroll_register.get_scroll(), (3 * 8 + 5, 2 * 8 + 1));
+        assert_eq!(ppu.ctrl_register.bits(), 0x89);
+        assert_eq!(
+            nes.take_events(),
+            vec![
+                Event::StateImportSkipped {
+                    part: String::from("cpu timing JAMM")
+                },
+                Event::StateImportSkipped {
+                    part: String::from("sound FHCN")
+                },
+                Event::StateLoaded,
+            ]
+        );
+
+        assert_eq!(
+            nes.import_state(b"FCSX"),
+            Err(String::from("not an FCEUX savestate"))
+        );
+        let state = fceux_state();
+        assert_eq!(
+            nes.import_state(&state[..state.len() - 1]),
+            Err(String::from("savestate cut short"))
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// a quick compatibility survey of a set of roms: each one runs headless for a number of
+// frames, then what is on its screen and how it went make one line of a report. a rom that
+// hits an illegal opcode is stopped there, the screenshot shows how far it got
+
+use crate::cartridge::Cartridge;
+use crate::csv;
+use crate::events::Event;
+use crate::json::{self, Value};
+use crate::nes::Nes;
+use crate::render::frame::Frame;
+
+use alloc::string::String;
+
+// ten seconds, past the title screen of most games
+pub const DEFAULT_SURVEY_FRAMES: u32 = 600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    // ran every frame and drew something
+    Booted,
+    // ran every frame, the screen is one color
+    Blank,
+    IllegalOpcode { pc: u16, opcode: u8 },
+    // the cartridge did not load
+    BadRom(String),
+    // only caught with std, elsewhere a panic ends the survey
+    Panic(String),
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Booted => "boot ok",
+            Status::Blank => "blank screen",
+            Status::IllegalOpcode { .. } => "illegal opcode",
+            Status::BadRom(_) => "bad rom",
+            Status::Panic(_) => "panic",
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            Status::Booted | Status::Blank => String::new(),
+            Status::IllegalOpcode { pc, opcode } => format!("${:02X} at ${:04X}", opcode, pc),
+            Status::BadRom(reason) | Status::Panic(reason) => reason.clone(),
+        }
+    }
+}
+
+pub struct SurveyEntry {
+    pub name: String,
+    // of the prg and chr like the gamedb, None when the rom did not load
+    pub crc32: Option<u32>,
+    pub status: Status,
+    // how many frames ran before the survey stopped
+    pub frames: u32,
+    // None when the rom never ran
+    pub screenshot: Option<Frame>,
+}
+
+pub fn survey_rom(name: &str, raw: &[u8], frames: u32) -> SurveyEntry {
+    #[cfg(feature = "std")]
+    let run = std::panic::catch_unwind(|| run_rom(raw, frames)).unwrap_or_else(|panic| {
+        let reason = match panic.downcast_ref::<&str>() {
+            Some(reason) => String::from(*reason),
+            None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+        (Status::Panic(reason), None, 0, None)
+    });
+    #[cfg(not(feature = "std"))]
+    let run = run_rom(raw, frames);
+
+    let (status, crc32, frames, screenshot) = run;
+    SurveyEntry {
+        name: String::from(name),
+        crc32: crc32,
+        status: status,
+        frames: frames,
+        screenshot: screenshot,
+    }
+}
+
+fn run_rom(raw: &[u8], frames: u32) -> (Status, Option<u32>, u32, Option<Frame>) {
+    let cartridge = match Cartridge::new(raw) {
+        Ok(cartridge) => cartridge,
+        Err(reason) => return (Status::BadRom(reason), None, 0, None),
+    };
+    let crc32 = Some(cartridge.crc32);
+    let mut nes = Nes::new(cartridge);
+    nes.reset();
+    for frame in 0..frames {
+        nes.run_frame();
+        let illegal = nes.take_events().into_iter().find_map(|event| match event {
+            Event::IllegalOpcode { pc, opcode } => Some(Status::IllegalOpcode {
+                pc: pc,
+                opcode: opcode,
+            }),
+            _ => None,
+        });
+        if let Some(status) = illegal {
+            return (status, crc32, frame + 1, Some(nes.frame().clone()));
+        }
+    }
+
+    let screen = nes.frame();
+    let first = screen.pixel(0, 0);
+    let blank = screen.data.chunks(4).all(|pixel| pixel == first);
+    let status = if blank { Status::Blank } else { Status::Booted };
+    (status, crc32, frames, Some(screen.clone()))
+}
+
+pub fn report_csv(entries: &[SurveyEntry]) -> String {
+    let mut text = String::new();
+    csv::write_row(&mut text, &["rom", "status", "detail", "frames"]);
+    for entry in entries.iter() {
+        csv::write_row(
+            &mut text,
+            &[
+                entry.name.clone(),
+                String::from(entry.status.name()),
+                entry.status.detail(),
+                format!("{}", entry.frames),
+            ],
+        );
+    }
+    text
+}
+
+pub fn report_json(entries: &[SurveyEntry]) -> Value {
+    Value::Array(
+        entries
+            .iter()
+            .map(|entry| {
+                json::object(vec![
+                    ("rom", Value::from(entry.name.clone())),
+                    ("status", Value::from(entry.status.name())),
+                    ("detail", Value::from(entry.status.detail())),
+                    ("frames", Value::from(entry.frames)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_survey() {
+        let nestest = include_bytes!("../res/test.nes");
+        let entry = survey_rom("nestest.nes", nestest, 30);
+        assert_eq!((entry.status.clone(), entry.frames), (Status::Booted, 30));
+        assert_eq!(entry.screenshot.unwrap().width, 256);
+        // snake keeps the screen black until start is pressed
+        let snake = include_bytes!("../res/snake.nes");
+        assert_eq!(survey_rom("snake.nes", snake, 30).status, Status::Blank);
+
+        // $02 right at the reset vector
+        let mut prg = vec![0xEA; 0x4000];
+        prg[0] = 0x02;
+        prg[0x3FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        let raw = crate::cartridge::test::create_rom(0, 1, prg);
+        let entry = survey_rom("jam.nes", &raw, 30);
+        assert_eq!(
+            entry.status,
+            Status::IllegalOpcode {
+                pc: 0x8000,
+                opcode: 0x02
+            }
+        );
+        assert_eq!(entry.frames, 1);
+
+        let entries = vec![entry, survey_rom("empty.nes", &[], 30)];
+        assert!(matches!(entries[1].status, Status::BadRom(_)));
+        assert!(entries[1].screenshot.is_none());
+        let report = report_csv(&entries);
+        assert!(
+            report.starts_with("rom,status,detail,frames\njam.nes,illegal opcode,$02 at $8000,1\n")
+        );
+        assert!(report.contains("\nempty.nes,bad rom,"));
+        let json = report_json(&entries);
+        assert_eq!(
+            json.as_array().unwrap()[0].get("status"),
+            Some(&Value::from("illegal opcode"))
+        );
+    }
+}
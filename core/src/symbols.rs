@@ -0,0 +1,82 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/*
+https://fceux.com/web/help/NLFilesFormat.html
+
+    fceux name lists, one label per line:
+
+        $C000#Reset#entry point after power-on
+        $0010#player_x#
+
+    assemblers (ca65 with a script, asm6 -n) can write them, which makes them the
+    most common way homebrew ships its labels. the per-bank files of larger games
+    all map into the cpu address space, labels of one bank can hide another's
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    labels: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Symbols {
+            labels: BTreeMap::new(),
+        }
+    }
+
+    // lines that are not labels (comments, blank lines) are skipped, a label with a
+    // bad address is an error
+    pub fn parse_nl(text: &str) -> Result<Self, String> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if !line.starts_with('$') {
+                continue;
+            }
+            let mut fields = line[1..].split('#');
+            let address = fields.next().unwrap_or("");
+            let address = u16::from_str_radix(address, 16)
+                .map_err(|_| format!("bad address {:?} on line {}", address, number + 1))?;
+            match fields.next() {
+                Some(name) if !name.is_empty() => symbols.insert(address, name),
+                _ => {}
+            }
+        }
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, address: u16, name: &str) {
+        self.labels.insert(address, String::from(name));
+    }
+
+    pub fn get(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_nl() {
+        let symbols =
+            Symbols::parse_nl("$C000#Reset#entry point\n\n; comment\n$0010#player_x#\n$C123##\n")
+                .unwrap();
+        assert_eq!(symbols.get(0xC000), Some("Reset"));
+        assert_eq!(symbols.get(0x0010), Some("player_x"));
+        assert_eq!(symbols.get(0xC123), None);
+        assert_eq!(symbols.len(), 2);
+
+        assert!(Symbols::parse_nl("$XYZ#bad#").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+// a RefCell that can sit in an Arc, so a console whose cpu and ppu share one mapper can
+// still be moved to another thread. borrows work like RefCell's, one that conflicts with
+// another panics instead of blocking, the emulator never holds two at once
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// the borrow count while shared, this while borrowed mutably
+const WRITING: usize = usize::MAX;
+
+pub struct AtomicRefCell<T: ?Sized> {
+    borrows: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// the borrow flag gives the same guarantees as a Mutex or RwLock would, without waiting
+unsafe impl<T: ?Sized + Send> Send for AtomicRefCell<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AtomicRefCell<T> {}
+
+impl<T> AtomicRefCell<T> {
+    pub fn new(value: T) -> Self {
+        AtomicRefCell {
+            borrows: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> AtomicRefCell<T> {
+    pub fn borrow(&self) -> AtomicRef<'_, T> {
+        let mut borrows = self.borrows.load(Ordering::Relaxed);
+        loop {
+            if borrows >= WRITING - 1 {
+                panic!("already mutably borrowed");
+            }
+            match self.borrows.compare_exchange_weak(
+                borrows,
+                borrows + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return AtomicRef { cell: self },
+                Err(now) => borrows = now,
+            }
+        }
+    }
+
+    pub fn borrow_mut(&self) -> AtomicRefMut<'_, T> {
+        if self
+            .borrows
+            .compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            panic!("already borrowed");
+        }
+        AtomicRefMut { cell: self }
+    }
+
+    // for the one place two threads really meet, an audio device callback and the console
+    // filling its ring buffer: waits the other borrow out instead of panicking. both sides
+    // only hold it for a copy
+    pub fn borrow_mut_spin(&self) -> AtomicRefMut<'_, T> {
+        while self
+            .borrows
+            .compare_exchange_weak(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        AtomicRefMut { cell: self }
+    }
+}
+
+pub struct AtomicRef<'a, T: ?Sized> {
+    cell: &'a AtomicRefCell<T>,
+}
+
+impl<'a, T: ?Sized> Deref for AtomicRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AtomicRef<'a, T> {
+    fn drop(&mut self) {
+        self.cell.borrows.fetch_sub(1, Ordering::Release);
+    }
+}
+
+pub struct AtomicRefMut<'a, T: ?Sized> {
+    cell: &'a AtomicRefCell<T>,
+}
+
+impl<'a, T: ?Sized> Deref for AtomicRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for AtomicRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AtomicRefMut<'a, T> {
+    fn drop(&mut self) {
+        self.cell.borrows.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_borrows() {
+        let cell = AtomicRefCell::new(1);
+        {
+            let a = cell.borrow();
+            let b = cell.borrow();
+            assert_eq!(*a + *b, 2);
+        }
+        *cell.borrow_mut() += 1;
+        assert_eq!(*cell.borrow(), 2);
+
+        let shared = cell.borrow();
+        let conflict = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cell.borrow_mut();
+        }));
+        assert!(conflict.is_err());
+        drop(shared);
+        assert_eq!(*cell.borrow_mut(), 2);
+        *cell.borrow_mut_spin() += 1;
+        assert_eq!(*cell.borrow(), 3);
+    }
+}
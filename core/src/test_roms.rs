@@ -0,0 +1,124 @@
+// self checking test roms that report through blargg's protocol, for a quick look at how
+// accurate the emulator currently is. the ones in res/test_roms are only embedded with
+// the test-roms feature, see TEST_ROMS
+
+use crate::cartridge::Cartridge;
+use crate::mem::Memory;
+use crate::nes::Nes;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/*
+https://github.com/christopherpow/nes-test-roms/blob/master/blargg_apu_2005.07.30/readme.txt
+
+    $6001-$6003 DE B0 61 once the rom reports through memory
+    $6000       $80 while running, $81 when the rom wants the reset button pressed,
+                anything else is the result code, 0 passed
+    $6004       zero terminated message
+*/
+const BLARGG_STATUS: u16 = 0x6000;
+pub(crate) const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_MESSAGE: u16 = 0x6004;
+const BLARGG_RUNNING: u8 = 0x80;
+const BLARGG_NEEDS_RESET: u8 = 0x81;
+// the roms ask for the reset to be held for at least 100ms
+const BLARGG_RESET_DELAY_FRAMES: usize = 6;
+const BLARGG_MAX_FRAMES: usize = 60 * 60;
+const PRG_RAM_MESSAGE_END: u16 = 0x8000;
+
+pub struct TestRom {
+    pub name: &'static str,
+    pub rom: &'static [u8],
+}
+
+impl TestRom {
+    // the error carries what the rom says went wrong
+    pub fn run(&self) -> Result<(), String> {
+        run_blargg(self.rom)
+    }
+}
+
+// small roms written for this crate, the sources are next to them. more go here, any
+// rom that reports through prg ram like blargg's works
+#[cfg(feature = "test-roms")]
+pub const TEST_ROMS: &[TestRom] = &[
+    TestRom {
+        name: "cpu arithmetic and JMP indirect",
+        rom: include_bytes!("../res/test_roms/cpu.nes"),
+    },
+    TestRom {
+        name: "ppu status and read buffer",
+        rom: include_bytes!("../res/test_roms/ppu.nes"),
+    },
+    TestRom {
+        name: "apu length counter status",
+        rom: include_bytes!("../res/test_roms/apu.nes"),
+    },
+];
+
+// runs one of blargg's self checking roms to its verdict, the error carries its message
+pub fn run_blargg(rom: &[u8]) -> Result<(), String> {
+    let mut nes = Nes::new(Cartridge::new(rom)?);
+    nes.reset();
+
+    let mut reset_in = None;
+    for _ in 0..BLARGG_MAX_FRAMES {
+        nes.run_frame();
+        let signature = [
+            nes.cpu.mem_peek(BLARGG_STATUS + 1),
+            nes.cpu.mem_peek(BLARGG_STATUS + 2),
+            nes.cpu.mem_peek(BLARGG_STATUS + 3),
+        ];
+        if signature != BLARGG_SIGNATURE {
+            continue;
+        }
+
+        match nes.cpu.mem_peek(BLARGG_STATUS) {
+            BLARGG_RUNNING => {}
+            BLARGG_NEEDS_RESET => match reset_in {
+                Some(0) => {
+                    nes.reset();
+                    reset_in = None;
+                }
+                Some(frames) => reset_in = Some(frames - 1),
+                None => reset_in = Some(BLARGG_RESET_DELAY_FRAMES),
+            },
+            0 => return Ok(()),
+            code => {
+                let message: Vec<u8> = (BLARGG_MESSAGE..PRG_RAM_MESSAGE_END)
+                    .map(|addr| nes.cpu.mem_peek(addr))
+                    .take_while(|byte| *byte != 0)
+                    .collect();
+                return Err(format!(
+                    "result {}: {}",
+                    code,
+                    String::from_utf8_lossy(&message).trim()
+                ));
+            }
+        }
+    }
+    Err(format!("no result after {} frames", BLARGG_MAX_FRAMES))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn verdict(name: &str) -> Result<(), String> {
+        let path = format!("{}/res/test_roms/{}.nes", env!("CARGO_MANIFEST_DIR"), name);
+        run_blargg(&std::fs::read(&path).unwrap())
+    }
+
+    #[test]
+    fn test_test_roms() {
+        assert_eq!(verdict("cpu"), Ok(()));
+        assert_eq!(verdict("apu"), Ok(()));
+    }
+
+    #[test]
+    #[ignore = "the $2007 read buffer is not emulated yet, PPU::read returns the data right away"]
+    fn test_ppu_test_rom() {
+        assert_eq!(verdict("ppu"), Ok(()));
+    }
+}
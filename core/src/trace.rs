@@ -0,0 +1,271 @@
+use crate::cpu;
+use crate::cpu::AddressMode;
+use crate::csv;
+use crate::json::{self, Value};
+use crate::mem::Memory;
+use crate::opcode;
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+// enough to see how a game got into a crash without printing every instruction
+pub const DEFAULT_TRACE_ENTRIES: usize = 10_000;
+
+// the columns of TraceLog::to_csv, numbers in decimal like the json export
+const CSV_HEADER: [&str; 13] = [
+    "frame", "pc", "opcode", "name", "operands", "address", "value", "a", "x", "y", "p", "sp",
+    "mode",
+];
+
+// the cpu right before it runs an instruction
+#[derive(Clone, Copy)]
+pub struct TraceInfo {
+    pub frame: u64,
+    pub pc: u16,
+    pub opcode: opcode::Opcode,
+    // the bytes after the opcode, only the first `opcode.bytes - 1` are operands
+    pub operands: [u8; 2],
+    // effective address and the value there, for modes that read memory
+    pub target: Option<(u16, u8)>,
+    pub sp: u8,
+    pub acc: u8,
+    pub rx: u8,
+    pub ry: u8,
+    pub status: cpu::CPUStatus,
+}
+
+impl TraceInfo {
+    pub fn new(frame: u64, cpu: &cpu::CPU) -> Self {
+        let op = cpu.mem_peek(cpu.pc);
+        let opcode = opcode::OPCODES_MAP[op as usize].unwrap_or(&opcode::UNSUPPORTED);
+        let target = match opcode.mode {
+            AddressMode::Immediate | AddressMode::NoneAddressing => None,
+            _ => {
+                // peek, so tracing does not acknowledge interrupts or shift controller bits
+                let addr = cpu.peek_absolute_address(&opcode.mode, cpu.pc.wrapping_add(1));
+                Some((addr, cpu.mem_peek(addr)))
+            }
+        };
+        TraceInfo {
+            frame: frame,
+            pc: cpu.pc,
+            opcode: *opcode,
+            operands: [
+                cpu.mem_peek(cpu.pc.wrapping_add(1)),
+                cpu.mem_peek(cpu.pc.wrapping_add(2)),
+            ],
+            target: target,
+            sp: cpu.sp,
+            acc: cpu.acc,
+            rx: cpu.rx,
+            ry: cpu.ry,
+            status: cpu.status,
+        }
+    }
+
+    // one line in the spirit of nestest.log, prefixed with the frame:
+    // "    12 C004  A9 10     LDA #$10       A:00 X:00 Y:00 P:24 SP:FD"
+    pub fn dump(&self) -> String {
+        let mut bytes = format!("{:02X}", self.opcode.op);
+        for operand in self.operands().iter() {
+            let _ = write!(bytes, " {:02X}", operand);
+        }
+
+        let argument = match (self.opcode.mode, self.target) {
+            (AddressMode::Immediate, _) => format!("#${:02X}", self.operands[0]),
+            (_, Some((addr, value))) => format!("${:04X} = {:02X}", addr, value),
+            (_, None) => String::new(),
+        };
+        format!(
+            "{:6} {:04X}  {:<8}  {} {:<10} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.frame,
+            self.pc,
+            bytes,
+            self.opcode.name,
+            argument,
+            self.acc,
+            self.rx,
+            self.ry,
+            self.status.bits(),
+            self.sp
+        )
+    }
+
+    fn operands(&self) -> &[u8] {
+        let operand_count = (self.opcode.bytes as usize).saturating_sub(1).min(2);
+        &self.operands[..operand_count]
+    }
+
+    // the same fields for scripts, numbers as numbers. address and value are null for
+    // instructions without a memory operand
+    pub fn to_json(&self) -> Value {
+        json::object(vec![
+            ("frame", Value::from(self.frame)),
+            ("pc", Value::from(self.pc)),
+            ("opcode", Value::from(self.opcode.op)),
+            ("name", Value::from(self.opcode.name)),
+            ("operands", Value::from(self.operands())),
+            ("address", Value::from(self.target.map(|(addr, _)| addr))),
+            ("value", Value::from(self.target.map(|(_, value)| value))),
+            ("a", Value::from(self.acc)),
+            ("x", Value::from(self.rx)),
+            ("y", Value::from(self.ry)),
+            ("p", Value::from(self.status.bits())),
+            ("sp", Value::from(self.sp)),
+            (
+                "mode",
+                Value::from(format!("{:?}", self.opcode.mode).as_str()),
+            ),
+        ])
+    }
+
+    fn csv_fields(&self) -> Vec<String> {
+        let operands: Vec<String> = self
+            .operands()
+            .iter()
+            .map(|byte| format!("{}", byte))
+            .collect();
+        let (address, value) = match self.target {
+            Some((addr, value)) => (format!("{}", addr), format!("{}", value)),
+            None => (String::new(), String::new()),
+        };
+        vec![
+            format!("{}", self.frame),
+            format!("{}", self.pc),
+            format!("{}", self.opcode.op),
+            String::from(self.opcode.name),
+            operands.join(" "),
+            address,
+            value,
+            format!("{}", self.acc),
+            format!("{}", self.rx),
+            format!("{}", self.ry),
+            format!("{}", self.status.bits()),
+            format!("{}", self.sp),
+            format!("{:?}", self.opcode.mode),
+        ]
+    }
+}
+
+// the last `capacity` instructions, the oldest one is dropped for every new one
+pub struct TraceLog {
+    entries: VecDeque<TraceInfo>,
+    capacity: usize,
+}
+
+impl TraceLog {
+    pub fn new(capacity: usize) -> Self {
+        TraceLog {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, cpu: &cpu::CPU) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TraceInfo::new(frame, cpu));
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &TraceInfo> {
+        self.entries.iter()
+    }
+
+    // everything as text, one instruction per line, for a file or the clipboard
+    pub fn dump(&self) -> String {
+        let mut text = String::new();
+        for entry in self.entries.iter() {
+            text.push_str(&entry.dump());
+            text.push('\n');
+        }
+        text
+    }
+
+    // an array of TraceInfo::to_json, oldest first
+    pub fn to_json(&self) -> Value {
+        Value::Array(self.entries.iter().map(|entry| entry.to_json()).collect())
+    }
+
+    // a header row, then one row per instruction. operands are separated by spaces, an
+    // instruction without a memory operand has address and value empty
+    pub fn to_csv(&self) -> String {
+        let mut text = String::new();
+        csv::write_row(&mut text, &CSV_HEADER);
+        for entry in self.entries.iter() {
+            csv::write_row(&mut text, &entry.csv_fields());
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::nes::Nes;
+
+    #[test]
+    fn test_trace_log() {
+        let raw = include_bytes!("../res/test.nes").to_vec();
+        let mut nes = Nes::new(Cartridge::new(&raw).unwrap());
+        nes.reset();
+        nes.enable_trace(3);
+
+        let mut pcs = Vec::new();
+        for _ in 0..5 {
+            pcs.push(nes.cpu.pc);
+            nes.step();
+        }
+
+        // only the last three are kept, oldest first
+        let trace_log = nes.trace_log().unwrap();
+        assert_eq!(trace_log.len(), 3);
+        let traced: Vec<u16> = trace_log.entries().map(|entry| entry.pc).collect();
+        assert_eq!(traced, pcs[2..].to_vec());
+
+        let dump = trace_log.dump();
+        assert_eq!(dump.lines().count(), 3);
+        assert!(dump.starts_with(&format!("     0 {:04X}  ", pcs[2])));
+        assert!(dump.lines().all(|line| line.contains(" SP:")));
+
+        let json = trace_log.to_json();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].get("pc"), Some(&Value::from(pcs[2])));
+        assert_eq!(json::parse(&format!("{}", json)), Ok(json.clone()));
+        let csv = trace_log.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.starts_with("frame,pc,opcode,name,operands,"));
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with(&format!("0,{},", pcs[2])));
+
+        nes.disable_trace();
+        assert!(nes.trace_log().is_none());
+    }
+}
@@ -0,0 +1,124 @@
+// audio output through cpal on the default output device. like the web frontend's web
+// audio sink it only plays what is in its ring buffer, audio::sink::connect fills it:
+//
+//     let audio = CpalAudio::new(AudioConfig::default())?;
+//     sink::connect(&mut nes, &audio);
+//
+// the device callback runs on its own thread, so both sides take the ring with
+// borrow_mut_spin
+
+use std::sync::Arc;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, Device, Sample, SampleFormat, Stream, StreamConfig, SupportedBufferSize};
+
+use feuernes_core::audio::ring_buffer::AudioRingBuffer;
+use feuernes_core::audio::sink::{AudioConfig, AudioSink};
+use feuernes_core::sync::AtomicRefCell;
+
+pub struct CpalAudio {
+    stream: Stream,
+    sample_rate: f64,
+    ring: Arc<AtomicRefCell<AudioRingBuffer>>,
+}
+
+impl CpalAudio {
+    // starts playing right away, silence until the console pushes samples
+    pub fn new(config: AudioConfig) -> Result<Self, String> {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let supported = device
+            .default_output_config()
+            .map_err(|e| format!("audio output: {}", e))?;
+        let stream_config = StreamConfig {
+            channels: supported.channels(),
+            sample_rate: supported.sample_rate(),
+            buffer_size: buffer_size(supported.buffer_size(), config.buffer_size),
+        };
+
+        let sample_rate = stream_config.sample_rate.0 as f64;
+        let capacity = config.ring_capacity(sample_rate);
+        let ring = Arc::new(AtomicRefCell::new(AudioRingBuffer::new(capacity)));
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, ring.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, ring.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, ring.clone()),
+        }?;
+        stream.play().map_err(|e| format!("audio output: {}", e))?;
+
+        Ok(CpalAudio {
+            stream: stream,
+            sample_rate: sample_rate,
+            ring: ring,
+        })
+    }
+}
+
+impl AudioSink for CpalAudio {
+    fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    fn ring(&self) -> Arc<AtomicRefCell<AudioRingBuffer>> {
+        self.ring.clone()
+    }
+
+    fn resume(&self) {
+        let _ = self.stream.play();
+    }
+
+    fn suspend(&self) {
+        let _ = self.stream.pause();
+    }
+}
+
+// the configured size where the device allows it, its own default when it does not say
+fn buffer_size(supported: &SupportedBufferSize, wanted: u32) -> BufferSize {
+    match supported {
+        SupportedBufferSize::Range { min, max } => BufferSize::Fixed(wanted.max(*min).min(*max)),
+        SupportedBufferSize::Unknown => BufferSize::Default,
+    }
+}
+
+fn build_stream<T: Sample>(
+    device: &Device,
+    config: &StreamConfig,
+    ring: Arc<AtomicRefCell<AudioRingBuffer>>,
+) -> Result<Stream, String> {
+    let channels = config.channels as usize;
+    let mut block = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                block.resize(data.len() / channels, 0.0);
+                ring.borrow_mut_spin().pop_into(&mut block);
+                // the console is mono, every channel gets the same sample
+                for (frame, sample) in data.chunks_mut(channels).zip(block.iter()) {
+                    let value = T::from(sample);
+                    frame.iter_mut().for_each(|out| *out = value);
+                }
+            },
+            |error| eprintln!("audio output: {}", error),
+        )
+        .map_err(|e| format!("audio output: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buffer_size() {
+        let range = SupportedBufferSize::Range { min: 64, max: 512 };
+        assert_eq!(buffer_size(&range, 1024), BufferSize::Fixed(512));
+        assert_eq!(buffer_size(&range, 256), BufferSize::Fixed(256));
+        assert_eq!(buffer_size(&range, 16), BufferSize::Fixed(64));
+        assert_eq!(
+            buffer_size(&SupportedBufferSize::Unknown, 256),
+            BufferSize::Default
+        );
+    }
+}
@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::cpu::CPU;
+use feuernes_core::mem::Memory;
+
+// any file a user uploads ends up here, it has to be refused or loaded, never crash
+fuzz_target!(|data: &[u8]| {
+    if let Ok(cartridge) = Cartridge::new(data) {
+        // odd PRG/CHR sizes must still map every address inside the rom
+        let cpu = CPU::from_cartridge(cartridge);
+        for addr in 0x6000..=0xFFFF {
+            cpu.mem_peek(addr);
+        }
+    }
+});
@@ -0,0 +1,34 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use feuernes_core::cpu::CPU;
+use feuernes_core::mem::Memory;
+
+const RAM_SIZE: usize = 0x800;
+// enough to leave the input and run into whatever it wrote or jumped to
+const MAX_INSTRUCTIONS: usize = 4096;
+
+// an empty NROM-128 whose reset vector points into work ram
+fn ram_program_rom() -> Vec<u8> {
+    let mut raw = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0; 0x4000];
+    prg[0x3FFC] = 0x00;
+    prg[0x3FFD] = 0x00;
+    raw.extend(prg);
+    raw.extend(vec![0; 0x2000]);
+    raw
+}
+
+// the input is the program: it is copied to $0000 and run from there, so opcodes,
+// operands, pointers and the stack page are all fuzzer controlled
+fuzz_target!(|data: &[u8]| {
+    let mut cpu = CPU::from_ines_bytes(&ram_program_rom()).unwrap();
+    for (addr, byte) in data.iter().take(RAM_SIZE).enumerate() {
+        cpu.mem_write(addr as u16, *byte);
+    }
+    cpu.reset();
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        cpu.interprect();
+    }
+});
@@ -0,0 +1,249 @@
+// python bindings for the headless core. the `feuernes` module has the console itself and
+// the gym environment around it, pictures come out as numpy arrays of rows of pixels:
+//
+//     import feuernes
+//
+//     nes = feuernes.Nes(open("game.nes", "rb").read())
+//     nes.set_buttons(feuernes.START)
+//     nes.step(60)
+//     picture = nes.frame()        # (240, 256, 3) uint8
+//     lives = nes.peek(0x075A)
+//
+//     env = feuernes.Env(rom, grayscale=True, downsample=2)
+//     env.act(feuernes.RIGHT | feuernes.A)
+//     env.step(4)
+//     observation = env.observe()  # (120, 128, 1) uint8
+//
+// both classes stay on the thread that made them, the console's callbacks are not Sync
+
+use numpy::{PyArray1, PyArray2, PyArray3, PyArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::gym::{Channels, GymEnv, ObservationMode};
+use feuernes_core::joypad::{JoypadButton, Port};
+use feuernes_core::mem::Memory;
+use feuernes_core::nes::Nes as CoreNes;
+use feuernes_core::render::frame::{Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// the internal ram at $0000-$07FF, mirrored up to $1FFF
+const RAM_SIZE: u16 = 0x0800;
+
+#[pyclass(name = "Nes", module = "feuernes", unsendable)]
+pub struct PyNes {
+    nes: CoreNes,
+}
+
+#[pymethods]
+impl PyNes {
+    // switched on and running, like after the power button
+    #[new]
+    fn new(rom: &[u8]) -> PyResult<Self> {
+        let mut nes = CoreNes::new(cartridge(rom)?);
+        nes.reset();
+        Ok(PyNes { nes })
+    }
+
+    // swaps the cartridge and power-cycles, see Nes::load_cartridge
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.nes.load_cartridge(cartridge(rom)?);
+        self.nes.reset();
+        Ok(())
+    }
+
+    // the reset button
+    fn reset(&mut self) {
+        self.nes.reset();
+    }
+
+    #[pyo3(signature = (frames = 1))]
+    fn step(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.nes.run_frame();
+        }
+    }
+
+    // a byte of the cpu's address space without side effects
+    fn peek(&self, addr: u16) -> u8 {
+        self.nes.cpu.mem_peek(addr)
+    }
+
+    // the 2KB of internal ram as bytes
+    fn ram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let ram: Vec<u8> = (0..RAM_SIZE)
+            .map(|addr| self.nes.cpu.mem_peek(addr))
+            .collect();
+        PyBytes::new(py, &ram)
+    }
+
+    // holds `buttons`, the module's button constants or'ed together, from the next frame
+    // on until they are set again
+    #[pyo3(signature = (buttons, port = 1))]
+    fn set_buttons(&mut self, buttons: u8, port: u8) -> PyResult<()> {
+        let frame = self.nes.stats().frames;
+        let port = parse_port(port).map_err(PyValueError::new_err)?;
+        self.nes
+            .queue_input(frame, port, JoypadButton::from_bits_truncate(buttons));
+        Ok(())
+    }
+
+    // the last finished frame as (240, 256, 3) rgb
+    fn frame<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        PyArray1::from_vec(py, rgb(self.nes.frame())).reshape([SCREEN_HEIGHT, SCREEN_WIDTH, 3])
+    }
+
+    // the last frame as (240, 256) system palette indices, before any colors are picked
+    fn screen<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        PyArray1::from_slice(py, self.nes.screen()).reshape([SCREEN_HEIGHT, SCREEN_WIDTH])
+    }
+
+    #[getter]
+    fn frames(&self) -> u64 {
+        self.nes.stats().frames
+    }
+
+    #[getter]
+    fn title(&self) -> Option<String> {
+        self.nes.title().map(String::from)
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.nes.save_state())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.nes.load_state(state).map_err(PyValueError::new_err)
+    }
+}
+
+// see gym::GymEnv
+#[pyclass(name = "Env", module = "feuernes", unsendable)]
+pub struct PyEnv {
+    env: GymEnv,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (rom, grayscale = false, downsample = 1))]
+    fn new(rom: &[u8], grayscale: bool, downsample: usize) -> PyResult<Self> {
+        if ![1, 2, 4, 8].contains(&downsample) {
+            return Err(PyValueError::new_err(format!(
+                "downsample by {}, not 1, 2, 4 or 8",
+                downsample
+            )));
+        }
+        let mode = ObservationMode {
+            channels: if grayscale {
+                Channels::Grayscale
+            } else {
+                Channels::Rgb
+            },
+            downsample,
+        };
+        Ok(PyEnv {
+            env: GymEnv::new(cartridge(rom)?, mode),
+        })
+    }
+
+    // height, width and channels of observe()
+    #[getter]
+    fn shape(&self) -> (usize, usize, usize) {
+        let (width, height, channels) = self.env.mode().shape();
+        (height, width, channels)
+    }
+
+    fn observe<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        PyArray1::from_slice(py, self.env.observe()).reshape(self.shape())
+    }
+
+    #[pyo3(signature = (buttons, port = 1))]
+    fn act(&mut self, buttons: u8, port: u8) -> PyResult<()> {
+        let port = parse_port(port).map_err(PyValueError::new_err)?;
+        self.env
+            .act_on(port, JoypadButton::from_bits_truncate(buttons));
+        Ok(())
+    }
+
+    #[pyo3(signature = (frames = 1))]
+    fn step(&mut self, frames: u32) {
+        self.env.step(frames);
+    }
+
+    fn reset(&mut self) {
+        self.env.reset();
+    }
+
+    fn set_start(&mut self) {
+        self.env.set_start();
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.env.peek(addr)
+    }
+
+    #[getter]
+    fn frames(&self) -> u64 {
+        self.env.frames()
+    }
+}
+
+#[pymodule]
+fn feuernes(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyNes>()?;
+    module.add_class::<PyEnv>()?;
+    let buttons = [
+        ("A", JoypadButton::BUTTON_A),
+        ("B", JoypadButton::BUTTON_B),
+        ("SELECT", JoypadButton::SELECT),
+        ("START", JoypadButton::START),
+        ("UP", JoypadButton::UP),
+        ("DOWN", JoypadButton::DOWN),
+        ("LEFT", JoypadButton::LEFT),
+        ("RIGHT", JoypadButton::RIGHT),
+    ];
+    for (name, button) in buttons.iter() {
+        module.add(*name, button.bits())?;
+    }
+    Ok(())
+}
+
+fn cartridge(rom: &[u8]) -> PyResult<Cartridge> {
+    Cartridge::new(rom).map_err(PyValueError::new_err)
+}
+
+// ports are numbered like on the console
+fn parse_port(port: u8) -> Result<Port, String> {
+    match port {
+        1 => Ok(Port::One),
+        2 => Ok(Port::Two),
+        _ => Err(format!("no controller port {}", port)),
+    }
+}
+
+// the frame without its alpha
+fn rgb(frame: &Frame) -> Vec<u8> {
+    frame
+        .data
+        .chunks_exact(4)
+        .flat_map(|pixel| pixel[..3].iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(parse_port(1), Ok(Port::One));
+        assert_eq!(parse_port(2), Ok(Port::Two));
+        assert!(parse_port(0).is_err());
+
+        let mut frame = Frame::new(2, 1);
+        frame.set_pixel(1, 0, [1, 2, 3, 0xFF]);
+        assert_eq!(rgb(&frame), [0, 0, 0, 1, 2, 3]);
+    }
+}
@@ -5,5 +5,5 @@ uniform sampler2D uScreenTex;
 varying highp vec2 vTexCoord;
 
 void main() {
-    gl_FragColor = texture2D(uScreenTex, vTexCoord) * 5.0;
+    gl_FragColor = texture2D(uScreenTex, vTexCoord);
 }
\ No newline at end of file
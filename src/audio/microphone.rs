@@ -0,0 +1,107 @@
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AnalyserNode, AudioContext, MediaStream, MediaStreamConstraints, MediaStreamTrack};
+
+// root mean square of the input over the last analysis window, above it the famicom's
+// microphone hears something. a quiet room stays well below, blowing or shouting is above
+const LOUD_LEVEL: f32 = 0.05;
+// about 20ms at 48kHz, a frame and a bit
+const WINDOW_SIZE: u32 = 1024;
+
+// the browser's microphone as the famicom's. nothing is played back, an analyser node
+// only keeps the latest input around to look at once per frame
+pub struct Microphone {
+    context: AudioContext,
+    analyser: AnalyserNode,
+    stream: MediaStream,
+    samples: Vec<f32>,
+}
+
+impl Microphone {
+    // asks the browser for the microphone, `on_ready` is called once the user answered
+    pub fn request<F>(on_ready: F)
+    where
+        F: FnOnce(Result<Microphone, String>) + 'static,
+    {
+        wasm_bindgen_futures::spawn_local(async move {
+            on_ready(Microphone::open().await);
+        });
+    }
+
+    async fn open() -> Result<Microphone, String> {
+        let devices = web_sys::window()
+            .ok_or("no window")?
+            .navigator()
+            .media_devices()
+            .map_err(|_| "this browser has no microphone access")?;
+        let mut constraints = MediaStreamConstraints::new();
+        constraints.audio(&true.into());
+        let request = devices
+            .get_user_media_with_constraints(&constraints)
+            .map_err(|_| "this browser has no microphone access")?;
+        let stream: MediaStream = JsFuture::from(request)
+            .await
+            .map_err(|_| "microphone access was denied")?
+            .unchecked_into();
+
+        let context = AudioContext::new().map_err(|_| "no audio context")?;
+        let source = context
+            .create_media_stream_source(&stream)
+            .map_err(|_| "the microphone cannot be read")?;
+        let analyser = context
+            .create_analyser()
+            .map_err(|_| "the microphone cannot be read")?;
+        analyser.set_fft_size(WINDOW_SIZE);
+        source
+            .connect_with_audio_node(&analyser)
+            .map_err(|_| "the microphone cannot be read")?;
+        Ok(Microphone {
+            context: context,
+            analyser: analyser,
+            stream: stream,
+            samples: vec![0.0; WINDOW_SIZE as usize],
+        })
+    }
+
+    pub fn is_loud(&mut self) -> bool {
+        self.analyser.get_float_time_domain_data(&mut self.samples);
+        is_loud(&self.samples)
+    }
+}
+
+impl Drop for Microphone {
+    // stopping the tracks turns off the browser's recording indicator
+    fn drop(&mut self) {
+        for track in self.stream.get_tracks().iter() {
+            track.unchecked_into::<MediaStreamTrack>().stop();
+        }
+        let _ = self.context.close();
+    }
+}
+
+fn is_loud(samples: &[f32]) -> bool {
+    if samples.is_empty() {
+        return false;
+    }
+    let power = samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32;
+    power.sqrt() > LOUD_LEVEL
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_loud() {
+        assert!(!is_loud(&[]));
+        assert!(!is_loud(&[0.0; 64]));
+        let hum: Vec<f32> = (0..64)
+            .map(|i| if i % 2 == 0 { 0.01 } else { -0.01 })
+            .collect();
+        assert!(!is_loud(&hum));
+        let shout: Vec<f32> = (0..64)
+            .map(|i| if i % 2 == 0 { 0.3 } else { -0.3 })
+            .collect();
+        assert!(is_loud(&shout));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod microphone;
+pub mod web_audio;
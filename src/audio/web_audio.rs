@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{AudioContext, AudioProcessingEvent, ScriptProcessorNode};
+
+use feuernes_core::audio::ring_buffer::AudioRingBuffer;
+use feuernes_core::audio::sink::{AudioConfig, AudioSink};
+use feuernes_core::sync::AtomicRefCell;
+
+// script processors only take powers of two from 256 to 16384
+const MIN_BUFFER_SIZE: u32 = 256;
+const MAX_BUFFER_SIZE: u32 = 16384;
+
+// plays whatever the emulator pushes into `ring` through a web audio script processor.
+// the ring is shared with the audio callback, which has to be Send
+pub struct WebAudio {
+    context: AudioContext,
+    ring: Arc<AtomicRefCell<AudioRingBuffer>>,
+    _processor: ScriptProcessorNode,
+    _on_audio_process: Closure<dyn FnMut(AudioProcessingEvent)>,
+}
+
+impl WebAudio {
+    pub fn new(config: AudioConfig) -> Option<Self> {
+        let context = AudioContext::new().ok()?;
+        let buffer_size = config
+            .buffer_size
+            .next_power_of_two()
+            .max(MIN_BUFFER_SIZE)
+            .min(MAX_BUFFER_SIZE);
+        let config = AudioConfig {
+            buffer_size: buffer_size,
+            ..config
+        };
+        let capacity = config.ring_capacity(context.sample_rate() as f64);
+        let ring = Arc::new(AtomicRefCell::new(AudioRingBuffer::new(capacity)));
+
+        let processor = context
+            .create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+                buffer_size,
+                0,
+                1,
+            )
+            .ok()?;
+
+        let on_audio_process = {
+            let ring = ring.clone();
+            let mut block = vec![0.0; buffer_size as usize];
+            Closure::wrap(Box::new(move |event: AudioProcessingEvent| {
+                if let Ok(output) = event.output_buffer() {
+                    block.resize(output.length() as usize, 0.0);
+                    ring.borrow_mut().pop_into(&mut block);
+                    let _ = output.copy_to_channel(&mut block, 0);
+                }
+            }) as Box<dyn FnMut(AudioProcessingEvent)>)
+        };
+        processor.set_onaudioprocess(Some(on_audio_process.as_ref().unchecked_ref()));
+        processor
+            .connect_with_audio_node(&context.destination())
+            .ok()?;
+
+        Some(WebAudio {
+            context: context,
+            ring: ring,
+            _processor: processor,
+            _on_audio_process: on_audio_process,
+        })
+    }
+}
+
+impl AudioSink for WebAudio {
+    fn sample_rate(&self) -> f64 {
+        self.context.sample_rate() as f64
+    }
+
+    fn ring(&self) -> Arc<AtomicRefCell<AudioRingBuffer>> {
+        self.ring.clone()
+    }
+
+    // browsers keep the context suspended until the page got a user gesture
+    fn resume(&self) {
+        let _ = self.context.resume();
+    }
+
+    fn suspend(&self) {
+        let _ = self.context.suspend();
+    }
+}
+
+impl Drop for WebAudio {
+    fn drop(&mut self) {
+        let _ = self.context.close();
+    }
+}
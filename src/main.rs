@@ -1,15 +1,7 @@
-mod bus;
-mod cartridge;
-mod cpu;
-mod mem;
-mod opcode;
-mod ppu;
+mod audio;
 mod render;
-mod trace;
-
-#[macro_use]
-extern crate lazy_static;
+mod ui;
 
 fn main() {
-    render::web_renderer::Screen::start();
+    ui::App::start();
 }
@@ -1 +1,2 @@
+pub mod perf;
 pub mod web_renderer;
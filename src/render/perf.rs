@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+// how many frames the fps average and the frame-time graph cover
+pub const PERF_HISTORY: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTiming {
+    // time spent running the emulator and drawing the picture for one frame
+    pub emulation_ms: f64,
+    pub render_ms: f64,
+}
+
+pub struct PerfMonitor {
+    last_timestamp: Option<f64>,
+    frame_intervals: VecDeque<f64>,
+    timings: VecDeque<FrameTiming>,
+    audio_underruns: u64,
+}
+
+impl PerfMonitor {
+    pub fn new() -> Self {
+        PerfMonitor {
+            last_timestamp: None,
+            frame_intervals: VecDeque::with_capacity(PERF_HISTORY),
+            timings: VecDeque::with_capacity(PERF_HISTORY),
+            audio_underruns: 0,
+        }
+    }
+
+    // timestamp is the requestAnimationFrame time in milliseconds
+    pub fn record_frame(&mut self, timestamp: f64, timing: FrameTiming) {
+        if let Some(last) = self.last_timestamp {
+            push_bounded(&mut self.frame_intervals, timestamp - last);
+        }
+        self.last_timestamp = Some(timestamp);
+        push_bounded(&mut self.timings, timing);
+    }
+
+    pub fn set_audio_underruns(&mut self, underruns: u64) {
+        self.audio_underruns = underruns;
+    }
+
+    pub fn fps(&self) -> f64 {
+        let total: f64 = self.frame_intervals.iter().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.frame_intervals.len() as f64 * 1000.0 / total
+    }
+
+    pub fn timings(&self) -> impl Iterator<Item = &FrameTiming> {
+        self.timings.iter()
+    }
+
+    pub fn last_timing(&self) -> Option<FrameTiming> {
+        self.timings.back().copied()
+    }
+
+    pub fn audio_underruns(&self) -> u64 {
+        self.audio_underruns
+    }
+
+    // a pause should not show up as one very long frame
+    pub fn reset_clock(&mut self) {
+        self.last_timestamp = None;
+    }
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, value: T) {
+    if queue.len() == PERF_HISTORY {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fps_and_history() {
+        let mut perf = PerfMonitor::new();
+        let timing = FrameTiming {
+            emulation_ms: 4.0,
+            render_ms: 1.0,
+        };
+        for i in 0..(PERF_HISTORY * 2) {
+            perf.record_frame(i as f64 * 20.0, timing);
+        }
+
+        assert!((perf.fps() - 50.0).abs() < 1e-9);
+        assert_eq!(perf.timings().count(), PERF_HISTORY);
+        assert_eq!(perf.last_timing(), Some(timing));
+
+        perf.reset_clock();
+        perf.record_frame(1e9, timing);
+        assert!((perf.fps() - 50.0).abs() < 1e-9);
+    }
+}
@@ -1,23 +1,176 @@
+use gloo::events::EventListener;
 use gloo::render::{request_animation_frame, AnimationFrame};
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{Clamped, JsCast};
 use web_sys::{
-    HtmlCanvasElement, WebGlBuffer, WebGlProgram, WebGlRenderingContext as GL, WebGlShader,
-    WebGlTexture, WebGlUniformLocation,
+    CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent, TouchEvent, WebGlBuffer,
+    WebGlProgram, WebGlRenderingContext as GL, WebGlShader, WebGlTexture, WebGlUniformLocation,
+};
+use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yew::{
+    html, ChangeData, Component, ComponentLink, Html, InputData, NodeRef, Properties, ShouldRender,
 };
-use yew::{html, Component, ComponentLink, Html, NodeRef, ShouldRender};
 
-use crate::bus;
-use crate::cartridge;
-use crate::cpu;
-use crate::mem::Memory;
-use crate::trace;
+use super::perf::{FrameTiming, PerfMonitor};
+use crate::audio::microphone::Microphone;
+use crate::audio::web_audio::WebAudio;
+use crate::ui::export;
+use crate::ui::palette::{self, Entry, Palette};
+use crate::ui::profile::{Override, Profile, Settings};
+use crate::ui::profiler::ProfilerPanel;
+use crate::ui::storage::{self, RomStore};
+use crate::ui::watch::WatchPanel;
+use feuernes_core::apu::CPU_CLOCK_RATE;
+use feuernes_core::audio::sink::{self, AudioConfig, AudioSink};
+use feuernes_core::cartridge;
+use feuernes_core::config::{Accuracy, Console};
+use feuernes_core::events::Event;
+use feuernes_core::expansion;
+use feuernes_core::input_macro::InputMacro;
+use feuernes_core::joypad::{JoypadButton, Port};
+use feuernes_core::nes::Nes;
+use feuernes_core::patch::PatchFormat;
+use feuernes_core::profiler::{Profiler, Report};
+use feuernes_core::ram_lock::{LockTiming, RamLock};
+use feuernes_core::render::frame::{Frame, Rect, SCREEN_HEIGHT, SCREEN_WIDTH};
+use feuernes_core::render::frame_buffer::{frame_buffer, FrameReader, FrameWriter};
+use feuernes_core::render::pattern_table::PATTERN_TABLE_SIZE;
+use feuernes_core::symbols::Symbols;
+use feuernes_core::sync::AtomicRefCell;
+use feuernes_core::trace::DEFAULT_TRACE_ENTRIES;
 
 use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+// emulation runs on real time, not on display refresh. after a stall (a background tab)
+// it does not try to catch up on more than this
+const MAX_ELAPSED_MS: f64 = 50.0;
+
+// frame-time graph scale, a full bar is two 60Hz frames
+const PERF_GRAPH_HEIGHT: f64 = 40.0;
+const PERF_GRAPH_MS: f64 = 1000.0 / 30.0;
+
+// extra cpu time after vblank starts offered in the settings, removes slowdown in games
+// that run out of time in their nmi handler
+const OVERCLOCK_SCANLINES: [u16; 4] = [0, 50, 100, 200];
+
+// how long an event notification stays on screen
+const TOAST_MS: f64 = 3000.0;
+
+// rows per profiler table and how often, in frames, the report is recomputed
+const PROFILE_ROWS: usize = 40;
+const PROFILE_REFRESH_FRAMES: u32 = 30;
 
-use rand::Rng;
+// input macros kept per rom, played with the number keys 1-9
+const MACRO_SLOTS: usize = 9;
+
+// while the page is hidden and set to run slowly, one frame of emulation per tick
+const BACKGROUND_FRAME_INTERVAL: Duration = Duration::from_millis(250);
 
 pub enum Message {
     Render(f64),
+    TogglePalette,
+    RunCommand(usize),
+    Touch(TouchEvent),
+    TogglePause,
+    Save,
+    ToggleSettings,
+    SetTouchControls(TouchControls),
+    SetBackground(Background),
+    VisibilityChanged,
+    BackgroundTick,
+    SetAccuracy(Accuracy),
+    SetOverclock(u16),
+    SetConsole(Console),
+    // a name from the expansion registry, None unplugs
+    PlugExpansion(Option<String>),
+    // the microphone key held down or let go
+    SetMicrophone(bool),
+    UseBrowserMicrophone(bool),
+    MicrophoneReady(Result<Microphone, String>),
+    ToggleSpriteLimit,
+    ToggleDebugTint,
+    ToggleRamLocks,
+    ToggleProfile,
+    SelectGame(usize),
+    ToggleBreakOnStackFault,
+    ToggleStats,
+    TogglePerf,
+    ToggleFrameStats,
+    ToggleChrViewer,
+    SetChrScanline(u16),
+    ToggleMapperState,
+    ToggleTrace,
+    CopyTrace,
+    DownloadTrace(ExportFormat),
+    ToggleProfiler,
+    ToggleLint,
+    ResetProfiler,
+    DownloadProfile(ExportFormat),
+    LoadSymbols(Vec<File>),
+    SymbolsLoaded(FileData),
+    LoadPatch(Vec<File>),
+    PatchLoaded(FileData),
+    ExportSram,
+    ImportSram(Vec<File>),
+    SramImported(FileData),
+    ImportState(Vec<File>),
+    StateImported(FileData),
+    ToggleSoftPatch,
+    ToggleMacroRecording,
+    PlayMacro(usize),
+    RemoveMacro(usize),
+    ReloadBuild(Vec<u8>),
+    Toast(String),
+    SetAsmAddress(String),
+    SetAsmSource(String),
+    Assemble,
+    UndoAssemble,
+    SetLockAddress(String),
+    SetLockValue(String),
+    SetLockTiming(LockTiming),
+    LockRam,
+    UnlockRam(u16),
+}
+
+// the readable text for people, json and csv for scripts
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TouchControls {
+    Auto,
+    Always,
+    Never,
+}
+
+// what emulation does while the page is hidden. either way nothing is drawn and audio is
+// suspended until the page is shown again
+#[derive(Clone, Copy, PartialEq)]
+pub enum Background {
+    Pause,
+    // a frame every BACKGROUND_FRAME_INTERVAL, enough for a game to keep its clock going
+    Slow,
+}
+
+// an entry of the command palette, what a button or checkbox somewhere does with a name to
+// search for and the key that does the same, if one does
+struct Command {
+    name: String,
+    binding: Option<String>,
+    message: Message,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct ScreenProps {
+    pub rom_name: String,
+    pub rom: Rc<Vec<u8>>,
 }
 
 pub struct ScreenBufferData {
@@ -64,8 +217,73 @@ impl ScreenProgramData {
 }
 
 pub struct Screen {
-    cpu: cpu::CPU,
+    props: ScreenProps,
+    nes: Nes,
+    // pictures handed over by the frame callback, the dirty parts of the newest one are
+    // uploaded on the next render
+    frames: FrameReader,
+    audio: Option<WebAudio>,
     frame: u32,
+    // cpu cycles owed to real time, negative when the last instruction overshot
+    cycle_budget: f64,
+    last_render_ts: Option<f64>,
+    paused: bool,
+    // the multicart game picked from the control bar, None while its menu runs
+    game: Option<usize>,
+    // why emulation stopped on its own, shown until resumed
+    break_reason: Option<String>,
+    // event notifications and the timestamp they expire at
+    toasts: Vec<(String, f64)>,
+    show_settings: bool,
+    // the command palette, opened with ctrl+k
+    show_palette: bool,
+    show_stats: bool,
+    show_perf: bool,
+    show_frame_stats: bool,
+    show_chr_viewer: bool,
+    // the chr viewer shows the banks this scanline of the last frame was drawn with
+    chr_scanline: u16,
+    chr_ref: NodeRef,
+    show_mapper_state: bool,
+    profiler: Option<Profiler>,
+    // the last report, refreshed every PROFILE_REFRESH_FRAMES
+    profile: Option<Rc<Report>>,
+    symbols: Option<Symbols>,
+    symbols_task: Option<ReaderTask>,
+    // the ips or bps patch the running game was started with
+    patch: Option<String>,
+    patch_task: Option<ReaderTask>,
+    sram_task: Option<ReaderTask>,
+    state_task: Option<ReaderTask>,
+    // the library patch made for this rom, applied at start while soft patching is on
+    soft_patch: Option<String>,
+    soft_patching: bool,
+    // the input macros of this rom, by slot
+    macros: Vec<InputMacro>,
+    // what the debugger assembles and the hex address it goes to
+    asm_address: String,
+    asm_source: String,
+    // the frozen address being typed in, hex
+    lock_address: String,
+    lock_value: String,
+    lock_timing: LockTiming,
+    perf: PerfMonitor,
+    touch_controls: TouchControls,
+    background: Background,
+    // set while the page is hidden, the render loop is stopped then
+    hidden: bool,
+    background_tick: Option<IntervalTask>,
+    _visibility_listener: Option<EventListener>,
+    // the famicom's microphone hears something while its key is held or, when the browser's
+    // microphone is used, while that is loud
+    microphone_key: bool,
+    use_browser_microphone: bool,
+    microphone: Option<Microphone>,
+    // the settings of every game, and this game's overrides while it has a profile
+    settings: Settings,
+    game_profile: Option<Profile>,
+    // read by the core whenever the game polls controller 1
+    buttons: Arc<AtomicRefCell<JoypadButton>>,
 
     gl: Option<GL>,
     link: ComponentLink<Self>,
@@ -75,15 +293,81 @@ pub struct Screen {
     _screen_program: Option<ScreenProgramData>,
     _screen_buffers: Option<ScreenBufferData>,
     _tex: Option<WebGlTexture>,
+    // until the first frame arrives the texture holds the test pattern
+    texture_uploaded: bool,
 }
 
 impl Component for Screen {
     type Message = Message;
-    type Properties = ();
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        Self {
-            cpu: init_cpu(),
+    type Properties = ScreenProps;
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let (frame_writer, frame_reader) = frame_buffer(SCREEN_WIDTH, SCREEN_HEIGHT);
+        let audio = WebAudio::new(AudioConfig::default());
+        let buttons = Arc::new(AtomicRefCell::new(JoypadButton::empty()));
+        let visibility_listener =
+            web_sys::window()
+                .and_then(|window| window.document())
+                .map(|document| {
+                    let link = link.clone();
+                    EventListener::new(&document, "visibilitychange", move |_| {
+                        link.send_message(Message::VisibilityChanged)
+                    })
+                });
+        let mut screen = Self {
+            nes: init_nes(
+                &props.rom_name,
+                &props.rom,
+                frame_writer,
+                audio.as_ref(),
+                buttons.clone(),
+            ),
+            frames: frame_reader,
+            audio: audio,
+            props: props,
             frame: 0,
+            cycle_budget: 0.0,
+            last_render_ts: None,
+            paused: false,
+            game: None,
+            break_reason: None,
+            toasts: Vec::new(),
+            show_settings: false,
+            show_palette: false,
+            show_stats: false,
+            show_perf: false,
+            show_frame_stats: false,
+            show_chr_viewer: false,
+            chr_scanline: 0,
+            chr_ref: NodeRef::default(),
+            show_mapper_state: false,
+            profiler: None,
+            profile: None,
+            symbols: None,
+            symbols_task: None,
+            patch: None,
+            patch_task: None,
+            sram_task: None,
+            state_task: None,
+            soft_patch: None,
+            soft_patching: true,
+            macros: Vec::new(),
+            asm_address: String::new(),
+            asm_source: String::new(),
+            lock_address: String::new(),
+            lock_value: String::new(),
+            lock_timing: LockTiming::Frame,
+            perf: PerfMonitor::new(),
+            touch_controls: TouchControls::Auto,
+            background: Background::Pause,
+            hidden: false,
+            background_tick: None,
+            _visibility_listener: visibility_listener,
+            microphone_key: false,
+            use_browser_microphone: false,
+            microphone: None,
+            settings: Settings::default(),
+            game_profile: None,
+            buttons: buttons,
 
             gl: None,
             link: link,
@@ -92,29 +376,41 @@ impl Component for Screen {
             _screen_program: None,
             _screen_buffers: None,
             _tex: None,
-        }
+            texture_uploaded: false,
+        };
+        screen.start_soft_patched();
+        screen.macros = RomStore::new().load_macros(&screen.props.rom_name);
+        screen
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+
+        // swap the cartridge in place, gl and audio stay alive
+        self.props = props;
+        self.start_soft_patched();
+        self.macros = RomStore::new().load_macros(&self.props.rom_name);
+        true
     }
 
     fn rendered(&mut self, _first_render: bool) {
-        let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
-        canvas.set_width(320);
-        canvas.set_height(320);
-        self.gl = Some(
-            canvas
-                .get_context("webgl")
-                .unwrap()
-                .unwrap()
-                .dyn_into()
-                .unwrap(),
-        );
+        if _first_render {
+            let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
+            canvas.set_width(SCREEN_WIDTH as u32 * 2);
+            canvas.set_height(SCREEN_HEIGHT as u32 * 2);
+            self.gl = Some(
+                canvas
+                    .get_context("webgl")
+                    .unwrap()
+                    .unwrap()
+                    .dyn_into()
+                    .unwrap(),
+            );
 
-        self.init();
+            self.init();
 
-        if _first_render {
             let handle = {
                 let link = self.link.clone();
                 request_animation_frame(move |time| link.send_message(Message::Render(time)))
@@ -124,67 +420,1462 @@ impl Component for Screen {
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let user_input = !matches!(
+            msg,
+            Message::Render(_) | Message::VisibilityChanged | Message::BackgroundTick
+        );
+        if let (Some(audio), true, false) = (&self.audio, user_input, self.hidden) {
+            // any user interaction is allowed to start audio output
+            audio.resume();
+        }
+
         match msg {
-            Message::Render(ts) => {
-                self.render_loop(ts);
+            Message::Render(ts) => self.render_loop(ts),
+            Message::Touch(event) => {
+                self.handle_touch(event);
+                false
+            }
+            Message::TogglePalette => {
+                self.show_palette = !self.show_palette;
+                true
+            }
+            Message::RunCommand(index) => {
+                self.show_palette = false;
+                match self.commands().into_iter().nth(index) {
+                    Some(command) => self.update(command.message),
+                    None => true,
+                }
+            }
+            Message::TogglePause => {
+                self.paused = !self.paused;
+                self.break_reason = None;
+                true
+            }
+            Message::Save => {
+                RomStore::new().save_sram(&self.save_name(), &self.nes.save_sram());
+                false
+            }
+            Message::ToggleSettings => {
+                self.show_settings = !self.show_settings;
+                true
+            }
+            Message::SetBackground(background) => {
+                self.background = background;
+                false
+            }
+            Message::VisibilityChanged => {
+                let hidden = web_sys::window()
+                    .and_then(|window| window.document())
+                    .map_or(false, |document| document.hidden());
+                if hidden != self.hidden {
+                    self.hidden = hidden;
+                    self.apply_visibility();
+                }
+                false
+            }
+            Message::BackgroundTick => {
+                if !self.paused && self.emulate(1000.0 / 60.0) {
+                    return true;
+                }
+                false
+            }
+            Message::SetTouchControls(touch_controls) => {
+                self.touch_controls = touch_controls;
+                true
+            }
+            Message::SetAccuracy(accuracy) => {
+                self.change_setting(Override::Accuracy(accuracy));
+                true
+            }
+            Message::SetOverclock(scanlines) => {
+                self.change_setting(Override::OverclockScanlines(scanlines));
+                true
+            }
+            Message::SetConsole(console) => {
+                self.change_setting(Override::Console(console));
+                true
+            }
+            Message::PlugExpansion(name) => {
+                let device = name.map(|name| expansion::Registry::default().create(&name));
+                match device.transpose() {
+                    Ok(device) => self.nes.plug_expansion(device),
+                    Err(reason) => self.toasts.push((reason, now() + TOAST_MS)),
+                }
+                true
+            }
+            Message::SetMicrophone(held) => {
+                self.microphone_key = held;
+                false
+            }
+            Message::UseBrowserMicrophone(on) => {
+                self.use_browser_microphone = on;
+                self.microphone = None;
+                if on {
+                    let link = self.link.clone();
+                    Microphone::request(move |microphone| {
+                        link.send_message(Message::MicrophoneReady(microphone))
+                    });
+                }
+                true
+            }
+            Message::MicrophoneReady(result) => {
+                match result {
+                    // turned off again while the browser was asking
+                    Ok(_) if !self.use_browser_microphone => {}
+                    Ok(microphone) => self.microphone = Some(microphone),
+                    Err(reason) => {
+                        self.use_browser_microphone = false;
+                        self.toasts
+                            .push((format!("Microphone: {}", reason), now() + TOAST_MS));
+                    }
+                }
+                true
+            }
+            Message::ToggleSpriteLimit => {
+                let on = self.nes.sprite_limit();
+                self.change_setting(Override::SpriteLimit(!on));
+                true
+            }
+            Message::ToggleDebugTint => {
+                let on = self.nes.debug_tint();
+                self.nes.set_debug_tint(!on);
+                true
+            }
+            Message::ToggleRamLocks => {
+                let on = self.game_settings().ram_locks;
+                self.change_setting(Override::RamLocks(!on));
+                true
+            }
+            Message::ToggleProfile => {
+                let mut store = RomStore::new();
+                match self.game_profile.take() {
+                    Some(_) => store.remove_profile(&self.props.rom),
+                    None => {
+                        let profile = Profile::default();
+                        store.save_profile(&self.props.rom, &profile);
+                        self.game_profile = Some(profile);
+                    }
+                }
+                self.apply_settings();
+                true
+            }
+            Message::SelectGame(game) => {
+                self.nes.select_game(game);
+                self.game = Some(game);
+                true
+            }
+            Message::ToggleBreakOnStackFault => {
+                self.nes.cpu.break_on_stack_fault = !self.nes.cpu.break_on_stack_fault;
+                true
+            }
+            Message::ToggleStats => {
+                self.show_stats = !self.show_stats;
+                true
+            }
+            Message::TogglePerf => {
+                self.show_perf = !self.show_perf;
+                true
+            }
+            Message::ToggleFrameStats => {
+                self.show_frame_stats = !self.show_frame_stats;
+                self.nes.show_frame_stats(self.show_frame_stats);
+                true
+            }
+            Message::ToggleChrViewer => {
+                self.show_chr_viewer = !self.show_chr_viewer;
+                true
+            }
+            Message::SetChrScanline(scanline) => {
+                self.chr_scanline = scanline;
+                true
+            }
+            Message::ToggleMapperState => {
+                self.show_mapper_state = !self.show_mapper_state;
+                true
+            }
+            Message::ToggleTrace => {
+                if self.nes.trace_log().is_some() {
+                    self.nes.disable_trace();
+                } else {
+                    self.nes.enable_trace(DEFAULT_TRACE_ENTRIES);
+                }
+                true
+            }
+            Message::CopyTrace => self.export_trace(None),
+            Message::DownloadTrace(format) => self.export_trace(Some(format)),
+            Message::ToggleProfiler => {
+                self.profiler = match self.profiler {
+                    Some(_) => None,
+                    None => Some(Profiler::attach(&mut self.nes.cpu.hooks)),
+                };
+                self.profile = None;
+                true
+            }
+            Message::ToggleLint => {
+                if self.nes.lint_enabled() {
+                    self.nes.disable_lint();
+                } else {
+                    self.nes.enable_lint();
+                }
+                true
+            }
+            Message::ResetProfiler => {
+                if let Some(profiler) = &self.profiler {
+                    profiler.reset();
+                }
+                self.profile = None;
+                true
+            }
+            Message::DownloadProfile(format) => self.export_profile(format),
+            Message::LoadSymbols(files) => {
+                if let Some(file) = files.into_iter().next() {
+                    let callback = self.link.callback(Message::SymbolsLoaded);
+                    self.symbols_task = ReaderService::read_file(file, callback).ok();
+                }
+                false
+            }
+            Message::SymbolsLoaded(file) => {
+                self.symbols_task = None;
+                let text = String::from_utf8_lossy(&file.content);
+                let toast = match Symbols::parse_nl(&text) {
+                    Ok(symbols) => {
+                        let toast = format!("Loaded {} labels", symbols.len());
+                        self.symbols = Some(symbols);
+                        self.refresh_profile();
+                        toast
+                    }
+                    Err(reason) => format!("{}: {}", file.name, reason),
+                };
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::LoadPatch(files) => {
+                if let Some(file) = files.into_iter().next() {
+                    let callback = self.link.callback(Message::PatchLoaded);
+                    self.patch_task = ReaderService::read_file(file, callback).ok();
+                }
+                false
+            }
+            Message::PatchLoaded(file) => {
+                self.patch_task = None;
+                let toast = match self.restart(Some((&file.name, &file.content))) {
+                    Ok(()) => format!("Started with {}", file.name),
+                    Err(reason) => format!("{}: {}", file.name, reason),
+                };
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::ExportSram => {
+                let sram = self.nes.save_sram();
+                RomStore::new().save_sram(&self.save_name(), &sram);
+                let file_name = format!("{}.sav", self.save_name());
+                let toast = match export::download_bytes(&file_name, &sram) {
+                    Ok(()) => format!("Exported {}", file_name),
+                    Err(_) => String::from("Could not export the save"),
+                };
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::ImportSram(files) => {
+                if let Some(file) = files.into_iter().next() {
+                    let callback = self.link.callback(Message::SramImported);
+                    self.sram_task = ReaderService::read_file(file, callback).ok();
+                }
+                false
+            }
+            Message::SramImported(file) => {
+                self.sram_task = None;
+                let toast = self.import_sram(&file.content);
+                let toast = format!("{}: {}", file.name, toast);
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::ImportState(files) => {
+                if let Some(file) = files.into_iter().next() {
+                    let callback = self.link.callback(Message::StateImported);
+                    self.state_task = ReaderService::read_file(file, callback).ok();
+                }
+                false
+            }
+            Message::StateImported(file) => {
+                self.state_task = None;
+                // success shows up as events
+                if let Err(reason) = self.nes.import_state(&file.content) {
+                    let toast = format!("{}: {}", file.name, reason);
+                    self.toasts.push((toast, now() + TOAST_MS));
+                }
+                true
+            }
+            Message::ToggleSoftPatch => {
+                RomStore::new().set_soft_patching(&self.props.rom_name, !self.soft_patching);
+                self.start_soft_patched();
+                true
+            }
+            Message::ToggleMacroRecording => {
+                match self.nes.stop_macro_recording() {
+                    Some(input_macro) => self.add_macro(input_macro),
+                    None => self.nes.start_macro_recording(Port::One),
+                }
+                true
+            }
+            Message::PlayMacro(slot) => {
+                if let Some(input_macro) = self.macros.get(slot) {
+                    self.nes.play_macro(Port::One, input_macro.clone());
+                }
+                false
+            }
+            Message::RemoveMacro(slot) => {
+                if slot < self.macros.len() {
+                    self.macros.remove(slot);
+                    RomStore::new().save_macros(&self.props.rom_name, &self.macros);
+                }
+                true
+            }
+            // the build is kept in the library next to the rom it replaced, see
+            // storage::watched_name, so the original is never overwritten. symbols, the
+            // profiler and the debugger settings stay
+            Message::ReloadBuild(rom) => {
+                RomStore::new().save(&storage::watched_name(&self.props.rom_name), &rom);
+                self.props.rom = Rc::new(rom);
+                self.start_soft_patched();
+                true
+            }
+            Message::Toast(toast) => {
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::SetAsmAddress(address) => {
+                self.asm_address = address;
+                false
+            }
+            Message::SetAsmSource(source) => {
+                self.asm_source = source;
                 false
             }
+            Message::Assemble => {
+                let address = self.asm_address.trim().trim_start_matches('$');
+                let toast = match u16::from_str_radix(address, 16) {
+                    Ok(address) => match self.nes.patch_code(address, &self.asm_source) {
+                        Ok(bytes) => format!("Assembled {} bytes at ${:04X}", bytes, address),
+                        Err(reason) => reason,
+                    },
+                    Err(_) => format!("Bad address {:?}", self.asm_address),
+                };
+                self.toasts.push((toast, now() + TOAST_MS));
+                true
+            }
+            Message::UndoAssemble => {
+                self.nes.undo_code_patch();
+                true
+            }
+            Message::SetLockAddress(address) => {
+                self.lock_address = address;
+                false
+            }
+            Message::SetLockValue(value) => {
+                self.lock_value = value;
+                false
+            }
+            Message::SetLockTiming(timing) => {
+                self.lock_timing = timing;
+                false
+            }
+            Message::LockRam => {
+                let hex = |text: &str| u16::from_str_radix(text.trim().trim_start_matches('$'), 16);
+                let result = match (hex(&self.lock_address), hex(&self.lock_value)) {
+                    (Ok(address), Ok(value)) if value <= 0xFF => self.nes.lock_ram(RamLock {
+                        addr: address,
+                        value: value as u8,
+                        timing: self.lock_timing,
+                    }),
+                    _ => Err(String::from("Give the address and value in hex")),
+                };
+                match result {
+                    Ok(()) => self.save_ram_locks(),
+                    Err(reason) => self.toasts.push((reason, now() + TOAST_MS)),
+                }
+                true
+            }
+            Message::UnlockRam(address) => {
+                self.nes.unlock_ram(address);
+                self.save_ram_locks();
+                true
+            }
         }
     }
 
     fn view(&self) -> Html {
+        // number keys play the macro in their slot while the emulator has focus, ctrl+k
+        // opens the command palette and m is held to speak into the famicom's microphone
+        let onkeydown = self.link.batch_callback(|event: KeyboardEvent| {
+            if palette::is_palette_key(&event) {
+                event.prevent_default();
+                return vec![Message::TogglePalette];
+            }
+            if is_microphone_key(&event) {
+                return vec![Message::SetMicrophone(true)];
+            }
+            match event.key().parse::<usize>() {
+                Ok(key) if (1..=MACRO_SLOTS).contains(&key) => vec![Message::PlayMacro(key - 1)],
+                _ => vec![],
+            }
+        });
+        let onkeyup = self.link.batch_callback(|event: KeyboardEvent| {
+            if is_microphone_key(&event) {
+                vec![Message::SetMicrophone(false)]
+            } else {
+                vec![]
+            }
+        });
         html! {
-            <canvas ref={self.node_ref.clone()} />
+            <div class="emulator" tabindex="0" onkeydown=onkeydown onkeyup=onkeyup>
+                <div class="screen">
+                    <canvas ref={self.node_ref.clone()} />
+                    { self.view_stats() }
+                    { self.view_perf() }
+                    { self.view_touch_controls() }
+                    { self.view_toasts() }
+                    { self.view_palette() }
+                </div>
+                { self.view_control_bar() }
+                { self.view_settings() }
+                { self.view_chr_viewer() }
+                { self.view_mapper_state() }
+                { self.view_profiler() }
+            </div>
         }
     }
 }
 
-fn byte_to_color(byte: u8) -> (u8, u8, u8, u8) {
-    match byte {
-        0 => (0, 0, 0, 255),
-        1 => (255, 255, 255, 255),
-        2 | 9 => (128, 128, 128, 255),
-        3 | 10 => (255, 0, 0, 255),
-        4 | 11 => (0, 255, 0, 255),
-        5 | 12 => (0, 0, 255, 255),
-        6 | 13 => (255, 0, 255, 255),
-        7 | 14 => (255, 255, 0, 255),
-        _ => (0, 255, 255, 255),
-    }
+fn is_mobile_user_agent() -> bool {
+    web_sys::window()
+        .and_then(|window| window.navigator().user_agent().ok())
+        .map(|ua| {
+            ["Android", "iPhone", "iPad", "Mobile"]
+                .iter()
+                .any(|name| ua.contains(name))
+        })
+        .unwrap_or(false)
 }
 
-fn render(cpu: &mut cpu::CPU) -> Vec<u8> {
-    let mut frame = vec![0u8; 32 * 32 * 4];
-    let mut frame_idx = 0;
-    for i in 0x200..0x600 {
-        let color_idx = cpu.mem_read(i);
+// milliseconds, high resolution where the browser allows it
+fn now() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or_else(js_sys::Date::now)
+}
 
-        // use web_sys::console;
-        // console::log_1(&format!("color: {}", color_idx).into());
+fn is_microphone_key(event: &KeyboardEvent) -> bool {
+    !event.ctrl_key() && !event.meta_key() && event.key().eq_ignore_ascii_case("m")
+}
 
-        let (b1, b2, b3, _) = byte_to_color(color_idx);
-        frame[frame_idx] = b1;
-        frame[frame_idx + 1] = b2;
-        frame[frame_idx + 2] = b3;
-        frame[frame_idx + 3] = 255;
-        frame_idx += 4;
-        // console::log_1(&format!("color: {}, {}, {}", b1, b2, b3).into());
+fn touch_button(name: &str) -> JoypadButton {
+    match name {
+        "up" => JoypadButton::UP,
+        "down" => JoypadButton::DOWN,
+        "left" => JoypadButton::LEFT,
+        "right" => JoypadButton::RIGHT,
+        "select" => JoypadButton::SELECT,
+        "start" => JoypadButton::START,
+        "b" => JoypadButton::BUTTON_B,
+        "a" => JoypadButton::BUTTON_A,
+        _ => JoypadButton::empty(),
     }
+}
 
-    frame
+fn toast_text(event: &Event) -> Option<String> {
+    match event {
+        // the title is already in the control bar
+        Event::RomLoaded { .. } => None,
+        Event::SramSaved { bytes } => Some(format!("Saved {} bytes of battery ram", bytes)),
+        Event::StateLoaded => Some(String::from("State loaded")),
+        Event::StateImportSkipped { part } => Some(format!("Not imported: {}", part)),
+        Event::IllegalOpcode { pc, opcode } => Some(format!(
+            "Skipped illegal opcode ${:02X} at ${:04X}",
+            opcode, pc
+        )),
+        Event::DesyncDetected { frame } => Some(format!("Desync detected at frame {}", frame)),
+        Event::DiskSideChanged { side: Some(side) } => Some(format!("Disk side {}", side + 1)),
+        Event::DiskSideChanged { side: None } => Some(String::from("Disk ejected")),
+        Event::AchievementUnlocked { title, .. } => {
+            Some(format!("Achievement unlocked: {}", title))
+        }
+        Event::Lint { pc, warning } => Some(format!("${:04X}: {}", pc, warning)),
+    }
 }
 
-fn init_cpu() -> cpu::CPU {
-    let bytes = include_bytes!("../../res/snake.nes");
-    let cartridge = cartridge::Cartridge::new(&bytes.to_vec()).unwrap();
-    let bus = bus::Bus::new(cartridge);
-    let cpu = cpu::CPU::new(bus);
-    cpu
+fn init_nes(
+    rom_name: &str,
+    rom: &Vec<u8>,
+    mut frames: FrameWriter,
+    audio: Option<&WebAudio>,
+    buttons: Arc<AtomicRefCell<JoypadButton>>,
+) -> Nes {
+    let cartridge = cartridge::Cartridge::new(rom).unwrap();
+    let mut nes = Nes::new(cartridge);
+    load_sram(&mut nes, rom_name);
+
+    nes.set_input_provider(move |port| match port {
+        Port::One => *buttons.borrow(),
+        Port::Two => JoypadButton::empty(),
+    });
+
+    nes.set_frame_callback(move |frame| {
+        frames.publish(frame);
+    });
+
+    if let Some(audio) = audio {
+        sink::connect(&mut nes, audio);
+    }
+    nes
+}
+
+fn load_sram(nes: &mut Nes, rom_name: &str) {
+    if let Some(sram) = RomStore::new().load_sram(rom_name) {
+        nes.load_sram(&sram);
+    }
 }
 
 impl Screen {
-    pub fn start() {
-        yew::start_app::<Screen>();
+    // a patched game keeps its battery save apart from the unpatched one
+    fn save_name(&self) -> String {
+        match &self.patch {
+            Some(patch) => format!("{}+{}", self.props.rom_name, patch),
+            None => self.props.rom_name.clone(),
+        }
+    }
+
+    // a .sav from this or another emulator, the raw battery ram. the game restarts so it
+    // reads the save the way it does at power-on
+    fn import_sram(&mut self, sram: &[u8]) -> String {
+        let size = self.nes.sram_size();
+        if size == 0 {
+            return String::from("this game has no battery save");
+        }
+        if sram.len() != size {
+            return format!("expected {} bytes, the file has {}", size, sram.len());
+        }
+        self.nes.load_sram(sram);
+        RomStore::new().save_sram(&self.save_name(), sram);
+        self.nes.reset();
+        String::from("imported the save")
+    }
+
+    // restarts the game from the library rom, with `patch` applied if there is one. the
+    // rom in the library stays as it is
+    fn restart(&mut self, patch: Option<(&str, &[u8])>) -> Result<(), String> {
+        let mut cartridge = cartridge::Cartridge::new(&self.props.rom)?;
+        if let Some((_, patch)) = patch {
+            let format = PatchFormat::detect(patch)
+                .ok_or_else(|| String::from("not an IPS or BPS patch"))?;
+            cartridge.apply_patch(format, patch)?;
+        }
+        self.nes.load_cartridge(cartridge);
+        self.apply_settings();
+        self.patch = patch.map(|(name, _)| String::from(name));
+        let save_name = self.save_name();
+        load_sram(&mut self.nes, &save_name);
+        self.nes.reset();
+        self.game = None;
+        self.frame = 0;
+        self.cycle_budget = 0.0;
+        self.paused = false;
+        self.break_reason = None;
+        Ok(())
+    }
+
+    // restarts the game with the library patch made for it unless the player turned soft
+    // patching off for this rom. a patch that does not apply leaves the game unpatched
+    fn start_soft_patched(&mut self) {
+        let store = RomStore::new();
+        let found = store.find_patch(&self.props.rom_name, &self.props.rom);
+        self.soft_patching = store.soft_patching(&self.props.rom_name);
+        self.game_profile = store.load_profile(&self.props.rom);
+        self.soft_patch = found.as_ref().map(|(name, _)| name.clone());
+        let patch = match &found {
+            Some((name, patch)) if self.soft_patching => Some((name.as_str(), patch.as_slice())),
+            _ => None,
+        };
+        if let Err(reason) = self.restart(patch) {
+            let name = self.soft_patch.clone().unwrap_or_default();
+            self.toasts
+                .push((format!("{}: {}", name, reason), now() + TOAST_MS));
+            self.restart(None).unwrap();
+        }
+    }
+
+    // the global settings with this game's profile on top
+    fn game_settings(&self) -> Settings {
+        match &self.game_profile {
+            Some(profile) => profile.apply(&self.settings),
+            None => self.settings,
+        }
+    }
+
+    fn apply_settings(&mut self) {
+        let settings = self.game_settings();
+        self.nes.set_accuracy(settings.accuracy);
+        self.nes
+            .set_overclock_scanlines(settings.overclock_scanlines);
+        self.nes.set_sprite_limit(settings.sprite_limit);
+        self.nes.set_console(settings.console);
+        if !settings.ram_locks {
+            let locked: Vec<u16> = self.nes.ram_locks().iter().map(|lock| lock.addr).collect();
+            for address in locked {
+                self.nes.unlock_ram(address);
+            }
+        } else if self.nes.ram_locks().is_empty() {
+            for lock in RomStore::new().load_ram_locks(&self.props.rom_name) {
+                // only valid locks are ever saved
+                let _ = self.nes.lock_ram(lock);
+            }
+        }
+    }
+
+    // a change made while the game has a profile is kept for this game only
+    fn change_setting(&mut self, setting: Override) {
+        match self.game_profile.as_mut() {
+            Some(profile) => {
+                profile.set(setting, &self.settings);
+                RomStore::new().save_profile(&self.props.rom, profile);
+            }
+            None => setting.apply(&mut self.settings),
+        }
+        self.apply_settings();
+    }
+
+    fn save_ram_locks(&self) {
+        RomStore::new().save_ram_locks(&self.props.rom_name, self.nes.ram_locks());
+    }
+
+    // a recording with no button pressed is dropped, and so is one with every slot taken
+    fn add_macro(&mut self, input_macro: InputMacro) {
+        let toast = if input_macro.is_empty() {
+            String::from("No buttons were pressed while recording")
+        } else if self.macros.len() >= MACRO_SLOTS {
+            format!("All {} macro slots are taken", MACRO_SLOTS)
+        } else {
+            let toast = format!(
+                "Recorded {} frames as macro {}",
+                input_macro.frames.len(),
+                self.macros.len() + 1
+            );
+            self.macros.push(input_macro);
+            RomStore::new().save_macros(&self.props.rom_name, &self.macros);
+            toast
+        };
+        self.toasts.push((toast, now() + TOAST_MS));
+    }
+
+    fn view_control_bar(&self) -> Html {
+        let pause_label = if self.paused { "Resume" } else { "Pause" };
+        html! {
+            <div class="control-bar">
+                <span class="game-title">{ self.nes.title().unwrap_or(&self.props.rom_name) }</span>
+                { self.view_game_select() }
+                <button onclick=self.link.callback(|_| Message::TogglePause)>{ pause_label }</button>
+                <button onclick=self.link.callback(|_| Message::Save)>{ "Save" }</button>
+                <button onclick=self.link.callback(|_| Message::ToggleSettings)>{ "Settings" }</button>
+                <button title="Ctrl+K" onclick=self.link.callback(|_| Message::TogglePalette)>
+                    { "Commands" }
+                </button>
+                {
+                    if self.nes.trace_log().is_some() {
+                        html! {
+                            <>
+                                <button onclick=self.link.callback(|_| Message::CopyTrace)>
+                                    { "Copy trace" }
+                                </button>
+                                <button onclick=self.link.callback(|_| Message::DownloadTrace(ExportFormat::Text))>
+                                    { "Download trace" }
+                                </button>
+                                <button onclick=self.link.callback(|_| Message::DownloadTrace(ExportFormat::Json))>
+                                    { "JSON" }
+                                </button>
+                                <button onclick=self.link.callback(|_| Message::DownloadTrace(ExportFormat::Csv))>
+                                    { "CSV" }
+                                </button>
+                            </>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    match &self.break_reason {
+                        Some(reason) => html! { <span class="break-reason">{ reason }</span> },
+                        None => html! {},
+                    }
+                }
+            </div>
+        }
+    }
+
+    // multicarts get a menu to start one of their games without going through their own
+    fn view_game_select(&self) -> Html {
+        let games = self.nes.games();
+        if games < 2 {
+            return html! {};
+        }
+        let onchange = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().parse() {
+                Ok(game) => vec![Message::SelectGame(game)],
+                Err(_) => vec![],
+            },
+            _ => vec![],
+        });
+        html! {
+            <select class="game-select" onchange=onchange>
+                <option value="" selected=self.game.is_none() disabled=true>{ "Multicart menu" }</option>
+                { for (0..games).map(|game| html! {
+                    <option value=game.to_string() selected=self.game == Some(game)>
+                        { format!("Game {}", game + 1) }
+                    </option>
+                }) }
+            </select>
+        }
+    }
+
+    // hidden rather than left out while closed, the watch panel in it keeps watching
+    fn view_settings(&self) -> Html {
+        let onchange = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "always" => vec![Message::SetTouchControls(TouchControls::Always)],
+                "never" => vec![Message::SetTouchControls(TouchControls::Never)],
+                _ => vec![Message::SetTouchControls(TouchControls::Auto)],
+            },
+            _ => vec![],
+        });
+
+        let on_background = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "slow" => vec![Message::SetBackground(Background::Slow)],
+                _ => vec![Message::SetBackground(Background::Pause)],
+            },
+            _ => vec![],
+        });
+
+        let on_microphone = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => {
+                vec![Message::UseBrowserMicrophone(select.value() == "browser")]
+            }
+            _ => vec![],
+        });
+
+        let on_accuracy = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "fast" => vec![Message::SetAccuracy(Accuracy::Fast)],
+                "cycle" => vec![Message::SetAccuracy(Accuracy::Cycle)],
+                _ => vec![Message::SetAccuracy(Accuracy::Balanced)],
+            },
+            _ => vec![],
+        });
+        let accuracy = self.nes.accuracy();
+
+        let on_console = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "famicom" => vec![Message::SetConsole(Console::Famicom)],
+                "av_famicom" => vec![Message::SetConsole(Console::AvFamicom)],
+                _ => vec![Message::SetConsole(Console::Nes)],
+            },
+            _ => vec![],
+        });
+        let console = self.nes.console();
+
+        let on_expansion = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "" => vec![Message::PlugExpansion(None)],
+                name => vec![Message::PlugExpansion(Some(name.to_string()))],
+            },
+            _ => vec![],
+        });
+        let plugged = self.nes.expansion_name();
+        let devices = expansion::Registry::default()
+            .names()
+            .into_iter()
+            .map(|name| {
+                html! {
+                    <option value=name selected=plugged == Some(name)>
+                        { name.replace('_', " ") }
+                    </option>
+                }
+            });
+
+        let on_overclock = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().parse() {
+                Ok(scanlines) => vec![Message::SetOverclock(scanlines)],
+                Err(_) => vec![],
+            },
+            _ => vec![],
+        });
+        let overclock = self.nes.overclock_scanlines();
+
+        let on_patch = self.link.batch_callback(|data| match data {
+            ChangeData::Files(files) => {
+                let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+                vec![Message::LoadPatch(files)]
+            }
+            _ => vec![],
+        });
+        let on_sram = self.link.batch_callback(|data| match data {
+            ChangeData::Files(files) => {
+                let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+                vec![Message::ImportSram(files)]
+            }
+            _ => vec![],
+        });
+        let on_state = self.link.batch_callback(|data| match data {
+            ChangeData::Files(files) => {
+                let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+                vec![Message::ImportState(files)]
+            }
+            _ => vec![],
+        });
+        let patch_label = match &self.patch {
+            Some(patch) => format!("Patch ({}) ", patch),
+            None => String::from("Patch (.ips, .bps) "),
+        };
+        let soft_patch = match &self.soft_patch {
+            Some(patch) => html! {
+                <label>
+                    <input type="checkbox"
+                        checked=self.soft_patching
+                        onclick=self.link.callback(|_| Message::ToggleSoftPatch) />
+                    { format!(" Apply {} automatically", patch) }
+                </label>
+            },
+            None => html! {},
+        };
+
+        html! {
+            <div class="settings" hidden=!self.show_settings>
+                <label>
+                    { "Touch controls " }
+                    <select onchange=onchange>
+                        <option value="auto" selected=self.touch_controls == TouchControls::Auto>
+                            { "Auto" }
+                        </option>
+                        <option value="always" selected=self.touch_controls == TouchControls::Always>
+                            { "Always" }
+                        </option>
+                        <option value="never" selected=self.touch_controls == TouchControls::Never>
+                            { "Never" }
+                        </option>
+                    </select>
+                </label>
+                <label>
+                    { "In a background tab " }
+                    <select onchange=on_background>
+                        <option value="pause" selected=self.background == Background::Pause>
+                            { "Pause" }
+                        </option>
+                        <option value="slow" selected=self.background == Background::Slow>
+                            { "Run slowly" }
+                        </option>
+                    </select>
+                </label>
+                <label>
+                    { "Famicom microphone " }
+                    <select onchange=on_microphone>
+                        <option value="key" selected=!self.use_browser_microphone>
+                            { "Hold M" }
+                        </option>
+                        <option value="browser" selected=self.use_browser_microphone>
+                            { "Hold M or speak" }
+                        </option>
+                    </select>
+                </label>
+                <label>
+                    { "Accuracy " }
+                    <select onchange=on_accuracy>
+                        <option value="fast" selected=accuracy == Accuracy::Fast>
+                            { "Fast" }
+                        </option>
+                        <option value="balanced" selected=accuracy == Accuracy::Balanced>
+                            { "Balanced" }
+                        </option>
+                        <option value="cycle" selected=accuracy == Accuracy::Cycle>
+                            { "Cycle" }
+                        </option>
+                    </select>
+                </label>
+                <label>
+                    { "Console " }
+                    <select onchange=on_console>
+                        <option value="nes" selected=console == Console::Nes>
+                            { "NES" }
+                        </option>
+                        <option value="famicom" selected=console == Console::Famicom>
+                            { "Famicom" }
+                        </option>
+                        <option value="av_famicom" selected=console == Console::AvFamicom>
+                            { "AV Famicom" }
+                        </option>
+                    </select>
+                </label>
+                <label>
+                    { "Expansion port " }
+                    <select onchange=on_expansion>
+                        <option value="" selected=plugged.is_none()>{ "Nothing" }</option>
+                        { for devices }
+                    </select>
+                </label>
+                <label>
+                    { "Overclock " }
+                    <select onchange=on_overclock>
+                        { for OVERCLOCK_SCANLINES.iter().map(|scanlines| html! {
+                            <option value=scanlines.to_string() selected=*scanlines == overclock>
+                                { if *scanlines == 0 {
+                                    String::from("Off")
+                                } else {
+                                    format!("{} scanlines", scanlines)
+                                } }
+                            </option>
+                        }) }
+                    </select>
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=!self.nes.sprite_limit()
+                        onclick=self.link.callback(|_| Message::ToggleSpriteLimit) />
+                    { " Reduce sprite flicker" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.game_profile.is_some()
+                        onclick=self.link.callback(|_| Message::ToggleProfile) />
+                    { " Keep settings for this game" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.nes.debug_tint()
+                        onclick=self.link.callback(|_| Message::ToggleDebugTint) />
+                    { " Tint background and sprites" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.nes.cpu.break_on_stack_fault
+                        onclick=self.link.callback(|_| Message::ToggleBreakOnStackFault) />
+                    { " Break on stack overflow/underflow" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.show_stats
+                        onclick=self.link.callback(|_| Message::ToggleStats) />
+                    { " Show stats" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.show_perf
+                        onclick=self.link.callback(|_| Message::TogglePerf) />
+                    { " Show FPS and frame times" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.show_frame_stats
+                        onclick=self.link.callback(|_| Message::ToggleFrameStats) />
+                    { " Show sprites per scanline and scroll splits" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.show_chr_viewer
+                        onclick=self.link.callback(|_| Message::ToggleChrViewer) />
+                    { " Show CHR banks" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.show_mapper_state
+                        onclick=self.link.callback(|_| Message::ToggleMapperState) />
+                    { " Show mapper registers" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.nes.trace_log().is_some()
+                        onclick=self.link.callback(|_| Message::ToggleTrace) />
+                    { format!(" Trace the last {} instructions", DEFAULT_TRACE_ENTRIES) }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.profiler.is_some()
+                        onclick=self.link.callback(|_| Message::ToggleProfiler) />
+                    { " Profile hot spots and subroutines" }
+                </label>
+                <label>
+                    <input type="checkbox"
+                        checked=self.nes.lint_enabled()
+                        onclick=self.link.callback(|_| Message::ToggleLint) />
+                    { " Warn about homebrew mistakes" }
+                </label>
+                <label>
+                    { patch_label }
+                    <input type="file" accept=".ips,.bps" onchange=on_patch />
+                </label>
+                { soft_patch }
+                <label>
+                    { "Battery save " }
+                    <button onclick=self.link.callback(|_| Message::ExportSram)>
+                        { "Export .sav" }
+                    </button>
+                    { " Import " }
+                    <input type="file" accept=".sav,.srm" onchange=on_sram />
+                </label>
+                <label>
+                    { "FCEUX savestate " }
+                    <input type="file" accept=".fc0,.fc1,.fc2,.fc3,.fc4,.fc5,.fc6,.fc7,.fc8,.fc9,.fcs"
+                        onchange=on_state />
+                </label>
+                { self.view_macros() }
+                { self.view_watch() }
+                { self.view_assembler() }
+                { self.view_ram_locks() }
+            </div>
+        }
+    }
+
+    fn view_ram_locks(&self) -> Html {
+        let on_timing = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => match select.value().as_str() {
+                "instruction" => vec![Message::SetLockTiming(LockTiming::Instruction)],
+                _ => vec![Message::SetLockTiming(LockTiming::Frame)],
+            },
+            _ => vec![],
+        });
+        let ram_locks = html! {
+            <label>
+                <input type="checkbox"
+                    checked=self.game_settings().ram_locks
+                    onclick=self.link.callback(|_| Message::ToggleRamLocks) />
+                { " Frozen addresses" }
+            </label>
+        };
+        // the stored locks are only shown and changed while they are in place
+        if !self.game_settings().ram_locks {
+            return html! { <div class="ram-locks">{ ram_locks }</div> };
+        }
+        html! {
+            <div class="ram-locks">
+                { ram_locks }
+                <label>
+                    { "Freeze $" }
+                    <input type="text"
+                        size="4"
+                        placeholder="0075"
+                        value=self.lock_address.clone()
+                        oninput=self.link.callback(|data: InputData| Message::SetLockAddress(data.value)) />
+                </label>
+                <label>
+                    { " at $" }
+                    <input type="text"
+                        size="2"
+                        placeholder="09"
+                        value=self.lock_value.clone()
+                        oninput=self.link.callback(|data: InputData| Message::SetLockValue(data.value)) />
+                </label>
+                <select onchange=on_timing>
+                    <option value="frame" selected=self.lock_timing == LockTiming::Frame>
+                        { "every frame" }
+                    </option>
+                    <option value="instruction" selected=self.lock_timing == LockTiming::Instruction>
+                        { "every instruction" }
+                    </option>
+                </select>
+                <button onclick=self.link.callback(|_| Message::LockRam)>{ "Freeze" }</button>
+                { for self.nes.ram_locks().iter().map(|lock| {
+                    let address = lock.addr;
+                    html! {
+                        <span class="ram-lock">
+                            { format!("${:04X} = ${:02X} ", lock.addr, lock.value) }
+                            <button onclick=self.link.callback(move |_| Message::UnlockRam(address))>
+                                { "Unfreeze" }
+                            </button>
+                        </span>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    fn view_assembler(&self) -> Html {
+        html! {
+            <div class="assembler">
+                <label>
+                    { "Assemble at $" }
+                    <input type="text"
+                        size="4"
+                        placeholder="8000"
+                        value=self.asm_address.clone()
+                        oninput=self.link.callback(|data: InputData| Message::SetAsmAddress(data.value)) />
+                </label>
+                <textarea
+                    rows="4"
+                    placeholder="NOP"
+                    value=self.asm_source.clone()
+                    oninput=self.link.callback(|data: InputData| Message::SetAsmSource(data.value)) />
+                <button onclick=self.link.callback(|_| Message::Assemble)>{ "Assemble" }</button>
+                <button
+                    disabled=self.nes.code_patches() == 0
+                    onclick=self.link.callback(|_| Message::UndoAssemble)>
+                    { format!("Undo ({})", self.nes.code_patches()) }
+                </button>
+            </div>
+        }
+    }
+
+    fn view_watch(&self) -> Html {
+        html! {
+            <WatchPanel rom=self.props.rom.clone()
+                onbuild=self.link.callback(Message::ReloadBuild)
+                ontoast=self.link.callback(Message::Toast) />
+        }
+    }
+
+    fn view_macros(&self) -> Html {
+        let record_label = if self.nes.macro_recording() {
+            "Stop recording"
+        } else {
+            "Record macro"
+        };
+        html! {
+            <div class="macros">
+                <button onclick=self.link.callback(|_| Message::ToggleMacroRecording)>
+                    { record_label }
+                </button>
+                { for self.macros.iter().enumerate().map(|(slot, input_macro)| html! {
+                    <span class="macro">
+                        { format!("{}: {} frames ", slot + 1, input_macro.frames.len()) }
+                        <button onclick=self.link.callback(move |_| Message::PlayMacro(slot))>
+                            { "Play" }
+                        </button>
+                        <button onclick=self.link.callback(move |_| Message::RemoveMacro(slot))>
+                            { "Remove" }
+                        </button>
+                    </span>
+                }) }
+            </div>
+        }
+    }
+
+    // everything the control bar and the settings offer, named the way they are there
+    fn commands(&self) -> Vec<Command> {
+        let command = |name: &str, message: Message| Command {
+            name: String::from(name),
+            binding: None,
+            message: message,
+        };
+        let toggle = |name: &str, on: bool, message: Message| {
+            let state = if on { "off" } else { "on" };
+            command(&format!("Turn {} {}", state, name), message)
+        };
+        let pause = if self.paused { "Resume" } else { "Pause" };
+        let settings = self.game_settings();
+        let mut commands = vec![
+            command(pause, Message::TogglePause),
+            command("Save battery ram", Message::Save),
+            command("Export battery save (.sav)", Message::ExportSram),
+            command("Show or hide settings", Message::ToggleSettings),
+            command("Accuracy: fast", Message::SetAccuracy(Accuracy::Fast)),
+            command(
+                "Accuracy: balanced",
+                Message::SetAccuracy(Accuracy::Balanced),
+            ),
+            command("Accuracy: cycle", Message::SetAccuracy(Accuracy::Cycle)),
+            command("Console: NES", Message::SetConsole(Console::Nes)),
+            command("Console: Famicom", Message::SetConsole(Console::Famicom)),
+            command(
+                "Console: AV Famicom",
+                Message::SetConsole(Console::AvFamicom),
+            ),
+            toggle(
+                "sprite flicker reduction",
+                !settings.sprite_limit,
+                Message::ToggleSpriteLimit,
+            ),
+            toggle(
+                "background and sprite tint",
+                self.nes.debug_tint(),
+                Message::ToggleDebugTint,
+            ),
+            toggle(
+                "settings for this game",
+                self.game_profile.is_some(),
+                Message::ToggleProfile,
+            ),
+            toggle(
+                "frozen addresses",
+                settings.ram_locks,
+                Message::ToggleRamLocks,
+            ),
+            toggle(
+                "break on stack overflow/underflow",
+                self.nes.cpu.break_on_stack_fault,
+                Message::ToggleBreakOnStackFault,
+            ),
+            toggle("stats", self.show_stats, Message::ToggleStats),
+            toggle("FPS and frame times", self.show_perf, Message::TogglePerf),
+            toggle(
+                "sprites per scanline and scroll splits",
+                self.show_frame_stats,
+                Message::ToggleFrameStats,
+            ),
+            toggle("CHR banks", self.show_chr_viewer, Message::ToggleChrViewer),
+            toggle(
+                "mapper registers",
+                self.show_mapper_state,
+                Message::ToggleMapperState,
+            ),
+            toggle(
+                "instruction trace",
+                self.nes.trace_log().is_some(),
+                Message::ToggleTrace,
+            ),
+            toggle("profiler", self.profiler.is_some(), Message::ToggleProfiler),
+            toggle("lint", self.nes.lint_enabled(), Message::ToggleLint),
+            command(
+                if self.nes.macro_recording() {
+                    "Stop recording macro"
+                } else {
+                    "Record macro"
+                },
+                Message::ToggleMacroRecording,
+            ),
+        ];
+        for &scanlines in OVERCLOCK_SCANLINES.iter() {
+            commands.push(match scanlines {
+                0 => command("Overclock: off", Message::SetOverclock(0)),
+                _ => command(
+                    &format!("Overclock: {} scanlines", scanlines),
+                    Message::SetOverclock(scanlines),
+                ),
+            });
+        }
+        if self.nes.trace_log().is_some() {
+            commands.push(command("Copy trace", Message::CopyTrace));
+            commands.push(command(
+                "Download trace",
+                Message::DownloadTrace(ExportFormat::Text),
+            ));
+        }
+        for slot in 0..self.macros.len() {
+            commands.push(Command {
+                name: format!("Play macro {}", slot + 1),
+                binding: Some((slot + 1).to_string()),
+                message: Message::PlayMacro(slot),
+            });
+        }
+        commands
+    }
+
+    fn view_palette(&self) -> Html {
+        if !self.show_palette {
+            return html! {};
+        }
+        let commands = self
+            .commands()
+            .into_iter()
+            .map(|command| Entry {
+                name: command.name,
+                binding: command.binding,
+            })
+            .collect::<Vec<_>>();
+        html! {
+            <Palette commands=commands
+                onrun=self.link.callback(Message::RunCommand)
+                onclose=self.link.callback(|_| Message::TogglePalette) />
+        }
+    }
+
+    fn view_stats(&self) -> Html {
+        if !self.show_stats {
+            return html! {};
+        }
+
+        let stats = self.nes.stats();
+        let lines = vec![
+            format!("frame {}", stats.frames),
+            format!("cpu cycles {}", stats.cpu_cycles),
+            format!("instructions {}", stats.instructions),
+            format!("scanline {} dot {}", stats.scanline, stats.dot),
+            format!(
+                "audio {} samples/frame, {} dropped",
+                stats.audio_samples_last_frame, stats.audio_samples_dropped
+            ),
+        ];
+        html! {
+            <div class="stats-overlay">
+                { for lines.into_iter().map(|line| html! { <div>{ line }</div> }) }
+            </div>
+        }
+    }
+
+    // both pattern tables as the selected scanline saw them and every bank switch of
+    // the last frame
+    fn view_chr_viewer(&self) -> Html {
+        if !self.show_chr_viewer {
+            return html! {};
+        }
+
+        let on_scanline = self
+            .link
+            .batch_callback(|data: InputData| match data.value.parse() {
+                Ok(scanline) => vec![Message::SetChrScanline(scanline)],
+                Err(_) => vec![],
+            });
+        let pages = |banks: &[usize]| {
+            banks
+                .iter()
+                .map(|page| format!("{:02X}", page))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let stats = self.nes.frame_stats();
+        html! {
+            <div class="chr-viewer">
+                <canvas ref={self.chr_ref.clone()} />
+                <label>
+                    { format!("Scanline {} ", self.chr_scanline) }
+                    <input type="range" min="0" max=(SCREEN_HEIGHT - 1).to_string()
+                        value=self.chr_scanline.to_string() oninput=on_scanline />
+                </label>
+                <div>{ format!("now: {}", pages(&self.nes.chr_banks())) }</div>
+                { for stats.chr_banks.iter().map(|(scanline, banks)| html! {
+                    <div>{ format!("line {:3}: {}", scanline, pages(banks)) }</div>
+                }) }
+            </div>
+        }
+    }
+
+    // the board's banks, irq counter and mirroring as of the last frame
+    fn view_mapper_state(&self) -> Html {
+        if !self.show_mapper_state {
+            return html! {};
+        }
+
+        html! {
+            <pre class="mapper-state">{ format!("{:#}", self.nes.mapper_debug_state()) }</pre>
+        }
+    }
+
+    fn view_profiler(&self) -> Html {
+        if self.profiler.is_none() {
+            return html! {};
+        }
+        html! {
+            <ProfilerPanel report=self.profile.clone()
+                onreset=self.link.callback(|_| Message::ResetProfiler)
+                ondownload=self.link.callback(Message::DownloadProfile)
+                onsymbols=self.link.callback(Message::LoadSymbols) />
+        }
+    }
+
+    fn view_toasts(&self) -> Html {
+        if self.toasts.is_empty() {
+            return html! {};
+        }
+
+        html! {
+            <div class="toasts">
+                { for self.toasts.iter().map(|(text, _)| html! { <div class="toast">{ text }</div> }) }
+            </div>
+        }
+    }
+
+    fn view_perf(&self) -> Html {
+        if !self.show_perf {
+            return html! {};
+        }
+
+        let timing = self.perf.last_timing().unwrap_or(FrameTiming {
+            emulation_ms: 0.0,
+            render_ms: 0.0,
+        });
+        let bar_height = |ms: f64| (ms / PERF_GRAPH_MS * PERF_GRAPH_HEIGHT).min(PERF_GRAPH_HEIGHT);
+        let bars = self.perf.timings().map(|timing| {
+            html! {
+                <div class="perf-bar">
+                    <div class="perf-render"
+                        style=format!("height: {:.1}px", bar_height(timing.render_ms)) />
+                    <div class="perf-emulation"
+                        style=format!("height: {:.1}px", bar_height(timing.emulation_ms)) />
+                </div>
+            }
+        });
+
+        html! {
+            <div class="perf-overlay">
+                <div>{ format!("{:.1} fps", self.perf.fps()) }</div>
+                <div>
+                    { format!("emu {:.1} ms / render {:.1} ms", timing.emulation_ms, timing.render_ms) }
+                </div>
+                <div>{ format!("audio underruns {}", self.perf.audio_underruns()) }</div>
+                <div class="perf-graph" style=format!("height: {}px", PERF_GRAPH_HEIGHT)>
+                    { for bars }
+                </div>
+            </div>
+        }
+    }
+
+    fn view_touch_controls(&self) -> Html {
+        let show_touch_controls = match self.touch_controls {
+            TouchControls::Auto => is_mobile_user_agent(),
+            TouchControls::Always => true,
+            TouchControls::Never => false,
+        };
+        if !show_touch_controls {
+            return html! {};
+        }
+
+        // a single listener on the overlay hit-tests every active touch, so sliding a
+        // thumb across the d-pad and holding a button with the other hand both work
+        let ontouch = self.link.callback(Message::Touch);
+        html! {
+            <div class="touch-controls"
+                ontouchstart=ontouch.clone()
+                ontouchmove=ontouch.clone()
+                ontouchend=ontouch.clone()
+                ontouchcancel=ontouch.clone()>
+                <div class="touch-dpad">
+                    <div class="touch-button up" data-button="up" />
+                    <div class="touch-button left" data-button="left" />
+                    <div class="touch-button right" data-button="right" />
+                    <div class="touch-button down" data-button="down" />
+                </div>
+                <div class="touch-system">
+                    <div class="touch-button select" data-button="select">{ "SELECT" }</div>
+                    <div class="touch-button start" data-button="start">{ "START" }</div>
+                </div>
+                <div class="touch-actions">
+                    <div class="touch-button b" data-button="b">{ "B" }</div>
+                    <div class="touch-button a" data-button="a">{ "A" }</div>
+                </div>
+            </div>
+        }
+    }
+
+    fn handle_touch(&mut self, event: TouchEvent) {
+        // keep the browser from scrolling or zooming while playing
+        event.prevent_default();
+
+        let document = match web_sys::window().and_then(|window| window.document()) {
+            Some(document) => document,
+            None => return,
+        };
+
+        let mut buttons = JoypadButton::empty();
+        let touches = event.touches();
+        for i in 0..touches.length() {
+            let touch = match touches.get(i) {
+                Some(touch) => touch,
+                None => continue,
+            };
+            let element =
+                document.element_from_point(touch.client_x() as f32, touch.client_y() as f32);
+            if let Some(name) = element.and_then(|element| element.get_attribute("data-button")) {
+                buttons |= touch_button(&name);
+            }
+        }
+
+        *self.buttons.borrow_mut() = buttons;
     }
 
     pub fn update_texture(&self, width: i32, height: i32, bytes: Vec<u8>) {
@@ -206,6 +1897,28 @@ impl Screen {
         .expect("upload texture data error");
     }
 
+    // textures are flipped on upload, so rows are counted from the bottom
+    fn update_texture_region(gl: &GL, frame: &Frame, rect: &Rect) {
+        let mut bytes = Vec::with_capacity(rect.width * rect.height * 4);
+        for y in rect.y..rect.y + rect.height {
+            bytes.extend_from_slice(&frame.row(y)[rect.x * 4..(rect.x + rect.width) * 4]);
+        }
+        let js_data = js_sys::Uint8Array::from(bytes.as_slice());
+
+        gl.tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+            GL::TEXTURE_2D,
+            0,
+            rect.x as i32,
+            (frame.height - rect.y - rect.height) as i32,
+            rect.width as i32,
+            rect.height as i32,
+            GL::RGBA,
+            GL::UNSIGNED_BYTE,
+            Some(js_data.as_ref()),
+        )
+        .expect("upload texture data error");
+    }
+
     fn init_shader(&self, shader_type: u32, shader_code: &str) -> Option<WebGlShader> {
         let gl = self.gl.as_ref().expect("get gl context error");
         let shader = gl.create_shader(shader_type).unwrap();
@@ -222,12 +1935,13 @@ impl Screen {
         gl.bind_texture(GL::TEXTURE_2D, texture.as_ref());
         gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
         gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
-        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::NEAREST as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::NEAREST as i32);
         let mut data: Vec<u8> = vec![0u8; width as usize * height as usize * 4];
 
         for i in 0..width {
             for j in 0..height {
-                let index = ((j * height + i) * 4) as usize;
+                let index = ((j * width + i) * 4) as usize;
                 data[index] = i as u8;
                 data[index + 1] = ((i + j) / 2) as u8;
                 data[index + 2] = j as u8;
@@ -242,7 +1956,10 @@ impl Screen {
 
     fn init(&mut self) {
         let gl = self.gl.as_ref().expect("gl init error");
-        self.cpu.reset();
+        self.nes.reset();
+
+        // frames are stored top row first
+        gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 1);
 
         // VBO
         let vertices: Vec<f32> = vec![
@@ -302,16 +2019,215 @@ impl Screen {
         ));
 
         // Textures
-        let texture = self.create_texture(32, 32);
+        let texture = self.create_texture(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
         self._tex = texture;
 
         gl.use_program(None);
     }
 
-    fn render_loop(&mut self, ts: f64) {
+    // 1KB pages can switch mid-frame, so the viewer draws the banks of the chosen
+    // scanline and falls back to the current ones before the first frame is done
+    fn draw_chr_viewer(&self) {
+        let canvas = match self.chr_ref.cast::<HtmlCanvasElement>() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let context: CanvasRenderingContext2d = match canvas.get_context("2d") {
+            Ok(Some(context)) => context.dyn_into().unwrap(),
+            _ => return,
+        };
+        canvas.set_width(PATTERN_TABLE_SIZE as u32 * 2);
+        canvas.set_height(PATTERN_TABLE_SIZE as u32);
+
+        let banks = self
+            .nes
+            .frame_stats()
+            .chr_banks_at(self.chr_scanline)
+            .unwrap_or_else(|| self.nes.chr_banks());
+        for table in 0..2 {
+            let frame = self.nes.pattern_table(table, &banks, 0);
+            let image = ImageData::new_with_u8_clamped_array(
+                Clamped(&frame.data),
+                PATTERN_TABLE_SIZE as u32,
+            );
+            if let Ok(image) = image {
+                let x = (table * PATTERN_TABLE_SIZE) as f64;
+                let _ = context.put_image_data(&image, x, 0.0);
+            }
+        }
+    }
+
+    fn refresh_profile(&mut self) {
+        self.profile = self
+            .profiler
+            .as_ref()
+            .map(|profiler| Rc::new(profiler.report(PROFILE_ROWS, self.symbols.as_ref())));
+    }
+
+    // the trace as text to the clipboard, or to a file in `format`, with a toast either way
+    fn export_trace(&mut self, format: Option<ExportFormat>) -> ShouldRender {
+        let trace_log = match self.nes.trace_log() {
+            Some(trace_log) => trace_log,
+            None => return false,
+        };
+        let rom_name = &self.props.rom_name;
+        let result = match format {
+            None => export::copy_text(&trace_log.dump()),
+            Some(ExportFormat::Text) => {
+                export::download_text(&format!("{}.trace.txt", rom_name), &trace_log.dump())
+            }
+            Some(ExportFormat::Json) => export::download_text(
+                &format!("{}.trace.json", rom_name),
+                &format!("{}", trace_log.to_json()),
+            ),
+            Some(ExportFormat::Csv) => {
+                export::download_text(&format!("{}.trace.csv", rom_name), &trace_log.to_csv())
+            }
+        };
+        let toast = match result {
+            Ok(()) => format!("Exported {} instructions", trace_log.len()),
+            Err(_) => String::from("Could not export the trace"),
+        };
+        self.toasts.push((toast, now() + TOAST_MS));
+        true
+    }
+
+    // every pc and subroutine the profiler counted, not only the rows on screen. csv is
+    // one file per table
+    fn export_profile(&mut self, format: ExportFormat) -> ShouldRender {
+        let report = match &self.profiler {
+            Some(profiler) => profiler.report(usize::MAX, self.symbols.as_ref()),
+            None => return false,
+        };
+        let rom_name = &self.props.rom_name;
+        let result = match format {
+            ExportFormat::Csv => export::download_text(
+                &format!("{}.hotspots.csv", rom_name),
+                &report.hot_spots_csv(),
+            )
+            .and_then(|()| {
+                export::download_text(
+                    &format!("{}.subroutines.csv", rom_name),
+                    &report.subroutines_csv(),
+                )
+            })
+            .and_then(|()| {
+                export::download_text(&format!("{}.opcodes.csv", rom_name), &report.opcodes_csv())
+            }),
+            _ => export::download_text(
+                &format!("{}.profile.json", rom_name),
+                &format!("{:#}", report.to_json()),
+            ),
+        };
+        if result.is_err() {
+            let toast = String::from("Could not export the profile");
+            self.toasts.push((toast, now() + TOAST_MS));
+            return true;
+        }
+        false
+    }
+
+    // true when the visible toasts changed
+    fn update_toasts(&mut self, ts: f64) -> bool {
+        let count = self.toasts.len();
+        self.toasts.retain(|(_, expires)| *expires > ts);
+        let mut changed = self.toasts.len() != count;
+
+        for event in self.nes.take_events() {
+            if let Some(text) = toast_text(&event) {
+                self.toasts.push((text, ts + TOAST_MS));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // runs the console for `ms` of real time, true when it stopped on a stack fault
+    fn emulate(&mut self, ms: f64) -> bool {
+        self.cycle_budget += ms * CPU_CLOCK_RATE / 1000.0;
+        let start_cycles = self.nes.cpu.bus.cycles();
+        let target_cycles = start_cycles + self.cycle_budget.max(0.0) as usize;
+        let loud = self.microphone.as_mut().map_or(false, Microphone::is_loud);
+        self.nes.set_microphone(self.microphone_key || loud);
+        while self.nes.cpu.bus.cycles() < target_cycles {
+            self.nes.step();
+            if let Some((fault, pc)) = self.nes.cpu.take_stack_break() {
+                self.paused = true;
+                self.break_reason = Some(format!("Stack {:?} at ${:04X}", fault, pc));
+                break;
+            }
+        }
+        // whatever the last instruction overshot is paid back on the next frame
+        self.cycle_budget -= (self.nes.cpu.bus.cycles() - start_cycles) as f64;
+        if self.paused {
+            self.cycle_budget = 0.0;
+        }
+        self.frame += 1;
+        self.paused
+    }
+
+    // a hidden page gets no animation frames and no audio, and runs slowly or not at all
+    // depending on the background setting. shown again, the render loop picks up from now
+    fn apply_visibility(&mut self) {
+        if self.hidden {
+            self._render_loop = None;
+            if let Some(audio) = &self.audio {
+                audio.suspend();
+            }
+            self.background_tick = match self.background {
+                Background::Pause => None,
+                Background::Slow => {
+                    let callback = self.link.callback(|_| Message::BackgroundTick);
+                    Some(IntervalService::spawn(BACKGROUND_FRAME_INTERVAL, callback))
+                }
+            };
+        } else {
+            self.background_tick = None;
+            if let Some(audio) = &self.audio {
+                audio.resume();
+            }
+            self.last_render_ts = None;
+            self.perf.reset_clock();
+            let link = self.link.clone();
+            self._render_loop = Some(request_animation_frame(move |time| {
+                link.send_message(Message::Render(time))
+            }));
+        }
+    }
+
+    fn render_loop(&mut self, ts: f64) -> ShouldRender {
         // use web_sys::console;
         // console::log_1(&format!("ts: {}", ts).into());
 
+        // the overlays follow the emulation every frame
+        let mut should_render =
+            (self.show_stats || self.show_perf || self.show_chr_viewer || self.show_mapper_state)
+                && !self.paused;
+        if self.profiler.is_some() && !self.paused && self.frame % PROFILE_REFRESH_FRAMES == 0 {
+            self.refresh_profile();
+            should_render = true;
+        }
+        if self.paused {
+            self.perf.reset_clock();
+        }
+
+        let elapsed = self
+            .last_render_ts
+            .map_or(0.0, |last| (ts - last).min(MAX_ELAPSED_MS));
+        self.last_render_ts = Some(ts);
+
+        let emulation_start = now();
+        if !self.paused && self.emulate(elapsed) {
+            should_render = true;
+        }
+        if self.update_toasts(ts) {
+            should_render = true;
+        }
+        if self.show_chr_viewer {
+            self.draw_chr_viewer();
+        }
+        let render_start = now();
+
         let gl = self.gl.as_ref().expect("gl init error");
         let program = self._screen_program.as_ref().expect("screen program error");
         let buffers = self._screen_buffers.as_ref().expect("screen buffers error");
@@ -322,9 +2238,25 @@ impl Screen {
         gl.use_program(program.program.as_ref());
         gl.active_texture(GL::TEXTURE0);
         gl.bind_texture(GL::TEXTURE_2D, self._tex.as_ref());
+        if let Some(slot) = self.frames.take_latest() {
+            let frame = slot.frame();
+            if !self.texture_uploaded {
+                let screen = Rect::new(0, 0, frame.width, frame.height);
+                Screen::update_texture_region(gl, frame, &screen);
+                self.texture_uploaded = true;
+            } else {
+                for rect in frame.dirty_rects() {
+                    Screen::update_texture_region(gl, frame, rect);
+                }
+            }
+        }
 
         gl.uniform1f(program.u_time.as_ref(), ts as f32);
-        gl.uniform2i(program.u_time.as_ref(), 320, 320);
+        gl.uniform2i(
+            program.u_time.as_ref(),
+            SCREEN_WIDTH as i32 * 2,
+            SCREEN_HEIGHT as i32 * 2,
+        );
 
         let size_of_f32 = mem::size_of::<f32>() as i32;
         gl.bind_buffer(GL::ARRAY_BUFFER, buffers.vbo.as_ref());
@@ -357,25 +2289,17 @@ impl Screen {
         gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, None);
         gl.use_program(None);
 
-        let frame = self.frame;
-        let mut cycles = 0;
-        loop {
-            self.cpu.interprect_with_callback(move |cpu| {
-                // trace::trace(cpu, &frame);
-                let mut rng = rand::thread_rng();
-                cpu.bus.mem_write(0x00FE, rng.gen_range(1, 16));
-            });
-            cycles += 1;
-            if cycles > 240 {
-                break
-            }
+        if let Some(audio) = &self.audio {
+            self.perf
+                .set_audio_underruns(audio.ring().borrow().underruns());
+        }
+        if !self.paused {
+            let timing = FrameTiming {
+                emulation_ms: render_start - emulation_start,
+                render_ms: now() - render_start,
+            };
+            self.perf.record_frame(ts, timing);
         }
-        self.frame += 1;
-        // use web_sys::console;
-        // console::log_1(&format!("frame: {}", frame).into());
-
-        let bytes = render(&mut self.cpu);
-        self.update_texture(32, 32, bytes);
 
         let handle = {
             let link = self.link.clone();
@@ -383,5 +2307,6 @@ impl Screen {
         };
 
         self._render_loop = Some(handle);
+        should_render
     }
 }
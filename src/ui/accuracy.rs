@@ -0,0 +1,88 @@
+use gloo::render::{request_animation_frame, AnimationFrame};
+use yew::{html, Component, ComponentLink, Html, ShouldRender};
+
+use feuernes_core::test_roms::TEST_ROMS;
+
+pub enum Message {
+    RunNext,
+}
+
+// runs the embedded test roms one per animation frame, so the page shows each verdict as
+// it comes in instead of freezing until all of them are done
+pub struct Accuracy {
+    link: ComponentLink<Self>,
+    // the verdicts so far, in the order of TEST_ROMS
+    results: Vec<Result<(), String>>,
+    _next: Option<AnimationFrame>,
+}
+
+impl Component for Accuracy {
+    type Message = Message;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut accuracy = Accuracy {
+            link: link,
+            results: Vec::new(),
+            _next: None,
+        };
+        accuracy.schedule();
+        accuracy
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::RunNext => {
+                if let Some(test) = TEST_ROMS.get(self.results.len()) {
+                    self.results.push(test.run());
+                }
+                self.schedule();
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let passed = self.results.iter().filter(|result| result.is_ok()).count();
+        html! {
+            <div class="accuracy">
+                <p>{ format!("{} of {} test roms passed", passed, TEST_ROMS.len()) }</p>
+                <table>
+                    { for TEST_ROMS.iter().enumerate().map(|(i, test)| self.view_test(i, test.name)) }
+                </table>
+            </div>
+        }
+    }
+}
+
+impl Accuracy {
+    fn schedule(&mut self) {
+        self._next = if self.results.len() < TEST_ROMS.len() {
+            let link = self.link.clone();
+            Some(request_animation_frame(move |_| {
+                link.send_message(Message::RunNext)
+            }))
+        } else {
+            None
+        };
+    }
+
+    fn view_test(&self, i: usize, name: &str) -> Html {
+        let (class, verdict, reason) = match self.results.get(i) {
+            Some(Ok(())) => ("pass", "PASS", String::new()),
+            Some(Err(reason)) => ("fail", "FAIL", reason.clone()),
+            None => ("pending", "...", String::new()),
+        };
+        html! {
+            <tr class=class>
+                <td>{ name }</td>
+                <td class="verdict">{ verdict }</td>
+                <td>{ reason }</td>
+            </tr>
+        }
+    }
+}
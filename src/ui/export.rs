@@ -0,0 +1,47 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+// hands `text` to the browser as a file download
+pub fn download_text(file_name: &str, text: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(text));
+    let blob = Blob::new_with_str_sequence_and_options(
+        &parts,
+        BlobPropertyBag::new().type_("text/plain"),
+    )?;
+    download_blob(file_name, &blob)
+}
+
+// the same for binary files, battery saves and such
+pub fn download_bytes(file_name: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let blob = Blob::new_with_u8_array_sequence_and_options(
+        &parts,
+        BlobPropertyBag::new().type_("application/octet-stream"),
+    )?;
+    download_blob(file_name, &blob)
+}
+
+fn download_blob(file_name: &str, blob: &Blob) -> Result<(), JsValue> {
+    let url = Url::create_object_url_with_blob(blob)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let anchor: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    Url::revoke_object_url(&url)
+}
+
+// navigator.clipboard is behind web-sys' unstable apis, so it is called through js
+pub fn copy_text(text: &str) -> Result<(), JsValue> {
+    let navigator = web_sys::window()
+        .map(|window| window.navigator())
+        .ok_or_else(|| JsValue::from_str("no window"))?;
+    let clipboard = js_sys::Reflect::get(&navigator, &JsValue::from_str("clipboard"))?;
+    let write_text: js_sys::Function =
+        js_sys::Reflect::get(&clipboard, &JsValue::from_str("writeText"))?.dyn_into()?;
+    write_text.call1(&clipboard, &JsValue::from_str(text))?;
+    Ok(())
+}
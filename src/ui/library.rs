@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yew::{html, ChangeData, Component, ComponentLink, Html, ShouldRender};
+
+use super::storage::{RomStore, BUILTIN_ROM};
+use super::Route;
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::compat::{self, CompatEntry};
+use feuernes_core::patch::PatchFormat;
+
+pub enum Message {
+    Upload(Vec<File>),
+    Loaded(FileData),
+    Remove(String),
+    RemovePatch(String),
+}
+
+pub struct Library {
+    link: ComponentLink<Self>,
+    store: RomStore,
+    roms: Vec<String>,
+    // what the compatibility database knows about the roms, by name
+    compat: HashMap<String, CompatEntry>,
+    // ips and bps patches, applied to the rom they were made for when it starts
+    patches: Vec<String>,
+    tasks: Vec<ReaderTask>,
+    // why the last upload was refused
+    error: Option<String>,
+}
+
+impl Component for Library {
+    type Message = Message;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let store = RomStore::new();
+        let patches = store.list_patches();
+        let mut library = Library {
+            link: link,
+            store: store,
+            roms: Vec::new(),
+            compat: HashMap::new(),
+            patches: patches,
+            tasks: Vec::new(),
+            error: None,
+        };
+        library.refresh_roms();
+        library
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::Upload(files) => {
+                for file in files {
+                    let callback = self.link.callback(Message::Loaded);
+                    if let Ok(task) = ReaderService::read_file(file, callback) {
+                        self.tasks.push(task);
+                    }
+                }
+                false
+            }
+            Message::Loaded(file) if PatchFormat::detect(&file.content).is_some() => {
+                self.store.save_patch(&file.name, &file.content);
+                self.patches = self.store.list_patches();
+                self.error = None;
+                true
+            }
+            Message::Loaded(file) => {
+                // only keep roms the emulator can actually start
+                match Cartridge::new(&file.content) {
+                    Ok(_) => {
+                        self.store.save(&file.name, &file.content);
+                        self.refresh_roms();
+                        self.error = None;
+                    }
+                    Err(reason) => self.error = Some(format!("{}: {}", file.name, reason)),
+                }
+                true
+            }
+            Message::Remove(name) => {
+                self.store.remove(&name);
+                self.refresh_roms();
+                true
+            }
+            Message::RemovePatch(name) => {
+                self.store.remove_patch(&name);
+                self.patches = self.store.list_patches();
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let onchange = self.link.batch_callback(|data| match data {
+            ChangeData::Files(files) => {
+                let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+                vec![Message::Upload(files)]
+            }
+            _ => vec![],
+        });
+
+        html! {
+            <div class="library">
+                <h2>{ "Library" }</h2>
+                {
+                    match &self.error {
+                        Some(error) => html! { <p class="error">{ error }</p> },
+                        None => html! {},
+                    }
+                }
+                <ul class="library-list">
+                    { for self.roms.iter().map(|name| self.view_rom(name)) }
+                </ul>
+                { self.view_patches() }
+                <label class="library-upload">
+                    { "Add ROM or patch" }
+                    <input type="file" accept=".nes,.ips,.bps" multiple=true onchange=onchange />
+                </label>
+            </div>
+        }
+    }
+}
+
+impl Library {
+    fn refresh_roms(&mut self) {
+        self.roms = self.store.list();
+        let store = &self.store;
+        self.compat = self
+            .roms
+            .iter()
+            .filter_map(|name| {
+                let cartridge = Cartridge::new(&store.load(name)?).ok()?;
+                Some((name.clone(), compat::lookup(cartridge.crc32)?))
+            })
+            .collect();
+    }
+
+    fn view_rom(&self, name: &str) -> Html {
+        let remove = if name == BUILTIN_ROM {
+            html! {}
+        } else {
+            let rom = String::from(name);
+            let onclick = self.link.callback(move |_| Message::Remove(rom.clone()));
+            html! { <button class="library-remove" onclick=onclick>{ "Remove" }</button> }
+        };
+
+        let badge = match self.compat.get(name) {
+            Some(entry) => {
+                let class = format!("compat-badge compat-{}", entry.status.name());
+                html! { <span class=class title=entry.note.clone()>{ entry.status.label() }</span> }
+            }
+            None => html! {},
+        };
+
+        html! {
+            <li class="library-item">
+                <a href=Route::Emulator(String::from(name)).to_hash()>{ name }</a>
+                { badge }
+                { remove }
+            </li>
+        }
+    }
+
+    fn view_patches(&self) -> Html {
+        if self.patches.is_empty() {
+            return html! {};
+        }
+        html! {
+            <>
+                <h3>{ "Patches" }</h3>
+                <ul class="library-list">
+                    { for self.patches.iter().map(|name| self.view_patch(name)) }
+                </ul>
+            </>
+        }
+    }
+
+    fn view_patch(&self, name: &str) -> Html {
+        let patch = String::from(name);
+        let onclick = self
+            .link
+            .callback(move |_| Message::RemovePatch(patch.clone()));
+        html! {
+            <li class="library-item">
+                { name }
+                <button class="library-remove" onclick=onclick>{ "Remove" }</button>
+            </li>
+        }
+    }
+}
@@ -0,0 +1,175 @@
+#[cfg(feature = "accuracy")]
+pub mod accuracy;
+pub mod export;
+pub mod library;
+pub mod palette;
+pub mod profile;
+pub mod profiler;
+pub mod storage;
+pub mod survey;
+pub mod watch;
+
+use gloo::events::EventListener;
+use std::rc::Rc;
+use yew::{html, Component, ComponentLink, Html, ShouldRender};
+
+#[cfg(feature = "accuracy")]
+use self::accuracy::Accuracy;
+use self::library::Library;
+use self::storage::RomStore;
+use self::survey::Survey;
+use crate::render::web_renderer::Screen;
+
+const EMULATOR_ROUTE: &str = "#/play/";
+const SURVEY_ROUTE: &str = "#/survey";
+#[cfg(feature = "accuracy")]
+const ACCURACY_ROUTE: &str = "#/accuracy";
+
+#[derive(Clone, PartialEq)]
+pub enum Route {
+    Library,
+    Emulator(String),
+    Survey,
+    #[cfg(feature = "accuracy")]
+    Accuracy,
+}
+
+impl Route {
+    pub fn from_hash(hash: &str) -> Self {
+        #[cfg(feature = "accuracy")]
+        {
+            if hash == ACCURACY_ROUTE {
+                return Route::Accuracy;
+            }
+        }
+        if hash == SURVEY_ROUTE {
+            return Route::Survey;
+        }
+        if hash.starts_with(EMULATOR_ROUTE) {
+            let name = js_sys::decode_uri_component(&hash[EMULATOR_ROUTE.len()..])
+                .map(String::from)
+                .unwrap_or_default();
+            if !name.is_empty() {
+                return Route::Emulator(name);
+            }
+        }
+        Route::Library
+    }
+
+    pub fn to_hash(&self) -> String {
+        match self {
+            Route::Library => String::from("#/"),
+            Route::Emulator(name) => format!(
+                "{}{}",
+                EMULATOR_ROUTE,
+                String::from(js_sys::encode_uri_component(name))
+            ),
+            Route::Survey => String::from(SURVEY_ROUTE),
+            #[cfg(feature = "accuracy")]
+            Route::Accuracy => String::from(ACCURACY_ROUTE),
+        }
+    }
+
+    fn current() -> Self {
+        let hash = web_sys::window()
+            .and_then(|window| window.location().hash().ok())
+            .unwrap_or_default();
+        Route::from_hash(&hash)
+    }
+}
+
+pub enum Message {
+    HashChanged,
+}
+
+pub struct App {
+    route: Route,
+    rom: Option<Rc<Vec<u8>>>,
+    _hash_listener: Option<EventListener>,
+}
+
+impl Component for App {
+    type Message = Message;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let hash_listener = web_sys::window().map(|window| {
+            EventListener::new(&window, "hashchange", move |_| {
+                link.send_message(Message::HashChanged)
+            })
+        });
+
+        let mut app = App {
+            route: Route::Library,
+            rom: None,
+            _hash_listener: hash_listener,
+        };
+        app.navigate(Route::current());
+        app
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::HashChanged => self.navigate(Route::current()),
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let page = match (&self.route, &self.rom) {
+            (Route::Library, _) => html! { <Library /> },
+            (Route::Emulator(name), Some(rom)) => html! {
+                <Screen rom_name=name.clone() rom=rom.clone() />
+            },
+            (Route::Emulator(name), None) => html! {
+                <p class="error">{ format!("ROM \"{}\" is not in the library.", name) }</p>
+            },
+            (Route::Survey, _) => html! { <Survey /> },
+            #[cfg(feature = "accuracy")]
+            (Route::Accuracy, _) => html! { <Accuracy /> },
+        };
+
+        html! {
+            <div class="app">
+                <header class="app-header">
+                    <a href=Route::Library.to_hash()>{ "FeuerNES" }</a>
+                    <a class="survey-link" href=Route::Survey.to_hash()>{ "Survey" }</a>
+                    { view_accuracy_link() }
+                </header>
+                <main class="app-page">{ page }</main>
+            </div>
+        }
+    }
+}
+
+#[cfg(feature = "accuracy")]
+fn view_accuracy_link() -> Html {
+    html! { <a class="accuracy-link" href=Route::Accuracy.to_hash()>{ "Accuracy" }</a> }
+}
+
+#[cfg(not(feature = "accuracy"))]
+fn view_accuracy_link() -> Html {
+    html! {}
+}
+
+impl App {
+    pub fn start() {
+        yew::start_app::<App>();
+    }
+
+    fn navigate(&mut self, route: Route) -> ShouldRender {
+        if route == self.route && self.rom.is_some() {
+            return false;
+        }
+
+        self.rom = match &route {
+            Route::Emulator(name) => RomStore::new().load(name).map(Rc::new),
+            _ => None,
+        };
+        self.route = route;
+        true
+    }
+}
@@ -0,0 +1,142 @@
+use web_sys::{HtmlElement, KeyboardEvent};
+use yew::{
+    html, Callback, Component, ComponentLink, Html, InputData, NodeRef, Properties, ShouldRender,
+};
+
+// a command the palette lists, by the name to search for and the key that does the same,
+// if one does. what it does is up to whoever opened the palette
+#[derive(Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub binding: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct PaletteProps {
+    pub commands: Vec<Entry>,
+    // the index into `commands` of the one picked
+    pub onrun: Callback<usize>,
+    // escape or ctrl+k in the search field
+    pub onclose: Callback<()>,
+}
+
+pub enum Message {
+    SetQuery(String),
+    Run(usize),
+    RunFirst,
+    Close,
+}
+
+// the command palette, opened with ctrl+k. the query starts empty every time it opens
+pub struct Palette {
+    props: PaletteProps,
+    link: ComponentLink<Self>,
+    query: String,
+    input_ref: NodeRef,
+}
+
+impl Component for Palette {
+    type Message = Message;
+    type Properties = PaletteProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Palette {
+            props: props,
+            link: link,
+            query: String::new(),
+            input_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::SetQuery(query) => {
+                self.query = query;
+                true
+            }
+            Message::Run(index) => {
+                self.props.onrun.emit(index);
+                false
+            }
+            Message::RunFirst => {
+                let first = self
+                    .props
+                    .commands
+                    .iter()
+                    .position(|command| matches_query(&command.name, &self.query));
+                if let Some(index) = first {
+                    self.props.onrun.emit(index);
+                }
+                false
+            }
+            Message::Close => {
+                self.props.onclose.emit(());
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+        self.props = props;
+        true
+    }
+
+    // the search field takes the focus as the palette opens
+    fn rendered(&mut self, first_render: bool) {
+        if first_render {
+            if let Some(input) = self.input_ref.cast::<HtmlElement>() {
+                let _ = input.focus();
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        // typing in the search field is not for the macro keys
+        let onkeydown = self.link.batch_callback(|event: KeyboardEvent| {
+            event.stop_propagation();
+            if is_palette_key(&event) || event.key() == "Escape" {
+                event.prevent_default();
+                vec![Message::Close]
+            } else if event.key() == "Enter" {
+                vec![Message::RunFirst]
+            } else {
+                vec![]
+            }
+        });
+        html! {
+            <div class="palette">
+                <input type="text"
+                    ref=self.input_ref.clone()
+                    placeholder="Search commands, Esc to close"
+                    value=self.query.clone()
+                    onkeydown=onkeydown
+                    oninput=self.link.callback(|data: InputData| Message::SetQuery(data.value)) />
+                { for self.props.commands.iter().enumerate()
+                    .filter(|(_, command)| matches_query(&command.name, &self.query))
+                    .map(|(index, command)| html! {
+                        <div class="palette-command"
+                            onclick=self.link.callback(move |_| Message::Run(index))>
+                            <span>{ &command.name }</span>
+                            <kbd>{ command.binding.clone().unwrap_or_default() }</kbd>
+                        </div>
+                    }) }
+            </div>
+        }
+    }
+}
+
+pub fn is_palette_key(event: &KeyboardEvent) -> bool {
+    (event.ctrl_key() || event.meta_key()) && event.key().eq_ignore_ascii_case("k")
+}
+
+// every word of the query somewhere in the name, in any case
+fn matches_query(name: &str, query: &str) -> bool {
+    let name = name.to_lowercase();
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| name.contains(word))
+}
@@ -0,0 +1,186 @@
+use feuernes_core::config::{Accuracy, Console};
+
+use std::mem;
+
+// the settings a game runs with. the global ones apply to every game, a game's profile
+// overrides some of them whenever that game is started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub accuracy: Accuracy,
+    pub overclock_scanlines: u16,
+    pub sprite_limit: bool,
+    // whether the game's frozen addresses are put in place
+    pub ram_locks: bool,
+    pub console: Console,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            accuracy: Accuracy::Balanced,
+            overclock_scanlines: 0,
+            sprite_limit: true,
+            ram_locks: true,
+            console: Console::Nes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Override {
+    Accuracy(Accuracy),
+    OverclockScanlines(u16),
+    SpriteLimit(bool),
+    RamLocks(bool),
+    Console(Console),
+}
+
+impl Override {
+    // "accuracy=cycle", one per line in the stored profile
+    pub fn to_text(&self) -> String {
+        match self {
+            Override::Accuracy(accuracy) => format!("accuracy={}", accuracy_name(*accuracy)),
+            Override::OverclockScanlines(scanlines) => format!("overclock={}", scanlines),
+            Override::SpriteLimit(on) => format!("sprite_limit={}", on_off(*on)),
+            Override::RamLocks(on) => format!("ram_locks={}", on_off(*on)),
+            Override::Console(console) => format!("console={}", console_name(*console)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bad = || format!("bad setting {:?}", text);
+        let mut fields = text.splitn(2, '=');
+        let (name, value) = match (fields.next(), fields.next()) {
+            (Some(name), Some(value)) => (name.trim(), value.trim()),
+            _ => return Err(bad()),
+        };
+        let on = || match value {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(bad()),
+        };
+        match name {
+            "accuracy" => match value {
+                "fast" => Ok(Override::Accuracy(Accuracy::Fast)),
+                "balanced" => Ok(Override::Accuracy(Accuracy::Balanced)),
+                "cycle" => Ok(Override::Accuracy(Accuracy::Cycle)),
+                _ => Err(bad()),
+            },
+            "overclock" => value
+                .parse()
+                .map(Override::OverclockScanlines)
+                .map_err(|_| bad()),
+            "sprite_limit" => on().map(Override::SpriteLimit),
+            "ram_locks" => on().map(Override::RamLocks),
+            "console" => match value {
+                "nes" => Ok(Override::Console(Console::Nes)),
+                "famicom" => Ok(Override::Console(Console::Famicom)),
+                "av_famicom" => Ok(Override::Console(Console::AvFamicom)),
+                _ => Err(bad()),
+            },
+            _ => Err(bad()),
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        match *self {
+            Override::Accuracy(accuracy) => settings.accuracy = accuracy,
+            Override::OverclockScanlines(scanlines) => settings.overclock_scanlines = scanlines,
+            Override::SpriteLimit(on) => settings.sprite_limit = on,
+            Override::RamLocks(on) => settings.ram_locks = on,
+            Override::Console(console) => settings.console = console,
+        }
+    }
+}
+
+// the overrides of one game, at most one per setting
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub overrides: Vec<Override>,
+}
+
+impl Profile {
+    // the global settings with this profile on top
+    pub fn apply(&self, global: &Settings) -> Settings {
+        let mut settings = *global;
+        for setting in self.overrides.iter() {
+            setting.apply(&mut settings);
+        }
+        settings
+    }
+
+    // keeps the setting for this game, or drops it from the profile when it is what the
+    // global settings have anyway so the game follows later global changes
+    pub fn set(&mut self, setting: Override, global: &Settings) {
+        let mut with_global = *global;
+        setting.apply(&mut with_global);
+        let same_kind = mem::discriminant(&setting);
+        self.overrides
+            .retain(|kept| mem::discriminant(kept) != same_kind);
+        if with_global != *global {
+            self.overrides.push(setting);
+        }
+    }
+}
+
+fn accuracy_name(accuracy: Accuracy) -> &'static str {
+    match accuracy {
+        Accuracy::Fast => "fast",
+        Accuracy::Balanced => "balanced",
+        Accuracy::Cycle => "cycle",
+    }
+}
+
+fn console_name(console: Console) -> &'static str {
+    match console {
+        Console::Nes => "nes",
+        Console::Famicom => "famicom",
+        Console::AvFamicom => "av_famicom",
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let global = Settings::default();
+        let mut profile = Profile::default();
+        profile.set(Override::Accuracy(Accuracy::Cycle), &global);
+        profile.set(Override::SpriteLimit(false), &global);
+        profile.set(Override::OverclockScanlines(0), &global);
+        profile.set(Override::SpriteLimit(true), &global);
+        assert_eq!(profile.overrides, vec![Override::Accuracy(Accuracy::Cycle)]);
+
+        let global = Settings {
+            overclock_scanlines: 50,
+            ..Settings::default()
+        };
+        let settings = profile.apply(&global);
+        assert_eq!(settings.accuracy, Accuracy::Cycle);
+        assert_eq!(settings.overclock_scanlines, 50);
+
+        for setting in [
+            Override::Accuracy(Accuracy::Fast),
+            Override::OverclockScanlines(100),
+            Override::SpriteLimit(false),
+            Override::RamLocks(false),
+            Override::Console(Console::AvFamicom),
+        ]
+        .iter()
+        {
+            assert_eq!(Override::parse(&setting.to_text()), Ok(*setting));
+        }
+        assert!(Override::parse("ram_locks=maybe").is_err());
+        assert!(Override::parse("palette").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::cmp::Reverse;
+use std::rc::Rc;
+
+use yew::services::reader::File;
+use yew::{html, Callback, ChangeData, Component, ComponentLink, Html, Properties, ShouldRender};
+
+use crate::render::web_renderer::ExportFormat;
+use feuernes_core::profiler::Report;
+
+// the profiler tables sort by address ascending, everything else descending. for hot
+// spots count is instructions and both cycle columns are the same, for subroutines
+// count is calls and the cycles are with and without callees
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProfileColumn {
+    Address,
+    Count,
+    Cycles,
+    SelfCycles,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct ProfilerProps {
+    // the last report, None until the profiler has one
+    pub report: Option<Rc<Report>>,
+    pub onreset: Callback<()>,
+    pub ondownload: Callback<ExportFormat>,
+    // an .nl file picked for the labels
+    pub onsymbols: Callback<Vec<File>>,
+}
+
+pub enum Message {
+    Sort(ProfileColumn),
+    LoadSymbols(Vec<File>),
+}
+
+// the hot spot and subroutine tables of the running profiler
+pub struct ProfilerPanel {
+    props: ProfilerProps,
+    link: ComponentLink<Self>,
+    sort: ProfileColumn,
+}
+
+impl Component for ProfilerPanel {
+    type Message = Message;
+    type Properties = ProfilerProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        ProfilerPanel {
+            props: props,
+            link: link,
+            sort: ProfileColumn::Cycles,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::Sort(column) => {
+                self.sort = column;
+                true
+            }
+            Message::LoadSymbols(files) => {
+                self.props.onsymbols.emit(files);
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let on_symbols = self.link.batch_callback(|data| match data {
+            ChangeData::Files(files) => {
+                let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+                vec![Message::LoadSymbols(files)]
+            }
+            _ => vec![],
+        });
+        let tables = match &self.props.report {
+            Some(report) => self.view_tables(report),
+            None => html! { <div>{ "Collecting..." }</div> },
+        };
+
+        html! {
+            <div class="profiler">
+                <div>
+                    <button onclick=self.props.onreset.reform(|_| ())>{ "Reset" }</button>
+                    <button onclick=self.props.ondownload.reform(|_| ExportFormat::Json)>
+                        { "Export JSON" }
+                    </button>
+                    <button onclick=self.props.ondownload.reform(|_| ExportFormat::Csv)>
+                        { "Export CSV" }
+                    </button>
+                    <label>
+                        { " Symbols (.nl) " }
+                        <input type="file" accept=".nl" onchange=on_symbols />
+                    </label>
+                </div>
+                { tables }
+            </div>
+        }
+    }
+}
+
+impl ProfilerPanel {
+    fn view_tables(&self, report: &Report) -> Html {
+        let header = |title: &str, column: ProfileColumn| {
+            let class = if self.sort == column { "sorted" } else { "" };
+            html! {
+                <th class=class onclick=self.link.callback(move |_| Message::Sort(column))>
+                    { title }
+                </th>
+            }
+        };
+        let percent = |cycles: u64, total: u64| {
+            format!("{:.1}%", cycles as f64 * 100.0 / total.max(1) as f64)
+        };
+        let label = |label: &Option<String>| label.clone().unwrap_or_default();
+
+        let mut hot_spots: Vec<_> = report.hot_spots.iter().collect();
+        let mut subroutines: Vec<_> = report.subroutines.iter().collect();
+        match self.sort {
+            ProfileColumn::Address => {
+                hot_spots.sort_by_key(|spot| spot.pc);
+                subroutines.sort_by_key(|subroutine| subroutine.address);
+            }
+            ProfileColumn::Count => {
+                hot_spots.sort_by_key(|spot| Reverse(spot.instructions));
+                subroutines.sort_by_key(|subroutine| Reverse(subroutine.calls));
+            }
+            ProfileColumn::Cycles => {
+                hot_spots.sort_by_key(|spot| Reverse(spot.cycles));
+                subroutines.sort_by_key(|subroutine| Reverse(subroutine.inclusive_cycles));
+            }
+            ProfileColumn::SelfCycles => {
+                hot_spots.sort_by_key(|spot| Reverse(spot.cycles));
+                subroutines.sort_by_key(|subroutine| Reverse(subroutine.exclusive_cycles));
+            }
+        }
+
+        html! {
+            <>
+                <div>
+                    { format!("{} instructions, {} cycles", report.instructions, report.cycles) }
+                </div>
+                <table>
+                    <tr>
+                        { header("PC", ProfileColumn::Address) }
+                        <th>{ "Label" }</th>
+                        { header("Instructions", ProfileColumn::Count) }
+                        { header("Cycles", ProfileColumn::Cycles) }
+                        <th>{ "Share" }</th>
+                    </tr>
+                    { for hot_spots.into_iter().map(|spot| html! {
+                        <tr>
+                            <td>{ format!("${:04X}", spot.pc) }</td>
+                            <td>{ label(&spot.label) }</td>
+                            <td>{ spot.instructions }</td>
+                            <td>{ spot.cycles }</td>
+                            <td>{ percent(spot.cycles, report.cycles) }</td>
+                        </tr>
+                    }) }
+                </table>
+                <table>
+                    <tr>
+                        { header("Subroutine", ProfileColumn::Address) }
+                        <th>{ "Label" }</th>
+                        { header("Calls", ProfileColumn::Count) }
+                        { header("Cycles", ProfileColumn::Cycles) }
+                        { header("Self", ProfileColumn::SelfCycles) }
+                        <th>{ "Share" }</th>
+                    </tr>
+                    { for subroutines.into_iter().map(|subroutine| html! {
+                        <tr>
+                            <td>{ format!("${:04X}", subroutine.address) }</td>
+                            <td>{ label(&subroutine.label) }</td>
+                            <td>{ subroutine.calls }</td>
+                            <td>{ subroutine.inclusive_cycles }</td>
+                            <td>{ subroutine.exclusive_cycles }</td>
+                            <td>{ percent(subroutine.inclusive_cycles, report.cycles) }</td>
+                        </tr>
+                    }) }
+                </table>
+            </>
+        }
+    }
+}
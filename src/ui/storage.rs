@@ -0,0 +1,326 @@
+use yew::format::Text;
+use yew::services::storage::{Area, StorageService};
+
+use feuernes_core::crc32;
+use feuernes_core::input_macro::InputMacro;
+use feuernes_core::patch;
+use feuernes_core::ram_lock::RamLock;
+
+use super::profile::{Override, Profile};
+
+// rom and patch names are kept in newline separated indexes, the files themselves base64
+// encoded
+const ROM_INDEX_KEY: &str = "feuernes.roms";
+const ROM_KEY_PREFIX: &str = "feuernes.rom.";
+const SRAM_KEY_PREFIX: &str = "feuernes.sram.";
+const PATCH_INDEX_KEY: &str = "feuernes.patches";
+const PATCH_KEY_PREFIX: &str = "feuernes.patch.";
+// roms the player turned soft patching off for
+const SOFT_PATCH_OFF_KEY_PREFIX: &str = "feuernes.softpatch.off.";
+// a rom's input macros as text, one per line
+const MACROS_KEY_PREFIX: &str = "feuernes.macros.";
+// a rom's frozen addresses, one per line
+const RAM_LOCKS_KEY_PREFIX: &str = "feuernes.ramlocks.";
+// a game's settings profile, one override per line. kept by the crc32 of the rom rather
+// than its name so it follows the game when it is renamed or added again
+const PROFILE_KEY_PREFIX: &str = "feuernes.profile.";
+
+pub const BUILTIN_ROM: &str = "nestest.nes";
+// added to a rom's name for the latest build fetched while watching it
+const WATCHED_SUFFIX: &str = " (watched)";
+
+const BASE64_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub struct RomStore {
+    storage: Option<StorageService>,
+}
+
+impl RomStore {
+    pub fn new() -> Self {
+        RomStore {
+            storage: StorageService::new(Area::Local).ok(),
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        let mut roms = vec![String::from(BUILTIN_ROM)];
+        roms.extend(self.index(ROM_INDEX_KEY));
+        roms
+    }
+
+    pub fn load(&self, name: &str) -> Option<Vec<u8>> {
+        if name == BUILTIN_ROM {
+            return Some(include_bytes!("../../core/res/test.nes").to_vec());
+        }
+        self.restore(&format!("{}{}", ROM_KEY_PREFIX, name))
+            .and_then(|data| decode(&data))
+    }
+
+    pub fn save(&mut self, name: &str, rom: &[u8]) {
+        if name == BUILTIN_ROM {
+            return;
+        }
+
+        let mut roms: Vec<String> = self.list().into_iter().skip(1).collect();
+        if !roms.iter().any(|rom| rom == name) {
+            roms.push(String::from(name));
+        }
+        self.store(ROM_INDEX_KEY, roms.join("\n"));
+        self.store(&format!("{}{}", ROM_KEY_PREFIX, name), encode(rom));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let roms: Vec<String> = self
+            .list()
+            .into_iter()
+            .skip(1)
+            .filter(|rom| rom != name)
+            .collect();
+        self.store(ROM_INDEX_KEY, roms.join("\n"));
+        if let Some(storage) = self.storage.as_mut() {
+            storage.remove(&format!("{}{}", ROM_KEY_PREFIX, name));
+            storage.remove(&format!("{}{}", SRAM_KEY_PREFIX, name));
+            storage.remove(&format!("{}{}", MACROS_KEY_PREFIX, name));
+            storage.remove(&format!("{}{}", RAM_LOCKS_KEY_PREFIX, name));
+        }
+    }
+
+    pub fn list_patches(&self) -> Vec<String> {
+        self.index(PATCH_INDEX_KEY)
+    }
+
+    pub fn load_patch(&self, name: &str) -> Option<Vec<u8>> {
+        self.restore(&format!("{}{}", PATCH_KEY_PREFIX, name))
+            .and_then(|data| decode(&data))
+    }
+
+    pub fn save_patch(&mut self, name: &str, patch: &[u8]) {
+        let mut patches = self.list_patches();
+        if !patches.iter().any(|patch| patch == name) {
+            patches.push(String::from(name));
+        }
+        self.store(PATCH_INDEX_KEY, patches.join("\n"));
+        self.store(&format!("{}{}", PATCH_KEY_PREFIX, name), encode(patch));
+    }
+
+    pub fn remove_patch(&mut self, name: &str) {
+        let patches: Vec<String> = self
+            .list_patches()
+            .into_iter()
+            .filter(|patch| patch != name)
+            .collect();
+        self.store(PATCH_INDEX_KEY, patches.join("\n"));
+        if let Some(storage) = self.storage.as_mut() {
+            storage.remove(&format!("{}{}", PATCH_KEY_PREFIX, name));
+        }
+    }
+
+    // the first stored patch made for `rom`: a BPS patch by the checksum it records of
+    // its rom, an IPS patch by sharing the rom's file name ("Game.ips" for "Game.nes")
+    pub fn find_patch(&self, rom_name: &str, rom: &[u8]) -> Option<(String, Vec<u8>)> {
+        let rom_crc32 = crc32::crc32(rom);
+        self.list_patches().into_iter().find_map(|name| {
+            let patch = self.load_patch(&name)?;
+            let made_for = match patch::source_crc32(&patch) {
+                Some(crc32) => crc32 == rom_crc32,
+                None => file_stem(&name) == file_stem(rom_name),
+            };
+            if made_for {
+                Some((name, patch))
+            } else {
+                None
+            }
+        })
+    }
+
+    // on for every rom until turned off
+    pub fn soft_patching(&self, rom_name: &str) -> bool {
+        self.restore(&format!("{}{}", SOFT_PATCH_OFF_KEY_PREFIX, rom_name))
+            .is_none()
+    }
+
+    pub fn set_soft_patching(&mut self, rom_name: &str, on: bool) {
+        let key = format!("{}{}", SOFT_PATCH_OFF_KEY_PREFIX, rom_name);
+        if on {
+            if let Some(storage) = self.storage.as_mut() {
+                storage.remove(&key);
+            }
+        } else {
+            self.store(&key, String::new());
+        }
+    }
+
+    pub fn load_sram(&self, name: &str) -> Option<Vec<u8>> {
+        self.restore(&format!("{}{}", SRAM_KEY_PREFIX, name))
+            .and_then(|data| decode(&data))
+    }
+
+    pub fn save_sram(&mut self, name: &str, sram: &[u8]) {
+        self.store(&format!("{}{}", SRAM_KEY_PREFIX, name), encode(sram));
+    }
+
+    // lines that do not parse are dropped
+    pub fn load_macros(&self, rom_name: &str) -> Vec<InputMacro> {
+        self.index(&format!("{}{}", MACROS_KEY_PREFIX, rom_name))
+            .iter()
+            .filter_map(|text| InputMacro::parse(text).ok())
+            .collect()
+    }
+
+    pub fn save_macros(&mut self, rom_name: &str, macros: &[InputMacro]) {
+        let lines: Vec<String> = macros.iter().map(InputMacro::to_text).collect();
+        self.store(
+            &format!("{}{}", MACROS_KEY_PREFIX, rom_name),
+            lines.join("\n"),
+        );
+    }
+
+    pub fn load_ram_locks(&self, rom_name: &str) -> Vec<RamLock> {
+        self.index(&format!("{}{}", RAM_LOCKS_KEY_PREFIX, rom_name))
+            .iter()
+            .filter_map(|text| RamLock::parse(text).ok())
+            .collect()
+    }
+
+    pub fn save_ram_locks(&mut self, rom_name: &str, locks: &[RamLock]) {
+        let lines: Vec<String> = locks.iter().map(RamLock::to_text).collect();
+        self.store(
+            &format!("{}{}", RAM_LOCKS_KEY_PREFIX, rom_name),
+            lines.join("\n"),
+        );
+    }
+
+    // None when the game has no profile and runs with the global settings
+    pub fn load_profile(&self, rom: &[u8]) -> Option<Profile> {
+        let key = profile_key(rom);
+        self.restore(&key)?;
+        Some(Profile {
+            overrides: self
+                .index(&key)
+                .iter()
+                .filter_map(|text| Override::parse(text).ok())
+                .collect(),
+        })
+    }
+
+    pub fn save_profile(&mut self, rom: &[u8], profile: &Profile) {
+        let lines: Vec<String> = profile.overrides.iter().map(Override::to_text).collect();
+        self.store(&profile_key(rom), lines.join("\n"));
+    }
+
+    pub fn remove_profile(&mut self, rom: &[u8]) {
+        if let Some(storage) = self.storage.as_mut() {
+            storage.remove(&profile_key(rom));
+        }
+    }
+
+    fn index(&self, key: &str) -> Vec<String> {
+        match self.restore(key) {
+            Some(index) => index
+                .lines()
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn restore(&self, key: &str) -> Option<String> {
+        let storage = self.storage.as_ref()?;
+        let data: Text = storage.restore(key);
+        data.ok()
+    }
+
+    fn store(&mut self, key: &str, value: String) {
+        if let Some(storage) = self.storage.as_mut() {
+            let data: Text = Ok(value);
+            storage.store(key, data);
+        }
+    }
+}
+
+fn profile_key(rom: &[u8]) -> String {
+    format!("{}{:08X}", PROFILE_KEY_PREFIX, crc32::crc32(rom))
+}
+
+// the library entry builds fetched while watching a rom go to, next to the rom itself
+pub fn watched_name(rom_name: &str) -> String {
+    if rom_name.ends_with(WATCHED_SUFFIX) {
+        return String::from(rom_name);
+    }
+    format!("{}{}", rom_name, WATCHED_SUFFIX)
+}
+
+// the name without its extension
+fn file_stem(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => &name[..dot],
+        _ => name,
+    }
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_TABLE[(n >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn decode(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut n: u32 = 0;
+    let mut bits = 0;
+    for c in data.bytes().filter(|c| *c != b'=') {
+        let value = BASE64_TABLE.iter().position(|b| *b == c)? as u32;
+        n = (n << 6 | value) & 0xFFFF;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 77 + 3) as u8).collect();
+            assert_eq!(decode(&encode(&bytes)), Some(bytes));
+        }
+        assert_eq!(encode(b"NES"), "TkVT");
+        assert_eq!(encode(b"NE"), "TkU=");
+    }
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(file_stem("Some Game (U).nes"), "Some Game (U)");
+        assert_eq!(file_stem("Some Game (U).ips"), "Some Game (U)");
+        assert_eq!(file_stem("patch"), "patch");
+        assert_eq!(file_stem(".ips"), ".ips");
+    }
+
+    #[test]
+    fn test_watched_name() {
+        assert_eq!(watched_name("game.nes"), "game.nes (watched)");
+        assert_eq!(watched_name("game.nes (watched)"), "game.nes (watched)");
+        assert_ne!(watched_name(BUILTIN_ROM), BUILTIN_ROM);
+    }
+}
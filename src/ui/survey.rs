@@ -0,0 +1,150 @@
+use gloo::render::{request_animation_frame, AnimationFrame};
+use yew::{html, Component, ComponentLink, Html, ShouldRender};
+
+use super::export;
+use super::storage::{self, RomStore};
+use feuernes_core::compat::{self, CompatEntry};
+use feuernes_core::render::png;
+use feuernes_core::survey::{self, SurveyEntry, DEFAULT_SURVEY_FRAMES};
+
+pub enum Message {
+    RunNext,
+    DownloadCsv,
+    DownloadJson,
+    DownloadCompat,
+}
+
+// runs every rom of the library for a while, one per animation frame like the accuracy
+// page, and shows where each one ended up
+pub struct Survey {
+    link: ComponentLink<Self>,
+    roms: Vec<String>,
+    // in the order of `roms`
+    entries: Vec<SurveyEntry>,
+    // the screenshots as data urls for the <img>s
+    screenshots: Vec<Option<String>>,
+    _next: Option<AnimationFrame>,
+}
+
+impl Component for Survey {
+    type Message = Message;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut survey = Survey {
+            link: link,
+            roms: RomStore::new().list(),
+            entries: Vec::new(),
+            screenshots: Vec::new(),
+            _next: None,
+        };
+        survey.schedule();
+        survey
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::RunNext => {
+                if let Some(name) = self.roms.get(self.entries.len()) {
+                    let rom = RomStore::new().load(name).unwrap_or_default();
+                    let entry = survey::survey_rom(name, &rom, DEFAULT_SURVEY_FRAMES);
+                    let screenshot = entry.screenshot.as_ref().map(|frame| {
+                        format!(
+                            "data:image/png;base64,{}",
+                            storage::encode(&png::encode(frame))
+                        )
+                    });
+                    self.entries.push(entry);
+                    self.screenshots.push(screenshot);
+                }
+                self.schedule();
+                true
+            }
+            Message::DownloadCsv => {
+                let _ = export::download_text("survey.csv", &survey::report_csv(&self.entries));
+                false
+            }
+            Message::DownloadJson => {
+                let report = survey::report_json(&self.entries).to_string();
+                let _ = export::download_text("survey.json", &report);
+                false
+            }
+            // in the form of the core's res/compat.json, to merge into it
+            Message::DownloadCompat => {
+                let entries: Vec<_> = self
+                    .entries
+                    .iter()
+                    .filter_map(CompatEntry::from_survey)
+                    .collect();
+                let _ = export::download_text("compat.json", &compat::to_json(&entries));
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <div class="survey">
+                <p>
+                    { format!(
+                        "{} of {} roms run for {} frames ",
+                        self.entries.len(),
+                        self.roms.len(),
+                        DEFAULT_SURVEY_FRAMES
+                    ) }
+                    <button onclick=self.link.callback(|_| Message::DownloadCsv)>{ "CSV" }</button>
+                    <button onclick=self.link.callback(|_| Message::DownloadJson)>{ "JSON" }</button>
+                    <button onclick=self.link.callback(|_| Message::DownloadCompat)>
+                        { "compat.json" }
+                    </button>
+                </p>
+                <table>
+                    { for self.roms.iter().enumerate().map(|(i, name)| self.view_rom(i, name)) }
+                </table>
+            </div>
+        }
+    }
+}
+
+impl Survey {
+    fn schedule(&mut self) {
+        self._next = if self.entries.len() < self.roms.len() {
+            let link = self.link.clone();
+            Some(request_animation_frame(move |_| {
+                link.send_message(Message::RunNext)
+            }))
+        } else {
+            None
+        };
+    }
+
+    fn view_rom(&self, i: usize, name: &str) -> Html {
+        match (self.entries.get(i), self.screenshots.get(i)) {
+            (Some(entry), Some(screenshot)) => html! {
+                <tr>
+                    <td>{ name }</td>
+                    <td>{ entry.status.name() }</td>
+                    <td>{ entry.status.detail() }</td>
+                    <td>
+                        { match screenshot {
+                            Some(url) => html! { <img src=url.clone() /> },
+                            None => html! {},
+                        } }
+                    </td>
+                </tr>
+            },
+            _ => html! {
+                <tr class="pending">
+                    <td>{ name }</td>
+                    <td>{ "..." }</td>
+                    <td />
+                    <td />
+                </tr>
+            },
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use yew::format::{Binary, Nothing};
+use yew::services::fetch::{Cache, FetchOptions, FetchService, FetchTask, Request, Response};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+use yew::{html, Callback, Component, ComponentLink, Html, InputData, Properties, ShouldRender};
+
+use feuernes_core::cartridge::Cartridge;
+use feuernes_core::crc32::crc32;
+
+// how often a watched rom is fetched again. failed fetches wait twice as long each time
+// up to WATCH_MAX_INTERVAL, and watching stops after WATCH_MAX_FAILURES in a row
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+const WATCH_MAX_INTERVAL: Duration = Duration::from_secs(30);
+const WATCH_MAX_FAILURES: u32 = 10;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct WatchProps {
+    // the rom running now, a build that is the same is not reloaded
+    pub rom: Rc<Vec<u8>>,
+    // a new build that starts as a cartridge, for the console to power-cycle with
+    pub onbuild: Callback<Vec<u8>>,
+    pub ontoast: Callback<String>,
+}
+
+pub enum Message {
+    SetUrl(String),
+    Toggle,
+    Tick,
+    Fetched(Result<Vec<u8>, String>),
+}
+
+// fetches a homebrew build over and over, usually an assembler's output served by a local
+// web server, and hands every new one to onbuild
+pub struct WatchPanel {
+    props: WatchProps,
+    link: ComponentLink<Self>,
+    url: String,
+    watching: bool,
+    // the wait for the next fetch, None while one is running
+    timeout: Option<TimeoutTask>,
+    task: Option<FetchTask>,
+    // of the last build fetched, None until one was and after a failed fetch
+    crc32: Option<u32>,
+    // fetches failed in a row and why the last one did
+    failures: u32,
+    error: Option<String>,
+}
+
+impl Component for WatchPanel {
+    type Message = Message;
+    type Properties = WatchProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        WatchPanel {
+            props: props,
+            link: link,
+            url: String::new(),
+            watching: false,
+            timeout: None,
+            task: None,
+            crc32: None,
+            failures: 0,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::SetUrl(url) => {
+                self.url = url;
+                false
+            }
+            Message::Toggle => {
+                let watching = !self.watching && !self.url.is_empty();
+                self.stop();
+                if watching {
+                    self.watching = true;
+                    self.link.send_message(Message::Tick);
+                }
+                true
+            }
+            Message::Tick => {
+                self.timeout = None;
+                if !self.watching || self.task.is_some() {
+                    return false;
+                }
+                match self.fetch() {
+                    Ok(task) => {
+                        self.task = Some(task);
+                        false
+                    }
+                    Err(reason) => {
+                        self.fetched(Err(reason));
+                        self.schedule();
+                        true
+                    }
+                }
+            }
+            Message::Fetched(result) => {
+                self.task = None;
+                if !self.watching {
+                    return false;
+                }
+                self.fetched(result);
+                self.schedule();
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props == props {
+            return false;
+        }
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let watch_label = if self.watching {
+            "Stop watching"
+        } else {
+            "Watch"
+        };
+        let status = match &self.error {
+            Some(reason) => html! {
+                <span class="watch-error">
+                    { format!("{} failed fetches, retrying in {}s: {}",
+                        self.failures,
+                        watch_delay(self.failures).as_secs(),
+                        reason) }
+                </span>
+            },
+            None => html! {},
+        };
+        html! {
+            <div class="watch">
+                <label>
+                    { "Reload builds from " }
+                    <input type="url"
+                        placeholder="http://localhost:8000/game.nes"
+                        value=self.url.clone()
+                        disabled=self.watching
+                        oninput=self.link.callback(|data: InputData| Message::SetUrl(data.value)) />
+                </label>
+                <button onclick=self.link.callback(|_| Message::Toggle)>
+                    { watch_label }
+                </button>
+                { status }
+            </div>
+        }
+    }
+}
+
+impl WatchPanel {
+    fn fetch(&self) -> Result<FetchTask, String> {
+        let request = Request::get(self.url.as_str())
+            .body(Nothing)
+            .map_err(|error| error.to_string())?;
+        // the dev server's answer, not the browser's copy of the last build
+        let options = FetchOptions {
+            cache: Some(Cache::NoStore),
+            ..FetchOptions::default()
+        };
+        let callback = self.link.callback(|response: Response<Binary>| {
+            let (head, body) = response.into_parts();
+            let result = match body {
+                Ok(_) if !head.status.is_success() => Err(head.status.to_string()),
+                Ok(rom) => Ok(rom),
+                Err(error) => Err(error.to_string()),
+            };
+            Message::Fetched(result)
+        });
+        FetchService::fetch_binary_with_options(request, options, callback)
+            .map_err(|error| error.to_string())
+    }
+
+    // the next fetch, later the more have failed in a row
+    fn schedule(&mut self) {
+        if self.watching {
+            let callback = self.link.callback(|_| Message::Tick);
+            let delay = watch_delay(self.failures);
+            self.timeout = Some(TimeoutService::spawn(delay, callback));
+        }
+    }
+
+    fn stop(&mut self) {
+        self.watching = false;
+        self.timeout = None;
+        self.task = None;
+        self.crc32 = None;
+        self.failures = 0;
+        self.error = None;
+    }
+
+    // a build that differs from the running rom goes to onbuild. the first of a run of
+    // failed fetches is toasted, the view tells about the rest
+    fn fetched(&mut self, result: Result<Vec<u8>, String>) {
+        let rom = match result {
+            Ok(rom) => rom,
+            Err(reason) => {
+                self.crc32 = None;
+                self.failures += 1;
+                let toast = if self.failures >= WATCH_MAX_FAILURES {
+                    let toast = format!(
+                        "Stopped watching {} after {} failed fetches: {}",
+                        self.url, self.failures, reason
+                    );
+                    self.stop();
+                    Some(toast)
+                } else if self.failures == 1 {
+                    Some(format!("{}: {}", self.url, reason))
+                } else {
+                    None
+                };
+                if let Some(toast) = toast {
+                    self.props.ontoast.emit(toast);
+                }
+                if self.watching {
+                    self.error = Some(reason);
+                }
+                return;
+            }
+        };
+        self.failures = 0;
+        self.error = None;
+        let checksum = crc32(&rom);
+        if self.crc32.replace(checksum) == Some(checksum) || rom == *self.props.rom {
+            return;
+        }
+
+        let toast = match Cartridge::new(&rom) {
+            Ok(_) => {
+                self.props.onbuild.emit(rom);
+                format!("Reloaded {}", self.url)
+            }
+            Err(reason) => format!("{}: {}", self.url, reason),
+        };
+        self.props.ontoast.emit(toast);
+    }
+}
+
+// how long to wait before fetching a watched rom after `failures` failed fetches in a row
+fn watch_delay(failures: u32) -> Duration {
+    WATCH_INTERVAL
+        .checked_mul(1 << failures.min(16))
+        .map_or(WATCH_MAX_INTERVAL, |delay| delay.min(WATCH_MAX_INTERVAL))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_watch_delay() {
+        assert_eq!(watch_delay(0), WATCH_INTERVAL);
+        assert_eq!(watch_delay(1), WATCH_INTERVAL * 2);
+        assert_eq!(watch_delay(3), WATCH_INTERVAL * 8);
+        assert_eq!(watch_delay(5), WATCH_MAX_INTERVAL);
+        assert_eq!(watch_delay(WATCH_MAX_FAILURES), WATCH_MAX_INTERVAL);
+        assert_eq!(watch_delay(u32::MAX), WATCH_MAX_INTERVAL);
+    }
+}
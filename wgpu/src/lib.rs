@@ -0,0 +1,295 @@
+// draws emulated frames with wgpu. the picture goes through the same cpu filters as
+// screenshots and canvas frontends (render::filter), is uploaded to a texture and drawn
+// letterboxed over the target. the frontend owns the device and the surface:
+//
+//     let mut renderer = Renderer::new(&device, surface_format);
+//     renderer.set_filter(&device, Some(Filter::Scale2x));
+//     // every frame
+//     renderer.upload(&queue, &nes);
+//     renderer.render(&mut encoder, &view, (width, height));
+//
+// it runs wherever wgpu does, native backends on desktops and webgpu in a browser. huds
+// come with the frame: nes.show_stats(true) draws the counters of the web frontend's
+// stats overlay, nes.show_frame_stats(true) the sprite and split hud
+
+use std::num::NonZeroU32;
+
+use feuernes_core::nes::Nes;
+use feuernes_core::render::filter::{Filter, VideoFilter};
+use feuernes_core::render::frame::{Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub struct Renderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // the texture matches the target's srgb-ness so colors pass through unchanged
+    texture_format: wgpu::TextureFormat,
+    // None draws the frame as the ppu made it
+    filter: Option<Box<dyn VideoFilter>>,
+    // the filter's output, reused between frames
+    filtered: Frame,
+    screen: ScreenTexture,
+}
+
+// the texture the frame is uploaded to, made again when a filter changes its size
+struct ScreenTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    width: u32,
+    height: u32,
+}
+
+impl Renderer {
+    // `format` is what render() draws into, usually the surface's preferred format
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("feuernes screen"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("screen.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("feuernes screen"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("feuernes screen"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("feuernes screen"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        // filters do their own smoothing, the rest of the scaling keeps pixels sharp
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("feuernes screen"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texture_format = if format.describe().srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let screen = ScreenTexture::new(
+            device,
+            &bind_group_layout,
+            &sampler,
+            texture_format,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        );
+        Renderer {
+            pipeline: pipeline,
+            bind_group_layout: bind_group_layout,
+            sampler: sampler,
+            texture_format: texture_format,
+            filter: None,
+            filtered: Frame::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            screen: screen,
+        }
+    }
+
+    pub fn set_filter(&mut self, device: &wgpu::Device, filter: Option<Filter>) {
+        self.filter = filter.map(|filter| filter.create());
+        let (width, height) = match &self.filter {
+            Some(filter) => filter.output_size(SCREEN_WIDTH, SCREEN_HEIGHT),
+            None => (SCREEN_WIDTH, SCREEN_HEIGHT),
+        };
+        self.filtered = Frame::new(width, height);
+        self.screen = ScreenTexture::new(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            self.texture_format,
+            width as u32,
+            height as u32,
+        );
+    }
+
+    // the last frame the console finished, through the filter if one is set
+    pub fn upload(&mut self, queue: &wgpu::Queue, nes: &Nes) {
+        let frame = match &mut self.filter {
+            Some(filter) => {
+                filter.apply(
+                    nes.screen(),
+                    SCREEN_WIDTH,
+                    SCREEN_HEIGHT,
+                    &mut self.filtered,
+                );
+                &self.filtered
+            }
+            None => nes.frame(),
+        };
+        self.screen.write(queue, frame);
+    }
+
+    // clears `target` and draws the frame as large as it fits with its aspect kept
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        target_size: (u32, u32),
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("feuernes screen"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        let (x, y, width, height) = viewport(target_size.0, target_size.1);
+        if width < 1.0 || height < 1.0 {
+            // a minimized window
+            return;
+        }
+        pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+impl ScreenTexture {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("feuernes screen"),
+            size: wgpu::Extent3d {
+                width: width,
+                height: height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("feuernes screen"),
+            layout: layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        ScreenTexture {
+            texture: texture,
+            bind_group: bind_group,
+            width: width,
+            height: height,
+        }
+    }
+
+    // the whole frame, a filtered one changes everywhere anyway
+    fn write(&self, queue: &wgpu::Queue, frame: &Frame) {
+        assert_eq!(
+            (frame.width as u32, frame.height as u32),
+            (self.width, self.height)
+        );
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &frame.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(self.width * 4),
+                rows_per_image: NonZeroU32::new(self.height),
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+// x, y, width and height of the largest 256x240 area centered in the target
+fn viewport(target_width: u32, target_height: u32) -> (f32, f32, f32, f32) {
+    let (target_width, target_height) = (target_width as f32, target_height as f32);
+    let scale = (target_width / SCREEN_WIDTH as f32).min(target_height / SCREEN_HEIGHT as f32);
+    let width = SCREEN_WIDTH as f32 * scale;
+    let height = SCREEN_HEIGHT as f32 * scale;
+    (
+        (target_width - width) / 2.0,
+        (target_height - height) / 2.0,
+        width,
+        height,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_viewport() {
+        assert_eq!(viewport(512, 480), (0.0, 0.0, 512.0, 480.0));
+        // pillarboxed on a wide window, letterboxed on a tall one
+        assert_eq!(viewport(1280, 480), (384.0, 0.0, 512.0, 480.0));
+        assert_eq!(viewport(256, 480), (0.0, 120.0, 256.0, 240.0));
+        assert_eq!(viewport(0, 480).2, 0.0);
+    }
+}
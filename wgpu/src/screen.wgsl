@@ -0,0 +1,28 @@
+// the emulated frame over the whole viewport, like res/screen.vs and res/screen.fs of the
+// webgl renderer. no vertex buffer, the three corners of a triangle covering the viewport
+// come from the vertex index
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coord: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    // (0, 0), (2, 0) and (0, 2) in texture coordinates, the corners past 1 are clipped
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var output: VertexOutput;
+    output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.tex_coord = uv;
+    return output;
+}
+
+[[group(0), binding(0)]]
+var screen_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var screen_sampler: sampler;
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(screen_texture, screen_sampler, input.tex_coord);
+}